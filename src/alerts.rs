@@ -0,0 +1,426 @@
+use crate::format::NumberFormat;
+use crate::position::to_decimal;
+use crate::provider::{PriceProvider, PriceQuote};
+use chrono::prelude::*;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+// An alert of the config file, e.g. "ticker: AAPL, above: 200". Every threshold
+// that is set is a condition of its own.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AlertRule {
+    pub ticker: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub above: Option<Decimal>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub below: Option<Decimal>,
+    // fires when the price moved at least this many % since the previous close, up or down
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub day_change_percent: Option<Decimal>,
+}
+
+// A condition that is met. The key identifies it in the state file, a day change
+// is keyed by its day so the move of the next day is reported again.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Breach {
+    pub ticker: String,
+    pub key: String,
+    pub message: String,
+}
+
+// The breaches that were notified and still hold, per ticker. A breach is only
+// notified again after the price crossed back and then breached once more.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct AlertState {
+    #[serde(default)]
+    pub active: BTreeMap<String, BTreeSet<String>>,
+}
+
+// The conditions of the rule that the quote meets. Prices are in the currency of
+// the ticker, so they are shown without a currency symbol.
+pub fn evaluate(rule: &AlertRule, quote: &PriceQuote, format: &NumberFormat) -> Vec<Breach> {
+    let ticker = &rule.ticker;
+    let price = to_decimal(quote.price);
+    let breach = |key: String, message: String| Breach {
+        ticker: ticker.clone(),
+        key,
+        message,
+    };
+    let mut breaches = Vec::new();
+
+    if let Some(above) = rule.above.filter(|above| price >= *above) {
+        breaches.push(breach(
+            format!("{ticker} above {above}"),
+            format!(
+                "{ticker} is at {}, above {}",
+                format.number(price),
+                format.number(above)
+            ),
+        ));
+    }
+    if let Some(below) = rule.below.filter(|below| price <= *below) {
+        breaches.push(breach(
+            format!("{ticker} below {below}"),
+            format!(
+                "{ticker} is at {}, below {}",
+                format.number(price),
+                format.number(below)
+            ),
+        ));
+    }
+    let previous_close = quote
+        .previous_close
+        .map(to_decimal)
+        .filter(|close| !close.is_zero());
+    if let (Some(threshold), Some(previous_close)) = (rule.day_change_percent, previous_close) {
+        let change = (price - previous_close) / previous_close * Decimal::ONE_HUNDRED;
+        if change.abs() >= threshold {
+            let day = quote.time.unwrap_or_else(Utc::now).date_naive();
+            breaches.push(breach(
+                format!("{ticker} day change {threshold} on {day}"),
+                format!(
+                    "{ticker} moved {} today, to {}",
+                    format.signed_percent(change),
+                    format.number(price)
+                ),
+            ));
+        }
+    }
+    breaches
+}
+
+// The breaches that weren't notified yet
+pub fn new_breaches<'a>(breaches: &'a [Breach], state: &AlertState) -> Vec<&'a Breach> {
+    breaches
+        .iter()
+        .filter(|breach| {
+            !state
+                .active
+                .get(&breach.ticker)
+                .is_some_and(|keys| keys.contains(&breach.key))
+        })
+        .collect()
+}
+
+// The state after a run: the breaches that still hold and were notified, now or
+// before. A breach that isn't met anymore is dropped, so it fires when it happens
+// again. The breaches of a ticker whose quote couldn't be fetched are kept, a
+// network failure is not a cross back.
+pub fn next_state(
+    state: &AlertState,
+    breaches: &[Breach],
+    notified: &[String],
+    failed_tickers: &[String],
+) -> AlertState {
+    let mut active: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    for breach in breaches {
+        let was_active = state
+            .active
+            .get(&breach.ticker)
+            .is_some_and(|keys| keys.contains(&breach.key));
+        if was_active || notified.contains(&breach.key) {
+            active
+                .entry(breach.ticker.clone())
+                .or_default()
+                .insert(breach.key.clone());
+        }
+    }
+    for ticker in failed_tickers {
+        if let Some(keys) = state.active.get(ticker) {
+            active.insert(ticker.clone(), keys.clone());
+        }
+    }
+    active.retain(|_, keys| !keys.is_empty());
+    AlertState { active }
+}
+
+pub fn load_state(path: &str) -> Result<AlertState, String> {
+    match std::fs::read_to_string(path) {
+        Ok(data) => serde_json::from_str(&data)
+            .map_err(|e| format!("Failed to read the alert state {path}: {e}")),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(AlertState::default()),
+        Err(e) => Err(format!("Failed to read the alert state {path}: {e}")),
+    }
+}
+
+pub fn save_state(path: &str, state: &AlertState) -> Result<(), String> {
+    let data = serde_json::to_string_pretty(state)
+        .map_err(|e| format!("Failed to serialize data: {e}"))?;
+    std::fs::write(path, data).map_err(|e| format!("Failed to write to file: {e}"))
+}
+
+// The message under the keys read by Slack ("text") and Discord ("content"), ntfy
+// shows the body as it is
+fn webhook_body(breach: &Breach) -> String {
+    serde_json::json!({
+        "text": breach.message,
+        "content": breach.message,
+        "ticker": breach.ticker,
+        "alert": breach.key,
+    })
+    .to_string()
+}
+
+pub async fn send_webhook(
+    client: &reqwest::Client,
+    url: &str,
+    breach: &Breach,
+) -> Result<(), String> {
+    let error = |e: reqwest::Error| format!("Failed to send the alert to the webhook: {e}");
+    client
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(webhook_body(breach))
+        .send()
+        .await
+        .map_err(error)?
+        .error_for_status()
+        .map_err(error)?;
+    Ok(())
+}
+
+// Fetches the quotes of the rules, prints the new breaches and posts them to the
+// webhook. A breach that couldn't be posted isn't recorded, so the next run tries
+// again. Returns the new breaches.
+pub async fn run_alerts(
+    provider: &impl PriceProvider,
+    rules: &[AlertRule],
+    webhook: Option<&str>,
+    state_path: &str,
+    format: &NumberFormat,
+) -> Result<Vec<Breach>, String> {
+    let state = load_state(state_path)?;
+    let tickers: BTreeSet<&str> = rules.iter().map(|rule| rule.ticker.as_str()).collect();
+    let quotes = futures::future::join_all(tickers.iter().map(|ticker| async move {
+        (ticker.to_string(), provider.get_latest_price(ticker).await)
+    }))
+    .await;
+
+    let mut breaches = Vec::new();
+    let mut failed_tickers = Vec::new();
+    for (ticker, quote) in quotes {
+        match quote {
+            Ok(quote) => {
+                for rule in rules.iter().filter(|rule| rule.ticker == ticker) {
+                    breaches.extend(evaluate(rule, &quote, format));
+                }
+            }
+            Err(e) => {
+                eprintln!("Error getting the price of {ticker}: {e}");
+                failed_tickers.push(ticker);
+            }
+        }
+    }
+
+    let client = reqwest::Client::new();
+    let mut notified = Vec::new();
+    let mut fired = Vec::new();
+    for breach in new_breaches(&breaches, &state) {
+        println!("{}", breach.message);
+        let sent = match webhook {
+            Some(url) => send_webhook(&client, url, breach).await,
+            None => Ok(()),
+        };
+        match sent {
+            Ok(()) => {
+                notified.push(breach.key.clone());
+                fired.push(breach.clone());
+            }
+            Err(e) => eprintln!("{e}"),
+        }
+    }
+
+    save_state(
+        state_path,
+        &next_state(&state, &breaches, &notified, &failed_tickers),
+    )?;
+    Ok(fired)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::MockProvider;
+    use rust_decimal::dec;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn rule() -> AlertRule {
+        AlertRule {
+            ticker: "AAPL".to_string(),
+            above: Some(dec!(200)),
+            below: Some(dec!(150)),
+            day_change_percent: Some(dec!(5)),
+        }
+    }
+
+    fn quote(price: f64, previous_close: f64) -> PriceQuote {
+        PriceQuote {
+            price,
+            time: Some(Utc.with_ymd_and_hms(2024, 3, 4, 15, 0, 0).unwrap()),
+            previous_close: Some(previous_close),
+            open: None,
+            currency: None,
+        }
+    }
+
+    fn keys(breaches: &[Breach]) -> Vec<&str> {
+        breaches.iter().map(|breach| breach.key.as_str()).collect()
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let format = NumberFormat::for_currency("USD");
+        assert!(evaluate(&rule(), &quote(180.0, 179.0), &format).is_empty());
+
+        let breaches = evaluate(&rule(), &quote(210.0, 195.0), &format);
+        assert_eq!(
+            keys(&breaches),
+            ["AAPL above 200", "AAPL day change 5 on 2024-03-04"]
+        );
+        assert_eq!(breaches[0].message, "AAPL is at 210.00, above 200.00");
+        assert_eq!(breaches[1].message, "AAPL moved +7.69% today, to 210.00");
+
+        // a fall counts as a day change as well, the threshold itself is a breach
+        let breaches = evaluate(&rule(), &quote(150.0, 160.0), &format);
+        assert_eq!(
+            keys(&breaches),
+            ["AAPL below 150", "AAPL day change 5 on 2024-03-04"]
+        );
+
+        // without a previous close the day change is unknown
+        let mut no_close = quote(150.0, 160.0);
+        no_close.previous_close = None;
+        assert_eq!(
+            keys(&evaluate(&rule(), &no_close, &format)),
+            ["AAPL below 150"]
+        );
+    }
+
+    #[test]
+    fn test_rules_from_yaml() {
+        let rules: Vec<AlertRule> = serde_yaml::from_str(
+            "- ticker: AAPL\n  above: 200\n  below: 150.5\n- ticker: BTC-EUR\n  day_change_percent: 5\n",
+        )
+        .unwrap();
+        assert_eq!(rules[0].above, Some(dec!(200)));
+        assert_eq!(rules[0].below, Some(dec!(150.5)));
+        assert_eq!(rules[1].day_change_percent, Some(dec!(5)));
+        assert_eq!(rules[1].above, None);
+    }
+
+    #[test]
+    fn test_deduplication() {
+        let format = NumberFormat::for_currency("USD");
+        let above = evaluate(&rule(), &quote(201.0, 200.0), &format);
+        let state = AlertState::default();
+        assert_eq!(new_breaches(&above, &state).len(), 1);
+        let state = next_state(&state, &above, &["AAPL above 200".to_string()], &[]);
+
+        // the next run finds the same breach, nothing is notified again
+        let still_above = evaluate(&rule(), &quote(205.0, 201.0), &format);
+        assert!(new_breaches(&still_above, &state).is_empty());
+        let state = next_state(&state, &still_above, &[], &[]);
+        assert_eq!(state.active["AAPL"].len(), 1);
+
+        // a failed fetch keeps the breach
+        let kept = next_state(&state, &[], &[], &["AAPL".to_string()]);
+        assert_eq!(kept, state);
+
+        // the price crossed back, the next breach fires again
+        let state = next_state(&state, &[], &[], &[]);
+        assert_eq!(state, AlertState::default());
+        assert_eq!(new_breaches(&above, &state).len(), 1);
+
+        // a breach that couldn't be sent is tried again
+        let state = next_state(&state, &above, &[], &[]);
+        assert_eq!(new_breaches(&above, &state).len(), 1);
+    }
+
+    #[test]
+    fn test_state_file() {
+        let path = std::env::temp_dir().join(format!("alert_state_{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        assert_eq!(load_state(path).unwrap(), AlertState::default());
+        let state = AlertState {
+            active: BTreeMap::from([(
+                "AAPL".to_string(),
+                BTreeSet::from(["AAPL above 200".to_string()]),
+            )]),
+        };
+        save_state(path, &state).unwrap();
+        assert_eq!(load_state(path).unwrap(), state);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    // Answers one request with 200 and returns it
+    async fn mock_webhook() -> (String, tokio::task::JoinHandle<String>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/alerts", listener.local_addr().unwrap());
+        let handle = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buffer = [0; 4096];
+            // the headers and the body, the length of the body is in the headers
+            loop {
+                let read = stream.read(&mut buffer).await.unwrap();
+                request.extend_from_slice(&buffer[..read]);
+                let text = String::from_utf8_lossy(&request).to_string();
+                if let Some((headers, body)) = text.split_once("\r\n\r\n") {
+                    let length = headers
+                        .lines()
+                        .find_map(|line| {
+                            line.to_lowercase()
+                                .strip_prefix("content-length: ")
+                                .map(|length| length.trim().parse::<usize>().unwrap())
+                        })
+                        .unwrap_or(0);
+                    if body.len() >= length {
+                        break;
+                    }
+                }
+                if read == 0 {
+                    break;
+                }
+            }
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+                .await
+                .unwrap();
+            String::from_utf8(request).unwrap()
+        });
+        (url, handle)
+    }
+
+    #[tokio::test]
+    async fn test_run_alerts_posts_to_the_webhook() {
+        let (url, request) = mock_webhook().await;
+        let path = std::env::temp_dir().join(format!("alert_run_{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        let provider = MockProvider::new().with_price("AAPL", 210.0);
+        let rules = [AlertRule {
+            day_change_percent: None,
+            ..rule()
+        }];
+        let format = NumberFormat::for_currency("USD");
+
+        let fired = run_alerts(&provider, &rules, Some(&url), path, &format)
+            .await
+            .unwrap();
+        assert_eq!(keys(&fired), ["AAPL above 200"]);
+        let request = request.await.unwrap();
+        assert!(request.starts_with("POST /alerts HTTP/1.1\r\n"));
+        let body: serde_json::Value =
+            serde_json::from_str(request.split_once("\r\n\r\n").unwrap().1).unwrap();
+        assert_eq!(body["text"], "AAPL is at 210.00, above 200.00");
+        assert_eq!(body["ticker"], "AAPL");
+
+        // the second run doesn't post, there is no server anymore
+        let fired = run_alerts(&provider, &rules, Some(&url), path, &format)
+            .await
+            .unwrap();
+        assert!(fired.is_empty());
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,654 @@
+use crate::format::NumberFormat;
+use crate::position::{to_decimal, to_f64, PortfolioPosition};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// Current vs. target weight of a single asset class
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ClassAllocation {
+    pub asset_class: String,
+    pub balance: Decimal,
+    pub current_weight: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_weight: Option<f64>,
+    // Difference between current and target weight in percentage points
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub drift: Option<f64>,
+    // Amount to buy (positive) or sell (negative) to get back to the target
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rebalance_amount: Option<Decimal>,
+}
+
+// Sum up the balances of all assets per asset class, liabilities are left out
+// so they don't distort the weights of the assets
+pub fn balances_by_class(positions: &[PortfolioPosition]) -> HashMap<String, Decimal> {
+    let mut balances: HashMap<String, Decimal> = HashMap::new();
+
+    for position in positions.iter().filter(|position| !position.is_liability()) {
+        *balances
+            .entry(position.get_asset_class().to_string())
+            .or_default() += position.get_balance();
+    }
+    balances
+}
+
+// Sum of the (negative) balances of all liabilities
+pub fn total_liabilities(positions: &[PortfolioPosition]) -> Decimal {
+    positions
+        .iter()
+        .filter(|position| position.is_liability())
+        .map(|position| position.get_balance())
+        .sum()
+}
+
+// Share of each asset class in the total value of the assets in %
+pub fn allocation_percentages(positions: &[PortfolioPosition]) -> HashMap<String, f64> {
+    let balances = balances_by_class(positions);
+    let total_value: Decimal = balances.values().sum();
+
+    balances
+        .into_iter()
+        .map(|(asset_class, balance)| (asset_class, percentage_of(balance, total_value)))
+        .collect()
+}
+
+// Weights are f64, only the balances they are computed from are exact.
+// Without a positive total (e.g. no assets) there is nothing to be a share of.
+fn percentage_of(value: Decimal, total: Decimal) -> f64 {
+    if total <= Decimal::ZERO {
+        0.0
+    } else {
+        to_f64(value / total * Decimal::ONE_HUNDRED)
+    }
+}
+
+// Herfindahl-Hirschman index of the positions: the sum of their squared weights,
+// as fractions of the total value of the assets. 1 for a single holding, 1/N for N
+// holdings of the same size, 0 without assets. Liabilities are left out like in the
+// weights, and positions are weighted as given, consolidate them first to count a
+// ticker held in two accounts once.
+pub fn concentration_index(positions: &[PortfolioPosition]) -> f64 {
+    let balances: Vec<Decimal> = positions
+        .iter()
+        .filter(|position| !position.is_liability())
+        .map(|position| position.get_balance())
+        .filter(|balance| *balance > Decimal::ZERO)
+        .collect();
+    let total: Decimal = balances.iter().sum();
+    balances
+        .iter()
+        .map(|balance| percentage_of(*balance, total) / 100.0)
+        .map(|weight| weight * weight)
+        .sum()
+}
+
+// Compare the current allocation with the target weights (in %).
+// Target weights refer to the total portfolio value. Asset classes with a target
+// but without positions show up with a current weight of 0, asset classes without
+// a target are listed without drift and rebalancing amount.
+pub fn allocation_report(
+    positions: &[PortfolioPosition],
+    targets: &HashMap<String, f64>,
+) -> Vec<ClassAllocation> {
+    let mut balances = balances_by_class(positions);
+    let total_value: Decimal = balances.values().sum();
+
+    for asset_class in targets.keys() {
+        balances.entry(asset_class.to_string()).or_default();
+    }
+
+    let mut report: Vec<ClassAllocation> = balances
+        .into_iter()
+        .map(|(asset_class, balance)| {
+            let current_weight = percentage_of(balance, total_value);
+            let target_weight = targets.get(&asset_class).copied();
+
+            ClassAllocation {
+                drift: target_weight.map(|target| current_weight - target),
+                rebalance_amount: target_weight.map(|target| {
+                    total_value * to_decimal(target) / Decimal::ONE_HUNDRED - balance
+                }),
+                asset_class,
+                balance,
+                current_weight,
+                target_weight,
+            }
+        })
+        .collect();
+
+    // sort by the current weight in descending order, then by name for a stable output
+    report.sort_by(|a, b| {
+        b.current_weight
+            .partial_cmp(&a.current_weight)
+            .unwrap()
+            .then_with(|| a.asset_class.cmp(&b.asset_class))
+    });
+    report
+}
+
+// An asset class that drifted too far from its target weight
+#[derive(Debug, Clone, PartialEq)]
+pub struct DriftAlert {
+    pub asset_class: String,
+    pub current_weight: f64,
+    pub target_weight: f64,
+    // current minus target weight in percentage points
+    pub drift: f64,
+}
+
+// Asset classes whose weight differs from the target by more than threshold_pct
+// percentage points, the largest drift first. Uses the weights of the allocation
+// report, so both always agree.
+pub fn drift_alerts(
+    positions: &[PortfolioPosition],
+    targets: &HashMap<String, f64>,
+    threshold_pct: f64,
+) -> Vec<DriftAlert> {
+    let mut alerts: Vec<DriftAlert> = allocation_report(positions, targets)
+        .into_iter()
+        .filter_map(|row| {
+            let drift = row.drift.filter(|drift| drift.abs() > threshold_pct)?;
+            Some(DriftAlert {
+                asset_class: row.asset_class,
+                current_weight: row.current_weight,
+                target_weight: row.target_weight?,
+                drift,
+            })
+        })
+        .collect();
+
+    alerts.sort_by(|a, b| {
+        b.drift
+            .abs()
+            .partial_cmp(&a.drift.abs())
+            .unwrap()
+            .then_with(|| a.asset_class.cmp(&b.asset_class))
+    });
+    alerts
+}
+
+// Share of a new contribution that goes into a single asset class
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassContribution {
+    pub asset_class: String,
+    pub amount: Decimal,
+    pub target_weight: f64,
+    // weight of the asset class after the contribution is invested
+    pub resulting_weight: f64,
+}
+
+// Purchase of a single position for a new contribution
+#[derive(Debug, Clone, PartialEq)]
+pub struct PositionPurchase {
+    pub name: String,
+    pub asset_class: String,
+    pub amount: Decimal,
+}
+
+// Split a new contribution across the asset classes with a target weight without
+// ever selling. The most underweight class (lowest balance relative to its target)
+// is filled first until it reaches the level of the next one, then both are filled
+// together, and so on. Classes on the same level are filled in proportion to their
+// target weights. Once every class is on target, the remainder is spread
+// proportionally to the target weights as well.
+pub fn split_contribution(
+    positions: &[PortfolioPosition],
+    targets: &HashMap<String, f64>,
+    contribution: Decimal,
+) -> Vec<ClassContribution> {
+    let balances = balances_by_class(positions);
+    let total_after: Decimal = balances.values().sum::<Decimal>() + contribution;
+
+    let mut classes: Vec<(&String, Decimal, Decimal)> = targets
+        .iter()
+        .filter(|(_, target)| **target > 0.0)
+        .map(|(asset_class, target)| {
+            let balance = balances.get(asset_class).copied().unwrap_or_default();
+            (asset_class, to_decimal(*target), balance)
+        })
+        .collect();
+    classes.sort_by(|a, b| (a.2 / a.1).cmp(&(b.2 / b.1)).then_with(|| a.0.cmp(b.0)));
+
+    // find the level (balance per target percent) all funded classes end up on
+    let mut level = Decimal::ZERO;
+    let mut funded = 0;
+    let mut weight_sum = Decimal::ZERO;
+    let mut balance_sum = Decimal::ZERO;
+    while funded < classes.len() {
+        let (_, target, balance) = classes[funded];
+        weight_sum += target;
+        balance_sum += balance;
+        funded += 1;
+
+        level = (contribution + balance_sum) / weight_sum;
+        if funded < classes.len() && level <= classes[funded].2 / classes[funded].1 {
+            break;
+        }
+    }
+
+    let mut result: Vec<ClassContribution> = classes
+        .iter()
+        .enumerate()
+        .map(|(i, (asset_class, target, balance))| {
+            let amount = if i < funded && contribution > Decimal::ZERO {
+                (level * target - balance).max(Decimal::ZERO)
+            } else {
+                Decimal::ZERO
+            };
+
+            ClassContribution {
+                asset_class: asset_class.to_string(),
+                amount,
+                target_weight: to_f64(*target),
+                resulting_weight: percentage_of(balance + amount, total_after),
+            }
+        })
+        .collect();
+
+    result.sort_by(|a, b| {
+        b.amount
+            .cmp(&a.amount)
+            .then_with(|| a.asset_class.cmp(&b.asset_class))
+    });
+    result
+}
+
+// Distribute the contribution of each asset class across its positions in
+// proportion to their balances. Liabilities are never bought. Asset classes
+// without any other position (or only positions without a balance) are split
+// evenly or reported as a new position.
+pub fn position_purchases(
+    positions: &[PortfolioPosition],
+    contributions: &[ClassContribution],
+) -> Vec<PositionPurchase> {
+    let mut purchases = Vec::new();
+
+    for contribution in contributions.iter().filter(|c| c.amount > Decimal::ZERO) {
+        let class_positions: Vec<&PortfolioPosition> = positions
+            .iter()
+            .filter(|p| p.get_asset_class() == contribution.asset_class && !p.is_liability())
+            .collect();
+        let class_balance: Decimal = class_positions.iter().map(|p| p.get_balance()).sum();
+
+        if class_positions.is_empty() {
+            purchases.push(PositionPurchase {
+                name: "(new position)".to_string(),
+                asset_class: contribution.asset_class.to_string(),
+                amount: contribution.amount,
+            });
+            continue;
+        }
+
+        for position in &class_positions {
+            let share = if class_balance > Decimal::ZERO {
+                position.get_balance() / class_balance
+            } else {
+                Decimal::ONE / Decimal::from(class_positions.len())
+            };
+
+            purchases.push(PositionPurchase {
+                name: position.get_name().to_string(),
+                asset_class: contribution.asset_class.to_string(),
+                amount: contribution.amount * share,
+            });
+        }
+    }
+    purchases
+}
+
+// Print the purchases and the resulting allocation of a contribution
+pub fn print_contribution_plan(
+    purchases: &[PositionPurchase],
+    contributions: &[ClassContribution],
+    format: &NumberFormat,
+) {
+    println!(
+        "{0: >26} | {1: >12} | {2: >10}",
+        "Name", "Asset Class", "Buy"
+    );
+    println!("====================================================================");
+    for purchase in purchases {
+        println!(
+            "{0: >26} | {1: >12} | {2: >10}",
+            purchase.name,
+            purchase.asset_class,
+            format.money(purchase.amount)
+        );
+    }
+
+    println!();
+    println!(
+        "{0: >12} | {1: >10} | {2: >10} | {3: >10}",
+        "Asset Class", "Buy", "New %", "Target %"
+    );
+    println!("====================================================================");
+    for contribution in contributions {
+        println!(
+            "{0: >12} | {1: >10} | {2: >10} | {3: >10}",
+            contribution.asset_class,
+            format.money(contribution.amount),
+            format.percent_number(to_decimal(contribution.resulting_weight)),
+            format.percent_number(to_decimal(contribution.target_weight))
+        );
+    }
+}
+
+pub fn print_drift_alerts(alerts: &[DriftAlert], threshold_pct: f64, format: &NumberFormat) {
+    if alerts.is_empty() {
+        println!(
+            "No asset class drifted more than {} pp from its target",
+            format.percent_number(to_decimal(threshold_pct))
+        );
+        return;
+    }
+    for alert in alerts {
+        println!(
+            "Alert: {} is at {}, {} pp from its target of {}",
+            alert.asset_class,
+            format.percent(to_decimal(alert.current_weight)),
+            format.signed_percent_number(to_decimal(alert.drift)),
+            format.percent(to_decimal(alert.target_weight))
+        );
+    }
+}
+
+// Print the allocation report as a table
+pub fn print_allocation_report(report: &[ClassAllocation], format: &NumberFormat) {
+    println!(
+        "{0: >12} | {1: >10} | {2: >10} | {3: >10} | {4: >12}",
+        "Asset Class", "Current %", "Target %", "Drift (pp)", "Buy/Sell"
+    );
+    println!("====================================================================");
+    for row in report {
+        let target = match row.target_weight {
+            Some(target) => format.percent_number(to_decimal(target)),
+            None => "-".to_string(),
+        };
+        let drift = match row.drift {
+            Some(drift) => format.signed_percent_number(to_decimal(drift)),
+            None => "-".to_string(),
+        };
+        let rebalance_amount = match row.rebalance_amount {
+            Some(amount) => format.signed_money(amount),
+            None => "-".to_string(),
+        };
+
+        println!(
+            "{0: >12} | {1: >10} | {2: >10} | {3: >10} | {4: >12}",
+            row.asset_class,
+            format.percent_number(to_decimal(row.current_weight)),
+            target,
+            drift,
+            rebalance_amount
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::from_string;
+    use rust_decimal::dec;
+
+    fn fixed_portfolio() -> Vec<PortfolioPosition> {
+        from_string(
+            r#"[
+                {"Name": "World ETF", "AssetClass": "Stocks", "Amount": 500},
+                {"Name": "Tech ETF", "AssetClass": "Stocks", "Amount": 200},
+                {"Name": "Bond Fund", "AssetClass": "Bonds", "Amount": 300}
+            ]"#,
+        )
+    }
+
+    fn targets() -> HashMap<String, f64> {
+        HashMap::from([
+            ("Stocks".to_string(), 60.0),
+            ("Bonds".to_string(), 30.0),
+            ("Cash".to_string(), 10.0),
+        ])
+    }
+
+    #[test]
+    fn test_allocation_percentages() {
+        let allocation = allocation_percentages(&fixed_portfolio());
+        assert_eq!(allocation.len(), 2);
+        assert_eq!(allocation["Stocks"], 70.0);
+        assert_eq!(allocation["Bonds"], 30.0);
+    }
+
+    #[test]
+    fn test_allocation_percentages_empty_portfolio() {
+        assert!(allocation_percentages(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_concentration_index() {
+        let single = from_string(r#"[{"Name": "A", "AssetClass": "Cash", "Amount": 500}]"#);
+        assert_eq!(concentration_index(&single), 1.0);
+
+        // ten positions of the same size, the loan is not a holding
+        let mut data: Vec<String> = (0..10)
+            .map(|i| format!(r#"{{"Name": "P{i}", "AssetClass": "Cash", "Amount": 100}}"#))
+            .collect();
+        data.push(r#"{"Name": "Loan", "AssetClass": "Liability", "Amount": 5000}"#.to_string());
+        let even = from_string(&format!("[{}]", data.join(",")));
+        assert!((concentration_index(&even) - 0.1).abs() < 1e-12);
+
+        // 50%, 20% and 30%, the positions count rather than the asset classes
+        assert!((concentration_index(&fixed_portfolio()) - 0.38).abs() < 1e-12);
+        assert_eq!(concentration_index(&[]), 0.0);
+    }
+
+    #[test]
+    fn test_allocation_report() {
+        let report = allocation_report(&fixed_portfolio(), &targets());
+        assert_eq!(report.len(), 3);
+
+        let stocks = &report[0];
+        assert_eq!(stocks.asset_class, "Stocks");
+        assert_eq!(stocks.drift, Some(10.0));
+        assert_eq!(stocks.rebalance_amount, Some(dec!(-100)));
+
+        let bonds = &report[1];
+        assert_eq!(bonds.asset_class, "Bonds");
+        assert_eq!(bonds.drift, Some(0.0));
+        assert_eq!(bonds.rebalance_amount, Some(dec!(0)));
+
+        // targeted, but not held at all
+        let cash = &report[2];
+        assert_eq!(cash.asset_class, "Cash");
+        assert_eq!(cash.current_weight, 0.0);
+        assert_eq!(cash.drift, Some(-10.0));
+        assert_eq!(cash.rebalance_amount, Some(dec!(100)));
+
+        let sum: Decimal = report.iter().filter_map(|row| row.rebalance_amount).sum();
+        assert_eq!(sum, dec!(0));
+    }
+
+    fn contribution_for<'a>(
+        contributions: &'a [ClassContribution],
+        asset_class: &str,
+    ) -> &'a ClassContribution {
+        contributions
+            .iter()
+            .find(|c| c.asset_class == asset_class)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_split_contribution_too_small() {
+        let positions = fixed_portfolio();
+
+        // only the missing cash gets money
+        let contributions = split_contribution(&positions, &targets(), dec!(50));
+        assert_eq!(contribution_for(&contributions, "Cash").amount, dec!(50));
+        assert_eq!(contribution_for(&contributions, "Bonds").amount, dec!(0));
+        assert_eq!(contribution_for(&contributions, "Stocks").amount, dec!(0));
+
+        // cash is filled up to the level of bonds, then both are filled together
+        let contributions = split_contribution(&positions, &targets(), dec!(150));
+        assert_eq!(contribution_for(&contributions, "Cash").amount, dec!(112.5));
+        assert_eq!(contribution_for(&contributions, "Bonds").amount, dec!(37.5));
+        assert_eq!(contribution_for(&contributions, "Stocks").amount, dec!(0));
+
+        let sum: Decimal = contributions.iter().map(|c| c.amount).sum();
+        assert_eq!(sum, dec!(150));
+    }
+
+    #[test]
+    fn test_split_contribution_overshoot() {
+        let positions = fixed_portfolio();
+        let contributions = split_contribution(&positions, &targets(), dec!(2000));
+
+        // every class ends up on target, the remainder is spread by target weight
+        assert_eq!(
+            contribution_for(&contributions, "Stocks").amount,
+            dec!(1100)
+        );
+        assert_eq!(contribution_for(&contributions, "Bonds").amount, dec!(600));
+        assert_eq!(contribution_for(&contributions, "Cash").amount, dec!(300));
+        for contribution in &contributions {
+            assert!(contribution.amount >= dec!(0));
+            assert!((contribution.resulting_weight - contribution.target_weight).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_position_purchases() {
+        let positions = fixed_portfolio();
+        let contributions = split_contribution(&positions, &targets(), dec!(2000));
+        let purchases = position_purchases(&positions, &contributions);
+
+        let amount_for = |name: &str| {
+            purchases
+                .iter()
+                .find(|p| p.name == name)
+                .map(|p| p.amount)
+                .unwrap()
+        };
+        assert_eq!(amount_for("World ETF").round_dp(2), dec!(785.71));
+        assert_eq!(amount_for("Tech ETF").round_dp(2), dec!(314.29));
+        assert_eq!(amount_for("Bond Fund"), dec!(600));
+        assert_eq!(amount_for("(new position)"), dec!(300));
+    }
+
+    #[test]
+    fn test_position_purchases_skip_liabilities() {
+        let positions = from_string(
+            r#"[
+                {"Name": "Savings", "AssetClass": "Cash", "Amount": 1000},
+                {"Name": "Card", "AssetClass": "Cash", "Amount": -500},
+                {"Name": "Gold Coins", "AssetClass": "Gold", "Amount": 1000}
+            ]"#,
+        );
+        let targets = HashMap::from([("Cash".to_string(), 50.0), ("Gold".to_string(), 50.0)]);
+        let contributions = split_contribution(&positions, &targets, dec!(1000));
+        assert_eq!(contribution_for(&contributions, "Cash").amount, dec!(500));
+
+        let purchases = position_purchases(&positions, &contributions);
+        let cash: Vec<_> = purchases
+            .iter()
+            .filter(|p| p.asset_class == "Cash")
+            .collect();
+        assert_eq!(cash.len(), 1);
+        assert_eq!(cash[0].name, "Savings");
+        assert_eq!(cash[0].amount, dec!(500));
+
+        // a class of debts only gets a new position
+        let only_debt = from_string(
+            r#"[
+                {"Name": "Card", "AssetClass": "Cash", "Amount": -500},
+                {"Name": "Gold Coins", "AssetClass": "Gold", "Amount": 1000}
+            ]"#,
+        );
+        let contributions = split_contribution(&only_debt, &targets, dec!(1000));
+        let purchases = position_purchases(&only_debt, &contributions);
+        let cash: Vec<_> = purchases
+            .iter()
+            .filter(|p| p.asset_class == "Cash")
+            .collect();
+        assert_eq!(cash.len(), 1);
+        assert_eq!(cash[0].name, "(new position)");
+        assert!(purchases.iter().all(|p| p.amount > Decimal::ZERO));
+    }
+
+    #[test]
+    fn test_allocation_report_class_without_target() {
+        let mut positions = fixed_portfolio();
+        positions.extend(from_string(
+            r#"[{"Name": "Gold Coins", "AssetClass": "Gold", "Amount": 100}]"#,
+        ));
+
+        let report = allocation_report(&positions, &targets());
+        let gold = report.iter().find(|row| row.asset_class == "Gold").unwrap();
+        assert!((gold.current_weight - 100.0 / 11.0).abs() < 1e-9);
+        assert_eq!(gold.target_weight, None);
+        assert_eq!(gold.drift, None);
+        assert_eq!(gold.rebalance_amount, None);
+    }
+
+    #[test]
+    fn test_drift_alerts() {
+        // stocks and cash are exactly 10 pp off, bonds are on target
+        assert!(drift_alerts(&fixed_portfolio(), &targets(), 10.0).is_empty());
+
+        let alerts = drift_alerts(&fixed_portfolio(), &targets(), 5.0);
+        assert_eq!(
+            alerts,
+            [
+                DriftAlert {
+                    asset_class: "Cash".to_string(),
+                    current_weight: 0.0,
+                    target_weight: 10.0,
+                    drift: -10.0,
+                },
+                DriftAlert {
+                    asset_class: "Stocks".to_string(),
+                    current_weight: 70.0,
+                    target_weight: 60.0,
+                    drift: 10.0,
+                },
+            ]
+        );
+
+        // classes without a target never alert
+        let mut positions = fixed_portfolio();
+        positions.extend(from_string(
+            r#"[{"Name": "Gold Coins", "AssetClass": "Gold", "Amount": 1000}]"#,
+        ));
+        let alerts = drift_alerts(&positions, &targets(), 0.0);
+        assert!(alerts.iter().all(|alert| alert.asset_class != "Gold"));
+        assert_eq!(alerts.len(), 3);
+    }
+
+    #[test]
+    fn test_liabilities_are_not_part_of_the_allocation() {
+        let mut positions = fixed_portfolio();
+        positions.extend(from_string(
+            r#"[
+                {"Name": "Mortgage", "AssetClass": "Liability", "Amount": 5000},
+                {"Name": "Credit Card", "AssetClass": "Cash", "Amount": -200}
+            ]"#,
+        ));
+        assert_eq!(total_liabilities(&positions), dec!(-5200));
+
+        let allocation = allocation_percentages(&positions);
+        assert_eq!(allocation.len(), 2);
+        assert_eq!(allocation["Stocks"], 70.0);
+
+        // more debt than assets doesn't turn the weights around
+        let report = allocation_report(&positions, &targets());
+        let stocks = report
+            .iter()
+            .find(|row| row.asset_class == "Stocks")
+            .unwrap();
+        assert_eq!(stocks.rebalance_amount, Some(dec!(-100)));
+
+        let only_debt =
+            from_string(r#"[{"Name": "Mortgage", "AssetClass": "Liability", "Amount": 5000}]"#);
+        assert!(allocation_percentages(&only_debt).is_empty());
+        let report = allocation_report(&only_debt, &targets());
+        assert!(report.iter().all(|row| row.current_weight == 0.0));
+    }
+}
@@ -0,0 +1,215 @@
+use crate::format::NumberFormat;
+use crate::inflation::real_return_percent;
+use crate::position::{to_decimal, to_f64, PortfolioPosition};
+use crate::provider::{PriceAdjustment, PriceProvider};
+use chrono::prelude::*;
+use rust_decimal::Decimal;
+
+// Value of a position at `to`: the current balance for today or later, the
+// adjusted close before. Positions without a quote earn their interest rate.
+async fn values_between(
+    provider: &impl PriceProvider,
+    position: &PortfolioPosition,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    window: Option<chrono::Duration>,
+) -> Result<(Decimal, Decimal), String> {
+    let Some(ticker) = position
+        .get_ticker()
+        .filter(|_| position.is_market_instrument())
+    else {
+        // interest grows a debt, so it is subtracted from a negative balance
+        let interest = position.calculate_interest(from, to).abs();
+        let balance = position.get_balance();
+        let end = if balance < Decimal::ZERO {
+            balance - interest
+        } else {
+            balance + interest
+        };
+        return Ok((balance, end));
+    };
+
+    let value_at = |date: DateTime<Utc>| async move {
+        provider
+            .get_historic_price(
+                ticker,
+                date,
+                PriceAdjustment::Adjusted,
+                window.unwrap_or_else(|| position.historic_price_window()),
+            )
+            .await
+            .map(|price| to_decimal(price.price) * position.get_amount())
+            .map_err(|e| format!("Error getting historic price data for {ticker}: {e}"))
+    };
+    let start = value_at(from).await?;
+    let end = if to.date_naive() >= Utc::now().date_naive() {
+        position.get_balance()
+    } else {
+        value_at(to).await?
+    };
+    Ok((start, end))
+}
+
+// Each position's contribution to the portfolio return between two dates in
+// percentage points: its weight at `from` times its own return. The contributions
+// add up to the return of the whole portfolio, the largest comes first.
+// Like the performance, this uses today's amounts for both dates.
+pub async fn return_contributions(
+    provider: &impl PriceProvider,
+    positions: &[PortfolioPosition],
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    window: Option<chrono::Duration>,
+) -> Result<Vec<(String, f64)>, String> {
+    if from >= to {
+        return Err("The start of the period must be before its end".to_string());
+    }
+
+    let results = futures::future::join_all(
+        positions
+            .iter()
+            .map(|position| values_between(provider, position, from, to, window)),
+    )
+    .await;
+    let mut values = Vec::new();
+    let mut errors = Vec::new();
+    for (position, result) in positions.iter().zip(results) {
+        match result {
+            Ok(value) => values.push((position.get_name().to_string(), value)),
+            Err(e) => errors.push(e),
+        }
+    }
+    if !errors.is_empty() {
+        return Err(errors.join("; "));
+    }
+
+    let start_total: Decimal = values.iter().map(|(_, (start, _))| *start).sum();
+    if start_total <= Decimal::ZERO {
+        return Err(format!(
+            "The portfolio had no value on {}",
+            from.format("%Y-%m-%d")
+        ));
+    }
+
+    // weight * return = (start / total) * ((end - start) / start)
+    let mut contributions: Vec<(String, f64)> = values
+        .into_iter()
+        .map(|(name, (start, end))| {
+            let contribution = (end - start) / start_total * Decimal::ONE_HUNDRED;
+            (name, to_f64(contribution))
+        })
+        .collect();
+    contributions.sort_by(|a, b| b.1.total_cmp(&a.1));
+    Ok(contributions)
+}
+
+// With an annual inflation in %, the real return of the portfolio ends the table
+pub fn print_return_contributions(
+    contributions: &[(String, f64)],
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    inflation: Option<f64>,
+    format: &NumberFormat,
+) {
+    println!(
+        "Contribution to the return from {} to {}",
+        from.format("%Y-%m-%d"),
+        to.format("%Y-%m-%d")
+    );
+    println!("{0: >26} | {1: >12}", "Name", "Contribution");
+    println!("===========================================");
+    for (name, contribution) in contributions {
+        println!(
+            "{0: >26} | {1: >12}",
+            name,
+            format.signed_percent(to_decimal(*contribution))
+        );
+    }
+    println!("===========================================");
+    let total: f64 = contributions
+        .iter()
+        .map(|(_, contribution)| contribution)
+        .sum();
+    println!(
+        "{0: >26} | {1: >12}",
+        "Portfolio return",
+        format.signed_percent(to_decimal(total))
+    );
+    if let Some(inflation) = inflation {
+        println!(
+            "{0: >26} | {1: >12}",
+            "Real return",
+            format.signed_percent(to_decimal(real_return_percent(total, inflation, from, to)))
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::{priced, MockProvider};
+
+    #[tokio::test]
+    async fn test_return_contributions() {
+        // worth 1000 + 500 + 500 = 2000 at the start of the year
+        let provider = MockProvider::new()
+            .with_price("AAPL", 130.0)
+            .with_historic_price("AAPL", 100.0)
+            .with_price("BND", 45.0)
+            .with_historic_price("BND", 50.0);
+        let positions = priced(
+            &provider,
+            r#"[
+                {"Name": "Apple", "Ticker": "AAPL", "AssetClass": "Stocks", "Amount": 10},
+                {"Name": "Bonds", "Ticker": "BND", "AssetClass": "Bonds", "Amount": 10},
+                {"Name": "Savings", "AssetClass": "Cash", "Amount": 500, "InterestRate": 3.65}
+            ]"#,
+        )
+        .await;
+        let from = Utc::now() - chrono::Duration::days(100);
+
+        let contributions = return_contributions(&provider, &positions, from, Utc::now(), None)
+            .await
+            .unwrap();
+        let names: Vec<&str> = contributions
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        assert_eq!(names, ["Apple", "Savings", "Bonds"]);
+        // Apple +300, 5 interest on the savings over 100 days, Bonds -50
+        assert!((contributions[0].1 - 15.0).abs() < 1e-9);
+        assert!((contributions[1].1 - 0.25).abs() < 1e-9);
+        assert!((contributions[2].1 + 2.5).abs() < 1e-9);
+
+        // the contributions add up to the portfolio return: 2000 -> 2255
+        let total: f64 = contributions.iter().map(|(_, c)| c).sum();
+        assert!((total - 12.75).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_return_contributions_errors() {
+        let provider = MockProvider::new().with_price("AAPL", 130.0);
+        let positions = priced(
+            &provider,
+            r#"[{"Name": "Apple", "Ticker": "AAPL", "AssetClass": "Stocks", "Amount": 10}]"#,
+        )
+        .await;
+        let from = Utc.with_ymd_and_hms(2024, 1, 2, 0, 0, 0).unwrap();
+        let to = Utc.with_ymd_and_hms(2024, 6, 3, 0, 0, 0).unwrap();
+
+        // no history for AAPL
+        assert!(return_contributions(&provider, &positions, from, to, None)
+            .await
+            .unwrap_err()
+            .starts_with("Error getting historic price data for AAPL"));
+        assert!(return_contributions(&provider, &positions, to, from, None)
+            .await
+            .is_err());
+        assert_eq!(
+            return_contributions(&provider, &[], from, to, None)
+                .await
+                .unwrap_err(),
+            "The portfolio had no value on 2024-01-02"
+        );
+    }
+}
@@ -0,0 +1,364 @@
+use crate::format::NumberFormat;
+use crate::inflation::real_return_percent;
+use crate::portfolio::Portfolio;
+use crate::position::{to_decimal, to_f64, PortfolioPosition};
+use crate::provider::{
+    is_crypto_ticker, PriceAdjustment, PriceProvider, QuoteError, CRYPTO_HISTORIC_WINDOW_DAYS,
+    HISTORIC_WINDOW_DAYS,
+};
+use chrono::prelude::*;
+use rust_decimal::Decimal;
+
+// Returns in % over the same period
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchmarkComparison {
+    pub benchmark: String,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub portfolio_return: f64,
+    pub benchmark_return: f64,
+    // portfolio return minus benchmark return, in percentage points
+    pub alpha: f64,
+}
+
+// Value of the positions at `date`: today's balances from today on, otherwise the
+// historic valuation of the performance
+async fn portfolio_value_at(
+    provider: &impl PriceProvider,
+    portfolio: &Portfolio,
+    date: DateTime<Utc>,
+    window: Option<chrono::Duration>,
+) -> Result<Decimal, String> {
+    if date.date_naive() >= Utc::now().date_naive() {
+        return Ok(portfolio.get_total_value());
+    }
+    portfolio
+        .get_historic_total_value(provider, date, PriceAdjustment::Adjusted, window)
+        .await
+}
+
+async fn benchmark_price_at(
+    provider: &impl PriceProvider,
+    ticker: &str,
+    date: DateTime<Utc>,
+    window: Option<chrono::Duration>,
+) -> Result<f64, String> {
+    let error = |e: QuoteError| format!("Error getting the price of the benchmark {ticker}: {e}");
+    if date.date_naive() >= Utc::now().date_naive() {
+        return provider
+            .get_latest_price(ticker)
+            .await
+            .map(|quote| quote.price)
+            .map_err(error);
+    }
+    let window = window.unwrap_or_else(|| {
+        if is_crypto_ticker(ticker) {
+            chrono::Duration::days(CRYPTO_HISTORIC_WINDOW_DAYS)
+        } else {
+            chrono::Duration::days(HISTORIC_WINDOW_DAYS)
+        }
+    });
+    provider
+        .get_historic_price(ticker, date, PriceAdjustment::Adjusted, window)
+        .await
+        .map(|price| price.price)
+        .map_err(error)
+}
+
+// The return of the positions and of the benchmark between two dates. Both sides
+// look up their endpoints the same way, so a date on which the markets were closed
+// (a weekend or a holiday) uses a close of the trading days after it: the prices
+// are taken from the window of the historic valuation that starts at the date, 3
+// days (1 for crypto) unless a window is given. If there is no close in the window
+// the comparison fails rather than shifting the period. An end of today or later uses the current
+// prices. Adjusted closes include the dividends on both sides and, like the
+// performance, today's amounts are used for both dates.
+pub async fn compare_to_benchmark(
+    provider: &impl PriceProvider,
+    positions: &[PortfolioPosition],
+    benchmark_ticker: &str,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    window: Option<chrono::Duration>,
+) -> Result<BenchmarkComparison, String> {
+    if from >= to {
+        return Err("The start of the period must be before its end".to_string());
+    }
+
+    let mut portfolio = Portfolio::new();
+    for position in positions {
+        portfolio.add_position(position.clone());
+    }
+    let (start, end, benchmark_start, benchmark_end) = futures::join!(
+        portfolio_value_at(provider, &portfolio, from, window),
+        portfolio_value_at(provider, &portfolio, to, window),
+        benchmark_price_at(provider, benchmark_ticker, from, window),
+        benchmark_price_at(provider, benchmark_ticker, to, window),
+    );
+    let (start, end) = (start?, end?);
+    let (benchmark_start, benchmark_end) = (benchmark_start?, benchmark_end?);
+    if start <= Decimal::ZERO {
+        return Err(format!(
+            "The portfolio had no value on {}",
+            from.format("%Y-%m-%d")
+        ));
+    }
+    if benchmark_start <= 0.0 {
+        return Err(format!(
+            "The benchmark {benchmark_ticker} had no price on {}",
+            from.format("%Y-%m-%d")
+        ));
+    }
+
+    let portfolio_return = to_f64((end - start) / start * Decimal::ONE_HUNDRED);
+    let benchmark_return = (benchmark_end - benchmark_start) / benchmark_start * 100.0;
+    Ok(BenchmarkComparison {
+        benchmark: benchmark_ticker.to_string(),
+        from,
+        to,
+        portfolio_return,
+        benchmark_return,
+        alpha: portfolio_return - benchmark_return,
+    })
+}
+
+// The compound annual growth rate in % from the start to the end value, None
+// without a positive start value or period (and for an end value below 0, which
+// has no rate either)
+pub fn cagr(start_value: f64, end_value: f64, years: f64) -> Option<f64> {
+    if start_value <= 0.0 || years <= 0.0 || end_value < 0.0 {
+        return None;
+    }
+    Some(((end_value / start_value).powf(1.0 / years) - 1.0) * 100.0)
+}
+
+// The growth rate of the positions per year from one date to the other, valued
+// like the returns of compare_to_benchmark. A year is 365 days, like for the
+// annualized return of a position.
+pub async fn portfolio_cagr(
+    provider: &impl PriceProvider,
+    positions: &[PortfolioPosition],
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    window: Option<chrono::Duration>,
+) -> Result<f64, String> {
+    if from >= to {
+        return Err("The start of the period must be before its end".to_string());
+    }
+
+    let mut portfolio = Portfolio::new();
+    for position in positions {
+        portfolio.add_position(position.clone());
+    }
+    let (start, end) = futures::join!(
+        portfolio_value_at(provider, &portfolio, from, window),
+        portfolio_value_at(provider, &portfolio, to, window),
+    );
+    let (start, end) = (to_f64(start?), to_f64(end?));
+    if start <= 0.0 {
+        return Err(format!(
+            "The portfolio had no value on {} to grow from",
+            from.format("%Y-%m-%d")
+        ));
+    }
+    if end < 0.0 {
+        return Err(format!(
+            "The portfolio owed more than it was worth on {}, there is no growth rate",
+            to.format("%Y-%m-%d")
+        ));
+    }
+    let years = (to - from).num_seconds() as f64 / (365.0 * 86400.0);
+    cagr(start, end, years).ok_or_else(|| "The period is too short for a growth rate".to_string())
+}
+
+// With an annual inflation in %, the real returns follow the nominal ones. The
+// alpha stays nominal, inflation is the same for both sides.
+pub fn print_benchmark_comparison(
+    comparison: &BenchmarkComparison,
+    inflation: Option<f64>,
+    format: &NumberFormat,
+) {
+    println!(
+        "Return from {} to {}",
+        comparison.from.format("%Y-%m-%d"),
+        comparison.to.format("%Y-%m-%d")
+    );
+    for (label, value) in [
+        ("Portfolio".to_string(), comparison.portfolio_return),
+        (comparison.benchmark.clone(), comparison.benchmark_return),
+    ] {
+        println!(
+            "{0: >26} | {1: >12}",
+            label,
+            format.signed_percent(to_decimal(value))
+        );
+        if let Some(inflation) = inflation {
+            let real = real_return_percent(value, inflation, comparison.from, comparison.to);
+            println!(
+                "{0: >26} | {1: >12}",
+                format!("{label} (real)"),
+                format.signed_percent(to_decimal(real))
+            );
+        }
+    }
+    println!("===========================================");
+    println!(
+        "{0: >26} | {1: >12}",
+        "Difference (alpha)",
+        format.signed_percent(to_decimal(comparison.alpha))
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::{priced, MockProvider};
+
+    #[tokio::test]
+    async fn test_compare_to_benchmark() {
+        // the portfolio goes from 1000 + 1000 to 1300 + 1000, SPY from 400 to 440
+        let provider = MockProvider::new()
+            .with_price("AAPL", 130.0)
+            .with_historic_price("AAPL", 100.0)
+            .with_price("SPY", 440.0)
+            .with_historic_price("SPY", 400.0);
+        let positions = priced(
+            &provider,
+            r#"[
+                {"Name": "Apple", "Ticker": "AAPL", "AssetClass": "Stocks", "Amount": 10},
+                {"Name": "Savings", "AssetClass": "Cash", "Amount": 1000}
+            ]"#,
+        )
+        .await;
+        let from = Utc::now() - chrono::Duration::days(100);
+
+        let comparison = compare_to_benchmark(&provider, &positions, "SPY", from, Utc::now(), None)
+            .await
+            .unwrap();
+        assert_eq!(comparison.benchmark, "SPY");
+        assert!((comparison.portfolio_return - 15.0).abs() < 1e-9);
+        assert!((comparison.benchmark_return - 10.0).abs() < 1e-9);
+        assert!((comparison.alpha - 5.0).abs() < 1e-9);
+        // the start is looked up in the window of a stock
+        assert_eq!(
+            provider.historic_window("SPY"),
+            Some(chrono::Duration::days(HISTORIC_WINDOW_DAYS))
+        );
+
+        // a longer window, e.g. for a start in a holiday week, applies to both sides
+        let window = Some(chrono::Duration::days(10));
+        compare_to_benchmark(&provider, &positions, "SPY", from, Utc::now(), window)
+            .await
+            .unwrap();
+        assert_eq!(provider.historic_window("SPY"), window);
+        assert_eq!(provider.historic_window("AAPL"), window);
+    }
+
+    #[tokio::test]
+    async fn test_compare_to_benchmark_errors() {
+        let provider = MockProvider::new()
+            .with_price("AAPL", 130.0)
+            .with_historic_price("AAPL", 100.0)
+            .with_price("SPY", 440.0);
+        let positions = priced(
+            &provider,
+            r#"[{"Name": "Apple", "Ticker": "AAPL", "AssetClass": "Stocks", "Amount": 10}]"#,
+        )
+        .await;
+        let from = Utc.with_ymd_and_hms(2024, 1, 2, 0, 0, 0).unwrap();
+        let now = Utc::now();
+
+        // no history for SPY
+        assert!(
+            compare_to_benchmark(&provider, &positions, "SPY", from, now, None)
+                .await
+                .unwrap_err()
+                .starts_with("Error getting the price of the benchmark SPY")
+        );
+        assert!(
+            compare_to_benchmark(&provider, &positions, "SPY", now, from, None)
+                .await
+                .is_err()
+        );
+        let provider = provider.with_historic_price("SPY", 400.0);
+        assert_eq!(
+            compare_to_benchmark(&provider, &[], "SPY", from, now, None)
+                .await
+                .unwrap_err(),
+            "The portfolio had no value on 2024-01-02"
+        );
+    }
+
+    #[test]
+    fn test_cagr() {
+        // doubling in 2 years is sqrt(2) - 1 per year
+        let rate = cagr(1000.0, 2000.0, 2.0).unwrap();
+        assert!((rate - 41.421356).abs() < 1e-6);
+        assert!((cagr(1000.0, 1100.0, 1.0).unwrap() - 10.0).abs() < 1e-9);
+        assert!((cagr(1000.0, 810.0, 2.0).unwrap() + 10.0).abs() < 1e-9);
+        assert!((cagr(100.0, 0.0, 1.0).unwrap() + 100.0).abs() < 1e-9);
+
+        assert_eq!(cagr(0.0, 2000.0, 2.0), None);
+        assert_eq!(cagr(-500.0, 2000.0, 2.0), None);
+        assert_eq!(cagr(1000.0, 2000.0, 0.0), None);
+        assert_eq!(cagr(1000.0, 2000.0, -1.0), None);
+        assert_eq!(cagr(1000.0, -10.0, 1.0), None);
+    }
+
+    #[tokio::test]
+    async fn test_portfolio_cagr() {
+        // 10 AAPL from 100 to 200 over two years of 365 days
+        let provider = MockProvider::new()
+            .with_price("AAPL", 200.0)
+            .with_historic_price("AAPL", 100.0);
+        let positions = priced(
+            &provider,
+            r#"[{"Name": "Apple", "Ticker": "AAPL", "AssetClass": "Stocks", "Amount": 10}]"#,
+        )
+        .await;
+        let now = Utc::now();
+        let from = now - chrono::Duration::days(730);
+
+        let rate = portfolio_cagr(&provider, &positions, from, now, None)
+            .await
+            .unwrap();
+        assert!((rate - 41.421356).abs() < 1e-4);
+
+        assert!(portfolio_cagr(&provider, &positions, now, from, None)
+            .await
+            .is_err());
+        assert!(portfolio_cagr(&provider, &[], from, now, None)
+            .await
+            .unwrap_err()
+            .starts_with("The portfolio had no value on "));
+    }
+
+    #[tokio::test]
+    async fn test_portfolio_cagr_negative_end_value() {
+        // 10 AAPL fall from 100 to 10 while the loan of 500 stays
+        let provider = MockProvider::new()
+            .with_price("AAPL", 10.0)
+            .with_historic_price("AAPL", 100.0);
+        let positions = priced(
+            &provider,
+            r#"[
+                {"Name": "Apple", "Ticker": "AAPL", "AssetClass": "Stocks", "Amount": 10},
+                {"Name": "Loan", "AssetClass": "Liability", "Amount": 500}
+            ]"#,
+        )
+        .await;
+        let now = Utc::now();
+        let from = now - chrono::Duration::days(365);
+
+        let error = portfolio_cagr(&provider, &positions, from, now, None)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error,
+            format!(
+                "The portfolio owed more than it was worth on {}, there is no growth rate",
+                now.format("%Y-%m-%d")
+            )
+        );
+    }
+}
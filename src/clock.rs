@@ -0,0 +1,56 @@
+use chrono::{DateTime, NaiveDate, Utc};
+
+// The time a run happens at. Applying the due interest depends on it, tests use a
+// FixedClock and --as-of runs the commands as if it were another day.
+pub trait Clock {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+// Wall-clock time, the default
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+pub struct FixedClock(pub DateTime<Utc>);
+
+impl FixedClock {
+    // The start of the day, which is when the interest payment dates are due
+    pub fn at_date(date: NaiveDate) -> Self {
+        FixedClock(date.and_hms_opt(0, 0, 0).unwrap().and_utc())
+    }
+}
+
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.0
+    }
+}
+
+// A FixedClock for --as-of, the SystemClock without it
+pub fn as_of(date: Option<NaiveDate>) -> Box<dyn Clock> {
+    match date {
+        Some(date) => Box::new(FixedClock::at_date(date)),
+        None => Box::new(SystemClock),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_as_of() {
+        let date = NaiveDate::from_ymd_opt(2024, 2, 29).unwrap();
+        assert_eq!(
+            as_of(Some(date)).now(),
+            Utc.with_ymd_and_hms(2024, 2, 29, 0, 0, 0).unwrap()
+        );
+        let before = Utc::now();
+        assert!(as_of(None).now() >= before);
+    }
+}
@@ -0,0 +1,567 @@
+use crate::alerts::AlertRule;
+use crate::format::{NumberFormat, NumberFormatConfig, NumberStyle};
+use crate::inflation::check_inflation;
+use crate::position::{
+    proxy_for, CsvColumns, NetworkSettings, DEFAULT_MAX_QUOTE_AGE_DAYS,
+    DEFAULT_REQUEST_TIMEOUT_SECONDS,
+};
+use crate::rate_limit::DEFAULT_REQUESTS_PER_SECOND;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+// Path of the config file, instead of config.toml in the config directory
+pub const CONFIG_VARIABLE: &str = "PORTFOLIO_RS_CONFIG";
+// These override the config file, the flags override them
+pub const FILE_VARIABLE: &str = "PORTFOLIO_RS_FILE";
+pub const CURRENCY_VARIABLE: &str = "PORTFOLIO_RS_CURRENCY";
+pub const CACHE_TTL_VARIABLE: &str = "PORTFOLIO_RS_CACHE_TTL";
+pub const OUTPUT_VARIABLE: &str = "PORTFOLIO_RS_OUTPUT";
+// The proxy variables of the HTTP client, they override the proxy of the config file
+pub const PROXY_VARIABLES: [&str; 6] = [
+    "HTTPS_PROXY",
+    "https_proxy",
+    "HTTP_PROXY",
+    "http_proxy",
+    "ALL_PROXY",
+    "all_proxy",
+];
+pub const NUMBER_FORMAT_VARIABLE: &str = "PORTFOLIO_RS_NUMBER_FORMAT";
+
+// Seconds serve answers with a cached quote
+pub const DEFAULT_CACHE_TTL_SECONDS: u64 = 60;
+// Output modes of balances, the first is the default
+pub const OUTPUT_MODES: [&str; 4] = ["table", "csv", "json", "beancount"];
+
+// Every setting is optional, a missing one keeps its default
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    // used when no FILE or --file is given
+    pub portfolio_file: String,
+    pub currency: String,
+    // target weight in % per asset class, e.g. Stocks: 60.0
+    pub allocation_targets: HashMap<String, f64>,
+    // separators, symbol and precision of the amounts
+    pub number_format: NumberFormatConfig,
+    // quotes older than this many trading days are marked as stale
+    pub max_quote_age_days: i64,
+    // expected annual return in % per asset class for the forecast, e.g. Stocks: 7.0
+    pub expected_returns: HashMap<String, f64>,
+    // annual volatility in % per asset class for the Monte Carlo forecast, e.g. Stocks: 15.0
+    pub volatilities: HashMap<String, f64>,
+    // price alerts checked by the alerts command
+    pub alerts: Vec<AlertRule>,
+    // URL the alerts are posted to as JSON, e.g. of ntfy, Slack or Discord
+    pub alert_webhook: Option<String>,
+    // seconds serve reuses a quote, see serve --ttl
+    pub cache_ttl_seconds: Option<u64>,
+    // output of balances, see balances --output
+    pub output: Option<String>,
+    // write the names looked up for positions without a Name into the data file
+    pub save_resolved_names: bool,
+    // look up the names of the tickers of positions without a Name
+    pub fetch_names: bool,
+    // annual inflation in % for the real returns and yields, see --inflation
+    pub inflation_rate: Option<f64>,
+    // seconds a quote request may take, DEFAULT_REQUEST_TIMEOUT_SECONDS without one
+    pub request_timeout_seconds: Option<u64>,
+    // proxy of the quote requests, instead of HTTPS_PROXY and HTTP_PROXY
+    pub proxy: Option<String>,
+    // requests sent to Yahoo per second, DEFAULT_REQUESTS_PER_SECOND without them
+    pub requests_per_second: Option<f64>,
+    // headers of the columns of CSV data files, as the broker exports them
+    pub csv_columns: CsvColumns,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            portfolio_file: String::new(),
+            currency: "EUR".to_string(),
+            allocation_targets: HashMap::new(),
+            number_format: NumberFormatConfig::default(),
+            max_quote_age_days: DEFAULT_MAX_QUOTE_AGE_DAYS,
+            expected_returns: HashMap::new(),
+            volatilities: HashMap::new(),
+            alerts: Vec::new(),
+            alert_webhook: None,
+            cache_ttl_seconds: None,
+            output: None,
+            save_resolved_names: false,
+            fetch_names: true,
+            inflation_rate: None,
+            request_timeout_seconds: None,
+            proxy: None,
+            requests_per_second: None,
+            csv_columns: CsvColumns::default(),
+        }
+    }
+}
+
+// A variable of the environment, an empty one counts as unset
+pub fn environment(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+// PORTFOLIO_RS_CONFIG, or else config.toml in the config directory
+// (~/.config/portfolio_rs on Linux). Without either, the YAML file of earlier
+// versions is read if there is one.
+pub fn config_path(env: &dyn Fn(&str) -> Option<String>) -> PathBuf {
+    if let Some(path) = env(CONFIG_VARIABLE) {
+        return PathBuf::from(path);
+    }
+    let path = confy::get_configuration_file_path("portfolio_rs", "config")
+        .map(|path| path.with_file_name("config.toml"))
+        .unwrap_or_else(|_| PathBuf::from("config.toml"));
+    match confy::get_configuration_file_path("portfolio", "config") {
+        Ok(legacy) if !path.exists() && legacy.exists() => legacy,
+        _ => path,
+    }
+}
+
+// The settings of the file, or the defaults if there is no file
+pub fn load(path: &Path) -> Result<Config, String> {
+    match std::fs::read_to_string(path) {
+        Ok(data) => parse(&data, path),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+        Err(e) => Err(format!("Error reading config file {}: {e}", path.display())),
+    }
+}
+
+// TOML, or YAML for a .yml or .yaml file. The error names the key whose value
+// is wrong, or the key that isn't a setting.
+pub fn parse(data: &str, path: &Path) -> Result<Config, String> {
+    let error = |key: String, message: String| {
+        // serde_yaml starts the message with the key already
+        let message = message.trim();
+        let message = message.strip_prefix(&format!("{key}: ")).unwrap_or(message);
+        match key.as_str() {
+            "" | "." => format!("Invalid config file {}: {message}", path.display()),
+            key => format!("Invalid config file {}: `{key}`: {message}", path.display()),
+        }
+    };
+    let yaml = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| matches!(extension, "yml" | "yaml"));
+    let config: Config = if yaml {
+        serde_path_to_error::deserialize(serde_yaml::Deserializer::from_str(data))
+            .map_err(|e| error(e.path().to_string(), e.inner().to_string()))?
+    } else {
+        let deserializer = toml_edit::de::Deserializer::parse(data)
+            .map_err(|e| error(String::new(), e.to_string()))?;
+        serde_path_to_error::deserialize(deserializer)
+            .map_err(|e| error(e.path().to_string(), e.inner().message().to_string()))?
+    };
+    if let Some(output) = config.output.as_deref() {
+        check_output(output).map_err(|message| error("output".to_string(), message))?;
+    }
+    if let Some(inflation) = config.inflation_rate {
+        check_inflation(inflation)
+            .map_err(|message| error("inflation_rate".to_string(), message))?;
+    }
+    if config.request_timeout_seconds == Some(0) {
+        return Err(error(
+            "request_timeout_seconds".to_string(),
+            "the timeout must be at least 1 second".to_string(),
+        ));
+    }
+    if let Some(proxy) = config.proxy.as_deref() {
+        proxy_for(proxy).map_err(|message| error("proxy".to_string(), message))?;
+    }
+    if config
+        .requests_per_second
+        .is_some_and(|rate| !(rate.is_finite() && rate > 0.0))
+    {
+        return Err(error(
+            "requests_per_second".to_string(),
+            "the rate must be above 0".to_string(),
+        ));
+    }
+    if let Some(style) = config.number_format.style.as_deref() {
+        check_style(style).map_err(|message| error("number_format.style".to_string(), message))?;
+    }
+    Ok(config)
+}
+
+fn check_output(output: &str) -> Result<(), String> {
+    if OUTPUT_MODES.contains(&output) {
+        Ok(())
+    } else {
+        Err(format!(
+            "{output} is not an output mode, expected {}",
+            OUTPUT_MODES.join(", ")
+        ))
+    }
+}
+
+fn check_style(style: &str) -> Result<(), String> {
+    style.parse::<NumberStyle>().map(|_| ())
+}
+
+impl Config {
+    // The PORTFOLIO_RS_ variables take the place of the settings of the file
+    pub fn with_environment(
+        mut self,
+        env: &dyn Fn(&str) -> Option<String>,
+    ) -> Result<Self, String> {
+        if let Some(file) = env(FILE_VARIABLE) {
+            self.portfolio_file = file;
+        }
+        if let Some(currency) = env(CURRENCY_VARIABLE) {
+            self.currency = currency;
+        }
+        if let Some(ttl) = env(CACHE_TTL_VARIABLE) {
+            let seconds = ttl.parse::<u64>().map_err(|_| {
+                format!("{CACHE_TTL_VARIABLE} must be a number of seconds, not {ttl}")
+            })?;
+            self.cache_ttl_seconds = Some(seconds);
+        }
+        if let Some(output) = env(OUTPUT_VARIABLE) {
+            check_output(&output).map_err(|e| format!("{OUTPUT_VARIABLE}: {e}"))?;
+            self.output = Some(output);
+        }
+        if let Some(style) = env(NUMBER_FORMAT_VARIABLE) {
+            check_style(&style).map_err(|e| format!("{NUMBER_FORMAT_VARIABLE}: {e}"))?;
+            self.number_format.style = Some(style);
+        }
+        // the HTTP client reads the proxy variables itself once no proxy is set
+        if PROXY_VARIABLES.iter().any(|name| env(name).is_some()) {
+            self.proxy = None;
+        }
+        Ok(self)
+    }
+
+    // The files of FILE or --file, or else the configured one
+    pub fn data_files(&self, files: Vec<String>) -> Vec<String> {
+        if files.is_empty() && !self.portfolio_file.is_empty() {
+            vec![self.portfolio_file.clone()]
+        } else {
+            files
+        }
+    }
+
+    pub fn cache_ttl(&self, flag: Option<u64>) -> u64 {
+        flag.or(self.cache_ttl_seconds)
+            .unwrap_or(DEFAULT_CACHE_TTL_SECONDS)
+    }
+
+    pub fn inflation(&self, flag: Option<f64>) -> Option<f64> {
+        flag.or(self.inflation_rate)
+    }
+
+    pub fn network(&self) -> NetworkSettings {
+        NetworkSettings {
+            timeout: std::time::Duration::from_secs(
+                self.request_timeout_seconds
+                    .unwrap_or(DEFAULT_REQUEST_TIMEOUT_SECONDS),
+            ),
+            proxy: self.proxy.clone(),
+            requests_per_second: self
+                .requests_per_second
+                .unwrap_or(DEFAULT_REQUESTS_PER_SECOND),
+        }
+    }
+
+    pub fn output(&self, flag: Option<String>) -> String {
+        flag.or_else(|| self.output.clone())
+            .unwrap_or_else(|| OUTPUT_MODES[0].to_string())
+    }
+
+    // The format of the configured currency, adjusted by the config file
+    // and then by the --number-format flag
+    pub fn number_format(&self, style: Option<&str>) -> NumberFormat {
+        let format = NumberFormat::for_currency(&self.currency).with_config(&self.number_format);
+        match style.and_then(|style| style.parse().ok()) {
+            Some(style) => format.with_style(style),
+            None => format,
+        }
+    }
+}
+
+// Written by config init, every setting is commented out and shows an example
+pub const TEMPLATE: &str = r#"# portfolio_rs settings. Every setting is optional and keeps its default if it
+# is left out. The environment variables in parentheses override a setting, and
+# the flags on the command line override both.
+
+# The data file used when no FILE or --file is given (PORTFOLIO_RS_FILE)
+# portfolio_file = "/home/me/portfolio.json"
+
+# The currency of the balances (PORTFOLIO_RS_CURRENCY)
+# currency = "EUR"
+
+# Output of balances: table, csv, json or beancount, like --output (PORTFOLIO_RS_OUTPUT)
+# output = "table"
+
+# Seconds serve answers with a cached quote, like --ttl (PORTFOLIO_RS_CACHE_TTL)
+# cache_ttl_seconds = 60
+
+# Seconds a quote request may take before it fails
+# request_timeout_seconds = 10
+
+# Proxy of the quote requests, HTTPS_PROXY or HTTP_PROXY override it
+# proxy = "http://proxy.example.com:3128"
+
+# Requests sent to Yahoo per second, all of them wait a while after Yahoo rejects one
+# requests_per_second = 5
+
+# Quotes older than this many trading days are marked as stale
+# max_quote_age_days = 3
+
+# Write the name of the ticker into positions without a Name, so later runs don't
+# look it up (JSON data files only, the names are also kept in names.json)
+# save_resolved_names = false
+
+# Show positions without a Name by their ticker instead of looking the name up,
+# one request less per position
+# fetch_names = true
+
+# Annual inflation in % to show the real returns of performance and the real yields
+# of interest next to the nominal ones, like --inflation
+# inflation_rate = 2.5
+
+# URL the price alerts are posted to as JSON
+# alert_webhook = "https://ntfy.sh/my-portfolio"
+
+# Separators, symbol and decimals of the numbers, style is like --number-format
+# (PORTFOLIO_RS_NUMBER_FORMAT)
+# [number_format]
+# style = "de"
+# precision = 2
+
+# Headers of the columns of CSV data files (brokerage exports), of any case.
+# cost is the purchase price per unit, the other columns are ignored
+# [csv_columns]
+# symbol = "Symbol"
+# quantity = "Quantity"
+# cost = "Cost Basis"
+# asset_class = "Asset Class"
+
+# Target weight in % per asset class for allocation and rebalance
+# [allocation_targets]
+# Stocks = 60.0
+# Bonds = 30.0
+# Cash = 10.0
+
+# Price alerts checked by the alerts command
+# [[alerts]]
+# ticker = "AAPL"
+# above = 250
+"#;
+
+// Writes the template, an existing file is only replaced with force
+pub fn init(path: &Path, force: bool) -> Result<(), String> {
+    if path.exists() && !force {
+        return Err(format!(
+            "{} already exists, use --force to replace it",
+            path.display()
+        ));
+    }
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Error creating directory {}: {e}", dir.display()))?;
+    }
+    std::fs::write(path, TEMPLATE)
+        .map_err(|e| format!("Error writing config file {}: {e}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(variables: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let variables: HashMap<String, String> = variables
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        move |name: &str| variables.get(name).cloned()
+    }
+
+    const CONFIG: &str = r#"
+        portfolio_file = "config.json"
+        currency = "USD"
+        output = "csv"
+        cache_ttl_seconds = 30
+        inflation_rate = 2.5
+        request_timeout_seconds = 3
+        proxy = "http://proxy.example.com:3128"
+        requests_per_second = 2.5
+        fetch_names = false
+
+        [number_format]
+        style = "fr"
+
+        [csv_columns]
+        symbol = "Ticker Symbol"
+        quantity = "Shares"
+    "#;
+
+    fn config(data: &str, variables: &[(&str, &str)]) -> Config {
+        parse(data, Path::new("config.toml"))
+            .unwrap()
+            .with_environment(&env(variables))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_precedence() {
+        let variables = [
+            (FILE_VARIABLE, "env.json"),
+            (CACHE_TTL_VARIABLE, "90"),
+            (OUTPUT_VARIABLE, "json"),
+            (NUMBER_FORMAT_VARIABLE, "ch"),
+        ];
+        let from_env = config(CONFIG, &variables);
+        let from_file = config(CONFIG, &[]);
+        let defaults = config("", &[]);
+
+        // the flag wins over the environment, the config file and the default
+        let flag = vec!["flag.json".to_string()];
+        assert_eq!(from_env.data_files(flag.clone()), flag);
+        assert_eq!(from_env.cache_ttl(Some(5)), 5);
+        assert_eq!(from_env.output(Some("table".to_string())), "table");
+        assert_eq!(from_env.number_format(Some("de")).decimal_separator, ',');
+        assert_eq!(
+            from_env.number_format(Some("de")).grouping_separator,
+            Some('.')
+        );
+
+        // the environment wins over the config file
+        assert_eq!(from_env.data_files(Vec::new()), ["env.json"]);
+        assert_eq!(from_env.cache_ttl(None), 90);
+        assert_eq!(from_env.output(None), "json");
+        assert_eq!(from_env.number_format(None).grouping_separator, Some('\''));
+
+        // the config file wins over the defaults
+        assert_eq!(from_file.data_files(Vec::new()), ["config.json"]);
+        assert_eq!(from_file.cache_ttl(None), 30);
+        assert_eq!(from_file.output(None), "csv");
+        assert_eq!(from_file.number_format(None).grouping_separator, Some(' '));
+        assert_eq!(from_file.currency, "USD");
+        assert_eq!(from_file.inflation(None), Some(2.5));
+        assert_eq!(
+            from_file.network(),
+            NetworkSettings {
+                timeout: std::time::Duration::from_secs(3),
+                proxy: Some("http://proxy.example.com:3128".to_string()),
+                requests_per_second: 2.5,
+            }
+        );
+        assert_eq!(from_file.inflation(Some(4.0)), Some(4.0));
+        assert!(!from_file.fetch_names);
+        assert_eq!(from_file.csv_columns.symbol, "Ticker Symbol");
+        assert_eq!(from_file.csv_columns.cost, "Cost Basis");
+
+        assert!(defaults.data_files(Vec::new()).is_empty());
+        assert_eq!(defaults.cache_ttl(None), DEFAULT_CACHE_TTL_SECONDS);
+        assert_eq!(defaults.output(None), "table");
+        assert_eq!(defaults.number_format(None).grouping_separator, Some(','));
+        assert_eq!(defaults.currency, "EUR");
+        assert_eq!(defaults.inflation(None), None);
+        assert_eq!(defaults.network(), NetworkSettings::default());
+        assert!(defaults.fetch_names);
+        assert_eq!(defaults.csv_columns, CsvColumns::default());
+
+        // a proxy of the environment wins over the one of the config file
+        let behind_proxy = config(CONFIG, &[("https_proxy", "http://env.example.com:8080")]);
+        assert_eq!(behind_proxy.network().proxy, None);
+    }
+
+    #[test]
+    fn test_invalid_settings() {
+        let error = |data: &str| parse(data, Path::new("config.toml")).unwrap_err();
+        assert_eq!(
+            error("max_quote_age_days = \"five\""),
+            "Invalid config file config.toml: `max_quote_age_days`: invalid type: string \"five\", expected i64"
+        );
+        assert!(error("[number_format]\nprecison = 2").starts_with(
+            "Invalid config file config.toml: `number_format.precison`: unknown field `precison`"
+        ));
+        assert_eq!(
+            error("request_timeout_seconds = 0"),
+            "Invalid config file config.toml: `request_timeout_seconds`: the timeout must be at least 1 second"
+        );
+        assert_eq!(
+            error("requests_per_second = 0"),
+            "Invalid config file config.toml: `requests_per_second`: the rate must be above 0"
+        );
+        assert!(error("proxy = \"not a url\"")
+            .starts_with("Invalid config file config.toml: `proxy`: Invalid proxy not a url"));
+        assert_eq!(
+            error("inflation_rate = -100"),
+            "Invalid config file config.toml: `inflation_rate`: The inflation rate must be above -100%, not -100%"
+        );
+        assert_eq!(
+            error("output = \"xml\""),
+            "Invalid config file config.toml: `output`: xml is not an output mode, expected table, csv, json, beancount"
+        );
+        assert!(
+            error("currency = ").starts_with("Invalid config file config.toml: TOML parse error")
+        );
+
+        let error = parse("max_quote_age_days: [1]", Path::new("config.yml")).unwrap_err();
+        assert!(
+            error.starts_with("Invalid config file config.yml: `max_quote_age_days`: invalid type")
+        );
+
+        let error = Config::default()
+            .with_environment(&env(&[(CACHE_TTL_VARIABLE, "soon")]))
+            .unwrap_err();
+        assert_eq!(
+            error,
+            "PORTFOLIO_RS_CACHE_TTL must be a number of seconds, not soon"
+        );
+    }
+
+    #[test]
+    fn test_legacy_yaml() {
+        let config = parse(
+            "portfolio_file: /home/me/portfolio.json\ncurrency: USD\nmax_quote_age_days: 5\n",
+            Path::new("config.yml"),
+        )
+        .unwrap();
+        assert_eq!(config.portfolio_file, "/home/me/portfolio.json");
+        assert_eq!(config.max_quote_age_days, 5);
+    }
+
+    #[test]
+    fn test_missing_config() {
+        let config = load(Path::new("tests/fixtures/no_config.toml")).unwrap();
+        assert_eq!(config.currency, "EUR");
+        assert_eq!(
+            config_path(&env(&[(CONFIG_VARIABLE, "/etc/portfolio.toml")])),
+            PathBuf::from("/etc/portfolio.toml")
+        );
+    }
+
+    #[test]
+    fn test_template() {
+        // as written, every setting keeps its default
+        let config = parse(TEMPLATE, Path::new("config.toml")).unwrap();
+        assert_eq!(config.output(None), "table");
+
+        // and every example is a valid setting
+        let uncommented: String = TEMPLATE
+            .lines()
+            .map(|line| match line.strip_prefix("# ") {
+                Some(setting) if setting.contains(" = ") || setting.starts_with('[') => setting,
+                _ => line,
+            })
+            .collect::<Vec<&str>>()
+            .join("\n");
+        let config = parse(&uncommented, Path::new("config.toml")).unwrap();
+        assert_eq!(config.portfolio_file, "/home/me/portfolio.json");
+        assert_eq!(config.cache_ttl(None), 60);
+        assert_eq!(config.allocation_targets["Stocks"], 60.0);
+        assert_eq!(config.alerts.len(), 1);
+
+        let path = std::env::temp_dir().join(format!("config_init_{}.toml", std::process::id()));
+        init(&path, false).unwrap();
+        assert!(init(&path, false)
+            .unwrap_err()
+            .ends_with("already exists, use --force to replace it"));
+        init(&path, true).unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+}
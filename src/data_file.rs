@@ -0,0 +1,612 @@
+use crate::import::from_csv;
+use crate::position::{to_string, CsvColumns};
+use serde_json::{Map, Number, Value};
+use std::path::Path;
+use std::str::FromStr;
+
+// The format of a portfolio data file. Everything is converted to JSON when
+// loading, so the positions are parsed the same way for all formats.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DataFormat {
+    Json,
+    Yaml,
+    Toml,
+    // a brokerage export, read with the csv_columns of the config, or a spreadsheet
+    Csv,
+}
+
+impl FromStr for DataFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_lowercase().as_str() {
+            "json" => Ok(DataFormat::Json),
+            "yaml" | "yml" => Ok(DataFormat::Yaml),
+            "toml" => Ok(DataFormat::Toml),
+            "csv" => Ok(DataFormat::Csv),
+            _ => Err(format!(
+                "{s} is not a data format, expected json, yaml, toml or csv"
+            )),
+        }
+    }
+}
+
+impl DataFormat {
+    // Detected from the extension, an encrypted portfolio.yaml.gpg is YAML.
+    // Files with any other extension are read as JSON.
+    pub fn from_path(path: &str) -> Self {
+        let path = path.strip_suffix(".gpg").unwrap_or(path);
+        Path::new(path)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(|extension| extension.parse().ok())
+            .unwrap_or(DataFormat::Json)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DataFormat::Json => "JSON",
+            DataFormat::Yaml => "YAML",
+            DataFormat::Toml => "TOML",
+            DataFormat::Csv => "CSV",
+        }
+    }
+}
+
+// Fails with a message that says what is wrong with the path, before anything is read
+pub fn check_data_file(path: &str) -> Result<(), String> {
+    let file = Path::new(path);
+    if !file.exists() {
+        return Err(format!(
+            "Portfolio file not found: {path}. Pass the path as FILE or with --file, or set portfolio_file in your config (see portfolio_rs config)."
+        ));
+    }
+    if file.is_dir() {
+        return Err(format!("{path} is a directory, expected a portfolio file"));
+    }
+    Ok(())
+}
+
+// The positions are saved to unencrypted JSON, YAML and TOML files, a CSV export
+// is only read
+pub fn check_writable(path: &str) -> Result<(), String> {
+    if path.ends_with(".gpg") {
+        return Err("Saving to encrypted files is not supported".to_string());
+    }
+    let format = DataFormat::from_path(path);
+    if format == DataFormat::Csv {
+        return Err(format!(
+            "Saving to {} files is not supported",
+            format.name()
+        ));
+    }
+    Ok(())
+}
+
+// Saves the JSON data (of to_data_string) in the format of the file. A TOML file
+// is updated in place, so its comments are kept. Comments in YAML can't be kept, a
+// YAML file with comments isn't overwritten.
+pub fn save_data(path: &str, data: &str) -> Result<(), String> {
+    check_writable(path)?;
+    let existing = match std::fs::read_to_string(path) {
+        Ok(existing) => Some(existing),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(format!("Error reading file {path}: {e}")),
+    };
+    let data = from_json_data(data, DataFormat::from_path(path), existing.as_deref())
+        .map_err(|e| format!("{path}: {e}"))?;
+    write_atomic(path, &data)
+}
+
+// The JSON data in the format, as a change of the existing text of the file
+pub fn from_json_data(
+    data: &str,
+    format: DataFormat,
+    existing: Option<&str>,
+) -> Result<String, String> {
+    match format {
+        // a file read with --format, saving it as JSON would lose its format
+        DataFormat::Json
+            if existing.is_some_and(|text| serde_json::from_str::<Value>(text).is_err()) =>
+        {
+            Err("the file is not JSON, save it with the extension of its format".to_string())
+        }
+        DataFormat::Json => Ok(data.to_string()),
+        DataFormat::Csv => Err("Saving to CSV files is not supported".to_string()),
+        DataFormat::Yaml if existing.is_some_and(has_yaml_comments) => Err(
+            "the comments of the YAML file would be lost, it is not saved (remove them or use TOML, which keeps them)"
+                .to_string(),
+        ),
+        DataFormat::Yaml => {
+            let value: Value = serde_json::from_str(data)
+                .map_err(|e| format!("Failed to convert the data: {e}"))?;
+            serde_yaml::to_string(&value).map_err(|e| format!("Failed to convert the data: {e}"))
+        }
+        DataFormat::Toml => json_to_toml(data, existing),
+    }
+}
+
+// A # outside of quotes that starts the line or follows a space
+fn has_yaml_comments(data: &str) -> bool {
+    data.lines().any(|line| {
+        let mut quote: Option<char> = None;
+        let mut previous = ' ';
+        for c in line.chars() {
+            match (quote, c) {
+                (Some(open), c) if c == open => quote = None,
+                (Some(_), _) => {}
+                (None, '\'' | '"') => quote = Some(c),
+                (None, '#') if previous.is_whitespace() => return true,
+                (None, _) => {}
+            }
+            previous = c;
+        }
+        false
+    })
+}
+
+// The data as TOML, written into the existing document: its values, tables and
+// comments stay where they are, the positions are matched by their Name
+fn json_to_toml(data: &str, existing: Option<&str>) -> Result<String, String> {
+    let value: Value =
+        serde_json::from_str(data).map_err(|e| format!("Failed to convert the data: {e}"))?;
+    let mut document = existing
+        .map(|text| text.parse::<toml_edit::DocumentMut>())
+        .transpose()
+        .map_err(|e| format!("The TOML data is not valid: {e}"))?
+        .unwrap_or_default();
+    let data = match value {
+        Value::Array(positions) => {
+            Map::from_iter([("Positions".to_string(), Value::Array(positions))])
+        }
+        Value::Object(data) => data,
+        _ => return Err("The data is neither a list of positions nor an object".to_string()),
+    };
+
+    let root = document.as_table_mut();
+    root.retain(|key, _| data.contains_key(key));
+    for (key, value) in &data {
+        match (key.as_str(), value) {
+            ("Positions", Value::Array(positions)) => {
+                let item = toml_positions(positions, root.get(key));
+                root.insert(key, item);
+            }
+            (_, Value::Object(table)) => {
+                let mut updated = match root.get(key) {
+                    Some(toml_edit::Item::Table(old)) => old.clone(),
+                    _ => toml_edit::Table::new(),
+                };
+                update_toml_table(&mut updated, table);
+                root.insert(key, toml_edit::Item::Table(updated));
+            }
+            _ => update_toml_item(root, key, value),
+        }
+    }
+    Ok(document.to_string())
+}
+
+// The positions as [[Positions]] tables. The existing table of the same position
+// is updated so its comments and the order of its keys are kept: the one with the
+// same Name and Ticker, else the same Ticker (it was renamed), else the same Name,
+// else the table at the same place.
+fn toml_positions(positions: &[Value], old: Option<&toml_edit::Item>) -> toml_edit::Item {
+    // TOML can't write an empty array of tables, but it can write an empty array
+    if positions.is_empty() {
+        return toml_edit::value(toml_edit::Array::new());
+    }
+    let mut old_tables: Vec<Option<&toml_edit::Table>> = match old {
+        Some(toml_edit::Item::ArrayOfTables(tables)) => tables.iter().map(Some).collect(),
+        _ => Vec::new(),
+    };
+    let field = |table: &toml_edit::Table, key: &str| {
+        table
+            .get(key)
+            .and_then(|value| value.as_str())
+            .map(str::to_string)
+    };
+
+    let mut tables = toml_edit::ArrayOfTables::new();
+    for (index, position) in positions.iter().enumerate() {
+        let Value::Object(position) = position else {
+            continue;
+        };
+        let given = |key: &str| {
+            position
+                .get(key)
+                .and_then(Value::as_str)
+                .map(str::to_string)
+        };
+        let (name, ticker) = (given("Name"), given("Ticker"));
+        let same: [&dyn Fn(&toml_edit::Table) -> bool; 3] = [
+            &|old| field(old, "Name") == name && field(old, "Ticker") == ticker,
+            &|old| ticker.is_some() && field(old, "Ticker") == ticker,
+            &|old| name.is_some() && field(old, "Name") == name,
+        ];
+        let found = same
+            .iter()
+            .find_map(|same| old_tables.iter().position(|old| old.is_some_and(same)));
+        let reused = found
+            .or((index < old_tables.len()).then_some(index))
+            .and_then(|found| old_tables[found].take());
+        let mut table = reused.cloned().unwrap_or_default();
+        update_toml_table(&mut table, position);
+        tables.push(table);
+    }
+    toml_edit::Item::ArrayOfTables(tables)
+}
+
+fn update_toml_table(table: &mut toml_edit::Table, data: &Map<String, Value>) {
+    table.retain(|key, _| data.get(key).is_some_and(|value| !value.is_null()));
+    for (key, value) in data {
+        update_toml_item(table, key, value);
+    }
+}
+
+// Sets the key to the value, unless it already has that value. A changed value
+// keeps the comment after it.
+fn update_toml_item(table: &mut toml_edit::Table, key: &str, value: &Value) {
+    let old = table.get(key).and_then(|item| item.as_value());
+    if old.is_some_and(|old| same_value(&toml_value(old), value)) {
+        return;
+    }
+    let Some(mut new) = to_toml_value(value, old) else {
+        table.remove(key);
+        return;
+    };
+    if let Some(old) = old {
+        *new.decor_mut() = old.decor().clone();
+    }
+    table.insert(key, toml_edit::Item::Value(new));
+}
+
+// Numbers are the same whether they are written as 200 or 200.0
+fn same_value(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64() == b.as_f64(),
+        (Value::Array(a), Value::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same_value(a, b))
+        }
+        (Value::Object(a), Value::Object(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .all(|(key, a)| b.get(key).is_some_and(|b| same_value(a, b)))
+        }
+        (a, b) => a == b,
+    }
+}
+
+// A date is written as a TOML date like in the data files, unless the old value
+// was a string. Whole numbers are integers, unless the old value was a float.
+fn to_toml_value(value: &Value, old: Option<&toml_edit::Value>) -> Option<toml_edit::Value> {
+    Some(match value {
+        Value::Null => return None,
+        Value::Bool(b) => (*b).into(),
+        Value::String(s) => {
+            let is_date = chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d").is_ok();
+            match s.parse::<toml_edit::Datetime>() {
+                Ok(date) if is_date && !old.is_some_and(|old| old.is_str()) => date.into(),
+                _ => s.as_str().into(),
+            }
+        }
+        Value::Number(number) => match (number.as_i64(), number.as_f64()) {
+            (Some(i), _) => i.into(),
+            (None, Some(f))
+                if f.fract() == 0.0 && f.abs() < 1e15 && !old.is_some_and(|old| old.is_float()) =>
+            {
+                (f as i64).into()
+            }
+            (_, f) => f.unwrap_or_default().into(),
+        },
+        Value::Array(values) => {
+            let old = old.and_then(|old| old.as_array());
+            let mut array = toml_edit::Array::new();
+            for (index, value) in values.iter().enumerate() {
+                if let Some(value) = to_toml_value(value, old.and_then(|old| old.get(index))) {
+                    array.push(value);
+                }
+            }
+            array.into()
+        }
+        Value::Object(data) => {
+            let mut table = toml_edit::InlineTable::new();
+            for (key, value) in data {
+                if let Some(value) = to_toml_value(value, None) {
+                    table.insert(key, value);
+                }
+            }
+            table.into()
+        }
+    })
+}
+
+// Writes the data to a file next to the path and renames it, so the data file is
+// never left half written
+pub fn write_atomic(path: &str, data: &str) -> Result<(), String> {
+    let file = Path::new(path);
+    let name = file
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| format!("{path} is not a file name"))?;
+    let temporary = file.with_file_name(format!(".{name}.tmp"));
+    std::fs::write(&temporary, data)
+        .and_then(|()| std::fs::rename(&temporary, file))
+        .map_err(|e| {
+            let _ = std::fs::remove_file(&temporary);
+            format!("Failed to write to file {path}: {e}")
+        })
+}
+
+// The data as JSON text, which is what from_string and total_investment read.
+// The columns are those of a CSV export.
+pub fn to_json_data(
+    data: &str,
+    format: DataFormat,
+    columns: &CsvColumns,
+) -> Result<String, String> {
+    let value = match format {
+        DataFormat::Json => return Ok(data.to_string()),
+        DataFormat::Csv => return to_string(&from_csv(data.as_bytes(), columns)?),
+        DataFormat::Yaml => serde_yaml::from_str::<Value>(data)
+            .map_err(|e| format!("The YAML data is not valid: {e}"))?,
+        DataFormat::Toml => toml_to_json(data)?,
+    };
+    serde_json::to_string(&value).map_err(|e| format!("Failed to convert the data: {e}"))
+}
+
+// A TOML document is always a table, so the positions are listed as [[Positions]].
+// Without other keys, like a TotalInvestment, this is the plain list of positions.
+fn toml_to_json(data: &str) -> Result<Value, String> {
+    let document = data
+        .parse::<toml_edit::DocumentMut>()
+        .map_err(|e| format!("The TOML data is not valid: {e}"))?;
+    let mut table = toml_table(document.as_table().iter());
+    if table.len() == 1 {
+        if let Some(positions) = table.remove("Positions") {
+            return Ok(positions);
+        }
+    }
+    Ok(Value::Object(table))
+}
+
+fn toml_table<'a>(
+    items: impl Iterator<Item = (&'a str, &'a toml_edit::Item)>,
+) -> Map<String, Value> {
+    items
+        .filter_map(|(key, item)| Some((key.to_string(), toml_item(item)?)))
+        .collect()
+}
+
+fn toml_item(item: &toml_edit::Item) -> Option<Value> {
+    match item {
+        toml_edit::Item::None => None,
+        toml_edit::Item::Value(value) => Some(toml_value(value)),
+        toml_edit::Item::Table(table) => Some(Value::Object(toml_table(table.iter()))),
+        toml_edit::Item::ArrayOfTables(tables) => Some(Value::Array(
+            tables
+                .iter()
+                .map(|table| Value::Object(toml_table(table.iter())))
+                .collect(),
+        )),
+    }
+}
+
+fn toml_value(value: &toml_edit::Value) -> Value {
+    match value {
+        toml_edit::Value::String(s) => Value::String(s.value().clone()),
+        toml_edit::Value::Integer(i) => Value::Number((*i.value()).into()),
+        toml_edit::Value::Float(f) => Number::from_f64(*f.value())
+            .map(Value::Number)
+            .unwrap_or(Value::Null),
+        toml_edit::Value::Boolean(b) => Value::Bool(*b.value()),
+        // dates like NextInterestPayment = 2025-01-31 are read like the strings in JSON
+        toml_edit::Value::Datetime(datetime) => Value::String(datetime.value().to_string()),
+        toml_edit::Value::Array(array) => Value::Array(array.iter().map(toml_value).collect()),
+        toml_edit::Value::InlineTable(table) => Value::Object(
+            table
+                .iter()
+                .map(|(key, value)| (key.to_string(), toml_value(value)))
+                .collect(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::{from_string, total_investment};
+    use rust_decimal::dec;
+
+    fn load(path: &str) -> String {
+        let data = std::fs::read_to_string(path).unwrap();
+        to_json_data(&data, DataFormat::from_path(path), &CsvColumns::default()).unwrap()
+    }
+
+    #[test]
+    fn test_from_path() {
+        assert_eq!(DataFormat::from_path("portfolio.json"), DataFormat::Json);
+        assert_eq!(
+            DataFormat::from_path("dir.yaml/portfolio"),
+            DataFormat::Json
+        );
+        assert_eq!(DataFormat::from_path("portfolio.YML"), DataFormat::Yaml);
+        assert_eq!(
+            DataFormat::from_path("portfolio.toml.gpg"),
+            DataFormat::Toml
+        );
+        assert_eq!(DataFormat::from_path("export.csv"), DataFormat::Csv);
+        assert_eq!("xml".parse::<DataFormat>().ok(), None);
+    }
+
+    #[test]
+    fn test_yaml_and_toml_match_json() {
+        let json = from_string(&load("tests/fixtures/portfolio.json"));
+        assert_eq!(json.len(), 3);
+        for path in [
+            "tests/fixtures/portfolio.yaml",
+            "tests/fixtures/portfolio.toml",
+        ] {
+            let data = load(path);
+            assert_eq!(
+                to_string(&from_string(&data)).unwrap(),
+                to_string(&json).unwrap(),
+                "{path}"
+            );
+            assert_eq!(total_investment(&data), None);
+        }
+    }
+
+    #[test]
+    fn test_toml_with_total_investment() {
+        let data = to_json_data(
+            r#"
+            TotalInvestment = 10000
+
+            [[Positions]]
+            Name = "S&P 500"
+            Ticker = "SPY"
+            AssetClass = "Stocks"
+            PercentOf = 60
+            "#,
+            DataFormat::Toml,
+            &CsvColumns::default(),
+        )
+        .unwrap();
+        assert_eq!(total_investment(&data), Some(dec!(10000)));
+        assert_eq!(from_string(&data)[0].get_percent_of(), Some(dec!(60)));
+    }
+
+    #[test]
+    fn test_invalid_data() {
+        assert!(
+            to_json_data("Name = ", DataFormat::Toml, &CsvColumns::default())
+                .unwrap_err()
+                .starts_with("The TOML data is not valid")
+        );
+        assert!(
+            to_json_data("- [", DataFormat::Yaml, &CsvColumns::default())
+                .unwrap_err()
+                .starts_with("The YAML data is not valid")
+        );
+    }
+
+    #[test]
+    fn test_example_data_formats() {
+        let json = from_string(&load("example_data.json"));
+        assert_eq!(json.len(), 6);
+        for path in ["example_data.yaml", "example_data.toml"] {
+            assert_eq!(
+                to_string(&from_string(&load(path))).unwrap(),
+                to_string(&json).unwrap(),
+                "{path}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_save_toml_keeps_comments() {
+        let existing = std::fs::read_to_string("example_data.toml").unwrap();
+        let data = load("example_data.toml");
+        // unchanged, the file is written as it was
+        assert_eq!(
+            from_json_data(&data, DataFormat::Toml, Some(&existing)).unwrap(),
+            existing
+        );
+
+        let mut document: Value = serde_json::from_str(&data).unwrap();
+        document[5]["Amount"] = serde_json::json!(250);
+        document[0]["Name"] = serde_json::json!("S&P 500");
+        let saved = from_json_data(
+            &serde_json::to_string(&document).unwrap(),
+            DataFormat::Toml,
+            Some(&existing),
+        )
+        .unwrap();
+        assert!(saved.starts_with("# The example data as TOML"));
+        assert!(saved.contains("# interest is credited every 30 days"));
+        assert!(saved.contains("InterestRate = 2.5 # in % per year"));
+        assert!(saved.contains("Amount = 250\n"));
+        assert!(saved
+            .contains("Ticker = \"SPY\"\nAssetClass = \"Stocks\"\nAmount = 2\nName = \"S&P 500\""));
+        assert!(saved.contains("\n\n[[Positions]]\nTicker = \"GLD\""));
+        // a date stays a TOML date
+        assert!(saved.contains("NextInterestPayment = 2025-01-31"));
+        assert_eq!(
+            from_string(&to_json_data(&saved, DataFormat::Toml, &CsvColumns::default()).unwrap())
+                [5]
+            .get_amount(),
+            dec!(250)
+        );
+    }
+
+    #[test]
+    fn test_save_new_files() {
+        let data = load("example_data.json");
+        for format in [DataFormat::Yaml, DataFormat::Toml] {
+            let saved = from_json_data(&data, format, None).unwrap();
+            let loaded = to_json_data(&saved, format, &CsvColumns::default()).unwrap();
+            assert_eq!(
+                to_string(&from_string(&loaded)).unwrap(),
+                to_string(&from_string(&data)).unwrap()
+            );
+        }
+        let toml = from_json_data(&data, DataFormat::Toml, None).unwrap();
+        assert!(toml.starts_with("[[Positions]]\nTicker = \"SPY\""));
+        assert_eq!(
+            from_json_data("[]", DataFormat::Toml, None).unwrap(),
+            "Positions = []\n"
+        );
+    }
+
+    #[test]
+    fn test_save_refused() {
+        let data = load("example_data.json");
+        assert_eq!(
+            from_json_data(
+                &data,
+                DataFormat::Yaml,
+                Some("# my positions\n- Ticker: SPY\n")
+            )
+            .unwrap_err(),
+            "the comments of the YAML file would be lost, it is not saved \
+            (remove them or use TOML, which keeps them)"
+        );
+        // a hash in a value is no comment
+        assert!(from_json_data(&data, DataFormat::Yaml, Some("- Name: \"Fund #1\"\n")).is_ok());
+        assert!(from_json_data(&data, DataFormat::Json, Some("Positions = []")).is_err());
+        assert_eq!(
+            check_writable("export.csv").unwrap_err(),
+            "Saving to CSV files is not supported"
+        );
+        assert!(check_writable("portfolio.yml").is_ok());
+    }
+
+    #[test]
+    fn test_write_atomic() {
+        let path = std::env::temp_dir().join(format!("write_atomic_{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        write_atomic(path, "[]").unwrap();
+        write_atomic(path, "[{}]").unwrap();
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "[{}]");
+        let temporary = Path::new(path).with_file_name(format!(
+            ".{}.tmp",
+            Path::new(path).file_name().unwrap().to_str().unwrap()
+        ));
+        assert!(!temporary.exists());
+        std::fs::remove_file(path).unwrap();
+        assert!(write_atomic("tests/fixtures/missing/portfolio.json", "[]")
+            .unwrap_err()
+            .starts_with("Failed to write to file tests/fixtures/missing/portfolio.json"));
+    }
+
+    #[test]
+    fn test_check_data_file() {
+        assert!(check_data_file("tests/fixtures/portfolio.json").is_ok());
+        assert!(check_data_file("tests/fixtures/missing.json")
+            .unwrap_err()
+            .starts_with("Portfolio file not found: tests/fixtures/missing.json."));
+        assert_eq!(
+            check_data_file("tests/fixtures").unwrap_err(),
+            "tests/fixtures is a directory, expected a portfolio file"
+        );
+    }
+}
@@ -0,0 +1,244 @@
+use crate::format::NumberFormat;
+use crate::position::PortfolioPosition;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+// A position that is only part of one of the snapshots
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct DiffEntry {
+    pub name: String,
+    pub ticker: Option<String>,
+    pub amount: Decimal,
+    pub balance: Decimal,
+}
+
+// A position of both snapshots whose amount or balance changed
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct PositionChange {
+    pub name: String,
+    pub ticker: Option<String>,
+    pub old_amount: Decimal,
+    pub new_amount: Decimal,
+    pub amount_change: Decimal,
+    pub old_balance: Decimal,
+    pub new_balance: Decimal,
+    pub balance_change: Decimal,
+}
+
+// What changed between two snapshots of the portfolio, in the order of the snapshots
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+pub struct PortfolioDiff {
+    pub added: Vec<DiffEntry>,
+    pub removed: Vec<DiffEntry>,
+    pub changed: Vec<PositionChange>,
+}
+
+impl PortfolioDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|e| format!("Failed to serialize diff: {e}"))
+    }
+}
+
+fn entry(position: &PortfolioPosition) -> DiffEntry {
+    DiffEntry {
+        name: position.get_name().to_string(),
+        ticker: position.get_ticker().map(|ticker| ticker.to_string()),
+        amount: position.get_amount(),
+        balance: position.get_balance(),
+    }
+}
+
+// Positions are matched by ticker first, the remaining ones by name, so a
+// renamed position with the same ticker counts as changed, not as removed and added
+pub fn diff(old: &[PortfolioPosition], new: &[PortfolioPosition]) -> PortfolioDiff {
+    let mut matched_old = vec![false; old.len()];
+    let mut matches: Vec<Option<usize>> = vec![None; new.len()];
+
+    let keys: [fn(&PortfolioPosition) -> Option<&str>; 2] = [
+        |position| position.get_ticker(),
+        |position| Some(position.get_name()),
+    ];
+    for key in keys {
+        for (new_index, position) in new.iter().enumerate() {
+            if matches[new_index].is_some() {
+                continue;
+            }
+            let Some(new_key) = key(position) else {
+                continue;
+            };
+            let old_index = old
+                .iter()
+                .enumerate()
+                .position(|(i, old)| !matched_old[i] && key(old) == Some(new_key));
+            if let Some(old_index) = old_index {
+                matched_old[old_index] = true;
+                matches[new_index] = Some(old_index);
+            }
+        }
+    }
+
+    let mut result = PortfolioDiff::default();
+    for (position, old_index) in new.iter().zip(matches) {
+        let Some(old_index) = old_index else {
+            result.added.push(entry(position));
+            continue;
+        };
+        let previous = &old[old_index];
+        let amount_change = position.get_amount() - previous.get_amount();
+        let balance_change = position.get_balance() - previous.get_balance();
+        if !amount_change.is_zero() || !balance_change.is_zero() {
+            result.changed.push(PositionChange {
+                name: position.get_name().to_string(),
+                ticker: position.get_ticker().map(|ticker| ticker.to_string()),
+                old_amount: previous.get_amount(),
+                new_amount: position.get_amount(),
+                amount_change,
+                old_balance: previous.get_balance(),
+                new_balance: position.get_balance(),
+                balance_change,
+            });
+        }
+    }
+    result.removed = old
+        .iter()
+        .zip(matched_old)
+        .filter(|(_, matched)| !matched)
+        .map(|(position, _)| entry(position))
+        .collect();
+    result
+}
+
+pub fn print_diff(diff: &PortfolioDiff, format: &NumberFormat) {
+    if diff.is_empty() {
+        println!("No changes between the snapshots.");
+        return;
+    }
+
+    println!(
+        "{0: >8} | {1: >26} | {2: >12} | {3: >12}",
+        "Change", "Name", "Amount", "Balance"
+    );
+    println!("=====================================================================");
+    for added in &diff.added {
+        println!(
+            "{0: >8} | {1: >26} | {2: >12} | {3: >12}",
+            "added",
+            added.name,
+            format.signed_number(added.amount),
+            format.signed_money(added.balance)
+        );
+    }
+    for removed in &diff.removed {
+        println!(
+            "{0: >8} | {1: >26} | {2: >12} | {3: >12}",
+            "removed",
+            removed.name,
+            format.signed_number(-removed.amount),
+            format.signed_money(-removed.balance)
+        );
+    }
+    for changed in &diff.changed {
+        println!(
+            "{0: >8} | {1: >26} | {2: >12} | {3: >12}",
+            "changed",
+            changed.name,
+            format.signed_number(changed.amount_change),
+            format.signed_money(changed.balance_change)
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::priced_at;
+    use rust_decimal::dec;
+
+    fn snapshot(data: &str) -> Vec<PortfolioPosition> {
+        priced_at(data, |_| 10.0)
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = snapshot(
+            r#"[
+                {"Name": "Apple", "Ticker": "AAPL", "AssetClass": "Stocks", "Amount": 10},
+                {"Name": "Savings", "AssetClass": "Cash", "Amount": 500},
+                {"Name": "Bond Fund", "Ticker": "BND", "AssetClass": "Bonds", "Amount": 5}
+            ]"#,
+        );
+        let new = snapshot(
+            r#"[
+                {"Name": "Apple Inc.", "Ticker": "AAPL", "AssetClass": "Stocks", "Amount": 12},
+                {"Name": "Savings", "AssetClass": "Cash", "Amount": 500},
+                {"Name": "World ETF", "Ticker": "EUNL.DE", "AssetClass": "Stocks", "Amount": 3}
+            ]"#,
+        );
+
+        let diff = diff(&old, &new);
+        assert_eq!(
+            diff.added,
+            [DiffEntry {
+                name: "World ETF".to_string(),
+                ticker: Some("EUNL.DE".to_string()),
+                amount: dec!(3),
+                balance: dec!(30),
+            }]
+        );
+        assert_eq!(
+            diff.removed,
+            [DiffEntry {
+                name: "Bond Fund".to_string(),
+                ticker: Some("BND".to_string()),
+                amount: dec!(5),
+                balance: dec!(50),
+            }]
+        );
+        // matched by the ticker despite the new name, the unchanged savings are left out
+        assert_eq!(
+            diff.changed,
+            [PositionChange {
+                name: "Apple Inc.".to_string(),
+                ticker: Some("AAPL".to_string()),
+                old_amount: dec!(10),
+                new_amount: dec!(12),
+                amount_change: dec!(2),
+                old_balance: dec!(100),
+                new_balance: dec!(120),
+                balance_change: dec!(20),
+            }]
+        );
+
+        let parsed: PortfolioDiff = serde_json::from_str(&diff.to_json().unwrap()).unwrap();
+        assert_eq!(parsed, diff);
+    }
+
+    #[test]
+    fn test_diff_prefers_ticker_over_name() {
+        // the name moved to another ticker, each ticker keeps its own position
+        let old = snapshot(
+            r#"[
+                {"Name": "World", "Ticker": "EUNL.DE", "AssetClass": "Stocks", "Amount": 1},
+                {"Name": "World (acc)", "Ticker": "IWDA.AS", "AssetClass": "Stocks", "Amount": 2}
+            ]"#,
+        );
+        let new = snapshot(
+            r#"[
+                {"Name": "World (acc)", "Ticker": "EUNL.DE", "AssetClass": "Stocks", "Amount": 1},
+                {"Name": "World", "Ticker": "IWDA.AS", "AssetClass": "Stocks", "Amount": 4}
+            ]"#,
+        );
+
+        let diff = diff(&old, &new);
+        assert!(diff.added.is_empty());
+        assert!(diff.removed.is_empty());
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].ticker.as_deref(), Some("IWDA.AS"));
+        assert_eq!(diff.changed[0].amount_change, dec!(2));
+        assert!(super::diff(&new, &new).is_empty());
+    }
+}
@@ -0,0 +1,764 @@
+use crate::data_file::{check_writable, save_data, to_json_data, DataFormat};
+use crate::position::{CsvColumns, PortfolioPosition};
+use crate::provider::{is_crypto_ticker, PriceProvider};
+use crate::search::{render_search_results, search, DEFAULT_SEARCH_LIMIT};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde_json::{Map, Value};
+use std::io::{BufRead, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+// The add, edit and remove commands change the data file as JSON (whatever its
+// format), so the other positions and the keys this version doesn't know are
+// written back as they were
+
+// The amount, the interest settings and the notes, the values that are left out
+// are kept. An empty institution or notes removes them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PositionChanges {
+    pub amount: Option<Decimal>,
+    pub interest_rate: Option<Decimal>,
+    pub payment_frequency_days: Option<u32>,
+    pub next_interest_payment: Option<NaiveDate>,
+    pub institution: Option<String>,
+    pub notes: Option<String>,
+}
+
+impl PositionChanges {
+    pub fn is_empty(&self) -> bool {
+        *self == PositionChanges::default()
+    }
+
+    fn apply(&self, position: &mut Map<String, Value>) -> Result<(), String> {
+        // whole numbers are written like 12 rather than 12.0, as in a hand written file
+        let number = |value: Decimal| {
+            serde_json::from_str::<Value>(&value.normalize().to_string())
+                .map_err(|e| format!("Failed to serialize data: {e}"))
+        };
+        if let Some(amount) = self.amount {
+            // the amount may have been read from one of its aliases, e.g. Shares
+            for alias in ["quantity", "Quantity", "shares", "Shares"] {
+                position.remove(alias);
+            }
+            position.insert("Amount".to_string(), number(amount)?);
+        }
+        if let Some(rate) = self.interest_rate {
+            position.insert("InterestRate".to_string(), number(rate)?);
+        }
+        if let Some(days) = self.payment_frequency_days {
+            position.insert("PaymentFrequencyDays".to_string(), days.into());
+        }
+        if let Some(date) = self.next_interest_payment {
+            position.insert(
+                "NextInterestPayment".to_string(),
+                date.format("%Y-%m-%d").to_string().into(),
+            );
+        }
+        for (key, text) in [("Institution", &self.institution), ("Notes", &self.notes)] {
+            match text.as_deref() {
+                Some("") => {
+                    position.remove(key);
+                }
+                Some(text) => {
+                    position.insert(key.to_string(), text.into());
+                }
+                None => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+// A position for the add command, it needs a name or a ticker
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NewPosition {
+    pub name: Option<String>,
+    pub ticker: Option<String>,
+    pub asset_class: String,
+    pub changes: PositionChanges,
+}
+
+impl NewPosition {
+    fn to_value(&self) -> Result<Value, String> {
+        if self.name.is_none() && self.ticker.is_none() {
+            return Err("A position needs a name or a ticker".to_string());
+        }
+        if self.asset_class.trim().is_empty() {
+            return Err("A position needs an asset class".to_string());
+        }
+        let mut position = Map::new();
+        if let Some(name) = &self.name {
+            position.insert("Name".to_string(), name.clone().into());
+        }
+        if let Some(ticker) = &self.ticker {
+            position.insert("Ticker".to_string(), ticker.clone().into());
+        }
+        position.insert("AssetClass".to_string(), self.asset_class.clone().into());
+        position.insert("Amount".to_string(), 0.into());
+        self.changes.apply(&mut position)?;
+        Ok(Value::Object(position))
+    }
+}
+
+// The data file as JSON. A file that doesn't exist yet is an empty list, so the
+// first position can be added to a new file.
+pub fn read_document(path: &str, create: bool) -> Result<Value, String> {
+    check_writable(path)?;
+    let file = Path::new(path);
+    if create && !file.exists() {
+        return Ok(Value::Array(Vec::new()));
+    }
+    crate::data_file::check_data_file(path)?;
+    let data =
+        std::fs::read_to_string(file).map_err(|e| format!("Error reading file {path}: {e}"))?;
+    let data = match DataFormat::from_path(path) {
+        DataFormat::Json => data,
+        format => to_json_data(&data, format, &CsvColumns::default())
+            .map_err(|e| format!("{path}: {e}"))?,
+    };
+    serde_json::from_str(&data).map_err(|e| format!("{path}: the JSON data is not valid: {e}"))
+}
+
+pub fn save_document(path: &str, document: &Value) -> Result<(), String> {
+    let data = serde_json::to_string_pretty(document)
+        .map_err(|e| format!("Failed to serialize data: {e}"))?;
+    save_data(path, &data)
+}
+
+// The plain list or the Positions of the object form
+fn positions_mut(document: &mut Value) -> Result<&mut Vec<Value>, String> {
+    match document {
+        Value::Array(positions) => Ok(positions),
+        Value::Object(data) => match data.get_mut("Positions") {
+            Some(Value::Array(positions)) => Ok(positions),
+            _ => Err("The data file has no list of Positions".to_string()),
+        },
+        _ => Err("The data file is neither a list of positions nor an object".to_string()),
+    }
+}
+
+fn to_position(value: &Value) -> Result<PortfolioPosition, String> {
+    serde_json::from_value(value.clone()).map_err(|e| format!("Invalid position: {e}"))
+}
+
+// The index of the position with the name or ticker (in any case)
+fn find(positions: &[Value], name: &str) -> Result<usize, String> {
+    let found: Vec<usize> = positions
+        .iter()
+        .enumerate()
+        .filter(|(_, value)| to_position(value).is_ok_and(|position| position.is_named(name)))
+        .map(|(index, _)| index)
+        .collect();
+    match found.as_slice() {
+        [index] => Ok(*index),
+        [] => Err(format!("There is no position named {name}")),
+        _ => Err(format!(
+            "{} positions are named {name}, edit the data file to change one of them",
+            found.len()
+        )),
+    }
+}
+
+pub fn get(document: &mut Value, name: &str) -> Result<PortfolioPosition, String> {
+    let positions = positions_mut(document)?;
+    let index = find(positions, name)?;
+    to_position(&positions[index])
+}
+
+pub fn add(document: &mut Value, new: &NewPosition) -> Result<PortfolioPosition, String> {
+    let value = new.to_value()?;
+    let position = to_position(&value)?;
+    positions_mut(document)?.push(value);
+    Ok(position)
+}
+
+pub fn edit(
+    document: &mut Value,
+    name: &str,
+    changes: &PositionChanges,
+) -> Result<PortfolioPosition, String> {
+    let positions = positions_mut(document)?;
+    let index = find(positions, name)?;
+    let mut value = positions[index].clone();
+    let Value::Object(position) = &mut value else {
+        return Err(format!("{name} is not a position"));
+    };
+    changes.apply(position)?;
+    let position = to_position(&value)?;
+    positions[index] = value;
+    Ok(position)
+}
+
+pub fn remove(document: &mut Value, name: &str) -> Result<PortfolioPosition, String> {
+    let positions = positions_mut(document)?;
+    let index = find(positions, name)?;
+    let position = to_position(&positions[index])?;
+    positions.remove(index);
+    Ok(position)
+}
+
+// Writes the name of its ticker into every position without a Name, the names that
+// were added. A ticker without a name is left as it is, it is shown as the ticker.
+pub async fn add_missing_names(
+    document: &mut Value,
+    provider: &impl PriceProvider,
+) -> Result<Vec<String>, String> {
+    let mut added = Vec::new();
+    for value in positions_mut(document)? {
+        let Ok(position) = to_position(value) else {
+            continue;
+        };
+        let (Value::Object(object), Some(ticker)) = (&mut *value, position.get_ticker()) else {
+            continue;
+        };
+        if object.contains_key("Name") {
+            continue;
+        }
+        match provider.get_name(ticker).await {
+            Ok(name) => {
+                object.shift_insert(0, "Name".to_string(), name.clone().into());
+                added.push(name);
+            }
+            Err(e) => log::info!("No name found for {ticker}: {e}"),
+        }
+    }
+    Ok(added)
+}
+
+// The name of the ticker, which fails if there is no quote for it
+pub async fn resolve_ticker(provider: &impl PriceProvider, ticker: &str) -> Result<String, String> {
+    provider
+        .get_latest_price(ticker)
+        .await
+        .map_err(|e| format!("There is no quote for {ticker}: {e}"))?;
+    Ok(provider
+        .get_name(ticker)
+        .await
+        .unwrap_or_else(|_| ticker.to_string()))
+}
+
+// Questions on the terminal, an empty answer takes the default in brackets
+pub struct Prompt<R, W> {
+    input: R,
+    output: W,
+}
+
+impl<R: BufRead, W: Write> Prompt<R, W> {
+    pub fn new(input: R, output: W) -> Self {
+        Self { input, output }
+    }
+
+    pub fn ask(&mut self, question: &str, default: Option<&str>) -> Result<String, String> {
+        let write_error = |e: std::io::Error| format!("Failed to write the question: {e}");
+        match default {
+            Some(default) if !default.is_empty() => {
+                write!(self.output, "{question} [{default}]: ").map_err(write_error)?
+            }
+            _ => write!(self.output, "{question}: ").map_err(write_error)?,
+        }
+        self.output.flush().map_err(write_error)?;
+        let mut answer = String::new();
+        let read = self
+            .input
+            .read_line(&mut answer)
+            .map_err(|e| format!("Failed to read the answer: {e}"))?;
+        if read == 0 {
+            return Err(format!("No answer to \"{question}\""));
+        }
+        match answer.trim() {
+            "" => Ok(default.unwrap_or_default().to_string()),
+            answer => Ok(answer.to_string()),
+        }
+    }
+
+    // Asks again until the answer can be parsed, an empty answer is None
+    fn ask_parsed<T>(
+        &mut self,
+        question: &str,
+        default: Option<String>,
+        parse: impl Fn(&str) -> Result<T, String>,
+    ) -> Result<Option<T>, String> {
+        loop {
+            let answer = self.ask(question, default.as_deref())?;
+            if answer.is_empty() {
+                return Ok(None);
+            }
+            match parse(&answer) {
+                Ok(value) => return Ok(Some(value)),
+                Err(e) => writeln!(self.output, "{e}")
+                    .map_err(|e| format!("Failed to write the question: {e}"))?,
+            }
+        }
+    }
+
+    // Asks for a text, an empty answer keeps the current one and "-" removes it
+    fn ask_text(
+        &mut self,
+        question: &str,
+        current: Option<&str>,
+    ) -> Result<Option<String>, String> {
+        let question = match current {
+            Some(_) => format!("{question} (- to remove)"),
+            None => question.to_string(),
+        };
+        let answer = self.ask(&question, current)?;
+        Ok(match answer.as_str() {
+            "-" if current.is_some() => Some(String::new()),
+            "" | "-" => None,
+            answer if Some(answer) == current => None,
+            answer => Some(answer.to_string()),
+        })
+    }
+
+    pub fn confirm(&mut self, question: &str) -> Result<bool, String> {
+        let answer = self.ask(&format!("{question} [y/N]"), None)?;
+        Ok(matches!(answer.to_lowercase().as_str(), "y" | "yes"))
+    }
+
+    fn say(&mut self, message: &str) -> Result<(), String> {
+        writeln!(self.output, "{message}").map_err(|e| format!("Failed to write the question: {e}"))
+    }
+
+    // Asks for the ticker (checked with the provider, the matches of a search are shown
+    // for one without a quote), the name, the asset class, the amount and the interest
+    // settings of cash
+    pub async fn new_position(
+        &mut self,
+        provider: &impl PriceProvider,
+    ) -> Result<NewPosition, String> {
+        let (ticker, resolved) = loop {
+            let ticker = self.ask("Ticker (empty for cash and assets without a quote)", None)?;
+            if ticker.is_empty() {
+                break (None, None);
+            }
+            match resolve_ticker(provider, &ticker).await {
+                Ok(name) => break (Some(ticker), Some(name)),
+                Err(e) => {
+                    self.say(&e)?;
+                    // it may be a name or an ISIN, show the symbols it finds
+                    match search(provider, &ticker, None, DEFAULT_SEARCH_LIMIT).await {
+                        Ok(results) => {
+                            self.say(render_search_results(&ticker, &results).trim_end())?
+                        }
+                        Err(e) => self.say(&e)?,
+                    }
+                }
+            }
+        };
+        let name = loop {
+            let name = self.ask("Name", resolved.as_deref())?;
+            if !name.is_empty() || ticker.is_some() {
+                break Some(name).filter(|name| !name.is_empty());
+            }
+            self.say("A position without a ticker needs a name")?;
+        };
+        let default_class = match &ticker {
+            Some(ticker) if is_crypto_ticker(ticker) => "Crypto",
+            Some(_) => "Stocks",
+            None => "Cash",
+        };
+        let asset_class = self.ask("Asset class", Some(default_class))?;
+        let mut changes = PositionChanges {
+            amount: self.ask_parsed("Amount", Some("0".to_string()), parse_decimal)?,
+            ..PositionChanges::default()
+        };
+        if asset_class.eq_ignore_ascii_case("cash") {
+            self.interest(&mut changes, None)?;
+        }
+        Ok(NewPosition {
+            name,
+            ticker,
+            asset_class,
+            changes,
+        })
+    }
+
+    // Asks for the new amount, interest settings, institution and notes, the current
+    // ones are the defaults
+    pub fn changes(&mut self, position: &PortfolioPosition) -> Result<PositionChanges, String> {
+        let mut changes = PositionChanges::default();
+        let amount = self.ask_parsed(
+            "Amount",
+            Some(position.get_amount().normalize().to_string()),
+            parse_decimal,
+        )?;
+        changes.amount = amount.filter(|amount| *amount != position.get_amount());
+        if position.is_cash() {
+            self.interest(&mut changes, Some(position))?;
+        }
+        changes.institution = self.ask_text("Institution", position.get_institution())?;
+        changes.notes = self.ask_text("Notes", position.get_notes())?;
+        Ok(changes)
+    }
+
+    fn interest(
+        &mut self,
+        changes: &mut PositionChanges,
+        current: Option<&PortfolioPosition>,
+    ) -> Result<(), String> {
+        let rate = current.and_then(|position| position.get_interest_rate());
+        changes.interest_rate = self
+            .ask_parsed(
+                "Interest rate in % (empty for none)",
+                rate.map(|rate| rate.normalize().to_string()),
+                parse_decimal,
+            )?
+            .filter(|new| Some(*new) != rate);
+        if changes.interest_rate.is_none() && rate.is_none() {
+            return Ok(());
+        }
+        let days = current.and_then(|position| position.get_payment_frequency_days());
+        changes.payment_frequency_days = self
+            .ask_parsed(
+                "Days between the interest payments",
+                Some(days.unwrap_or(30).to_string()),
+                |answer| {
+                    answer
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|days| *days > 0)
+                        .ok_or_else(|| format!("Invalid number of days '{answer}'"))
+                },
+            )?
+            .filter(|new| Some(*new) != days);
+        let next = current
+            .and_then(|position| position.get_next_interest_payment())
+            .map(|date| date.date_naive());
+        changes.next_interest_payment = loop {
+            let date = self.ask_parsed(
+                "Next interest payment (YYYY-MM-DD)",
+                next.map(|date| date.format("%Y-%m-%d").to_string()),
+                |answer| {
+                    NaiveDate::parse_from_str(answer, "%Y-%m-%d")
+                        .map_err(|_| format!("Invalid date '{answer}', expected YYYY-MM-DD"))
+                },
+            )?;
+            if date.is_some() || next.is_some() {
+                break date.filter(|new| Some(*new) != next);
+            }
+            self.say("Interest is paid from the next payment date on, it is needed")?;
+        };
+        Ok(())
+    }
+}
+
+fn parse_decimal(answer: &str) -> Result<Decimal, String> {
+    Decimal::from_str(answer).map_err(|_| format!("Invalid number '{answer}'"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::from_string;
+    use crate::provider::{MockProvider, SymbolMatch};
+    use rust_decimal::dec;
+    use std::io::Cursor;
+
+    const DATA: &str = r#"{
+  "Name": "Alex",
+  "Positions": [
+    {
+      "Ticker": "AAPL",
+      "AssetClass": "Stocks",
+      "Shares": 10,
+      "Broker": "Example Bank"
+    },
+    {
+      "Name": "Savings",
+      "AssetClass": "Cash",
+      "Amount": 1000,
+      "Note": "emergency fund"
+    }
+  ]
+}"#;
+
+    fn temp_file(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("edit_{name}_{}.json", std::process::id()));
+        std::fs::write(&path, DATA).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_edit_notes() {
+        let mut document: Value = serde_json::from_str(DATA).unwrap();
+        let changes = PositionChanges {
+            institution: Some("Example Bank".to_string()),
+            notes: Some("emergency fund, 3 months of expenses".to_string()),
+            ..PositionChanges::default()
+        };
+        let edited = edit(&mut document, "Savings", &changes).unwrap();
+        assert_eq!(edited.get_institution(), Some("Example Bank"));
+        assert_eq!(
+            edited.get_notes(),
+            Some("emergency fund, 3 months of expenses")
+        );
+        assert_eq!(
+            document["Positions"][1]["Notes"],
+            "emergency fund, 3 months of expenses"
+        );
+
+        let changes = PositionChanges {
+            notes: Some(String::new()),
+            ..PositionChanges::default()
+        };
+        let edited = edit(&mut document, "Savings", &changes).unwrap();
+        assert_eq!(edited.get_notes(), None);
+        assert_eq!(edited.get_institution(), Some("Example Bank"));
+        assert!(document["Positions"][1].get("Notes").is_none());
+    }
+
+    #[test]
+    fn test_add_edit_remove() {
+        let path = temp_file("commands");
+
+        let mut document = read_document(&path, false).unwrap();
+        let added = add(
+            &mut document,
+            &NewPosition {
+                ticker: Some("MSFT".to_string()),
+                asset_class: "Stocks".to_string(),
+                changes: PositionChanges {
+                    amount: Some(dec!(5)),
+                    ..PositionChanges::default()
+                },
+                ..NewPosition::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(added.get_name(), "MSFT");
+        save_document(&path, &document).unwrap();
+
+        let mut document = read_document(&path, false).unwrap();
+        let edited = edit(
+            &mut document,
+            "savings",
+            &PositionChanges {
+                amount: Some(dec!(1200.50)),
+                interest_rate: Some(dec!(2.5)),
+                payment_frequency_days: Some(30),
+                next_interest_payment: NaiveDate::from_ymd_opt(2025, 1, 31),
+                ..PositionChanges::default()
+            },
+        )
+        .unwrap();
+        assert!(edited.is_cash_with_interest());
+        edit(
+            &mut document,
+            "aapl",
+            &PositionChanges {
+                amount: Some(dec!(12)),
+                ..PositionChanges::default()
+            },
+        )
+        .unwrap();
+        save_document(&path, &document).unwrap();
+
+        let data = std::fs::read_to_string(&path).unwrap();
+        let expected = r#"{
+  "Name": "Alex",
+  "Positions": [
+    {
+      "Ticker": "AAPL",
+      "AssetClass": "Stocks",
+      "Broker": "Example Bank",
+      "Amount": 12
+    },
+    {
+      "Name": "Savings",
+      "AssetClass": "Cash",
+      "Amount": 1200.5,
+      "Note": "emergency fund",
+      "InterestRate": 2.5,
+      "PaymentFrequencyDays": 30,
+      "NextInterestPayment": "2025-01-31"
+    },
+    {
+      "Ticker": "MSFT",
+      "AssetClass": "Stocks",
+      "Amount": 5
+    }
+  ]
+}"#;
+        assert_eq!(data, expected);
+
+        let mut document = read_document(&path, false).unwrap();
+        assert_eq!(remove(&mut document, "MSFT").unwrap().get_name(), "MSFT");
+        save_document(&path, &document).unwrap();
+        let positions = from_string(&std::fs::read_to_string(&path).unwrap());
+        assert_eq!(positions.len(), 2);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_add_to_a_new_file() {
+        let path = std::env::temp_dir().join(format!("edit_new_{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        assert!(read_document(path, false)
+            .unwrap_err()
+            .starts_with("Portfolio file not found"));
+        let mut document = read_document(path, true).unwrap();
+        let cash = NewPosition {
+            name: Some("Wallet".to_string()),
+            asset_class: "Cash".to_string(),
+            ..NewPosition::default()
+        };
+        add(&mut document, &cash).unwrap();
+        save_document(path, &document).unwrap();
+        let positions = from_string(&std::fs::read_to_string(path).unwrap());
+        assert_eq!(positions[0].get_name(), "Wallet");
+        assert_eq!(positions[0].get_amount(), Decimal::ZERO);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut document: Value = serde_json::from_str(DATA).unwrap();
+        assert_eq!(
+            remove(&mut document, "MSFT").unwrap_err(),
+            "There is no position named MSFT"
+        );
+        let again = NewPosition {
+            name: Some("Savings".to_string()),
+            asset_class: "Cash".to_string(),
+            ..NewPosition::default()
+        };
+        add(&mut document, &again).unwrap();
+        assert_eq!(
+            edit(&mut document, "Savings", &PositionChanges::default()).unwrap_err(),
+            "2 positions are named Savings, edit the data file to change one of them"
+        );
+        assert_eq!(
+            add(&mut document, &NewPosition::default()).unwrap_err(),
+            "A position needs a name or a ticker"
+        );
+        // YAML and TOML files are changed as JSON as well, a CSV export isn't
+        let yaml = read_document("tests/fixtures/portfolio.yaml", false).unwrap();
+        assert_eq!(positions_mut(&mut yaml.clone()).unwrap().len(), 3);
+        assert_eq!(
+            read_document("tests/fixtures/broker.csv", false).unwrap_err(),
+            "Saving to CSV files is not supported"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_add_missing_names() {
+        let provider = MockProvider::new()
+            .with_name("AAPL", "Apple Inc.")
+            .with_name("MSFT", "Microsoft Corporation");
+        let mut document: Value = serde_json::from_str(
+            r#"{"Name": "Alex", "Positions": [
+                {"Ticker": "AAPL", "AssetClass": "Stocks", "Amount": 10},
+                {"Name": "My Microsoft", "Ticker": "MSFT", "AssetClass": "Stocks", "Amount": 1},
+                {"Ticker": "NONAME", "AssetClass": "Stocks", "Amount": 1},
+                {"Name": "Savings", "AssetClass": "Cash", "Amount": 1000}
+            ]}"#,
+        )
+        .unwrap();
+
+        let added = add_missing_names(&mut document, &provider).await.unwrap();
+        assert_eq!(added, ["Apple Inc."]);
+        let positions = positions_mut(&mut document).unwrap();
+        // the name comes first, like in a position written by hand
+        assert_eq!(
+            positions[0].as_object().unwrap().keys().next().unwrap(),
+            "Name"
+        );
+        assert_eq!(positions[0]["Name"], "Apple Inc.");
+        assert_eq!(positions[1]["Name"], "My Microsoft");
+        assert!(positions[2].get("Name").is_none());
+        assert_eq!(document["Name"], "Alex");
+    }
+
+    #[tokio::test]
+    async fn test_prompt_new_position() {
+        let provider = MockProvider::new()
+            .with_price("AAPL", 200.0)
+            .with_name("AAPL", "Apple Inc.")
+            .with_matches(
+                "APPL",
+                vec![SymbolMatch {
+                    symbol: "AAPL".to_string(),
+                    name: "Apple Inc.".to_string(),
+                    exchange: "NMS".to_string(),
+                    quote_type: "EQUITY".to_string(),
+                }],
+            );
+        let answers = "APPL\nAAPL\n\n\nabc\n7.5\n";
+        let mut output = Vec::new();
+        let new = Prompt::new(Cursor::new(answers), &mut output)
+            .new_position(&provider)
+            .await
+            .unwrap();
+        assert_eq!(new.name.as_deref(), Some("Apple Inc."));
+        assert_eq!(new.ticker.as_deref(), Some("AAPL"));
+        assert_eq!(new.asset_class, "Stocks");
+        assert_eq!(new.changes.amount, Some(dec!(7.5)));
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("There is no quote for APPL"));
+        // the symbols the search finds for it are shown
+        assert!(output.contains("AAPL   | Apple Inc. | NMS      | EQUITY | -"));
+        assert!(output.contains("Name [Apple Inc.]: "));
+        assert!(output.contains("Invalid number 'abc'"));
+
+        // cash asks for the interest, the next payment can't be left out
+        let answers = "\nSavings\n\n1000\n2\n\n\n2025-02-01\n";
+        let new = Prompt::new(Cursor::new(answers), Vec::new())
+            .new_position(&provider)
+            .await
+            .unwrap();
+        assert_eq!(new.asset_class, "Cash");
+        assert_eq!(
+            new.changes,
+            PositionChanges {
+                amount: Some(dec!(1000)),
+                interest_rate: Some(dec!(2)),
+                payment_frequency_days: Some(30),
+                next_interest_payment: NaiveDate::from_ymd_opt(2025, 2, 1),
+                ..PositionChanges::default()
+            }
+        );
+
+        let error = Prompt::new(Cursor::new(""), Vec::new())
+            .new_position(&provider)
+            .await
+            .unwrap_err();
+        assert!(error.starts_with("No answer to"));
+    }
+
+    #[test]
+    fn test_prompt_changes() {
+        let mut document: Value = serde_json::from_str(DATA).unwrap();
+        let savings = get(&mut document, "Savings").unwrap();
+        // the current amount is kept, no interest or notes are added
+        let changes = Prompt::new(Cursor::new("\n\n\n\n"), Vec::new())
+            .changes(&savings)
+            .unwrap();
+        assert!(changes.is_empty());
+
+        let changes = Prompt::new(Cursor::new("900\n\nsellable after May\n"), Vec::new())
+            .changes(&get(&mut document, "AAPL").unwrap())
+            .unwrap();
+        assert_eq!(changes.amount, Some(dec!(900)));
+        assert_eq!(changes.notes.as_deref(), Some("sellable after May"));
+
+        // "-" removes the notes, the institution is kept
+        let aapl = from_string(
+            r#"[{"Ticker": "AAPL", "AssetClass": "Stocks", "Amount": 10,
+                 "Institution": "Example Bank", "Notes": "sellable after May"}]"#,
+        )
+        .remove(0);
+        let mut output = Vec::new();
+        let changes = Prompt::new(Cursor::new("\n\n-\n"), &mut output)
+            .changes(&aapl)
+            .unwrap();
+        assert_eq!(changes.institution, None);
+        assert_eq!(changes.notes.as_deref(), Some(""));
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("Notes (- to remove) [sellable after May]: "));
+
+        let mut prompt = Prompt::new(Cursor::new("yes\nn\n"), Vec::new());
+        assert!(prompt.confirm("Remove Savings?").unwrap());
+        assert!(!prompt.confirm("Remove Savings?").unwrap());
+    }
+}
@@ -0,0 +1,627 @@
+use crate::format::NumberFormat;
+use crate::position::{currency_decimals, is_false, round_to, PortfolioPosition};
+use chrono::prelude::*;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+pub const CSV_HEADER: [&str; 14] = [
+    "Name",
+    "Ticker",
+    "AssetClass",
+    "Amount",
+    "LastSpot",
+    "Balance",
+    "Currency",
+    "InterestRate",
+    "PaymentFrequencyDays",
+    "NextInterestPayment",
+    "LastInterestPayment",
+    "CostBasis",
+    // RFC 3339 time of the quote the LastSpot is from
+    "LastSpotTime",
+    // "true" if the LastSpot is a simulated PriceOverride
+    "PriceOverride",
+];
+
+// Numbers use the separators of the format (NumberFormat::machine for plain
+// numbers), fields that don't apply to a position (e.g. the ticker of cash) are left empty
+fn format_number(value: Option<Decimal>, format: &NumberFormat) -> String {
+    value.map(|value| format.number(value)).unwrap_or_default()
+}
+
+// One row per priced position (after handle_position ran) plus a header row.
+// Names containing commas, quotes or line breaks are quoted by the csv writer.
+pub fn to_csv(
+    positions: &[PortfolioPosition],
+    currency: &str,
+    format: &NumberFormat,
+) -> Result<String, String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    let error = |e: csv::Error| format!("Failed to write CSV: {e}");
+
+    writer.write_record(CSV_HEADER).map_err(error)?;
+    for position in positions {
+        writer
+            .write_record([
+                position.get_name().to_string(),
+                position.get_ticker().unwrap_or_default().to_string(),
+                position.get_asset_class().to_string(),
+                format_number(Some(position.get_amount()), format),
+                format_number(position.get_last_spot(), format),
+                format_number(Some(position.get_balance()), format),
+                position.get_currency().unwrap_or(currency).to_string(),
+                format_number(position.get_interest_rate(), format),
+                position
+                    .get_payment_frequency_days()
+                    .map(|days| days.to_string())
+                    .unwrap_or_default(),
+                position
+                    .get_next_interest_payment()
+                    .map(|date| date.format("%Y-%m-%d").to_string())
+                    .unwrap_or_default(),
+                position
+                    .get_last_interest_payment()
+                    .map(|date| date.format("%Y-%m-%d").to_string())
+                    .unwrap_or_default(),
+                format_number(position.get_cost_basis(), format),
+                position
+                    .get_last_spot_time()
+                    .map(|time| time.to_rfc3339())
+                    .unwrap_or_default(),
+                if position.has_price_override() {
+                    "true".to_string()
+                } else {
+                    String::new()
+                },
+            ])
+            .map_err(error)?;
+    }
+
+    let bytes = writer
+        .into_inner()
+        .map_err(|e| format!("Failed to write CSV: {e}"))?;
+    String::from_utf8(bytes).map_err(|e| format!("Failed to write CSV: {e}"))
+}
+
+// A position together with the values computed while pricing it,
+// emitted by the JSON output mode
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct PricedPosition {
+    pub name: String,
+    pub ticker: Option<String>,
+    pub asset_class: String,
+    pub amount: Decimal,
+    pub last_spot: Option<Decimal>,
+    // time of the quote, so scripts can apply their own staleness policy
+    pub last_spot_time: Option<DateTime<Utc>>,
+    // the last_spot is a simulated price, not a quote
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub price_override: bool,
+    // the quote couldn't be fetched, the balance is without a price
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub unpriced: bool,
+    pub balance: Decimal,
+    pub currency: String,
+    pub accrued_interest: Option<Decimal>,
+    // the balance with the accrued interest, the balance is what was credited so far
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub balance_including_accrued: Option<Decimal>,
+    pub unrealized_gain: Option<Decimal>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub institution: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    // the data file the position is from when several are loaded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub portfolio: Option<String>,
+    // what every portfolio holds of a consolidated position
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub portfolios: Vec<PortfolioShare>,
+}
+
+// The part of a consolidated position held in one portfolio
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct PortfolioShare {
+    pub name: String,
+    pub amount: Decimal,
+    pub balance: Decimal,
+}
+
+// The balance is split by the amounts, they all have the same price
+fn portfolio_shares(position: &PortfolioPosition) -> Vec<PortfolioShare> {
+    position
+        .get_portfolio_amounts()
+        .iter()
+        .map(|(name, amount)| PortfolioShare {
+            name: name.clone(),
+            amount: *amount,
+            balance: if position.get_amount().is_zero() {
+                Decimal::ZERO
+            } else {
+                position.get_balance() * amount / position.get_amount()
+            },
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct BalancesSummary {
+    pub total: Decimal,
+    pub by_asset_class: BTreeMap<String, Decimal>,
+    pub generated_at: DateTime<Utc>,
+}
+
+// A ticker on the watchlist, the day change is per unit
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct WatchedTicker {
+    pub name: String,
+    pub ticker: Option<String>,
+    pub last_spot: Option<Decimal>,
+    pub last_spot_time: Option<DateTime<Utc>>,
+    pub day_change: Option<Decimal>,
+    pub day_change_percent: Option<Decimal>,
+    pub currency: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct BalancesDocument {
+    pub positions: Vec<PricedPosition>,
+    pub summary: BalancesSummary,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub watchlist: Vec<WatchedTicker>,
+}
+
+pub fn balances_document(
+    positions: &[PortfolioPosition],
+    watchlist: &[PortfolioPosition],
+    currency: &str,
+    as_of: DateTime<Utc>,
+) -> BalancesDocument {
+    let mut by_asset_class = BTreeMap::new();
+    for position in positions {
+        *by_asset_class
+            .entry(position.get_asset_class().to_string())
+            .or_default() += position.get_balance();
+    }
+
+    BalancesDocument {
+        positions: positions
+            .iter()
+            .map(|position| PricedPosition {
+                name: position.get_name().to_string(),
+                ticker: position.get_ticker().map(|ticker| ticker.to_string()),
+                asset_class: position.get_asset_class().to_string(),
+                amount: position.get_amount(),
+                last_spot: position.get_last_spot(),
+                last_spot_time: position.get_last_spot_time(),
+                price_override: position.has_price_override(),
+                unpriced: position.is_unpriced(),
+                balance: position.get_balance(),
+                currency: position.get_currency().unwrap_or(currency).to_string(),
+                accrued_interest: position
+                    .is_cash_with_interest()
+                    .then(|| position.accrued_interest(as_of)),
+                balance_including_accrued: position
+                    .is_cash_with_interest()
+                    .then(|| position.balance_including_accrued(as_of)),
+                unrealized_gain: position.get_unrealized_gain(),
+                institution: position.get_institution().map(|name| name.to_string()),
+                notes: position.get_notes().map(|notes| notes.to_string()),
+                portfolio: position.get_portfolio().map(|name| name.to_string()),
+                portfolios: portfolio_shares(position),
+            })
+            .collect(),
+        summary: BalancesSummary {
+            total: positions
+                .iter()
+                .map(|position| position.get_balance())
+                .sum(),
+            by_asset_class,
+            generated_at: as_of,
+        },
+        watchlist: watchlist
+            .iter()
+            .map(|position| WatchedTicker {
+                name: position.get_name().to_string(),
+                ticker: position.get_ticker().map(|ticker| ticker.to_string()),
+                last_spot: position.get_last_spot(),
+                last_spot_time: position.get_last_spot_time(),
+                day_change: position.get_price_change(),
+                day_change_percent: position.get_day_change_percent(),
+                currency: position.get_currency().unwrap_or(currency).to_string(),
+            })
+            .collect(),
+    }
+}
+
+pub fn to_json(
+    positions: &[PortfolioPosition],
+    watchlist: &[PortfolioPosition],
+    currency: &str,
+) -> Result<String, String> {
+    let document = balances_document(positions, watchlist, currency, Utc::now());
+    serde_json::to_string_pretty(&document).map_err(|e| format!("Failed to serialize data: {e}"))
+}
+
+// Beancount account names are made of components starting with a capital letter
+// or digit followed by letters, digits and dashes, e.g. Assets:Stocks:Apple-Inc
+fn account_component(text: &str) -> String {
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect())
+                .unwrap_or_default()
+        })
+        .collect();
+    if words.is_empty() {
+        "Unnamed".to_string()
+    } else {
+        words.join("-")
+    }
+}
+
+// Beancount commodities are capital letters, digits and ' . _ -, starting with a
+// letter and ending with a letter or digit. None if nothing of the ticker is left,
+// e.g. of an index like ^GSPC it's GSPC.
+fn commodity(ticker: &str) -> Option<String> {
+    let symbol: String = ticker
+        .to_uppercase()
+        .chars()
+        .filter(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || "'._-".contains(*c))
+        .collect();
+    let symbol = symbol
+        .trim_start_matches(|c: char| !c.is_ascii_uppercase())
+        .trim_end_matches(|c: char| !c.is_ascii_alphanumeric());
+    (!symbol.is_empty()).then(|| symbol.to_string())
+}
+
+// Plain numbers with at least the decimals of the currency, e.g. 150.00 USD,
+// but without cutting off the places of a share amount or crypto price
+fn beancount_number(value: Decimal, decimals: u32) -> String {
+    let value = value.normalize();
+    format!("{value:.*}", value.scale().max(decimals) as usize)
+}
+
+// Balance assertions of the priced positions on the date, for including in a
+// ledger that has the accounts. A position with a ticker is held in the ticker as
+// a commodity, with a price directive of its last spot. There is no price
+// directive for a PriceOverride, it's not a price that was traded, or without a
+// quote, e.g. with --no-fetch. Cash, liabilities and positions without a quote
+// are balances in their currency.
+pub fn to_beancount(
+    positions: &[PortfolioPosition],
+    currency: &str,
+    date: DateTime<Utc>,
+) -> String {
+    let date = date.format("%Y-%m-%d");
+    let mut prices = String::new();
+    let mut balances = String::new();
+    for position in positions {
+        let root = if position.is_liability() {
+            "Liabilities"
+        } else {
+            "Assets"
+        };
+        let account = format!(
+            "{root}:{}:{}",
+            account_component(position.get_asset_class()),
+            account_component(position.get_name())
+        );
+        let position_currency = position.get_currency().unwrap_or(currency);
+        let decimals = currency_decimals(position_currency);
+        let held = position
+            .get_ticker()
+            .and_then(commodity)
+            .zip(position.get_last_spot())
+            .filter(|_| !position.is_cash() && !position.is_liability());
+        match held {
+            Some((symbol, last_spot)) => {
+                if !position.has_price_override() && last_spot > Decimal::ZERO {
+                    prices.push_str(&format!(
+                        "{date} price {symbol} {} {position_currency}\n",
+                        beancount_number(last_spot, decimals)
+                    ));
+                }
+                balances.push_str(&format!(
+                    "{date} balance {account} {} {symbol}\n",
+                    beancount_number(position.get_amount(), 0)
+                ));
+            }
+            None => balances.push_str(&format!(
+                "{date} balance {account} {} {position_currency}\n",
+                beancount_number(round_to(position.get_balance(), decimals), decimals)
+            )),
+        }
+    }
+
+    let mut output = format!("; Balances of the portfolio on {date}\n");
+    if !prices.is_empty() {
+        output.push('\n');
+        output.push_str(&prices);
+    }
+    if !balances.is_empty() {
+        output.push('\n');
+        output.push_str(&balances);
+    }
+    output
+}
+
+// Print the output to stdout, or write it to the given file
+pub fn write_output(output: &str, file: Option<&str>) -> Result<(), String> {
+    match file {
+        Some(file) => {
+            std::fs::write(file, output).map_err(|e| format!("Error writing file {file}: {e}"))
+        }
+        None => {
+            print!("{output}");
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::NumberStyle;
+    use crate::position::from_string;
+    use crate::provider::{priced_at, priced_blocking, MockProvider};
+    use rust_decimal::dec;
+
+    #[test]
+    fn test_csv_round_trip() {
+        let quote_time = Utc.with_ymd_and_hms(2024, 6, 7, 20, 0, 0).unwrap();
+        let provider = MockProvider::new()
+            .with_price("AAPL", 170.25)
+            .with_quote_time("AAPL", quote_time)
+            .with_price("BND", 72.0)
+            .with_quote_time("BND", quote_time);
+        let positions = priced_blocking(
+            &provider,
+            r#"[
+                {"Name": "Apple, Inc.", "Ticker": "AAPL", "AssetClass": "Stocks", "Amount": 10, "CostBasis": 150.5},
+                {"Name": "The \"Safe\" Bond", "Ticker": "BND", "AssetClass": "Bonds", "Amount": 2.5},
+                {
+                    "Name": "Savings",
+                    "AssetClass": "Cash",
+                    "Amount": 1000.125,
+                    "InterestRate": 3.65,
+                    "PaymentFrequencyDays": 30,
+                    "NextInterestPayment": "2024-01-31",
+                    "LastInterestPayment": "2024-01-01"
+                }
+            ]"#,
+        );
+
+        let csv_str = to_csv(&positions, "EUR", &NumberFormat::machine("EUR", 3)).unwrap();
+        let mut reader = csv::Reader::from_reader(csv_str.as_bytes());
+        assert_eq!(reader.headers().unwrap(), CSV_HEADER.as_slice());
+
+        let rows: Vec<csv::StringRecord> = reader.records().map(|r| r.unwrap()).collect();
+        assert_eq!(rows.len(), positions.len());
+
+        for (row, position) in rows.iter().zip(&positions) {
+            assert_eq!(&row[0], position.get_name());
+            assert_eq!(&row[1], position.get_ticker().unwrap_or_default());
+            assert_eq!(&row[2], position.get_asset_class());
+            assert_eq!(row[3].parse::<Decimal>().unwrap(), position.get_amount());
+            assert_eq!(row[5].parse::<Decimal>().unwrap(), position.get_balance());
+            assert_eq!(&row[6], "EUR");
+            assert_eq!(row[7].parse::<Decimal>().ok(), position.get_interest_rate());
+            assert_eq!(row[11].parse::<Decimal>().ok(), position.get_cost_basis());
+        }
+
+        assert_eq!(&rows[0][4], "170.250");
+        assert_eq!(&rows[0][5], "1702.500");
+        assert_eq!(&rows[2][4], "");
+        assert_eq!(&rows[2][8], "30");
+        assert_eq!(&rows[2][9], "2024-01-31");
+        assert_eq!(&rows[2][10], "2024-01-01");
+        assert_eq!(&rows[0][12], "2024-06-07T20:00:00+00:00");
+        assert_eq!(&rows[2][12], "");
+        assert!(csv_str.contains("\"Apple, Inc.\""));
+        assert!(csv_str.contains("\"The \"\"Safe\"\" Bond\""));
+    }
+
+    #[test]
+    fn test_csv_precision() {
+        let positions =
+            from_string(r#"[{"Name": "Savings", "AssetClass": "Cash", "Amount": 1000.125}]"#);
+        let csv_str = to_csv(&positions, "EUR", &NumberFormat::machine("EUR", 1)).unwrap();
+        assert!(csv_str.ends_with("Savings,,Cash,1000.1,,1000.1,EUR,,,,,,,\n"));
+    }
+
+    #[test]
+    fn test_csv_localized_numbers() {
+        let positions =
+            from_string(r#"[{"Name": "Savings", "AssetClass": "Cash", "Amount": 1234.5}]"#);
+        let format = NumberFormat::for_currency("EUR").with_style(NumberStyle::De);
+        let csv_str = to_csv(&positions, "EUR", &format).unwrap();
+        // the decimal comma is quoted by the csv writer
+        assert!(csv_str.ends_with("Savings,,Cash,\"1.234,50\",,\"1.234,50\",EUR,,,,,,,\n"));
+    }
+
+    #[test]
+    fn test_price_override_flag() {
+        let positions = from_string(
+            r#"[
+                {"Name": "Apple", "Ticker": "AAPL", "AssetClass": "Stocks", "Amount": 10, "PriceOverride": 136},
+                {"Name": "Savings", "AssetClass": "Cash", "Amount": 100}
+            ]"#,
+        );
+        let csv_str = to_csv(&positions, "USD", &NumberFormat::machine("USD", 2)).unwrap();
+        assert!(csv_str.contains("Apple,AAPL,Stocks,10.00,136.00,1360.00,USD,,,,,,,true\n"));
+        assert!(csv_str.ends_with("Savings,,Cash,100.00,,100.00,USD,,,,,,,\n"));
+
+        let value: serde_json::Value =
+            serde_json::from_str(&to_json(&positions, &[], "USD").unwrap()).unwrap();
+        assert_eq!(value["positions"][0]["price_override"], true);
+        assert_eq!(value["positions"][0]["last_spot"], 136.0);
+        assert!(value["positions"][1].get("price_override").is_none());
+    }
+
+    #[test]
+    fn test_json_notes_and_currency() {
+        let positions = from_string(
+            r#"[
+                {"Name": "RSUs", "AssetClass": "Stocks", "Amount": 10, "Institution": "Example Broker",
+                 "Notes": "vested, sellable after 2026-05", "Currency": "GBP"},
+                {"Name": "Savings", "AssetClass": "Cash", "Amount": 100}
+            ]"#,
+        );
+        let value: serde_json::Value =
+            serde_json::from_str(&to_json(&positions, &[], "USD").unwrap()).unwrap();
+        assert_eq!(value["positions"][0]["institution"], "Example Broker");
+        assert_eq!(value["positions"][0]["currency"], "GBP");
+        assert_eq!(
+            value["positions"][0]["notes"],
+            "vested, sellable after 2026-05"
+        );
+        assert!(value["positions"][1].get("institution").is_none());
+        assert_eq!(value["positions"][1]["currency"], "USD");
+        assert!(value["positions"][1].get("notes").is_none());
+    }
+
+    #[test]
+    fn test_json_document_schema() {
+        let quote_time = Utc.with_ymd_and_hms(2024, 6, 7, 20, 0, 0).unwrap();
+        let provider = MockProvider::new()
+            .with_price("AAPL", 170.0)
+            .with_quote_time("AAPL", quote_time)
+            .with_price("MSFT", 400.0)
+            .with_quote_time("MSFT", quote_time);
+        let positions = priced_blocking(
+            &provider,
+            r#"[
+                {"Name": "Apple", "Ticker": "AAPL", "AssetClass": "Stocks", "Amount": 10, "CostBasis": 150},
+                {"Name": "Microsoft", "Ticker": "MSFT", "AssetClass": "Stocks", "Amount": 2},
+                {
+                    "Name": "Savings",
+                    "AssetClass": "Cash",
+                    "Amount": 1000,
+                    "InterestRate": 3.65,
+                    "PaymentFrequencyDays": 30,
+                    "NextInterestPayment": "2024-01-31",
+                    "LastInterestPayment": "2024-01-01"
+                }
+            ]"#,
+        );
+
+        let json = to_json(&positions, &[], "EUR").unwrap();
+        let document: BalancesDocument = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(document.positions.len(), positions.len());
+        let sum: Decimal = document.positions.iter().map(|p| p.balance).sum();
+        assert_eq!(document.summary.total, sum);
+        let class_sum: Decimal = document.summary.by_asset_class.values().sum();
+        assert_eq!(document.summary.total, class_sum);
+        assert_eq!(document.summary.by_asset_class["Stocks"], dec!(2500));
+        assert!(document.positions.iter().all(|p| p.currency == "EUR"));
+
+        let apple = &document.positions[0];
+        assert_eq!(apple.last_spot, Some(dec!(170)));
+        assert_eq!(apple.unrealized_gain, Some(dec!(200)));
+        assert_eq!(apple.accrued_interest, None);
+
+        let savings = &document.positions[2];
+        assert_eq!(savings.ticker, None);
+        assert_eq!(savings.last_spot, None);
+        assert!(savings.accrued_interest.unwrap() > dec!(0));
+        assert_eq!(
+            savings.balance_including_accrued,
+            Some(savings.balance + savings.accrued_interest.unwrap())
+        );
+
+        // stable field names for scripts
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["positions"][0]["last_spot"], 170.0);
+        assert!(value["summary"]["generated_at"].is_string());
+        assert!(value.get("watchlist").is_none());
+        assert_eq!(
+            value["positions"][0]["last_spot_time"],
+            "2024-06-07T20:00:00Z"
+        );
+        assert!(value["positions"][2]["last_spot_time"].is_null());
+    }
+
+    #[test]
+    fn test_beancount_golden() {
+        let price = |ticker: &str| match ticker {
+            "AAPL" => 150.0,
+            "EUNL.DE" => 98.765,
+            "BTC-USD" => 64123.45,
+            "^GSPC" => 5000.5,
+            _ => 0.0,
+        };
+        let positions = priced_at(
+            r#"[
+                {"Name": "Apple Inc.", "Ticker": "AAPL", "AssetClass": "Stocks", "Amount": 10},
+                {"Name": "iShares Core MSCI World", "Ticker": "EUNL.DE", "AssetClass": "ETF", "Amount": 4.5, "Currency": "EUR"},
+                {"Name": "Bitcoin", "Ticker": "BTC-USD", "AssetClass": "Crypto", "Amount": 0.05},
+                {"Name": "What if", "Ticker": "NVDA", "AssetClass": "Stocks", "Amount": 1, "PriceOverride": 1000},
+                {"Name": "S&P 500", "Ticker": "^GSPC", "AssetClass": "Index", "Amount": 1},
+                {"Name": "Not fetched", "Ticker": "MSFT", "AssetClass": "Stocks", "Amount": 2},
+                {"Name": "Savings account", "AssetClass": "Cash", "Amount": 1000.125},
+                {"Name": "Yen", "AssetClass": "Cash", "Amount": 5000, "Currency": "JPY"},
+                {"Name": "Mortgage", "AssetClass": "Liability", "Amount": -150000}
+            ]"#,
+            price,
+        );
+
+        let date = Utc.with_ymd_and_hms(2025, 1, 1, 18, 0, 0).unwrap();
+        let golden = std::fs::read_to_string("tests/fixtures/portfolio.beancount").unwrap();
+        assert_eq!(to_beancount(&positions, "USD", date), golden);
+
+        assert_eq!(
+            to_beancount(&[], "USD", date),
+            "; Balances of the portfolio on 2025-01-01\n"
+        );
+    }
+
+    #[test]
+    fn test_beancount_names() {
+        assert_eq!(account_component("Apple, Inc."), "Apple-Inc");
+        assert_eq!(account_component("cash"), "Cash");
+        assert_eq!(
+            account_component("Tagesgeld für später"),
+            "Tagesgeld-Für-Später"
+        );
+        assert_eq!(account_component("--"), "Unnamed");
+        assert_eq!(commodity("apc.de").as_deref(), Some("APC.DE"));
+        assert_eq!(commodity("^GSPC").as_deref(), Some("GSPC"));
+        assert_eq!(commodity("EURUSD=X").as_deref(), Some("EURUSDX"));
+        assert_eq!(commodity("^^"), None);
+    }
+
+    #[test]
+    fn test_json_watchlist() {
+        let provider = MockProvider::new()
+            .with_price("AAPL", 100.0)
+            .with_previous_close("AAPL", 80.0)
+            .with_price("NVDA", 100.0)
+            .with_previous_close("NVDA", 80.0);
+        let mut portfolio = crate::portfolio::Portfolio::new();
+        for position in priced_blocking(
+            &provider,
+            r#"[
+                {"Name": "Apple", "Ticker": "AAPL", "AssetClass": "Stocks", "Amount": 10},
+                {"Name": "Nvidia", "Ticker": "NVDA", "AssetClass": "Stocks", "Amount": 0}
+            ]"#,
+        ) {
+            portfolio.add_position(position);
+        }
+
+        let json = to_json(&portfolio.positions, &portfolio.watchlist, "USD").unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["positions"].as_array().unwrap().len(), 1);
+        assert_eq!(value["summary"]["total"], 1000.0);
+        assert_eq!(value["watchlist"][0]["ticker"], "NVDA");
+        assert_eq!(value["watchlist"][0]["last_spot"], 100.0);
+        assert_eq!(value["watchlist"][0]["day_change"], 20.0);
+        assert_eq!(value["watchlist"][0]["day_change_percent"], 25.0);
+    }
+}
@@ -0,0 +1,346 @@
+use crate::position::PortfolioPosition;
+use rust_decimal::Decimal;
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+// Narrows down the priced positions shown by a report. All criteria that are
+// set have to match, unset criteria match every position.
+#[derive(Debug, Clone, Default)]
+pub struct PositionFilter {
+    pub asset_class: Option<String>,
+    pub min_balance: Option<Decimal>,
+    // case-insensitive substring of the name or ticker
+    pub search: Option<String>,
+    // the position has to carry all of these tags
+    pub tags: Vec<String>,
+}
+
+impl PositionFilter {
+    pub fn is_empty(&self) -> bool {
+        self.asset_class.is_none()
+            && self.min_balance.is_none()
+            && self.search.is_none()
+            && self.tags.iter().all(|tag| tag.trim().is_empty())
+    }
+
+    pub fn matches(&self, position: &PortfolioPosition) -> bool {
+        if let Some(asset_class) = &self.asset_class {
+            if !position.get_asset_class().eq_ignore_ascii_case(asset_class) {
+                return false;
+            }
+        }
+        if let Some(min_balance) = self.min_balance {
+            if position.get_balance() < min_balance {
+                return false;
+            }
+        }
+        if let Some(search) = &self.search {
+            let search = search.to_lowercase();
+            let in_name = position.get_name().to_lowercase().contains(&search);
+            let in_ticker = position
+                .get_ticker()
+                .is_some_and(|ticker| ticker.to_lowercase().contains(&search));
+            if !in_name && !in_ticker {
+                return false;
+            }
+        }
+        position.has_tags(&self.tags)
+    }
+
+    // The filter without the tags none of the positions carries, e.g. a misspelled
+    // one, so it doesn't hide every position
+    pub fn without_unknown_tags(&self, positions: &[PortfolioPosition]) -> PositionFilter {
+        let (tags, unknown): (Vec<String>, Vec<String>) =
+            self.tags.iter().cloned().partition(|tag| {
+                tag.trim().is_empty()
+                    || positions
+                        .iter()
+                        .any(|position| position.has_tags(std::slice::from_ref(tag)))
+            });
+        for tag in unknown {
+            log::warn!("No position is tagged {tag}, the tag is ignored");
+        }
+        PositionFilter {
+            tags,
+            ..self.clone()
+        }
+    }
+
+    // Keep the matching positions and return how many were hidden
+    pub fn apply(&self, positions: &mut Vec<PortfolioPosition>) -> usize {
+        let before = positions.len();
+        positions.retain(|position| self.matches(position));
+        before - positions.len()
+    }
+}
+
+// Reports print their totals over the displayed positions only
+pub fn print_hidden_note(hidden: usize) {
+    if hidden > 0 {
+        println!("Note: {hidden} position(s) hidden by filters, the totals only include the positions shown.");
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortKey {
+    Balance,
+    Name,
+    Gain,
+    AssetClass,
+}
+
+impl FromStr for SortKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_lowercase().as_str() {
+            "balance" => Ok(SortKey::Balance),
+            "name" => Ok(SortKey::Name),
+            "gain" => Ok(SortKey::Gain),
+            "asset_class" => Ok(SortKey::AssetClass),
+            _ => Err(format!(
+                "{s} is not a sort key, expected balance, name, gain or asset_class"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PositionSort {
+    pub key: SortKey,
+    pub descending: bool,
+}
+
+impl PositionSort {
+    // Stable sort, so positions with equal keys keep their order from the data file.
+    // Positions without a gain (cash, no cost basis) always come last when sorting by gain.
+    pub fn apply(&self, positions: &mut [PortfolioPosition]) {
+        positions.sort_by(|a, b| self.cmp(a, b));
+    }
+
+    // The order of two positions, e.g. to sort indices instead of the positions
+    pub fn cmp(&self, a: &PortfolioPosition, b: &PortfolioPosition) -> Ordering {
+        if self.key == SortKey::Gain {
+            match (a.get_unrealized_gain(), b.get_unrealized_gain()) {
+                (Some(a), Some(b)) => self.order(a.cmp(&b)),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            }
+        } else {
+            self.order(self.compare(a, b))
+        }
+    }
+
+    fn compare(&self, a: &PortfolioPosition, b: &PortfolioPosition) -> Ordering {
+        match self.key {
+            SortKey::Balance => a.get_balance().cmp(&b.get_balance()),
+            SortKey::Name => a
+                .get_name()
+                .to_lowercase()
+                .cmp(&b.get_name().to_lowercase()),
+            SortKey::AssetClass => a.get_asset_class().cmp(b.get_asset_class()),
+            SortKey::Gain => Ordering::Equal,
+        }
+    }
+
+    fn order(&self, ordering: Ordering) -> Ordering {
+        if self.descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::from_string;
+    use crate::provider::{priced_blocking, MockProvider};
+    use rust_decimal::dec;
+
+    fn priced_portfolio() -> Vec<PortfolioPosition> {
+        let positions_str = std::fs::read_to_string("tests/fixtures/priced.json").unwrap();
+        let provider = MockProvider::new()
+            .with_price("AAPL", 170.0)
+            .with_price("MSFT", 380.0)
+            .with_price("BND", 72.0)
+            .with_price("EUNL.DE", 90.0);
+        priced_blocking(&provider, &positions_str)
+    }
+
+    fn names(positions: &[PortfolioPosition]) -> Vec<&str> {
+        positions.iter().map(|p| p.get_name()).collect()
+    }
+
+    #[test]
+    fn test_empty_filter_keeps_everything() {
+        let mut positions = priced_portfolio();
+        let filter = PositionFilter::default();
+        assert!(filter.is_empty());
+        assert_eq!(filter.apply(&mut positions), 0);
+        assert_eq!(positions.len(), 5);
+    }
+
+    #[test]
+    fn test_combined_filters() {
+        let mut positions = priced_portfolio();
+        let filter = PositionFilter {
+            asset_class: Some("stock".to_string()),
+            min_balance: Some(dec!(500)),
+            ..Default::default()
+        };
+        assert_eq!(filter.apply(&mut positions), 3);
+        assert_eq!(names(&positions), ["Apple", "Microsoft"]);
+
+        let mut positions = priced_portfolio();
+        let filter = PositionFilter {
+            asset_class: Some("Stock".to_string()),
+            search: Some("eunl".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(filter.apply(&mut positions), 4);
+        assert_eq!(names(&positions), ["iShares Core MSCI World"]);
+
+        let mut positions = priced_portfolio();
+        let filter = PositionFilter {
+            search: Some("o".to_string()),
+            min_balance: Some(dec!(1000)),
+            ..Default::default()
+        };
+        assert_eq!(filter.apply(&mut positions), 4);
+        assert_eq!(names(&positions), ["Vanguard Total Bond"]);
+    }
+
+    #[test]
+    fn test_unknown_tags_are_ignored() {
+        let positions = from_string(
+            r#"[
+                {"Name": "World ETF", "AssetClass": "Stocks", "Amount": 5, "Tags": ["Retirement"]},
+                {"Name": "Savings", "AssetClass": "Cash", "Amount": 100, "Tags": ["kids"]}
+            ]"#,
+        );
+        let filter = PositionFilter {
+            tags: vec!["retirement ".to_string(), "speculative".to_string()],
+            ..Default::default()
+        }
+        .without_unknown_tags(&positions);
+        assert_eq!(filter.tags, ["retirement "]);
+
+        let mut shown = positions.clone();
+        assert_eq!(filter.apply(&mut shown), 1);
+        assert_eq!(names(&shown), ["World ETF"]);
+
+        // with only unknown tags nothing is hidden
+        let filter = PositionFilter {
+            tags: vec!["speculative".to_string()],
+            ..Default::default()
+        }
+        .without_unknown_tags(&positions);
+        assert!(filter.is_empty());
+    }
+
+    #[test]
+    fn test_sort_by_balance() {
+        let mut positions = priced_portfolio();
+        PositionSort {
+            key: SortKey::Balance,
+            descending: true,
+        }
+        .apply(&mut positions);
+        assert_eq!(
+            names(&positions),
+            [
+                "Apple",
+                "Vanguard Total Bond",
+                "Microsoft",
+                "iShares Core MSCI World",
+                "Savings"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sort_by_name() {
+        let mut positions = priced_portfolio();
+        PositionSort {
+            key: SortKey::Name,
+            descending: false,
+        }
+        .apply(&mut positions);
+        assert_eq!(
+            names(&positions),
+            [
+                "Apple",
+                "iShares Core MSCI World",
+                "Microsoft",
+                "Savings",
+                "Vanguard Total Bond"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sort_by_gain() {
+        let mut positions = priced_portfolio();
+        PositionSort {
+            key: SortKey::Gain,
+            descending: true,
+        }
+        .apply(&mut positions);
+        assert_eq!(
+            names(&positions),
+            [
+                "Apple",
+                "Microsoft",
+                "Vanguard Total Bond",
+                "iShares Core MSCI World",
+                "Savings"
+            ]
+        );
+
+        PositionSort {
+            key: SortKey::Gain,
+            descending: false,
+        }
+        .apply(&mut positions);
+        assert_eq!(
+            names(&positions),
+            [
+                "Vanguard Total Bond",
+                "Microsoft",
+                "Apple",
+                "iShares Core MSCI World",
+                "Savings"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sort_by_asset_class() {
+        let mut positions = priced_portfolio();
+        PositionSort {
+            key: SortKey::AssetClass,
+            descending: false,
+        }
+        .apply(&mut positions);
+        assert_eq!(
+            names(&positions),
+            [
+                "Vanguard Total Bond",
+                "Savings",
+                "Apple",
+                "Microsoft",
+                "iShares Core MSCI World"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sort_key_from_str() {
+        assert_eq!("asset_class".parse(), Ok(SortKey::AssetClass));
+        assert_eq!("Gain".parse(), Ok(SortKey::Gain));
+        assert_eq!("ticker".parse::<SortKey>().ok(), None);
+    }
+}
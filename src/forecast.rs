@@ -0,0 +1,370 @@
+use crate::format::NumberFormat;
+use crate::position::{is_false, to_decimal, PortfolioPosition};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+// Name of the bucket the contributions go into if nothing is invested yet
+const CONTRIBUTIONS_BUCKET: &str = "Contributions";
+
+// Part of the portfolio that grows at one expected annual return in %
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ForecastBucket {
+    pub name: String,
+    pub balance: Decimal,
+    pub annual_return: Decimal,
+    // cash growing at its InterestRate
+    #[serde(skip_serializing_if = "is_false")]
+    pub interest_bearing: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ForecastYear {
+    pub year: u32,
+    // paid in during the year
+    pub contributions: Decimal,
+    // returns earned during the year
+    pub growth: Decimal,
+    // at the end of the year
+    pub balance: Decimal,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Forecast {
+    pub start_balance: Decimal,
+    pub monthly_contribution: Decimal,
+    pub buckets: Vec<ForecastBucket>,
+    pub years: Vec<ForecastYear>,
+}
+
+impl Forecast {
+    pub fn end_balance(&self) -> Decimal {
+        self.years
+            .last()
+            .map_or(self.start_balance, |year| year.balance)
+    }
+
+    pub fn total_contributions(&self) -> Decimal {
+        self.years.iter().map(|year| year.contributions).sum()
+    }
+
+    pub fn total_growth(&self) -> Decimal {
+        self.years.iter().map(|year| year.growth).sum()
+    }
+}
+
+// One bucket per asset class, at its expected return or `default_return` if it has
+// none. Cash with an InterestRate grows at that rate, in a bucket of its own.
+// Liabilities are left out, the forecast is about the assets.
+pub fn forecast_buckets(
+    positions: &[PortfolioPosition],
+    expected_returns: &HashMap<String, f64>,
+    default_return: f64,
+) -> Vec<ForecastBucket> {
+    let mut by_class: BTreeMap<&str, Decimal> = BTreeMap::new();
+    let mut buckets = Vec::new();
+    for position in positions {
+        let balance = position.get_balance();
+        if position.is_liability() || balance <= Decimal::ZERO {
+            continue;
+        }
+        match position
+            .get_interest_rate()
+            .filter(|_| !position.is_market_instrument())
+        {
+            Some(rate) => buckets.push(ForecastBucket {
+                name: position.get_name().to_string(),
+                balance,
+                annual_return: rate,
+                interest_bearing: true,
+            }),
+            None => *by_class.entry(position.get_asset_class()).or_default() += balance,
+        }
+    }
+
+    let class_buckets = by_class.into_iter().map(|(class, balance)| ForecastBucket {
+        name: class.to_string(),
+        balance,
+        annual_return: to_decimal(
+            expected_returns
+                .get(class)
+                .copied()
+                .unwrap_or(default_return),
+        ),
+        interest_bearing: false,
+    });
+    let mut buckets: Vec<ForecastBucket> = class_buckets.chain(buckets).collect();
+    if buckets.is_empty() {
+        buckets.push(ForecastBucket {
+            name: CONTRIBUTIONS_BUCKET.to_string(),
+            balance: Decimal::ZERO,
+            annual_return: to_decimal(default_return),
+            interest_bearing: false,
+        });
+    }
+    buckets
+}
+
+// Compounds every bucket monthly at a twelfth of its annual return. The contribution
+// is paid in at the end of each month and split like the starting balances, so the
+// allocation stays the same (split evenly if there are no balances yet).
+pub fn forecast(
+    buckets: Vec<ForecastBucket>,
+    monthly_contribution: Decimal,
+    years: u32,
+) -> Forecast {
+    let start_balance: Decimal = buckets.iter().map(|bucket| bucket.balance).sum();
+    let shares: Vec<Decimal> = buckets
+        .iter()
+        .map(|bucket| {
+            if start_balance > Decimal::ZERO {
+                bucket.balance / start_balance
+            } else {
+                Decimal::ONE / Decimal::from(buckets.len())
+            }
+        })
+        .collect();
+    let monthly_rates: Vec<Decimal> = buckets
+        .iter()
+        .map(|bucket| bucket.annual_return / Decimal::ONE_HUNDRED / Decimal::from(12))
+        .collect();
+
+    let mut balances: Vec<Decimal> = buckets.iter().map(|bucket| bucket.balance).collect();
+    let mut rows = Vec::new();
+    for year in 1..=years {
+        let start: Decimal = balances.iter().sum();
+        for _ in 0..12 {
+            for ((balance, rate), share) in balances.iter_mut().zip(&monthly_rates).zip(&shares) {
+                *balance += *balance * rate + monthly_contribution * share;
+            }
+        }
+        let end: Decimal = balances.iter().sum();
+        let contributions = monthly_contribution * Decimal::from(12);
+        rows.push(ForecastYear {
+            year,
+            contributions,
+            growth: end - start - contributions,
+            balance: end,
+        });
+    }
+
+    Forecast {
+        start_balance,
+        monthly_contribution,
+        buckets,
+        years: rows,
+    }
+}
+
+pub fn print_forecast(forecast: &Forecast, format: &NumberFormat) {
+    println!("Expected annual returns");
+    for bucket in &forecast.buckets {
+        println!(
+            "{0: >26} | {1: >16} | {2: >8}",
+            bucket.name,
+            format.money(bucket.balance),
+            format!("{}%", bucket.annual_return.normalize())
+        );
+    }
+
+    println!(
+        "\nForecast with {} contributed per month",
+        format.money(forecast.monthly_contribution)
+    );
+    println!(
+        "{0: >6} | {1: >16} | {2: >16} | {3: >16}",
+        "Year", "Contributions", "Growth", "Balance"
+    );
+    println!("=================================================================");
+    println!(
+        "{0: >6} | {1: >16} | {2: >16} | {3: >16}",
+        "Start",
+        "",
+        "",
+        format.money(forecast.start_balance)
+    );
+    for year in &forecast.years {
+        println!(
+            "{0: >6} | {1: >16} | {2: >16} | {3: >16}",
+            year.year,
+            format.money(year.contributions),
+            format.money(year.growth),
+            format.money(year.balance)
+        );
+    }
+    println!("=================================================================");
+    println!(
+        "{0: >6} | {1: >16} | {2: >16} | {3: >16}",
+        "Total",
+        format.money(forecast.total_contributions()),
+        format.money(forecast.total_growth()),
+        format.money(forecast.end_balance())
+    );
+}
+
+// One row per year, numbers with the separators of the format
+pub fn forecast_to_csv(forecast: &Forecast, format: &NumberFormat) -> Result<String, String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    let error = |e: csv::Error| format!("Failed to write CSV: {e}");
+
+    writer
+        .write_record(["Year", "Contributions", "Growth", "Balance"])
+        .map_err(error)?;
+    for year in &forecast.years {
+        writer
+            .write_record([
+                year.year.to_string(),
+                format.number(year.contributions),
+                format.number(year.growth),
+                format.number(year.balance),
+            ])
+            .map_err(error)?;
+    }
+
+    let bytes = writer
+        .into_inner()
+        .map_err(|e| format!("Failed to write CSV: {e}"))?;
+    String::from_utf8(bytes).map_err(|e| format!("Failed to write CSV: {e}"))
+}
+
+pub fn forecast_to_json(forecast: &Forecast) -> Result<String, String> {
+    serde_json::to_string_pretty(forecast).map_err(|e| format!("Failed to serialize data: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::to_f64;
+    use crate::provider::{priced_blocking, MockProvider};
+    use rust_decimal::dec;
+
+    fn bucket(name: &str, balance: Decimal, annual_return: Decimal) -> ForecastBucket {
+        ForecastBucket {
+            name: name.to_string(),
+            balance,
+            annual_return,
+            interest_bearing: false,
+        }
+    }
+
+    // FV = P (1 + i)^n + C ((1 + i)^n - 1) / i, contributions at the end of each month
+    fn future_value(start: f64, annual_return: f64, monthly: f64, months: i32) -> f64 {
+        let rate = annual_return / 100.0 / 12.0;
+        let growth = (1.0 + rate).powi(months);
+        start * growth + monthly * (growth - 1.0) / rate
+    }
+
+    fn assert_close(actual: Decimal, expected: f64) {
+        assert!(
+            (to_f64(actual) - expected).abs() < 1e-6,
+            "{actual} != {expected}"
+        );
+    }
+
+    #[test]
+    fn test_forecast_matches_future_value() {
+        let forecast = forecast(vec![bucket("Stocks", dec!(10000), dec!(6))], dec!(500), 10);
+        assert_eq!(forecast.years.len(), 10);
+        for year in &forecast.years {
+            let months = year.year as i32 * 12;
+            assert_close(year.balance, future_value(10000.0, 6.0, 500.0, months));
+            assert_eq!(year.contributions, dec!(6000));
+        }
+        assert_eq!(forecast.total_contributions(), dec!(60000));
+        assert_close(
+            forecast.total_growth(),
+            future_value(10000.0, 6.0, 500.0, 120) - 70000.0,
+        );
+
+        // the first year: 10000 at 6% plus 12 contributions
+        let first = &forecast.years[0];
+        assert_close(
+            first.growth,
+            future_value(10000.0, 6.0, 500.0, 12) - 16000.0,
+        );
+    }
+
+    #[test]
+    fn test_forecast_buckets_grow_at_their_rate() {
+        // without contributions every bucket compounds on its own
+        let forecast = forecast(
+            vec![
+                bucket("Stocks", dec!(6000), dec!(8)),
+                bucket("Bonds", dec!(4000), dec!(3)),
+            ],
+            Decimal::ZERO,
+            5,
+        );
+        let expected =
+            6000.0 * (1.0 + 0.08 / 12.0_f64).powi(60) + 4000.0 * (1.0 + 0.03 / 12.0_f64).powi(60);
+        assert_close(forecast.end_balance(), expected);
+
+        // contributions are split 60/40 like the balances
+        let with_contributions = super::forecast(
+            vec![
+                bucket("Stocks", dec!(6000), dec!(8)),
+                bucket("Bonds", dec!(4000), dec!(3)),
+            ],
+            dec!(100),
+            5,
+        );
+        let expected = future_value(6000.0, 8.0, 60.0, 60) + future_value(4000.0, 3.0, 40.0, 60);
+        assert_close(with_contributions.end_balance(), expected);
+    }
+
+    #[test]
+    fn test_forecast_buckets() {
+        let provider = MockProvider::new()
+            .with_price("EUNL.DE", 80.0)
+            .with_price("SPY", 500.0)
+            .with_price("GLD", 200.0);
+        let positions = priced_blocking(
+            &provider,
+            r#"[
+                {"Name": "World", "Ticker": "EUNL.DE", "AssetClass": "Stocks", "Amount": 100},
+                {"Name": "S&P 500", "Ticker": "SPY", "AssetClass": "Stocks", "Amount": 10},
+                {"Name": "Gold", "Ticker": "GLD", "AssetClass": "Commodities", "Amount": 5},
+                {"Name": "Savings", "AssetClass": "Cash", "Amount": 5000, "InterestRate": 2.5},
+                {"Name": "Wallet", "AssetClass": "Cash", "Amount": 200},
+                {"Name": "Mortgage", "AssetClass": "Liability", "Amount": 100000, "InterestRate": 4}
+            ]"#,
+        );
+        let returns = HashMap::from([("Stocks".to_string(), 7.0), ("Cash".to_string(), 1.0)]);
+
+        let buckets = forecast_buckets(&positions, &returns, 4.0);
+        assert_eq!(
+            buckets,
+            [
+                bucket("Cash", dec!(200), dec!(1)),
+                bucket("Commodities", dec!(1000), dec!(4)),
+                bucket("Stocks", dec!(13000), dec!(7)),
+                ForecastBucket {
+                    interest_bearing: true,
+                    ..bucket("Savings", dec!(5000), dec!(2.5))
+                },
+            ]
+        );
+
+        // with nothing invested the contributions grow at the default return
+        let buckets = forecast_buckets(&[], &returns, 4.0);
+        assert_eq!(buckets, [bucket("Contributions", Decimal::ZERO, dec!(4))]);
+        let forecast = forecast(buckets, dec!(500), 1);
+        assert_close(forecast.end_balance(), future_value(0.0, 4.0, 500.0, 12));
+    }
+
+    #[test]
+    fn test_forecast_outputs() {
+        let forecast = forecast(vec![bucket("Stocks", dec!(1000), dec!(12))], dec!(100), 2);
+        let csv = forecast_to_csv(&forecast, &NumberFormat::machine("EUR", 2)).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "Year,Contributions,Growth,Balance");
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].starts_with("1,1200.00,"));
+
+        let json: serde_json::Value =
+            serde_json::from_str(&forecast_to_json(&forecast).unwrap()).unwrap();
+        assert_eq!(json["monthly_contribution"], 100.0);
+        assert_eq!(json["years"][1]["year"], 2);
+        assert_eq!(json["buckets"][0]["name"], "Stocks");
+    }
+}
@@ -0,0 +1,412 @@
+use crate::position::{currency_decimals, round_to};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+const PERCENT_DECIMALS: u32 = 2;
+
+// Whether the currency symbol comes before ("$1.00") or after ("1,00 €") the number
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SymbolPlacement {
+    #[default]
+    Before,
+    After,
+}
+
+// Separators commonly used in a region
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NumberStyle {
+    // 1,234,567.89
+    En,
+    // 1.234.567,89
+    De,
+    // 1 234 567,89
+    Fr,
+    // 1'234'567.89
+    Ch,
+}
+
+impl FromStr for NumberStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_lowercase().as_str() {
+            "en" => Ok(NumberStyle::En),
+            "de" => Ok(NumberStyle::De),
+            "fr" => Ok(NumberStyle::Fr),
+            "ch" => Ok(NumberStyle::Ch),
+            _ => Err(format!(
+                "{s} is not a number format, expected en, de, fr or ch"
+            )),
+        }
+    }
+}
+
+impl NumberStyle {
+    fn separators(&self) -> (char, char) {
+        match self {
+            NumberStyle::En => ('.', ','),
+            NumberStyle::De => (',', '.'),
+            NumberStyle::Fr => (',', ' '),
+            NumberStyle::Ch => ('.', '\''),
+        }
+    }
+}
+
+// Decimal places of the amounts: shares (and fund units) can be fractional to 6,
+// crypto to 8 (a satoshi) and cash to the cent. Set in the number_format section
+// of the config file, unset fields keep these defaults.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AmountPrecision {
+    pub shares: u32,
+    pub crypto: u32,
+    pub cash: u32,
+}
+
+impl Default for AmountPrecision {
+    fn default() -> Self {
+        Self {
+            shares: 6,
+            crypto: 8,
+            cash: 2,
+        }
+    }
+}
+
+// The number_format section of the config file. Every field is optional,
+// unset fields keep the defaults of the configured currency.
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NumberFormatConfig {
+    // en, de, fr or ch
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub style: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decimal_separator: Option<char>,
+    // an empty string turns off the grouping of the digits
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub grouping_separator: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub symbol_placement: Option<SymbolPlacement>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub precision: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub amount_precision: Option<AmountPrecision>,
+}
+
+// Symbol and placement of a currency, currencies without a symbol use their code
+fn currency_symbol(currency: &str) -> (String, SymbolPlacement) {
+    match currency.to_uppercase().as_str() {
+        "USD" | "CAD" | "AUD" | "HKD" | "SGD" => ("$".to_string(), SymbolPlacement::Before),
+        "EUR" => ("€".to_string(), SymbolPlacement::After),
+        "GBP" => ("£".to_string(), SymbolPlacement::Before),
+        "JPY" => ("¥".to_string(), SymbolPlacement::Before),
+        code => (code.to_string(), SymbolPlacement::After),
+    }
+}
+
+// How numbers and money are rendered
+#[derive(Debug, Clone, PartialEq)]
+pub struct NumberFormat {
+    pub decimal_separator: char,
+    pub grouping_separator: Option<char>,
+    pub symbol: Option<String>,
+    pub symbol_placement: SymbolPlacement,
+    // decimal places of money
+    pub precision: u32,
+    // decimal places of the amounts per kind of position
+    pub amount_precision: AmountPrecision,
+}
+
+impl NumberFormat {
+    // English separators with the symbol and the minor unit of the currency
+    pub fn for_currency(currency: &str) -> Self {
+        let (symbol, symbol_placement) = currency_symbol(currency);
+        Self {
+            decimal_separator: '.',
+            grouping_separator: Some(','),
+            symbol: Some(symbol),
+            symbol_placement,
+            precision: currency_decimals(currency),
+            amount_precision: AmountPrecision::default(),
+        }
+    }
+
+    // Plain numbers for scripts and spreadsheets: 1234567.89, money is followed by the code
+    pub fn machine(currency: &str, precision: u32) -> Self {
+        Self {
+            decimal_separator: '.',
+            grouping_separator: None,
+            symbol: Some(currency.to_uppercase()),
+            symbol_placement: SymbolPlacement::After,
+            precision,
+            amount_precision: AmountPrecision::default(),
+        }
+    }
+
+    pub fn with_style(mut self, style: NumberStyle) -> Self {
+        let (decimal_separator, grouping_separator) = style.separators();
+        self.decimal_separator = decimal_separator;
+        self.grouping_separator = Some(grouping_separator);
+        self
+    }
+
+    pub fn with_config(mut self, config: &NumberFormatConfig) -> Self {
+        if let Some(style) = config.style.as_deref().and_then(|style| style.parse().ok()) {
+            self = self.with_style(style);
+        }
+        if let Some(decimal_separator) = config.decimal_separator {
+            self.decimal_separator = decimal_separator;
+        }
+        if let Some(grouping_separator) = &config.grouping_separator {
+            self.grouping_separator = grouping_separator.chars().next();
+        }
+        if let Some(symbol) = &config.symbol {
+            self.symbol = Some(symbol.clone()).filter(|symbol| !symbol.is_empty());
+        }
+        if let Some(symbol_placement) = config.symbol_placement {
+            self.symbol_placement = symbol_placement;
+        }
+        if let Some(precision) = config.precision {
+            self.precision = precision;
+        }
+        if let Some(amount_precision) = config.amount_precision {
+            self.amount_precision = amount_precision;
+        }
+        self
+    }
+
+    // The same separators with the symbol and the minor unit of another currency,
+    // for a position held in it. A format without a symbol stays without one.
+    pub fn in_currency(&self, currency: &str) -> Self {
+        let (symbol, symbol_placement) = currency_symbol(currency);
+        Self {
+            symbol: self.symbol.as_ref().map(|_| symbol),
+            symbol_placement,
+            precision: currency_decimals(currency),
+            ..self.clone()
+        }
+    }
+
+    pub fn with_precision(mut self, precision: u32) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    // The number with the given decimal places, rounded half away from zero
+    pub fn number_with(&self, value: Decimal, decimals: u32) -> String {
+        let rounded = round_to(value, decimals);
+        let repr = rounded.abs().to_string();
+        let (integer, fraction) = repr.split_once('.').unwrap_or((&repr, ""));
+
+        let mut formatted = String::new();
+        if rounded.is_sign_negative() && !rounded.is_zero() {
+            formatted.push('-');
+        }
+        for (i, digit) in integer.chars().enumerate() {
+            if i > 0 && (integer.len() - i).is_multiple_of(3) {
+                if let Some(separator) = self.grouping_separator {
+                    formatted.push(separator);
+                }
+            }
+            formatted.push(digit);
+        }
+        if decimals > 0 {
+            formatted.push(self.decimal_separator);
+            formatted.push_str(fraction);
+            formatted.extend(std::iter::repeat_n('0', decimals as usize - fraction.len()));
+        }
+        formatted
+    }
+
+    // The number with the precision of money, without a symbol
+    pub fn number(&self, value: Decimal) -> String {
+        self.number_with(value, self.precision)
+    }
+
+    // e.g. "-$1,234.50" or "-1.234,50 €"
+    pub fn money(&self, value: Decimal) -> String {
+        let number = self.number(value);
+        match (&self.symbol, self.symbol_placement) {
+            (None, _) => number,
+            (Some(symbol), SymbolPlacement::After) => format!("{number} {symbol}"),
+            (Some(symbol), SymbolPlacement::Before) => match number.strip_prefix('-') {
+                Some(number) => format!("-{symbol}{number}"),
+                None => format!("{symbol}{number}"),
+            },
+        }
+    }
+
+    // Money with an explicit sign for changes, e.g. "+$12.00"
+    pub fn signed_money(&self, value: Decimal) -> String {
+        self.with_sign(value, self.money(value))
+    }
+
+    // The number with an explicit sign, e.g. "+12.00"
+    pub fn signed_number(&self, value: Decimal) -> String {
+        self.with_sign(value, self.number(value))
+    }
+
+    // A percentage with two decimals, without the sign for table columns in %, e.g. "12.50"
+    pub fn percent_number(&self, value: Decimal) -> String {
+        self.number_with(value, PERCENT_DECIMALS)
+    }
+
+    // e.g. "12.50%"
+    pub fn percent(&self, value: Decimal) -> String {
+        format!("{}%", self.percent_number(value))
+    }
+
+    // A percentage or percentage points with an explicit sign, e.g. "+1.25"
+    pub fn signed_percent_number(&self, value: Decimal) -> String {
+        let number = self.percent_number(value);
+        if number.starts_with('-') || number == self.percent_number(Decimal::ZERO) {
+            number
+        } else {
+            format!("+{number}")
+        }
+    }
+
+    // A percentage with two decimals and an explicit sign, e.g. "+1.25%"
+    pub fn signed_percent(&self, value: Decimal) -> String {
+        format!("{}%", self.signed_percent_number(value))
+    }
+
+    // values that round to zero don't get a sign
+    fn with_sign(&self, value: Decimal, formatted: String) -> String {
+        if formatted.starts_with('-') || self.number(value) == self.number(Decimal::ZERO) {
+            formatted
+        } else {
+            format!("+{formatted}")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::dec;
+
+    fn de() -> NumberFormat {
+        NumberFormat::for_currency("EUR").with_style(NumberStyle::De)
+    }
+
+    fn us() -> NumberFormat {
+        NumberFormat::for_currency("USD")
+    }
+
+    #[test]
+    fn test_money() {
+        assert_eq!(de().money(dec!(1234567.891)), "1.234.567,89 €");
+        assert_eq!(us().money(dec!(1234567.891)), "$1,234,567.89");
+        assert_eq!(
+            NumberFormat::for_currency("JPY").money(dec!(1234567.891)),
+            "¥1,234,568"
+        );
+        assert_eq!(
+            NumberFormat::for_currency("CHF")
+                .with_style(NumberStyle::Ch)
+                .money(dec!(1234.5)),
+            "1'234.50 CHF"
+        );
+        assert_eq!(
+            NumberFormat::machine("eur", 2).money(dec!(1234567.891)),
+            "1234567.89 EUR"
+        );
+    }
+
+    #[test]
+    fn test_in_currency() {
+        assert_eq!(de().in_currency("GBP").money(dec!(1200)), "£1.200,00");
+        assert_eq!(us().in_currency("eur").money(dec!(1200)), "1,200.00 €");
+        assert_eq!(us().in_currency("JPY").money(dec!(1200.4)), "¥1,200");
+        assert_eq!(us().in_currency("SEK").money(dec!(1200)), "1,200.00 SEK");
+        let mut plain = us();
+        plain.symbol = None;
+        assert_eq!(plain.in_currency("GBP").money(dec!(1200)), "1,200.00");
+    }
+
+    #[test]
+    fn test_negative_and_zero() {
+        assert_eq!(de().money(dec!(-1234.5)), "-1.234,50 €");
+        assert_eq!(us().money(dec!(-1234.5)), "-$1,234.50");
+        assert_eq!(us().money(dec!(0.0)), "$0.00");
+        assert_eq!(us().money(dec!(-0.001)), "$0.00");
+        assert_eq!(us().signed_money(dec!(12.0)), "+$12.00");
+        assert_eq!(us().signed_money(dec!(-12.0)), "-$12.00");
+        assert_eq!(us().signed_money(dec!(0.001)), "$0.00");
+        assert_eq!(de().signed_number(dec!(1234.0)), "+1.234,00");
+        assert_eq!(us().signed_percent(dec!(1.255)), "+1.26%");
+        assert_eq!(de().signed_percent(dec!(-0.5)), "-0,50%");
+        assert_eq!(us().signed_percent(dec!(-0.001)), "0.00%");
+    }
+
+    #[test]
+    fn test_percent() {
+        assert_eq!(us().percent(dec!(1234.567)), "1,234.57%");
+        assert_eq!(de().percent(dec!(1234.567)), "1.234,57%");
+        assert_eq!(de().percent_number(dec!(-12.5)), "-12,50");
+        assert_eq!(us().signed_percent_number(dec!(2.5)), "+2.50");
+        assert_eq!(de().signed_percent_number(dec!(-0.125)), "-0,13");
+        assert_eq!(us().signed_percent_number(dec!(0.001)), "0.00");
+        assert_eq!(
+            NumberFormat::for_currency("CHF")
+                .with_style(NumberStyle::Ch)
+                .percent(dec!(-1500)),
+            "-1'500.00%"
+        );
+    }
+
+    #[test]
+    fn test_large_values() {
+        assert_eq!(us().number(dec!(1234567890123.45)), "1,234,567,890,123.45");
+        assert_eq!(de().money(dec!(5000000000000)), "5.000.000.000.000,00 €");
+        assert_eq!(
+            NumberFormat::machine("USD", 2).number(dec!(2500000000000000)),
+            "2500000000000000.00"
+        );
+    }
+
+    #[test]
+    fn test_rounding() {
+        assert_eq!(us().number(dec!(0.005)), "0.01");
+        assert_eq!(us().number(dec!(1.005)), "1.01");
+        assert_eq!(us().number(dec!(-0.005)), "-0.01");
+        assert_eq!(us().number(dec!(0.004)), "0.00");
+        assert_eq!(us().number(dec!(999.995)), "1,000.00");
+        assert_eq!(us().number_with(dec!(2.5), 0), "3");
+        assert_eq!(us().number_with(dec!(0.0000001), 2), "0.00");
+        assert_eq!(us().number_with(dec!(9.9999), 3), "10.000");
+    }
+
+    #[test]
+    fn test_with_config() {
+        let config: NumberFormatConfig = serde_json::from_str(
+            r#"{"style": "fr", "grouping_separator": "", "symbol_placement": "before", "precision": 3}"#,
+        )
+        .unwrap();
+        let format = NumberFormat::for_currency("EUR").with_config(&config);
+        assert_eq!(format.money(dec!(-1234.5)), "-€1234,500");
+
+        let config: NumberFormatConfig = serde_json::from_str(r#"{"symbol": ""}"#).unwrap();
+        assert_eq!(us().with_config(&config).money(dec!(1.0)), "1.00");
+        assert_eq!(
+            us().with_config(&NumberFormatConfig::default())
+                .money(dec!(1.0)),
+            "$1.00"
+        );
+    }
+
+    #[test]
+    fn test_number_style_from_str() {
+        assert_eq!("DE".parse(), Ok(NumberStyle::De));
+        assert_eq!(
+            "us".parse::<NumberStyle>().unwrap_err(),
+            "us is not a number format, expected en, de, fr or ch"
+        );
+    }
+}
@@ -0,0 +1,327 @@
+use crate::format::NumberFormat;
+use crate::position::PortfolioPosition;
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+// Bucket for positions without an account
+pub const UNASSIGNED_ACCOUNT: &str = "(unassigned)";
+// Bucket for positions without tags
+pub const UNTAGGED: &str = "(untagged)";
+// Bucket for positions of no single portfolio, e.g. consolidated from several
+pub const COMBINED_PORTFOLIO: &str = "(combined)";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GroupBy {
+    Account,
+    AssetClass,
+    Tag,
+    Portfolio,
+}
+
+impl FromStr for GroupBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_lowercase().as_str() {
+            "account" => Ok(GroupBy::Account),
+            "asset_class" => Ok(GroupBy::AssetClass),
+            "tag" => Ok(GroupBy::Tag),
+            "portfolio" => Ok(GroupBy::Portfolio),
+            _ => Err(format!(
+                "{s} is not a grouping, expected account, asset_class, tag or portfolio"
+            )),
+        }
+    }
+}
+
+impl GroupBy {
+    fn keys(self, position: &PortfolioPosition) -> Vec<&str> {
+        match self {
+            GroupBy::Account => vec![account_of(position)],
+            GroupBy::AssetClass => vec![position.get_asset_class()],
+            GroupBy::Tag if position.get_tags().is_empty() => vec![UNTAGGED],
+            GroupBy::Tag => position.get_tags().iter().map(|t| t.as_str()).collect(),
+            GroupBy::Portfolio => vec![position.get_portfolio().unwrap_or(COMBINED_PORTFOLIO)],
+        }
+    }
+}
+
+// Positions sharing the same account, asset class, tag or portfolio
+#[derive(Debug)]
+pub struct PositionGroup<'a> {
+    pub name: String,
+    pub positions: Vec<&'a PortfolioPosition>,
+    pub subtotal: Decimal,
+}
+
+// Group the positions by account, asset class, tag or portfolio, sorted by group name.
+// Positions without an account (or tags, or a portfolio) are collected in a separate
+// group, which comes last.
+// When grouping by tag, a position shows up in every tag it carries, so the
+// subtotals can add up to more than the total portfolio value.
+pub fn group_positions(
+    positions: &[PortfolioPosition],
+    group_by: GroupBy,
+) -> Vec<PositionGroup<'_>> {
+    group_position_refs(positions, group_by)
+}
+
+// Like group_positions, for positions borrowed from elsewhere, e.g. the positions
+// of another group
+pub fn group_position_refs<'a>(
+    positions: impl IntoIterator<Item = &'a PortfolioPosition>,
+    group_by: GroupBy,
+) -> Vec<PositionGroup<'a>> {
+    let mut groups: Vec<PositionGroup> = Vec::new();
+
+    for position in positions {
+        for key in group_by.keys(position) {
+            let group = match groups.iter().position(|g| g.name == key) {
+                Some(index) => &mut groups[index],
+                None => {
+                    groups.push(PositionGroup {
+                        name: key.to_string(),
+                        positions: Vec::new(),
+                        subtotal: Decimal::ZERO,
+                    });
+                    groups.last_mut().unwrap()
+                }
+            };
+            group.positions.push(position);
+            group.subtotal += position.get_balance();
+        }
+    }
+
+    let is_catch_all =
+        |name: &str| name == UNASSIGNED_ACCOUNT || name == UNTAGGED || name == COMBINED_PORTFOLIO;
+    groups.sort_by(|a, b| {
+        is_catch_all(&a.name)
+            .cmp(&is_catch_all(&b.name))
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    groups
+}
+
+// Sum up the balances per tag. A position counts towards every tag it carries,
+// so the sum over all tags can exceed the total portfolio value.
+pub fn balance_by_tag(positions: &[PortfolioPosition]) -> HashMap<String, Decimal> {
+    let mut balances: HashMap<String, Decimal> = HashMap::new();
+
+    for position in positions {
+        for tag in position.get_tags() {
+            *balances.entry(tag.to_string()).or_default() += position.get_balance();
+        }
+    }
+    balances
+}
+
+// Like balance_by_tag, but treats the tags as a strict partition of the portfolio
+// and fails if any position carries more than one tag
+pub fn balance_by_tag_exclusive(
+    positions: &[PortfolioPosition],
+) -> Result<HashMap<String, Decimal>, String> {
+    if let Some(position) = positions.iter().find(|p| p.get_tags().len() > 1) {
+        return Err(format!(
+            "Position {} has more than one tag: {}",
+            position.get_name(),
+            position.get_tags().join(", ")
+        ));
+    }
+
+    Ok(balance_by_tag(positions))
+}
+
+// Print the balances per tag in descending order
+pub fn print_balance_by_tag(
+    balances: &HashMap<String, Decimal>,
+    exclusive: bool,
+    format: &NumberFormat,
+) {
+    let mut balances_vec: Vec<(&String, &Decimal)> = balances.iter().collect();
+    balances_vec.sort_by(|a, b| b.1.cmp(a.1));
+
+    println!("{0: >20} | {1: >12}", "Tag", "Balance");
+    println!("====================================");
+    for (tag, balance) in balances_vec {
+        println!("{tag: >20} | {: >12}", format.money(*balance));
+    }
+
+    if !exclusive {
+        println!("Note: positions with several tags are counted once per tag.");
+    }
+}
+
+// Sum up the balances per account, positions without one are in UNASSIGNED_ACCOUNT.
+// Every position is in exactly one account, so the sum is the total value.
+pub fn balance_by_account(positions: &[PortfolioPosition]) -> HashMap<String, Decimal> {
+    group_positions(positions, GroupBy::Account)
+        .into_iter()
+        .map(|group| (group.name, group.subtotal))
+        .collect()
+}
+
+// The balances of balance_by_account, split up per asset class
+pub fn balance_by_account_and_class(
+    positions: &[PortfolioPosition],
+) -> HashMap<String, HashMap<String, Decimal>> {
+    group_positions(positions, GroupBy::Account)
+        .into_iter()
+        .map(|account| {
+            let classes = group_position_refs(account.positions, GroupBy::AssetClass)
+                .into_iter()
+                .map(|class| (class.name, class.subtotal))
+                .collect();
+            (account.name, classes)
+        })
+        .collect()
+}
+
+fn account_of(position: &PortfolioPosition) -> &str {
+    position.get_account().unwrap_or(UNASSIGNED_ACCOUNT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::from_string;
+    use rust_decimal::dec;
+
+    #[test]
+    fn test_group_by_from_str() {
+        assert_eq!("account".parse(), Ok(GroupBy::Account));
+        assert_eq!("Asset_Class".parse(), Ok(GroupBy::AssetClass));
+        assert_eq!("tag".parse(), Ok(GroupBy::Tag));
+        assert_eq!("portfolio".parse(), Ok(GroupBy::Portfolio));
+        assert_eq!("broker".parse::<GroupBy>().ok(), None);
+    }
+
+    #[test]
+    fn test_group_by_account() {
+        let positions_str = std::fs::read_to_string("tests/fixtures/accounts.json").unwrap();
+        let positions = from_string(&positions_str);
+        let groups = group_positions(&positions, GroupBy::Account);
+
+        let names: Vec<&str> = groups.iter().map(|g| g.name.as_str()).collect();
+        assert_eq!(names, ["Bank", "Broker A", "Broker B", UNASSIGNED_ACCOUNT]);
+
+        let subtotals: Vec<Decimal> = groups.iter().map(|g| g.subtotal).collect();
+        assert_eq!(subtotals, [dec!(2500), dec!(1500), dec!(800), dec!(50)]);
+        assert_eq!(groups[1].positions.len(), 2);
+
+        let grand_total: Decimal = groups.iter().map(|g| g.subtotal).sum();
+        let flat_total: Decimal = positions.iter().map(|p| p.get_balance()).sum();
+        assert_eq!(grand_total, dec!(4850));
+        assert_eq!(grand_total, flat_total);
+    }
+
+    #[test]
+    fn test_group_by_overlapping_tags() {
+        let positions = tagged_portfolio();
+        let groups = group_positions(&positions, GroupBy::Tag);
+
+        let names: Vec<&str> = groups.iter().map(|g| g.name.as_str()).collect();
+        assert_eq!(names, ["Global", "Retirement", UNTAGGED]);
+
+        let subtotals: Vec<Decimal> = groups.iter().map(|g| g.subtotal).collect();
+        assert_eq!(subtotals, [dec!(700), dec!(500), dec!(300)]);
+        // World ETF is counted in both of its tags
+        assert_eq!(subtotals.iter().sum::<Decimal>(), dec!(1500));
+    }
+
+    #[test]
+    fn test_group_by_asset_class() {
+        let positions_str = std::fs::read_to_string("tests/fixtures/accounts.json").unwrap();
+        let positions = from_string(&positions_str);
+        let groups = group_positions(&positions, GroupBy::AssetClass);
+
+        let names: Vec<&str> = groups.iter().map(|g| g.name.as_str()).collect();
+        assert_eq!(names, ["Bonds", "Cash", "Stocks"]);
+        assert_eq!(groups[1].subtotal, dec!(2550));
+    }
+
+    fn tagged_portfolio() -> Vec<PortfolioPosition> {
+        from_string(
+            r#"[
+                {"Name": "World ETF", "AssetClass": "Stocks", "Amount": 500, "Tags": ["Retirement", "Global"]},
+                {"Name": "Tech ETF", "AssetClass": "Stocks", "Amount": 200, "Tags": ["Global"]},
+                {"Name": "Savings", "AssetClass": "Cash", "Amount": 300}
+            ]"#,
+        )
+    }
+
+    #[test]
+    fn test_balance_by_tag() {
+        let balances = balance_by_tag(&tagged_portfolio());
+        assert_eq!(balances.len(), 2);
+        assert_eq!(balances["Retirement"], dec!(500));
+        assert_eq!(balances["Global"], dec!(700));
+    }
+
+    #[test]
+    fn test_balance_by_tag_exclusive() {
+        let positions = from_string(
+            r#"[
+                {"Name": "World ETF", "AssetClass": "Stocks", "Amount": 500, "Tags": ["Retirement"]},
+                {"Name": "Tech ETF", "AssetClass": "Stocks", "Amount": 200, "Tags": ["Speculative"]},
+                {"Name": "Savings", "AssetClass": "Cash", "Amount": 300, "Tags": ["Retirement"]}
+            ]"#,
+        );
+
+        let balances = balance_by_tag_exclusive(&positions).unwrap();
+        assert_eq!(balances["Retirement"], dec!(800));
+        assert_eq!(balances["Speculative"], dec!(200));
+    }
+
+    #[test]
+    fn test_balance_by_tag_exclusive_rejects_multiple_tags() {
+        let result = balance_by_tag_exclusive(&tagged_portfolio());
+        assert_eq!(
+            result,
+            Err("Position World ETF has more than one tag: Retirement, Global".to_string())
+        );
+    }
+
+    #[test]
+    fn test_balance_by_account() {
+        let positions_str = std::fs::read_to_string("tests/fixtures/accounts.json").unwrap();
+        let positions = from_string(&positions_str);
+        let balances = balance_by_account(&positions);
+
+        assert_eq!(balances.len(), 4);
+        assert_eq!(balances["Broker A"], dec!(1500));
+        assert_eq!(balances["Broker B"], dec!(800));
+        assert_eq!(balances[UNASSIGNED_ACCOUNT], dec!(50));
+        assert_eq!(balances.values().sum::<Decimal>(), dec!(4850));
+    }
+
+    #[test]
+    fn test_balance_by_account_and_class() {
+        // the same ticker in a taxable account and an IRA
+        let positions = from_string(
+            r#"[
+                {"Name": "S&P 500", "Ticker": "VOO", "AssetClass": "Stocks", "Amount": 10, "ManualPrice": 500, "Account": "Taxable"},
+                {"Name": "S&P 500 (IRA)", "Ticker": "VOO", "AssetClass": "Stocks", "Amount": 4, "ManualPrice": 500, "Account": "IRA"},
+                {"Name": "Bonds (IRA)", "AssetClass": "Bonds", "Amount": 3000, "Account": "IRA"},
+                {"Name": "Settlement", "AssetClass": "Cash", "Amount": 200, "Account": "Taxable"},
+                {"Name": "Wallet", "AssetClass": "Cash", "Amount": 50}
+            ]"#,
+        );
+        let balances = balance_by_account_and_class(&positions);
+
+        assert_eq!(balances.len(), 3);
+        assert_eq!(balances["Taxable"]["Stocks"], dec!(5000));
+        assert_eq!(balances["Taxable"]["Cash"], dec!(200));
+        assert_eq!(balances["IRA"]["Stocks"], dec!(2000));
+        assert_eq!(balances["IRA"]["Bonds"], dec!(3000));
+        assert_eq!(balances[UNASSIGNED_ACCOUNT].len(), 1);
+        assert_eq!(balances[UNASSIGNED_ACCOUNT]["Cash"], dec!(50));
+
+        // the classes add up to the balance of the account
+        let by_account = balance_by_account(&positions);
+        for (account, classes) in &balances {
+            assert_eq!(classes.values().sum::<Decimal>(), by_account[account]);
+        }
+        assert_eq!(by_account["IRA"], dec!(5000));
+    }
+}
@@ -0,0 +1,413 @@
+use crate::format::NumberFormat;
+use crate::position::{to_decimal, to_f64, PortfolioPosition};
+use chrono::prelude::*;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+// The default tree of the database maps the time of each run to the total value,
+// this tree maps the same times to the balance of every position
+const POSITIONS_TREE: &str = "positions";
+
+// Keys of both trees, they sort chronologically
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+// Bar heights from 1/8 to a full cell
+const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+struct SnapshotEntry {
+    name: String,
+    #[serde(default)]
+    ticker: Option<String>,
+    asset_class: String,
+    balance: Decimal,
+}
+
+// Which values to chart
+#[derive(Debug, Clone, PartialEq)]
+pub enum HistorySelector {
+    Total,
+    // case-insensitive like the --class filter
+    AssetClass(String),
+    // case-insensitive name or ticker
+    Position(String),
+}
+
+impl HistorySelector {
+    fn matches(&self, entry: &SnapshotEntry) -> bool {
+        match self {
+            HistorySelector::Total => true,
+            HistorySelector::AssetClass(class) => entry.asset_class.eq_ignore_ascii_case(class),
+            HistorySelector::Position(name) => {
+                entry.name.eq_ignore_ascii_case(name)
+                    || entry
+                        .ticker
+                        .as_ref()
+                        .is_some_and(|ticker| ticker.eq_ignore_ascii_case(name))
+            }
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            HistorySelector::Total => "Total value".to_string(),
+            HistorySelector::AssetClass(class) => format!("Value of {class}"),
+            HistorySelector::Position(name) => format!("Value of {name}"),
+        }
+    }
+}
+
+// Stores the total and the balance of every position at `time`
+pub fn store_snapshot(
+    db: &sled::Db,
+    positions: &[PortfolioPosition],
+    total: Decimal,
+    time: NaiveDateTime,
+) -> Result<(), String> {
+    let error = |e: sled::Error| format!("Database error: {e}");
+    let key = time.format(TIME_FORMAT).to_string();
+    let entries: Vec<SnapshotEntry> = positions
+        .iter()
+        .map(|position| SnapshotEntry {
+            name: position.get_name().to_string(),
+            ticker: position.get_ticker().map(|ticker| ticker.to_string()),
+            asset_class: position.get_asset_class().to_string(),
+            balance: position.get_balance(),
+        })
+        .collect();
+    let entries =
+        serde_json::to_vec(&entries).map_err(|e| format!("Failed to serialize data: {e}"))?;
+
+    db.insert(&key, total.to_string().as_bytes())
+        .map_err(error)?;
+    db.open_tree(POSITIONS_TREE)
+        .and_then(|tree| tree.insert(&key, entries))
+        .map_err(error)?;
+    // block until all operations are stable on disk
+    db.flush().map_err(error)?;
+    Ok(())
+}
+
+fn day_of(key: &[u8]) -> Option<NaiveDate> {
+    let key = String::from_utf8_lossy(key);
+    NaiveDateTime::parse_from_str(&key, TIME_FORMAT)
+        .ok()
+        .map(|time| time.date())
+}
+
+// The value of the last snapshot of each day, oldest first. Days without a
+// snapshot are left out. The total comes from the default tree, which goes back
+// further than the balances of the positions.
+pub fn load_history(
+    db: &sled::Db,
+    selector: &HistorySelector,
+) -> Result<Vec<(NaiveDate, f64)>, String> {
+    let error = |e: sled::Error| format!("Database error: {e}");
+    let mut history: Vec<(NaiveDate, f64)> = Vec::new();
+    let mut push = |day: NaiveDate, value: f64| match history.last_mut() {
+        Some((last_day, last_value)) if *last_day == day => *last_value = value,
+        _ => history.push((day, value)),
+    };
+
+    if *selector == HistorySelector::Total {
+        for item in db.iter() {
+            let (key, value) = item.map_err(error)?;
+            let value = String::from_utf8_lossy(&value).parse::<f64>();
+            if let (Some(day), Ok(value)) = (day_of(&key), value) {
+                push(day, value);
+            }
+        }
+    } else {
+        for item in db.open_tree(POSITIONS_TREE).map_err(error)?.iter() {
+            let (key, value) = item.map_err(error)?;
+            let (Some(day), Ok(entries)) = (
+                day_of(&key),
+                serde_json::from_slice::<Vec<SnapshotEntry>>(&value),
+            ) else {
+                continue;
+            };
+            // snapshots without the position or class don't count as 0
+            let matching: Vec<&SnapshotEntry> = entries
+                .iter()
+                .filter(|entry| selector.matches(entry))
+                .collect();
+            if !matching.is_empty() {
+                let balance: Decimal = matching.iter().map(|entry| entry.balance).sum();
+                push(day, to_f64(balance));
+            }
+        }
+    }
+    Ok(history)
+}
+
+// The stored values as they are, without the interpolated days of the chart
+pub fn history_to_csv(
+    history: &[(NaiveDate, f64)],
+    format: &NumberFormat,
+) -> Result<String, String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    let error = |e: csv::Error| format!("Failed to write CSV: {e}");
+
+    writer.write_record(["Date", "Value"]).map_err(error)?;
+    for (day, value) in history {
+        writer
+            .write_record([
+                day.format("%Y-%m-%d").to_string(),
+                format.number(to_decimal(*value)),
+            ])
+            .map_err(error)?;
+    }
+
+    let bytes = writer
+        .into_inner()
+        .map_err(|e| format!("Failed to write CSV: {e}"))?;
+    String::from_utf8(bytes).map_err(|e| format!("Failed to write CSV: {e}"))
+}
+
+// Value at `day` on the straight line between the snapshots around it
+fn interpolate(history: &[(NaiveDate, f64)], day: f64, first: NaiveDate) -> f64 {
+    let offset = |date: NaiveDate| (date - first).num_days() as f64;
+    let next = history.partition_point(|(date, _)| offset(*date) < day);
+    match (
+        next.checked_sub(1).map(|i| history[i]),
+        history.get(next).copied(),
+    ) {
+        (Some((before, low)), Some((after, high))) => {
+            let span = offset(after) - offset(before);
+            low + (high - low) * (day - offset(before)) / span
+        }
+        (None, Some((_, value))) | (Some((_, value)), None) => value,
+        (None, None) => 0.0,
+    }
+}
+
+// The values of `columns` days spread evenly from the first to the last snapshot
+// (at most one per day), the days between snapshots on the line between them
+pub fn resample(history: &[(NaiveDate, f64)], columns: usize) -> Vec<f64> {
+    let (Some((first, _)), Some((last, _))) = (history.first(), history.last()) else {
+        return Vec::new();
+    };
+    let days = (*last - *first).num_days() as usize + 1;
+    let columns = columns.clamp(1, days);
+    (0..columns)
+        .map(|column| {
+            let day = if columns == 1 {
+                (days - 1) as f64
+            } else {
+                column as f64 * (days - 1) as f64 / (columns - 1) as f64
+            };
+            interpolate(history, day, *first)
+        })
+        .collect()
+}
+
+// A block chart of the history `height` lines high (at least 2), with the maximum and the minimum
+// on the axis, the first and last day below it and the latest value. The history is
+// stretched over the width (one column per day if it's shorter): days between two
+// snapshots are drawn on the line between them.
+pub fn render_history_chart(
+    title: &str,
+    history: &[(NaiveDate, f64)],
+    width: usize,
+    height: usize,
+    format: &NumberFormat,
+) -> String {
+    let (Some((first, _)), Some((last, latest))) = (history.first(), history.last()) else {
+        return format!("{title}: no history yet, it is stored by every run of balances.\n");
+    };
+    // at least the maximum and the minimum
+    let height = height.max(2);
+    let max = history
+        .iter()
+        .map(|(_, value)| *value)
+        .fold(f64::MIN, f64::max);
+    let min = history
+        .iter()
+        .map(|(_, value)| *value)
+        .fold(f64::MAX, f64::min);
+    let max_label = format.money(to_decimal(max));
+    let min_label = format.money(to_decimal(min));
+    let label_width = max_label.chars().count().max(min_label.chars().count());
+
+    let days = (*last - *first).num_days() as usize + 1;
+    let columns = width.saturating_sub(label_width + 2).clamp(1, days);
+    // eighths of a cell per column
+    let levels: Vec<usize> = resample(history, columns)
+        .into_iter()
+        .map(|value| {
+            if max > min {
+                let scaled = (value - min) / (max - min) * (height * 8 - 1) as f64;
+                scaled.round() as usize + 1
+            } else {
+                height * 4
+            }
+        })
+        .collect();
+
+    let mut chart = format!(
+        "{title} from {} to {}\n",
+        first.format("%Y-%m-%d"),
+        last.format("%Y-%m-%d")
+    );
+    for row in (0..height).rev() {
+        let label = match row {
+            _ if row == height - 1 => &max_label,
+            0 => &min_label,
+            _ => "",
+        };
+        let axis = if label.is_empty() { '│' } else { '┤' };
+        let bars: String = levels
+            .iter()
+            .map(|level| match level.saturating_sub(row * 8) {
+                0 => ' ',
+                filled => BLOCKS[filled.min(8) - 1],
+            })
+            .collect();
+        chart.push_str(&format!(
+            "{label:>label_width$} {axis}{}\n",
+            bars.trim_end()
+        ));
+    }
+    chart.push_str(&format!(
+        "Latest: {} on {}\n",
+        format.money(to_decimal(*latest)),
+        last.format("%Y-%m-%d")
+    ));
+    chart
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::{priced_blocking, MockProvider};
+
+    fn day(date: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap()
+    }
+
+    fn history() -> Vec<(NaiveDate, f64)> {
+        // a gap from the 5th to the 12th
+        [
+            ("2024-01-01", 10000.0),
+            ("2024-01-02", 10400.0),
+            ("2024-01-03", 10200.0),
+            ("2024-01-04", 10900.0),
+            ("2024-01-05", 11200.0),
+            ("2024-01-12", 9800.0),
+            ("2024-01-13", 10100.0),
+            ("2024-01-14", 10600.0),
+            ("2024-01-15", 12000.0),
+            ("2024-01-16", 11500.0),
+        ]
+        .into_iter()
+        .map(|(date, value)| (day(date), value))
+        .collect()
+    }
+
+    #[test]
+    fn test_render_history_chart() {
+        let format = NumberFormat::for_currency("EUR");
+        let chart = render_history_chart("Total value", &history(), 60, 4, &format);
+        let expected = std::fs::read_to_string("tests/fixtures/chart.txt").unwrap();
+        assert_eq!(chart, expected);
+    }
+
+    #[test]
+    fn test_render_history_chart_width() {
+        let format = NumberFormat::for_currency("EUR");
+        // 16 days are squeezed into the 7 columns next to the 13 characters of the axis
+        let chart = render_history_chart("Total value", &history(), 20, 2, &format);
+        let lines: Vec<&str> = chart.lines().collect();
+        assert_eq!(lines[1].chars().count(), 20);
+        assert_eq!(lines[2], " 9,800.00 € ┤▂▆█▆▂▅█");
+
+        // a flat history is drawn in the middle, a single day as one column
+        let flat = [(day("2024-01-01"), 100.0), (day("2024-01-03"), 100.0)];
+        let chart = render_history_chart("Flat", &flat, 80, 2, &format);
+        assert_eq!(chart.lines().nth(1).unwrap(), "100.00 € ┤");
+        assert_eq!(chart.lines().nth(2).unwrap(), "100.00 € ┤███");
+        let single = render_history_chart("One", &flat[..1], 80, 1, &format);
+        assert_eq!(single.lines().nth(2).unwrap(), "100.00 € ┤█");
+
+        assert_eq!(
+            render_history_chart("Total value", &[], 80, 8, &format),
+            "Total value: no history yet, it is stored by every run of balances.\n"
+        );
+    }
+
+    #[test]
+    fn test_resample() {
+        // one value per day, the 7th on the line from 11200 on the 5th to 9800 on the 12th
+        let values = resample(&history(), 80);
+        assert_eq!(values.len(), 16);
+        assert_eq!(values[6], 10800.0);
+        assert_eq!(resample(&history(), 3), [10000.0, 10500.0, 11500.0]);
+        assert!(resample(&[], 80).is_empty());
+    }
+
+    #[test]
+    fn test_interpolate() {
+        let history = history();
+        let first = day("2024-01-01");
+        assert_eq!(interpolate(&history, 0.0, first), 10000.0);
+        // halfway between 11200 on the 5th and 9800 on the 12th
+        assert_eq!(interpolate(&history, 7.5, first), 10500.0);
+        assert_eq!(interpolate(&history, 15.0, first), 11500.0);
+    }
+
+    #[test]
+    fn test_snapshots() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let priced = |[apple, world, _]: [f64; 3]| -> Vec<PortfolioPosition> {
+            let provider = MockProvider::new()
+                .with_price("AAPL", apple)
+                .with_price("EUNL.DE", world);
+            priced_blocking(
+                &provider,
+                r#"[
+                    {"Name": "Apple", "Ticker": "AAPL", "AssetClass": "Stocks", "Amount": 10},
+                    {"Name": "World", "Ticker": "EUNL.DE", "AssetClass": "Stocks", "Amount": 20},
+                    {"Name": "Savings", "AssetClass": "Cash", "Amount": 500}
+                ]"#,
+            )
+        };
+        let time = |date: &str, hour| day(date).and_hms_opt(hour, 0, 0).unwrap();
+
+        // two runs on the 1st, the later one counts. Nothing on the 2nd.
+        for (spots, at) in [
+            ([100.0, 50.0, 0.0], time("2024-01-01", 9)),
+            ([110.0, 50.0, 0.0], time("2024-01-01", 18)),
+            ([120.0, 55.0, 0.0], time("2024-01-03", 9)),
+        ] {
+            let positions = priced(spots);
+            let total: Decimal = positions.iter().map(|p| p.get_balance()).sum();
+            store_snapshot(&db, &positions, total, at).unwrap();
+        }
+
+        assert_eq!(
+            load_history(&db, &HistorySelector::Total).unwrap(),
+            [(day("2024-01-01"), 2600.0), (day("2024-01-03"), 2800.0)]
+        );
+        assert_eq!(
+            load_history(&db, &HistorySelector::AssetClass("stocks".to_string())).unwrap(),
+            [(day("2024-01-01"), 2100.0), (day("2024-01-03"), 2300.0)]
+        );
+        assert_eq!(
+            load_history(&db, &HistorySelector::Position("aapl".to_string())).unwrap(),
+            [(day("2024-01-01"), 1100.0), (day("2024-01-03"), 1200.0)]
+        );
+        assert!(
+            load_history(&db, &HistorySelector::Position("MSFT".to_string()))
+                .unwrap()
+                .is_empty()
+        );
+
+        // the export only has the stored days
+        let csv = history_to_csv(
+            &load_history(&db, &HistorySelector::Total).unwrap(),
+            &NumberFormat::machine("EUR", 2),
+        )
+        .unwrap();
+        assert_eq!(csv, "Date,Value\n2024-01-01,2600.00\n2024-01-03,2800.00\n");
+    }
+}
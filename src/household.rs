@@ -0,0 +1,388 @@
+use crate::data_file::{check_writable, save_data};
+use crate::format::NumberFormat;
+use crate::grouping::COMBINED_PORTFOLIO;
+use crate::position::{
+    from_string, portfolio_name, position_defaults, to_data_string, to_string, total_investment,
+    PortfolioPosition,
+};
+use rust_decimal::Decimal;
+use std::path::Path;
+
+// Files of a directory with one of these extensions are read as data files
+const DATA_FILE_EXTENSIONS: [&str; 5] = ["json", "yaml", "yml", "toml", "gpg"];
+
+// The data files of the paths, a directory stands for the data files in it,
+// sorted by name. Other paths are kept as they are, so a missing file is
+// reported when it is read.
+pub fn expand_paths(paths: &[String]) -> Result<Vec<String>, String> {
+    let mut files = Vec::new();
+    for path in paths {
+        if !Path::new(path).is_dir() {
+            files.push(path.clone());
+            continue;
+        }
+        let entries =
+            std::fs::read_dir(path).map_err(|e| format!("Error reading directory {path}: {e}"))?;
+        let mut found: Vec<String> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|file| {
+                file.is_file()
+                    && file
+                        .extension()
+                        .and_then(|extension| extension.to_str())
+                        .is_some_and(|extension| {
+                            DATA_FILE_EXTENSIONS.contains(&extension.to_lowercase().as_str())
+                        })
+            })
+            .map(|file| file.to_string_lossy().into_owned())
+            .collect();
+        if found.is_empty() {
+            return Err(format!("No portfolio files found in {path}"));
+        }
+        found.sort();
+        files.extend(found);
+    }
+    Ok(files)
+}
+
+// The Name of the data, or else the file name without its extensions,
+// e.g. alex for alex.json.gpg
+pub fn name_of(path: &str, data: &str) -> String {
+    portfolio_name(data).unwrap_or_else(|| {
+        let file_name = Path::new(path)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        match file_name.split_once('.') {
+            Some((stem, _)) if !stem.is_empty() => stem.to_string(),
+            _ => file_name,
+        }
+    })
+}
+
+// The positions of several data files (path and JSON data) as one list, each
+// marked with the portfolio of its file. The names have to be unique to save
+// the positions back. A TotalInvestment belongs to a single file, so PercentOf
+// can't be resolved across files.
+pub fn combine(files: &[(String, String)]) -> Result<String, String> {
+    let mut names: Vec<String> = Vec::new();
+    let mut positions = Vec::new();
+    for (path, data) in files {
+        if total_investment(data).is_some() {
+            return Err(format!(
+                "{path}: TotalInvestment can't be used when several portfolio files are loaded"
+            ));
+        }
+        let name = name_of(path, data);
+        if names.contains(&name) {
+            return Err(format!("{path}: there is already a portfolio named {name}"));
+        }
+        for mut position in from_string(data) {
+            position.set_portfolio(Some(name.clone()));
+            positions.push(position);
+        }
+        names.push(name);
+    }
+    to_string(&positions)
+}
+
+// Writes every position back to the data file of its portfolio, without the
+// portfolio it was marked with. Nothing is written if one of the files can't be
+// saved or a position belongs to none of them.
+pub fn save_files(paths: &[String], positions: &[PortfolioPosition]) -> Result<(), String> {
+    let mut files = Vec::new();
+    for path in paths {
+        check_writable(path)?;
+        let data =
+            std::fs::read_to_string(path).map_err(|e| format!("Error reading file {path}: {e}"))?;
+        files.push((
+            path,
+            name_of(path, &data),
+            portfolio_name(&data),
+            position_defaults(&data),
+        ));
+    }
+    if let Some(position) = positions.iter().find(|position| {
+        !files
+            .iter()
+            .any(|(_, name, _, _)| position.get_portfolio() == Some(name.as_str()))
+    }) {
+        return Err(format!(
+            "{} doesn't belong to any of the portfolio files",
+            position.get_name()
+        ));
+    }
+
+    for (path, name, data_name, defaults) in files {
+        let own: Vec<PortfolioPosition> = positions
+            .iter()
+            .filter(|position| position.get_portfolio() == Some(name.as_str()))
+            .cloned()
+            .map(|mut position| {
+                position.set_portfolio(None);
+                position
+            })
+            .collect();
+        let data = to_data_string(&own, data_name.as_deref(), None, defaults.as_ref())?;
+        save_data(path, &data)?;
+    }
+    Ok(())
+}
+
+// Positions with the same ticker (in any case) share a row, the others are
+// rows of their own
+fn row_key(position: &PortfolioPosition) -> String {
+    match position.get_ticker() {
+        Some(ticker) => ticker.to_uppercase(),
+        None => position.get_name().to_string(),
+    }
+}
+
+// The balances with a column per portfolio, in the order the portfolios were
+// loaded, and the total of every row and column. A portfolio that doesn't hold
+// a position has an empty cell.
+pub fn render_side_by_side(positions: &[PortfolioPosition], format: &NumberFormat) -> String {
+    let mut portfolios: Vec<&str> = Vec::new();
+    // the name shown, the key and the balance per portfolio
+    let mut rows: Vec<(String, String, Vec<Option<Decimal>>)> = Vec::new();
+    for position in positions {
+        let portfolio = position.get_portfolio().unwrap_or(COMBINED_PORTFOLIO);
+        let column = match portfolios.iter().position(|name| *name == portfolio) {
+            Some(column) => column,
+            None => {
+                portfolios.push(portfolio);
+                portfolios.len() - 1
+            }
+        };
+        let key = row_key(position);
+        let row = match rows.iter().position(|(_, other, _)| *other == key) {
+            Some(row) => row,
+            None => {
+                rows.push((position.get_name().to_string(), key, Vec::new()));
+                rows.len() - 1
+            }
+        };
+        let balances = &mut rows[row].2;
+        balances.resize(portfolios.len(), None);
+        *balances[column].get_or_insert(Decimal::ZERO) += position.get_balance();
+    }
+
+    let mut lines: Vec<Vec<String>> = Vec::new();
+    let mut header = vec!["Name".to_string()];
+    header.extend(portfolios.iter().map(|name| name.to_string()));
+    header.push("Total".to_string());
+    lines.push(header);
+    let mut column_totals = vec![Decimal::ZERO; portfolios.len()];
+    for (name, _, balances) in &rows {
+        let mut line = vec![name.clone()];
+        for (column, column_total) in column_totals.iter_mut().enumerate() {
+            let balance = balances.get(column).copied().flatten();
+            *column_total += balance.unwrap_or_default();
+            line.push(balance.map(|b| format.money(b)).unwrap_or_default());
+        }
+        line.push(format.money(balances.iter().flatten().sum()));
+        lines.push(line);
+    }
+    let mut total = vec!["Total".to_string()];
+    total.extend(column_totals.iter().map(|balance| format.money(*balance)));
+    total.push(format.money(column_totals.iter().sum()));
+    lines.push(total);
+
+    let mut widths = vec![0; portfolios.len() + 2];
+    for line in &lines {
+        for (width, cell) in widths.iter_mut().zip(line) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let rule = "=".repeat(widths.iter().sum::<usize>() + 3 * (widths.len() - 1));
+    let last = lines.len() - 1;
+    let mut output = String::new();
+    for (index, line) in lines.iter().enumerate() {
+        if index == 1 || index == last {
+            output.push_str(&rule);
+            output.push('\n');
+        }
+        let cells: Vec<String> = line
+            .iter()
+            .zip(&widths)
+            .enumerate()
+            .map(|(column, (cell, &width))| {
+                if column == 0 {
+                    format!("{cell:<width$}")
+                } else {
+                    format!("{cell:>width$}")
+                }
+            })
+            .collect();
+        output.push_str(&cells.join(" | "));
+        output.push('\n');
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FixedClock;
+    use crate::export::balances_document;
+    use crate::grouping::{group_positions, GroupBy};
+    use crate::portfolio::Portfolio;
+    use crate::position::consolidate;
+    use crate::provider::priced_at;
+    use chrono::prelude::*;
+    use rust_decimal::dec;
+
+    const ALEX: &str = "tests/fixtures/household/alex.json";
+    const SAM: &str = "tests/fixtures/household/sam.json";
+
+    fn household() -> String {
+        let files: Vec<(String, String)> = expand_paths(&["tests/fixtures/household".to_string()])
+            .unwrap()
+            .into_iter()
+            .map(|path| {
+                let data = std::fs::read_to_string(&path).unwrap();
+                (path, data)
+            })
+            .collect();
+        combine(&files).unwrap()
+    }
+
+    // the fixtures are valued without quotes, at 5 per share
+    fn priced(data: &str) -> Vec<PortfolioPosition> {
+        priced_at(data, |_| 5.0)
+    }
+
+    #[test]
+    fn test_expand_paths() {
+        let paths =
+            expand_paths(&["tests/fixtures/household".to_string(), "b.json".to_string()]).unwrap();
+        assert_eq!(paths, [ALEX, SAM, "b.json"]);
+        assert_eq!(
+            expand_paths(&["src".to_string()]).unwrap_err(),
+            "No portfolio files found in src"
+        );
+    }
+
+    #[test]
+    fn test_name_of() {
+        assert_eq!(name_of("dir/alex.json.gpg", "[]"), "alex");
+        assert_eq!(name_of("sam", "[]"), "sam");
+        assert_eq!(
+            name_of("sam.json", r#"{"Name": "Sam's", "Positions": []}"#),
+            "Sam's"
+        );
+    }
+
+    #[test]
+    fn test_combine() {
+        let positions = from_string(&household());
+        let portfolios: Vec<Option<&str>> = positions.iter().map(|p| p.get_portfolio()).collect();
+        assert_eq!(
+            portfolios,
+            [Some("Alex"), Some("Alex"), Some("sam"), Some("sam")]
+        );
+
+        let alex = std::fs::read_to_string(ALEX).unwrap();
+        let error = combine(&[
+            ("a.json".to_string(), alex.clone()),
+            ("b.json".to_string(), alex),
+        ])
+        .unwrap_err();
+        assert_eq!(error, "b.json: there is already a portfolio named Alex");
+        assert!(combine(&[(
+            "a.json".to_string(),
+            r#"{"TotalInvestment": 100, "Positions": []}"#.to_string()
+        )])
+        .is_err());
+    }
+
+    #[test]
+    fn test_per_portfolio_subtotals() {
+        let positions = priced(&household());
+        let groups = group_positions(&positions, GroupBy::Portfolio);
+        let subtotals: Vec<(&str, Decimal)> = groups
+            .iter()
+            .map(|group| (group.name.as_str(), group.subtotal))
+            .collect();
+        assert_eq!(subtotals, [("Alex", dec!(1050)), ("sam", dec!(27.5))]);
+    }
+
+    #[test]
+    fn test_merge_keeps_the_breakdown() {
+        let positions = consolidate(priced(&household()));
+        assert_eq!(positions.len(), 3);
+        let apple = &positions[0];
+        assert_eq!(apple.get_amount(), dec!(15));
+        assert_eq!(apple.get_portfolio(), None);
+        assert_eq!(
+            apple.get_portfolio_amounts(),
+            [("Alex".to_string(), dec!(10)), ("sam".to_string(), dec!(5))]
+        );
+
+        let document = balances_document(&positions, &[], "EUR", Utc::now());
+        let json = serde_json::to_value(&document.positions).unwrap();
+        assert_eq!(json[0]["portfolios"][1]["name"], "sam");
+        assert_eq!(json[0]["portfolios"][1]["balance"], 25.0);
+        assert!(json[0].get("portfolio").is_none());
+        assert_eq!(json[1]["portfolio"], "Alex");
+    }
+
+    #[test]
+    fn test_side_by_side() {
+        let format = NumberFormat::machine("EUR", 2);
+        let table = render_side_by_side(&priced(&household()), &format);
+        assert_eq!(
+            table,
+            "\
+Name    |        Alex |       sam |       Total
+===============================================
+Apple   |   50.00 EUR | 25.00 EUR |   75.00 EUR
+Savings | 1000.00 EUR |           | 1000.00 EUR
+Bitcoin |             |  2.50 EUR |    2.50 EUR
+===============================================
+Total   | 1050.00 EUR | 27.50 EUR | 1077.50 EUR
+"
+        );
+    }
+
+    #[test]
+    fn test_save_back_to_the_files() {
+        let dir = std::env::temp_dir().join(format!("household_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for fixture in [ALEX, SAM] {
+            std::fs::copy(fixture, dir.join(Path::new(fixture).file_name().unwrap())).unwrap();
+        }
+        let paths = expand_paths(&[dir.to_string_lossy().into_owned()]).unwrap();
+        let files: Vec<(String, String)> = paths
+            .iter()
+            .map(|path| (path.clone(), std::fs::read_to_string(path).unwrap()))
+            .collect();
+
+        let mut portfolio = Portfolio::new();
+        for position in from_string(&combine(&files).unwrap()) {
+            portfolio.add_position(position);
+        }
+        let as_of = FixedClock(Utc.with_ymd_and_hms(2024, 2, 1, 0, 0, 0).unwrap());
+        let credited = portfolio.apply_interest_if_due(&as_of, 2);
+        assert_eq!(credited.len(), 1);
+        portfolio.positions[2].set_amount(dec!(6));
+        save_files(&paths, &portfolio.all_positions()).unwrap();
+
+        let alex = std::fs::read_to_string(&paths[0]).unwrap();
+        let sam = std::fs::read_to_string(&paths[1]).unwrap();
+        let mut stray = from_string(&sam)[0].clone();
+        stray.set_portfolio(Some("Kim".to_string()));
+        let error = save_files(&paths[..1], &[stray]).unwrap_err();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(error, "Apple doesn't belong to any of the portfolio files");
+        assert_eq!(portfolio_name(&alex), Some("Alex".to_string()));
+        assert!(!alex.contains("\"Portfolio\""));
+        let alex = from_string(&alex);
+        assert_eq!(alex.len(), 2);
+        assert!(alex[1].get_amount() > dec!(1000));
+        let sam = from_string(&sam);
+        assert_eq!(sam.len(), 2);
+        assert_eq!(sam[0].get_amount(), dec!(6));
+        assert_eq!(sam[1].get_name(), "Bitcoin");
+    }
+}
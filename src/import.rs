@@ -0,0 +1,506 @@
+use crate::position::{CsvColumns, PortfolioPosition};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde_json::{Map, Value};
+use std::str::FromStr;
+
+// How the cell of a column is read
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Text,
+    // comma separated, e.g. "Core, Global"
+    List,
+    Number,
+    Integer,
+    // YYYY-MM-DD or DD.MM.YYYY
+    Date,
+    // yes, true, x or 1
+    Flag,
+}
+
+// The fields of a position that a column can hold, the lists of dividends and
+// interest payments can't be written in a cell
+const FIELDS: [(&str, Kind); 26] = [
+    ("Name", Kind::Text),
+    ("Ticker", Kind::Text),
+    ("ISIN", Kind::Text),
+    ("AssetClass", Kind::Text),
+    ("Amount", Kind::Number),
+    ("PercentOf", Kind::Number),
+    ("Tags", Kind::List),
+    ("Account", Kind::Text),
+    ("Institution", Kind::Text),
+    ("Notes", Kind::Text),
+    ("Portfolio", Kind::Text),
+    ("CostBasis", Kind::Number),
+    ("PurchaseDate", Kind::Date),
+    ("LastVerified", Kind::Date),
+    ("Currency", Kind::Text),
+    ("ExpenseRatio", Kind::Number),
+    ("DividendYield", Kind::Number),
+    ("ManualPrice", Kind::Number),
+    ("PriceDate", Kind::Date),
+    ("PriceOverride", Kind::Number),
+    ("Watch", Kind::Flag),
+    ("InterestRate", Kind::Number),
+    ("PaymentFrequencyDays", Kind::Integer),
+    ("NextInterestPayment", Kind::Date),
+    ("LastInterestPayment", Kind::Date),
+    ("PaymentAmount", Kind::Number),
+];
+
+// the asset class of the rows with a ticker but without a class, broker exports
+// mostly list securities
+const CSV_ASSET_CLASS: &str = "Stocks";
+
+// The positions of a spreadsheet, and the rows that couldn't be read
+#[derive(Debug, Clone, Default)]
+pub struct CsvImport {
+    pub positions: Vec<PortfolioPosition>,
+    // "Line 4: ..." for every row that wasn't imported
+    pub errors: Vec<String>,
+    // the headers of the columns that are no field of a position
+    pub ignored_columns: Vec<String>,
+}
+
+// "Asset Class", "asset_class" and "ASSETCLASS" are all the AssetClass
+fn normalize(header: &str) -> String {
+    header
+        .chars()
+        .filter(|c| !matches!(c, ' ' | '_' | '-'))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+fn field(name: &str) -> Option<(&'static str, Kind)> {
+    let name = normalize(name);
+    FIELDS
+        .iter()
+        .find(|(field, _)| normalize(field) == name)
+        .copied()
+}
+
+// A --map value, e.g. "Qty=Amount" reads the column Qty as the Amount
+pub fn parse_column_map(value: &str) -> Result<(String, String), String> {
+    let invalid = || format!("Invalid value '{value}', expected COLUMN=FIELD, e.g. Qty=Amount");
+    let (column, target) = value.split_once('=').ok_or_else(invalid)?;
+    if column.trim().is_empty() {
+        return Err(invalid());
+    }
+    let Some((target, _)) = field(target) else {
+        return Err(format!(
+            "Invalid value '{value}', {} is not a field of a position",
+            target.trim()
+        ));
+    };
+    Ok((column.trim().to_string(), target.to_string()))
+}
+
+// The numbers of spreadsheets and broker exports can have a currency symbol, a percent sign and
+// thousands separators in either style: 1,234.50 and 1.234,50 are the same. A
+// single comma is a decimal comma (2,5 and 0,015) unless three digits after it
+// group the thousands of a number that doesn't start with 0 (1,234).
+fn parse_number(value: &str) -> Option<Decimal> {
+    let digits: String = value
+        .chars()
+        .filter(|c| !matches!(c, '$' | '€' | '£' | '%' | ' ' | '\''))
+        .collect();
+    let groups_thousands = |comma: usize| {
+        let integer = digits[..comma].trim_start_matches('-');
+        digits.len() - comma - 1 == 3 && !integer.is_empty() && !integer.starts_with('0')
+    };
+    let normalized = match (digits.rfind(','), digits.rfind('.')) {
+        (Some(comma), Some(dot)) if comma > dot => digits.replace('.', "").replace(',', "."),
+        (Some(comma), None) if digits.matches(',').count() == 1 && !groups_thousands(comma) => {
+            digits.replace(',', ".")
+        }
+        _ => digits.replace(',', ""),
+    };
+    Decimal::from_str(&normalized).ok()
+}
+
+fn parse_date(value: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .or_else(|_| NaiveDate::parse_from_str(value, "%d.%m.%Y"))
+        .ok()
+}
+
+// The cell as the JSON value of the field
+fn cell_value(kind: Kind, value: &str) -> Result<Value, &'static str> {
+    match kind {
+        Kind::Text => Ok(Value::String(value.to_string())),
+        Kind::List => Ok(Value::Array(
+            value
+                .split(',')
+                .map(|item| Value::String(item.trim().to_string()))
+                .collect(),
+        )),
+        Kind::Number => parse_number(value)
+            .and_then(|number| serde_json::to_value(number).ok())
+            .ok_or("is not a number"),
+        Kind::Integer => parse_number(value)
+            .filter(|number| number.fract().is_zero() && !number.is_sign_negative())
+            .and_then(|number| number.to_string().parse::<u64>().ok())
+            .map(Value::from)
+            .ok_or("is not a whole number"),
+        Kind::Date => parse_date(value)
+            .map(|date| Value::String(date.format("%Y-%m-%d").to_string()))
+            .ok_or("is not a date (YYYY-MM-DD or DD.MM.YYYY)"),
+        Kind::Flag => match value.to_lowercase().as_str() {
+            "yes" | "true" | "x" | "1" => Ok(Value::Bool(true)),
+            "no" | "false" | "0" => Ok(Value::Bool(false)),
+            _ => Err("is not yes or no"),
+        },
+    }
+}
+
+// Reads a spreadsheet or a broker export with a position per row. The headers are
+// the fields of a position in any case and with or without spaces, e.g. "Asset
+// Class" for the AssetClass. The renames, like those of --map, and the columns of
+// a broker export in the config (Symbol, Quantity, ...) are read as their fields as
+// well, other columns are ignored. Empty cells are left out. A row that can't be
+// read is an error with its line number, the other rows are still imported. Empty
+// rows are skipped.
+pub fn import_csv(
+    reader: impl std::io::Read,
+    columns: &CsvColumns,
+    renames: &[(String, String)],
+) -> Result<CsvImport, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(reader);
+    let headers = reader
+        .headers()
+        .map_err(|e| format!("The CSV data is not valid: {e}"))?
+        .clone();
+    let broker_columns = [
+        (&columns.symbol, "Ticker"),
+        (&columns.quantity, "Amount"),
+        (&columns.cost, "CostBasis"),
+        (&columns.asset_class, "AssetClass"),
+    ];
+
+    let mut import = CsvImport::default();
+    let mut fields: Vec<Option<(&'static str, Kind)>> = Vec::new();
+    for header in &headers {
+        let renamed = renames
+            .iter()
+            .find(|(column, _)| normalize(column) == normalize(header))
+            .map(|(_, target)| target.as_str())
+            .or_else(|| {
+                broker_columns
+                    .iter()
+                    .find(|(column, _)| normalize(column) == normalize(header))
+                    .map(|(_, target)| *target)
+            });
+        let found = field(renamed.unwrap_or(header));
+        if found.is_none() && !header.is_empty() {
+            import.ignored_columns.push(header.to_string());
+        }
+        fields.push(found);
+    }
+    if !fields
+        .iter()
+        .flatten()
+        .any(|(field, _)| matches!(*field, "Amount" | "PercentOf"))
+    {
+        return Err(
+            "The CSV data has no column of the Amount, name one Amount, set csv_columns.quantity \
+            to its header or pass --map COLUMN=Amount to import csv"
+                .to_string(),
+        );
+    }
+
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                let line = e.position().map_or(0, |position| position.line());
+                import
+                    .errors
+                    .push(format!("Line {line}: the row is not valid CSV: {e}"));
+                continue;
+            }
+        };
+        if record.iter().all(str::is_empty) {
+            continue;
+        }
+        let line = record.position().map_or(0, |position| position.line());
+        match read_row(&record, &headers, &fields) {
+            Ok(position) => import.positions.push(position),
+            Err(e) => import.errors.push(format!("Line {line}: {e}")),
+        }
+    }
+    Ok(import)
+}
+
+fn read_row(
+    record: &csv::StringRecord,
+    headers: &csv::StringRecord,
+    fields: &[Option<(&'static str, Kind)>],
+) -> Result<PortfolioPosition, String> {
+    let cells: Vec<(&str, Kind, &str, &str)> = fields
+        .iter()
+        .zip(headers.iter().zip(record.iter()))
+        .filter_map(|(field, (header, value))| {
+            let (field, kind) = (*field)?;
+            Some((field, kind, header, value)).filter(|_| !value.is_empty())
+        })
+        .collect();
+    let text = |wanted: &str| {
+        cells
+            .iter()
+            .find(|(field, ..)| *field == wanted)
+            .map(|(.., value)| *value)
+    };
+    let Some(label) = text("Name").or(text("Ticker")).or(text("ISIN")) else {
+        return Err("the row has no Name, Ticker or ISIN".to_string());
+    };
+
+    let mut position = Map::new();
+    for (field, kind, header, value) in &cells {
+        let value = cell_value(*kind, value)
+            .map_err(|problem| format!("the {header} of {label} {problem}: {value}"))?;
+        position.insert(field.to_string(), value);
+    }
+    if !position.contains_key("AssetClass") {
+        if text("Ticker").is_none() {
+            return Err(format!("the AssetClass of {label} is missing"));
+        }
+        position.insert("AssetClass".to_string(), Value::from(CSV_ASSET_CLASS));
+    }
+    if !position.contains_key("Amount") && !position.contains_key("PercentOf") {
+        // by the header of the column, e.g. the Quantity of a broker export
+        let amount = fields
+            .iter()
+            .zip(headers.iter())
+            .find(|(field, _)| field.is_some_and(|(field, _)| field == "Amount"))
+            .map_or("Amount", |(_, header)| header);
+        return Err(format!("the {amount} of {label} is missing"));
+    }
+    serde_json::from_value(Value::Object(position)).map_err(|e| format!("{label}: {e}"))
+}
+
+// The positions of a CSV data file, read like import_csv. Unlike the import, a row
+// that can't be read fails the file, so no position is left out silently.
+pub fn from_csv(
+    reader: impl std::io::Read,
+    columns: &CsvColumns,
+) -> Result<Vec<PortfolioPosition>, String> {
+    let import = import_csv(reader, columns, &[])?;
+    if !import.errors.is_empty() {
+        return Err(format!(
+            "{}, import the other rows with import csv",
+            import.errors.join("; ")
+        ));
+    }
+    Ok(import.positions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::dec;
+
+    fn renames() -> Vec<(String, String)> {
+        vec![parse_column_map("Qty=Amount").unwrap()]
+    }
+
+    fn spreadsheet() -> CsvImport {
+        let data = std::fs::read("tests/fixtures/spreadsheet.csv").unwrap();
+        import_csv(data.as_slice(), &CsvColumns::default(), &renames()).unwrap()
+    }
+
+    #[test]
+    fn test_import_csv() {
+        let import = spreadsheet();
+        let rows: Vec<(&str, Option<&str>, &str, Decimal)> = import
+            .positions
+            .iter()
+            .map(|position| {
+                (
+                    position.get_name(),
+                    position.get_ticker(),
+                    position.get_asset_class(),
+                    position.get_amount(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                (
+                    "Vanguard FTSE All-World",
+                    Some("VWCE.DE"),
+                    "Stocks",
+                    dec!(1234.5)
+                ),
+                ("Apple", Some("AAPL"), "Stocks", dec!(10)),
+                ("Savings account", None, "Cash", dec!(12500)),
+                ("Bitcoin", Some("BTC-USD"), "Crypto", dec!(0.015)),
+            ]
+        );
+        assert_eq!(import.ignored_columns, ["Comment"]);
+
+        // the cells of the other fields, empty ones are left out
+        let world = &import.positions[0];
+        assert_eq!(world.get_tags(), ["Core", "Global"]);
+        assert_eq!(world.get_notes(), Some("bought in tranches"));
+        assert_eq!(world.get_cost_basis(), Some(dec!(98.1)));
+        assert_eq!(
+            world.get_purchase_date().map(|date| date.date_naive()),
+            NaiveDate::from_ymd_opt(2021, 3, 15)
+        );
+        assert_eq!(import.positions[1].get_cost_basis(), None);
+        let savings = &import.positions[2];
+        assert_eq!(savings.get_interest_rate(), Some(dec!(2.5)));
+        assert_eq!(savings.get_payment_frequency_days(), Some(30));
+        assert_eq!(
+            savings
+                .get_given_next_interest_payment()
+                .map(|date| date.date_naive()),
+            NaiveDate::from_ymd_opt(2025, 1, 31)
+        );
+    }
+
+    #[test]
+    fn test_import_csv_errors() {
+        // the other rows are imported, the empty row is skipped
+        assert_eq!(
+            spreadsheet().errors,
+            [
+                "Line 6: the Qty of Broken row is not a number: ten",
+                "Line 7: the Purchase Date of Tesla is not a date (YYYY-MM-DD or DD.MM.YYYY): 31.02.2021",
+                "Line 9: the row has no Name, Ticker or ISIN",
+                "Line 10: the AssetClass of Loan is missing",
+            ]
+        );
+
+        let import = |data: &str| import_csv(data.as_bytes(), &CsvColumns::default(), &[]);
+        assert_eq!(
+            import("Name,Qty\nApple,1\n").unwrap_err(),
+            "The CSV data has no column of the Amount, name one Amount, set csv_columns.quantity \
+            to its header or pass --map COLUMN=Amount to import csv"
+        );
+        assert_eq!(
+            import("Name,amount,Asset Class\nSavings,,Cash\n")
+                .unwrap()
+                .errors,
+            ["Line 2: the amount of Savings is missing"]
+        );
+        assert_eq!(
+            import("Ticker,Amount,Payment Frequency Days\nSPY,1,7.5\n")
+                .unwrap()
+                .errors,
+            ["Line 2: the Payment Frequency Days of SPY is not a whole number: 7.5"]
+        );
+    }
+
+    #[test]
+    fn test_parse_column_map() {
+        assert_eq!(
+            parse_column_map(" Qty = amount").unwrap(),
+            ("Qty".to_string(), "Amount".to_string())
+        );
+        assert_eq!(
+            parse_column_map("Rate=interest rate").unwrap().1,
+            "InterestRate"
+        );
+        assert_eq!(
+            parse_column_map("Qty=Amout").unwrap_err(),
+            "Invalid value 'Qty=Amout', Amout is not a field of a position"
+        );
+        assert!(parse_column_map("Amount").is_err());
+    }
+
+    #[test]
+    fn test_parse_number_and_date() {
+        assert_eq!(parse_number("$1,234.50"), Some(dec!(1234.50)));
+        assert_eq!(parse_number("1.234,50 €"), Some(dec!(1234.50)));
+        assert_eq!(parse_number("2,5%"), Some(dec!(2.5)));
+        assert_eq!(parse_number("1,234"), Some(dec!(1234)));
+        assert_eq!(parse_number("0,015"), Some(dec!(0.015)));
+        assert_eq!(parse_number("1,234,567"), Some(dec!(1234567)));
+        assert_eq!(parse_number("-0.5"), Some(dec!(-0.5)));
+        assert_eq!(parse_number("n/a"), None);
+        assert_eq!(
+            parse_date("2024-02-29"),
+            NaiveDate::from_ymd_opt(2024, 2, 29)
+        );
+        assert_eq!(
+            parse_date("01.12.2023"),
+            NaiveDate::from_ymd_opt(2023, 12, 1)
+        );
+        assert_eq!(parse_date("12/01/2023"), None);
+    }
+
+    #[test]
+    fn test_from_csv() {
+        let data = std::fs::read("tests/fixtures/broker.csv").unwrap();
+        let positions = from_csv(data.as_slice(), &CsvColumns::default()).unwrap();
+        let rows: Vec<(Option<&str>, Decimal, Option<Decimal>, &str)> = positions
+            .iter()
+            .map(|position| {
+                (
+                    position.get_ticker(),
+                    position.get_amount(),
+                    position.get_cost_basis(),
+                    position.get_asset_class(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                (Some("AAPL"), dec!(10), Some(dec!(150.25)), "Stocks"),
+                (Some("VWCE.DE"), dec!(1200.5), Some(dec!(98.10)), "ETF"),
+                (Some("MSFT"), dec!(2), None, "Stocks"),
+            ]
+        );
+        // the columns of the export that are fields of a position
+        assert_eq!(positions[0].get_account(), Some("Brokerage"));
+
+        // other headers, of any case
+        let columns = CsvColumns {
+            symbol: "ticker".to_string(),
+            quantity: "shares".to_string(),
+            ..Default::default()
+        };
+        let positions = from_csv("Ticker,Shares\nSPY,3\n".as_bytes(), &columns).unwrap();
+        assert_eq!(positions[0].get_ticker(), Some("SPY"));
+        assert_eq!(positions[0].get_amount(), dec!(3));
+
+        // a spreadsheet, with the numbers of either style
+        let data = "Name,Ticker,Asset Class,Amount,Cost Basis\n\
+            Apple,AAPL,Stocks,10,\"1.234,50\"\n\
+            Cash,,Cash,100,\n";
+        let positions = from_csv(data.as_bytes(), &CsvColumns::default()).unwrap();
+        assert_eq!(positions.len(), 2);
+        assert_eq!(positions[0].get_cost_basis(), Some(dec!(1234.50)));
+    }
+
+    #[test]
+    fn test_from_csv_errors() {
+        let error = |data: &str| from_csv(data.as_bytes(), &CsvColumns::default()).unwrap_err();
+        assert_eq!(
+            error("Symbol,Quantity\nAAPL,10\nMSFT,\n"),
+            "Line 3: the Quantity of MSFT is missing, import the other rows with import csv"
+        );
+        assert_eq!(
+            error("Symbol,Quantity\nAAPL,ten\nMSFT,\n"),
+            "Line 2: the Quantity of AAPL is not a number: ten; \
+            Line 3: the Quantity of MSFT is missing, import the other rows with import csv"
+        );
+        assert_eq!(
+            error("Symbol,Quantity\n,10\n"),
+            "Line 2: the row has no Name, Ticker or ISIN, import the other rows with import csv"
+        );
+        assert_eq!(
+            error("Name,Amount\nCash,100\n"),
+            "Line 2: the AssetClass of Cash is missing, import the other rows with import csv"
+        );
+        assert!(error("Symbol,Shares\nAAPL,10\n")
+            .starts_with("The CSV data has no column of the Amount"));
+    }
+}
@@ -0,0 +1,257 @@
+use crate::format::NumberFormat;
+use crate::position::PortfolioPosition;
+use chrono::prelude::*;
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+
+// Interest and dividends of one month or year
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct IncomeTotals {
+    pub interest: Decimal,
+    pub dividends: Decimal,
+}
+
+impl IncomeTotals {
+    pub fn total(&self) -> Decimal {
+        self.interest + self.dividends
+    }
+}
+
+// Expected income of a position over the next 12 months
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProjectedIncome {
+    pub name: String,
+    pub interest: Decimal,
+    pub dividends: Decimal,
+}
+
+// Realized income comes from the Dividends and InterestPayments logged in the
+// data file, projected income from the DividendYield and the InterestRate
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct IncomeReport {
+    // months as YYYY-MM
+    pub realized_by_month: BTreeMap<String, IncomeTotals>,
+    pub realized_by_year: BTreeMap<i32, IncomeTotals>,
+    // in the order of the positions, only positions with income
+    pub realized_by_position: Vec<(String, IncomeTotals)>,
+    pub realized: IncomeTotals,
+    pub projected: Vec<ProjectedIncome>,
+    pub projected_total: IncomeTotals,
+}
+
+pub fn income_report(positions: &[PortfolioPosition]) -> IncomeReport {
+    let mut report = IncomeReport::default();
+
+    for position in positions {
+        let mut position_totals = IncomeTotals::default();
+        let dividends = position.get_dividends().iter().map(|event| (event, false));
+        let interest = position
+            .get_interest_payments()
+            .iter()
+            .map(|event| (event, true));
+        for (event, is_interest) in dividends.chain(interest) {
+            let month = event.date.format("%Y-%m").to_string();
+            for totals in [
+                report.realized_by_month.entry(month).or_default(),
+                report
+                    .realized_by_year
+                    .entry(event.date.year())
+                    .or_default(),
+                &mut position_totals,
+            ] {
+                if is_interest {
+                    totals.interest += event.amount;
+                } else {
+                    totals.dividends += event.amount;
+                }
+            }
+        }
+        if position_totals != IncomeTotals::default() {
+            report.realized.interest += position_totals.interest;
+            report.realized.dividends += position_totals.dividends;
+            report
+                .realized_by_position
+                .push((position.get_name().to_string(), position_totals));
+        }
+
+        let projected = ProjectedIncome {
+            name: position.get_name().to_string(),
+            interest: position.projected_interest(),
+            dividends: position.projected_dividends(),
+        };
+        if !projected.interest.is_zero() || !projected.dividends.is_zero() {
+            report.projected_total.interest += projected.interest;
+            report.projected_total.dividends += projected.dividends;
+            report.projected.push(projected);
+        }
+    }
+
+    report
+}
+
+fn print_totals_table(label: &str, rows: &[(String, IncomeTotals)], format: &NumberFormat) {
+    println!(
+        "{0: >26} | {1: >14} | {2: >14} | {3: >14}",
+        label, "Interest", "Dividends", "Total"
+    );
+    println!("=============================================================================");
+    for (label, totals) in rows {
+        println!(
+            "{0: >26} | {1: >14} | {2: >14} | {3: >14}",
+            label,
+            format.money(totals.interest),
+            format.money(totals.dividends),
+            format.money(totals.total())
+        );
+    }
+}
+
+pub fn print_income_report(report: &IncomeReport, format: &NumberFormat) {
+    println!("Realized income");
+    if report.realized_by_position.is_empty() {
+        println!("No dividends or interest payments logged yet.");
+    } else {
+        let months: Vec<(String, IncomeTotals)> = report
+            .realized_by_month
+            .iter()
+            .map(|(month, totals)| (month.clone(), *totals))
+            .collect();
+        print_totals_table("Month", &months, format);
+        println!();
+        let years: Vec<(String, IncomeTotals)> = report
+            .realized_by_year
+            .iter()
+            .map(|(year, totals)| (year.to_string(), *totals))
+            .collect();
+        print_totals_table("Year", &years, format);
+        println!();
+        print_totals_table("Position", &report.realized_by_position, format);
+        println!("Total realized: {}", format.money(report.realized.total()));
+    }
+
+    println!("\nProjected income of the next 12 months");
+    if report.projected.is_empty() {
+        println!("No positions with a DividendYield or an InterestRate.");
+        return;
+    }
+    let projected: Vec<(String, IncomeTotals)> = report
+        .projected
+        .iter()
+        .map(|projected| {
+            (
+                projected.name.clone(),
+                IncomeTotals {
+                    interest: projected.interest,
+                    dividends: projected.dividends,
+                },
+            )
+        })
+        .collect();
+    print_totals_table("Position", &projected, format);
+    println!(
+        "Total projected: {}",
+        format.money(report.projected_total.total())
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::from_string;
+    use crate::provider::priced_at;
+    use rust_decimal::dec;
+
+    fn income_positions() -> Vec<PortfolioPosition> {
+        let price = |ticker: &str| if ticker == "KO" { 60.0 } else { 180.0 };
+        priced_at(
+            r#"[
+                {"Name": "Coca-Cola", "Ticker": "KO", "AssetClass": "Stocks", "Amount": 100, "DividendYield": 3},
+                {
+                    "Name": "Apple",
+                    "Ticker": "AAPL",
+                    "AssetClass": "Stocks",
+                    "Amount": 10,
+                    "Dividends": [{"Date": "2024-02-16", "Amount": 2.4}]
+                },
+                {
+                    "Name": "Savings",
+                    "AssetClass": "Cash",
+                    "Amount": 1000,
+                    "InterestRate": 3.65,
+                    "PaymentFrequencyDays": 30,
+                    "NextInterestPayment": "2024-01-31",
+                    "LastInterestPayment": "2024-01-01"
+                }
+            ]"#,
+            price,
+        )
+    }
+
+    #[test]
+    fn test_income_report() {
+        let mut positions = income_positions();
+        // credit the payments of January 31 and March 1, which logs them
+        let now = Utc.with_ymd_and_hms(2024, 3, 5, 0, 0, 0).unwrap();
+        assert_eq!(positions[2].apply_interest_if_due(now, 2), Some(dec!(6.01)));
+
+        let report = income_report(&positions);
+        // realized: the logged dividend and the two interest payments
+        assert_eq!(
+            report.realized_by_month.keys().collect::<Vec<_>>(),
+            ["2024-01", "2024-02", "2024-03"]
+        );
+        assert_eq!(report.realized_by_month["2024-01"].interest, dec!(3.00));
+        assert_eq!(report.realized_by_month["2024-02"].dividends, dec!(2.4));
+        assert_eq!(report.realized_by_month["2024-03"].interest, dec!(3.01));
+        assert_eq!(
+            report.realized_by_year[&2024],
+            IncomeTotals {
+                interest: dec!(6.01),
+                dividends: dec!(2.4),
+            }
+        );
+        let names: Vec<&str> = report
+            .realized_by_position
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        assert_eq!(names, ["Apple", "Savings"]);
+        assert_eq!(report.realized.total(), dec!(8.41));
+
+        // projected: 3% of the 6000 in Coca-Cola and a year of interest on the new amount.
+        // The logged Apple dividend doesn't project anything without a yield.
+        assert_eq!(report.projected.len(), 2);
+        assert_eq!(report.projected[0].name, "Coca-Cola");
+        assert_eq!(report.projected[0].dividends, dec!(180));
+        assert_eq!(report.projected[1].interest, dec!(36.719365));
+        assert_eq!(
+            report.projected_total,
+            IncomeTotals {
+                interest: dec!(36.719365),
+                dividends: dec!(180),
+            }
+        );
+    }
+
+    #[test]
+    fn test_income_report_keeps_the_log() {
+        let mut positions = income_positions();
+        let now = Utc.with_ymd_and_hms(2024, 2, 1, 0, 0, 0).unwrap();
+        positions[2].apply_interest_if_due(now, 2);
+        let saved = from_string(&crate::position::to_string(&positions).unwrap());
+        assert_eq!(saved[1].get_dividends(), positions[1].get_dividends());
+        assert_eq!(saved[2].get_interest_payments().len(), 1);
+        assert_eq!(
+            saved[2].get_interest_payments()[0].date,
+            NaiveDate::from_ymd_opt(2024, 1, 31).unwrap()
+        );
+
+        // the interest on a debt is not income
+        let mut loan = from_string(
+            r#"[{"Name": "Overdraft", "AssetClass": "Cash", "Amount": -100, "InterestRate": 10,
+                 "PaymentFrequencyDays": 30, "NextInterestPayment": "2024-01-31"}]"#,
+        );
+        loan[0].apply_interest_if_due(now, 2);
+        assert_eq!(income_report(&loan), IncomeReport::default());
+    }
+}
@@ -0,0 +1,315 @@
+use crate::position::{get_quote_history_range, to_f64, PortfolioPosition};
+use chrono::prelude::*;
+use std::collections::BTreeSet;
+use yahoo_finance_api as yahoo;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Signal {
+    Buy,
+    Sell,
+    Hold,
+}
+
+impl std::fmt::Display for Signal {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Signal::Buy => write!(f, "Buy"),
+            Signal::Sell => write!(f, "Sell"),
+            Signal::Hold => write!(f, "Hold"),
+        }
+    }
+}
+
+// Average of the last `window` closes
+pub fn simple_moving_average(closes: &[f64], window: usize) -> Result<f64, String> {
+    if window == 0 {
+        return Err("The moving average window must be at least 1".to_string());
+    }
+    if window > closes.len() {
+        return Err(format!(
+            "Not enough price history for a {window}-day moving average ({} closes available)",
+            closes.len()
+        ));
+    }
+
+    let recent = &closes[closes.len() - window..];
+    Ok(recent.iter().sum::<f64>() / window as f64)
+}
+
+// Buy if the short moving average is above the long one, Sell if it is below
+pub fn crossover_signal(closes: &[f64], short: usize, long: usize) -> Result<Signal, String> {
+    if short >= long {
+        return Err(format!(
+            "The short window ({short}) must be smaller than the long window ({long})"
+        ));
+    }
+
+    let short_average = simple_moving_average(closes, short)?;
+    let long_average = simple_moving_average(closes, long)?;
+
+    Ok(if short_average > long_average {
+        Signal::Buy
+    } else if short_average < long_average {
+        Signal::Sell
+    } else {
+        Signal::Hold
+    })
+}
+
+// Largest decline from a running peak to a later value, in percent of the peak
+pub fn max_drawdown_of(values: &[f64]) -> Result<f64, String> {
+    if values.len() < 2 {
+        return Err(format!(
+            "Not enough price history for a drawdown ({} closes available)",
+            values.len()
+        ));
+    }
+
+    let mut peak = values[0];
+    let mut drawdown: f64 = 0.0;
+    for &value in &values[1..] {
+        if value > peak {
+            peak = value;
+        } else if peak > 0.0 {
+            drawdown = drawdown.max((peak - value) / peak * 100.0);
+        }
+    }
+    Ok(drawdown)
+}
+
+// Maximum drawdown of the last `days` values
+fn max_drawdown_over(values: &[f64], days: usize) -> Result<f64, String> {
+    if days < 2 {
+        return Err("The drawdown needs at least 2 days".to_string());
+    }
+    if days > values.len() {
+        return Err(format!(
+            "Not enough price history for a {days}-day drawdown ({} closes available)",
+            values.len()
+        ));
+    }
+    max_drawdown_of(&values[values.len() - days..])
+}
+
+// Daily values of a portfolio holding its current balances. Each series of closes is
+// scaled so that its last close is the balance of the position, `constant` (cash,
+// manually priced positions) doesn't move. Days start when every series has a close,
+// a series without a close on a day keeps its previous one.
+pub fn portfolio_value_path(series: &[(f64, Vec<(NaiveDate, f64)>)], constant: f64) -> Vec<f64> {
+    let series: Vec<&(f64, Vec<(NaiveDate, f64)>)> = series
+        .iter()
+        .filter(|(_, closes)| closes.last().is_some_and(|(_, close)| *close > 0.0))
+        .collect();
+    let Some(start) = series.iter().map(|(_, closes)| closes[0].0).max() else {
+        return Vec::new();
+    };
+    let days: BTreeSet<NaiveDate> = series
+        .iter()
+        .flat_map(|(_, closes)| closes.iter().map(|(day, _)| *day))
+        .filter(|day| *day >= start)
+        .collect();
+
+    let mut indices = vec![0; series.len()];
+    days.into_iter()
+        .map(|day| {
+            let mut value = constant;
+            for ((balance, closes), index) in series.iter().zip(indices.iter_mut()) {
+                while *index + 1 < closes.len() && closes[*index + 1].0 <= day {
+                    *index += 1;
+                }
+                value += balance * closes[*index].1 / closes[closes.len() - 1].1;
+            }
+            value
+        })
+        .collect()
+}
+
+// Smallest Yahoo range that covers `window` trading days (~250 per year)
+fn history_range_for(window: usize) -> &'static str {
+    match window {
+        0..=40 => "3mo",
+        41..=120 => "6mo",
+        121..=240 => "1y",
+        241..=480 => "2y",
+        481..=1200 => "5y",
+        1201..=2400 => "10y",
+        _ => "max",
+    }
+}
+
+async fn get_dated_closes(
+    connector: &yahoo::YahooConnector,
+    ticker: &str,
+    window: usize,
+) -> Result<Vec<(NaiveDate, f64)>, String> {
+    let quotes =
+        get_quote_history_range(connector, ticker, "1d", history_range_for(window)).await?;
+    Ok(quotes
+        .iter()
+        .filter_map(|quote| {
+            let day = DateTime::from_timestamp(quote.timestamp, 0)?.date_naive();
+            Some((day, quote.close))
+        })
+        .collect())
+}
+
+async fn get_daily_closes(
+    connector: &yahoo::YahooConnector,
+    ticker: &str,
+    window: usize,
+) -> Result<Vec<f64>, String> {
+    let closes = get_dated_closes(connector, ticker, window).await?;
+    Ok(closes.into_iter().map(|(_, close)| close).collect())
+}
+
+// Simple moving average of the last `window` daily closes of a ticker
+pub async fn moving_average(
+    connector: &yahoo::YahooConnector,
+    ticker: &str,
+    window: usize,
+) -> Result<f64, String> {
+    let closes = get_daily_closes(connector, ticker, window).await?;
+    simple_moving_average(&closes, window)
+}
+
+// Compare a short and a long simple moving average of the daily closes of a ticker
+pub async fn sma_crossover_signal(
+    connector: &yahoo::YahooConnector,
+    ticker: &str,
+    short: usize,
+    long: usize,
+) -> Result<Signal, String> {
+    let closes = get_daily_closes(connector, ticker, long).await?;
+    crossover_signal(&closes, short, long)
+}
+
+// Maximum drawdown of the last `days` daily closes of a ticker, in percent
+pub async fn max_drawdown(
+    connector: &yahoo::YahooConnector,
+    ticker: &str,
+    days: usize,
+) -> Result<f64, String> {
+    let closes = get_daily_closes(connector, ticker, days).await?;
+    max_drawdown_over(&closes, days)
+}
+
+// Maximum drawdown over the last `days` of the portfolio, weighted by the current balances
+pub async fn portfolio_max_drawdown(
+    connector: &yahoo::YahooConnector,
+    positions: &[PortfolioPosition],
+    days: usize,
+) -> Result<f64, String> {
+    let mut constant = 0.0;
+    let mut tickers = Vec::new();
+    for position in positions {
+        let balance = to_f64(position.get_balance());
+        match position
+            .get_ticker()
+            .filter(|_| position.is_market_instrument())
+        {
+            Some(ticker) => tickers.push((balance, ticker)),
+            None => constant += balance,
+        }
+    }
+    if tickers.is_empty() {
+        return Err("None of the positions has a ticker with price history".to_string());
+    }
+
+    let histories = futures::future::join_all(
+        tickers
+            .iter()
+            .map(|(_, ticker)| get_dated_closes(connector, ticker, days)),
+    )
+    .await;
+    let mut series = Vec::new();
+    for ((balance, _), closes) in tickers.iter().zip(histories) {
+        series.push((*balance, closes?));
+    }
+    max_drawdown_over(&portfolio_value_path(&series, constant), days)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simple_moving_average() {
+        let closes = [1.0, 2.0, 3.0, 4.0, 5.0];
+        assert_eq!(simple_moving_average(&closes, 2), Ok(4.5));
+        assert_eq!(simple_moving_average(&closes, 5), Ok(3.0));
+    }
+
+    #[test]
+    fn test_simple_moving_average_window_too_long() {
+        let closes = [1.0, 2.0, 3.0];
+        assert_eq!(
+            simple_moving_average(&closes, 4),
+            Err(
+                "Not enough price history for a 4-day moving average (3 closes available)"
+                    .to_string()
+            )
+        );
+        assert!(simple_moving_average(&closes, 0).is_err());
+    }
+
+    #[test]
+    fn test_crossover_signal() {
+        let rising = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0];
+        assert_eq!(crossover_signal(&rising, 2, 5), Ok(Signal::Buy));
+
+        let falling = [6.0, 5.0, 4.0, 3.0, 2.0, 1.0];
+        assert_eq!(crossover_signal(&falling, 2, 5), Ok(Signal::Sell));
+
+        let flat = [3.0; 6];
+        assert_eq!(crossover_signal(&flat, 2, 5), Ok(Signal::Hold));
+
+        assert!(crossover_signal(&rising, 5, 2).is_err());
+        assert!(crossover_signal(&rising, 2, 10).is_err());
+    }
+
+    #[test]
+    fn test_max_drawdown_of() {
+        // peak 120, trough 84: -30%. The later fall from 110 to 99 is only -10%.
+        let closes = [100.0, 120.0, 90.0, 84.0, 110.0, 99.0, 115.0];
+        assert!((max_drawdown_of(&closes).unwrap() - 30.0).abs() < 1e-9);
+        assert_eq!(max_drawdown_of(&[1.0, 2.0, 3.0]), Ok(0.0));
+        assert!(max_drawdown_of(&[1.0]).is_err());
+
+        assert!((max_drawdown_over(&closes, 3).unwrap() - 10.0).abs() < 1e-9);
+        assert_eq!(
+            max_drawdown_over(&closes, 10),
+            Err("Not enough price history for a 10-day drawdown (7 closes available)".to_string())
+        );
+        assert!(max_drawdown_over(&closes, 1).is_err());
+    }
+
+    #[test]
+    fn test_portfolio_value_path() {
+        let day = |d| NaiveDate::from_ymd_opt(2024, 1, d).unwrap();
+        // 1000 in a stock that halves and recovers, 500 in one missing the 3rd, 500 in cash
+        let series = [
+            (
+                1000.0,
+                vec![
+                    (day(1), 80.0),
+                    (day(2), 100.0),
+                    (day(3), 50.0),
+                    (day(4), 100.0),
+                ],
+            ),
+            (500.0, vec![(day(2), 10.0), (day(4), 10.0)]),
+        ];
+        let path = portfolio_value_path(&series, 500.0);
+        assert_eq!(path, [2000.0, 1500.0, 2000.0]);
+        assert!((max_drawdown_of(&path).unwrap() - 25.0).abs() < 1e-9);
+
+        assert!(portfolio_value_path(&[], 500.0).is_empty());
+    }
+
+    #[test]
+    fn test_history_range_for() {
+        assert_eq!(history_range_for(20), "3mo");
+        assert_eq!(history_range_for(200), "1y");
+        assert_eq!(history_range_for(5000), "max");
+    }
+}
@@ -0,0 +1,91 @@
+use chrono::prelude::*;
+
+// The Fisher equation: what a nominal return buys after inflation. Both are
+// fractions of the same period, e.g. 0.07 and 0.03 for a year.
+pub fn real_return(nominal_return: f64, inflation_rate: f64) -> f64 {
+    (1.0 + nominal_return) / (1.0 + inflation_rate) - 1.0
+}
+
+// An annual inflation rate compounded over the days between the dates (actual/365
+// like the interest), as a fraction
+pub fn inflation_between(annual_rate: f64, from: DateTime<Utc>, to: DateTime<Utc>) -> f64 {
+    let years = (to - from).num_days() as f64 / 365.0;
+    (1.0 + annual_rate).powf(years) - 1.0
+}
+
+// The real return in % of a nominal return in % between the dates, at an annual
+// inflation in %, like the performance and return tables show them
+pub fn real_return_percent(
+    nominal_percent: f64,
+    annual_inflation_percent: f64,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> f64 {
+    let inflation = inflation_between(annual_inflation_percent / 100.0, from, to);
+    real_return(nominal_percent / 100.0, inflation) * 100.0
+}
+
+// A --inflation or inflation_rate in %. At -100% or below prices would vanish and the
+// Fisher equation divides by zero.
+pub fn check_inflation(percent: f64) -> Result<f64, String> {
+    if percent.is_finite() && percent > -100.0 {
+        Ok(percent)
+    } else {
+        Err(format!(
+            "The inflation rate must be above -100%, not {percent}%"
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{actual} is not {expected}"
+        );
+    }
+
+    #[test]
+    fn test_real_return() {
+        // (1 + 0.07) / (1 + 0.03) - 1
+        assert_close(real_return(0.07, 0.03), 0.038834951456);
+        // not just the difference of the two
+        assert!(real_return(0.07, 0.03) < 0.04);
+        assert_close(real_return(0.03, 0.03), 0.0);
+        // a loss gets bigger, deflation helps
+        assert_close(real_return(-0.1, 0.05), 0.9 / 1.05 - 1.0);
+        assert_close(real_return(0.02, -0.01), 1.02 / 0.99 - 1.0);
+    }
+
+    #[test]
+    fn test_real_return_of_a_period() {
+        let from = Utc.with_ymd_and_hms(2023, 1, 1, 0, 0, 0).unwrap();
+        let year = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        assert_close(inflation_between(0.03, from, year), 0.03);
+        assert_close(
+            real_return_percent(7.0, 3.0, from, year),
+            real_return(0.07, 0.03) * 100.0,
+        );
+
+        // half a year's inflation compounds to less than half of the annual rate
+        let half = from + chrono::Duration::days(365 / 2);
+        let inflation = inflation_between(0.04, from, half);
+        assert!(inflation > 0.0 && inflation < 0.02);
+        assert_close(
+            real_return_percent(5.0, 4.0, from, half),
+            (1.05 / (1.0 + inflation) - 1.0) * 100.0,
+        );
+        assert_close(real_return_percent(5.0, 4.0, from, from), 5.0);
+    }
+
+    #[test]
+    fn test_check_inflation() {
+        assert_eq!(check_inflation(2.5), Ok(2.5));
+        assert_eq!(check_inflation(-0.5), Ok(-0.5));
+        assert!(check_inflation(-100.0).is_err());
+        assert!(check_inflation(f64::NAN).is_err());
+    }
+}
@@ -0,0 +1,227 @@
+use crate::clock::Clock;
+use crate::format::NumberFormat;
+use crate::position::PortfolioPosition;
+use chrono::prelude::*;
+use chrono::Duration;
+use rust_decimal::Decimal;
+
+// The next interest payment of a cash position
+#[derive(Debug, Clone, PartialEq)]
+pub struct UpcomingPayment {
+    pub name: String,
+    pub date: DateTime<Utc>,
+    pub interest: Decimal,
+}
+
+impl UpcomingPayment {
+    pub fn is_past_due(&self, now: DateTime<Utc>) -> bool {
+        self.date < now
+    }
+}
+
+// The next payment of every interest-bearing cash position due within the window
+// from now, sorted by date. Payments that are past due (not credited yet) come first.
+pub fn upcoming_payments(
+    positions: &[PortfolioPosition],
+    within: Duration,
+    clock: &dyn Clock,
+) -> Vec<UpcomingPayment> {
+    let now = clock.now();
+    let mut payments: Vec<UpcomingPayment> = positions
+        .iter()
+        .filter(|position| position.is_cash_with_interest())
+        .filter_map(|position| {
+            let date = position.get_next_interest_payment()?;
+            if date > now + within {
+                return None;
+            }
+            // the period of the payment started at the last one
+            let frequency = Duration::days(position.get_payment_frequency_days()? as i64);
+            let since = position
+                .get_last_interest_payment()
+                .unwrap_or(date - frequency);
+            Some(UpcomingPayment {
+                name: position.get_name().to_string(),
+                date,
+                interest: position.calculate_interest(since, date),
+            })
+        })
+        .collect();
+
+    payments.sort_by_key(|payment| payment.date);
+    payments
+}
+
+pub fn print_upcoming_payments(
+    payments: &[UpcomingPayment],
+    clock: &dyn Clock,
+    format: &NumberFormat,
+) {
+    let now = clock.now();
+    println!("{0: >12} | {1: >26} | {2: >10}", "Date", "Name", "Interest");
+    println!("====================================================");
+    for payment in payments {
+        let note = if payment.is_past_due(now) {
+            " (past due)"
+        } else {
+            ""
+        };
+        println!(
+            "{0: >12} | {1: >26} | {2: >10}{3}",
+            payment.date.format("%Y-%m-%d"),
+            payment.name,
+            format.money(payment.interest),
+            note
+        );
+    }
+}
+
+// The projected balance of every interest-bearing cash position after each payment until `until`
+pub fn print_interest_simulation(
+    positions: &[PortfolioPosition],
+    until: DateTime<Utc>,
+    decimals: u32,
+    format: &NumberFormat,
+) {
+    let schedules: Vec<(&PortfolioPosition, Vec<_>)> = positions
+        .iter()
+        .map(|position| (position, position.simulate_interest(until, decimals)))
+        .filter(|(_, schedule)| !schedule.is_empty())
+        .collect();
+    if schedules.is_empty() {
+        println!("No interest payments until {}.", until.format("%Y-%m-%d"));
+        return;
+    }
+
+    for (position, schedule) in schedules {
+        println!("{}", position.get_name());
+        println!("{0: >12} | {1: >14}", "Date", "Balance");
+        println!("===============================");
+        for (date, balance) in &schedule {
+            println!(
+                "{0: >12} | {1: >14}",
+                date.format("%Y-%m-%d"),
+                format.money(*balance)
+            );
+        }
+        println!();
+    }
+}
+
+// The remaining installments of every loan and what is left to pay until payoff
+pub fn print_loan_schedules(positions: &[PortfolioPosition], decimals: u32, format: &NumberFormat) {
+    let loans: Vec<&PortfolioPosition> = positions
+        .iter()
+        .filter(|position| position.is_amortized_loan())
+        .collect();
+    if loans.is_empty() {
+        println!("No loans with a PaymentAmount.");
+        return;
+    }
+
+    for loan in loans {
+        println!("{}", loan.get_name());
+        let schedule = match loan.amortization_schedule(decimals) {
+            Ok(schedule) => schedule,
+            Err(e) => {
+                eprintln!("{e}");
+                continue;
+            }
+        };
+        println!(
+            "{0: >12} | {1: >12} | {2: >12} | {3: >12} | {4: >12}",
+            "Date", "Payment", "Interest", "Principal", "Balance"
+        );
+        println!("======================================================================");
+        for payment in &schedule {
+            println!(
+                "{0: >12} | {1: >12} | {2: >12} | {3: >12} | {4: >12}",
+                payment.date.format("%Y-%m-%d"),
+                format.money(payment.payment),
+                format.money(payment.interest),
+                format.money(payment.principal),
+                format.money(payment.balance)
+            );
+        }
+        let interest: Decimal = schedule.iter().map(|payment| payment.interest).sum();
+        println!(
+            "{} payments left, {} interest remaining",
+            schedule.len(),
+            format.money(interest)
+        );
+        println!();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FixedClock;
+    use crate::position::from_string;
+    use rust_decimal::dec;
+
+    fn positions() -> Vec<PortfolioPosition> {
+        from_string(
+            r#"[
+                {
+                    "Name": "Savings",
+                    "AssetClass": "Cash",
+                    "Amount": 1000,
+                    "InterestRate": 3.65,
+                    "PaymentFrequencyDays": 30,
+                    "NextInterestPayment": "2024-01-31",
+                    "LastInterestPayment": "2024-01-01"
+                },
+                {
+                    "Name": "Overdue",
+                    "AssetClass": "Cash",
+                    "Amount": 2000,
+                    "InterestRate": 3.65,
+                    "PaymentFrequencyDays": 10,
+                    "NextInterestPayment": "2024-01-05"
+                },
+                {
+                    "Name": "Quarterly",
+                    "AssetClass": "Cash",
+                    "Amount": 1000,
+                    "InterestRate": 3.65,
+                    "PaymentFrequencyDays": 90,
+                    "NextInterestPayment": "2024-03-31"
+                },
+                {"Name": "Checking", "AssetClass": "Cash", "Amount": 500},
+                {"Name": "Apple", "Ticker": "AAPL", "AssetClass": "Stock", "Amount": 10}
+            ]"#,
+        )
+    }
+
+    #[test]
+    fn test_upcoming_payments() {
+        let now = Utc.with_ymd_and_hms(2024, 1, 11, 0, 0, 0).unwrap();
+        let payments = upcoming_payments(&positions(), Duration::days(30), &FixedClock(now));
+
+        let names: Vec<&str> = payments.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["Overdue", "Savings"]);
+
+        assert!(payments[0].is_past_due(now));
+        assert_eq!(
+            payments[0].date,
+            Utc.with_ymd_and_hms(2024, 1, 5, 0, 0, 0).unwrap()
+        );
+        // 10 days since the start of the period at 0.2 per day
+        assert_eq!(payments[0].interest, dec!(2));
+
+        assert!(!payments[1].is_past_due(now));
+        assert_eq!(payments[1].interest, dec!(3));
+    }
+
+    #[test]
+    fn test_upcoming_payments_window() {
+        let clock = FixedClock(Utc.with_ymd_and_hms(2024, 1, 11, 0, 0, 0).unwrap());
+        let payments = upcoming_payments(&positions(), Duration::days(0), &clock);
+        assert_eq!(payments.len(), 1);
+
+        let payments = upcoming_payments(&positions(), Duration::days(90), &clock);
+        assert_eq!(payments.len(), 3);
+        assert_eq!(payments[2].name, "Quarterly");
+    }
+}
@@ -0,0 +1,236 @@
+//! The valuation engine of portfolio_rs: data files, positions, prices, interest
+//! and reports, without the command line tool around it.
+//!
+//! The items at the top of the crate are its stable API. The modules are the
+//! internals the `portfolio_rs` binary is built from and change between versions.
+//!
+//! Load a data file (JSON, YAML, TOML or a CSV export) of unpriced positions:
+//!
+//! ```
+//! use portfolio_rs::Portfolio;
+//!
+//! let portfolio = Portfolio::load("tests/fixtures/portfolio.json")?;
+//! assert_eq!(portfolio.positions.len(), 3);
+//! assert_eq!(portfolio.positions[0].get_ticker(), Some("SPY"));
+//! # Ok::<(), String>(())
+//! ```
+//!
+//! Or build the positions in code, the builder checks that the settings fit together:
+//!
+//! ```
+//! use portfolio_rs::{Portfolio, PortfolioPosition};
+//! use chrono::{TimeZone, Utc};
+//! use rust_decimal::dec;
+//!
+//! let mut portfolio = Portfolio::new();
+//! portfolio.add_position(PortfolioPosition::new("Apple", Some("AAPL"), "Stocks", dec!(10))?);
+//! portfolio.add_position(
+//!     PortfolioPosition::builder("Savings", None, "Cash", dec!(1000))
+//!         .with_interest_rate(dec!(5))
+//!         .with_payment_frequency_days(30)
+//!         .with_next_interest_payment(Utc.with_ymd_and_hms(2024, 2, 1, 0, 0, 0).unwrap())
+//!         .build()?,
+//! );
+//! assert!(portfolio.positions[1].is_cash_with_interest());
+//!
+//! // a rate without the dates it is paid on
+//! let error = PortfolioPosition::builder("Savings", None, "Cash", dec!(1000))
+//!     .with_interest_rate(dec!(5))
+//!     .build()
+//!     .unwrap_err();
+//! assert!(error.ends_with("the InterestRate needs a PaymentFrequencyDays and a NextInterestPayment"));
+//! # Ok::<(), String>(())
+//! ```
+//!
+//! Price it with any source of prices, [`yahoo_provider`] is the one the binary
+//! uses:
+//!
+//! ```
+//! use portfolio_rs::{
+//!     HistoricPrice, PriceAdjustment, PriceProvider, PriceQuote, QuoteError, Split, SymbolMatch,
+//!     ErrorKind, Portfolio,
+//! };
+//! use chrono::{DateTime, Utc};
+//!
+//! struct FixedPrices;
+//!
+//! impl PriceProvider for FixedPrices {
+//!     async fn get_latest_price(&self, ticker: &str) -> Result<PriceQuote, QuoteError> {
+//!         match ticker {
+//!             "AAPL" => Ok(PriceQuote {
+//!                 price: 200.0,
+//!                 time: Some(Utc::now()),
+//!                 previous_close: Some(190.0),
+//!                 open: Some(195.0),
+//!                 currency: Some("USD".to_string()),
+//!             }),
+//!             _ => Err(QuoteError::new(ErrorKind::NotFound, format!("no price of {ticker}"))),
+//!         }
+//!     }
+//!
+//!     async fn get_historic_price(
+//!         &self,
+//!         ticker: &str,
+//!         date: DateTime<Utc>,
+//!         adjustment: PriceAdjustment,
+//!         _window: chrono::Duration,
+//!     ) -> Result<HistoricPrice, QuoteError> {
+//!         let quote = self.get_latest_price(ticker).await?;
+//!         Ok(HistoricPrice { price: quote.price, adjustment, date: date.date_naive() })
+//!     }
+//!
+//!     async fn get_splits(&self, _: &str, _: DateTime<Utc>) -> Result<Vec<Split>, QuoteError> {
+//!         Ok(Vec::new())
+//!     }
+//!
+//!     async fn get_name(&self, ticker: &str) -> Result<String, QuoteError> {
+//!         Ok(ticker.to_string())
+//!     }
+//!
+//!     async fn find_symbols(&self, _: &str) -> Result<Vec<String>, QuoteError> {
+//!         Ok(Vec::new())
+//!     }
+//!
+//!     async fn search_symbols(&self, _: &str) -> Result<Vec<SymbolMatch>, QuoteError> {
+//!         Ok(Vec::new())
+//!     }
+//! }
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), String> {
+//! let mut portfolio = Portfolio::from_data(
+//!     r#"[
+//!         {"Name": "Apple", "Ticker": "AAPL", "AssetClass": "Stocks", "Amount": 10},
+//!         {"Name": "Delisted", "Ticker": "GONE", "AssetClass": "Stocks", "Amount": 5}
+//!     ]"#,
+//! )?;
+//! let errors = portfolio.price(&FixedPrices, |_, _| {}).await;
+//! // a position without a price is kept, valued at 0
+//! assert_eq!(errors.len(), 1);
+//! assert_eq!(errors[0].kind, ErrorKind::NotFound);
+//! assert_eq!(portfolio.positions[0].get_balance().to_string(), "2000");
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Compute the totals of the priced positions:
+//!
+//! ```
+//! use portfolio_rs::{build_report, Portfolio};
+//! use rust_decimal::Decimal;
+//!
+//! let portfolio = Portfolio::from_data(
+//!     r#"[
+//!         {"Name": "Savings", "AssetClass": "Cash", "Amount": 3000},
+//!         {"Name": "Gold", "AssetClass": "Commodities", "Amount": 1, "ManualPrice": 1000},
+//!         {"Name": "Car Loan", "AssetClass": "Liability", "Amount": 1000}
+//!     ]"#,
+//! )?;
+//! // the net worth, the liabilities are subtracted
+//! assert_eq!(portfolio.get_total_value(), Decimal::from(3000));
+//! assert_eq!(portfolio.get_liabilities_value(), Decimal::from(-1000));
+//! assert_eq!(portfolio.get_allocation()["Cash"], 75.0);
+//!
+//! let report = build_report(&portfolio.positions, &Default::default());
+//! assert_eq!(report.total_value, portfolio.get_total_value());
+//! # Ok::<(), String>(())
+//! ```
+
+#[doc(hidden)]
+pub mod alerts;
+#[doc(hidden)]
+pub mod allocation;
+#[doc(hidden)]
+pub mod attribution;
+#[doc(hidden)]
+pub mod benchmark;
+#[doc(hidden)]
+pub mod clock;
+#[doc(hidden)]
+pub mod config;
+#[doc(hidden)]
+pub mod data_file;
+#[doc(hidden)]
+pub mod diff;
+#[doc(hidden)]
+pub mod edit;
+#[doc(hidden)]
+pub mod export;
+#[doc(hidden)]
+pub mod filter;
+#[doc(hidden)]
+pub mod forecast;
+#[doc(hidden)]
+pub mod format;
+#[doc(hidden)]
+pub mod grouping;
+#[doc(hidden)]
+pub mod history;
+#[doc(hidden)]
+pub mod household;
+#[doc(hidden)]
+pub mod import;
+#[doc(hidden)]
+pub mod income;
+#[doc(hidden)]
+pub mod indicators;
+#[doc(hidden)]
+pub mod inflation;
+#[doc(hidden)]
+pub mod interest;
+#[doc(hidden)]
+pub mod live;
+#[doc(hidden)]
+pub mod logger;
+#[doc(hidden)]
+pub mod metrics;
+#[cfg(test)]
+mod mock_yahoo;
+#[doc(hidden)]
+pub mod monte_carlo;
+#[doc(hidden)]
+pub mod movers;
+#[doc(hidden)]
+pub mod portfolio;
+#[doc(hidden)]
+pub mod position;
+#[doc(hidden)]
+pub mod pricing;
+#[doc(hidden)]
+pub mod provider;
+#[doc(hidden)]
+pub mod purchase;
+#[doc(hidden)]
+pub mod rate_limit;
+#[doc(hidden)]
+pub mod report;
+#[doc(hidden)]
+pub mod search;
+#[doc(hidden)]
+pub mod series;
+#[doc(hidden)]
+pub mod server;
+#[doc(hidden)]
+pub mod splits;
+#[doc(hidden)]
+pub mod table;
+#[doc(hidden)]
+pub mod tui;
+#[doc(hidden)]
+pub mod validate;
+#[doc(hidden)]
+pub mod watch;
+
+pub use crate::allocation::ClassAllocation;
+pub use crate::clock::{Clock, FixedClock, SystemClock};
+pub use crate::interest::{upcoming_payments, UpcomingPayment};
+pub use crate::portfolio::Portfolio;
+pub use crate::position::{
+    merge_duplicates, yahoo_provider, IncomeEvent, InterestPreview, LoanPayment, PortfolioPosition,
+    PositionBuilder,
+};
+pub use crate::pricing::{price_positions, ErrorKind, PositionError, PricingReport};
+pub use crate::provider::{
+    HistoricPrice, PriceAdjustment, PriceProvider, PriceQuote, QuoteError, Split, SymbolMatch,
+};
+pub use crate::report::{build_report, PortfolioReport};
@@ -0,0 +1,194 @@
+// Pricing the data of the data files for the commands, the TUI and the HTTP
+// server, and saving it back
+use crate::config;
+use crate::household::{expand_paths, save_files};
+use crate::portfolio::Portfolio;
+use crate::position::{connector_with, NetworkSettings};
+use crate::pricing::{count_tickers, PositionError};
+use crate::provider::{PriceProvider, StoredNames};
+use crate::splits::check_splits;
+use crate::tui::NetworkStatus;
+use colored::Colorize;
+use std::io::IsTerminal;
+
+// The positions the commands couldn't price, printed after their output, and the
+// number of tickers they priced
+static PRICING_ERRORS: std::sync::Mutex<(Vec<PositionError>, usize)> =
+    std::sync::Mutex::new((Vec::new(), 0));
+
+// The positions that couldn't be priced since the last call, and of how many tickers
+pub fn take_pricing_errors() -> (Vec<PositionError>, usize) {
+    std::mem::take(&mut *PRICING_ERRORS.lock().unwrap())
+}
+
+// How the commands, the TUI and the HTTP server fetch quotes, from the config
+// file and --no-fetch
+#[derive(Debug, Clone, PartialEq)]
+pub struct FetchSettings {
+    // false with --no-fetch
+    pub enabled: bool,
+    pub network: NetworkSettings,
+    // fetch_names of the config file, without it a position without a Name is
+    // shown by its ticker
+    pub names: bool,
+}
+
+impl Default for FetchSettings {
+    fn default() -> Self {
+        FetchSettings {
+            enabled: true,
+            network: NetworkSettings::default(),
+            names: true,
+        }
+    }
+}
+
+impl FetchSettings {
+    // A connector with the network settings, shared by the requests of a command
+    pub fn connector(&self) -> Result<yahoo_finance_api::YahooConnector, String> {
+        connector_with(&self.network)
+    }
+}
+
+// returns a porfolio with the latest quotes from json data
+pub async fn create_live_portfolio(
+    positions_str: String,
+    fetch: &FetchSettings,
+) -> (Portfolio, NetworkStatus) {
+    create_live_portfolio_with_logging(positions_str, false, fetch).await
+}
+
+// returns a porfolio with the latest quotes from json data, with optional error logging.
+// Without fetching, positions with a ticker have no quote and are valued at 0. So
+// are they if there is no connector, the cash, manual and loan positions are still shown.
+pub async fn create_live_portfolio_with_logging(
+    positions_str: String,
+    log_errors: bool,
+    fetch: &FetchSettings,
+) -> (Portfolio, NetworkStatus) {
+    let connector = if fetch.enabled {
+        match fetch.connector() {
+            Ok(connector) => Some(connector),
+            Err(e) if log_errors => {
+                eprintln!("{e}, no quotes are fetched");
+                None
+            }
+            Err(e) => {
+                log::warn!("{e}, no quotes are fetched");
+                None
+            }
+        }
+    } else {
+        None
+    };
+    // the names of positions without a Name are only looked up once
+    let provider = connector
+        .map(|connector| StoredNames::open(connector, names_file()).with_name_lookup(fetch.names));
+    let priced = price_portfolio(provider.as_ref(), &positions_str, log_errors).await;
+    if let Some(Err(e)) = provider.map(|provider| provider.save()) {
+        log::warn!("{e}");
+    }
+    priced
+}
+
+// The names of the tickers looked up before, next to the config file
+pub fn names_file() -> std::path::PathBuf {
+    config::config_path(&config::environment).with_file_name("names.json")
+}
+
+// Prices the positions of the json data with the provider, or leaves them unpriced
+// without one. Shared by the commands and the HTTP server, which passes a cached provider.
+pub async fn price_portfolio(
+    connector: Option<&impl PriceProvider>,
+    positions_str: &str,
+    log_errors: bool,
+) -> (Portfolio, NetworkStatus) {
+    let mut portfolio = Portfolio::from_data(positions_str).expect("JSON was not well-formatted");
+
+    let errors = match connector {
+        Some(connector) => {
+            // all positions share the same connector, the progress is only shown in a terminal
+            let show_progress = log_errors && std::io::stderr().is_terminal();
+            portfolio
+                .price(connector, |done, total| {
+                    if show_progress {
+                        eprint!("\rFetching quotes {done}/{total}");
+                        if done == total {
+                            eprintln!();
+                        }
+                    }
+                })
+                .await
+        }
+        None => Vec::new(),
+    };
+    let failed_positions = errors.len();
+    let successful_positions =
+        portfolio.positions.len() + portfolio.watchlist.len() - failed_positions;
+
+    // failed positions are kept unpriced (and fetched again by the TUI), the
+    // commands list why after their output
+    if log_errors && connector.is_some() {
+        PRICING_ERRORS.lock().unwrap().1 += count_tickers(&portfolio.all_positions());
+    }
+    for error in errors {
+        if log_errors {
+            PRICING_ERRORS.lock().unwrap().0.push(error);
+        } else {
+            log::warn!(
+                "Could not price {}: {}: {}",
+                error.name,
+                error.kind,
+                error.message
+            );
+        }
+    }
+
+    // amounts that were verified before a split are out of date
+    if let Some(connector) = connector.filter(|_| log_errors) {
+        for result in check_splits(connector, &portfolio.positions).await {
+            match result {
+                Ok(Some(check)) => eprintln!(
+                    "{}",
+                    format!(
+                        "Warning: {} (use balances --apply-splits to update it)",
+                        check.warning()
+                    )
+                    .yellow()
+                ),
+                Ok(None) => {}
+                Err(e) => eprintln!("{e}"),
+            }
+        }
+    }
+
+    // positions sized by PercentOf get their amount from the current price
+    if let Some(total) = portfolio.total_investment {
+        if let Err(e) = portfolio.resolve_amounts(total) {
+            if log_errors {
+                eprintln!("{e}");
+            }
+        }
+    }
+
+    let network_status = if connector.is_none() {
+        NetworkStatus::Disconnected
+    } else if failed_positions == 0 {
+        NetworkStatus::Connected
+    } else if successful_positions == 0 {
+        NetworkStatus::Disconnected
+    } else {
+        NetworkStatus::Partial
+    };
+
+    (portfolio, network_status)
+}
+
+// write the positions back to the data files, each to the file of its portfolio
+// if several were loaded
+pub fn save_data_files(filenames: &[String], portfolio: &Portfolio) -> Result<(), String> {
+    match expand_paths(filenames)?.as_slice() {
+        [filename] => portfolio.save(filename),
+        paths => save_files(paths, &portfolio.all_positions()),
+    }
+}
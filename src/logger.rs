@@ -0,0 +1,75 @@
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+// Writes the records of this crate to stderr, e.g. "[debug provider] Cache hit for AAPL".
+// The records of the dependencies are left out, they would drown the ones of the quotes.
+struct StderrLogger {
+    level: LevelFilter,
+}
+
+// Warnings by default, every --verbose shows one level more: info, debug and trace
+pub fn level_for(verbosity: u8) -> LevelFilter {
+    match verbosity {
+        0 => LevelFilter::Warn,
+        1 => LevelFilter::Info,
+        2 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    }
+}
+
+fn format_record(level: Level, target: &str, message: &str) -> String {
+    let module = target.strip_prefix("portfolio_rs::").unwrap_or(target);
+    format!("[{} {module}] {message}", level.as_str().to_lowercase())
+}
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level && metadata.target().starts_with("portfolio_rs")
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!(
+                "{}",
+                format_record(record.level(), record.target(), &record.args().to_string())
+            );
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+// Can only be called once, later calls are ignored
+pub fn init(verbosity: u8) {
+    let level = level_for(verbosity);
+    if log::set_boxed_logger(Box::new(StderrLogger { level })).is_ok() {
+        log::set_max_level(level);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_level_for() {
+        assert_eq!(level_for(0), LevelFilter::Warn);
+        assert_eq!(level_for(1), LevelFilter::Info);
+        assert_eq!(level_for(2), LevelFilter::Debug);
+        assert_eq!(level_for(5), LevelFilter::Trace);
+    }
+
+    #[test]
+    fn test_format_record() {
+        assert_eq!(
+            format_record(Level::Debug, "portfolio_rs::provider", "Cache hit for AAPL"),
+            "[debug provider] Cache hit for AAPL"
+        );
+        let logger = StderrLogger {
+            level: LevelFilter::Info,
+        };
+        let metadata = |level, target| Metadata::builder().level(level).target(target).build();
+        assert!(logger.enabled(&metadata(Level::Info, "portfolio_rs::position")));
+        assert!(!logger.enabled(&metadata(Level::Debug, "portfolio_rs::position")));
+        assert!(!logger.enabled(&metadata(Level::Warn, "hyper::client")));
+    }
+}
@@ -1,31 +1,80 @@
 use std::fs::read_to_string;
 
-use crate::portfolio::Portfolio;
-use crate::position::from_string;
-use crate::position::handle_position;
+use portfolio_rs::alerts::run_alerts;
+use portfolio_rs::allocation::{
+    allocation_report, drift_alerts, position_purchases, print_allocation_report,
+    print_contribution_plan, print_drift_alerts, split_contribution,
+};
+use portfolio_rs::attribution::{print_return_contributions, return_contributions};
+use portfolio_rs::benchmark::{compare_to_benchmark, portfolio_cagr, print_benchmark_comparison};
+use portfolio_rs::config::OUTPUT_MODES;
+use portfolio_rs::data_file::{
+    check_data_file, check_writable, save_data, to_json_data, DataFormat,
+};
+use portfolio_rs::diff::{diff, print_diff};
+use portfolio_rs::edit::{NewPosition, PositionChanges, Prompt};
+use portfolio_rs::export::{to_beancount, to_csv, to_json, write_output};
+use portfolio_rs::filter::{print_hidden_note, PositionFilter, PositionSort, SortKey};
+use portfolio_rs::forecast::{
+    forecast, forecast_buckets, forecast_to_csv, forecast_to_json, print_forecast,
+};
+use portfolio_rs::format::{AmountPrecision, NumberFormat};
+use portfolio_rs::grouping::{
+    balance_by_tag, balance_by_tag_exclusive, print_balance_by_tag, GroupBy,
+};
+use portfolio_rs::history::{history_to_csv, load_history, render_history_chart, HistorySelector};
+use portfolio_rs::household::{combine, expand_paths, render_side_by_side};
+use portfolio_rs::import::{import_csv, parse_column_map};
+use portfolio_rs::income::{income_report, print_income_report};
+use portfolio_rs::indicators::{
+    max_drawdown, moving_average, portfolio_max_drawdown, sma_crossover_signal,
+};
+use portfolio_rs::inflation::check_inflation;
+use portfolio_rs::interest::{
+    print_interest_simulation, print_loan_schedules, print_upcoming_payments, upcoming_payments,
+};
+use portfolio_rs::live::{
+    create_live_portfolio, create_live_portfolio_with_logging, names_file, save_data_files,
+    take_pricing_errors, FetchSettings,
+};
+use portfolio_rs::monte_carlo::{
+    bucket_volatilities, monte_carlo_to_csv, monte_carlo_to_json, print_monte_carlo, simulate,
+    MonteCarloSettings,
+};
+use portfolio_rs::movers::{daily_movers, print_movers};
+use portfolio_rs::portfolio::Portfolio;
+use portfolio_rs::position::check_strict;
+use portfolio_rs::position::consolidate;
+use portfolio_rs::position::currency_decimals;
+use portfolio_rs::position::from_string;
+use portfolio_rs::position::merge_duplicates;
+use portfolio_rs::position::missing_data_report;
+use portfolio_rs::position::portfolio_name;
+use portfolio_rs::position::position_defaults;
+use portfolio_rs::position::to_decimal;
+use portfolio_rs::position::total_investment;
+use portfolio_rs::position::{to_string, CsvColumns};
+use portfolio_rs::pricing::render_errors;
+use portfolio_rs::provider::{CachedProvider, StoredNames};
+use portfolio_rs::purchase::{print_share_purchase, shares_for_amount};
+use portfolio_rs::report::{build_report, render_document, DocumentFormat};
+use portfolio_rs::search::{render_search_results, search, QUOTE_TYPES};
+use portfolio_rs::series::{price_series, series_to_csv, series_to_json};
+use portfolio_rs::splits::apply_splits;
+use portfolio_rs::table::{
+    render_account_balances, render_balances, render_position_details, render_watchlist, use_color,
+};
+use portfolio_rs::validate::{
+    has_errors, print_findings, print_validation_issues, validate_data, validate_positions,
+    Finding, Severity,
+};
+use portfolio_rs::watch::{run_watch, WatchOptions};
+use portfolio_rs::{clock, config, edit, history, logger, position, rate_limit, server, tui};
 
-use clap::{arg, Command};
-use serde::Deserialize;
-use serde::Serialize;
-
-mod portfolio;
-mod position;
-mod tui;
-
-#[derive(Serialize, Deserialize)]
-struct Config {
-    portfolio_file: String,
-    currency: String,
-}
-
-impl Default for Config {
-    fn default() -> Self {
-        Self {
-            portfolio_file: "/home/Joe/portfolio.json".to_string(),
-            currency: "EUR".to_string(),
-        }
-    }
-}
+use chrono::Datelike;
+use clap::{arg, Arg, ArgAction, ArgGroup, Command};
+use colored::Colorize;
+use rust_decimal::Decimal;
 
 fn cli() -> Command {
     Command::new("portfolio_rs")
@@ -35,110 +84,837 @@ fn cli() -> Command {
             arg!([FILE] "JSON file with your positions")
                 .help("Portfolio data file (uses config file if not specified)"),
         )
+        .arg(
+            arg!(--file <PATH> "Portfolio data file, instead of the FILE argument")
+                .help("Portfolio data file or a directory of them, instead of the FILE argument. Repeat it to combine several portfolios")
+                .action(ArgAction::Append)
+                .conflicts_with("FILE"),
+        )
+        .arg(
+            arg!(--format <FORMAT> "Format of the portfolio data file")
+                .value_parser(["json", "yaml", "toml", "csv"])
+                .help("Format of the portfolio data file (detected from the extension if not specified)"),
+        )
+        .arg(
+            arg!(--"no-fetch" "Don't fetch quotes, positions with a ticker are valued at 0")
+                .global(true),
+        )
+        .arg(
+            arg!(--strict "Reject keys of the data file portfolio_rs doesn't know, e.g. typos, and positions with errors instead of keeping them, and exit with 1 if a position couldn't be priced")
+                .global(true),
+        )
+        .arg(
+            arg!(-v --verbose "Log the quote requests to stderr, -vv and -vvv for more details")
+                .action(ArgAction::Count)
+                .global(true),
+        )
         .arg(
             arg!(--tab <TAB> "Tab to open at start")
                 .default_value("overview")
                 .help("Specify the tab to open at start (overview/balances)"),
         )
-        .subcommand(Command::new("config").about("Print the path to the config file"))
+        .arg(
+            arg!(--"number-format" <STYLE> "Separators of the amounts, e.g. de for 1.234,56")
+                .value_parser(["en", "de", "fr", "ch"])
+                .global(true),
+        )
         .subcommand(
-            Command::new("balances")
-                .about("Show the current balances of your portfolio (CLI mode)")
+            Command::new("config")
+                .about("Print the path to the config file")
+                .subcommand(
+                    Command::new("init")
+                        .about("Write a config file with all settings commented out")
+                        .arg(arg!(--force "Replace an existing config file")),
+                ),
+        )
+        .subcommand(
+            Command::new("add")
+                .about("Add a position to the data file, asks for it unless it is given as flags")
+                .arg(
+                    arg!([FILE] "JSON file with your positions")
+                        .help("Portfolio data file, created if it doesn't exist (uses config file if not specified)"),
+                )
+                .arg(arg!(--ticker <TICKER> "Ticker of the position, checked with Yahoo"))
+                .arg(arg!(--name <NAME> "Name of the position, needed without a ticker"))
+                .arg(arg!(--class <CLASS> "Asset class, e.g. Stocks or Cash"))
+                .args(position_change_args()),
+        )
+        .subcommand(
+            Command::new("edit")
+                .about("Change the amount, the interest settings or the notes of a position, asks for them unless they are given as flags")
+                .arg(arg!(<NAME> "Name or ticker of the position"))
+                .arg(
+                    arg!([FILE] "JSON file with your positions")
+                        .help("Portfolio data file (uses config file if not specified)"),
+                )
+                .args(position_change_args()),
+        )
+        .subcommand(
+            Command::new("remove")
+                .about("Remove a position from the data file")
+                .arg(arg!(<NAME> "Name or ticker of the position"))
                 .arg(
                     arg!([FILE] "JSON file with your positions")
                         .help("Portfolio data file (uses config file if not specified)"),
+                )
+                .arg(arg!(-y --yes "Don't ask for confirmation")),
+        )
+        .subcommand(
+            Command::new("show")
+                .about("Show the details of a position, with its institution and notes")
+                .arg(arg!(<NAME> "Name or ticker of the position"))
+                .arg(
+                    arg!([FILE]... "JSON file with your positions")
+                        .help("Portfolio data files or directories, several are combined (uses config file if not specified)"),
+                ),
+        )
+        .subcommand(
+            Command::new("balances")
+                .about("Show the current balances of your portfolio (CLI mode)")
+                .arg(
+                    arg!([FILE]... "JSON file with your positions")
+                        .help("Portfolio data files or directories, several are combined (uses config file if not specified)"),
+                )
+                .args(filter_args())
+                .arg(
+                    arg!(--"group-by" <GROUP> "Show subtotals per account, asset class, tag or portfolio")
+                        .value_parser(["account", "asset_class", "tag", "portfolio"]),
+                )
+                .arg(
+                    arg!(--sort <KEY> "Sort the positions")
+                        .value_parser(["balance", "name", "gain", "asset_class"]),
+                )
+                .arg(arg!(--desc "Sort in descending order"))
+                .arg(arg!(--plain "Print the table without colors and asset class subtotals"))
+                .arg(
+                    arg!(--output <FORMAT> "Output format (default: table, or output of the config file)")
+                        .value_parser(OUTPUT_MODES),
+                )
+                .arg(arg!(--file <PATH> "Write the output to a file instead of stdout"))
+                .arg(
+                    arg!(--precision <DIGITS> "Decimal places of the numbers in the CSV output")
+                        .value_parser(clap::value_parser!(u32)),
+                )
+                .arg(arg!(--machine "Plain numbers in the CSV output, without separators"))
+                .arg(arg!(--"apply-splits" "Multiply the amounts by the splits since their LastVerified or PurchaseDate and save them to the data file"))
+                .arg(arg!(--"merge-duplicates" "Merge the positions of the same ticker (or name, without a ticker) in the same account and save them to the data file"))
+                .arg(arg!(--consolidate "Show the positions with the same ticker as one, the data file is not changed"))
+                .arg(
+                    arg!(--"side-by-side" "Show the balances of several portfolio files in a column each")
+                        .conflicts_with_all(["consolidate", "group-by", "plain", "output"]),
+                )
+                .arg(
+                    arg!(--watch <SECONDS> "Refresh the quotes and redraw the table every SECONDS until Ctrl-C")
+                        .value_parser(clap::value_parser!(u64).range(1..))
+                        .conflicts_with_all([
+                            "output",
+                            "apply-splits",
+                            "merge-duplicates",
+                            "plain",
+                            "group-by",
+                        ]),
                 ),
         )
+        .subcommand(
+            Command::new("movers")
+                .about("Show the positions that moved the most over the day, up or down (CLI mode)")
+                .arg(
+                    arg!([FILE]... "JSON file with your positions")
+                        .help("Portfolio data files or directories, several are combined (uses config file if not specified)"),
+                )
+                .args(filter_args()),
+        )
         .subcommand(
             Command::new("allocation")
                 .about("Show the current allocation of your portfolio (CLI mode)")
                 .arg(
-                    arg!([FILE] "JSON file with your positions")
-                        .help("Portfolio data file (uses config file if not specified)"),
+                    arg!([FILE]... "JSON file with your positions")
+                        .help("Portfolio data files or directories, several are combined (uses config file if not specified)"),
+                )
+                .args(filter_args())
+                .arg(
+                    arg!(--output <FORMAT> "Output format")
+                        .value_parser(["chart", "json"])
+                        .default_value("chart"),
+                ),
+        )
+        .subcommand(
+            Command::new("rebalance")
+                .about("Compare your allocation with your target allocation (CLI mode)")
+                .arg(
+                    arg!([FILE]... "JSON file with your positions")
+                        .help("Portfolio data files or directories, several are combined (uses config file if not specified)"),
+                )
+                .args(filter_args())
+                .arg(
+                    arg!(--contribute <AMOUNT> "Split a new contribution across your positions without selling")
+                        .value_parser(clap::value_parser!(Decimal)),
+                )
+                .arg(
+                    arg!(--alert <PCT> "Only list the asset classes that drifted more than PCT percentage points from their target")
+                        .value_parser(clap::value_parser!(f64))
+                        .conflicts_with("contribute"),
+                ),
+        )
+        .subcommand(
+            Command::new("forecast")
+                .about("Project the value of your portfolio with monthly contributions (CLI mode)")
+                .arg(
+                    arg!([FILE]... "JSON file with your positions")
+                        .help("Portfolio data files or directories, several are combined (uses config file if not specified)"),
+                )
+                .args(filter_args())
+                .arg(
+                    arg!(--monthly <AMOUNT> "Contribution at the end of every month, negative for a withdrawal")
+                        .value_parser(clap::value_parser!(Decimal))
+                        .allow_negative_numbers(true)
+                        .default_value("0"),
+                )
+                .arg(
+                    arg!(--years <YEARS> "Number of years to project")
+                        .value_parser(clap::value_parser!(u32).range(1..=100))
+                        .default_value("10"),
+                )
+                .arg(
+                    arg!(--return <RETURN> "Expected annual return of an asset class in %, e.g. Stocks=7 (overrides expected_returns of the config)")
+                        .value_parser(parse_class_percent)
+                        .action(ArgAction::Append),
+                )
+                .arg(
+                    arg!(--"default-return" <PCT> "Expected annual return in % of the asset classes without one")
+                        .value_parser(clap::value_parser!(f64))
+                        .default_value("0"),
+                )
+                .arg(arg!(--"monte-carlo" "Simulate random returns and show the percentiles of the ending value"))
+                .arg(
+                    arg!(--paths <N> "Number of simulated paths")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("10000")
+                        .requires("monte-carlo"),
+                )
+                .arg(
+                    arg!(--seed <SEED> "Seed of the random numbers, for reproducible results")
+                        .value_parser(clap::value_parser!(u64))
+                        .requires("monte-carlo"),
+                )
+                .arg(
+                    arg!(--floor <AMOUNT> "Show the probability of ending below this value")
+                        .value_parser(clap::value_parser!(Decimal))
+                        .requires("monte-carlo"),
+                )
+                .arg(
+                    arg!(--volatility <VOLATILITY> "Annual volatility of an asset class in %, e.g. Stocks=15 (overrides volatilities of the config)")
+                        .value_parser(parse_class_percent)
+                        .action(ArgAction::Append)
+                        .requires("monte-carlo"),
+                )
+                .arg(
+                    arg!(--output <FORMAT> "Output format")
+                        .value_parser(["table", "csv", "json"])
+                        .default_value("table"),
+                ),
+        )
+        .subcommand(
+            Command::new("tags")
+                .about("Show the balances of your portfolio per tag (CLI mode)")
+                .arg(
+                    arg!([FILE]... "JSON file with your positions")
+                        .help("Portfolio data files or directories, several are combined (uses config file if not specified)"),
+                )
+                .args(filter_args())
+                .arg(arg!(--exclusive "Fail if a position has more than one tag")),
+        )
+        .subcommand(
+            Command::new("accounts")
+                .about("Show the balances of your portfolio per account and asset class (CLI mode)")
+                .arg(
+                    arg!([FILE]... "JSON file with your positions")
+                        .help("Portfolio data files or directories, several are combined (uses config file if not specified)"),
+                )
+                .args(filter_args()),
+        )
+        .subcommand(
+            Command::new("diff")
+                .about("Show what changed between two snapshots of your portfolio (CLI mode)")
+                .arg(arg!(<OLD> "Earlier portfolio data file"))
+                .arg(arg!(<NEW> "Later portfolio data file"))
+                .arg(
+                    arg!(--output <FORMAT> "Output format")
+                        .value_parser(["table", "json"])
+                        .default_value("table"),
+                ),
+        )
+        .subcommand(
+            Command::new("interest")
+                .about("Show the upcoming interest payments of your cash positions (CLI mode)")
+                .arg(
+                    arg!([FILE]... "JSON file with your positions")
+                        .help("Portfolio data files or directories, several are combined (uses config file if not specified)"),
+                )
+                .arg(
+                    arg!(--apply "Credit all due interest payments and save them to the data file"),
+                )
+                .arg(
+                    arg!(--within <DAYS> "List the payments due within the next days by date")
+                        .value_parser(clap::value_parser!(u32))
+                        .conflicts_with("apply"),
+                )
+                .arg(
+                    arg!(--until <DATE> "Project the balances after every payment until the date (YYYY-MM-DD)")
+                        .value_parser(parse_date)
+                        .conflicts_with_all(["apply", "within"]),
+                )
+                .arg(
+                    arg!(--"as-of" <DATE> "Run as if it were this date (YYYY-MM-DD), e.g. to credit the interest due back then")
+                        .value_parser(parse_date),
+                )
+                .arg(inflation_arg()),
+        )
+        .subcommand(
+            Command::new("income")
+                .about("Show the logged dividends and interest and the projected income (CLI mode)")
+                .arg(
+                    arg!([FILE]... "JSON file with your positions")
+                        .help("Portfolio data files or directories, several are combined (uses config file if not specified)"),
+                )
+                .args(filter_args()),
+        )
+        .subcommand(
+            Command::new("loans")
+                .about("Show the remaining payments of your loans until payoff (CLI mode)")
+                .arg(
+                    arg!([FILE]... "JSON file with your positions")
+                        .help("Portfolio data files or directories, several are combined (uses config file if not specified)"),
+                ),
+        )
+        .subcommand(
+            Command::new("missing")
+                .about("List the positions that are missing data, e.g. a stock without a ticker (CLI mode)")
+                .arg(
+                    arg!([FILE]... "JSON file with your positions")
+                        .help("Portfolio data files or directories, several are combined (uses config file if not specified)"),
+                ),
+        )
+        .subcommand(
+            Command::new("alerts")
+                .about("Check the price alerts of the config file and post the new ones to the webhook (CLI mode)")
+                .arg(arg!(--state <PATH> "File of the alerts that were sent, next to the config file by default")),
+        )
+        .subcommand(
+            Command::new("history")
+                .about("Show the price history of a ticker (CLI mode)")
+                .arg(arg!(<TICKER> "Ticker symbol, e.g. AAPL"))
+                .arg(
+                    arg!(--interval <INTERVAL> "Interval between quotes, e.g. 1d or 1wk")
+                        .default_value("1d"),
+                )
+                .arg(arg!(--range <RANGE> "Period to fetch, e.g. 1mo or 1y").default_value("1mo"))
+                .arg(
+                    arg!(--output <FORMAT> "Output format, csv and json list open, high, low, close and volume")
+                        .value_parser(["table", "csv", "json"])
+                        .default_value("table"),
+                ),
+        )
+        .subcommand(
+            Command::new("search")
+                .about("Find the Yahoo symbols of a company name, a partial ticker or an ISIN (CLI mode)")
+                .arg(arg!(<QUERY> "e.g. apple, AAP or US0378331005"))
+                .arg(
+                    arg!(--type <TYPE> "Only show the symbols of this quote type")
+                        .value_parser(QUOTE_TYPES),
+                )
+                .arg(
+                    arg!(--limit <N> "Number of results to show")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("10"),
+                ),
+        )
+        .subcommand(
+            Command::new("shares")
+                .about("Show how many shares of a ticker a cash amount buys at its latest price (CLI mode)")
+                .arg(arg!(<TICKER> "Ticker symbol, e.g. AAPL"))
+                .arg(
+                    arg!(<CASH> "Cash to spend")
+                        .value_parser(clap::value_parser!(Decimal)),
+                )
+                .arg(arg!(--fractional "Allow fractional shares, to the shares precision of number_format")),
+        )
+        .subcommand(
+            Command::new("validate")
+                .about("Check a data file before trusting it: parse it strictly and look up every ticker, exits with 1 on errors (CLI mode)")
+                .arg(
+                    arg!([FILE]... "JSON file with your positions")
+                        .help("Portfolio data files or directories, each is checked on its own (uses config file if not specified)"),
+                ),
+        )
+        .subcommand(
+            Command::new("sma")
+                .about("Show the simple moving average of the daily closes of a ticker (CLI mode)")
+                .arg(arg!(<TICKER> "Ticker symbol, e.g. AAPL"))
+                .arg(
+                    arg!(--window <DAYS> "Number of daily closes to average")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("50"),
+                ),
+        )
+        .subcommand(
+            Command::new("signal")
+                .about("Show a moving average crossover signal for a ticker (CLI mode)")
+                .arg(arg!(<TICKER> "Ticker symbol, e.g. AAPL"))
+                .arg(
+                    arg!(--short <DAYS> "Window of the short moving average")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("50"),
+                )
+                .arg(
+                    arg!(--long <DAYS> "Window of the long moving average")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("200"),
                 ),
         )
+        .subcommand(
+            Command::new("drawdown")
+                .about("Show the maximum drawdown of a ticker or of your portfolio (CLI mode)")
+                .arg(
+                    arg!([TICKER] "Ticker symbol, e.g. AAPL")
+                        .help("Ticker symbol, e.g. AAPL (uses the portfolio from --file or the config if not specified)"),
+                )
+                .arg(
+                    arg!(--days <DAYS> "Number of daily closes to look back over")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("250"),
+                )
+                .args(filter_args()),
+        )
+        .subcommand(
+            Command::new("chart")
+                .about("Draw the value of your portfolio over time, as stored by every run of balances (CLI mode)")
+                .arg(arg!(--class <CLASS> "Only the positions of this asset class"))
+                .arg(
+                    arg!(--position <NAME> "Only the position with this name or ticker")
+                        .conflicts_with("class"),
+                )
+                .arg(
+                    arg!(--width <COLUMNS> "Width of the chart, the width of the terminal by default")
+                        .value_parser(clap::value_parser!(usize)),
+                )
+                .arg(
+                    arg!(--height <LINES> "Height of the chart")
+                        .value_parser(clap::value_parser!(u16).range(1..=100))
+                        .default_value("10"),
+                )
+                .arg(
+                    arg!(--output <FORMAT> "Draw the chart or list the stored values")
+                        .value_parser(["chart", "csv"])
+                        .default_value("chart"),
+                )
+                .arg(arg!(--file <PATH> "Write the output to a file instead of stdout")),
+        )
+        .subcommand(
+            Command::new("report")
+                .about("Write a Markdown or HTML summary of your portfolio (CLI mode)")
+                .arg(
+                    arg!([FILE]... "JSON file with your positions")
+                        .help("Portfolio data files or directories, several are combined (uses config file if not specified)"),
+                )
+                .args(filter_args())
+                .arg(
+                    arg!(--format <FORMAT> "Document format")
+                        .value_parser(["markdown", "html"])
+                        .default_value("markdown"),
+                )
+                .arg(arg!(--file <PATH> "Write the report to a file instead of stdout"))
+                .arg(arg!(--machine "Plain numbers without separators or currency symbols")),
+        )
         .subcommand(
             Command::new("performance")
                 .about("Show the performance of your portfolio (CLI mode)")
                 .arg(
-                    arg!([FILE] "JSON file with your positions")
-                        .help("Portfolio data file (uses config file if not specified)"),
+                    arg!([FILE]... "JSON file with your positions")
+                        .help("Portfolio data files or directories, several are combined (uses config file if not specified)"),
+                )
+                .args(filter_args())
+                .arg(arg!(--contributions "Show how much each position contributed to the return"))
+                .arg(
+                    arg!(--benchmark <TICKER> "Compare the return with a benchmark, e.g. SPY")
+                        .conflicts_with("contributions"),
+                )
+                .arg(
+                    arg!(--cagr "Show the growth of the portfolio per year (CAGR) over the period")
+                        .conflicts_with_all(["contributions", "benchmark"]),
+                )
+                .group(
+                    ArgGroup::new("period")
+                        .args(["contributions", "benchmark", "cagr"])
+                        .multiple(true),
+                )
+                .arg(
+                    arg!(--since <DATE> "Start of the period (YYYY-MM-DD), the first of the year by default")
+                        .value_parser(parse_date)
+                        .requires("period"),
+                )
+                .arg(
+                    arg!(--until <DATE> "End of the period (YYYY-MM-DD), today by default")
+                        .value_parser(parse_date)
+                        .requires("period"),
+                )
+                .arg(
+                    arg!(--window <DAYS> "Days after each date searched for its close, 3 by default (1 for crypto)")
+                        .value_parser(clap::value_parser!(u32).range(1..=30)),
+                )
+                .arg(inflation_arg()),
+        )
+        .subcommand(
+            Command::new("import")
+                .about("Convert the positions of another format into a data file")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("csv")
+                        .about("Import a spreadsheet with a position per row, the rows that can't be read are reported with their line number")
+                        .arg(arg!(<CSV> "CSV file with a header row, e.g. Name,Ticker,AssetClass,Amount"))
+                        .arg(arg!(--out <PATH> "Data file to write, JSON, YAML or TOML by its extension (prints JSON if not specified)"))
+                        .arg(
+                            arg!(--map <RENAME> "Read a column as a field of the positions, e.g. Qty=Amount")
+                                .value_parser(parse_column_map)
+                                .action(ArgAction::Append),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("serve")
+                .about("Serve the balances as JSON and Prometheus metrics over HTTP, e.g. for a dashboard")
+                .arg(
+                    arg!([FILE]... "JSON file with your positions")
+                        .help("Portfolio data files or directories, several are combined (uses config file if not specified)"),
+                )
+                .arg(
+                    arg!(--address <ADDRESS> "Address to listen on")
+                        .value_parser(clap::value_parser!(std::net::SocketAddr))
+                        .default_value(server::DEFAULT_ADDRESS),
+                )
+                .arg(
+                    arg!(--ttl <SECONDS> "Answer with the cached quotes until they are this old (default: 60, or cache_ttl_seconds of the config file)")
+                        .value_parser(clap::value_parser!(u64)),
                 ),
         )
 }
 
-// returns a porfolio with the latest quotes from json data
-pub async fn create_live_portfolio(
-    positions_str: String,
-) -> (Portfolio, crate::tui::NetworkStatus) {
-    create_live_portfolio_with_logging(positions_str, false).await
+// Filters shared by the reports, all given filters have to match.
+// --tag can be given several times, only positions carrying all of the tags are kept
+fn filter_args() -> [Arg; 4] {
+    [
+        arg!(--class <CLASS> "Only include positions of this asset class"),
+        arg!(--min <BALANCE> "Only include positions with at least this balance")
+            .value_parser(clap::value_parser!(Decimal)),
+        arg!(--search <TEXT> "Only include positions whose name or ticker contains this text"),
+        arg!(--tag <TAG> "Only include positions with this tag").action(ArgAction::Append),
+    ]
 }
 
-// returns a porfolio with the latest quotes from json data, with optional error logging
-pub async fn create_live_portfolio_with_logging(
-    positions_str: String,
-    log_errors: bool,
-) -> (Portfolio, crate::tui::NetworkStatus) {
-    let positions = from_string(&positions_str);
-    let mut portfolio = Portfolio::new();
-    let _total_positions = positions.len();
-    let mut successful_positions = 0;
-    let mut failed_positions = 0;
-
-    // move tasks into the async closure passed to tokio::spawn()
-    let tasks: Vec<_> = positions
-        .into_iter()
-        .map(move |mut position| tokio::spawn(async move { handle_position(&mut position).await }))
-        .collect();
+// The amount and interest flags of add and edit
+fn position_change_args() -> Vec<Arg> {
+    vec![
+        arg!(--amount <AMOUNT> "Amount, e.g. the number of shares")
+            .value_parser(clap::value_parser!(Decimal)),
+        arg!(--"interest-rate" <PCT> "Annual interest rate in % of a cash position")
+            .value_parser(clap::value_parser!(Decimal)),
+        arg!(--"payment-frequency-days" <DAYS> "Days between the interest payments")
+            .value_parser(clap::value_parser!(u32).range(1..)),
+        arg!(--"next-interest-payment" <DATE> "Date of the next interest payment (YYYY-MM-DD)")
+            .value_parser(parse_date),
+        arg!(--institution <NAME> "Bank or broker the position is held at, \"\" to remove it"),
+        arg!(--notes <TEXT> "Notes shown by the show command, \"\" to remove them"),
+    ]
+}
 
-    for task in tasks {
-        let p = task.await;
-        match p {
-            Ok(p) => match p {
-                Ok(p) => {
-                    portfolio.add_position(p);
-                    successful_positions += 1;
-                }
-                Err(e) => {
-                    if log_errors {
-                        eprintln!("Error handling position: {e:?}");
-                    }
-                    // Skip positions with network errors (will be retried in TUI mode)
-                    failed_positions += 1;
-                }
-            },
-            Err(e) => {
-                if log_errors {
-                    eprintln!("Error handling position: {e:?}");
-                }
-                // Skip positions with task errors (will be retried in TUI mode)
-                failed_positions += 1;
-            }
-        }
+fn position_changes(matches: &clap::ArgMatches) -> PositionChanges {
+    PositionChanges {
+        amount: matches.get_one::<Decimal>("amount").copied(),
+        interest_rate: matches.get_one::<Decimal>("interest-rate").copied(),
+        payment_frequency_days: matches.get_one::<u32>("payment-frequency-days").copied(),
+        next_interest_payment: matches
+            .get_one::<chrono::NaiveDate>("next-interest-payment")
+            .copied(),
+        institution: matches.get_one::<String>("institution").cloned(),
+        notes: matches.get_one::<String>("notes").cloned(),
     }
+}
 
-    let network_status = if failed_positions == 0 {
-        crate::tui::NetworkStatus::Connected
-    } else if successful_positions == 0 {
-        crate::tui::NetworkStatus::Disconnected
-    } else {
-        crate::tui::NetworkStatus::Partial
-    };
+fn parse_date(value: &str) -> Result<chrono::NaiveDate, String> {
+    chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| format!("Invalid date '{value}', expected YYYY-MM-DD"))
+}
+
+// --inflation of the commands that show returns or yields
+fn inflation_arg() -> Arg {
+    arg!(--inflation <PCT> "Annual inflation in %, to show the real figures next to the nominal ones (default: inflation_rate of the config file)")
+        .value_parser(|value: &str| {
+            value
+                .parse::<f64>()
+                .map_err(|_| format!("Invalid value '{value}', expected a percentage, e.g. 2.5"))
+                .and_then(check_inflation)
+        })
+        .allow_negative_numbers(true)
+}
+
+// CLASS=PCT, e.g. Stocks=7
+fn parse_class_percent(value: &str) -> Result<(String, f64), String> {
+    let invalid = || format!("Invalid value '{value}', expected CLASS=PCT, e.g. Stocks=7");
+    let (class, percent) = value.split_once('=').ok_or_else(invalid)?;
+    let percent = percent.trim().parse::<f64>().map_err(|_| invalid())?;
+    if class.trim().is_empty() {
+        return Err(invalid());
+    }
+    Ok((class.trim().to_string(), percent))
+}
 
-    (portfolio, network_status)
+// Seed of a simulation without --seed
+fn random_seed() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|time| time.as_nanos() as u64)
+        .unwrap_or_default()
 }
 
-// TODO: change this to store entire portfolio in DB
+fn position_filter(matches: &clap::ArgMatches) -> PositionFilter {
+    PositionFilter {
+        asset_class: get_arg_value(Some(matches), "class"),
+        min_balance: matches.get_one::<Decimal>("min").copied(),
+        search: get_arg_value(Some(matches), "search"),
+        tags: matches
+            .get_many::<String>("tag")
+            .map(|tags| tags.cloned().collect())
+            .unwrap_or_default(),
+    }
+}
+
+// Writes the names of the positions without a Name into their (JSON) data files,
+// the other files are left as they are and use the stored names
+async fn save_resolved_names(filenames: &[String], fetch: &FetchSettings) -> Result<(), String> {
+    let provider = StoredNames::open(fetch.connector()?, names_file());
+    for path in expand_paths(filenames)? {
+        if check_writable(&path).is_err() || DataFormat::from_path(&path) != DataFormat::Json {
+            continue;
+        }
+        let mut document = edit::read_document(&path, false)?;
+        let added = edit::add_missing_names(&mut document, &provider).await?;
+        if !added.is_empty() {
+            edit::save_document(&path, &document)?;
+            log::info!("Wrote the names {} to {path}", added.join(", "));
+        }
+    }
+    provider.save()
+}
+
+// stores the total and the balance of every position, which the chart command draws
 fn store_balance_in_db(portfolio: &Portfolio) {
-    let db = sled::open("database").unwrap();
-    let curr_value = portfolio.get_total_value();
-    let curr_time = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    let result = sled::open("database")
+        .map_err(|e| format!("Database error: {e}"))
+        .and_then(|db| {
+            history::store_snapshot(
+                &db,
+                &portfolio.positions,
+                portfolio.get_total_value(),
+                chrono::Local::now().naive_local(),
+            )
+        });
+    if let Err(e) = result {
+        eprintln!("{e}");
+    }
+}
 
-    db.insert(curr_time, curr_value.to_string().as_bytes())
-        .unwrap();
+// Multiplies the amounts by the splits since they were verified and saves them,
+// returns the updated data
+async fn save_split_amounts(
+    filenames: &[String],
+    positions_str: &str,
+    precision: &AmountPrecision,
+    fetch: &FetchSettings,
+) -> Result<String, String> {
+    let connector = fetch.connector()?;
+    let mut portfolio = Portfolio::from_data(positions_str)?;
+
+    let (applied, errors) = apply_splits(
+        &connector,
+        &mut portfolio.positions,
+        chrono::Utc::now(),
+        precision,
+    )
+    .await;
+    for e in errors {
+        eprintln!("{e}");
+    }
+    // stderr, so that the balances can still be piped as CSV or JSON
+    if applied.is_empty() {
+        eprintln!("No splits to apply.");
+        return Ok(positions_str.to_string());
+    }
+    for check in &applied {
+        eprintln!("{}", check.applied());
+    }
+    save_data_files(filenames, &portfolio)?;
+    portfolio.to_data_string()
+}
 
-    // block until all operations are stable on disk
-    db.flush().unwrap();
+// Merges the duplicate positions and saves them, returns the updated data
+fn save_merged_duplicates(filenames: &[String], positions_str: &str) -> Result<String, String> {
+    let mut portfolio = Portfolio::from_data(positions_str)?;
+    let count = portfolio.positions.len();
+    portfolio.positions = merge_duplicates(std::mem::take(&mut portfolio.positions))?;
+    // stderr, so that the balances can still be piped as CSV or JSON
+    if portfolio.positions.len() == count {
+        eprintln!("No duplicate positions to merge.");
+        return Ok(positions_str.to_string());
+    }
+    eprintln!(
+        "Merged {count} positions into {}.",
+        portfolio.positions.len()
+    );
+    save_data_files(filenames, &portfolio)?;
+    portfolio.to_data_string()
+}
+
+// Imports the rows of a spreadsheet into a new data file, or prints them as JSON.
+// With strict the rows that can't be read fail the import instead of being left out.
+fn import_csv_file(
+    path: &str,
+    columns: &CsvColumns,
+    renames: &[(String, String)],
+    out: Option<&str>,
+    strict: bool,
+) -> Result<(), String> {
+    check_data_file(path)?;
+    if let Some(out) = out.filter(|out| std::path::Path::new(out).exists()) {
+        return Err(format!("{out} exists already, it is not overwritten"));
+    }
+    let file = std::fs::File::open(path).map_err(|e| format!("Error reading file {path}: {e}"))?;
+    let import = import_csv(file, columns, renames).map_err(|e| format!("{path}: {e}"))?;
+    match import.ignored_columns.as_slice() {
+        [] => (),
+        [column] => {
+            eprintln!("Warning: the column {column} is no field of a position, it is not imported")
+        }
+        columns => eprintln!(
+            "Warning: the columns {} are no fields of a position, they are not imported",
+            columns.join(", ")
+        ),
+    }
+    for e in &import.errors {
+        eprintln!("Warning: {e}, the row is not imported");
+    }
+    if strict && !import.errors.is_empty() {
+        return Err(format!(
+            "{path}: {} rows can't be read, nothing is imported with --strict",
+            import.errors.len()
+        ));
+    }
+    if import.positions.is_empty() {
+        return Err(format!("{path}: no row was imported"));
+    }
+
+    let data = to_string(&import.positions)?;
+    let rows = import.positions.len() + import.errors.len();
+    match out {
+        Some(out) => {
+            save_data(out, &data)?;
+            eprintln!(
+                "Imported {} of {rows} rows into {out}.",
+                import.positions.len()
+            );
+        }
+        None => println!("{data}"),
+    }
+    Ok(())
+}
+
+// Runs add, edit or remove on a single data file. Without flags the position is
+// asked for on the terminal.
+async fn edit_data_file(
+    command: &str,
+    matches: &clap::ArgMatches,
+    filenames: &[String],
+    fetch: &FetchSettings,
+) -> Result<(), String> {
+    let path = match expand_paths(filenames)?.as_slice() {
+        [] => {
+            return Err(
+                "No portfolio file specified. Use --help for usage information.".to_string(),
+            )
+        }
+        [path] => path.clone(),
+        _ => {
+            return Err(format!(
+                "{command} changes a single data file, pass it as FILE"
+            ))
+        }
+    };
+    let mut document = edit::read_document(&path, command == "add")?;
+    if matches.get_flag("strict") {
+        check_strict(&document.to_string()).map_err(|e| format!("{path}: {e}"))?;
+    }
+    let mut prompt = Prompt::new(std::io::stdin().lock(), std::io::stdout());
+    let name = || get_arg_value(Some(matches), "NAME").unwrap_or_default();
+
+    let message = match command {
+        "add" => {
+            let changes = position_changes(matches);
+            let ticker = get_arg_value(Some(matches), "ticker");
+            let asked = ticker.is_none()
+                && !matches.contains_id("name")
+                && !matches.contains_id("class")
+                && changes.is_empty();
+            let new = if asked {
+                if !fetch.enabled {
+                    return Err(
+                        "Tickers are checked with Yahoo, pass the position as flags to add it with --no-fetch"
+                            .to_string(),
+                    );
+                }
+                prompt.new_position(&fetch.connector()?).await?
+            } else {
+                if let Some(ticker) = ticker.as_deref().filter(|_| fetch.enabled) {
+                    edit::resolve_ticker(&fetch.connector()?, ticker).await?;
+                }
+                NewPosition {
+                    name: get_arg_value(Some(matches), "name"),
+                    ticker,
+                    asset_class: get_arg_value(Some(matches), "class")
+                        .ok_or("--class is needed to add a position with flags")?,
+                    changes,
+                }
+            };
+            let position = edit::add(&mut document, &new)?;
+            format!("Added {} to {path}", position.get_name())
+        }
+        "edit" => {
+            let changes = position_changes(matches);
+            let changes = if changes.is_empty() {
+                prompt.changes(&edit::get(&mut document, &name())?)?
+            } else {
+                changes
+            };
+            if changes.is_empty() {
+                println!("Nothing was changed.");
+                return Ok(());
+            }
+            let position = edit::edit(&mut document, &name(), &changes)?;
+            format!("Updated {}", position.get_name())
+        }
+        _ => {
+            let position = edit::get(&mut document, &name())?;
+            if !matches.get_flag("yes")
+                && !prompt.confirm(&format!("Remove {}?", position.get_name()))?
+            {
+                println!("Nothing was removed.");
+                return Ok(());
+            }
+            edit::remove(&mut document, &name())?;
+            format!("Removed {}", position.get_name())
+        }
+    };
+    edit::save_document(&path, &document)?;
+    println!("{message}");
+    Ok(())
 }
 
 fn open_encrpted_file(filename: String) -> String {
@@ -158,131 +934,1052 @@ fn get_arg_value(matches: Option<&clap::ArgMatches>, arg_name: &str) -> Option<S
     matches.and_then(|m| m.get_one::<String>(arg_name).map(|s| s.to_string()))
 }
 
-fn parse_tab(tab_str: Option<String>) -> Option<crate::tui::Tab> {
+fn parse_tab(tab_str: Option<String>) -> Option<tui::Tab> {
     match tab_str {
-        Some(s) => crate::tui::Tab::from_str(&s).or(Some(crate::tui::Tab::Overview)),
-        None => Some(crate::tui::Tab::Overview), // Default to overview
+        Some(s) => s.parse().ok().or(Some(tui::Tab::Overview)),
+        None => Some(tui::Tab::Overview), // Default to overview
     }
 }
 
-#[tokio::main]
-async fn main() {
-    let cfg: Config = confy::load("portfolio", "config").unwrap();
+// The settings of a run, from the config file and the global flags
+struct Context {
+    cfg: config::Config,
+    config_path: std::path::PathBuf,
+    number_format: NumberFormat,
+    max_quote_age: chrono::Duration,
+    fetch: FetchSettings,
+    strict: bool,
+    data_format: Option<DataFormat>,
+    file_args: Vec<String>,
+}
 
-    let matches = cli().get_matches();
+impl Context {
+    // The files of the subcommand or the main args, then --file, then the config file
+    fn filenames(&self, matches: Option<&clap::ArgMatches>) -> Vec<String> {
+        let mut filenames: Vec<String> = matches
+            .and_then(|m| m.get_many::<String>("FILE"))
+            .map(|files| files.cloned().collect())
+            .unwrap_or_default();
+        if filenames.is_empty() {
+            filenames.clone_from(&self.file_args);
+        }
 
-    // Handle config subcommand
-    if let Some(_matches) = matches.subcommand_matches("config") {
-        println!(
-            "Your config file is located here: \n{}",
-            confy::get_configuration_file_path("portfolio", "config")
-                .unwrap()
-                .to_str()
-                .unwrap()
-        );
-        return;
+        self.cfg.data_files(filenames)
     }
 
-    // Get filename from arguments or config
-    let get_filename = |matches: Option<&clap::ArgMatches>| -> String {
-        let mut filename = String::new();
+    // Read the data of one file, as JSON
+    fn read_file(&self, filename: &str) -> Result<String, String> {
+        check_data_file(filename)?;
 
-        // Try to get filename from subcommand or main args
-        if let Some(f) = get_arg_value(matches, "FILE") {
-            filename = f;
-        }
+        let positions_str = if filename.ends_with(".gpg") {
+            open_encrpted_file(filename.to_string())
+        } else {
+            read_to_string(filename).map_err(|e| format!("Error reading file {filename}: {e}"))?
+        };
 
-        // Fall back to config file
-        if filename.is_empty() {
-            filename.clone_from(&cfg.portfolio_file);
-        }
+        to_json_data(
+            &positions_str,
+            self.data_format
+                .unwrap_or_else(|| DataFormat::from_path(filename)),
+            &self.cfg.csv_columns,
+        )
+        .map_err(|e| format!("{filename}: {e}"))
+    }
 
-        filename
-    };
+    // Load the data of one file
+    fn load_file(&self, filename: &str) -> Result<String, String> {
+        let data = self.read_file(filename)?;
+        if self.strict {
+            check_strict(&data).map_err(|e| format!("{filename}: {e}"))?;
+        }
+        // data that can't be parsed is reported by the command
+        if let Ok(positions) = position::try_from_string(&data) {
+            let issues = validate_positions(&positions);
+            print_validation_issues(filename, &issues);
+            if self.strict && issues.iter().any(|issue| issue.severity == Severity::Error) {
+                return Err(format!(
+                    "{filename}: the positions have errors, they are rejected with --strict"
+                ));
+            }
+        }
+        Ok(data)
+    }
 
-    // Load portfolio data
-    let load_portfolio = |filename: String| -> Result<String, String> {
-        if filename.is_empty() {
+    // Load portfolio data, several files (or a directory of them) are combined
+    // into one household with a portfolio per file
+    fn load_portfolio(&self, filenames: Vec<String>) -> Result<String, String> {
+        if filenames.is_empty() {
             return Err(
                 "No portfolio file specified. Use --help for usage information.".to_string(),
             );
         }
 
-        let positions_str = if filename.ends_with(".gpg") {
-            open_encrpted_file(filename.to_string())
-        } else if let Ok(s) = read_to_string(&filename) {
-            s
-        } else {
-            return Err(format!("Error reading file: {filename}"));
-        };
+        match expand_paths(&filenames)?.as_slice() {
+            [filename] => self.load_file(filename),
+            paths => {
+                let files = paths
+                    .iter()
+                    .map(|path| Ok((path.clone(), self.load_file(path)?)))
+                    .collect::<Result<Vec<(String, String)>, String>>()?;
+                combine(&files)
+            }
+        }
+    }
 
-        Ok(positions_str)
-    };
+    // The priced portfolio of the files of a subcommand with its filters applied,
+    // and the number of positions the filters hide
+    async fn filtered_portfolio(
+        &self,
+        sub_matches: &clap::ArgMatches,
+    ) -> Result<(Portfolio, usize), String> {
+        let positions_str = self.load_portfolio(self.filenames(Some(sub_matches)))?;
+        let (mut portfolio, _network_status) =
+            create_live_portfolio_with_logging(positions_str, true, &self.fetch).await;
+        let hidden = portfolio.apply_filter(&position_filter(sub_matches));
+        Ok((portfolio, hidden))
+    }
 
-    // Handle subcommands or default to TUI
-    match matches.subcommand() {
-        Some(("balances", sub_matches)) => {
-            let filename = get_filename(Some(sub_matches));
-            match load_portfolio(filename) {
-                Ok(positions_str) => {
-                    let (portfolio, _network_status) =
-                        create_live_portfolio_with_logging(positions_str, true).await;
-                    portfolio.print(true);
-                    store_balance_in_db(&portfolio);
-                }
-                Err(e) => eprintln!("{e}"),
+    // Commands that only show quotes can't do anything offline
+    fn require_fetch(&self) -> Result<(), String> {
+        if self.fetch.enabled {
+            Ok(())
+        } else {
+            Err("This command fetches quotes and can't be used with --no-fetch.".to_string())
+        }
+    }
+}
+
+async fn show_command(ctx: &Context, sub_matches: &clap::ArgMatches) -> Result<(), String> {
+    let name = get_arg_value(Some(sub_matches), "NAME").unwrap_or_default();
+    let positions_str = ctx.load_portfolio(ctx.filenames(Some(sub_matches)))?;
+    let (portfolio, _network_status) =
+        create_live_portfolio_with_logging(positions_str, true, &ctx.fetch).await;
+    let found: Vec<_> = portfolio
+        .positions
+        .iter()
+        .filter(|position| position.is_named(&name))
+        .collect();
+    if found.is_empty() {
+        eprintln!("There is no position named {name}");
+    }
+    let width = crossterm::terminal::size()
+        .map(|(width, _)| width as usize)
+        .unwrap_or(80);
+    for (index, position) in found.into_iter().enumerate() {
+        if index > 0 {
+            println!();
+        }
+        print!(
+            "{}",
+            render_position_details(position, &ctx.number_format, width)
+        );
+    }
+    Ok(())
+}
+
+async fn balances_command(ctx: &Context, sub_matches: &clap::ArgMatches) -> Result<(), String> {
+    let filenames = ctx.filenames(Some(sub_matches));
+    let filter = position_filter(sub_matches);
+    let number_format = &ctx.number_format;
+    let mut positions_str = ctx.load_portfolio(filenames.clone())?;
+    if sub_matches.get_flag("apply-splits") {
+        ctx.require_fetch()?;
+        positions_str = save_split_amounts(
+            &filenames,
+            &positions_str,
+            &number_format.amount_precision,
+            &ctx.fetch,
+        )
+        .await?;
+    }
+    if sub_matches.get_flag("merge-duplicates") {
+        positions_str = save_merged_duplicates(&filenames, &positions_str)?;
+    }
+    if let Some(&seconds) = sub_matches.get_one::<u64>("watch") {
+        return watch_balances(ctx, sub_matches, &positions_str, filter, seconds).await;
+    }
+
+    let (mut portfolio, _network_status) =
+        create_live_portfolio_with_logging(positions_str, true, &ctx.fetch).await;
+    if sub_matches.get_flag("consolidate") {
+        portfolio.positions = consolidate(std::mem::take(&mut portfolio.positions));
+    }
+    let hidden = portfolio.apply_filter(&filter);
+    if let Some(key) =
+        get_arg_value(Some(sub_matches), "sort").and_then(|s| s.parse::<SortKey>().ok())
+    {
+        portfolio.sort(&PositionSort {
+            key,
+            descending: sub_matches.get_flag("desc"),
+        });
+    }
+    let output = ctx.cfg.output(get_arg_value(Some(sub_matches), "output"));
+    let file = get_arg_value(Some(sub_matches), "file");
+    if output == "csv" {
+        let precision = sub_matches
+            .get_one::<u32>("precision")
+            .copied()
+            .unwrap_or(number_format.precision);
+        let format = if sub_matches.get_flag("machine") {
+            NumberFormat::machine(&ctx.cfg.currency, precision)
+        } else {
+            number_format.clone().with_precision(precision)
+        };
+        if let Err(e) = to_csv(&portfolio.positions, &ctx.cfg.currency, &format)
+            .and_then(|csv| write_output(&csv, file.as_deref()))
+        {
+            eprintln!("{e}");
+        }
+    } else if output == "json" {
+        if let Err(e) = to_json(
+            &portfolio.positions,
+            &portfolio.watchlist,
+            &ctx.cfg.currency,
+        )
+        .and_then(|json| write_output(&format!("{json}\n"), file.as_deref()))
+        {
+            eprintln!("{e}");
+        }
+    } else if output == "beancount" {
+        let beancount = to_beancount(&portfolio.positions, &ctx.cfg.currency, chrono::Utc::now());
+        if let Err(e) = write_output(&beancount, file.as_deref()) {
+            eprintln!("{e}");
+        }
+    } else {
+        print_balances(ctx, sub_matches, &portfolio);
+        print_hidden_note(hidden);
+        portfolio.print_stale_warnings(ctx.max_quote_age);
+        portfolio.print_override_note();
+        portfolio.print_currency_note(&ctx.cfg.currency);
+    }
+    // a filtered, unpriced or simulated balance is not the balance of the whole portfolio
+    let simulated = portfolio
+        .positions
+        .iter()
+        .any(|position| position.has_price_override() || position.is_unpriced());
+    if filter.is_empty() && ctx.fetch.enabled && !simulated {
+        store_balance_in_db(&portfolio);
+    }
+    Ok(())
+}
+
+// The balances table in the layout picked by the flags, then the watchlist
+fn print_balances(ctx: &Context, sub_matches: &clap::ArgMatches, portfolio: &Portfolio) {
+    let number_format = &ctx.number_format;
+    match get_arg_value(Some(sub_matches), "group-by").and_then(|s| s.parse::<GroupBy>().ok()) {
+        None if sub_matches.get_flag("side-by-side") => print!(
+            "{}",
+            render_side_by_side(&portfolio.positions, number_format)
+        ),
+        Some(group_by) => portfolio.print_grouped(group_by, number_format, ctx.max_quote_age),
+        None if sub_matches.get_flag("plain") => {
+            portfolio.print(true, number_format, ctx.max_quote_age)
+        }
+        None => print!(
+            "{}",
+            render_balances(
+                &portfolio.positions,
+                number_format,
+                use_color(),
+                ctx.max_quote_age
+            )
+        ),
+    }
+    if !portfolio.watchlist.is_empty() {
+        println!("\nWatchlist");
+        print!(
+            "{}",
+            render_watchlist(
+                &portfolio.watchlist,
+                number_format,
+                use_color(),
+                ctx.max_quote_age
+            )
+        );
+    }
+}
+
+// balances --watch, the balances are refreshed every few seconds until Ctrl-C
+async fn watch_balances(
+    ctx: &Context,
+    sub_matches: &clap::ArgMatches,
+    positions_str: &str,
+    filter: PositionFilter,
+    seconds: u64,
+) -> Result<(), String> {
+    ctx.require_fetch()?;
+    // a quote is reused within a refresh, e.g. for a ticker listed twice
+    let provider = CachedProvider::new(
+        ctx.fetch.connector()?,
+        chrono::Duration::seconds(seconds as i64 / 2),
+    )
+    .with_name_lookup(ctx.fetch.names);
+    let mut positions = from_string(positions_str);
+    if sub_matches.get_flag("consolidate") {
+        positions = consolidate(positions);
+    }
+    let options = WatchOptions {
+        interval_seconds: seconds,
+        filter,
+        sort: get_arg_value(Some(sub_matches), "sort")
+            .and_then(|s| s.parse::<SortKey>().ok())
+            .map(|key| PositionSort {
+                key,
+                descending: sub_matches.get_flag("desc"),
+            }),
+        max_quote_age: ctx.max_quote_age,
+        color: use_color(),
+    };
+    run_watch(
+        &provider,
+        positions,
+        total_investment(positions_str),
+        options,
+        &ctx.number_format,
+    )
+    .await;
+    Ok(())
+}
+
+async fn movers_command(ctx: &Context, sub_matches: &clap::ArgMatches) -> Result<(), String> {
+    ctx.require_fetch()?;
+    let mut portfolio = ctx
+        .load_portfolio(ctx.filenames(Some(sub_matches)))
+        .and_then(|data| Portfolio::from_data(&data))?;
+    let connector = ctx.fetch.connector()?;
+    let hidden = portfolio.apply_filter(&position_filter(sub_matches));
+    let rows = daily_movers(&connector, &portfolio.positions).await?;
+    print_movers(&rows, &ctx.number_format);
+    print_hidden_note(hidden);
+    Ok(())
+}
+
+async fn allocation_command(ctx: &Context, sub_matches: &clap::ArgMatches) -> Result<(), String> {
+    let (portfolio, hidden) = ctx.filtered_portfolio(sub_matches).await?;
+    if get_arg_value(Some(sub_matches), "output").as_deref() == Some("json") {
+        let report = build_report(&portfolio.positions, &ctx.cfg.allocation_targets);
+        println!("{}", report.to_json()?);
+    } else {
+        portfolio.draw_pie_chart();
+        portfolio.print_allocation(&ctx.number_format);
+        print_hidden_note(hidden);
+    }
+    Ok(())
+}
+
+async fn rebalance_command(ctx: &Context, sub_matches: &clap::ArgMatches) -> Result<(), String> {
+    let targets = &ctx.cfg.allocation_targets;
+    if targets.is_empty() {
+        return Err("No allocation targets configured. Add allocation_targets to your config file (see portfolio_rs config).".to_string());
+    }
+    let (portfolio, hidden) = ctx.filtered_portfolio(sub_matches).await?;
+    if let Some(contribution) = sub_matches.get_one::<Decimal>("contribute") {
+        let contributions = split_contribution(&portfolio.positions, targets, *contribution);
+        let purchases = position_purchases(&portfolio.positions, &contributions);
+        print_contribution_plan(&purchases, &contributions, &ctx.number_format);
+    } else if let Some(threshold) = sub_matches.get_one::<f64>("alert") {
+        let alerts = drift_alerts(&portfolio.positions, targets, *threshold);
+        print_drift_alerts(&alerts, *threshold, &ctx.number_format);
+    } else {
+        let report = allocation_report(&portfolio.positions, targets);
+        print_allocation_report(&report, &ctx.number_format);
+    }
+    print_hidden_note(hidden);
+    Ok(())
+}
+
+async fn diff_command(ctx: &Context, sub_matches: &clap::ArgMatches) -> Result<(), String> {
+    let old = get_arg_value(Some(sub_matches), "OLD").unwrap_or_default();
+    let new = get_arg_value(Some(sub_matches), "NEW").unwrap_or_default();
+    let old_str = ctx.load_portfolio(vec![old])?;
+    let new_str = ctx.load_portfolio(vec![new])?;
+    // both snapshots are valued with today's prices
+    let (old, _) = create_live_portfolio_with_logging(old_str, true, &ctx.fetch).await;
+    let (new, _) = create_live_portfolio_with_logging(new_str, true, &ctx.fetch).await;
+    let diff = diff(&old.positions, &new.positions);
+    if get_arg_value(Some(sub_matches), "output").as_deref() == Some("json") {
+        println!("{}", diff.to_json()?);
+    } else {
+        print_diff(&diff, &ctx.number_format);
+    }
+    Ok(())
+}
+
+async fn forecast_command(ctx: &Context, sub_matches: &clap::ArgMatches) -> Result<(), String> {
+    let mut expected_returns = ctx.cfg.expected_returns.clone();
+    if let Some(returns) = sub_matches.get_many::<(String, f64)>("return") {
+        expected_returns.extend(returns.cloned());
+    }
+    let default_return = *sub_matches.get_one::<f64>("default-return").unwrap();
+    let monthly = *sub_matches.get_one::<Decimal>("monthly").unwrap();
+    let years = *sub_matches.get_one::<u32>("years").unwrap();
+    let number_format = &ctx.number_format;
+    let (portfolio, hidden) = ctx.filtered_portfolio(sub_matches).await?;
+    let buckets = forecast_buckets(&portfolio.positions, &expected_returns, default_return);
+    let output_format = get_arg_value(Some(sub_matches), "output");
+    let output = if sub_matches.get_flag("monte-carlo") {
+        let mut volatilities = ctx.cfg.volatilities.clone();
+        if let Some(configured) = sub_matches.get_many::<(String, f64)>("volatility") {
+            volatilities.extend(configured.cloned());
+        }
+        let settings = MonteCarloSettings {
+            paths: *sub_matches.get_one::<usize>("paths").unwrap(),
+            seed: sub_matches
+                .get_one::<u64>("seed")
+                .copied()
+                .unwrap_or_else(random_seed),
+            floor: sub_matches.get_one::<Decimal>("floor").copied(),
+        };
+        let result = simulate(
+            &buckets,
+            &bucket_volatilities(&buckets, &volatilities),
+            monthly,
+            years,
+            settings,
+        );
+        match output_format.as_deref() {
+            Some("csv") => monte_carlo_to_csv(&result, number_format)?,
+            Some("json") => format!("{}\n", monte_carlo_to_json(&result)?),
+            _ => {
+                print_monte_carlo(&result, number_format);
+                print_hidden_note(hidden);
+                return Ok(());
+            }
+        }
+    } else {
+        let forecast = forecast(buckets, monthly, years);
+        match output_format.as_deref() {
+            Some("csv") => forecast_to_csv(&forecast, number_format)?,
+            Some("json") => format!("{}\n", forecast_to_json(&forecast)?),
+            _ => {
+                print_forecast(&forecast, number_format);
+                print_hidden_note(hidden);
+                return Ok(());
+            }
+        }
+    };
+    write_output(&output, None)
+}
+
+async fn tags_command(ctx: &Context, sub_matches: &clap::ArgMatches) -> Result<(), String> {
+    let exclusive = sub_matches.get_flag("exclusive");
+    let (portfolio, hidden) = ctx.filtered_portfolio(sub_matches).await?;
+    let balances = if exclusive {
+        balance_by_tag_exclusive(&portfolio.positions)
+    } else {
+        Ok(balance_by_tag(&portfolio.positions))
+    };
+    match balances {
+        Ok(balances) => print_balance_by_tag(&balances, exclusive, &ctx.number_format),
+        Err(e) => eprintln!("{e}"),
+    }
+    print_hidden_note(hidden);
+    Ok(())
+}
+
+async fn accounts_command(ctx: &Context, sub_matches: &clap::ArgMatches) -> Result<(), String> {
+    let (portfolio, hidden) = ctx.filtered_portfolio(sub_matches).await?;
+    print!(
+        "{}",
+        render_account_balances(&portfolio.positions, &ctx.number_format, use_color())
+    );
+    print_hidden_note(hidden);
+    Ok(())
+}
+
+fn interest_command(ctx: &Context, sub_matches: &clap::ArgMatches) -> Result<(), String> {
+    let filenames = ctx.filenames(Some(sub_matches));
+    let number_format = &ctx.number_format;
+    // interest is credited in the minor unit of the configured currency
+    let decimals = currency_decimals(&ctx.cfg.currency);
+    let positions_str = ctx.load_portfolio(filenames.clone())?;
+    // interest only depends on the cash amounts, no quotes needed
+    let mut portfolio = Portfolio::new();
+    for position in from_string(&positions_str) {
+        portfolio.add_position(position);
+    }
+    portfolio.total_investment = total_investment(&positions_str);
+    portfolio.name = portfolio_name(&positions_str);
+    portfolio.defaults = position_defaults(&positions_str);
+
+    let clock = clock::as_of(sub_matches.get_one::<chrono::NaiveDate>("as-of").copied());
+    portfolio.print_interest_date_warnings(clock.as_ref());
+    if sub_matches.get_flag("apply") {
+        let credited = portfolio.apply_interest_if_due(clock.as_ref(), decimals);
+        let paid = portfolio.apply_loan_payments_if_due(clock.as_ref(), decimals);
+        if credited.is_empty() && paid.is_empty() {
+            println!("No interest payments are due.");
+            return Ok(());
+        }
+        for (name, interest) in &credited {
+            println!(
+                "Credited {} interest to {name}",
+                number_format.money(*interest)
+            );
+        }
+        for (name, payment) in &paid {
+            println!(
+                "Paid {} to {name} ({} interest, {} principal)",
+                number_format.money(payment.payment),
+                number_format.money(payment.interest),
+                number_format.money(payment.principal)
+            );
+        }
+        save_data_files(&filenames, &portfolio)?;
+    } else if let Some(until) = sub_matches.get_one::<chrono::NaiveDate>("until") {
+        print_interest_simulation(
+            &portfolio.positions,
+            until.and_hms_opt(0, 0, 0).unwrap().and_utc(),
+            decimals,
+            number_format,
+        );
+    } else if let Some(days) = sub_matches.get_one::<u32>("within") {
+        print_upcoming_payments(
+            &upcoming_payments(
+                &portfolio.positions,
+                chrono::Duration::days(*days as i64),
+                clock.as_ref(),
+            ),
+            clock.as_ref(),
+            number_format,
+        );
+    } else {
+        portfolio.print_interest_preview(
+            decimals,
+            ctx.cfg
+                .inflation(sub_matches.get_one::<f64>("inflation").copied()),
+            number_format,
+        );
+    }
+    Ok(())
+}
+
+async fn income_command(ctx: &Context, sub_matches: &clap::ArgMatches) -> Result<(), String> {
+    // the projected dividends depend on the current balances
+    let (portfolio, hidden) = ctx.filtered_portfolio(sub_matches).await?;
+    print_income_report(&income_report(&portfolio.positions), &ctx.number_format);
+    print_hidden_note(hidden);
+    Ok(())
+}
+
+fn loans_command(ctx: &Context, sub_matches: &clap::ArgMatches) -> Result<(), String> {
+    let positions_str = ctx.load_portfolio(ctx.filenames(Some(sub_matches)))?;
+    print_loan_schedules(
+        &from_string(&positions_str),
+        currency_decimals(&ctx.cfg.currency),
+        &ctx.number_format,
+    );
+    Ok(())
+}
+
+fn missing_command(ctx: &Context, sub_matches: &clap::ArgMatches) -> Result<(), String> {
+    let positions_str = ctx.load_portfolio(ctx.filenames(Some(sub_matches)))?;
+    let report = missing_data_report(&from_string(&positions_str));
+    if report.is_empty() {
+        println!("No position is missing data");
+    }
+    for (name, fields) in report {
+        println!("{name}: missing {}", fields.join(", "));
+    }
+    Ok(())
+}
+
+async fn alerts_command(ctx: &Context, sub_matches: &clap::ArgMatches) -> Result<(), String> {
+    ctx.require_fetch()?;
+    if ctx.cfg.alerts.is_empty() {
+        return Err("No alerts configured, add them under alerts in the config file".to_string());
+    }
+    let state_path = get_arg_value(Some(sub_matches), "state").unwrap_or_else(|| {
+        ctx.config_path
+            .with_file_name("alert_state.json")
+            .to_string_lossy()
+            .into_owned()
+    });
+    run_alerts(
+        &ctx.fetch.connector()?,
+        &ctx.cfg.alerts,
+        ctx.cfg.alert_webhook.as_deref(),
+        &state_path,
+        &ctx.number_format,
+    )
+    .await
+    .map(|_breaches| ())
+}
+
+async fn history_command(ctx: &Context, sub_matches: &clap::ArgMatches) -> Result<(), String> {
+    ctx.require_fetch()?;
+    let ticker = get_arg_value(Some(sub_matches), "TICKER").unwrap_or_default();
+    let interval = get_arg_value(Some(sub_matches), "interval").unwrap_or_default();
+    let range = get_arg_value(Some(sub_matches), "range").unwrap_or_default();
+    let series = price_series(&ctx.fetch.connector()?, &ticker, &interval, &range).await?;
+    match get_arg_value(Some(sub_matches), "output").as_deref() {
+        Some("csv") => print!("{}", series_to_csv(&series)?),
+        Some("json") => println!("{}", series_to_json(&series)?),
+        _ => {
+            println!("{0: >20} | {1: >10}", "Date", "Close");
+            println!("====================================");
+            for point in series {
+                println!(
+                    "{0: >20} | {1: >10}",
+                    point.date.format("%Y-%m-%d %H:%M"),
+                    ctx.number_format.number(to_decimal(point.close))
+                );
             }
         }
-        Some(("allocation", sub_matches)) => {
-            let filename = get_filename(Some(sub_matches));
-            match load_portfolio(filename) {
-                Ok(positions_str) => {
-                    let (portfolio, _network_status) =
-                        create_live_portfolio_with_logging(positions_str, true).await;
-                    portfolio.draw_pie_chart();
-                    portfolio.print_allocation();
+    }
+    Ok(())
+}
+
+async fn search_command(ctx: &Context, sub_matches: &clap::ArgMatches) -> Result<(), String> {
+    ctx.require_fetch()?;
+    let query = get_arg_value(Some(sub_matches), "QUERY").unwrap_or_default();
+    let quote_type = get_arg_value(Some(sub_matches), "type");
+    let limit = *sub_matches.get_one::<usize>("limit").unwrap();
+    let results = search(
+        &ctx.fetch.connector()?,
+        &query,
+        quote_type.as_deref(),
+        limit,
+    )
+    .await?;
+    print!("{}", render_search_results(&query, &results));
+    Ok(())
+}
+
+async fn shares_command(ctx: &Context, sub_matches: &clap::ArgMatches) -> Result<(), String> {
+    ctx.require_fetch()?;
+    let ticker = get_arg_value(Some(sub_matches), "TICKER").unwrap_or_default();
+    let cash = *sub_matches.get_one::<Decimal>("CASH").unwrap();
+    let purchase = shares_for_amount(
+        &ctx.fetch.connector()?,
+        &ticker,
+        cash,
+        sub_matches.get_flag("fractional"),
+        ctx.number_format.amount_precision.shares,
+    )
+    .await?;
+    print_share_purchase(&ticker, &purchase, &ctx.number_format);
+    Ok(())
+}
+
+// Exits with 1 if a file has errors, for pre-commit hooks and scripts
+async fn validate_command(ctx: &Context, sub_matches: &clap::ArgMatches) -> Result<(), String> {
+    ctx.require_fetch()?;
+    match validate_files(ctx, &ctx.filenames(Some(sub_matches))).await {
+        Ok(false) => Ok(()),
+        Ok(true) => std::process::exit(1),
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+// Prints the findings of every file, true if one of them has errors
+async fn validate_files(ctx: &Context, filenames: &[String]) -> Result<bool, String> {
+    if filenames.is_empty() {
+        return Err("No portfolio file specified. Use --help for usage information.".to_string());
+    }
+    let connector = ctx.fetch.connector()?;
+    let paths = expand_paths(filenames)?;
+    let mut failed = false;
+    for (index, path) in paths.iter().enumerate() {
+        if paths.len() > 1 {
+            if index > 0 {
+                println!();
+            }
+            println!("{}", path.bold());
+        }
+        let findings = match ctx.read_file(path) {
+            Ok(data) => {
+                validate_data(&connector, &data, ctx.max_quote_age, chrono::Utc::now()).await
+            }
+            Err(e) => vec![Finding {
+                subject: path.clone(),
+                severity: Severity::Error,
+                message: e,
+            }],
+        };
+        print_findings(&findings);
+        failed |= has_errors(&findings);
+    }
+    Ok(failed)
+}
+
+async fn sma_command(ctx: &Context, sub_matches: &clap::ArgMatches) -> Result<(), String> {
+    ctx.require_fetch()?;
+    let ticker = get_arg_value(Some(sub_matches), "TICKER").unwrap_or_default();
+    let window = *sub_matches.get_one::<usize>("window").unwrap();
+    let average = moving_average(&ctx.fetch.connector()?, &ticker, window).await?;
+    println!(
+        "{window}-day SMA of {ticker}: {}",
+        ctx.number_format.number(to_decimal(average))
+    );
+    Ok(())
+}
+
+async fn signal_command(ctx: &Context, sub_matches: &clap::ArgMatches) -> Result<(), String> {
+    ctx.require_fetch()?;
+    let ticker = get_arg_value(Some(sub_matches), "TICKER").unwrap_or_default();
+    let short = *sub_matches.get_one::<usize>("short").unwrap();
+    let long = *sub_matches.get_one::<usize>("long").unwrap();
+    let signal = sma_crossover_signal(&ctx.fetch.connector()?, &ticker, short, long).await?;
+    println!("{short}/{long}-day SMA crossover for {ticker}: {signal}");
+    Ok(())
+}
+
+async fn drawdown_command(ctx: &Context, sub_matches: &clap::ArgMatches) -> Result<(), String> {
+    ctx.require_fetch()?;
+    let days = *sub_matches.get_one::<usize>("days").unwrap();
+    let connector = ctx.fetch.connector()?;
+    let (label, drawdown) = match get_arg_value(Some(sub_matches), "TICKER") {
+        Some(ticker) => {
+            let drawdown = max_drawdown(&connector, &ticker, days).await?;
+            (ticker, drawdown)
+        }
+        None => {
+            let positions_str = ctx.load_portfolio(ctx.filenames(None))?;
+            let (mut portfolio, _network_status) =
+                create_live_portfolio_with_logging(positions_str, true, &ctx.fetch).await;
+            let hidden = portfolio.apply_filter(&position_filter(sub_matches));
+            print_hidden_note(hidden);
+            let drawdown = portfolio_max_drawdown(&connector, &portfolio.positions, days).await?;
+            ("the portfolio".to_string(), drawdown)
+        }
+    };
+    println!(
+        "{days}-day maximum drawdown of {label}: {}",
+        ctx.number_format.signed_percent(to_decimal(-drawdown))
+    );
+    Ok(())
+}
+
+fn chart_command(ctx: &Context, sub_matches: &clap::ArgMatches) -> Result<(), String> {
+    let selector = match (
+        get_arg_value(Some(sub_matches), "class"),
+        get_arg_value(Some(sub_matches), "position"),
+    ) {
+        (Some(class), _) => HistorySelector::AssetClass(class),
+        (_, Some(name)) => HistorySelector::Position(name),
+        _ => HistorySelector::Total,
+    };
+    let history = sled::open("database")
+        .map_err(|e| format!("Database error: {e}"))
+        .and_then(|db| load_history(&db, &selector))?;
+    let output = if get_arg_value(Some(sub_matches), "output").as_deref() == Some("csv") {
+        history_to_csv(
+            &history,
+            &NumberFormat::machine(&ctx.cfg.currency, ctx.number_format.precision),
+        )?
+    } else {
+        let width = sub_matches
+            .get_one::<usize>("width")
+            .copied()
+            .or_else(|| crossterm::terminal::size().ok().map(|(w, _)| w as usize))
+            .unwrap_or(80);
+        let height = *sub_matches.get_one::<u16>("height").unwrap() as usize;
+        render_history_chart(
+            &selector.label(),
+            &history,
+            width,
+            height,
+            &ctx.number_format,
+        )
+    };
+    let file = get_arg_value(Some(sub_matches), "file");
+    write_output(&output, file.as_deref())
+}
+
+async fn report_command(ctx: &Context, sub_matches: &clap::ArgMatches) -> Result<(), String> {
+    let format = get_arg_value(Some(sub_matches), "format")
+        .and_then(|s| s.parse::<DocumentFormat>().ok())
+        .unwrap_or(DocumentFormat::Markdown);
+    let (portfolio, hidden) = ctx.filtered_portfolio(sub_matches).await?;
+    let document_format = if sub_matches.get_flag("machine") {
+        NumberFormat::machine(&ctx.cfg.currency, ctx.number_format.precision)
+    } else {
+        ctx.number_format.clone()
+    };
+    let document = render_document(
+        &portfolio.positions,
+        chrono::Utc::now(),
+        format,
+        &document_format,
+    );
+    let file = get_arg_value(Some(sub_matches), "file");
+    if let Err(e) = write_output(&document, file.as_deref()) {
+        eprintln!("{e}");
+    }
+    if hidden > 0 {
+        eprintln!("Note: {hidden} position(s) hidden by filters.");
+    }
+    Ok(())
+}
+
+async fn performance_command(ctx: &Context, sub_matches: &clap::ArgMatches) -> Result<(), String> {
+    ctx.require_fetch()?;
+    let number_format = &ctx.number_format;
+    let (portfolio, hidden) = ctx.filtered_portfolio(sub_matches).await?;
+    let benchmark = get_arg_value(Some(sub_matches), "benchmark");
+    let window = sub_matches
+        .get_one::<u32>("window")
+        .map(|days| chrono::Duration::days(*days as i64));
+    let inflation = ctx
+        .cfg
+        .inflation(sub_matches.get_one::<f64>("inflation").copied());
+    let cagr = sub_matches.get_flag("cagr");
+    if sub_matches.get_flag("contributions") || benchmark.is_some() || cagr {
+        let now = chrono::Utc::now();
+        let day = |date: &chrono::NaiveDate| date.and_hms_opt(0, 0, 0).unwrap().and_utc();
+        let from = sub_matches
+            .get_one::<chrono::NaiveDate>("since")
+            .map(day)
+            .unwrap_or_else(|| {
+                day(&chrono::NaiveDate::from_yo_opt(now.date_naive().year(), 1).unwrap())
+            });
+        let to = sub_matches
+            .get_one::<chrono::NaiveDate>("until")
+            .map(day)
+            .unwrap_or(now);
+        let connector = ctx.fetch.connector()?;
+        if let Some(benchmark) = benchmark {
+            match compare_to_benchmark(
+                &connector,
+                &portfolio.positions,
+                &benchmark,
+                from,
+                to,
+                window,
+            )
+            .await
+            {
+                Ok(comparison) => print_benchmark_comparison(&comparison, inflation, number_format),
+                Err(e) => eprintln!("{e}"),
+            }
+        } else if cagr {
+            match portfolio_cagr(&connector, &portfolio.positions, from, to, window).await {
+                Ok(rate) => println!(
+                    "Growth per year (CAGR) from {} to {}: {}",
+                    from.format("%Y-%m-%d"),
+                    to.format("%Y-%m-%d"),
+                    number_format.signed_percent(to_decimal(rate))
+                ),
+                Err(e) => eprintln!("{e}"),
+            }
+        } else {
+            match return_contributions(&connector, &portfolio.positions, from, to, window).await {
+                Ok(contributions) => {
+                    print_return_contributions(&contributions, from, to, inflation, number_format)
                 }
                 Err(e) => eprintln!("{e}"),
             }
         }
-        Some(("performance", sub_matches)) => {
-            let filename = get_filename(Some(sub_matches));
-            match load_portfolio(filename) {
-                Ok(positions_str) => {
-                    let (portfolio, _network_status) =
-                        create_live_portfolio_with_logging(positions_str, true).await;
-                    portfolio.print_performance().await;
-                }
+    } else {
+        portfolio
+            .print_performance(&ctx.fetch.connector()?, window, inflation, number_format)
+            .await;
+    }
+    print_hidden_note(hidden);
+    Ok(())
+}
+
+fn import_command(ctx: &Context, sub_matches: &clap::ArgMatches) -> Result<(), String> {
+    if let Some(("csv", csv_matches)) = sub_matches.subcommand() {
+        let path = get_arg_value(Some(csv_matches), "CSV").unwrap_or_default();
+        let renames: Vec<(String, String)> = csv_matches
+            .get_many::<(String, String)>("map")
+            .map(|renames| renames.cloned().collect())
+            .unwrap_or_default();
+        let out = get_arg_value(Some(csv_matches), "out");
+        if let Err(e) = import_csv_file(
+            &path,
+            &ctx.cfg.csv_columns,
+            &renames,
+            out.as_deref(),
+            ctx.strict,
+        ) {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    }
+    Ok(())
+}
+
+async fn serve_command(ctx: &Context, sub_matches: &clap::ArgMatches) -> Result<(), String> {
+    ctx.require_fetch()?;
+    let positions_str = ctx.load_portfolio(ctx.filenames(Some(sub_matches)))?;
+    let connector = ctx.fetch.connector()?;
+    let ttl = ctx
+        .cfg
+        .cache_ttl(sub_matches.get_one::<u64>("ttl").copied());
+    let state = server::ServerState {
+        provider: CachedProvider::new(connector, chrono::Duration::seconds(ttl as i64))
+            .with_name_lookup(ctx.fetch.names),
+        positions_str,
+        currency: ctx.cfg.currency.clone(),
+        token: std::env::var(server::TOKEN_VARIABLE)
+            .ok()
+            .filter(|token| !token.is_empty()),
+    };
+    let address = *sub_matches
+        .get_one::<std::net::SocketAddr>("address")
+        .unwrap();
+    let listener = tokio::net::TcpListener::bind(address)
+        .await
+        .map_err(|e| format!("Failed to listen on {address}: {e}"))?;
+    eprintln!("Serving the portfolio on http://{address} (Ctrl-C to quit)");
+    server::serve(listener, std::sync::Arc::new(state)).await
+}
+
+// Without a subcommand the portfolio is shown in the TUI
+async fn tui_command(ctx: &Context, matches: &clap::ArgMatches) {
+    let filenames = ctx.filenames(Some(matches));
+    let tab_value = parse_tab(get_arg_value(Some(matches), "tab"));
+    match ctx.load_portfolio(filenames.clone()) {
+        Ok(positions_str) => {
+            let (portfolio, _network_status) =
+                create_live_portfolio(positions_str.clone(), &ctx.fetch).await;
+            if let Err(e) = tui::run_tui(
+                portfolio,
+                ctx.cfg.currency.clone(),
+                ctx.number_format.clone(),
+                positions_str,
+                filenames,
+                tab_value,
+                ctx.fetch.clone(),
+            )
+            .await
+            {
+                eprintln!("Error running TUI: {e}");
+            }
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            cli().print_help().unwrap();
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let matches = cli().get_matches();
+    let config_path = config::config_path(&config::environment);
+
+    // Handle config subcommand, init also works if the config file is broken
+    if let Some(config_matches) = matches.subcommand_matches("config") {
+        if let Some(init_matches) = config_matches.subcommand_matches("init") {
+            match config::init(&config_path, init_matches.get_flag("force")) {
+                Ok(()) => println!("Wrote the config file {}", config_path.display()),
                 Err(e) => eprintln!("{e}"),
             }
+            return;
         }
-        _ => {
-            // Default to TUI when no subcommand is given
-            let filename = get_filename(Some(&matches));
-            let tab_value = parse_tab(get_arg_value(Some(&matches), "tab"));
-            match load_portfolio(filename.clone()) {
-                Ok(positions_str) => {
-                    let (portfolio, _network_status) =
-                        create_live_portfolio(positions_str.clone()).await;
-                    if let Err(e) = tui::run_tui(
-                        portfolio,
-                        cfg.currency.clone(),
-                        positions_str,
-                        filename,
-                        tab_value,
-                    )
-                    .await
-                    {
-                        eprintln!("Error running TUI: {e}");
-                    }
-                }
-                Err(e) => {
-                    eprintln!("{e}");
-                    cli().print_help().unwrap();
-                }
+        println!(
+            "Your config file is located here: \n{}",
+            config_path.display()
+        );
+        return;
+    }
+
+    let cfg = match config::load(&config_path)
+        .and_then(|cfg| cfg.with_environment(&config::environment))
+    {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            eprintln!("{e}");
+            return;
+        }
+    };
+    // the requests of all connectors share the rate limit
+    rate_limit::configure(cfg.network().requests_per_second);
+    // the TUI owns the terminal, records would be drawn over it
+    if matches.subcommand().is_some() {
+        logger::init(matches.get_count("verbose"));
+    }
+    let ctx = Context {
+        number_format: cfg.number_format(get_arg_value(Some(&matches), "number-format").as_deref()),
+        max_quote_age: chrono::Duration::days(cfg.max_quote_age_days),
+        fetch: FetchSettings {
+            enabled: !matches.get_flag("no-fetch"),
+            network: cfg.network(),
+            names: cfg.fetch_names,
+        },
+        strict: matches.get_flag("strict"),
+        data_format: get_arg_value(Some(&matches), "format")
+            .and_then(|s| s.parse::<DataFormat>().ok()),
+        file_args: matches
+            .get_many::<String>("file")
+            .map(|files| files.cloned().collect())
+            .unwrap_or_default(),
+        cfg,
+        config_path,
+    };
+
+    // for the commands that read the data files, not those that edit them
+    if ctx.cfg.save_resolved_names && ctx.fetch.names && ctx.fetch.enabled {
+        let data_files = match matches.subcommand() {
+            Some(("add" | "edit" | "remove", _)) => None,
+            Some((_, sub_matches)) => sub_matches
+                .try_get_many::<String>("FILE")
+                .is_ok()
+                .then(|| ctx.filenames(Some(sub_matches))),
+            None => Some(ctx.filenames(None)),
+        };
+        if let Some(files) = data_files {
+            if let Err(e) = save_resolved_names(&files, &ctx.fetch).await {
+                eprintln!("{e}");
             }
         }
     }
+
+    // Handle subcommands or default to TUI
+    let result = match matches.subcommand() {
+        Some((command @ ("add" | "edit" | "remove"), sub_matches)) => {
+            let filenames = ctx.filenames(Some(sub_matches));
+            edit_data_file(command, sub_matches, &filenames, &ctx.fetch).await
+        }
+        Some(("show", sub_matches)) => show_command(&ctx, sub_matches).await,
+        Some(("balances", sub_matches)) => balances_command(&ctx, sub_matches).await,
+        Some(("movers", sub_matches)) => movers_command(&ctx, sub_matches).await,
+        Some(("allocation", sub_matches)) => allocation_command(&ctx, sub_matches).await,
+        Some(("rebalance", sub_matches)) => rebalance_command(&ctx, sub_matches).await,
+        Some(("diff", sub_matches)) => diff_command(&ctx, sub_matches).await,
+        Some(("forecast", sub_matches)) => forecast_command(&ctx, sub_matches).await,
+        Some(("tags", sub_matches)) => tags_command(&ctx, sub_matches).await,
+        Some(("accounts", sub_matches)) => accounts_command(&ctx, sub_matches).await,
+        Some(("interest", sub_matches)) => interest_command(&ctx, sub_matches),
+        Some(("income", sub_matches)) => income_command(&ctx, sub_matches).await,
+        Some(("loans", sub_matches)) => loans_command(&ctx, sub_matches),
+        Some(("missing", sub_matches)) => missing_command(&ctx, sub_matches),
+        Some(("alerts", sub_matches)) => alerts_command(&ctx, sub_matches).await,
+        Some(("history", sub_matches)) => history_command(&ctx, sub_matches).await,
+        Some(("search", sub_matches)) => search_command(&ctx, sub_matches).await,
+        Some(("shares", sub_matches)) => shares_command(&ctx, sub_matches).await,
+        Some(("validate", sub_matches)) => validate_command(&ctx, sub_matches).await,
+        Some(("sma", sub_matches)) => sma_command(&ctx, sub_matches).await,
+        Some(("signal", sub_matches)) => signal_command(&ctx, sub_matches).await,
+        Some(("drawdown", sub_matches)) => drawdown_command(&ctx, sub_matches).await,
+        Some(("chart", sub_matches)) => chart_command(&ctx, sub_matches),
+        Some(("report", sub_matches)) => report_command(&ctx, sub_matches).await,
+        Some(("performance", sub_matches)) => performance_command(&ctx, sub_matches).await,
+        Some(("import", sub_matches)) => import_command(&ctx, sub_matches),
+        Some(("serve", sub_matches)) => serve_command(&ctx, sub_matches).await,
+        _ => {
+            tui_command(&ctx, &matches).await;
+            Ok(())
+        }
+    };
+    if let Err(e) = result {
+        eprintln!("{e}");
+    }
+
+    let (pricing_errors, priced_tickers) = take_pricing_errors();
+    if !pricing_errors.is_empty() {
+        eprint!("{}", render_errors(&pricing_errors, priced_tickers).red());
+        if ctx.strict {
+            std::process::exit(1);
+        }
+    }
 }
 
 #[cfg(test)]
@@ -296,6 +1993,367 @@ mod tests {
         assert_eq!(matches.subcommand_name(), Some("balances"));
     }
 
+    #[tokio::test]
+    async fn test_cli_add_edit_remove() {
+        let path = std::env::temp_dir().join(format!("cli_edit_{}.json", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        let run = |args: &[&str]| {
+            let matches = cli().get_matches_from(["portfolio_rs"].iter().chain(args));
+            let (command, sub_matches) = matches.subcommand().unwrap();
+            let (command, sub_matches) = (command.to_string(), sub_matches.clone());
+            let filenames = vec![path.clone()];
+            async move {
+                let offline = FetchSettings {
+                    enabled: false,
+                    ..FetchSettings::default()
+                };
+                edit_data_file(&command, &sub_matches, &filenames, &offline).await
+            }
+        };
+
+        run(&[
+            "add", "--ticker", "AAPL", "--amount", "5", "--class", "Stock",
+        ])
+        .await
+        .unwrap();
+        run(&[
+            "add", "--name", "Savings", "--class", "Cash", "--amount", "100",
+        ])
+        .await
+        .unwrap();
+        run(&[
+            "edit",
+            "aapl",
+            "--amount",
+            "7.5",
+            "--notes",
+            "from the ESPP",
+        ])
+        .await
+        .unwrap();
+        run(&["remove", "Savings", "--yes"]).await.unwrap();
+        assert_eq!(
+            run(&["add", "--ticker", "MSFT"]).await.unwrap_err(),
+            "--class is needed to add a position with flags"
+        );
+
+        let positions = from_string(&read_to_string(&path).unwrap());
+        assert_eq!(positions.len(), 1);
+        assert_eq!(positions[0].get_amount(), Decimal::new(75, 1));
+        assert_eq!(positions[0].get_notes(), Some("from the ESPP"));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_cli_balances_group_by() {
+        let matches = cli().get_matches_from(vec![
+            "portfolio_rs",
+            "balances",
+            "--group-by",
+            "account",
+            "example_data.json",
+        ]);
+        let (_, sub_matches) = matches.subcommand().unwrap();
+        assert_eq!(
+            get_arg_value(Some(sub_matches), "group-by"),
+            Some("account".to_string())
+        );
+        assert!(cli()
+            .try_get_matches_from(vec!["portfolio_rs", "balances", "--group-by", "broker"])
+            .is_err());
+    }
+
+    #[test]
+    fn test_cli_position_filter() {
+        let matches = cli().get_matches_from(vec![
+            "portfolio_rs",
+            "allocation",
+            "--tag",
+            "retirement",
+            "--tag",
+            "global",
+            "--class",
+            "Stock",
+            "--min",
+            "500",
+        ]);
+        let (_, sub_matches) = matches.subcommand().unwrap();
+        let filter = position_filter(sub_matches);
+        assert_eq!(filter.tags, ["retirement", "global"]);
+        assert_eq!(filter.asset_class, Some("Stock".to_string()));
+        assert_eq!(filter.min_balance, Some(Decimal::from(500)));
+        assert_eq!(filter.search, None);
+
+        let matches = cli().get_matches_from(vec!["portfolio_rs", "allocation"]);
+        let (_, sub_matches) = matches.subcommand().unwrap();
+        assert!(position_filter(sub_matches).is_empty());
+    }
+
+    #[test]
+    fn test_cli_file_format_and_no_fetch() {
+        let matches = cli().get_matches_from(vec![
+            "portfolio_rs",
+            "--file",
+            "portfolio.yml",
+            "--format",
+            "yaml",
+            "balances",
+            "--no-fetch",
+        ]);
+        assert_eq!(
+            get_arg_value(Some(&matches), "file"),
+            Some("portfolio.yml".to_string())
+        );
+        assert_eq!(
+            get_arg_value(Some(&matches), "format").and_then(|s| s.parse::<DataFormat>().ok()),
+            Some(DataFormat::Yaml)
+        );
+        // global, so it can also be given after the subcommand
+        assert!(matches.get_flag("no-fetch"));
+        let (_, sub_matches) = matches.subcommand().unwrap();
+        assert_eq!(get_arg_value(Some(sub_matches), "file"), None);
+
+        assert!(!cli()
+            .get_matches_from(vec!["portfolio_rs", "balances"])
+            .get_flag("no-fetch"));
+        assert!(cli()
+            .try_get_matches_from(vec!["portfolio_rs", "--format", "xml"])
+            .is_err());
+        assert!(cli()
+            .try_get_matches_from(vec!["portfolio_rs", "--file", "a.json", "b.json"])
+            .is_err());
+    }
+
+    #[test]
+    fn test_cli_several_files() {
+        let matches = cli().get_matches_from(vec![
+            "portfolio_rs",
+            "--file",
+            "alex.json",
+            "--file",
+            "sam.json",
+            "balances",
+            "--side-by-side",
+        ]);
+        let files: Vec<&String> = matches.get_many::<String>("file").unwrap().collect();
+        assert_eq!(files, ["alex.json", "sam.json"]);
+
+        let matches = cli().get_matches_from(vec!["portfolio_rs", "interest", "a.json", "b.json"]);
+        let (_, sub_matches) = matches.subcommand().unwrap();
+        assert_eq!(sub_matches.get_many::<String>("FILE").unwrap().len(), 2);
+        assert!(cli()
+            .try_get_matches_from(vec![
+                "portfolio_rs",
+                "balances",
+                "--side-by-side",
+                "--consolidate"
+            ])
+            .is_err());
+    }
+
+    #[test]
+    fn test_save_portfolio_file_formats() {
+        let portfolio = Portfolio::new();
+        assert_eq!(
+            portfolio.save("export.csv").unwrap_err(),
+            "Saving to CSV files is not supported"
+        );
+        assert_eq!(
+            portfolio.save("portfolio.toml.gpg").unwrap_err(),
+            "Saving to encrypted files is not supported"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create_live_portfolio_without_fetching() {
+        let positions_str = std::fs::read_to_string("tests/fixtures/portfolio.json").unwrap();
+        let (portfolio, network_status) = create_live_portfolio(
+            positions_str,
+            &FetchSettings {
+                enabled: false,
+                ..FetchSettings::default()
+            },
+        )
+        .await;
+        assert_eq!(network_status, tui::NetworkStatus::Disconnected);
+        assert_eq!(portfolio.positions.len(), 3);
+        assert_eq!(portfolio.get_total_value(), Decimal::from(-800));
+    }
+
+    #[test]
+    fn test_cli_performance_contributions() {
+        let matches = cli().get_matches_from(vec![
+            "portfolio_rs",
+            "performance",
+            "--contributions",
+            "--since",
+            "2024-01-02",
+        ]);
+        let (_, sub_matches) = matches.subcommand().unwrap();
+        assert!(sub_matches.get_flag("contributions"));
+        assert_eq!(
+            sub_matches.get_one::<chrono::NaiveDate>("since"),
+            chrono::NaiveDate::from_ymd_opt(2024, 1, 2).as_ref()
+        );
+        assert!(cli()
+            .try_get_matches_from(vec!["portfolio_rs", "performance", "--since", "2024-01-02"])
+            .is_err());
+        assert!(cli()
+            .try_get_matches_from(vec![
+                "portfolio_rs",
+                "performance",
+                "--contributions",
+                "--until",
+                "02.01.2024"
+            ])
+            .is_err());
+    }
+
+    #[test]
+    fn test_cli_performance_benchmark() {
+        let matches = cli().get_matches_from(vec![
+            "portfolio_rs",
+            "performance",
+            "--benchmark",
+            "SPY",
+            "--since",
+            "2024-01-02",
+        ]);
+        let (_, sub_matches) = matches.subcommand().unwrap();
+        assert_eq!(
+            get_arg_value(Some(sub_matches), "benchmark").as_deref(),
+            Some("SPY")
+        );
+        assert!(cli()
+            .try_get_matches_from(vec![
+                "portfolio_rs",
+                "performance",
+                "--benchmark",
+                "SPY",
+                "--contributions"
+            ])
+            .is_err());
+        assert!(cli()
+            .try_get_matches_from(vec![
+                "portfolio_rs",
+                "performance",
+                "--cagr",
+                "--benchmark",
+                "SPY"
+            ])
+            .is_err());
+        let matches = cli().get_matches_from(vec![
+            "portfolio_rs",
+            "performance",
+            "--cagr",
+            "--since",
+            "2020-01-02",
+        ]);
+        assert!(matches.subcommand().unwrap().1.get_flag("cagr"));
+    }
+
+    #[test]
+    fn test_cli_import_csv() {
+        let matches = cli().get_matches_from(vec![
+            "portfolio_rs",
+            "import",
+            "csv",
+            "positions.csv",
+            "--map",
+            "Qty=Amount",
+            "--map",
+            "Rate=InterestRate",
+            "--out",
+            "portfolio.json",
+        ]);
+        let (_, import) = matches.subcommand().unwrap();
+        let (_, csv) = import.subcommand().unwrap();
+        let renames: Vec<&(String, String)> = csv.get_many("map").unwrap().collect();
+        assert_eq!(
+            renames[1],
+            &("Rate".to_string(), "InterestRate".to_string())
+        );
+        assert!(cli()
+            .try_get_matches_from(vec![
+                "portfolio_rs",
+                "import",
+                "csv",
+                "a.csv",
+                "--map",
+                "Qty"
+            ])
+            .is_err());
+        assert!(cli()
+            .try_get_matches_from(vec!["portfolio_rs", "import"])
+            .is_err());
+    }
+
+    #[test]
+    fn test_import_csv_file() {
+        let out = std::env::temp_dir().join(format!("import_{}.yaml", std::process::id()));
+        let out = out.to_str().unwrap();
+        let renames = [("Qty".to_string(), "Amount".to_string())];
+        let columns = CsvColumns::default();
+        import_csv_file(
+            "tests/fixtures/spreadsheet.csv",
+            &columns,
+            &renames,
+            Some(out),
+            false,
+        )
+        .unwrap();
+        let imported = Portfolio::load(out).unwrap();
+        assert_eq!(imported.positions.len(), 4);
+        // an existing file isn't overwritten
+        assert!(import_csv_file(
+            "tests/fixtures/spreadsheet.csv",
+            &columns,
+            &renames,
+            Some(out),
+            false
+        )
+        .unwrap_err()
+        .ends_with("exists already, it is not overwritten"));
+        std::fs::remove_file(out).unwrap();
+        assert_eq!(
+            import_csv_file("tests/fixtures/spreadsheet.csv", &columns, &renames, None, true)
+                .unwrap_err(),
+            "tests/fixtures/spreadsheet.csv: 4 rows can't be read, nothing is imported with --strict"
+        );
+    }
+
+    #[test]
+    fn test_cli_forecast() {
+        let matches = cli().get_matches_from(vec![
+            "portfolio_rs",
+            "forecast",
+            "--monthly",
+            "500",
+            "--return",
+            "Stocks=7",
+            "--return",
+            "Bonds = 3.5",
+        ]);
+        let (_, sub_matches) = matches.subcommand().unwrap();
+        assert_eq!(
+            sub_matches.get_one::<Decimal>("monthly"),
+            Some(&Decimal::from(500))
+        );
+        assert_eq!(sub_matches.get_one::<u32>("years"), Some(&10));
+        let returns: Vec<&(String, f64)> = sub_matches.get_many("return").unwrap().collect();
+        assert_eq!(
+            returns,
+            [&("Stocks".to_string(), 7.0), &("Bonds".to_string(), 3.5)]
+        );
+
+        for invalid in ["Stocks", "=7", "Stocks=seven"] {
+            assert!(parse_class_percent(invalid).is_err(), "{invalid}");
+        }
+        assert!(cli()
+            .try_get_matches_from(vec!["portfolio_rs", "forecast", "--years", "0"])
+            .is_err());
+    }
+
     #[test]
     fn test_cli_with_tab_flag() {
         let matches = cli().get_matches_from(vec!["portfolio_rs", "--tab", "balances"]);
@@ -345,47 +2403,47 @@ mod tests {
     #[test]
     fn test_parse_tab_overview() {
         let result = parse_tab(Some("overview".to_string()));
-        assert_eq!(result, Some(crate::tui::Tab::Overview));
+        assert_eq!(result, Some(tui::Tab::Overview));
     }
 
     #[test]
     fn test_parse_tab_balance() {
         let result = parse_tab(Some("balances".to_string()));
-        assert_eq!(result, Some(crate::tui::Tab::Balances));
+        assert_eq!(result, Some(tui::Tab::Balances));
     }
 
     #[test]
     fn test_parse_tab_case_insensitive() {
         assert_eq!(
             parse_tab(Some("OVERVIEW".to_string())),
-            Some(crate::tui::Tab::Overview)
+            Some(tui::Tab::Overview)
         );
         assert_eq!(
             parse_tab(Some("Balances".to_string())),
-            Some(crate::tui::Tab::Balances)
+            Some(tui::Tab::Balances)
         );
         assert_eq!(
             parse_tab(Some("bAlAnCeS".to_string())),
-            Some(crate::tui::Tab::Balances)
+            Some(tui::Tab::Balances)
         );
     }
 
     #[test]
     fn test_parse_tab_invalid_defaults_to_overview() {
         let result = parse_tab(Some("invalid".to_string()));
-        assert_eq!(result, Some(crate::tui::Tab::Overview));
+        assert_eq!(result, Some(tui::Tab::Overview));
     }
 
     #[test]
     fn test_parse_tab_none_defaults_to_overview() {
         let result = parse_tab(None);
-        assert_eq!(result, Some(crate::tui::Tab::Overview));
+        assert_eq!(result, Some(tui::Tab::Overview));
     }
 
     #[test]
     fn test_parse_tab_empty_string_defaults_to_overview() {
         let result = parse_tab(Some("".to_string()));
-        assert_eq!(result, Some(crate::tui::Tab::Overview));
+        assert_eq!(result, Some(tui::Tab::Overview));
     }
 
     #[test]
@@ -411,7 +2469,8 @@ mod tests {
     #[tokio::test]
     async fn test_create_live_portfolio() {
         let positions_str = std::fs::read_to_string("example_data.json").unwrap();
-        let (portfolio, _network_status) = create_live_portfolio(positions_str).await;
+        let (portfolio, _network_status) =
+            create_live_portfolio(positions_str, &FetchSettings::default()).await;
         let x: Result<Portfolio, ParseError> = Ok(portfolio);
         assert!(x.is_ok());
     }
@@ -0,0 +1,161 @@
+use crate::position::PortfolioPosition;
+use chrono::prelude::*;
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+
+// Backslashes, quotes and line breaks have to be escaped in a label value
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn labels(pairs: &[(&str, &str)]) -> String {
+    let labels: Vec<String> = pairs
+        .iter()
+        .map(|(name, value)| format!("{name}=\"{}\"", escape_label(value)))
+        .collect();
+    format!("{{{}}}", labels.join(","))
+}
+
+fn gauge(output: &mut String, name: &str, help: &str, samples: &[(String, String)]) {
+    output.push_str(&format!("# HELP {name} {help}\n# TYPE {name} gauge\n"));
+    for (labels, value) in samples {
+        output.push_str(&format!("{name}{labels} {value}\n"));
+    }
+}
+
+fn value(amount: Decimal) -> String {
+    amount.normalize().to_string()
+}
+
+// The priced positions in the Prometheus text format, served by GET /metrics.
+// Labels without a value (a position without a ticker or an account) are empty.
+// The age of a quote is reported once per ticker.
+pub fn render_metrics(positions: &[PortfolioPosition], now: DateTime<Utc>) -> String {
+    let mut output = String::new();
+
+    let balances: Vec<(String, String)> = positions
+        .iter()
+        .map(|position| {
+            (
+                labels(&[
+                    ("name", position.get_name()),
+                    ("ticker", position.get_ticker().unwrap_or_default()),
+                    ("asset_class", position.get_asset_class()),
+                    ("account", position.get_account().unwrap_or_default()),
+                ]),
+                value(position.get_balance()),
+            )
+        })
+        .collect();
+    gauge(
+        &mut output,
+        "portfolio_position_balance",
+        "Balance of the position in the portfolio currency",
+        &balances,
+    );
+
+    let total: Decimal = positions
+        .iter()
+        .map(|position| position.get_balance())
+        .sum();
+    gauge(
+        &mut output,
+        "portfolio_total_balance",
+        "Total balance of the portfolio",
+        &[(String::new(), value(total))],
+    );
+
+    let mut by_asset_class: BTreeMap<&str, Decimal> = BTreeMap::new();
+    for position in positions {
+        *by_asset_class
+            .entry(position.get_asset_class())
+            .or_default() += position.get_balance();
+    }
+    let classes: Vec<(String, String)> = by_asset_class
+        .into_iter()
+        .map(|(class, balance)| (labels(&[("class", class)]), value(balance)))
+        .collect();
+    gauge(
+        &mut output,
+        "portfolio_asset_class_balance",
+        "Balance of the asset class",
+        &classes,
+    );
+
+    let mut quote_times: BTreeMap<&str, DateTime<Utc>> = BTreeMap::new();
+    for position in positions {
+        if let (Some(ticker), Some(time)) = (position.get_ticker(), position.get_last_spot_time()) {
+            quote_times.entry(ticker).or_insert(time);
+        }
+    }
+    let ages: Vec<(String, String)> = quote_times
+        .into_iter()
+        .map(|(ticker, time)| {
+            (
+                labels(&[("ticker", ticker)]),
+                (now - time).num_seconds().to_string(),
+            )
+        })
+        .collect();
+    gauge(
+        &mut output,
+        "portfolio_quote_age_seconds",
+        "Seconds since the quote of the ticker",
+        &ages,
+    );
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::{priced, MockProvider};
+
+    #[tokio::test]
+    async fn test_render_metrics() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 4, 15, 0, 0).unwrap();
+        let provider = MockProvider::new()
+            .with_price("AAPL", 180.0)
+            .with_quote_time("AAPL", now - chrono::Duration::seconds(90));
+        let positions = priced(
+            &provider,
+            r#"[
+                {"Name": "Apple", "Ticker": "AAPL", "AssetClass": "Stocks", "Amount": 10, "Account": "Broker"},
+                {"Name": "Apple \"IRA\"", "Ticker": "AAPL", "AssetClass": "Stocks", "Amount": 1},
+                {"Name": "C:\\Savings", "AssetClass": "Cash", "Amount": 500.50}
+            ]"#,
+        )
+        .await;
+
+        let metrics = render_metrics(&positions, now);
+        assert_eq!(
+            metrics,
+            r#"# HELP portfolio_position_balance Balance of the position in the portfolio currency
+# TYPE portfolio_position_balance gauge
+portfolio_position_balance{name="Apple",ticker="AAPL",asset_class="Stocks",account="Broker"} 1800
+portfolio_position_balance{name="Apple \"IRA\"",ticker="AAPL",asset_class="Stocks",account=""} 180
+portfolio_position_balance{name="C:\\Savings",ticker="",asset_class="Cash",account=""} 500.5
+# HELP portfolio_total_balance Total balance of the portfolio
+# TYPE portfolio_total_balance gauge
+portfolio_total_balance 2480.5
+# HELP portfolio_asset_class_balance Balance of the asset class
+# TYPE portfolio_asset_class_balance gauge
+portfolio_asset_class_balance{class="Cash"} 500.5
+portfolio_asset_class_balance{class="Stocks"} 1980
+# HELP portfolio_quote_age_seconds Seconds since the quote of the ticker
+# TYPE portfolio_quote_age_seconds gauge
+portfolio_quote_age_seconds{ticker="AAPL"} 90
+"#
+        );
+    }
+
+    #[test]
+    fn test_escape_label() {
+        assert_eq!(escape_label("plain"), "plain");
+        assert_eq!(escape_label("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+}
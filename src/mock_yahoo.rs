@@ -0,0 +1,174 @@
+// A local stand-in for the Yahoo Finance API, so the tests of the Yahoo connector
+// run offline. It answers the HTTPS requests for query1 and query2.finance.yahoo.com
+// with the responses in tests/fixtures/yahoo, the connector of a MockYahoo resolves
+// both hosts to it and trusts its test certificate (signed by ca.pem, valid until 2126).
+//
+// The fixtures are responses in the format of the chart and search APIs. To add
+// one, save the response of the URL the connector requests (run a test with -v
+// to log it) and add a route for it. The certificate has to be renewed with a new
+// CA for both hosts if the names or the key types rustls accepts ever change.
+use crate::position::{client_builder, connector_for, NetworkSettings};
+use http_body_util::Full;
+use hyper::body::{Bytes, Incoming};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
+use tokio_rustls::rustls;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::TlsAcceptor;
+use yahoo_finance_api as yahoo;
+
+const FIXTURES: &str = "tests/fixtures/yahoo";
+
+// the chart API answers a symbol it doesn't know with 404 and this body
+const NOT_FOUND: &str = "chart_not_found.json";
+
+// The fixture a request is answered with if its path is `path` and its query
+// contains `query`, e.g. "range=1mo" for the latest quotes
+struct Route {
+    path: String,
+    query: String,
+    fixture: String,
+}
+
+pub struct MockYahoo {
+    address: SocketAddr,
+    // path and query of every request, in the order they came in
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+fn fixture(name: &str) -> Vec<u8> {
+    std::fs::read(format!("{FIXTURES}/{name}")).unwrap()
+}
+
+fn tls_acceptor() -> TlsAcceptor {
+    let certificate = CertificateDer::from_pem_slice(&fixture("server.pem")).unwrap();
+    let key = PrivateKeyDer::from_pem_slice(&fixture("server.key")).unwrap();
+    let config = rustls::ServerConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()
+    .unwrap()
+    .with_no_client_auth()
+    .with_single_cert(vec![certificate], key)
+    .unwrap();
+    TlsAcceptor::from(Arc::new(config))
+}
+
+fn respond(routes: &[Route], request: &Request<Incoming>) -> Response<Full<Bytes>> {
+    let path = request.uri().path();
+    let query = request.uri().query().unwrap_or_default();
+    let (status, name) = routes
+        .iter()
+        .find(|route| route.path == path && query.contains(&route.query))
+        .map_or((StatusCode::NOT_FOUND, NOT_FOUND), |route| {
+            (StatusCode::OK, route.fixture.as_str())
+        });
+    let mut response = Response::new(Full::new(Bytes::from(fixture(name))));
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        hyper::header::CONTENT_TYPE,
+        hyper::header::HeaderValue::from_static("application/json"),
+    );
+    response
+}
+
+impl MockYahoo {
+    // Serves the routes as (path, part of the query, fixture file), the first
+    // matching route answers a request
+    pub async fn start(routes: &[(&str, &str, &str)]) -> MockYahoo {
+        let routes: Arc<Vec<Route>> = Arc::new(
+            routes
+                .iter()
+                .map(|(path, query, fixture)| Route {
+                    path: path.to_string(),
+                    query: query.to_string(),
+                    fixture: fixture.to_string(),
+                })
+                .collect(),
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let acceptor = tls_acceptor();
+
+        let logged = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let (acceptor, routes, logged) = (acceptor.clone(), routes.clone(), logged.clone());
+                tokio::spawn(async move {
+                    let Ok(stream) = acceptor.accept(stream).await else {
+                        return;
+                    };
+                    let service = service_fn(move |request: Request<Incoming>| {
+                        logged.lock().unwrap().push(request.uri().to_string());
+                        let response = respond(&routes, &request);
+                        async move { Ok::<_, Infallible>(response) }
+                    });
+                    let _ = http1::Builder::new()
+                        .serve_connection(TokioIo::new(stream), service)
+                        .await;
+                });
+            }
+        });
+        MockYahoo { address, requests }
+    }
+
+    // The fixtures of AAPL: its latest daily quotes, the closes after new year's
+    // day 2020, the monthly closes since with the 4-for-1 split of 2020-08-31, and
+    // the search for it. Other searches find nothing, other charts are not found.
+    pub async fn aapl() -> MockYahoo {
+        MockYahoo::start(&[
+            (
+                "/v8/finance/chart/AAPL",
+                "range=1mo",
+                "chart_AAPL_latest.json",
+            ),
+            (
+                "/v8/finance/chart/AAPL",
+                "interval=1mo",
+                "chart_AAPL_monthly.json",
+            ),
+            (
+                "/v8/finance/chart/AAPL",
+                "interval=1d",
+                "chart_AAPL_2020-01.json",
+            ),
+            ("/v1/finance/search", "q=AAPL", "search_AAPL.json"),
+            ("/v1/finance/search", "", "search_empty.json"),
+        ])
+        .await
+    }
+
+    // A connector like connector_with's that sends its requests here
+    pub fn connector(&self) -> yahoo::YahooConnector {
+        let ca = reqwest::Certificate::from_pem(&fixture("ca.pem")).unwrap();
+        let builder = client_builder(&NetworkSettings::default())
+            .unwrap()
+            .no_proxy()
+            .add_root_certificate(ca)
+            .resolve("query1.finance.yahoo.com", self.address)
+            .resolve("query2.finance.yahoo.com", self.address);
+        let Ok(connector) = connector_for(builder) else {
+            panic!("the connector of the mock server wasn't created");
+        };
+        connector
+    }
+
+    // The requests that were sent for the path
+    pub fn requests(&self, path: &str) -> Vec<String> {
+        self.requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|request| request.split('?').next() == Some(path))
+            .cloned()
+            .collect()
+    }
+}
@@ -0,0 +1,334 @@
+use crate::forecast::ForecastBucket;
+use crate::format::NumberFormat;
+use crate::position::{to_decimal, to_f64};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::collections::HashMap;
+
+// Annual volatility in % of the asset classes without a configured one
+fn default_volatility(asset_class: &str) -> f64 {
+    match asset_class.to_lowercase().as_str() {
+        "stocks" | "stock" | "equities" | "etf" => 15.0,
+        "bonds" | "bond" => 5.0,
+        "cash" => 0.0,
+        "crypto" => 60.0,
+        "commodities" | "gold" => 15.0,
+        "real estate" | "realestate" => 10.0,
+        _ => 10.0,
+    }
+}
+
+// Annual volatility in % of each bucket. Cash growing at its InterestRate doesn't fluctuate.
+pub fn bucket_volatilities(
+    buckets: &[ForecastBucket],
+    volatilities: &HashMap<String, f64>,
+) -> Vec<f64> {
+    buckets
+        .iter()
+        .map(|bucket| {
+            if bucket.interest_bearing {
+                0.0
+            } else {
+                volatilities
+                    .get(&bucket.name)
+                    .copied()
+                    .unwrap_or_else(|| default_volatility(&bucket.name))
+            }
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MonteCarloSettings {
+    pub paths: usize,
+    pub seed: u64,
+    // the probability of ending below it is reported
+    pub floor: Option<Decimal>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MonteCarloResult {
+    pub paths: usize,
+    pub seed: u64,
+    pub years: u32,
+    pub monthly_contribution: Decimal,
+    pub start_balance: Decimal,
+    // ending values
+    pub p10: Decimal,
+    pub p50: Decimal,
+    pub p90: Decimal,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub floor: Option<Decimal>,
+    // share of the paths ending below the floor, 0 to 1
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub probability_below_floor: Option<f64>,
+}
+
+// Standard normal numbers with the polar method, which makes them in pairs
+struct NormalSampler {
+    spare: Option<f64>,
+}
+
+impl NormalSampler {
+    fn sample(&mut self, rng: &mut StdRng) -> f64 {
+        if let Some(spare) = self.spare.take() {
+            return spare;
+        }
+        loop {
+            let u = rng.random::<f64>() * 2.0 - 1.0;
+            let v = rng.random::<f64>() * 2.0 - 1.0;
+            let s = u * u + v * v;
+            if s > 0.0 && s < 1.0 {
+                let factor = (-2.0 * s.ln() / s).sqrt();
+                self.spare = Some(v * factor);
+                return u * factor;
+            }
+        }
+    }
+}
+
+// Value at the p-th percentile of sorted values (nearest rank)
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let index = (p / 100.0 * (sorted.len() - 1) as f64).round() as usize;
+    sorted[index.min(sorted.len() - 1)]
+}
+
+// Simulates the forecast with random monthly returns: each month a bucket returns a
+// twelfth of its annual return plus a normally distributed deviation with its annual
+// volatility scaled to a month (the buckets move independently). With a volatility of
+// 0 every path is the deterministic forecast. The contribution (negative for a
+// withdrawal) is split like the starting balances, buckets don't go below 0.
+pub fn simulate(
+    buckets: &[ForecastBucket],
+    volatilities: &[f64],
+    monthly_contribution: Decimal,
+    years: u32,
+    settings: MonteCarloSettings,
+) -> MonteCarloResult {
+    let start: Vec<f64> = buckets
+        .iter()
+        .map(|bucket| to_f64(bucket.balance))
+        .collect();
+    let start_balance: f64 = start.iter().sum();
+    let contribution = to_f64(monthly_contribution);
+    // the contribution of each bucket per month
+    let contributions: Vec<f64> = start
+        .iter()
+        .map(|balance| {
+            if start_balance > 0.0 {
+                contribution * balance / start_balance
+            } else {
+                contribution / buckets.len().max(1) as f64
+            }
+        })
+        .collect();
+    let means: Vec<f64> = buckets
+        .iter()
+        .map(|bucket| to_f64(bucket.annual_return) / 100.0 / 12.0)
+        .collect();
+    let deviations: Vec<f64> = volatilities
+        .iter()
+        .map(|volatility| volatility / 100.0 / 12.0_f64.sqrt())
+        .collect();
+
+    let mut rng = StdRng::seed_from_u64(settings.seed);
+    let mut normal = NormalSampler { spare: None };
+    let mut balances = start.clone();
+    let mut endings = Vec::with_capacity(settings.paths);
+    for _ in 0..settings.paths {
+        balances.copy_from_slice(&start);
+        for _ in 0..years * 12 {
+            for (((balance, mean), deviation), contribution) in balances
+                .iter_mut()
+                .zip(&means)
+                .zip(&deviations)
+                .zip(&contributions)
+            {
+                let monthly_return = if *deviation > 0.0 {
+                    (mean + deviation * normal.sample(&mut rng)).max(-1.0)
+                } else {
+                    *mean
+                };
+                *balance = (*balance * (1.0 + monthly_return) + contribution).max(0.0);
+            }
+        }
+        endings.push(balances.iter().sum::<f64>());
+    }
+
+    endings.sort_unstable_by(f64::total_cmp);
+    let probability_below_floor = settings.floor.map(|floor| {
+        let floor = to_f64(floor);
+        let below = endings.partition_point(|ending| *ending < floor);
+        below as f64 / endings.len().max(1) as f64
+    });
+
+    MonteCarloResult {
+        paths: settings.paths,
+        seed: settings.seed,
+        years,
+        monthly_contribution,
+        start_balance: to_decimal(start_balance),
+        p10: to_decimal(percentile(&endings, 10.0)),
+        p50: to_decimal(percentile(&endings, 50.0)),
+        p90: to_decimal(percentile(&endings, 90.0)),
+        floor: settings.floor,
+        probability_below_floor,
+    }
+}
+
+pub fn print_monte_carlo(result: &MonteCarloResult, format: &NumberFormat) {
+    println!(
+        "{} simulated paths over {} years with {} per month (seed {})",
+        result.paths,
+        result.years,
+        format.money(result.monthly_contribution),
+        result.seed
+    );
+    println!(
+        "{0: >26} | {1: >16}",
+        "Start",
+        format.money(result.start_balance)
+    );
+    println!("===============================================");
+    for (label, value) in [
+        ("10th percentile", result.p10),
+        ("Median", result.p50),
+        ("90th percentile", result.p90),
+    ] {
+        println!("{0: >26} | {1: >16}", label, format.money(value));
+    }
+    if let (Some(floor), Some(probability)) = (result.floor, result.probability_below_floor) {
+        println!(
+            "Probability of ending below {}: {}",
+            format.money(floor),
+            format.number_with(to_decimal(probability * 100.0), 1) + "%"
+        );
+    }
+}
+
+pub fn monte_carlo_to_csv(
+    result: &MonteCarloResult,
+    format: &NumberFormat,
+) -> Result<String, String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    let error = |e: csv::Error| format!("Failed to write CSV: {e}");
+
+    writer
+        .write_record(["Percentile", "Balance"])
+        .map_err(error)?;
+    for (percentile, value) in [(10, result.p10), (50, result.p50), (90, result.p90)] {
+        writer
+            .write_record([percentile.to_string(), format.number(value)])
+            .map_err(error)?;
+    }
+
+    let bytes = writer
+        .into_inner()
+        .map_err(|e| format!("Failed to write CSV: {e}"))?;
+    String::from_utf8(bytes).map_err(|e| format!("Failed to write CSV: {e}"))
+}
+
+pub fn monte_carlo_to_json(result: &MonteCarloResult) -> Result<String, String> {
+    serde_json::to_string_pretty(result).map_err(|e| format!("Failed to serialize data: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::forecast::forecast;
+    use rust_decimal::dec;
+
+    fn buckets() -> Vec<ForecastBucket> {
+        vec![
+            ForecastBucket {
+                name: "Stocks".to_string(),
+                balance: dec!(60000),
+                annual_return: dec!(7),
+                interest_bearing: false,
+            },
+            ForecastBucket {
+                name: "Savings".to_string(),
+                balance: dec!(40000),
+                annual_return: dec!(2),
+                interest_bearing: true,
+            },
+        ]
+    }
+
+    fn settings(seed: u64) -> MonteCarloSettings {
+        MonteCarloSettings {
+            paths: 2000,
+            seed,
+            floor: Some(dec!(100000)),
+        }
+    }
+
+    #[test]
+    fn test_bucket_volatilities() {
+        let mut buckets = buckets();
+        buckets.push(ForecastBucket {
+            name: "Crypto".to_string(),
+            balance: dec!(1000),
+            annual_return: dec!(10),
+            interest_bearing: false,
+        });
+        let configured = HashMap::from([("Crypto".to_string(), 80.0)]);
+        assert_eq!(
+            bucket_volatilities(&buckets, &configured),
+            [15.0, 0.0, 80.0]
+        );
+    }
+
+    #[test]
+    fn test_without_volatility_every_path_is_the_forecast() {
+        let result = simulate(&buckets(), &[0.0, 0.0], dec!(500), 10, settings(1));
+        let expected = to_f64(forecast(buckets(), dec!(500), 10).end_balance());
+        for value in [result.p10, result.p50, result.p90] {
+            assert!((to_f64(value) - expected).abs() < 1e-6);
+        }
+        assert_eq!(result.probability_below_floor, Some(0.0));
+    }
+
+    #[test]
+    fn test_simulation_is_reproducible() {
+        let first = simulate(&buckets(), &[15.0, 0.0], dec!(500), 30, settings(42));
+        let second = simulate(&buckets(), &[15.0, 0.0], dec!(500), 30, settings(42));
+        assert_eq!(first, second);
+        let other_seed = simulate(&buckets(), &[15.0, 0.0], dec!(500), 30, settings(7));
+        assert_ne!(first.p50, other_seed.p50);
+
+        // the deterministic result lies between the percentiles. The median is below
+        // it, as losses weigh more than gains of the same size when they compound.
+        let expected = forecast(buckets(), dec!(500), 30).end_balance();
+        assert!(first.p10 < first.p50 && first.p50 < expected && expected < first.p90);
+    }
+
+    #[test]
+    fn test_withdrawals_and_floor() {
+        // withdrawing 1000 a month from 100000 runs out in around 10 years
+        let result = simulate(&buckets(), &[15.0, 0.0], dec!(-1000), 20, settings(3));
+        assert_eq!(result.p10, Decimal::ZERO);
+        assert!(result.probability_below_floor.unwrap() > 0.9);
+
+        let csv = monte_carlo_to_csv(&result, &NumberFormat::machine("EUR", 2)).unwrap();
+        assert_eq!(csv.lines().next(), Some("Percentile,Balance"));
+        assert!(csv.contains("\n10,0.00\n"));
+        let json: serde_json::Value =
+            serde_json::from_str(&monte_carlo_to_json(&result).unwrap()).unwrap();
+        assert_eq!(json["seed"], 3);
+        assert_eq!(json["floor"], 100000.0);
+    }
+
+    #[test]
+    fn test_percentile() {
+        let sorted: Vec<f64> = (0..=100).map(f64::from).collect();
+        assert_eq!(percentile(&sorted, 10.0), 10.0);
+        assert_eq!(percentile(&sorted, 50.0), 50.0);
+        assert_eq!(percentile(&[], 50.0), 0.0);
+    }
+}
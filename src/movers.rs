@@ -0,0 +1,202 @@
+use crate::format::NumberFormat;
+use crate::position::{to_decimal, PortfolioPosition};
+use crate::provider::PriceProvider;
+use chrono::prelude::*;
+use rust_decimal::Decimal;
+
+// The move of a ticker over the trading day of its latest quote
+#[derive(Debug, Clone, PartialEq)]
+pub struct MoverRow {
+    pub name: String,
+    pub ticker: String,
+    // from the open of the day to the latest price, in %
+    pub percent_change: Decimal,
+    // the change of the value of all units held, in the currency of the quote
+    pub change: Decimal,
+    // the trading day of the move, before today while the market is closed
+    pub date: Option<NaiveDate>,
+}
+
+// The moves of the tickers of the positions over the trading day of their latest
+// quote, the largest first whether up or down. While the market is open the move
+// is from today's open to the latest price. While it is closed the latest quote is
+// the close of the last trading day, so the move is from that day's open to its
+// close: the last day that traded is shown instead of no move at all, and the date
+// of the row tells which day it is. Cash, positions with a ManualPrice and those
+// without a Ticker are left out, positions of the same ticker count as one. A
+// ticker without a quote is a warning, without any quote it fails.
+pub async fn daily_movers(
+    provider: &impl PriceProvider,
+    positions: &[PortfolioPosition],
+) -> Result<Vec<MoverRow>, String> {
+    // the name of the first position of each ticker, and the units of all of them
+    let mut holdings: Vec<(String, String, Decimal)> = Vec::new();
+    for position in positions
+        .iter()
+        .filter(|position| !position.is_cash() && position.is_market_instrument())
+    {
+        let Some(ticker) = position.get_ticker() else {
+            continue;
+        };
+        match holdings.iter_mut().find(|(held, _, _)| held == ticker) {
+            Some((_, _, amount)) => *amount += position.get_amount(),
+            None => holdings.push((
+                ticker.to_string(),
+                position.get_name().to_string(),
+                position.get_amount(),
+            )),
+        }
+    }
+
+    let quotes = futures::future::join_all(
+        holdings
+            .iter()
+            .map(|(ticker, _, _)| provider.get_latest_price(ticker)),
+    )
+    .await;
+
+    let mut rows = Vec::new();
+    let mut errors = Vec::new();
+    for ((ticker, name, amount), quote) in holdings.into_iter().zip(quotes) {
+        let quote = match quote {
+            Ok(quote) => quote,
+            Err(e) => {
+                errors.push(format!("no quote of {ticker}: {e}"));
+                continue;
+            }
+        };
+        let Some(open) = quote.open.filter(|open| *open > 0.0) else {
+            errors.push(format!("no open of {ticker}"));
+            continue;
+        };
+        let move_per_unit = to_decimal(quote.price - open);
+        rows.push(MoverRow {
+            name,
+            ticker,
+            percent_change: move_per_unit / to_decimal(open) * Decimal::ONE_HUNDRED,
+            change: move_per_unit * amount,
+            date: quote.time.map(|time| time.date_naive()),
+        });
+    }
+    if rows.is_empty() && !errors.is_empty() {
+        return Err(format!(
+            "Error getting the daily moves: {}",
+            errors.join("; ")
+        ));
+    }
+    for e in errors {
+        log::warn!("{e}, the ticker is not listed");
+    }
+
+    rows.sort_by(|a, b| {
+        b.percent_change
+            .abs()
+            .cmp(&a.percent_change.abs())
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    Ok(rows)
+}
+
+pub fn print_movers(rows: &[MoverRow], format: &NumberFormat) {
+    if rows.is_empty() {
+        println!("No positions with a ticker to show the moves of.");
+        return;
+    }
+    println!(
+        "{0: <24} | {1: <10} | {2: >10} | {3: >14} | {4: >10}",
+        "Name", "Ticker", "Move", "Change", "Day"
+    );
+    println!("==================================================================================");
+    for row in rows {
+        println!(
+            "{0: <24} | {1: <10} | {2: >10} | {3: >14} | {4: >10}",
+            row.name,
+            row.ticker,
+            format.signed_percent(row.percent_change),
+            format.signed_money(row.change),
+            row.date
+                .map(|date| date.format("%Y-%m-%d").to_string())
+                .unwrap_or_default()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::from_string;
+    use crate::provider::MockProvider;
+    use rust_decimal::dec;
+
+    fn positions() -> Vec<PortfolioPosition> {
+        from_string(
+            r#"[
+                {"Name": "Apple", "Ticker": "AAPL", "AssetClass": "Stocks", "Amount": 10},
+                {"Name": "Microsoft", "Ticker": "MSFT", "AssetClass": "Stocks", "Amount": 2},
+                {"Name": "Apple (IRA)", "Ticker": "AAPL", "AssetClass": "Stocks", "Amount": 5},
+                {"Name": "Nvidia", "Ticker": "NVDA", "AssetClass": "Stocks", "Amount": 4},
+                {"Name": "Money market", "Ticker": "VMFXX", "AssetClass": "Cash", "Amount": 100},
+                {"Name": "Gold", "Ticker": "GLD", "AssetClass": "Commodities", "Amount": 1, "ManualPrice": 180},
+                {"Name": "Savings", "AssetClass": "Cash", "Amount": 1000}
+            ]"#,
+        )
+    }
+
+    fn provider() -> MockProvider {
+        let friday = Utc.with_ymd_and_hms(2024, 10, 18, 20, 0, 0).unwrap();
+        MockProvider::new()
+            .with_price("AAPL", 202.0)
+            .with_open("AAPL", 200.0)
+            .with_price("MSFT", 388.0)
+            .with_open("MSFT", 400.0)
+            .with_quote_time("MSFT", friday)
+            .with_price("NVDA", 120.0)
+            .with_open("NVDA", 119.4)
+            .with_price("VMFXX", 1.0)
+            .with_open("VMFXX", 1.0)
+    }
+
+    #[tokio::test]
+    async fn test_daily_movers() {
+        let rows = daily_movers(&provider(), &positions()).await.unwrap();
+        let moves: Vec<(&str, &str, Decimal, Decimal)> = rows
+            .iter()
+            .map(|row| {
+                (
+                    row.name.as_str(),
+                    row.ticker.as_str(),
+                    row.percent_change.round_dp(2),
+                    row.change.round_dp(2),
+                )
+            })
+            .collect();
+        // sorted by the size of the move, the two positions of AAPL as one
+        assert_eq!(
+            moves,
+            [
+                ("Microsoft", "MSFT", dec!(-3), dec!(-24)),
+                ("Apple", "AAPL", dec!(1), dec!(30)),
+                ("Nvidia", "NVDA", dec!(0.50), dec!(2.40)),
+            ]
+        );
+        // a closed market has the move of its last trading day
+        assert_eq!(rows[0].date, NaiveDate::from_ymd_opt(2024, 10, 18));
+    }
+
+    #[tokio::test]
+    async fn test_daily_movers_without_quotes() {
+        // NVDA has no open, the others are listed
+        let provider = provider().with_open("NVDA", 0.0);
+        let rows = daily_movers(&provider, &positions()).await.unwrap();
+        assert_eq!(rows.len(), 2);
+
+        let error = daily_movers(&MockProvider::new(), &positions())
+            .await
+            .unwrap_err();
+        assert!(error.starts_with("Error getting the daily moves: no quote of AAPL"));
+        assert!(daily_movers(&MockProvider::new(), &[])
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
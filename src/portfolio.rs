@@ -1,12 +1,33 @@
-use crate::position::get_historic_price;
-use crate::position::PortfolioPosition;
+use crate::allocation::{allocation_percentages, concentration_index, total_liabilities};
+use crate::clock::Clock;
+use crate::data_file::{check_data_file, save_data, to_json_data, DataFormat};
+use crate::filter::{PositionFilter, PositionSort};
+use crate::format::NumberFormat;
+use crate::grouping::{group_positions, GroupBy};
+use crate::inflation::{real_return, real_return_percent};
+use crate::position::{portfolio_name, position_defaults, to_data_string, total_investment};
+use crate::position::{to_decimal, to_f64, try_from_string, CsvColumns};
+use crate::position::{LoanPayment, PortfolioPosition};
+use crate::position::{OVERRIDE_MARKER, STALE_MARKER};
+use crate::pricing::{price_positions, PositionError};
+use crate::provider::{PriceAdjustment, PriceProvider};
 use chrono::prelude::*;
 use colored::Colorize;
 use piechart::{Chart, Color};
+use rust_decimal::Decimal;
 use std::collections::HashMap;
 
+#[derive(Debug)]
 pub struct Portfolio {
     pub positions: Vec<PortfolioPosition>,
+    // watched tickers, kept out of all totals
+    pub watchlist: Vec<PortfolioPosition>,
+    // deposit the PercentOf of the positions refers to
+    pub total_investment: Option<Decimal>,
+    // the Name of the data file, kept when saving
+    pub name: Option<String>,
+    // the Defaults of the cash positions of the data file, kept when saving
+    pub defaults: Option<serde_json::Map<String, serde_json::Value>>,
 }
 
 impl Default for Portfolio {
@@ -19,15 +40,88 @@ impl Portfolio {
     pub fn new() -> Portfolio {
         Portfolio {
             positions: Vec::new(),
+            watchlist: Vec::new(),
+            total_investment: None,
+            name: None,
+            defaults: None,
         }
     }
 
+    // The portfolio of the JSON data of a data file, its positions unpriced
+    pub fn from_data(data: &str) -> Result<Portfolio, String> {
+        let mut portfolio = Portfolio::new();
+        for position in try_from_string(data)? {
+            portfolio.add_position(position);
+        }
+        portfolio.total_investment = total_investment(data);
+        portfolio.name = portfolio_name(data);
+        portfolio.defaults = position_defaults(data);
+        Ok(portfolio)
+    }
+
+    // Reads a data file in the format of its extension (JSON, YAML, TOML or a CSV
+    // export with the default columns), its positions unpriced. Encrypted files are
+    // decrypted by the command line tool only.
+    pub fn load(path: &str) -> Result<Portfolio, String> {
+        check_data_file(path)?;
+        let data =
+            std::fs::read_to_string(path).map_err(|e| format!("Error reading file {path}: {e}"))?;
+        to_json_data(&data, DataFormat::from_path(path), &CsvColumns::default())
+            .and_then(|data| Portfolio::from_data(&data))
+            .map_err(|e| format!("{path}: {e}"))
+    }
+
+    // The positions as the JSON data they were loaded from
+    pub fn to_data_string(&self) -> Result<String, String> {
+        to_data_string(
+            &self.all_positions(),
+            self.name.as_deref(),
+            self.total_investment,
+            self.defaults.as_ref(),
+        )
+    }
+
+    // Writes the positions back to an (unencrypted) JSON data file
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        save_data(path, &self.to_data_string()?)
+    }
+
+    // Prices the positions and the watchlist with the provider. A position whose
+    // quote can't be fetched is kept unpriced, the errors say why. on_progress is
+    // called like for handle_positions.
+    pub async fn price(
+        &mut self,
+        provider: &impl PriceProvider,
+        on_progress: impl Fn(usize, usize) + Sync,
+    ) -> Vec<PositionError> {
+        let report = price_positions(provider, self.all_positions(), on_progress).await;
+        self.positions.clear();
+        self.watchlist.clear();
+        for position in report.positions {
+            self.add_position(position);
+        }
+        report.errors
+    }
+
     pub fn add_position(&mut self, position: PortfolioPosition) {
-        self.positions.push(position);
+        if position.is_watch_entry() {
+            self.watchlist.push(position);
+        } else {
+            self.positions.push(position);
+        }
     }
 
-    pub fn get_total_value(&self) -> f64 {
-        let mut sum = 0.0;
+    // The held positions followed by the watchlist, e.g. to save them
+    pub fn all_positions(&self) -> Vec<PortfolioPosition> {
+        self.positions
+            .iter()
+            .chain(&self.watchlist)
+            .cloned()
+            .collect()
+    }
+
+    pub fn get_total_value(&self) -> Decimal {
+        let mut sum = Decimal::ZERO;
 
         for position in &self.positions {
             sum += position.get_balance();
@@ -35,19 +129,31 @@ impl Portfolio {
         sum
     }
 
-    // Get the total value of the portfolio at a specific date
+    // Get the total value of the portfolio at a specific date. Use adjusted
+    // prices for returns, as today's amounts already include later splits.
+    // Without an explicit window every position gets the window of its market.
     // TODO: this function is not working as intended and the y_response is often an error
-    pub async fn get_historic_total_value(&self, date: DateTime<Utc>) -> Result<f64, String> {
-        let mut sum = 0.0;
+    pub async fn get_historic_total_value(
+        &self,
+        provider: &impl PriceProvider,
+        date: DateTime<Utc>,
+        adjustment: PriceAdjustment,
+        window: Option<chrono::Duration>,
+    ) -> Result<Decimal, String> {
+        let mut sum = Decimal::ZERO;
         let mut errors = Vec::new();
 
         use futures::future::join_all;
-        let mut cash_sum = 0.0;
+        let mut cash_sum = Decimal::ZERO;
         let mut tasks = Vec::new();
         let mut positions_with_ticker = Vec::new();
 
         for position in &self.positions {
-            if let Some(ticker) = position.get_ticker() {
+            // manual prices have no history, they count with today's value
+            if let Some(ticker) = position
+                .get_ticker()
+                .filter(|_| position.is_market_instrument())
+            {
                 positions_with_ticker.push((
                     ticker,
                     position.get_amount(),
@@ -56,24 +162,26 @@ impl Portfolio {
                         .unwrap_or(position.get_name())
                         .to_string(),
                 ));
-                tasks.push(get_historic_price(ticker, date));
+                let window = window.unwrap_or_else(|| position.historic_price_window());
+                tasks.push(provider.get_historic_price(ticker, date, adjustment, window));
             } else {
-                cash_sum += position.get_amount();
+                cash_sum += position.get_balance();
             }
         }
 
         let results = join_all(tasks).await;
-        for ((_, amount, label), y_response) in positions_with_ticker.into_iter().zip(results) {
-            match y_response {
-                Ok(response) => match response.last_quote() {
-                    Ok(quote) => {
-                        sum += quote.close * amount;
-                    }
-                    Err(e) => {
-                        errors.push(format!("Error getting last quote for {label}: {e}"));
-                        continue;
+        for ((_, amount, label), price) in positions_with_ticker.into_iter().zip(results) {
+            match price {
+                Ok(price) => {
+                    if price.date > date.date_naive() {
+                        log::info!(
+                            "No close of {label} on {}, using the one of {}",
+                            date.format("%Y-%m-%d"),
+                            price.date
+                        );
                     }
-                },
+                    sum += to_decimal(price.price) * amount;
+                }
                 Err(e) => {
                     let err_str = format!("{e}");
                     if err_str.contains("Bad Request") {
@@ -96,50 +204,344 @@ impl Portfolio {
         Ok(sum)
     }
 
+    // The (negative) sum of all liabilities, the total value is the net worth
+    pub fn get_liabilities_value(&self) -> Decimal {
+        total_liabilities(&self.positions)
+    }
+
     pub fn get_allocation(&self) -> HashMap<String, f64> {
-        let mut allocation: HashMap<String, f64> = HashMap::new();
+        allocation_percentages(&self.positions)
+    }
 
+    // Print the portfolio as a table
+    // maybe replace this function with a library
+    pub fn print(&self, include_sum: bool, format: &NumberFormat, max_quote_age: chrono::Duration) {
+        println!(
+            "{0: >26} | {1: >12} | {2: >10} | {3: >10}",
+            "Name", "Asset Class", "Amount", "Balance"
+        );
+        println!("====================================================================");
         for position in &self.positions {
-            let asset_class = position.get_asset_class();
-            let balance = position.get_balance();
-            let total_value = self.get_total_value();
-
-            let percentage = balance / total_value * 100.0;
-
-            if let Some(value) = allocation.get_mut(asset_class) {
-                *value += percentage;
+            println!(
+                "{0: >26} | {1: >12} | {2: >10} | {3: >10}",
+                position.get_marked_name(max_quote_age),
+                position.get_asset_class(),
+                position.display_amount(format),
+                position.format_balance(format)
+            );
+        }
+        if include_sum {
+            println!("====================================================================");
+            let liabilities = self.get_liabilities_value();
+            if liabilities.is_zero() {
+                println!(
+                    "Your total balance is: {}",
+                    format.money(self.get_total_value())
+                );
             } else {
-                allocation.insert(asset_class.to_string(), percentage);
+                println!(
+                    "Assets: {}",
+                    format.money(self.get_total_value() - liabilities)
+                );
+                println!("Liabilities: {}", format.money(liabilities));
+                println!("Net worth: {}", format.money(self.get_total_value()));
             }
         }
-        allocation
     }
 
-    // Print the portfolio as a table
-    // maybe replace this function with a library
-    pub fn print(&self, include_sum: bool) {
+    // Print the portfolio as a table with a subtotal per account or asset class
+    pub fn print_grouped(
+        &self,
+        group_by: GroupBy,
+        format: &NumberFormat,
+        max_quote_age: chrono::Duration,
+    ) {
         println!(
             "{0: >26} | {1: >12} | {2: >10} | {3: >10}",
             "Name", "Asset Class", "Amount", "Balance"
         );
+        for group in group_positions(&self.positions, group_by) {
+            println!("====================================================================");
+            println!("{}", group.name.bold());
+            for position in &group.positions {
+                println!(
+                    "{0: >26} | {1: >12} | {2: >10} | {3: >10}",
+                    position.get_marked_name(max_quote_age),
+                    position.get_asset_class(),
+                    position.display_amount(format),
+                    position.format_balance(format)
+                );
+            }
+            println!(
+                "{0: >26} | {1: >12} | {2: >10} | {3: >10}",
+                "Subtotal",
+                "",
+                "",
+                format.money(group.subtotal)
+            );
+        }
         println!("====================================================================");
+        println!(
+            "Your total balance is: {}",
+            format.money(self.get_total_value())
+        );
+        if group_by == GroupBy::Tag {
+            println!("Note: positions with several tags are counted once per tag.");
+        }
+    }
+
+    // Turn the PercentOf of the positions into amounts, i.e. the number of shares that
+    // buy the percentage of total at the current price. Fails without changing any
+    // position if the percentages add up to more than 100% or a price is missing.
+    pub fn resolve_amounts(&mut self, total: Decimal) -> Result<(), String> {
+        let mut percent_sum = Decimal::ZERO;
+        for position in &self.positions {
+            if let Some(percent) = position.get_percent_of() {
+                if percent.is_sign_negative() {
+                    return Err(format!(
+                        "PercentOf of {} must not be negative",
+                        position.get_name()
+                    ));
+                }
+                percent_sum += percent;
+            }
+        }
+        if percent_sum > Decimal::ONE_HUNDRED {
+            return Err(format!(
+                "The PercentOf of all positions add up to {percent_sum:.2}%, more than 100%"
+            ));
+        }
+
+        let mut amounts = Vec::new();
+        for position in &self.positions {
+            let Some(percent) = position.get_percent_of() else {
+                amounts.push(position.get_amount());
+                continue;
+            };
+            let value = total * percent / Decimal::ONE_HUNDRED;
+            let amount = match position.get_last_spot() {
+                Some(price) if price > Decimal::ZERO => value / price,
+                Some(_) => {
+                    return Err(format!(
+                        "No price for {} to resolve its PercentOf",
+                        position.get_name()
+                    ))
+                }
+                // cash is worth its amount
+                None => value,
+            };
+            amounts.push(amount);
+        }
+
+        for (position, amount) in self.positions.iter_mut().zip(amounts) {
+            position.set_amount(amount);
+        }
+        Ok(())
+    }
+
+    // Only keep the positions matching the filter, returns the number of hidden positions
+    // The watchlist is filtered on its own and has no balance to filter by.
+    // Returns the number of hidden positions, the totals don't include them.
+    pub fn apply_filter(&mut self, filter: &PositionFilter) -> usize {
+        // a tag is known if a position or a watch entry carries it
+        let filter = &filter.without_unknown_tags(&self.all_positions());
+        let watch_filter = PositionFilter {
+            min_balance: None,
+            ..filter.clone()
+        };
+        watch_filter.apply(&mut self.watchlist);
+        filter.apply(&mut self.positions)
+    }
+
+    pub fn sort(&mut self, sort: &PositionSort) {
+        sort.apply(&mut self.positions);
+        sort.apply(&mut self.watchlist);
+    }
+
+    // Get all positions whose quote is older than max_age (see PortfolioPosition::is_stale),
+    // the unpriced ones have no quote and are listed with the pricing errors
+    pub fn get_stale_positions(&self, max_age: chrono::Duration) -> Vec<&PortfolioPosition> {
+        self.positions
+            .iter()
+            .filter(|position| position.is_stale(max_age) && !position.is_unpriced())
+            .collect()
+    }
+
+    // Warn about positions whose quotes are older than max_age, the summary line
+    // explains the marker of their names in the tables
+    pub fn print_stale_warnings(&self, max_age: chrono::Duration) {
+        let stale = self.get_stale_positions(max_age);
+
+        for position in &stale {
+            let quote_time = match position.get_last_spot_time() {
+                Some(time) => time.format("%Y-%m-%d %H:%M UTC").to_string(),
+                None => "never".to_string(),
+            };
+            let warning = format!(
+                "Warning: quote for {} is older than {} trading days (last quote: {})",
+                position.get_name(),
+                max_age.num_days(),
+                quote_time
+            );
+            println!("{}", warning.yellow());
+        }
+        if !stale.is_empty() {
+            let quoted = self
+                .positions
+                .iter()
+                .filter(|position| position.has_dated_price())
+                .count();
+            let summary = format!(
+                "Warning: {} of {} quotes are stale, their positions are marked with {}",
+                stale.len(),
+                quoted,
+                STALE_MARKER
+            );
+            println!("{}", summary.yellow());
+        }
+    }
+
+    // Point out simulated prices, so the totals aren't mistaken for the real ones
+    pub fn print_override_note(&self) {
+        let overridden = self
+            .positions
+            .iter()
+            .chain(&self.watchlist)
+            .filter(|position| position.has_price_override())
+            .count();
+        if overridden > 0 {
+            let note = format!(
+                "Note: {overridden} position(s) are valued with a PriceOverride and marked with {OVERRIDE_MARKER}, the totals are simulated"
+            );
+            println!("{}", note.yellow());
+        }
+    }
+
+    // Point out interest payment dates that were corrected or are in the future
+    pub fn print_interest_date_warnings(&self, clock: &dyn Clock) {
+        let now = clock.now();
         for position in &self.positions {
+            for warning in position.interest_date_warnings(now) {
+                println!(
+                    "{}",
+                    format!("Warning: {}: {warning}", position.get_name()).yellow()
+                );
+            }
+        }
+    }
+
+    // The totals add up the balances of positions in another currency as they are
+    pub fn print_currency_note(&self, currency: &str) {
+        let foreign = self
+            .positions
+            .iter()
+            .filter(|position| {
+                position
+                    .get_currency()
+                    .is_some_and(|other| !other.eq_ignore_ascii_case(currency))
+            })
+            .count();
+        if foreign > 0 {
+            let note = format!(
+                "Note: {foreign} position(s) are in another currency than {currency}, the totals add up their balances without conversion"
+            );
+            println!("{}", note.yellow());
+        }
+    }
+
+    // Credit due interest on all interest-bearing cash positions and
+    // return the names of the positions together with the credited interest
+    pub fn apply_interest_if_due(
+        &mut self,
+        clock: &dyn Clock,
+        decimals: u32,
+    ) -> Vec<(String, Decimal)> {
+        let current_date = clock.now();
+        let mut credited = Vec::new();
+
+        for position in &mut self.positions {
+            if let Some(interest) = position.apply_interest_if_due(current_date, decimals) {
+                credited.push((position.get_name().to_string(), interest));
+            }
+        }
+        credited
+    }
+
+    // Make the due installments of all loans, see PortfolioPosition::apply_loan_payments_if_due
+    pub fn apply_loan_payments_if_due(
+        &mut self,
+        clock: &dyn Clock,
+        decimals: u32,
+    ) -> Vec<(String, LoanPayment)> {
+        let current_date = clock.now();
+        let mut paid = Vec::new();
+
+        for position in &mut self.positions {
+            for payment in position.apply_loan_payments_if_due(current_date, decimals) {
+                paid.push((position.get_name().to_string(), payment));
+            }
+        }
+        paid
+    }
+
+    // Print the projected interest of the next payment of every interest-bearing cash position
+    // With an annual inflation in %, the real rate of every position follows its rate
+    pub fn print_interest_preview(
+        &self,
+        decimals: u32,
+        inflation: Option<f64>,
+        format: &NumberFormat,
+    ) {
+        let real_header = match inflation {
+            Some(_) => format!(" | {0: >8}", "Real %"),
+            None => String::new(),
+        };
+        println!(
+            "{0: >26} | {1: >8}{real_header} | {2: >12} | {3: >10}",
+            "Name", "Rate %", "Next Payment", "Interest"
+        );
+        println!("{}", "=".repeat(68 + real_header.len()));
+        for position in self.positions.iter().filter(|p| p.is_cash_with_interest()) {
+            let Some(next_payment) = position.get_next_interest_payment() else {
+                continue;
+            };
+            let interest = position
+                .preview_interest_due(next_payment, decimals)
+                .map(|preview| preview.interest)
+                .unwrap_or_default();
+
+            let rate = position.get_interest_rate().unwrap_or_default();
+            let real = match inflation {
+                Some(inflation) => {
+                    let real = real_return(to_f64(rate) / 100.0, inflation / 100.0) * 100.0;
+                    format!(" | {: >8}", format.percent_number(to_decimal(real)))
+                }
+                None => String::new(),
+            };
             println!(
-                "{0: >26} | {1: >12} | {2: >10.2} | {3: >10.2}",
+                "{0: >26} | {1: >8}{real} | {2: >12} | {3: >10}",
                 position.get_name(),
-                position.get_asset_class(),
-                position.get_amount(),
-                position.get_balance()
+                format.percent_number(rate),
+                next_payment.format("%Y-%m-%d"),
+                format.money(interest)
             );
         }
-        if include_sum {
-            println!("====================================================================");
-            println!("Your total balance is: {:.2}", self.get_total_value());
+    }
+
+    // The number of holdings of the same size that would be as concentrated as the
+    // portfolio, 1 / concentration_index. 0 without assets.
+    pub fn effective_holdings(&self) -> f64 {
+        let index = concentration_index(&self.positions);
+        if index > 0.0 {
+            1.0 / index
+        } else {
+            0.0
         }
     }
 
     // Print the allocation in descending order %-wise
-    pub fn print_allocation(&self) {
+    // The liabilities are listed on their own, the percentages only refer to the assets
+    pub fn print_allocation(&self, format: &NumberFormat) {
         let allocation = self.get_allocation();
 
         // create a vector and sort it by the %-value of the allocation in descending order
@@ -148,7 +550,29 @@ impl Portfolio {
 
         println!("====================================");
         for (asset_class, percentage) in allocation_vec {
-            println!("{asset_class: >12} | {percentage: >10.2}");
+            println!(
+                "{asset_class: >12} | {: >10}",
+                format.percent_number(to_decimal(*percentage))
+            );
+        }
+
+        let liabilities = self.get_liabilities_value();
+        if !liabilities.is_zero() {
+            println!("====================================");
+            println!(
+                "{: >12} | {: >10}",
+                "Liabilities",
+                format.money(liabilities)
+            );
+        }
+
+        let index = concentration_index(&self.positions);
+        if index > 0.0 {
+            println!(
+                "\nConcentration (HHI): {}, like {} holdings of the same size",
+                format.number_with(to_decimal(index), 3),
+                format.number_with(to_decimal(self.effective_holdings()), 1)
+            );
         }
     }
 
@@ -171,7 +595,7 @@ impl Portfolio {
                 let this = &position;
                 this.get_name()
             };
-            let balance = position.get_balance() as f32;
+            let balance = to_f64(position.get_balance()) as f32;
 
             data.push(piechart::Data {
                 label: name.to_string(),
@@ -188,7 +612,10 @@ impl Portfolio {
             .draw(&data);
     }
 
-    pub async fn get_performance_data(&self) -> Result<(f64, f64, f64), String> {
+    pub async fn get_performance_data(
+        &self,
+        provider: &impl PriceProvider,
+    ) -> Result<(f64, f64, f64), String> {
         let db = sled::open("database").map_err(|e| format!("Database error: {e}"))?;
 
         // Yahoo first of the year is YYYY-01-03
@@ -199,15 +626,26 @@ impl Portfolio {
             .with_ymd_and_hms(Utc::now().year(), Utc::now().month(), 3, 0, 0, 0)
             .unwrap();
 
-        let value_at_beginning_of_year = self.get_historic_total_value(first_of_the_year).await?;
-        let value_at_beginning_of_month = self.get_historic_total_value(first_of_the_month).await?;
+        let value_at_beginning_of_year = self
+            .get_historic_total_value(provider, first_of_the_year, PriceAdjustment::Adjusted, None)
+            .await?;
+        let value_at_beginning_of_month = self
+            .get_historic_total_value(
+                provider,
+                first_of_the_month,
+                PriceAdjustment::Adjusted,
+                None,
+            )
+            .await?;
 
         let last: f64 = match &db.iter().last() {
             Some(Ok(last)) => String::from_utf8_lossy(&last.1).parse().unwrap_or(0.0),
             _ => 0.0,
         };
 
-        let current_value = self.get_total_value();
+        let value_at_beginning_of_year = to_f64(value_at_beginning_of_year);
+        let value_at_beginning_of_month = to_f64(value_at_beginning_of_month);
+        let current_value = to_f64(self.get_total_value());
 
         let ytd_performance =
             (last - value_at_beginning_of_year) / value_at_beginning_of_year * 100.0;
@@ -218,7 +656,15 @@ impl Portfolio {
         Ok((ytd_performance, monthly_performance, recent_performance))
     }
 
-    pub async fn print_performance(&self) {
+    // An explicit window is searched for the closes after every date, see get_historic_total_value.
+    // With an annual inflation in %, the periods since a date also show their real return.
+    pub async fn print_performance(
+        &self,
+        provider: &impl PriceProvider,
+        window: Option<chrono::Duration>,
+        inflation: Option<f64>,
+        format: &NumberFormat,
+    ) {
         let db = sled::open("database").unwrap();
 
         // Yahoo first of the year is YYYY-01-03
@@ -229,13 +675,27 @@ impl Portfolio {
             .with_ymd_and_hms(Utc::now().year(), Utc::now().month(), 3, 0, 0, 0)
             .unwrap();
 
-        let value_at_beginning_of_year = self.get_historic_total_value(first_of_the_year).await;
+        let value_at_beginning_of_year = self
+            .get_historic_total_value(
+                provider,
+                first_of_the_year,
+                PriceAdjustment::Adjusted,
+                window,
+            )
+            .await;
         if let Err(e) = value_at_beginning_of_year {
             println!("Error getting value for beginning of year: {e}");
             return;
         }
 
-        let value_at_beginning_of_month = self.get_historic_total_value(first_of_the_month).await;
+        let value_at_beginning_of_month = self
+            .get_historic_total_value(
+                provider,
+                first_of_the_month,
+                PriceAdjustment::Adjusted,
+                window,
+            )
+            .await;
         if let Err(e) = value_at_beginning_of_month {
             println!("Error getting value for beginning of month: {e}");
             return;
@@ -254,46 +714,429 @@ impl Portfolio {
 
         for (i, value) in values.iter().enumerate() {
             let value = match value {
-                Ok(value) => *value,
+                Ok(value) => to_f64(*value),
                 Err(_) => continue,
             };
             let performance = (last - value) / value * 100.0;
-            let s = format!("{performance:.2}%");
+            let s = format.percent(to_decimal(performance));
             let s = if performance >= 0.0 {
                 s.green()
             } else {
                 s.red()
             };
+            let since = match i {
+                0 => Some(first_of_the_year),
+                1 => Some(first_of_the_month),
+                _ => None,
+            };
+            let real = match since.zip(inflation) {
+                Some((since, inflation)) => format!(
+                    " (real {})",
+                    format.percent(to_decimal(real_return_percent(
+                        performance,
+                        inflation,
+                        since,
+                        Utc::now()
+                    )))
+                ),
+                None => String::new(),
+            };
 
             match i {
-                0 => println!("YTD: {s}"),
-                1 => println!("Since beginning of month: {s}"),
+                0 => println!("YTD: {s}{real}"),
+                1 => println!("Since beginning of month: {s}{real}"),
                 2 => println!("Since last balance check: {s}"),
                 _ => (),
             }
         }
+        println!(
+            "Historic values use {} closes (splits and dividends included).",
+            PriceAdjustment::Adjusted
+        );
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::clock::FixedClock;
+    use crate::position::{
+        from_string, handle_position, total_investment, DEFAULT_MAX_QUOTE_AGE_DAYS,
+    };
+    use crate::provider::{priced_at, MockProvider};
+    use chrono::Duration;
+    use rust_decimal::dec;
 
-    #[tokio::test]
-    async fn test_get_historic_total_value() {
-        use crate::position::from_string;
+    fn example_provider() -> MockProvider {
+        MockProvider::new()
+            .with_price("SPY", 300.0)
+            .with_price("TLT", 75.0)
+            .with_price("GSG", 50.0)
+            .with_price("GLD", 250.0)
+            .with_price("BTC-USD", 50000.0)
+            .with_name("SPY", "SPDR S&P 500 ETF")
+            .with_name("GLD", "SPDR Gold Shares")
+            .with_historic_price("SPY", 250.0)
+            .with_historic_price("TLT", 50.0)
+            .with_historic_price("GSG", 50.0)
+            .with_historic_price("GLD", 200.0)
+            .with_historic_price("BTC-USD", 30000.0)
+    }
+
+    async fn priced_example_portfolio(provider: &MockProvider) -> Portfolio {
         let positions_str = std::fs::read_to_string("example_data.json").unwrap();
-        let positions = from_string(&positions_str);
         let mut portfolio = Portfolio::new();
-        for p in positions {
-            portfolio.add_position(p);
+        for mut position in from_string(&positions_str) {
+            portfolio.add_position(handle_position(provider, &mut position).await.unwrap());
+        }
+        portfolio
+    }
+
+    #[tokio::test]
+    async fn test_load_price_and_save() {
+        let mut portfolio = Portfolio::load("tests/fixtures/portfolio.yaml").unwrap();
+        assert_eq!(portfolio.positions.len(), 3);
+        let errors = portfolio
+            .price(&MockProvider::new().with_price("SPY", 400.0), |_, _| {})
+            .await;
+        assert!(errors.is_empty());
+        assert_eq!(portfolio.get_total_value(), dec!(200));
+
+        // saved in the format of the file name
+        for extension in ["json", "yaml", "toml"] {
+            let path = std::env::temp_dir()
+                .join(format!("portfolio_save_{}.{extension}", std::process::id()));
+            let path = path.to_str().unwrap();
+            portfolio.save(path).unwrap();
+            let saved = Portfolio::load(path).unwrap();
+            std::fs::remove_file(path).unwrap();
+            assert_eq!(
+                saved.to_data_string(),
+                portfolio.to_data_string(),
+                "{extension}"
+            );
+        }
+
+        assert!(Portfolio::load("tests/fixtures/missing.json")
+            .unwrap_err()
+            .starts_with("Portfolio file not found"));
+        assert!(Portfolio::from_data("[{\"Name\": 1}]")
+            .unwrap_err()
+            .starts_with("The data has no valid positions"));
+    }
+
+    #[tokio::test]
+    async fn test_total_value_and_allocation_with_mock_provider() {
+        let portfolio = priced_example_portfolio(&example_provider()).await;
+        assert_eq!(portfolio.get_total_value(), dec!(2000));
+        assert_eq!(portfolio.positions[0].get_name(), "SPDR S&P 500 ETF");
+
+        let allocation = portfolio.get_allocation();
+        assert_eq!(allocation["Stocks"], 30.0);
+        assert_eq!(allocation["Bonds"], 15.0);
+        assert_eq!(allocation["Commodities"], 7.5);
+        assert_eq!(allocation["Gold"], 12.5);
+        assert_eq!(allocation["Crypto"], 25.0);
+        assert_eq!(allocation["Cash"], 10.0);
+    }
+
+    #[tokio::test]
+    async fn test_watchlist_is_kept_out_of_totals() {
+        let provider = example_provider()
+            .with_price("NVDA", 120.0)
+            .with_name("NVDA", "NVIDIA Corporation")
+            .with_price("TSLA", 250.0);
+        let mut portfolio = priced_example_portfolio(&provider).await;
+        for mut position in from_string(
+            r#"[
+                {"Ticker": "NVDA", "AssetClass": "Stocks", "Amount": 0},
+                {"Name": "Tesla", "Ticker": "TSLA", "AssetClass": "Stocks", "Amount": 3, "Watch": true}
+            ]"#,
+        ) {
+            portfolio.add_position(handle_position(&provider, &mut position).await.unwrap());
         }
+
+        assert_eq!(portfolio.positions.len(), 6);
+        assert_eq!(portfolio.get_total_value(), dec!(2000));
+        assert_eq!(portfolio.get_allocation()["Stocks"], 30.0);
+
+        // still priced, and named by the provider
+        let names: Vec<&str> = portfolio.watchlist.iter().map(|p| p.get_name()).collect();
+        assert_eq!(names, ["NVIDIA Corporation", "Tesla"]);
+        assert_eq!(portfolio.watchlist[0].get_last_spot(), Some(dec!(120)));
+
+        // saved together with the positions
+        assert_eq!(portfolio.all_positions().len(), 8);
+
+        // the watchlist is filtered on its own, without the balance
+        let hidden = portfolio.apply_filter(&PositionFilter {
+            min_balance: Some(dec!(100)),
+            search: Some("t".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(hidden, 2);
+        assert_eq!(portfolio.watchlist.len(), 2);
+    }
+
+    #[test]
+    fn test_loan_interest_grows_the_debt() {
+        let mut portfolio = Portfolio::new();
+        for position in from_string(
+            r#"[
+                {"Name": "Savings", "AssetClass": "Cash", "Amount": 20000},
+                {
+                    "Name": "Car Loan",
+                    "AssetClass": "Liability",
+                    "Amount": 10000,
+                    "InterestRate": 3.65,
+                    "PaymentFrequencyDays": 30,
+                    "NextInterestPayment": "2024-01-31",
+                    "LastInterestPayment": "2024-01-01"
+                }
+            ]"#,
+        ) {
+            portfolio.add_position(position);
+        }
+        assert_eq!(portfolio.get_liabilities_value(), dec!(-10000));
+        assert_eq!(portfolio.get_total_value(), dec!(10000));
+        assert_eq!(portfolio.get_allocation()["Cash"], 100.0);
+
+        // two periods of 30 days, the second one on the grown debt
+        let now = FixedClock(Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap());
+        let credited = portfolio.apply_interest_if_due(&now, 2);
+        assert_eq!(credited, [("Car Loan".to_string(), dec!(60.09))]);
+        assert_eq!(portfolio.positions[1].get_amount(), dec!(10060.09));
+        assert_eq!(portfolio.get_liabilities_value(), dec!(-10060.09));
+        assert_eq!(portfolio.get_total_value(), dec!(9939.91));
+    }
+
+    #[test]
+    fn test_effective_holdings() {
+        let mut portfolio = Portfolio::new();
+        assert_eq!(portfolio.effective_holdings(), 0.0);
+        portfolio.add_position(
+            from_string(r#"[{"Name": "A", "AssetClass": "Cash", "Amount": 1}]"#).remove(0),
+        );
+        assert_eq!(portfolio.effective_holdings(), 1.0);
+        for position in from_string(
+            r#"[
+                {"Name": "B", "AssetClass": "Cash", "Amount": 1},
+                {"Name": "C", "AssetClass": "Cash", "Amount": 1},
+                {"Name": "D", "AssetClass": "Cash", "Amount": 1}
+            ]"#,
+        ) {
+            portfolio.add_position(position);
+        }
+        assert!((portfolio.effective_holdings() - 4.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_total_value_is_exact() {
+        let mut portfolio = Portfolio::new();
+        for position in from_string(
+            r#"[
+                {"Name": "A", "AssetClass": "Cash", "Amount": 0.1},
+                {"Name": "B", "AssetClass": "Cash", "Amount": 0.2}
+            ]"#,
+        ) {
+            portfolio.add_position(position);
+        }
+        // 0.1 + 0.2 is 0.30000000000000004 in f64
+        assert_eq!(portfolio.get_total_value(), dec!(0.3));
+    }
+
+    #[tokio::test]
+    async fn test_historic_total_value_with_mock_provider() {
+        let provider = example_provider();
+        let portfolio = priced_example_portfolio(&provider).await;
         let date = Utc.with_ymd_and_hms(2023, 1, 1, 0, 0, 0).unwrap();
-        let value = portfolio.get_historic_total_value(date).await;
-        // Should include cash amount directly, and use tickers for others
-        match value {
-            Ok(v) => assert!(v > 0.0),
-            Err(e) => panic!("Error occurred in performance command: {e}"),
+        let value = portfolio
+            .get_historic_total_value(&provider, date, PriceAdjustment::Adjusted, None)
+            .await;
+        assert_eq!(value, Ok(dec!(1550)));
+
+        let provider = MockProvider::new();
+        let value = portfolio
+            .get_historic_total_value(&provider, date, PriceAdjustment::Adjusted, None)
+            .await;
+        assert!(value
+            .unwrap_err()
+            .starts_with("Error getting historic price data for SPY"));
+    }
+
+    #[tokio::test]
+    async fn test_historic_window_per_market() {
+        let mut portfolio = Portfolio::new();
+        for position in from_string(
+            r#"[
+                {"Name": "Apple", "Ticker": "AAPL", "AssetClass": "Stock", "Amount": 1},
+                {"Name": "Bitcoin", "Ticker": "BTC-EUR", "AssetClass": "Digital", "Amount": 1},
+                {"Name": "Grayscale", "Ticker": "GBTC", "AssetClass": "Crypto", "Amount": 1}
+            ]"#,
+        ) {
+            portfolio.add_position(position);
         }
+        let date = Utc.with_ymd_and_hms(2024, 1, 6, 0, 0, 0).unwrap();
+        let provider = MockProvider::new()
+            .with_historic_price("AAPL", 180.0)
+            .with_historic_price("BTC-EUR", 40000.0)
+            .with_historic_price("GBTC", 40.0);
+
+        let value = portfolio
+            .get_historic_total_value(&provider, date, PriceAdjustment::Adjusted, None)
+            .await;
+        assert_eq!(value, Ok(dec!(40220)));
+        assert_eq!(provider.historic_window("AAPL"), Some(Duration::days(3)));
+        assert_eq!(provider.historic_window("BTC-EUR"), Some(Duration::days(1)));
+        assert_eq!(provider.historic_window("GBTC"), Some(Duration::days(1)));
+
+        // an explicit window applies to every position
+        let provider = MockProvider::new()
+            .with_historic_price("AAPL", 180.0)
+            .with_historic_price("BTC-EUR", 40000.0)
+            .with_historic_price("GBTC", 40.0);
+        let window = Some(Duration::days(5));
+        portfolio
+            .get_historic_total_value(&provider, date, PriceAdjustment::Adjusted, window)
+            .await
+            .unwrap();
+        assert_eq!(provider.historic_window("AAPL"), window);
+        assert_eq!(provider.historic_window("BTC-EUR"), window);
+    }
+
+    #[tokio::test]
+    async fn test_stale_positions_with_mock_quotes() {
+        let now = Utc::now();
+        let provider = MockProvider::new()
+            .with_price("AAPL", 170.0)
+            .with_quote_time("AAPL", now - chrono::Duration::hours(1))
+            .with_price("BND", 72.0)
+            .with_quote_time("BND", now - chrono::Duration::days(7))
+            .with_price("MSFT", 380.0)
+            .with_quote_time("MSFT", now - chrono::Duration::days(14));
+        let mut portfolio = Portfolio::new();
+        for mut position in from_string(
+            r#"[
+                {"Name": "Apple", "Ticker": "AAPL", "AssetClass": "Stocks", "Amount": 1},
+                {"Name": "Bond Fund", "Ticker": "BND", "AssetClass": "Bonds", "Amount": 1},
+                {"Name": "Microsoft", "Ticker": "MSFT", "AssetClass": "Stocks", "Amount": 1},
+                {"Name": "Savings", "AssetClass": "Cash", "Amount": 100}
+            ]"#,
+        ) {
+            portfolio.add_position(handle_position(&provider, &mut position).await.unwrap());
+        }
+
+        // a week of quotes has 5 trading days, two weeks have 10
+        let stale = |days: i64| -> Vec<&str> {
+            portfolio
+                .get_stale_positions(chrono::Duration::days(days))
+                .iter()
+                .map(|position| position.get_name())
+                .collect()
+        };
+        assert_eq!(
+            stale(DEFAULT_MAX_QUOTE_AGE_DAYS),
+            ["Bond Fund", "Microsoft"]
+        );
+        assert_eq!(stale(6), ["Microsoft"]);
+        assert!(stale(11).is_empty());
+
+        let max_age = chrono::Duration::days(DEFAULT_MAX_QUOTE_AGE_DAYS);
+        assert_eq!(portfolio.positions[0].get_marked_name(max_age), "Apple");
+        assert_eq!(
+            portfolio.positions[2].get_marked_name(max_age),
+            "Microsoft *"
+        );
+        assert_eq!(portfolio.positions[3].get_marked_name(max_age), "Savings");
+    }
+
+    fn target_portfolio(data: &str) -> Portfolio {
+        let mut portfolio = Portfolio::new();
+        let price = |ticker: &str| if ticker == "SPY" { 400.0 } else { 0.0 };
+        for position in priced_at(data, price) {
+            portfolio.add_position(position);
+        }
+        portfolio
+    }
+
+    #[test]
+    fn test_resolve_amounts() {
+        let data = r#"{
+            "TotalInvestment": 10000,
+            "Positions": [
+                {"Name": "S&P 500", "Ticker": "SPY", "AssetClass": "Stocks", "PercentOf": 60},
+                {"Name": "Savings", "AssetClass": "Cash", "PercentOf": 30},
+                {"Name": "Checking", "AssetClass": "Cash", "Amount": 500}
+            ]
+        }"#;
+        assert_eq!(total_investment(data), Some(dec!(10000)));
+
+        let mut portfolio = target_portfolio(data);
+        portfolio.resolve_amounts(dec!(10000)).unwrap();
+        assert_eq!(portfolio.positions[0].get_amount(), dec!(15));
+        assert_eq!(portfolio.positions[0].get_balance(), dec!(6000));
+        assert_eq!(portfolio.positions[1].get_amount(), dec!(3000));
+        assert_eq!(portfolio.positions[2].get_amount(), dec!(500));
+
+        // a plain list of positions has no total
+        assert_eq!(
+            total_investment(r#"[{"AssetClass": "Cash", "Amount": 1}]"#),
+            None
+        );
+    }
+
+    #[test]
+    fn test_resolve_amounts_validation() {
+        let mut portfolio = target_portfolio(
+            r#"[
+                {"Name": "S&P 500", "Ticker": "SPY", "AssetClass": "Stocks", "PercentOf": 70},
+                {"Name": "Savings", "AssetClass": "Cash", "PercentOf": 40}
+            ]"#,
+        );
+        let error = portfolio.resolve_amounts(dec!(10000)).unwrap_err();
+        assert!(error.contains("110.00%"));
+        assert_eq!(portfolio.positions[0].get_amount(), dec!(0));
+
+        let mut portfolio = target_portfolio(
+            r#"[{"Name": "Unpriced", "Ticker": "XYZ", "AssetClass": "Stocks", "PercentOf": 10}]"#,
+        );
+        assert!(portfolio.resolve_amounts(dec!(10000)).is_err());
+
+        let mut portfolio = target_portfolio(
+            r#"[
+                {"Name": "A", "AssetClass": "Cash", "PercentOf": 33.33},
+                {"Name": "B", "AssetClass": "Cash", "PercentOf": 33.33},
+                {"Name": "C", "AssetClass": "Cash", "PercentOf": 33.34}
+            ]"#,
+        );
+        assert!(portfolio.resolve_amounts(dec!(300)).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_get_historic_total_value() {
+        let yahoo = crate::mock_yahoo::MockYahoo::aapl().await;
+        let mut portfolio = Portfolio::new();
+        for p in from_string(
+            r#"[
+                {"Name": "Apple", "Ticker": "AAPL", "AssetClass": "Stocks", "Amount": 10},
+                {"Name": "Savings", "AssetClass": "Cash", "Amount": 1000}
+            ]"#,
+        ) {
+            portfolio.add_position(p);
+        }
+        let date = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        let value = portfolio
+            .get_historic_total_value(&yahoo.connector(), date, PriceAdjustment::Adjusted, None)
+            .await
+            .unwrap();
+        // cash counts with its amount, AAPL with the close of 2020-01-02
+        assert_eq!(value.round_dp(2), dec!(1727.96));
+
+        // the close traded back then, before the 4-for-1 split
+        let raw = portfolio
+            .get_historic_total_value(&yahoo.connector(), date, PriceAdjustment::Raw, None)
+            .await
+            .unwrap();
+        assert_eq!(raw.round_dp(2), dec!(4003.50));
     }
 }
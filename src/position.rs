@@ -1,192 +1,3761 @@
+use crate::format::{AmountPrecision, NumberFormat};
+use crate::pricing::ErrorKind;
+use crate::provider::{
+    is_crypto_ticker, PriceProvider, PriceQuote, QuoteError, CRYPTO_HISTORIC_WINDOW_DAYS,
+    HISTORIC_WINDOW_DAYS,
+};
+use crate::rate_limit::{self, DEFAULT_REQUESTS_PER_SECOND};
 use chrono::prelude::*;
-use serde::Deserialize;
-use time::OffsetDateTime;
+use rust_decimal::prelude::*;
+use serde::{Deserialize, Serialize};
 use yahoo_finance_api as yahoo;
 
-#[derive(Debug, Deserialize)]
+// Decimal places interest is rounded to, unless the currency uses a different number
+pub const DEFAULT_CURRENCY_DECIMALS: u32 = 2;
+
+// Number of decimal places of the minor unit of a currency (ISO 4217)
+pub fn currency_decimals(currency: &str) -> u32 {
+    match currency.to_uppercase().as_str() {
+        "JPY" | "KRW" | "ISK" | "CLP" | "VND" | "PYG" | "UGX" => 0,
+        "BHD" | "KWD" | "OMR" | "JOD" | "TND" | "LYD" | "IQD" => 3,
+        _ => DEFAULT_CURRENCY_DECIMALS,
+    }
+}
+
+// Half-way values are rounded away from zero, like 0.005 to 0.01
+pub fn round_to(value: Decimal, decimals: u32) -> Decimal {
+    value.round_dp_with_strategy(decimals, RoundingStrategy::MidpointAwayFromZero)
+}
+
+// Money is kept as Decimal, f64 is only used at the boundaries, e.g. for the
+// Yahoo quotes and the percentages of the charts. The shortest representation
+// of the f64 is converted, so 0.1 becomes exactly 0.1.
+pub fn to_decimal(value: f64) -> Decimal {
+    Decimal::from_str(&value.to_string())
+        .ok()
+        .or_else(|| Decimal::from_f64(value))
+        .unwrap_or_default()
+}
+
+pub fn to_f64(value: Decimal) -> f64 {
+    value.to_f64().unwrap_or_default()
+}
+
+// Amounts are shown with at least this many decimals, unless their precision is lower
+const MIN_AMOUNT_DECIMALS: u32 = 2;
+
+// Quotes older than this many trading days (weekdays) are considered stale
+pub const DEFAULT_MAX_QUOTE_AGE_DAYS: i64 = 3;
+// Appended to the name of positions with a stale quote
+pub const STALE_MARKER: &str = "*";
+// Marks the positions valued with a PriceOverride
+pub const OVERRIDE_MARKER: &str = "(what-if)";
+// Marks the positions whose quote couldn't be fetched, see pricing::price_positions
+pub const UNPRICED_MARKER: &str = "(unpriced)";
+// Marks the positions with Notes in the balances table
+pub const NOTES_MARKER: &str = "†";
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct PortfolioPosition {
+    #[serde(skip_serializing_if = "Option::is_none")]
     name: Option<String>,
-    ticker: Option<String>,
+    // broker exports call the ticker a symbol and the amount a quantity or shares.
+    // A list of listings, e.g. ["EUNL.DE", "IWDA.AS"], is tried in order.
+    #[serde(
+        default,
+        alias = "symbol",
+        alias = "Symbol",
+        with = "listings",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    ticker: Vec<String>,
+    // the listing of the Ticker that had a quote, the first one until it is priced
+    #[serde(skip)]
+    listing: usize,
+    // European brokers identify instruments by ISIN, a position with one and
+    // no ticker is priced with the symbol the Yahoo search finds for it
+    #[serde(
+        alias = "ISIN",
+        alias = "isin",
+        skip_serializing_if = "Option::is_none"
+    )]
+    isin: Option<String>,
     asset_class: String,
-    amount: f64,
+    // can be left out if the position is sized by PercentOf
+    #[serde(
+        default,
+        alias = "quantity",
+        alias = "Quantity",
+        alias = "shares",
+        alias = "Shares"
+    )]
+    amount: Decimal,
+    // share of the TotalInvestment of the data file in %, the amount
+    // is resolved from it with the current price
+    #[serde(skip_serializing_if = "Option::is_none")]
+    percent_of: Option<Decimal>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_tags",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    account: Option<String>,
+    // the bank or broker the position is held at
+    #[serde(skip_serializing_if = "Option::is_none")]
+    institution: Option<String>,
+    // free text, e.g. "vested RSUs, sellable after 2026-05", shown by the show command
+    #[serde(skip_serializing_if = "Option::is_none")]
+    notes: Option<String>,
+    // the portfolio of the data file the position was loaded from, set when
+    // several files are combined so it can be written back to its file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    portfolio: Option<String>,
+    // purchase price per unit, used for the unrealized gain
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cost_basis: Option<Decimal>,
+    // the Amount was right on LastVerified (or else on the PurchaseDate),
+    // splits after that date are reported by the split check
+    #[serde(default, with = "date_format", skip_serializing_if = "Option::is_none")]
+    purchase_date: Option<DateTime<Utc>>,
+    #[serde(default, with = "date_format", skip_serializing_if = "Option::is_none")]
+    last_verified: Option<DateTime<Utc>>,
+    // the currency the position is quoted or held in when it isn't the one of the
+    // portfolio, only used to show its balance, it isn't converted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    currency: Option<String>,
+    // annual fund fees (TER) in %, already included in the price of the fund
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expense_ratio: Option<Decimal>,
+    // expected annual dividends in % of the balance, for the income projection
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dividend_yield: Option<Decimal>,
+    // dividends received, in the currency of the portfolio
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    dividends: Vec<IncomeEvent>,
+    // price per unit of unlisted assets, used instead of a quote
+    #[serde(skip_serializing_if = "Option::is_none")]
+    manual_price: Option<Decimal>,
+    // when the manual price was last updated, for the staleness warning
+    #[serde(default, with = "date_format", skip_serializing_if = "Option::is_none")]
+    price_date: Option<DateTime<Utc>>,
+    // simulated price per unit of a ticker for what-if scenarios, used instead of the quote
+    #[serde(skip_serializing_if = "Option::is_none")]
+    price_override: Option<Decimal>,
+    // a ticker that is only watched, see is_watch_entry
+    #[serde(default, skip_serializing_if = "is_false")]
+    watch: bool,
+
+    // annual interest rate in % for cash positions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    interest_rate: Option<Decimal>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    payment_frequency_days: Option<u32>,
+    #[serde(default, with = "date_format", skip_serializing_if = "Option::is_none")]
+    next_interest_payment: Option<DateTime<Utc>>,
+    #[serde(default, with = "date_format", skip_serializing_if = "Option::is_none")]
+    last_interest_payment: Option<DateTime<Utc>>,
+    // fixed installment of a loan, paid on the interest payment dates
+    #[serde(skip_serializing_if = "Option::is_none")]
+    payment_amount: Option<Decimal>,
+    // interest credited by apply_interest_if_due
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    interest_payments: Vec<IncomeEvent>,
+
+    // the keys this version doesn't know, e.g. a Broker or a WKN read by other
+    // scripts, they are written back as they were (see unknown_fields for --strict)
+    #[serde(flatten)]
+    extra: serde_json::Map<String, serde_json::Value>,
+
+    #[serde(skip)]
+    last_spot: Decimal,
+
+    #[serde(skip)]
+    last_spot_time: Option<DateTime<Utc>>,
+
+    #[serde(skip)]
+    previous_close: Option<Decimal>,
+
+    // the amount every portfolio holds of a consolidated position
+    #[serde(skip)]
+    portfolio_amounts: Vec<(String, Decimal)>,
+
+    // fetching the quote failed, the position is shown without a price
+    #[serde(skip)]
+    unpriced: bool,
+}
+
+// A dividend or interest payment that was received
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct IncomeEvent {
+    pub date: NaiveDate,
+    pub amount: Decimal,
+}
+
+// Interest that would be credited by apply_interest_if_due
+#[derive(Debug, Clone, PartialEq)]
+pub struct InterestPreview {
+    pub interest: Decimal,
+    // every payment on its own
+    pub payments_log: Vec<IncomeEvent>,
+    pub new_amount: Decimal,
+    pub payments: u32,
+    pub last_interest_payment: DateTime<Utc>,
+    pub next_interest_payment: DateTime<Utc>,
+}
+
+// A single installment of a loan, see amortization_schedule
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoanPayment {
+    pub date: DateTime<Utc>,
+    pub payment: Decimal,
+    pub interest: Decimal,
+    pub principal: Decimal,
+    // amount still owed after the payment
+    pub balance: Decimal,
+}
+
+// Loans are paid off within this many installments, or not at all
+const MAX_LOAN_PAYMENTS: usize = 1200;
+
+pub fn is_false(value: &bool) -> bool {
+    !value
+}
+
+// Tags are trimmed, empty tags are dropped
+fn deserialize_tags<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let tags = Option::<Vec<String>>::deserialize(deserializer)?.unwrap_or_default();
+    Ok(tags
+        .into_iter()
+        .map(|tag| tag.trim().to_string())
+        .filter(|tag| !tag.is_empty())
+        .collect())
+}
+
+// The Ticker is a symbol or a list of them, a single one is written as a symbol
+mod listings {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    pub fn serialize<S>(tickers: &[String], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match tickers {
+            [ticker] => serializer.serialize_str(ticker),
+            tickers => tickers.serialize(serializer),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(match Option::<OneOrMany>::deserialize(deserializer)? {
+            Some(OneOrMany::One(ticker)) => vec![ticker],
+            Some(OneOrMany::Many(tickers)) => tickers,
+            None => Vec::new(),
+        })
+    }
+}
+
+// Interest payment dates are stored as plain dates (YYYY-MM-DD) in the JSON file,
+// full RFC 3339 timestamps are accepted as well
+mod date_format {
+    use chrono::{DateTime, NaiveDate, Utc};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(date: &Option<DateTime<Utc>>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match date {
+            Some(date) => serializer.serialize_str(&date.format("%Y-%m-%d").to_string()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let Some(s) = Option::<String>::deserialize(deserializer)? else {
+            return Ok(None);
+        };
+
+        if let Ok(date) = NaiveDate::parse_from_str(&s, "%Y-%m-%d") {
+            return Ok(Some(date.and_hms_opt(0, 0, 0).unwrap().and_utc()));
+        }
+        DateTime::parse_from_rfc3339(&s)
+            .map(|date| Some(date.with_timezone(&Utc)))
+            .map_err(|_| serde::de::Error::custom(format!("invalid date: {s}")))
+    }
+}
+
+// Builds a position in code instead of reading it from a data file. The settings
+// that only make sense together are checked by build, like a data file is by the
+// validate command.
+#[derive(Debug, Clone)]
+pub struct PositionBuilder {
+    position: PortfolioPosition,
+}
+
+impl PositionBuilder {
+    pub fn with_isin(mut self, isin: &str) -> Self {
+        self.position.isin = Some(isin.to_string());
+        self
+    }
+
+    // more listings of the ticker, tried in order after it
+    pub fn with_listing(mut self, ticker: &str) -> Self {
+        self.position.ticker.push(ticker.to_string());
+        self
+    }
+
+    pub fn with_tag(mut self, tag: &str) -> Self {
+        let tag = tag.trim();
+        if !tag.is_empty() {
+            self.position.tags.push(tag.to_string());
+        }
+        self
+    }
+
+    pub fn with_account(mut self, account: &str) -> Self {
+        self.position.account = Some(account.to_string());
+        self
+    }
+
+    pub fn with_institution(mut self, institution: &str) -> Self {
+        self.position.institution = Some(institution.to_string());
+        self
+    }
 
-    #[serde(skip_deserializing)]
-    last_spot: f64,
+    pub fn with_notes(mut self, notes: &str) -> Self {
+        self.position.notes = Some(notes.to_string());
+        self
+    }
+
+    pub fn with_currency(mut self, currency: &str) -> Self {
+        self.position.currency = Some(currency.to_string());
+        self
+    }
+
+    pub fn with_cost_basis(mut self, cost_basis: Decimal) -> Self {
+        self.position.cost_basis = Some(cost_basis);
+        self
+    }
+
+    pub fn with_purchase_date(mut self, date: DateTime<Utc>) -> Self {
+        self.position.purchase_date = Some(date);
+        self
+    }
+
+    pub fn with_manual_price(mut self, price: Decimal, date: Option<DateTime<Utc>>) -> Self {
+        self.position.manual_price = Some(price);
+        self.position.price_date = date;
+        self
+    }
+
+    pub fn with_interest_rate(mut self, rate: Decimal) -> Self {
+        self.position.interest_rate = Some(rate);
+        self
+    }
+
+    pub fn with_payment_frequency_days(mut self, days: u32) -> Self {
+        self.position.payment_frequency_days = Some(days);
+        self
+    }
+
+    pub fn with_next_interest_payment(mut self, date: DateTime<Utc>) -> Self {
+        self.position.next_interest_payment = Some(date);
+        self
+    }
+
+    pub fn with_last_interest_payment(mut self, date: DateTime<Utc>) -> Self {
+        self.position.last_interest_payment = Some(date);
+        self
+    }
+
+    // the installment of a loan
+    pub fn with_payment_amount(mut self, amount: Decimal) -> Self {
+        self.position.payment_amount = Some(amount);
+        self
+    }
+
+    pub fn build(self) -> Result<PortfolioPosition, String> {
+        let position = self.position;
+        let invalid = |message: &str| {
+            Err(format!(
+                "The position {} is not valid: {message}",
+                position.get_name()
+            ))
+        };
+        if position.asset_class.trim().is_empty() {
+            return invalid("the AssetClass is empty");
+        }
+        if position
+            .name
+            .as_deref()
+            .is_some_and(|name| name.trim().is_empty())
+        {
+            return invalid("the Name is empty");
+        }
+        if position
+            .ticker
+            .iter()
+            .any(|ticker| ticker.trim().is_empty())
+        {
+            return invalid("the Ticker is empty");
+        }
+        if position.payment_frequency_days == Some(0) {
+            return invalid("the PaymentFrequencyDays has to be at least 1");
+        }
+
+        let schedule = position.payment_frequency_days.is_some()
+            || position.next_interest_payment.is_some()
+            || position.last_interest_payment.is_some()
+            || position.payment_amount.is_some();
+        match position.interest_rate {
+            None if schedule => {
+                return invalid("the interest payments have no InterestRate");
+            }
+            Some(_)
+                if position.payment_frequency_days.is_none()
+                    || position.next_interest_payment.is_none() =>
+            {
+                return invalid(
+                    "the InterestRate needs a PaymentFrequencyDays and a NextInterestPayment",
+                );
+            }
+            Some(_) if !(position.is_cash() || position.is_liability()) => {
+                return invalid("interest is only paid on Cash or a Liability");
+            }
+            Some(_) if position.is_market_instrument() => {
+                return invalid("interest is not paid on a position priced by its Ticker");
+            }
+            _ => {}
+        }
+        if position.payment_amount.is_some() && !position.is_liability() {
+            return invalid("only a Liability has a PaymentAmount");
+        }
+        if let (Some(next), Some(last)) = (
+            position.next_interest_payment,
+            position.last_interest_payment,
+        ) {
+            if next <= last {
+                return invalid("the NextInterestPayment is not after the LastInterestPayment");
+            }
+        }
+        Ok(position)
+    }
 }
 
 impl PortfolioPosition {
-    fn update_price(&mut self, last_spot: f64) {
-        self.last_spot = last_spot;
+    // A position like one of a data file with these four keys, see builder
+    pub fn new(
+        name: &str,
+        ticker: Option<&str>,
+        asset_class: &str,
+        amount: Decimal,
+    ) -> Result<PortfolioPosition, String> {
+        PortfolioPosition::builder(name, ticker, asset_class, amount).build()
+    }
+
+    // Cash and other positions valued at their amount have no ticker
+    pub fn builder(
+        name: &str,
+        ticker: Option<&str>,
+        asset_class: &str,
+        amount: Decimal,
+    ) -> PositionBuilder {
+        PositionBuilder {
+            position: PortfolioPosition {
+                name: Some(name.to_string()),
+                ticker: ticker.map(str::to_string).into_iter().collect(),
+                asset_class: asset_class.to_string(),
+                amount,
+                ..Default::default()
+            },
+        }
+    }
+
+    fn update_price(&mut self, quote: PriceQuote) {
+        self.last_spot = to_decimal(quote.price);
+        self.last_spot_time = quote.time;
+        self.previous_close = quote.previous_close.map(to_decimal);
     }
 
     pub fn get_name(&self) -> &str {
         if let Some(name) = &self.name {
             name
-        } else if let Some(ticker) = &self.ticker {
+        } else if let Some(ticker) = self.get_ticker() {
             ticker
         } else {
             "Unknown"
         }
     }
 
+    // The Name of the data file, get_name falls back to the ticker
+    pub fn get_given_name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
     pub fn get_asset_class(&self) -> &str {
         &self.asset_class
     }
 
-    pub fn get_balance(&self) -> f64 {
-        if let Some(_ticker) = &self.ticker {
-            self.last_spot * self.amount
+    // Positions with a ticker are priced by the market, unless they have a manual
+    // price. Everything else (cash, but also e.g. a house) is worth its amount.
+    pub fn is_market_instrument(&self) -> bool {
+        (!self.ticker.is_empty() || self.isin.is_some()) && !self.has_manual_price()
+    }
+
+    // Tickers on the watchlist are priced, but not held: they have Watch set,
+    // or a ticker without an Amount (and without a PercentOf to size them)
+    pub fn is_watch_entry(&self) -> bool {
+        self.watch
+            || (!self.ticker.is_empty() && self.amount.is_zero() && self.percent_of.is_none())
+    }
+
+    pub fn has_manual_price(&self) -> bool {
+        self.manual_price.is_some()
+    }
+
+    // A market instrument valued with a simulated price instead of its quote
+    pub fn has_price_override(&self) -> bool {
+        self.is_market_instrument() && self.price_override.is_some()
+    }
+
+    // Price per unit, None for positions that are worth their amount
+    fn unit_price(&self) -> Option<Decimal> {
+        self.manual_price.or(self
+            .is_market_instrument()
+            .then(|| self.price_override.unwrap_or(self.last_spot)))
+    }
+
+    pub fn is_cash(&self) -> bool {
+        self.asset_class.eq_ignore_ascii_case("cash")
+    }
+
+    // Debts like a mortgage or a loan: positions of the asset class "Liability"
+    // (the Amount is what is owed), or cash with a negative Amount
+    pub fn is_liability(&self) -> bool {
+        self.is_liability_class() || self.get_balance() < Decimal::ZERO
+    }
+
+    // Of the asset class "Liability", whatever the sign of the Amount
+    pub fn is_liability_class(&self) -> bool {
+        ["liability", "liabilities"]
+            .iter()
+            .any(|class| self.asset_class.eq_ignore_ascii_case(class))
+    }
+
+    // Crypto by asset class, or by a ticker that looks like a crypto pair (BTC-EUR)
+    pub fn is_crypto(&self) -> bool {
+        self.asset_class.eq_ignore_ascii_case("crypto")
+            || self.get_ticker().is_some_and(is_crypto_ticker)
+    }
+
+    // How far after a date to look for its close, crypto trades on weekends too
+    pub fn historic_price_window(&self) -> chrono::Duration {
+        if self.is_crypto() {
+            chrono::Duration::days(CRYPTO_HISTORIC_WINDOW_DAYS)
         } else {
-            self.amount
+            chrono::Duration::days(HISTORIC_WINDOW_DAYS)
+        }
+    }
+
+    // Liabilities have a negative balance, whatever the sign of their amount
+    pub fn get_balance(&self) -> Decimal {
+        match self.unit_price() {
+            Some(price) => price * self.amount,
+            None if self.is_liability_class() => -self.amount.abs(),
+            None => self.amount,
         }
     }
 
-    pub fn get_amount(&self) -> f64 {
+    pub fn get_isin(&self) -> Option<&str> {
+        self.isin.as_deref()
+    }
+
+    pub fn get_currency(&self) -> Option<&str> {
+        self.currency.as_deref()
+    }
+
+    // The format of the money of the position, in its Currency if it has one
+    pub fn money_format(&self, format: &NumberFormat) -> NumberFormat {
+        match &self.currency {
+            Some(currency) => format.in_currency(currency),
+            None => format.clone(),
+        }
+    }
+
+    // e.g. "£1,200.00" for a position in GBP, the balance itself is not converted
+    pub fn format_balance(&self, format: &NumberFormat) -> String {
+        self.money_format(format).money(self.get_balance())
+    }
+
+    pub fn get_amount(&self) -> Decimal {
         self.amount
     }
 
+    // The symbol the position is priced with, the listing that had a quote
     pub fn get_ticker(&self) -> Option<&str> {
-        self.ticker.as_deref()
+        self.ticker.get(self.listing).map(|ticker| ticker.as_str())
     }
 
-    pub fn get_name_option(&self) -> Option<&str> {
-        self.name.as_deref()
+    // All symbols of the Ticker, in the order they are tried
+    pub fn get_listings(&self) -> &[String] {
+        &self.ticker
     }
 
-    pub fn set_amount(&mut self, amount: f64) {
-        self.amount = amount;
+    // The keys of the position this version doesn't know, see unknown_fields
+    pub fn unknown_keys(&self) -> impl Iterator<Item = &str> {
+        self.extra.keys().map(String::as_str)
     }
-}
 
-pub fn from_string(data: &str) -> Vec<PortfolioPosition> {
-    serde_json::from_str::<Vec<PortfolioPosition>>(data).expect("JSON was not well-formatted")
-}
+    pub fn get_percent_of(&self) -> Option<Decimal> {
+        self.percent_of
+    }
 
-// Get the latest price for a ticker
-async fn get_quote_price(ticker: &str) -> Result<yahoo::YResponse, yahoo::YahooError> {
-    yahoo::YahooConnector::new()?
-        .get_latest_quotes(ticker, "1d")
-        .await
-}
+    pub fn get_cost_basis(&self) -> Option<Decimal> {
+        self.cost_basis
+    }
 
-// get the price at a given date
-pub async fn get_historic_price(
-    ticker: &str,
-    date: DateTime<Utc>,
-) -> Result<yahoo::YResponse, yahoo::YahooError> {
-    let start = OffsetDateTime::from_unix_timestamp(date.timestamp()).unwrap();
+    pub fn get_purchase_date(&self) -> Option<DateTime<Utc>> {
+        self.purchase_date
+    }
 
-    // get a range of 3 days in case the market is closed on the given date
-    let end = start + time::Duration::days(3);
+    // Date since which splits change the amount, None for positions whose
+    // amount doesn't come from the data file or that have no dates
+    pub fn split_reference_date(&self) -> Option<DateTime<Utc>> {
+        if !self.is_market_instrument() || self.amount.is_zero() || self.percent_of.is_some() {
+            return None;
+        }
+        self.last_verified.or(self.purchase_date)
+    }
 
-    yahoo::YahooConnector::new()?
-        .get_quote_history(ticker, start, end)
-        .await
-}
+    // Decimal places the amount is kept to: crypto has more than shares, and cash
+    // or anything else without a ticker is counted in cents
+    pub fn amount_decimals(&self, precision: &AmountPrecision) -> u32 {
+        if self.is_crypto() {
+            precision.crypto
+        } else if !self.ticker.is_empty() {
+            precision.shares
+        } else {
+            precision.cash
+        }
+    }
 
-// Try to get the short name for a ticker from Yahoo Finance
-async fn get_quote_name(ticker: &str) -> Result<String, yahoo::YahooError> {
-    let connector = yahoo::YahooConnector::new();
-    let resp = connector?.search_ticker(ticker).await?;
+    // The amount rounded to its precision, without trailing zeros beyond two decimals,
+    // so 1.0000000004 BTC is 1.00 and 0.12345678 BTC keeps all of its digits
+    pub fn display_amount(&self, format: &NumberFormat) -> String {
+        let decimals = self.amount_decimals(&format.amount_precision);
+        let rounded = round_to(self.amount, decimals).normalize();
+        format.number_with(
+            rounded,
+            rounded.scale().max(decimals.min(MIN_AMOUNT_DECIMALS)),
+        )
+    }
 
-    if let Some(item) = resp.quotes.first() {
-        Ok(item.short_name.clone())
-    } else {
-        Err(yahoo::YahooError::NoResult)
+    // Rounds the amount to its precision, e.g. after a split left a fraction of a share
+    pub fn round_amount(&mut self, precision: &AmountPrecision) {
+        self.amount = round_to(self.amount, self.amount_decimals(precision));
     }
-}
 
-// Get the latest price for a ticker and update the positionthen
-// then return the updated position as a new object
-pub async fn handle_position(
-    position: &mut PortfolioPosition,
-) -> Result<PortfolioPosition, yahoo::YahooError> {
-    if let Some(ticker) = &position.ticker {
-        let quote = get_quote_price(ticker).await?;
-        if let Ok(last_spot) = quote.last_quote() {
-            position.update_price(last_spot.close)
-        } else {
-            // if the market is closed, try to get the last available price
-            if let Ok(last_spot) = quote.quotes() {
-                if let Some(last_spot) = last_spot.last() {
-                    position.update_price(last_spot.close);
-                }
+    // After a split with `factor` new shares per old share the amount grows and
+    // the cost per share shrinks by it. The amount is then right as of `today`.
+    pub fn apply_split_factor(&mut self, factor: Decimal, today: DateTime<Utc>) {
+        if factor.is_zero() {
+            return;
+        }
+        self.amount *= factor;
+        self.cost_basis = self.cost_basis.map(|cost| cost / factor);
+        self.last_verified = Some(today);
+    }
+
+    pub fn get_expense_ratio(&self) -> Option<Decimal> {
+        self.expense_ratio
+    }
+
+    // Fund fees paid per year at the current balance, 0 without an ExpenseRatio
+    pub fn annual_expense_cost(&self) -> Decimal {
+        match self.expense_ratio {
+            Some(ratio) if self.get_balance() > Decimal::ZERO => {
+                self.get_balance() * ratio / Decimal::ONE_HUNDRED
             }
+            _ => Decimal::ZERO,
         }
+    }
 
-        // if no name was provided in the JSON, try to get it from Yahoo Finance
-        if position.name.is_none() {
-            if let Some(ticker) = &position.ticker {
-                let name = get_quote_name(ticker).await?;
-                position.name = Some(name);
+    pub fn get_dividend_yield(&self) -> Option<Decimal> {
+        self.dividend_yield
+    }
+
+    pub fn get_dividends(&self) -> &[IncomeEvent] {
+        &self.dividends
+    }
+
+    pub fn get_interest_payments(&self) -> &[IncomeEvent] {
+        &self.interest_payments
+    }
+
+    // Dividends of the next 12 months at the current balance and DividendYield
+    pub fn projected_dividends(&self) -> Decimal {
+        match self.dividend_yield {
+            Some(dividend_yield) if self.get_balance() > Decimal::ZERO => {
+                self.get_balance() * dividend_yield / Decimal::ONE_HUNDRED
             }
+            _ => Decimal::ZERO,
         }
     }
 
-    Ok(PortfolioPosition {
-        name: position.name.clone(),
-        ticker: position.ticker.to_owned(),
-        asset_class: position.asset_class.to_string(),
-        amount: position.amount,
-        last_spot: position.last_spot,
-    })
-}
+    // Simple interest of the next 12 months on the current amount, 0 for debts
+    pub fn projected_interest(&self) -> Decimal {
+        if !self.is_cash_with_interest() || self.is_liability() {
+            return Decimal::ZERO;
+        }
+        self.amount * self.interest_rate.unwrap_or_default() / Decimal::ONE_HUNDRED
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::fs;
+    // Difference between the current balance and the purchase cost,
+    // None for cash and positions without a cost basis
+    pub fn get_unrealized_gain(&self) -> Option<Decimal> {
+        let price = self.unit_price()?;
+        let cost_basis = self.cost_basis?;
+        Some((price - cost_basis) * self.amount)
+    }
 
-    #[tokio::test]
-    async fn test_get_quote_name() {
-        let name = get_quote_name("AAPL").await.unwrap();
-        assert_eq!(name, "Apple Inc.");
+    // The return from the cost basis to the price as a fraction per year since the
+    // PurchaseDate (the earliest one of the merged lots), None for cash and positions
+    // without a cost basis or a purchase date. Held for less than a day it is the
+    // simple return, annualizing a few hours would blow it up.
+    pub fn annualized_return(&self, as_of: DateTime<Utc>) -> Option<f64> {
+        let price = to_f64(self.unit_price()?);
+        let cost_basis = to_f64(self.cost_basis?);
+        let purchase_date = self.purchase_date?;
+        if cost_basis <= 0.0 {
+            return None;
+        }
+        let growth = price / cost_basis;
+        let days = (as_of - purchase_date).num_days();
+        if days < 1 {
+            return Some(growth - 1.0);
+        }
+        Some(growth.powf(365.0 / days as f64) - 1.0)
+    }
 
-        let name = get_quote_name("BTC-EUR").await.unwrap();
-        assert_eq!(name, "Bitcoin EUR");
+    // Change of the balance since the previous close, None for cash
+    // and if the previous close is unknown
+    pub fn get_day_change(&self) -> Option<Decimal> {
+        if !self.is_market_instrument() {
+            return None;
+        }
+        Some((self.last_spot - self.previous_close?) * self.amount)
     }
 
-    #[tokio::test]
-    async fn test_get_quote_price() {
-        let quote = get_quote_price("AAPL").await.unwrap();
-        assert!(quote.last_quote().unwrap().close > 0.0);
+    // Change of the price per unit since the previous close
+    pub fn get_price_change(&self) -> Option<Decimal> {
+        if !self.is_market_instrument() {
+            return None;
+        }
+        Some(self.last_spot - self.previous_close?)
     }
 
-    #[tokio::test]
-    async fn test_get_historic_price() {
-        let date = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
-        let quote = get_historic_price("AAPL", date).await.unwrap();
-        assert_eq!(
-            quote.quotes().unwrap().last().unwrap().close,
-            74.35749816894531
-        );
+    // Change of the price since the previous close in %
+    pub fn get_day_change_percent(&self) -> Option<Decimal> {
+        if !self.is_market_instrument() {
+            return None;
+        }
+        let previous_close = self.previous_close.filter(|close| !close.is_zero())?;
+        Some((self.last_spot - previous_close) / previous_close * Decimal::ONE_HUNDRED)
     }
 
-    #[tokio::test]
-    async fn test_handle_position() {
-        let mut position = PortfolioPosition {
-            name: None,
-            ticker: Some("AAPL".to_string()),
-            asset_class: "Stock".to_string(),
-            amount: 1.0,
-            last_spot: 0.0,
+    pub fn with_unpriced(mut self) -> Self {
+        self.unpriced = true;
+        self
+    }
+
+    pub fn is_unpriced(&self) -> bool {
+        self.unpriced
+    }
+
+    pub fn get_tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    // true if the position carries all of the given tags, compared trimmed and
+    // case-insensitively
+    pub fn has_tags(&self, tags: &[String]) -> bool {
+        tags.iter()
+            .map(|tag| tag.trim().to_lowercase())
+            .filter(|tag| !tag.is_empty())
+            .all(|tag| self.tags.iter().any(|own| own.trim().to_lowercase() == tag))
+    }
+
+    pub fn get_account(&self) -> Option<&str> {
+        self.account.as_deref()
+    }
+
+    pub fn get_institution(&self) -> Option<&str> {
+        self.institution.as_deref()
+    }
+
+    pub fn get_notes(&self) -> Option<&str> {
+        self.notes.as_deref()
+    }
+
+    pub fn get_portfolio(&self) -> Option<&str> {
+        self.portfolio.as_deref()
+    }
+
+    pub fn set_portfolio(&mut self, portfolio: Option<String>) {
+        self.portfolio = portfolio;
+    }
+
+    // Filled by consolidate for the positions of combined data files
+    pub fn get_portfolio_amounts(&self) -> &[(String, Decimal)] {
+        &self.portfolio_amounts
+    }
+
+    pub fn set_amount(&mut self, amount: Decimal) {
+        self.amount = amount;
+    }
+
+    // Time of the quote, or the PriceDate of a manual price
+    pub fn get_last_spot_time(&self) -> Option<DateTime<Utc>> {
+        if self.has_manual_price() {
+            self.price_date
+        } else {
+            self.last_spot_time
+        }
+    }
+
+    pub fn get_interest_rate(&self) -> Option<Decimal> {
+        self.interest_rate
+    }
+
+    // The NextInterestPayment of the data file, get_next_interest_payment corrects it
+    pub fn get_given_next_interest_payment(&self) -> Option<DateTime<Utc>> {
+        self.next_interest_payment
+    }
+
+    // Any of the keys of interest payments or loan installments is set
+    pub fn has_interest_terms(&self) -> bool {
+        self.interest_rate.is_some()
+            || self.payment_frequency_days.is_some()
+            || self.next_interest_payment.is_some()
+            || self.last_interest_payment.is_some()
+            || self.payment_amount.is_some()
+    }
+
+    // The NextInterestPayment, or one payment frequency after the LastInterestPayment
+    // if it isn't after it. Interest is only computed for periods that start at the
+    // last payment, so a next payment before it would credit nothing and skip ahead.
+    pub fn get_next_interest_payment(&self) -> Option<DateTime<Utc>> {
+        let next = self.next_interest_payment?;
+        match (self.last_interest_payment, self.payment_frequency_days) {
+            (Some(last), Some(days)) if days > 0 && next <= last => {
+                Some(last + chrono::Duration::days(days as i64))
+            }
+            _ => Some(next),
+        }
+    }
+
+    // Payment dates that don't fit together, as warnings for the interest command and
+    // validate. The payments are computed from the corrected dates, see
+    // get_next_interest_payment.
+    pub fn interest_date_warnings(&self, current_date: DateTime<Utc>) -> Vec<String> {
+        let (Some(next), Some(last)) = (self.next_interest_payment, self.last_interest_payment)
+        else {
+            return Vec::new();
         };
+        let date = |date: DateTime<Utc>| date.format("%Y-%m-%d").to_string();
+        let mut warnings = Vec::new();
+        if next <= last {
+            warnings.push(format!(
+                "the NextInterestPayment {} is not after the LastInterestPayment {}, the next payment is expected on {}",
+                date(next),
+                date(last),
+                self.get_next_interest_payment().map(date).unwrap_or_default()
+            ));
+        }
+        if last > current_date {
+            warnings.push(format!(
+                "the LastInterestPayment {} is in the future, no interest is credited until the next payment",
+                date(last)
+            ));
+        }
+        warnings
+    }
 
-        let updated_position = handle_position(&mut position)
-            .await
-            .expect("Error handling position");
-        assert_eq!(updated_position.get_name(), "Apple Inc.");
-        assert_eq!(
-            updated_position.get_balance(),
-            updated_position.get_amount() * updated_position.last_spot
-        );
+    pub fn get_last_interest_payment(&self) -> Option<DateTime<Utc>> {
+        self.last_interest_payment
     }
 
-    #[tokio::test]
-    async fn test_from_file() {
-        let positions_str = fs::read_to_string("example_data.json").unwrap();
-        let positions = from_string(&positions_str);
-        assert_eq!(positions.len(), 6);
+    pub fn get_payment_frequency_days(&self) -> Option<u32> {
+        self.payment_frequency_days
+    }
+
+    // Last quoted or manual price, None for cash positions
+    pub fn get_last_spot(&self) -> Option<Decimal> {
+        self.unit_price()
+    }
+
+    // Cash positions that pay interest need a rate, a payment frequency and a payment date.
+    // A cash fund with a ticker is priced like any other instrument instead. Interest on
+    // a liability is added to the amount owed, so a positive rate grows the debt.
+    pub fn is_cash_with_interest(&self) -> bool {
+        (self.is_cash() || self.is_liability())
+            && !self.is_market_instrument()
+            && !self.is_amortized_loan()
+            && self.interest_rate.is_some()
+            && self.payment_frequency_days.is_some_and(|days| days > 0)
+            && self.next_interest_payment.is_some()
+    }
+
+    // Simple interest earned per day on the current amount
+    pub fn daily_interest_amount(&self) -> Decimal {
+        match self.interest_rate {
+            Some(rate) => self.amount * rate / Decimal::ONE_HUNDRED / Decimal::from(365),
+            None => Decimal::ZERO,
+        }
+    }
+
+    // Simple interest on the current amount between two dates (actual/365)
+    pub fn calculate_interest(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Decimal {
+        let days = (to - from).num_days().max(0);
+        self.daily_interest_amount() * Decimal::from(days)
+    }
+
+    // Start of the current interest period: the last payment, or one payment
+    // frequency before the next payment if there wasn't one yet
+    pub fn interest_period_start(&self) -> Option<DateTime<Utc>> {
+        if !self.is_cash_with_interest() {
+            return None;
+        }
+        let frequency = chrono::Duration::days(self.payment_frequency_days? as i64);
+        self.last_interest_payment
+            .or(Some(self.get_next_interest_payment()? - frequency))
+    }
+
+    // Interest earned since the start of the period up to as_of that has not been
+    // credited yet. Nothing is applied, and nothing has accrued before the period.
+    pub fn accrued_interest(&self, as_of: DateTime<Utc>) -> Decimal {
+        match self.interest_period_start() {
+            Some(since) if as_of > since => self.calculate_interest(since, as_of),
+            _ => Decimal::ZERO,
+        }
+    }
+
+    // The balance as of a date with the accrued interest, what an account would be
+    // worth if it was paid out then. get_balance is the amount credited so far. A
+    // liability owes the interest on top of its amount.
+    pub fn balance_including_accrued(&self, as_of: DateTime<Utc>) -> Decimal {
+        let accrued = self.accrued_interest(as_of);
+        if self.is_liability_class() {
+            -(self.amount + accrued).abs()
+        } else {
+            self.get_balance() + accrued
+        }
+    }
+
+    // Round the amount to the given number of decimal places, e.g. 2 for cents
+    pub fn round_to_cents(&mut self, decimals: u32) {
+        self.amount = round_to(self.amount, decimals);
+    }
+
+    // Interest that is due at current_date without changing the position.
+    // If several payments are overdue, each one is computed on the amount
+    // including the interest of the previous payments. Like a bank, every
+    // payment is rounded to `decimals` places before it is credited, so
+    // repeated runs don't accumulate sub-cent fractions.
+    pub fn preview_interest_due(
+        &self,
+        current_date: DateTime<Utc>,
+        decimals: u32,
+    ) -> Option<InterestPreview> {
+        if !self.is_cash_with_interest() {
+            return None;
+        }
+
+        let frequency = chrono::Duration::days(self.payment_frequency_days? as i64);
+        let mut next_payment = self.get_next_interest_payment()?;
+        if next_payment > current_date {
+            return None;
+        }
+
+        let mut period_start = self
+            .last_interest_payment
+            .unwrap_or(next_payment - frequency);
+        let mut simulated = self.clone();
+        let mut interest = Decimal::ZERO;
+        let mut payments = 0;
+        let mut payments_log = Vec::new();
+
+        while next_payment <= current_date {
+            let period_interest = round_to(
+                simulated.calculate_interest(period_start, next_payment),
+                decimals,
+            );
+            simulated.amount += period_interest;
+            simulated.round_to_cents(decimals);
+            interest += period_interest;
+            payments += 1;
+            payments_log.push(IncomeEvent {
+                date: next_payment.date_naive(),
+                amount: period_interest,
+            });
+
+            period_start = next_payment;
+            next_payment += frequency;
+        }
+
+        Some(InterestPreview {
+            interest: round_to(interest, decimals),
+            payments_log,
+            new_amount: simulated.amount,
+            payments,
+            last_interest_payment: period_start,
+            next_interest_payment: next_payment,
+        })
+    }
+
+    // The balance after each interest payment up to `until`, for a growth schedule.
+    // The payments compound and are rounded like preview_interest_due, the position
+    // isn't changed. Empty for positions without interest.
+    pub fn simulate_interest(
+        &self,
+        until: DateTime<Utc>,
+        decimals: u32,
+    ) -> Vec<(DateTime<Utc>, Decimal)> {
+        let (Some(preview), Some(days), Some(mut date)) = (
+            self.preview_interest_due(until, decimals),
+            self.payment_frequency_days,
+            self.get_next_interest_payment(),
+        ) else {
+            return Vec::new();
+        };
+
+        let mut balance = self.amount;
+        let mut schedule = Vec::new();
+        for payment in &preview.payments_log {
+            balance = round_to(balance + payment.amount, decimals);
+            schedule.push((date, balance));
+            date += chrono::Duration::days(days as i64);
+        }
+        schedule
+    }
+
+    // Credit all interest payments that are due at current_date and
+    // return the interest that was added. The payments on assets are logged
+    // as income, the interest on a debt isn't. A negative rate takes interest
+    // from the amount.
+    pub fn apply_interest_if_due(
+        &mut self,
+        current_date: DateTime<Utc>,
+        decimals: u32,
+    ) -> Option<Decimal> {
+        let preview = self.preview_interest_due(current_date, decimals)?;
+
+        self.amount = preview.new_amount;
+        self.last_interest_payment = Some(preview.last_interest_payment);
+        self.next_interest_payment = Some(preview.next_interest_payment);
+        // a 0% rate only moves the dates, there is nothing to log
+        if !self.is_liability() {
+            self.interest_payments.extend(
+                preview
+                    .payments_log
+                    .into_iter()
+                    .filter(|payment| !payment.amount.is_zero()),
+            );
+        }
+
+        Some(preview.interest)
+    }
+
+    // Liabilities with a PaymentAmount are paid off in installments instead of only
+    // accruing interest. They need a rate, a payment frequency and a payment date.
+    pub fn is_amortized_loan(&self) -> bool {
+        self.is_liability()
+            && !self.is_market_instrument()
+            && self.payment_amount.is_some()
+            && self.interest_rate.is_some()
+            && self.payment_frequency_days.is_some_and(|days| days > 0)
+            && self.next_interest_payment.is_some()
+    }
+
+    // The installments from the next payment on, while the date is <= until (all of
+    // them without a limit). On each date the interest of the period is added, then
+    // the payment is subtracted. Loans quote a nominal annual rate, so every period
+    // gets the same share of it, e.g. a 12th for payments every 30 days. The last
+    // payment only covers what is left, it never pushes the balance below zero.
+    fn loan_payments(&self, decimals: u32, until: Option<DateTime<Utc>>) -> Vec<LoanPayment> {
+        let (Some(payment_amount), Some(rate), Some(days), Some(mut date)) = (
+            self.payment_amount,
+            self.interest_rate,
+            self.payment_frequency_days,
+            self.get_next_interest_payment(),
+        ) else {
+            return Vec::new();
+        };
+        if !self.is_amortized_loan() {
+            return Vec::new();
+        }
+        let periods_per_year = Decimal::from((365.0 / days as f64).round().max(1.0) as u32);
+        let period_rate = rate / Decimal::ONE_HUNDRED / periods_per_year;
+
+        let mut balance = -self.get_balance();
+        let mut payments = Vec::new();
+        while balance > Decimal::ZERO
+            && until.is_none_or(|until| date <= until)
+            && payments.len() < MAX_LOAN_PAYMENTS
+        {
+            let interest = round_to(balance * period_rate, decimals);
+            let payment = payment_amount.min(balance + interest);
+            let principal = payment - interest;
+            balance -= principal;
+            payments.push(LoanPayment {
+                date,
+                payment,
+                interest,
+                principal,
+                balance,
+            });
+            date += chrono::Duration::days(days as i64);
+        }
+        payments
+    }
+
+    // The remaining installments until the loan is paid off, without changing it
+    pub fn amortization_schedule(&self, decimals: u32) -> Result<Vec<LoanPayment>, String> {
+        let schedule = self.loan_payments(decimals, None);
+        match schedule.last() {
+            Some(last) if last.balance > Decimal::ZERO => Err(format!(
+                "{} is not paid off within {MAX_LOAN_PAYMENTS} payments, the PaymentAmount is too low",
+                self.get_name()
+            )),
+            _ => Ok(schedule),
+        }
+    }
+
+    // Make all installments that are due at current_date and return them
+    pub fn apply_loan_payments_if_due(
+        &mut self,
+        current_date: DateTime<Utc>,
+        decimals: u32,
+    ) -> Vec<LoanPayment> {
+        let payments = self.loan_payments(decimals, Some(current_date));
+        let (Some(last), Some(days)) = (payments.last(), self.payment_frequency_days) else {
+            return payments;
+        };
+
+        // the amount keeps the sign it was entered with
+        self.amount = if self.amount < Decimal::ZERO {
+            -last.balance
+        } else {
+            last.balance
+        };
+        self.last_interest_payment = Some(last.date);
+        self.next_interest_payment = Some(last.date + chrono::Duration::days(days as i64));
+        payments
+    }
+
+    // A quote is stale if it is older than max_age, counting only weekday time.
+    // Weekends are skipped so Friday's close is still fresh on Monday morning,
+    // but exchange holidays are not known and count as regular trading days.
+    // Only a dated price (see has_dated_price) can be stale: a quote, or a
+    // ManualPrice with a PriceDate, even without a ticker. A quote without a quote
+    // time always is. A PriceOverride or a ManualPrice without a PriceDate never is.
+    pub fn is_stale(&self, max_age: chrono::Duration) -> bool {
+        self.is_stale_at(max_age, Utc::now())
+    }
+
+    // Market quotes, and manual prices with a PriceDate, can get old.
+    // An overridden price is made up, so it doesn't get old.
+    pub fn has_dated_price(&self) -> bool {
+        (self.is_market_instrument() && !self.has_price_override())
+            || (self.has_manual_price() && self.price_date.is_some())
+    }
+
+    fn is_stale_at(&self, max_age: chrono::Duration, now: DateTime<Utc>) -> bool {
+        if !self.has_dated_price() {
+            return false;
+        }
+
+        match self.get_last_spot_time() {
+            Some(last_spot_time) => weekday_duration(last_spot_time, now) > max_age,
+            None => true,
+        }
+    }
+
+    // The name or one of the tickers, like the commands that take a NAME match it
+    pub fn is_named(&self, name: &str) -> bool {
+        self.get_name().eq_ignore_ascii_case(name)
+            || self
+                .ticker
+                .iter()
+                .any(|ticker| ticker.eq_ignore_ascii_case(name))
+    }
+
+    // The name for the reports, marked if the price is overridden, couldn't be
+    // fetched or the quote is stale
+    pub fn get_marked_name(&self, max_age: chrono::Duration) -> String {
+        if self.has_price_override() {
+            format!("{} {OVERRIDE_MARKER}", self.get_name())
+        } else if self.unpriced {
+            format!("{} {UNPRICED_MARKER}", self.get_name())
+        } else if self.is_stale(max_age) {
+            format!("{} {STALE_MARKER}", self.get_name())
+        } else {
+            self.get_name().to_string()
+        }
+    }
+}
+
+// Time elapsed between two instants, ignoring Saturdays and Sundays
+pub fn weekday_duration(from: DateTime<Utc>, to: DateTime<Utc>) -> chrono::Duration {
+    let mut total = chrono::Duration::zero();
+    let mut cursor = from;
+
+    while cursor < to {
+        let next_midnight = (cursor.date_naive() + chrono::Days::new(1))
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+            .and_utc();
+        let segment_end = next_midnight.min(to);
+
+        if !matches!(cursor.weekday(), Weekday::Sat | Weekday::Sun) {
+            total += segment_end - cursor;
+        }
+        cursor = segment_end;
+    }
+    total
+}
+
+// The keys of a data file in the object form
+pub const DATA_FILE_KEYS: [&str; 4] = ["Name", "TotalInvestment", "Defaults", "Positions"];
+
+// A data file is either a list of positions, or an object with the positions,
+// the TotalInvestment their PercentOf refers to, the Name of the portfolio and the
+// Defaults of its cash positions
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+enum PortfolioData {
+    Positions(Vec<PortfolioPosition>),
+    #[serde(rename_all = "PascalCase")]
+    Object {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        total_investment: Option<Decimal>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        defaults: Option<serde_json::Map<String, serde_json::Value>>,
+        positions: Vec<PortfolioPosition>,
+    },
+}
+
+fn parse_data(data: &str) -> PortfolioData {
+    try_parse_data(data).expect("JSON was not well-formatted")
+}
+
+fn try_parse_data(data: &str) -> Result<PortfolioData, String> {
+    let mut value: serde_json::Value =
+        serde_json::from_str(data).map_err(|e| format!("The data is not valid JSON: {e}"))?;
+    apply_defaults(&mut value);
+    serde_json::from_value::<PortfolioData>(value)
+        .map_err(|e| format!("The data has no valid positions: {e}"))
+}
+
+// Fills the keys of the Defaults into the cash positions that don't have them,
+// the values of a position always win
+pub fn apply_defaults(data: &mut serde_json::Value) {
+    let Some(defaults) = defaults_of(data) else {
+        return;
+    };
+    if let Some(serde_json::Value::Array(positions)) = data.get_mut("Positions") {
+        for position in positions.iter_mut().filter_map(|p| p.as_object_mut()) {
+            if is_cash_object(position) {
+                for (key, default) in &defaults {
+                    position
+                        .entry(key.clone())
+                        .or_insert_with(|| default.clone());
+                }
+            }
+        }
+    }
+}
+
+fn defaults_of(data: &serde_json::Value) -> Option<serde_json::Map<String, serde_json::Value>> {
+    match data.get("Defaults") {
+        Some(serde_json::Value::Object(defaults)) => Some(defaults.clone()),
+        _ => None,
+    }
+}
+
+// The Defaults apply to the positions of the asset class Cash, like is_cash
+fn is_cash_object(position: &serde_json::Map<String, serde_json::Value>) -> bool {
+    position
+        .get("AssetClass")
+        .and_then(|class| class.as_str())
+        .is_some_and(|class| class.eq_ignore_ascii_case("cash"))
+}
+
+// The same value, a number in the data file may be written as 2 or 2.0
+fn same_value(a: &serde_json::Value, b: &serde_json::Value) -> bool {
+    match (a.as_f64(), b.as_f64()) {
+        (Some(a), Some(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+pub fn from_string(data: &str) -> Vec<PortfolioPosition> {
+    match parse_data(data) {
+        PortfolioData::Positions(positions) => positions,
+        PortfolioData::Object { positions, .. } => positions,
+    }
+}
+
+// from_string for data that may not be valid, e.g. read by a library user
+pub fn try_from_string(data: &str) -> Result<Vec<PortfolioPosition>, String> {
+    match try_parse_data(data)? {
+        PortfolioData::Positions(positions) => Ok(positions),
+        PortfolioData::Object { positions, .. } => Ok(positions),
+    }
+}
+
+// The columns of a brokerage CSV export that hold the fields of a position, by their
+// header (of any case), see import_csv. The other columns of the export are ignored.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CsvColumns {
+    pub symbol: String,
+    pub quantity: String,
+    // the purchase price per unit, the CostBasis
+    pub cost: String,
+    pub asset_class: String,
+}
+
+impl Default for CsvColumns {
+    fn default() -> Self {
+        Self {
+            symbol: "Symbol".to_string(),
+            quantity: "Quantity".to_string(),
+            cost: "Cost Basis".to_string(),
+            asset_class: "Asset Class".to_string(),
+        }
+    }
+}
+
+// The TotalInvestment of a data file in the object form
+pub fn total_investment(data: &str) -> Option<Decimal> {
+    match parse_data(data) {
+        PortfolioData::Positions(_) => None,
+        PortfolioData::Object {
+            total_investment, ..
+        } => total_investment,
+    }
+}
+
+// The keys every cash position of a data file in the object form gets unless it
+// has them itself, e.g. a PaymentFrequencyDays shared by all accounts. from_string
+// merges them into the positions.
+pub fn position_defaults(data: &str) -> Option<serde_json::Map<String, serde_json::Value>> {
+    serde_json::from_str(data)
+        .ok()
+        .as_ref()
+        .and_then(defaults_of)
+}
+
+// The Name of a data file in the object form
+pub fn portfolio_name(data: &str) -> Option<String> {
+    match parse_data(data) {
+        PortfolioData::Positions(_) => None,
+        PortfolioData::Object { name, .. } => name,
+    }
+}
+
+// The keys of the data file this version doesn't know, "Position: Key" for those
+// of a position. They are kept, but often a typo, e.g. Amout for Amount.
+pub fn unknown_fields(data: &str) -> Vec<String> {
+    let mut unknown: Vec<String> = match serde_json::from_str(data) {
+        Ok(serde_json::Value::Object(object)) => object
+            .keys()
+            .filter(|key| !DATA_FILE_KEYS.contains(&key.as_str()))
+            .cloned()
+            .collect(),
+        _ => Vec::new(),
+    };
+    for position in from_string(data) {
+        for key in position.unknown_keys() {
+            unknown.push(format!("{}: {key}", position.get_name()));
+        }
+    }
+    unknown
+}
+
+// --strict rejects the data if it has keys that are not known
+pub fn check_strict(data: &str) -> Result<(), String> {
+    match unknown_fields(data).as_slice() {
+        [] => Ok(()),
+        unknown => Err(format!(
+            "Unknown fields in the data file: {} (they are kept without --strict)",
+            unknown.join(", ")
+        )),
+    }
+}
+
+pub fn to_string(positions: &[PortfolioPosition]) -> Result<String, String> {
+    serde_json::to_string_pretty(positions).map_err(|e| format!("Failed to serialize data: {e}"))
+}
+
+// Write the object form if there is a Name, a TotalInvestment or Defaults, so they
+// are kept when saving, and the plain list otherwise. The keys of a cash position
+// with the value of the Defaults are left out, they come from the Defaults again.
+pub fn to_data_string(
+    positions: &[PortfolioPosition],
+    name: Option<&str>,
+    total_investment: Option<Decimal>,
+    defaults: Option<&serde_json::Map<String, serde_json::Value>>,
+) -> Result<String, String> {
+    if name.is_none() && total_investment.is_none() && defaults.is_none() {
+        return to_string(positions);
+    }
+    let data = PortfolioData::Object {
+        name: name.map(str::to_string),
+        total_investment,
+        defaults: defaults.cloned(),
+        positions: positions.to_vec(),
+    };
+    let mut value =
+        serde_json::to_value(&data).map_err(|e| format!("Failed to serialize data: {e}"))?;
+    if let (Some(defaults), Some(serde_json::Value::Array(positions))) =
+        (defaults, value.get_mut("Positions"))
+    {
+        for position in positions.iter_mut().filter_map(|p| p.as_object_mut()) {
+            if is_cash_object(position) {
+                position.retain(|key, value| {
+                    !defaults
+                        .get(key)
+                        .is_some_and(|default| same_value(value, default))
+                });
+            }
+        }
+    }
+    serde_json::to_string_pretty(&value).map_err(|e| format!("Failed to serialize data: {e}"))
+}
+
+// Merges the held positions sharing a ticker (in any case) into the first of them,
+// see merge_into. The cost basis is dropped if one of them has none, as the gain
+// would be wrong, and the account or the portfolio if they differ, the amount of
+// every portfolio is kept in the portfolio amounts. Positions with a different
+// PriceOverride stay apart. Positions without a ticker are left distinct even with
+// the same name and asset class, two savings accounts can have their own interest
+// schedules. So are the positions sized by PercentOf, manually priced ones and the
+// watchlist.
+pub fn consolidate(positions: Vec<PortfolioPosition>) -> Vec<PortfolioPosition> {
+    let mut consolidated: Vec<PortfolioPosition> = Vec::new();
+    let mergeable = |position: &PortfolioPosition| {
+        position.is_market_instrument()
+            && !position.is_watch_entry()
+            && position.percent_of.is_none()
+    };
+
+    for position in positions {
+        let existing = mergeable(&position)
+            .then(|| {
+                consolidated.iter_mut().find(|other| {
+                    mergeable(other)
+                        && other.get_ticker().map(|t| t.to_uppercase())
+                            == position.get_ticker().map(|t| t.to_uppercase())
+                        && other.price_override == position.price_override
+                })
+            })
+            .flatten();
+        match existing {
+            Some(merged) => merge_into(merged, position),
+            None => consolidated.push(merge_target(position)),
+        }
+    }
+    consolidated
+}
+
+// A position the ones after it are merged into, its amount is the first of its portfolio
+fn merge_target(mut position: PortfolioPosition) -> PortfolioPosition {
+    position.portfolio_amounts = position
+        .portfolio
+        .iter()
+        .map(|portfolio| (portfolio.clone(), position.amount))
+        .collect();
+    position
+}
+
+// Adds a position to the one it is merged into: the amounts, dividends and interest
+// payments are added up, the cost basis is weighted by the amounts and the tags and
+// notes are combined. A field only one of them has is kept, an account or portfolio
+// that differs is dropped, like a cost basis that only one of them has.
+fn merge_into(merged: &mut PortfolioPosition, position: PortfolioPosition) {
+    let amount = merged.amount + position.amount;
+    merged.cost_basis = match (merged.cost_basis, position.cost_basis) {
+        (Some(a), Some(b)) if !amount.is_zero() => {
+            Some((a * merged.amount + b * position.amount) / amount)
+        }
+        _ => None,
+    };
+    merged.amount = amount;
+    for tag in position.tags {
+        if !merged.tags.contains(&tag) {
+            merged.tags.push(tag);
+        }
+    }
+    merged.notes = match (merged.notes.take(), position.notes) {
+        (Some(a), Some(b)) if a != b => Some(format!("{a}; {b}")),
+        (a, b) => a.or(b),
+    };
+    if merged.account != position.account {
+        merged.account = None;
+    }
+    if let Some(portfolio) = &position.portfolio {
+        match merged
+            .portfolio_amounts
+            .iter_mut()
+            .find(|(name, _)| name == portfolio)
+        {
+            Some((_, amount)) => *amount += position.amount,
+            None => merged
+                .portfolio_amounts
+                .push((portfolio.clone(), position.amount)),
+        }
+    }
+    if merged.portfolio != position.portfolio {
+        merged.portfolio = None;
+    }
+    // the earliest lot, and the amount is only verified on the earlier date
+    merged.purchase_date = match (merged.purchase_date, position.purchase_date) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    };
+    merged.last_verified = match (merged.last_verified, position.last_verified) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    };
+    merged.isin = merged.isin.take().or(position.isin);
+    merged.expense_ratio = merged.expense_ratio.or(position.expense_ratio);
+    merged.dividend_yield = merged.dividend_yield.or(position.dividend_yield);
+    merged.price_date = merged.price_date.max(position.price_date);
+    merged.dividends.extend(position.dividends);
+    merged.dividends.sort_by_key(|event| event.date);
+    merged.interest_payments.extend(position.interest_payments);
+    merged.interest_payments.sort_by_key(|event| event.date);
+    for (key, value) in position.extra {
+        merged.extra.entry(key).or_insert(value);
+    }
+}
+
+// Positions of the same instrument that were likely meant to be one, e.g. after an
+// import from two brokers: the same ticker in any case, the same ISIN, or the same
+// name if neither has a ticker or ISIN. Positions in two accounts are held apart
+// on purpose, like those of another portfolio and the watchlist.
+pub fn is_duplicate_of(position: &PortfolioPosition, other: &PortfolioPosition) -> bool {
+    let same = |a: &str, b: &str| a.trim().eq_ignore_ascii_case(b.trim());
+    let same_instrument = match (position.ticker.first(), other.ticker.first()) {
+        (Some(a), Some(b)) => same(a, b),
+        (None, None) => match (&position.isin, &other.isin) {
+            (Some(a), Some(b)) => same(a, b),
+            (None, None) => same(position.get_name(), other.get_name()),
+            _ => false,
+        },
+        _ => false,
+    };
+    let held_apart =
+        position.account.is_some() && other.account.is_some() && position.account != other.account;
+    same_instrument
+        && !held_apart
+        && position.portfolio == other.portfolio
+        && !position.is_watch_entry()
+        && !other.is_watch_entry()
+}
+
+// What keeps two duplicates from being merged into one position without losing
+// or changing what the data file says
+fn merge_conflict(
+    merged: &PortfolioPosition,
+    position: &PortfolioPosition,
+) -> Option<&'static str> {
+    let interest = |p: &PortfolioPosition| {
+        (
+            p.interest_rate,
+            p.payment_frequency_days,
+            p.next_interest_payment,
+            p.last_interest_payment,
+            p.payment_amount,
+        )
+    };
+    if merged.account != position.account {
+        Some("they are in different Accounts")
+    } else if merged.institution != position.institution {
+        Some("they are at different Institutions")
+    } else if !merged
+        .asset_class
+        .eq_ignore_ascii_case(&position.asset_class)
+    {
+        Some("their AssetClass differs")
+    } else if interest(merged) != interest(position) {
+        Some("their interest settings differ")
+    } else if merged.manual_price != position.manual_price
+        || merged.price_override != position.price_override
+    {
+        Some("their ManualPrice or PriceOverride differs")
+    } else if merged.currency != position.currency {
+        Some("their Currency differs")
+    } else if merged.percent_of.is_some() || position.percent_of.is_some() {
+        Some("a position sized by PercentOf can't be merged")
+    } else if merged.cost_basis.is_some() != position.cost_basis.is_some() {
+        Some("only one of them has a CostBasis")
+    } else {
+        None
+    }
+}
+
+// Merges the duplicates (see is_duplicate_of) into the first of them with
+// merge_into, to save them. Unlike consolidate the data of the file is never
+// dropped, so duplicates with a different account, interest settings or another
+// field only one of them can keep are not merged, it fails instead.
+pub fn merge_duplicates(
+    positions: Vec<PortfolioPosition>,
+) -> Result<Vec<PortfolioPosition>, String> {
+    let mut merged_positions: Vec<PortfolioPosition> = Vec::new();
+
+    for position in positions {
+        let Some(merged) = merged_positions
+            .iter_mut()
+            .find(|merged| is_duplicate_of(merged, &position))
+        else {
+            merged_positions.push(merge_target(position));
+            continue;
+        };
+        if let Some(reason) = merge_conflict(merged, &position) {
+            return Err(format!(
+                "Can't merge {} and {}, {reason}",
+                merged.get_name(),
+                position.get_name()
+            ));
+        }
+        merge_into(merged, position);
+    }
+    Ok(merged_positions)
+}
+
+// Asset classes that are priced by a quote, a position of them needs a Ticker
+// (or a ManualPrice if it isn't listed)
+const QUOTED_ASSET_CLASSES: [&str; 10] = [
+    "stocks", "stock", "equities", "equity", "etf", "etfs", "bonds", "bond", "funds", "crypto",
+];
+
+// The fields each position is missing for the reports, by the name in the data file.
+// A stock or crypto position can't be priced without a Ticker, interest (and the
+// installments of a loan) can't be scheduled without the payment dates, and a
+// ManualPrice without its PriceDate is never marked as stale. Positions that have
+// everything are left out, the rest is sorted by name.
+pub fn missing_data_report(positions: &[PortfolioPosition]) -> Vec<(String, Vec<&'static str>)> {
+    let mut report: Vec<(String, Vec<&'static str>)> = positions
+        .iter()
+        .filter_map(|position| {
+            let mut missing = Vec::new();
+            let quoted = QUOTED_ASSET_CLASSES
+                .iter()
+                .any(|class| position.asset_class.eq_ignore_ascii_case(class));
+            if quoted
+                && position.ticker.is_empty()
+                && position.isin.is_none()
+                && !position.has_manual_price()
+            {
+                missing.push("Ticker");
+            }
+            if position.has_manual_price() && position.price_date.is_none() {
+                missing.push("PriceDate");
+            }
+            if position.payment_amount.is_some() && position.interest_rate.is_none() {
+                missing.push("InterestRate");
+            }
+            if position.interest_rate.is_some() || position.payment_amount.is_some() {
+                if position.next_interest_payment.is_none() {
+                    missing.push("NextInterestPayment");
+                }
+                if position.payment_frequency_days.is_none_or(|days| days == 0) {
+                    missing.push("PaymentFrequencyDays");
+                }
+            }
+            (!missing.is_empty()).then(|| (position.get_name().to_string(), missing))
+        })
+        .collect();
+    report.sort_by(|a, b| a.0.cmp(&b.0));
+    report
+}
+
+// Seconds a quote request may take before it fails, unless the config file sets them
+pub const DEFAULT_REQUEST_TIMEOUT_SECONDS: u64 = 10;
+
+// Timeout and proxy of the HTTP client of the connectors and the rate limit of
+// all their requests. Without a proxy the HTTPS_PROXY, HTTP_PROXY, ALL_PROXY and
+// NO_PROXY variables are honored.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetworkSettings {
+    pub timeout: std::time::Duration,
+    pub proxy: Option<String>,
+    pub requests_per_second: f64,
+}
+
+impl Default for NetworkSettings {
+    fn default() -> Self {
+        Self {
+            timeout: std::time::Duration::from_secs(DEFAULT_REQUEST_TIMEOUT_SECONDS),
+            proxy: None,
+            requests_per_second: DEFAULT_REQUESTS_PER_SECOND,
+        }
+    }
+}
+
+// The Yahoo Finance provider the commands price with, without its type and with
+// the default network settings
+pub fn yahoo_provider() -> Result<impl PriceProvider + Sync, String> {
+    connector_with(&NetworkSettings::default())
+}
+
+// Create a connector to share between all quote requests, so the
+// HTTP client and its connections are reused
+pub fn connector_with(settings: &NetworkSettings) -> Result<yahoo::YahooConnector, String> {
+    connector_for(client_builder(settings)?)
+}
+
+// Yahoo rejects requests without the user agent of a browser
+const USER_AGENT: &str = "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/122.0.0.0 Safari/537.36";
+
+// The HTTP client of a connector like YahooConnector::builder sets it up, the
+// tests point it at a local server before the connector is created
+pub fn client_builder(settings: &NetworkSettings) -> Result<reqwest::ClientBuilder, String> {
+    let mut builder = reqwest::Client::builder()
+        .timeout(settings.timeout)
+        .user_agent(USER_AGENT)
+        .use_rustls_tls();
+    if let Some(proxy) = &settings.proxy {
+        builder = builder.proxy(proxy_for(proxy).map_err(|e| connector_error(&e))?);
+    }
+    Ok(builder)
+}
+
+pub fn connector_for(builder: reqwest::ClientBuilder) -> Result<yahoo::YahooConnector, String> {
+    let client = builder.build().map_err(|e| connector_error(&e))?;
+    yahoo::YahooConnectorBuilder::build_with_client(client).map_err(|e| connector_error(&e))
+}
+
+fn connector_error(e: &dyn std::fmt::Display) -> String {
+    format!("Error creating the Yahoo connector: {e}")
+}
+
+// A proxy for all requests, e.g. http://proxy.example.com:3128
+pub fn proxy_for(url: &str) -> Result<reqwest::Proxy, String> {
+    reqwest::Proxy::all(url).map_err(|e| format!("Invalid proxy {url}: {e}"))
+}
+
+// Intervals and ranges accepted by the Yahoo Finance chart API
+pub const VALID_INTERVALS: [&str; 13] = [
+    "1m", "2m", "5m", "15m", "30m", "60m", "90m", "1h", "1d", "5d", "1wk", "1mo", "3mo",
+];
+pub const VALID_RANGES: [&str; 11] = [
+    "1d", "5d", "1mo", "3mo", "6mo", "1y", "2y", "5y", "10y", "ytd", "max",
+];
+
+// Check an interval/range combination before sending it to Yahoo. Intraday data
+// is only available for a limited period: 1m for the last 7 days, up to 90m for
+// the last 60 days and hourly data for the last 2 years.
+pub fn validate_range_query(interval: &str, range: &str) -> Result<(), String> {
+    if !VALID_INTERVALS.contains(&interval) {
+        return Err(format!(
+            "Invalid interval '{interval}', expected one of: {}",
+            VALID_INTERVALS.join(", ")
+        ));
+    }
+    if !VALID_RANGES.contains(&range) {
+        return Err(format!(
+            "Invalid range '{range}', expected one of: {}",
+            VALID_RANGES.join(", ")
+        ));
+    }
+
+    let supported_ranges: &[&str] = match interval {
+        "1m" => &["1d", "5d"],
+        "2m" | "5m" | "15m" | "30m" | "90m" => &["1d", "5d", "1mo"],
+        "60m" | "1h" => &["1d", "5d", "1mo", "3mo", "6mo", "1y", "2y", "ytd"],
+        _ => &VALID_RANGES,
+    };
+    if !supported_ranges.contains(&range) {
+        return Err(format!(
+            "Interval '{interval}' is not available for range '{range}', supported ranges: {}",
+            supported_ranges.join(", ")
+        ));
+    }
+    Ok(())
+}
+
+// Get all quotes of a ticker for a range (e.g. 1y) in the given interval (e.g. 1wk)
+pub async fn get_quote_history_range(
+    connector: &yahoo::YahooConnector,
+    ticker: &str,
+    interval: &str,
+    range: &str,
+) -> Result<Vec<yahoo::Quote>, String> {
+    validate_range_query(interval, range)?;
+
+    let response = rate_limit::shared()
+        .send(|| connector.get_quote_range(ticker, interval, range))
+        .await
+        .map_err(|e| format!("Error getting quotes for {ticker}: {e}"))?;
+
+    response
+        .quotes()
+        .map_err(|e| format!("Error getting quotes for {ticker}: {e}"))
+}
+
+// Get the latest price for a ticker and update the position,
+// then return the updated position as a new object. The name of a position
+// without a Name is looked up if the provider looks up names.
+pub async fn handle_position(
+    provider: &impl PriceProvider,
+    position: &mut PortfolioPosition,
+) -> Result<PortfolioPosition, QuoteError> {
+    price_position(provider, position, provider.looks_up_names()).await
+}
+
+// handle_position, looking up the names of positions without a Name only with
+// fetch_names. Positions with a Name never need the extra request.
+pub async fn price_position(
+    provider: &impl PriceProvider,
+    position: &mut PortfolioPosition,
+    fetch_names: bool,
+) -> Result<PortfolioPosition, QuoteError> {
+    if position.has_manual_price() {
+        log::debug!("{} is valued with its ManualPrice", position.get_name());
+        if let Some(ticker) = position.get_ticker() {
+            log::warn!(
+                "{} has a ManualPrice, the quote for {ticker} is not fetched",
+                position.get_name()
+            );
+        }
+        return Ok(position.clone());
+    }
+
+    // what-if prices are used as they are, there is no quote to compare them with
+    if let Some(price) = position
+        .price_override
+        .filter(|_| !position.ticker.is_empty())
+    {
+        log::info!(
+            "{} is valued with its PriceOverride of {price} instead of a quote",
+            position.get_name()
+        );
+        position.last_spot = price;
+        position.last_spot_time = None;
+        position.previous_close = None;
+        return Ok(position.clone());
+    }
+
+    if position.ticker.is_empty() {
+        if let Some(isin) = position.isin.clone() {
+            match resolve_isin(provider, &isin, position.get_name()).await {
+                Some(ticker) => position.ticker = vec![ticker],
+                // unpriced like a ticker without a quote, not valued at its amount
+                None => {
+                    return Err(QuoteError::new(
+                        ErrorKind::NotFound,
+                        format!("no listing found for ISIN {isin}"),
+                    ))
+                }
+            }
+        }
+    }
+
+    if !position.ticker.is_empty() {
+        let quote = latest_listing_price(provider, position).await?;
+        position.update_price(quote);
+        let ticker = position.get_ticker().unwrap_or_default().to_string();
+
+        // if no name was provided in the JSON, try to get it from the provider. The
+        // position has a price, so without a name it is shown by its ticker.
+        if position.name.is_none() && !fetch_names {
+            log::debug!("{ticker} has no Name, showing the ticker without looking it up");
+        } else if position.name.is_none() {
+            log::debug!("{ticker} has no Name, fetching it");
+            match provider.get_name(&ticker).await {
+                Ok(name) => position.name = Some(name),
+                Err(e) => log::warn!("No name found for {ticker}, showing the ticker: {e}"),
+            }
+        }
+    }
+
+    Ok(position.clone())
+}
+
+// The quote of the first listing of the Ticker that has one, the position is
+// priced with it from then on. If none has, the error names every attempt.
+async fn latest_listing_price(
+    provider: &impl PriceProvider,
+    position: &mut PortfolioPosition,
+) -> Result<PriceQuote, QuoteError> {
+    let tickers = position.ticker.clone();
+    let mut errors = Vec::new();
+    for (listing, ticker) in tickers.iter().enumerate() {
+        match provider.get_latest_price(ticker).await {
+            Ok(quote) => {
+                if listing > 0 {
+                    log::info!(
+                        "{} is priced with {ticker}, there is no quote for {}",
+                        position.get_name(),
+                        tickers[..listing].join(", ")
+                    );
+                }
+                position.listing = listing;
+                return Ok(quote);
+            }
+            Err(e) => errors.push((ticker, e)),
+        }
+    }
+    if errors.len() == 1 {
+        return Err(errors.remove(0).1);
+    }
+    let attempts: Vec<String> = errors
+        .iter()
+        .map(|(ticker, e)| format!("{ticker}: {e}"))
+        .collect();
+    // trying again helps if it helps with one of the listings
+    let kind = errors
+        .iter()
+        .map(|(_, e)| e.kind)
+        .find(|kind| *kind != ErrorKind::NotFound)
+        .unwrap_or(ErrorKind::NotFound);
+    Err(QuoteError::new(
+        kind,
+        format!(
+            "no listing of {} has a quote ({})",
+            position.get_name(),
+            attempts.join("; ")
+        ),
+    ))
+}
+
+// The symbol of an ISIN, the first one if the search finds several. A failed or
+// empty search is a warning, the position is shown without a price.
+async fn resolve_isin(provider: &impl PriceProvider, isin: &str, name: &str) -> Option<String> {
+    let symbols = match provider.find_symbols(isin).await {
+        Ok(symbols) => symbols,
+        Err(e) => {
+            log::warn!("The symbol of the ISIN {isin} of {name} can't be looked up: {e}");
+            return None;
+        }
+    };
+    match symbols.as_slice() {
+        [] => {
+            log::warn!("No symbol found for the ISIN {isin} of {name}, it is not priced");
+            None
+        }
+        [symbol] => {
+            log::info!("The ISIN {isin} of {name} is {symbol}");
+            Some(symbol.clone())
+        }
+        [symbol, ..] => {
+            log::warn!(
+                "The ISIN {isin} of {name} matches {}, using {symbol}. Add the Ticker to choose another one.",
+                symbols.join(", ")
+            );
+            Some(symbol.clone())
+        }
+    }
+}
+
+// Price all positions concurrently, in the order of the input. on_progress is called
+// with (done, total) each time a position completes, exactly once per position,
+// so a caller can render a progress bar.
+pub async fn handle_positions(
+    provider: &impl PriceProvider,
+    positions: Vec<PortfolioPosition>,
+    on_progress: impl Fn(usize, usize) + Sync,
+) -> Vec<Result<PortfolioPosition, QuoteError>> {
+    use futures::future::join_all;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let total = positions.len();
+    let done = AtomicUsize::new(0);
+
+    let tasks = positions.into_iter().map(|mut position| {
+        let done = &done;
+        let on_progress = &on_progress;
+        async move {
+            let result = handle_position(provider, &mut position).await;
+            on_progress(done.fetch_add(1, Ordering::SeqCst) + 1, total);
+            result
+        }
+    });
+
+    join_all(tasks).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_yahoo::MockYahoo;
+    use crate::provider::{CachedProvider, MockProvider, StoredNames};
+    use rust_decimal::dec;
+    use std::fs;
+
+    trait WithLastSpot {
+        fn with_last_spot(self, last_spot: f64) -> Self;
+    }
+
+    // a position as if it had been quoted at the price, without a quote time
+    impl WithLastSpot for PortfolioPosition {
+        fn with_last_spot(mut self, last_spot: f64) -> Self {
+            self.last_spot = to_decimal(last_spot);
+            self
+        }
+    }
+
+    #[test]
+    fn test_validate_range_query() {
+        assert!(validate_range_query("1d", "1mo").is_ok());
+        assert!(validate_range_query("1wk", "1y").is_ok());
+        assert!(validate_range_query("1m", "5d").is_ok());
+
+        let err = validate_range_query("3d", "1y").unwrap_err();
+        assert!(err.starts_with("Invalid interval '3d'"));
+        let err = validate_range_query("1d", "4y").unwrap_err();
+        assert!(err.starts_with("Invalid range '4y'"));
+        let err = validate_range_query("1m", "1y").unwrap_err();
+        assert_eq!(
+            err,
+            "Interval '1m' is not available for range '1y', supported ranges: 1d, 5d"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_request_timeout() {
+        // 10.255.255.1 is not routed, a connection to the proxy never gets an answer
+        let Ok(connector) = connector_with(&NetworkSettings {
+            timeout: std::time::Duration::from_secs(1),
+            proxy: Some("http://10.255.255.1:3128".to_string()),
+            ..Default::default()
+        }) else {
+            panic!("the connector wasn't created");
+        };
+        let start = std::time::Instant::now();
+        let error = connector.get_latest_price("AAPL").await.unwrap_err();
+        assert!(start.elapsed() < std::time::Duration::from_secs(5));
+        // a sandbox without a route fails before the timeout
+        assert!(matches!(
+            error.kind,
+            ErrorKind::Timeout | ErrorKind::Network
+        ));
+
+        // a proxy that accepts the connection but never answers runs into the timeout
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                held.push(socket);
+            }
+        });
+        let Ok(connector) = connector_with(&NetworkSettings {
+            timeout: std::time::Duration::from_secs(1),
+            proxy: Some(format!("http://{address}")),
+            ..Default::default()
+        }) else {
+            panic!("the connector wasn't created");
+        };
+        let start = std::time::Instant::now();
+        let error = connector.get_latest_price("AAPL").await.unwrap_err();
+        let elapsed = start.elapsed();
+        assert!(elapsed >= std::time::Duration::from_secs(1));
+        assert!(elapsed < std::time::Duration::from_secs(5));
+        assert_eq!(error.kind, ErrorKind::Timeout);
+
+        let Err(invalid) = connector_with(&NetworkSettings {
+            proxy: Some("not a url".to_string()),
+            ..Default::default()
+        }) else {
+            panic!("a proxy that isn't a URL was accepted");
+        };
+        assert!(invalid.starts_with("Error creating the Yahoo connector: Invalid proxy not a url"));
+    }
+
+    #[tokio::test]
+    async fn test_get_quote_history_range_invalid() {
+        let result = get_quote_history_range(
+            &connector_with(&NetworkSettings::default()).unwrap(),
+            "AAPL",
+            "5m",
+            "max",
+        )
+        .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_get_quote_history_range() {
+        let yahoo = MockYahoo::aapl().await;
+        let quotes = get_quote_history_range(&yahoo.connector(), "AAPL", "1d", "1mo")
+            .await
+            .unwrap();
+        // the bar without trades is left out
+        assert_eq!(quotes.len(), 5);
+        assert_eq!(quotes[4].close, 235.0);
+        assert!(yahoo.requests("/v8/finance/chart/AAPL")[0].contains("interval=1d&range=1mo"));
+
+        let error = get_quote_history_range(&yahoo.connector(), "GONE", "1d", "1mo")
+            .await
+            .unwrap_err();
+        assert!(error.starts_with("Error getting quotes for GONE"));
+    }
+
+    #[tokio::test]
+    async fn test_handle_position() {
+        let yahoo = MockYahoo::aapl().await;
+        let mut position = PortfolioPosition {
+            ticker: vec!["AAPL".to_string()],
+            asset_class: "Stock".to_string(),
+            amount: dec!(2),
+            ..Default::default()
+        };
+
+        let updated_position = handle_position(&yahoo.connector(), &mut position)
+            .await
+            .expect("Error handling position");
+        // the name is looked up by a search for the ticker
+        assert_eq!(updated_position.get_name(), "Apple Inc.");
+        assert_eq!(yahoo.requests("/v1/finance/search").len(), 1);
+        assert_eq!(updated_position.get_balance(), dec!(470));
+        assert_eq!(
+            updated_position.get_last_spot_time(),
+            Some(Utc.with_ymd_and_hms(2024, 10, 18, 13, 30, 0).unwrap())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_price_position_without_names() {
+        let yahoo = MockYahoo::aapl().await;
+        let positions = from_string(
+            r#"[
+                {"Ticker": "AAPL", "AssetClass": "Stocks", "Amount": 1},
+                {"Name": "Apple", "Ticker": "AAPL", "AssetClass": "Stocks", "Amount": 1}
+            ]"#,
+        );
+
+        // priced, but shown by the ticker without a search for the name
+        let priced = price_position(&yahoo.connector(), &mut positions[0].clone(), false)
+            .await
+            .unwrap();
+        assert_eq!(priced.get_name(), "AAPL");
+        assert_eq!(priced.get_balance(), dec!(235));
+        assert!(yahoo.requests("/v1/finance/search").is_empty());
+
+        // a position with a Name never needs the lookup
+        let named = price_position(&yahoo.connector(), &mut positions[1].clone(), true)
+            .await
+            .unwrap();
+        assert_eq!(named.get_name(), "Apple");
+        assert!(yahoo.requests("/v1/finance/search").is_empty());
+
+        // handle_position asks the provider, the commands turn the lookup off on theirs
+        let cached = CachedProvider::new(yahoo.connector(), chrono::Duration::minutes(1))
+            .with_name_lookup(false);
+        let priced = handle_position(&cached, &mut positions[0].clone())
+            .await
+            .unwrap();
+        assert_eq!(priced.get_name(), "AAPL");
+        let path = std::env::temp_dir().join(format!("no_names_{}.json", std::process::id()));
+        let stored = StoredNames::open(cached, path).with_name_lookup(true);
+        assert!(!stored.looks_up_names());
+        assert!(yahoo.requests("/v1/finance/search").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_handle_positions_progress() {
+        let provider = MockProvider::new()
+            .with_price("AAPL", 170.0)
+            .with_price("MSFT", 380.0);
+        let positions = from_string(
+            r#"[
+                {"Name": "Apple", "Ticker": "AAPL", "AssetClass": "Stock", "Amount": 1},
+                {"Name": "Unknown", "Ticker": "UNKNOWN", "AssetClass": "Stock", "Amount": 1},
+                {"Name": "Microsoft", "Ticker": "MSFT", "AssetClass": "Stock", "Amount": 1},
+                {"Name": "Savings", "AssetClass": "Cash", "Amount": 100}
+            ]"#,
+        );
+
+        let calls = std::sync::Mutex::new(Vec::new());
+        let results = handle_positions(&provider, positions, |done, total| {
+            calls.lock().unwrap().push((done, total));
+        })
+        .await;
+
+        // failed positions count as completed too
+        assert_eq!(
+            calls.into_inner().unwrap(),
+            [(1, 4), (2, 4), (3, 4), (4, 4)]
+        );
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[0].as_ref().unwrap().get_balance(), dec!(170));
+        assert!(results[1].is_err());
+        assert_eq!(results[2].as_ref().unwrap().get_name(), "Microsoft");
+        assert_eq!(results[3].as_ref().unwrap().get_balance(), dec!(100));
+
+        let results = handle_positions(&provider, Vec::new(), |_, _| panic!()).await;
+        assert!(results.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_handle_position_listings() {
+        let provider = MockProvider::new()
+            .with_price("IWDA.AS", 90.0)
+            .with_name("IWDA.AS", "iShares Core MSCI World");
+        let positions = from_string(
+            r#"[
+                {"Ticker": ["EUNL.DE", "IWDA.AS", "SWDA.L"], "AssetClass": "ETF", "Amount": 10},
+                {"Name": "Gone", "Ticker": ["OLD.DE", "OLD.L"], "AssetClass": "ETF", "Amount": 1}
+            ]"#,
+        );
+        assert_eq!(positions[0].get_ticker(), Some("EUNL.DE"));
+        let results = handle_positions(&provider, positions, |_, _| {}).await;
+
+        // the first listing with a quote is used, also for the name
+        let world = results[0].as_ref().unwrap();
+        assert_eq!(world.get_ticker(), Some("IWDA.AS"));
+        assert_eq!(world.get_name(), "iShares Core MSCI World");
+        assert_eq!(world.get_balance(), dec!(900));
+        assert_eq!(provider.latest_requests("EUNL.DE"), 1);
+        assert_eq!(provider.latest_requests("SWDA.L"), 0);
+        let value: serde_json::Value = serde_json::from_str(
+            &crate::export::to_json(std::slice::from_ref(world), &[], "EUR").unwrap(),
+        )
+        .unwrap();
+        assert_eq!(value["positions"][0]["ticker"], "IWDA.AS");
+
+        // the list is saved as it was
+        let saved = to_data_string(std::slice::from_ref(world), None, None, None).unwrap();
+        let saved: serde_json::Value = serde_json::from_str(&saved).unwrap();
+        assert_eq!(
+            saved[0]["Ticker"],
+            serde_json::json!(["EUNL.DE", "IWDA.AS", "SWDA.L"])
+        );
+
+        let error = results[1].as_ref().unwrap_err().to_string();
+        assert!(error.contains("no listing of Gone has a quote"));
+        assert!(error.contains("OLD.DE: "));
+        assert!(error.contains("; OLD.L: "));
+    }
+
+    #[test]
+    fn test_single_ticker_round_trip() {
+        let data = r#"[{"Ticker": "AAPL", "AssetClass": "Stocks", "Amount": 1}]"#;
+        let positions = from_string(data);
+        assert_eq!(positions[0].get_listings(), ["AAPL"]);
+        let saved: serde_json::Value =
+            serde_json::from_str(&to_data_string(&positions, None, None, None).unwrap()).unwrap();
+        assert_eq!(saved[0]["Ticker"], "AAPL");
+
+        let positions =
+            from_string(r#"[{"Name": "Cash", "Ticker": null, "AssetClass": "Cash", "Amount": 1}]"#);
+        assert_eq!(positions[0].get_ticker(), None);
+    }
+
+    #[tokio::test]
+    async fn test_handle_position_isin() {
+        let provider = MockProvider::new()
+            .with_symbols("IE00B4L5Y983", &["EUNL.DE"])
+            .with_symbols("LU0274208692", &["XDWD.DE", "XDWD.L"])
+            .with_price("EUNL.DE", 90.0)
+            .with_price("XDWD.DE", 110.0)
+            .with_price("XDWD.L", 120.0);
+        let positions = from_string(
+            r#"[
+                {"Name": "World", "ISIN": "IE00B4L5Y983", "AssetClass": "ETF", "Amount": 10},
+                {"Name": "World Swap", "Isin": "LU0274208692", "AssetClass": "ETF", "Amount": 10},
+                {"Name": "Unknown", "Isin": "XX0000000000", "AssetClass": "ETF", "Amount": 10}
+            ]"#,
+        );
+        let results = handle_positions(&provider, positions, |_, _| {}).await;
+
+        let world = results[0].as_ref().unwrap();
+        assert_eq!(world.get_ticker(), Some("EUNL.DE"));
+        assert_eq!(world.get_balance(), dec!(900));
+        // the first of several symbols is used
+        let swap = results[1].as_ref().unwrap();
+        assert_eq!(swap.get_ticker(), Some("XDWD.DE"));
+        assert_eq!(swap.get_balance(), dec!(1100));
+        // without a symbol the position fails like a ticker without a quote
+        let error = results[2].as_ref().unwrap_err();
+        assert_eq!(error.kind, ErrorKind::NotFound);
+        assert!(error.to_string().contains("XX0000000000"));
+        let unknown =
+            r#"[{"Name": "Unknown", "Isin": "XX0000000000", "AssetClass": "ETF", "Amount": 10}]"#;
+        let report =
+            crate::pricing::price_positions(&provider, from_string(unknown), |_, _| {}).await;
+        assert!(report.positions[0].is_unpriced());
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(report.errors[0].name, "Unknown");
+
+        // a position with a ticker isn't searched, the resolved ticker is saved
+        let mut priced = world.clone();
+        handle_position(&provider, &mut priced).await.unwrap();
+        assert_eq!(provider.searches("IE00B4L5Y983"), 1);
+        let saved = to_data_string(&[priced], None, None, None).unwrap();
+        assert!(saved.contains(r#""Ticker": "EUNL.DE""#));
+        assert!(saved.contains(r#""Isin": "IE00B4L5Y983""#));
+        assert!(missing_data_report(&from_string(&saved)).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_handle_position_keeps_the_quote_time() {
+        let quoted = Utc.with_ymd_and_hms(2024, 1, 5, 21, 0, 0).unwrap();
+        let provider = MockProvider::new()
+            .with_price("AAPL", 181.0)
+            .with_quote_time("AAPL", quoted);
+        let mut position = PortfolioPosition {
+            ticker: vec!["AAPL".to_string()],
+            asset_class: "Stocks".to_string(),
+            amount: dec!(1),
+            ..Default::default()
+        };
+
+        let priced = handle_position(&provider, &mut position).await.unwrap();
+        assert_eq!(priced.get_last_spot_time(), Some(quoted));
+        assert_eq!(
+            priced.get_last_spot_time().unwrap().timestamp(),
+            provider
+                .get_latest_price("AAPL")
+                .await
+                .unwrap()
+                .time
+                .unwrap()
+                .timestamp()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handle_position_without_a_name() {
+        // Yahoo's search doesn't know some symbols its chart API quotes, e.g. of
+        // Frankfurt or OTC listings: the quote is there, the name is not
+        let provider = MockProvider::new().with_price("APC.F", 180.0);
+        let mut position = PortfolioPosition {
+            ticker: vec!["APC.F".to_string()],
+            asset_class: "Stocks".to_string(),
+            amount: dec!(3),
+            ..Default::default()
+        };
+
+        let priced = handle_position(&provider, &mut position).await.unwrap();
+        assert_eq!(priced.get_balance(), dec!(540));
+        assert_eq!(priced.get_name(), "APC.F");
+        assert_eq!(priced.get_given_name(), None);
+        assert!(!priced.is_stale(chrono::Duration::days(DEFAULT_MAX_QUOTE_AGE_DAYS)));
+
+        // the price fails like before, there is nothing to show without it
+        let mut unpriced = PortfolioPosition {
+            ticker: vec!["OTC".to_string()],
+            asset_class: "Stocks".to_string(),
+            amount: dec!(1),
+            ..Default::default()
+        };
+        assert!(handle_position(&provider, &mut unpriced).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_handle_position_with_mock_provider() {
+        let provider = MockProvider::new()
+            .with_price("AAPL", 170.0)
+            .with_previous_close("AAPL", 172.5)
+            .with_name("AAPL", "Apple Inc.");
+        let mut position = PortfolioPosition {
+            ticker: vec!["AAPL".to_string()],
+            asset_class: "Stock".to_string(),
+            amount: dec!(2),
+            ..Default::default()
+        };
+
+        let updated_position = handle_position(&provider, &mut position).await.unwrap();
+        assert_eq!(updated_position.get_name(), "Apple Inc.");
+        assert_eq!(updated_position.get_balance(), dec!(340));
+        assert_eq!(updated_position.get_day_change(), Some(dec!(-5)));
+        assert_eq!(
+            updated_position
+                .get_day_change_percent()
+                .map(|percent| percent.round_dp(2)),
+            Some(dec!(-1.45))
+        );
+        assert!(updated_position.get_last_spot_time().is_some());
+
+        let mut unknown = PortfolioPosition {
+            ticker: vec!["UNKNOWN".to_string()],
+            asset_class: "Stock".to_string(),
+            amount: dec!(1),
+            last_spot: dec!(12),
+            ..Default::default()
+        };
+        assert!(handle_position(&provider, &mut unknown).await.is_err());
+        // without a quote the price of before is kept
+        assert_eq!(unknown.last_spot, dec!(12));
+
+        // cash positions are not priced
+        let mut cash = PortfolioPosition {
+            name: Some("Savings".to_string()),
+            asset_class: "Cash".to_string(),
+            amount: dec!(100),
+            ..Default::default()
+        };
+        let cash = handle_position(&provider, &mut cash).await.unwrap();
+        assert_eq!(cash.get_balance(), dec!(100));
+    }
+
+    fn position_quoted_at(ticker: Option<&str>, time: Option<DateTime<Utc>>) -> PortfolioPosition {
+        PortfolioPosition {
+            ticker: ticker.map(|t| t.to_string()).into_iter().collect(),
+            asset_class: "Stock".to_string(),
+            amount: dec!(1),
+            last_spot: dec!(100),
+            last_spot_time: time,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_is_stale_skips_weekends() {
+        let max_age = chrono::Duration::days(DEFAULT_MAX_QUOTE_AGE_DAYS);
+        // Friday close, checked on the following Monday and Wednesday
+        let friday_close = Utc.with_ymd_and_hms(2024, 6, 7, 20, 0, 0).unwrap();
+        let position = position_quoted_at(Some("AAPL"), Some(friday_close));
+
+        let monday = Utc.with_ymd_and_hms(2024, 6, 10, 14, 0, 0).unwrap();
+        assert!(!position.is_stale_at(max_age, monday));
+
+        let wednesday = Utc.with_ymd_and_hms(2024, 6, 12, 19, 0, 0).unwrap();
+        assert!(!position.is_stale_at(max_age, wednesday));
+
+        let thursday = Utc.with_ymd_and_hms(2024, 6, 13, 21, 0, 0).unwrap();
+        assert!(position.is_stale_at(max_age, thursday));
+    }
+
+    #[test]
+    fn test_is_stale_boundary() {
+        let max_age = chrono::Duration::days(DEFAULT_MAX_QUOTE_AGE_DAYS);
+        // Friday noon to Wednesday noon is exactly 3 trading days
+        let friday_noon = Utc.with_ymd_and_hms(2024, 6, 7, 12, 0, 0).unwrap();
+        let wednesday_noon = Utc.with_ymd_and_hms(2024, 6, 12, 12, 0, 0).unwrap();
+        let position = position_quoted_at(Some("AAPL"), Some(friday_noon));
+
+        assert!(!position.is_stale_at(max_age, wednesday_noon));
+        assert!(position.is_stale_at(max_age, wednesday_noon + chrono::Duration::seconds(1)));
+        assert!(position.is_stale_at(chrono::Duration::days(2), wednesday_noon));
+    }
+
+    #[test]
+    fn test_is_stale_without_quote() {
+        let max_age = chrono::Duration::days(DEFAULT_MAX_QUOTE_AGE_DAYS);
+        let now = Utc.with_ymd_and_hms(2024, 6, 10, 14, 0, 0).unwrap();
+
+        assert!(position_quoted_at(Some("AAPL"), None).is_stale_at(max_age, now));
+        assert!(!position_quoted_at(None, None).is_stale_at(max_age, now));
+    }
+
+    #[test]
+    fn test_weekday_duration() {
+        let saturday = Utc.with_ymd_and_hms(2024, 6, 8, 12, 0, 0).unwrap();
+        let monday = Utc.with_ymd_and_hms(2024, 6, 10, 6, 0, 0).unwrap();
+        assert_eq!(
+            weekday_duration(saturday, monday),
+            chrono::Duration::hours(6)
+        );
+        assert_eq!(weekday_duration(monday, saturday), chrono::Duration::zero());
+    }
+
+    #[tokio::test]
+    async fn test_from_file() {
+        let positions_str = fs::read_to_string("example_data.json").unwrap();
+        let positions = from_string(&positions_str);
+        assert_eq!(positions.len(), 6);
+    }
+
+    #[test]
+    fn test_missing_data_report() {
+        let positions = from_string(
+            r#"[
+                {"Name": "World ETF", "AssetClass": "ETF", "Amount": 10},
+                {"Name": "Apple", "Ticker": "AAPL", "AssetClass": "Stocks", "Amount": 10},
+                {"Name": "Savings", "AssetClass": "Cash", "Amount": 1000, "InterestRate": 2},
+                {"Name": "Checking", "AssetClass": "Cash", "Amount": 500},
+                {"Name": "Startup", "AssetClass": "Stocks", "Amount": 100, "ManualPrice": 3},
+                {"Name": "Car Loan", "AssetClass": "Liability", "Amount": 8000,
+                 "PaymentAmount": 300, "PaymentFrequencyDays": 0, "NextInterestPayment": "2024-07-01"},
+                {"Name": "Deposit", "AssetClass": "Cash", "Amount": 5000, "InterestRate": 3,
+                 "PaymentFrequencyDays": 90, "NextInterestPayment": "2024-07-01"}
+            ]"#,
+        );
+        assert_eq!(
+            missing_data_report(&positions),
+            vec![
+                (
+                    "Car Loan".to_string(),
+                    vec!["InterestRate", "PaymentFrequencyDays"]
+                ),
+                (
+                    "Savings".to_string(),
+                    vec!["NextInterestPayment", "PaymentFrequencyDays"]
+                ),
+                ("Startup".to_string(), vec!["PriceDate"]),
+                ("World ETF".to_string(), vec!["Ticker"]),
+            ]
+        );
+        assert!(missing_data_report(&positions[1..2]).is_empty());
+    }
+
+    fn savings_account() -> PortfolioPosition {
+        PortfolioPosition::builder("Savings", None, "Cash", dec!(1000))
+            .with_interest_rate(dec!(3.65))
+            .with_payment_frequency_days(30)
+            .with_next_interest_payment(at("2024-01-31"))
+            .with_last_interest_payment(at("2024-01-01"))
+            .build()
+            .unwrap()
+    }
+
+    fn at(date: &str) -> DateTime<Utc> {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+            .and_utc()
+    }
+
+    #[test]
+    fn test_next_interest_payment_before_last() {
+        let mut savings = savings_account();
+        savings.next_interest_payment = Some(at("2024-01-01"));
+        savings.last_interest_payment = Some(at("2024-01-31"));
+
+        // the next payment is one period after the last one
+        assert_eq!(savings.get_next_interest_payment(), Some(at("2024-03-01")));
+        let warnings = savings.interest_date_warnings(at("2024-02-15"));
+        assert_eq!(
+            warnings,
+            ["the NextInterestPayment 2024-01-01 is not after the LastInterestPayment 2024-01-31, the next payment is expected on 2024-03-01"]
+        );
+        assert_eq!(savings.apply_interest_if_due(at("2024-02-15"), 2), None);
+        assert_eq!(
+            savings.apply_interest_if_due(at("2024-03-01"), 2),
+            Some(dec!(3))
+        );
+        assert_eq!(savings.get_amount(), dec!(1003));
+        assert_eq!(savings.next_interest_payment, Some(at("2024-03-31")));
+        assert!(savings.interest_date_warnings(at("2024-03-01")).is_empty());
+    }
+
+    #[test]
+    fn test_last_interest_payment_in_the_future() {
+        let mut savings = savings_account();
+        savings.last_interest_payment = Some(at("2024-02-01"));
+        savings.next_interest_payment = Some(at("2024-01-10"));
+
+        let warnings = savings.interest_date_warnings(at("2024-01-15"));
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(
+            warnings[1],
+            "the LastInterestPayment 2024-02-01 is in the future, no interest is credited until the next payment"
+        );
+        // nothing is credited or accrued before the last payment, never a negative amount
+        assert_eq!(savings.accrued_interest(at("2024-01-15")), dec!(0));
+        assert_eq!(savings.apply_interest_if_due(at("2024-01-15"), 2), None);
+        assert_eq!(savings.get_amount(), dec!(1000));
+        assert!(savings
+            .simulate_interest(at("2024-06-01"), 2)
+            .iter()
+            .all(|(date, balance)| *date > at("2024-02-01") && *balance > dec!(1000)));
+    }
+
+    #[test]
+    fn test_broker_field_aliases() {
+        let positions = from_string(
+            r#"[
+                {"Name": "Apple", "symbol": "AAPL", "quantity": 10, "AssetClass": "Stocks"},
+                {"Name": "Microsoft", "Symbol": "MSFT", "shares": 2.5, "AssetClass": "Stocks"},
+                {"Name": "Nvidia", "Ticker": "NVDA", "Shares": 4, "AssetClass": "Stocks"}
+            ]"#,
+        );
+        assert_eq!(positions.len(), 3);
+        assert_eq!(positions[0].get_ticker(), Some("AAPL"));
+        assert_eq!(positions[0].get_amount(), dec!(10));
+        assert_eq!(positions[1].get_ticker(), Some("MSFT"));
+        assert_eq!(positions[1].get_amount(), dec!(2.5));
+        assert_eq!(positions[2].get_amount(), dec!(4));
+
+        // saved with the usual names
+        let saved = to_string(&positions[..1]).unwrap();
+        assert!(saved.contains("\"Ticker\": \"AAPL\""));
+        assert!(saved.contains("\"Amount\": 10"));
+        assert!(!saved.contains("symbol"));
+    }
+
+    #[test]
+    fn test_merge_duplicates() {
+        // the same ETF from the exports of two brokers
+        let positions = from_string(
+            r#"[
+                {"Name": "Vanguard FTSE All-World", "Ticker": "VWCE.DE", "AssetClass": "Stocks",
+                 "Amount": 10, "CostBasis": 100, "Tags": ["Core"], "PurchaseDate": "2023-05-01"},
+                {"Name": "Savings", "AssetClass": "Cash", "Amount": 1000},
+                {"Name": "VWCE", "Ticker": "vwce.de", "AssetClass": "Stocks",
+                 "Amount": 30, "CostBasis": 120, "Tags": ["Core", "Global"], "PurchaseDate": "2022-01-03"},
+                {"Name": "Apple", "Ticker": "AAPL", "AssetClass": "Stocks", "Amount": 5, "Account": "Taxable"},
+                {"Name": "Apple", "Ticker": "AAPL", "AssetClass": "Stocks", "Amount": 2, "Account": "IRA"}
+            ]"#,
+        );
+        let merged = merge_duplicates(positions).unwrap();
+
+        let names: Vec<&str> = merged.iter().map(|p| p.get_name()).collect();
+        // the positions in two accounts are held apart
+        assert_eq!(
+            names,
+            ["Vanguard FTSE All-World", "Savings", "Apple", "Apple"]
+        );
+        let etf = &merged[0];
+        assert_eq!(etf.amount, dec!(40));
+        assert_eq!(etf.cost_basis, Some(dec!(115)));
+        assert_eq!(etf.tags, ["Core", "Global"]);
+        assert_eq!(etf.purchase_date, Some(at("2022-01-03")));
+        // nothing left to merge
+        assert_eq!(merge_duplicates(merged.clone()).unwrap().len(), 4);
+
+        // consolidate merges the fields the same way
+        let consolidated = consolidate(from_string(
+            r#"[
+                {"Name": "Vanguard FTSE All-World", "Ticker": "VWCE.DE", "AssetClass": "Stocks",
+                 "Amount": 10, "CostBasis": 100, "Tags": ["Core"], "PurchaseDate": "2023-05-01"},
+                {"Name": "VWCE", "Ticker": "vwce.de", "AssetClass": "Stocks",
+                 "Amount": 30, "CostBasis": 120, "Tags": ["Core", "Global"], "PurchaseDate": "2022-01-03"}
+            ]"#,
+        ));
+        assert_eq!(
+            to_string(&consolidated).unwrap(),
+            to_string(&merged[..1]).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_merge_duplicates_without_a_ticker() {
+        let positions = from_string(
+            r#"[
+                {"Name": "Emergency Fund", "AssetClass": "Cash", "Amount": 1000, "Notes": "bank A"},
+                {"Name": "emergency fund", "AssetClass": "Cash", "Amount": 500, "Notes": "bank B"},
+                {"Name": "House", "AssetClass": "Real Estate", "Amount": 300000}
+            ]"#,
+        );
+        assert!(is_duplicate_of(&positions[0], &positions[1]));
+        assert!(!is_duplicate_of(&positions[0], &positions[2]));
+
+        let merged = merge_duplicates(positions).unwrap();
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].get_name(), "Emergency Fund");
+        assert_eq!(merged[0].amount, dec!(1500));
+        assert_eq!(merged[0].notes.as_deref(), Some("bank A; bank B"));
+        assert_eq!(merged[0].cost_basis, None);
+    }
+
+    #[test]
+    fn test_merge_duplicates_with_conflicts() {
+        let merge = |data: &str| merge_duplicates(from_string(data)).unwrap_err();
+        assert_eq!(
+            merge(
+                r#"[
+                    {"Name": "Savings", "AssetClass": "Cash", "Amount": 1000, "InterestRate": 2,
+                     "PaymentFrequencyDays": 30, "NextInterestPayment": "2024-02-01"},
+                    {"Name": "Savings", "AssetClass": "Cash", "Amount": 500, "InterestRate": 3,
+                     "PaymentFrequencyDays": 30, "NextInterestPayment": "2024-02-01"}
+                ]"#
+            ),
+            "Can't merge Savings and Savings, their interest settings differ"
+        );
+        // an account on only one of them
+        assert_eq!(
+            merge(
+                r#"[
+                    {"Name": "World", "Ticker": "VWCE.DE", "AssetClass": "Stocks", "Amount": 10, "Account": "Broker A"},
+                    {"Name": "VWCE", "Ticker": "VWCE.DE", "AssetClass": "Stocks", "Amount": 5}
+                ]"#
+            ),
+            "Can't merge World and VWCE, they are in different Accounts"
+        );
+        assert!(merge(
+            r#"[
+                {"Name": "World", "Ticker": "VWCE.DE", "AssetClass": "Stocks", "Amount": 10, "CostBasis": 100},
+                {"Name": "VWCE", "Ticker": "VWCE.DE", "AssetClass": "Stocks", "Amount": 5}
+            ]"#
+        )
+        .ends_with("only one of them has a CostBasis"));
+    }
+
+    #[test]
+    fn test_consolidate() {
+        let positions = from_string(
+            r#"[
+                {"Name": "Apple", "Ticker": "AAPL", "AssetClass": "Stocks", "Amount": 10,
+                 "CostBasis": 100, "Tags": ["core"], "Account": "Broker",
+                 "Dividends": [{"Date": "2024-05-16", "Amount": 2.5}]},
+                {"Name": "Savings", "AssetClass": "Cash", "Amount": 500},
+                {"Name": "Apple (IRA)", "Ticker": "aapl", "AssetClass": "Stocks", "Amount": 30,
+                 "CostBasis": 140, "Tags": ["core", "retirement"], "Account": "IRA",
+                 "Dividends": [{"Date": "2024-02-15", "Amount": 7.2}]},
+                {"Name": "Savings", "AssetClass": "Cash", "Amount": 300},
+                {"Name": "Apple", "Ticker": "AAPL", "AssetClass": "Stocks", "Watch": true},
+                {"Name": "Apple what-if", "Ticker": "AAPL", "AssetClass": "Stocks", "Amount": 1,
+                 "PriceOverride": 50}
+            ]"#,
+        );
+        let consolidated = consolidate(positions);
+        // the duplicate AAPL is merged into the first, cash and the others stay apart
+        assert_eq!(consolidated.len(), 5);
+        let apple = &consolidated[0];
+        assert_eq!(apple.get_name(), "Apple");
+        assert_eq!(apple.get_amount(), dec!(40));
+        // (10 * 100 + 30 * 140) / 40
+        assert_eq!(apple.get_cost_basis(), Some(dec!(130)));
+        assert_eq!(apple.get_tags(), ["core", "retirement"]);
+        assert_eq!(apple.get_account(), None);
+        let dates: Vec<NaiveDate> = apple.get_dividends().iter().map(|d| d.date).collect();
+        assert_eq!(
+            dates,
+            [
+                NaiveDate::from_ymd_opt(2024, 2, 15).unwrap(),
+                NaiveDate::from_ymd_opt(2024, 5, 16).unwrap()
+            ]
+        );
+        assert_eq!(consolidated[1].get_amount(), dec!(500));
+        assert_eq!(consolidated[2].get_amount(), dec!(300));
+        assert!(consolidated[3].is_watch_entry());
+        assert_eq!(consolidated[4].get_amount(), dec!(1));
+
+        // without the cost basis of every lot the gain is unknown
+        let positions = from_string(
+            r#"[
+                {"Ticker": "MSFT", "Name": "Microsoft", "AssetClass": "Stocks", "Amount": 1, "CostBasis": 300},
+                {"Ticker": "MSFT", "Name": "Microsoft", "AssetClass": "Stocks", "Amount": 2}
+            ]"#,
+        );
+        let consolidated = consolidate(positions);
+        assert_eq!(consolidated.len(), 1);
+        assert_eq!(consolidated[0].get_amount(), dec!(3));
+        assert_eq!(consolidated[0].get_cost_basis(), None);
+    }
+
+    fn savings_at(rate: &str) -> PortfolioPosition {
+        from_string(&format!(
+            r#"[{{"Name": "Savings", "AssetClass": "Cash", "Amount": 1000, "InterestRate": {rate},
+                 "PaymentFrequencyDays": 30, "NextInterestPayment": "2024-01-31",
+                 "LastInterestPayment": "2024-01-01"}}]"#
+        ))
+        .remove(0)
+    }
+
+    #[test]
+    fn test_negative_interest_rate() {
+        let mut savings = savings_at("-0.5");
+        assert!(savings.is_cash_with_interest());
+        assert!(savings.daily_interest_amount() < Decimal::ZERO);
+        let from = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let to = Utc.with_ymd_and_hms(2024, 1, 31, 0, 0, 0).unwrap();
+        assert_eq!(
+            round_to(savings.calculate_interest(from, to), 2),
+            dec!(-0.41)
+        );
+
+        // two payments of -0.41, the second on the reduced amount
+        let now = Utc.with_ymd_and_hms(2024, 3, 5, 0, 0, 0).unwrap();
+        assert_eq!(savings.apply_interest_if_due(now, 2), Some(dec!(-0.82)));
+        assert_eq!(savings.get_amount(), dec!(999.18));
+        assert_eq!(
+            savings.get_next_interest_payment(),
+            Some(Utc.with_ymd_and_hms(2024, 3, 31, 0, 0, 0).unwrap())
+        );
+        assert_eq!(savings.get_interest_payments()[0].amount, dec!(-0.41));
+    }
+
+    #[test]
+    fn test_zero_interest_rate() {
+        let mut savings = savings_at("0");
+        assert!(savings.is_cash_with_interest());
+        assert_eq!(savings.daily_interest_amount(), Decimal::ZERO);
+
+        // nothing is credited or logged, but the payment dates move on
+        let now = Utc.with_ymd_and_hms(2024, 3, 5, 0, 0, 0).unwrap();
+        assert_eq!(savings.apply_interest_if_due(now, 2), Some(Decimal::ZERO));
+        assert_eq!(savings.get_amount(), dec!(1000));
+        assert_eq!(
+            savings.get_last_interest_payment(),
+            Some(Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap())
+        );
+        assert_eq!(
+            savings.get_next_interest_payment(),
+            Some(Utc.with_ymd_and_hms(2024, 3, 31, 0, 0, 0).unwrap())
+        );
+        assert!(savings.get_interest_payments().is_empty());
+        assert_eq!(savings.apply_interest_if_due(now, 2), None);
+    }
+
+    #[test]
+    fn test_interest_fields_round_trip() {
+        let position = savings_account();
+        assert!(position.is_cash_with_interest());
+
+        let json = to_string(&[position]).unwrap();
+        assert!(json.contains("\"NextInterestPayment\": \"2024-01-31\""));
+        assert!(!json.contains("LastSpot"));
+
+        let position = from_string(&json).remove(0);
+        assert_eq!(position.get_interest_rate(), Some(dec!(3.65)));
+        assert_eq!(position.payment_frequency_days, Some(30));
+        assert_eq!(
+            position.last_interest_payment,
+            Some(Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_simulate_interest() {
+        let savings = from_string(
+            r#"[{"Name": "Savings", "AssetClass": "Cash", "Amount": 1000, "InterestRate": 3.65,
+                 "PaymentFrequencyDays": 30, "NextInterestPayment": "2024-01-31",
+                 "LastInterestPayment": "2024-01-01"}]"#,
+        )
+        .remove(0);
+        let date = |y, m, d| Utc.with_ymd_and_hms(y, m, d, 0, 0, 0).unwrap();
+
+        // 12 months of payments every 30 days
+        let schedule = savings.simulate_interest(date(2025, 1, 1), 2);
+        assert_eq!(schedule.len(), 12);
+        assert_eq!(schedule[0], (date(2024, 1, 31), dec!(1003.00)));
+        assert_eq!(schedule[1], (date(2024, 3, 1), dec!(1006.01)));
+        assert_eq!(schedule[11].0, date(2024, 12, 26));
+        assert!(schedule.windows(2).all(|pair| pair[0].1 < pair[1].1));
+
+        // the last balance is what crediting the same payments would give
+        let mut credited = savings.clone();
+        credited.apply_interest_if_due(date(2025, 1, 1), 2);
+        assert_eq!(schedule[11].1, credited.get_amount());
+        assert_eq!(savings.get_amount(), dec!(1000));
+
+        assert!(savings.simulate_interest(date(2024, 1, 30), 2).is_empty());
+        let wallet = from_string(r#"[{"Name": "Wallet", "AssetClass": "Cash", "Amount": 100}]"#);
+        assert!(wallet[0].simulate_interest(date(2025, 1, 1), 2).is_empty());
+    }
+
+    #[test]
+    fn test_liabilities() {
+        let positions = from_string(
+            r#"[
+                {"Name": "Mortgage", "AssetClass": "Liability", "Amount": 200000},
+                {"Name": "Car Loan", "AssetClass": "liabilities", "Amount": -8000},
+                {"Name": "Overdraft", "AssetClass": "Cash", "Amount": -150},
+                {"Name": "Savings", "AssetClass": "Cash", "Amount": 150}
+            ]"#,
+        );
+        let balances: Vec<Decimal> = positions.iter().map(|p| p.get_balance()).collect();
+        assert_eq!(
+            balances,
+            [dec!(-200000), dec!(-8000), dec!(-150), dec!(150)]
+        );
+        let liabilities: Vec<bool> = positions.iter().map(|p| p.is_liability()).collect();
+        assert_eq!(liabilities, [true, true, true, false]);
+    }
+
+    // 1,000 at 12% a year, paid off in 12 monthly installments of 88.85
+    fn car_loan() -> PortfolioPosition {
+        from_string(
+            r#"[{
+                "Name": "Car Loan",
+                "AssetClass": "Liability",
+                "Amount": 1000,
+                "InterestRate": 12,
+                "PaymentFrequencyDays": 30,
+                "NextInterestPayment": "2024-01-31",
+                "PaymentAmount": 88.85
+            }]"#,
+        )
+        .remove(0)
+    }
+
+    #[test]
+    fn test_amortization_schedule() {
+        let loan = car_loan();
+        assert!(loan.is_amortized_loan());
+        assert!(!loan.is_cash_with_interest());
+
+        let schedule = loan.amortization_schedule(2).unwrap();
+        assert_eq!(schedule.len(), 12);
+        // the rows of the standard amortization table
+        let rows: Vec<(Decimal, Decimal, Decimal, Decimal)> = schedule
+            .iter()
+            .map(|p| (p.payment, p.interest, p.principal, p.balance))
+            .collect();
+        assert_eq!(
+            rows[0],
+            (dec!(88.85), dec!(10.00), dec!(78.85), dec!(921.15))
+        );
+        assert_eq!(
+            rows[1],
+            (dec!(88.85), dec!(9.21), dec!(79.64), dec!(841.51))
+        );
+        assert_eq!(
+            rows[5],
+            (dec!(88.85), dec!(5.98), dec!(82.87), dec!(514.92))
+        );
+        // the last payment only covers what is left
+        assert_eq!(rows[11], (dec!(88.84), dec!(0.88), dec!(87.96), dec!(0.00)));
+        let interest: Decimal = schedule.iter().map(|p| p.interest).sum();
+        assert_eq!(interest, dec!(66.19));
+        assert_eq!(
+            schedule[11].date,
+            Utc.with_ymd_and_hms(2024, 12, 26, 0, 0, 0).unwrap()
+        );
+        // nothing was paid yet
+        assert_eq!(loan.get_amount(), dec!(1000));
+    }
+
+    #[test]
+    fn test_apply_loan_payments_if_due() {
+        let mut loan = car_loan();
+        let now = Utc.with_ymd_and_hms(2024, 3, 5, 0, 0, 0).unwrap();
+        let paid = loan.apply_loan_payments_if_due(now, 2);
+        assert_eq!(paid.len(), 2);
+        assert_eq!(loan.get_amount(), dec!(841.51));
+        assert_eq!(loan.get_balance(), dec!(-841.51));
+        assert_eq!(
+            loan.get_next_interest_payment(),
+            Some(Utc.with_ymd_and_hms(2024, 3, 31, 0, 0, 0).unwrap())
+        );
+        assert!(loan.apply_loan_payments_if_due(now, 2).is_empty());
+
+        // paid off, the balance doesn't go below zero
+        let much_later = Utc.with_ymd_and_hms(2030, 1, 1, 0, 0, 0).unwrap();
+        assert_eq!(loan.apply_loan_payments_if_due(much_later, 2).len(), 10);
+        assert_eq!(loan.get_amount(), dec!(0));
+        assert!(loan.apply_loan_payments_if_due(much_later, 2).is_empty());
+    }
+
+    #[test]
+    fn test_amortization_payment_too_low() {
+        let mut loan = car_loan();
+        loan.payment_amount = Some(dec!(10));
+        assert!(loan
+            .amortization_schedule(2)
+            .unwrap_err()
+            .starts_with("Car Loan is not paid off"));
+
+        // a negative cash amount keeps its sign
+        let mut overdraft = car_loan();
+        overdraft.asset_class = "Cash".to_string();
+        overdraft.amount = dec!(-1000);
+        let now = Utc.with_ymd_and_hms(2024, 2, 1, 0, 0, 0).unwrap();
+        overdraft.apply_loan_payments_if_due(now, 2);
+        assert_eq!(overdraft.get_amount(), dec!(-921.15));
+    }
+
+    #[test]
+    fn test_accrued_interest() {
+        let mut savings = savings_account();
+        savings.last_interest_payment = None;
+        // the period started 30 days before the next payment, 0.1 accrues per day
+        assert_eq!(
+            savings.interest_period_start(),
+            Some(Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap())
+        );
+        let as_of = Utc.with_ymd_and_hms(2024, 1, 11, 0, 0, 0).unwrap();
+        assert_eq!(savings.accrued_interest(as_of), dec!(1));
+        assert_eq!(savings.get_amount(), dec!(1000));
+
+        let before = Utc.with_ymd_and_hms(2023, 12, 1, 0, 0, 0).unwrap();
+        assert_eq!(savings.accrued_interest(before), dec!(0));
+
+        let checking =
+            from_string(r#"[{"Name": "Checking", "AssetClass": "Cash", "Amount": 500}]"#);
+        assert_eq!(checking[0].accrued_interest(as_of), dec!(0));
+        assert_eq!(checking[0].interest_period_start(), None);
+    }
+
+    #[test]
+    fn test_balance_including_accrued() {
+        let mut savings = savings_account();
+        savings.last_interest_payment = None;
+        let as_of = Utc.with_ymd_and_hms(2024, 1, 11, 0, 0, 0).unwrap();
+        assert_eq!(savings.balance_including_accrued(as_of), dec!(1001));
+        assert_eq!(savings.get_balance(), dec!(1000));
+
+        // the interest on a liability is owed on top of it
+        let mut loan = savings.clone();
+        loan.asset_class = "Liability".to_string();
+        assert_eq!(loan.get_balance(), dec!(-1000));
+        assert_eq!(loan.balance_including_accrued(as_of), dec!(-1001));
+
+        // everything else is worth its balance
+        let checking =
+            from_string(r#"[{"Name": "Checking", "AssetClass": "Cash", "Amount": 500}]"#);
+        assert_eq!(checking[0].balance_including_accrued(as_of), dec!(500));
+    }
+
+    fn amounts() -> Vec<PortfolioPosition> {
+        from_string(
+            r#"[
+                {"Name": "Apple", "Ticker": "AAPL", "AssetClass": "Stocks", "Amount": 2.12345678},
+                {"Name": "Bitcoin", "Ticker": "BTC-USD", "AssetClass": "Crypto", "Amount": 1.0000000004},
+                {"Name": "Wallet", "AssetClass": "Crypto", "Amount": 0.123456789},
+                {"Name": "Savings", "AssetClass": "Cash", "Amount": 1000.125},
+                {"Name": "Shares", "Ticker": "MSFT", "AssetClass": "Stocks", "Amount": 10}
+            ]"#,
+        )
+    }
+
+    #[test]
+    fn test_format_balance() {
+        let positions = from_string(
+            r#"[
+                {"Name": "ISA", "AssetClass": "Cash", "Amount": 1200, "Currency": "GBP"},
+                {"Name": "Brokerage", "AssetClass": "Cash", "Amount": 1200, "Currency": "usd"},
+                {"Name": "Krona", "AssetClass": "Cash", "Amount": 1200, "Currency": "SEK"},
+                {"Name": "Savings", "AssetClass": "Cash", "Amount": 1200}
+            ]"#,
+        );
+        let format = NumberFormat::for_currency("EUR");
+        let balances: Vec<String> = positions
+            .iter()
+            .map(|position| position.format_balance(&format))
+            .collect();
+        assert_eq!(
+            balances,
+            ["£1,200.00", "$1,200.00", "1,200.00 SEK", "1,200.00 €"]
+        );
+        // only the display changes
+        assert!(positions
+            .iter()
+            .all(|position| position.get_balance() == Decimal::new(1200, 0)));
+    }
+
+    #[test]
+    fn test_display_amount() {
+        let format = NumberFormat::for_currency("EUR");
+        let shown: Vec<String> = amounts()
+            .iter()
+            .map(|position| position.display_amount(&format))
+            .collect();
+        assert_eq!(
+            shown,
+            ["2.123457", "1.00", "0.12345679", "1,000.13", "10.00"]
+        );
+
+        let format = format.with_config(&crate::format::NumberFormatConfig {
+            amount_precision: Some(AmountPrecision {
+                shares: 3,
+                ..AmountPrecision::default()
+            }),
+            ..Default::default()
+        });
+        assert_eq!(amounts()[0].display_amount(&format), "2.123");
+    }
+
+    #[test]
+    fn test_round_amount() {
+        let precision = AmountPrecision::default();
+        let mut positions = amounts();
+        for position in &mut positions {
+            position.round_amount(&precision);
+        }
+        let rounded: Vec<Decimal> = positions.iter().map(|p| p.get_amount()).collect();
+        assert_eq!(
+            rounded,
+            [
+                dec!(2.123457),
+                dec!(1.00000000),
+                dec!(0.12345679),
+                dec!(1000.13),
+                dec!(10)
+            ]
+        );
+        assert_eq!(positions[1].amount_decimals(&precision), 8);
+        assert_eq!(positions[3].amount_decimals(&precision), 2);
+    }
+
+    // serde-float writes 10 back as 10.0, which is the same number
+    fn numbers_as_f64(value: serde_json::Value) -> serde_json::Value {
+        use serde_json::Value;
+        match value {
+            Value::Number(number) => number
+                .as_f64()
+                .and_then(serde_json::Number::from_f64)
+                .map(Value::Number)
+                .unwrap_or(Value::Null),
+            Value::Array(values) => Value::Array(values.into_iter().map(numbers_as_f64).collect()),
+            Value::Object(object) => Value::Object(
+                object
+                    .into_iter()
+                    .map(|(key, value)| (key, numbers_as_f64(value)))
+                    .collect(),
+            ),
+            value => value,
+        }
+    }
+
+    #[test]
+    fn test_unknown_fields_round_trip() {
+        let data = std::fs::read_to_string("tests/fixtures/extra_fields.json").unwrap();
+        let positions = from_string(&data);
+        assert_eq!(positions[0].get_amount(), dec!(10));
+        let json =
+            to_data_string(&positions, portfolio_name(&data).as_deref(), None, None).unwrap();
+
+        let parse = |data: &str| numbers_as_f64(serde_json::from_str(data).unwrap());
+        assert_eq!(parse(&json), parse(&data));
+        assert_eq!(unknown_fields(&json), unknown_fields(&data));
+    }
+
+    #[test]
+    fn test_check_strict() {
+        let data = std::fs::read_to_string("tests/fixtures/extra_fields.json").unwrap();
+        assert_eq!(
+            unknown_fields(&data),
+            [
+                "Apple: Broker",
+                "Apple: WKN",
+                "Apple: Review",
+                "Apple: Lots",
+                "Savings: Comment",
+                "Savings: Amout"
+            ]
+        );
+        assert_eq!(
+            check_strict(r#"{"Positions": [], "TotalInvestmnt": 100}"#).unwrap_err(),
+            "Unknown fields in the data file: TotalInvestmnt (they are kept without --strict)"
+        );
+        let known = std::fs::read_to_string("tests/fixtures/portfolio.json").unwrap();
+        assert!(check_strict(&known).is_ok());
+    }
+
+    #[test]
+    fn test_cash_defaults() {
+        let data = std::fs::read_to_string("tests/fixtures/cash_defaults.json").unwrap();
+        let positions = from_string(&data);
+
+        // the Defaults fill in what a cash position leaves out
+        let checking = &positions[0];
+        assert_eq!(checking.get_interest_rate(), Some(dec!(2.5)));
+        assert_eq!(checking.get_payment_frequency_days(), Some(30));
+        assert_eq!(checking.get_institution(), Some("Example Bank"));
+        assert!(checking.is_cash_with_interest());
+        // the values of a position win
+        let savings = &positions[1];
+        assert_eq!(savings.get_interest_rate(), Some(dec!(3.75)));
+        assert_eq!(savings.get_payment_frequency_days(), Some(90));
+        assert_eq!(savings.get_institution(), Some("Example Bank"));
+        // other asset classes don't get them
+        let apple = &positions[2];
+        assert_eq!(apple.get_interest_rate(), None);
+        assert_eq!(apple.get_institution(), None);
+        assert!(check_strict(&data).is_ok());
+
+        // saved, the defaulted keys are left out again and the Defaults are kept
+        let json = to_data_string(
+            &positions,
+            portfolio_name(&data).as_deref(),
+            None,
+            position_defaults(&data).as_ref(),
+        )
+        .unwrap();
+        let parse = |data: &str| numbers_as_f64(serde_json::from_str(data).unwrap());
+        assert_eq!(parse(&json), parse(&data));
+    }
+
+    #[test]
+    fn test_total_investment_round_trip() {
+        let positions = from_string(
+            r#"[{"Name": "S&P 500", "Ticker": "SPY", "AssetClass": "Stocks", "PercentOf": 60}]"#,
+        );
+        assert_eq!(positions[0].get_percent_of(), Some(dec!(60)));
+        assert_eq!(positions[0].get_amount(), dec!(0));
+
+        let json = to_data_string(&positions, None, Some(dec!(10000)), None).unwrap();
+        assert_eq!(total_investment(&json), Some(dec!(10000)));
+        assert_eq!(portfolio_name(&json), None);
+        assert_eq!(from_string(&json)[0].get_percent_of(), Some(dec!(60)));
+        assert!(!to_string(&positions).unwrap().contains("TotalInvestment"));
+    }
+
+    fn unlisted_assets() -> Vec<PortfolioPosition> {
+        from_string(
+            r#"[
+                {"Name": "Private Loan", "AssetClass": "Loans", "Amount": 150, "ManualPrice": 12.5},
+                {
+                    "Name": "Employee Shares",
+                    "Ticker": "ACME",
+                    "AssetClass": "Stocks",
+                    "Amount": 40,
+                    "ManualPrice": 20,
+                    "CostBasis": 15,
+                    "PriceDate": "2024-06-07"
+                }
+            ]"#,
+        )
+    }
+
+    #[test]
+    fn test_manual_price_round_trip() {
+        let json = to_string(&unlisted_assets()).unwrap();
+        assert!(json.contains("\"ManualPrice\": 12.5"));
+        assert!(json.contains("\"PriceDate\": \"2024-06-07\""));
+        assert!(!to_string(&[savings_account()])
+            .unwrap()
+            .contains("ManualPrice"));
+
+        let positions = from_string(&json);
+        assert_eq!(positions[0].manual_price, Some(dec!(12.5)));
+        assert_eq!(
+            positions[1].price_date,
+            Some(Utc.with_ymd_and_hms(2024, 6, 7, 0, 0, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_manual_price_valuation() {
+        let positions = unlisted_assets();
+        let loan = &positions[0];
+        assert!(!loan.is_market_instrument());
+        assert_eq!(loan.get_balance(), dec!(1875));
+        assert_eq!(loan.get_last_spot(), Some(dec!(12.5)));
+        assert_eq!(loan.get_day_change(), None);
+
+        // the manual price wins over a quote of the ticker
+        let shares = positions[1].clone().with_last_spot(99.0);
+        assert!(!shares.is_market_instrument());
+        assert_eq!(shares.get_balance(), dec!(800));
+        assert_eq!(shares.get_unrealized_gain(), Some(dec!(200)));
+    }
+
+    #[tokio::test]
+    async fn test_handle_position_skips_manual_price() {
+        // the provider knows nothing, so any lookup would fail
+        let provider = MockProvider::new();
+        for mut position in unlisted_assets() {
+            let balance = position.get_balance();
+            let handled = handle_position(&provider, &mut position).await.unwrap();
+            assert_eq!(handled.get_balance(), balance);
+        }
+    }
+
+    #[test]
+    fn test_manual_price_staleness() {
+        let max_age = chrono::Duration::days(DEFAULT_MAX_QUOTE_AGE_DAYS);
+        let positions = unlisted_assets();
+        // without a PriceDate there is nothing to warn about
+        let now = Utc.with_ymd_and_hms(2024, 6, 12, 0, 0, 0).unwrap();
+        assert!(!positions[0].has_dated_price());
+        assert!(!positions[0].is_stale_at(max_age, now));
+
+        // priced on Friday, stale after Wednesday
+        let shares = &positions[1];
+        assert!(shares.has_dated_price());
+        assert!(!shares.is_stale_at(max_age, now));
+        assert!(shares.is_stale_at(max_age, now + chrono::Duration::days(1)));
+    }
+
+    #[test]
+    fn test_annual_expense_cost() {
+        let positions: Vec<PortfolioPosition> = from_string(
+            r#"[
+                {"Name": "World ETF", "Ticker": "EUNL.DE", "AssetClass": "Stocks", "Amount": 100, "ExpenseRatio": 0.2},
+                {"Name": "Apple", "Ticker": "AAPL", "AssetClass": "Stocks", "Amount": 5}
+            ]"#,
+        )
+        .into_iter()
+        .map(|position| position.with_last_spot(90.0))
+        .collect();
+        assert_eq!(positions[0].get_expense_ratio(), Some(dec!(0.2)));
+        assert_eq!(positions[0].annual_expense_cost(), dec!(18));
+        // the fees are part of the price, the balance doesn't change
+        assert_eq!(positions[0].get_balance(), dec!(9000));
+        assert_eq!(positions[1].annual_expense_cost(), dec!(0));
+    }
+
+    #[tokio::test]
+    async fn test_handle_position_uses_price_override() {
+        // "what if AAPL drops 20%", the provider has no quote for it
+        let provider = MockProvider::new();
+        let mut position = from_string(
+            r#"[{"Name": "Apple", "Ticker": "AAPL", "AssetClass": "Stocks", "Amount": 10, "CostBasis": 150, "PriceOverride": 136}]"#,
+        )
+        .remove(0);
+        // valued with the override even when no quotes are fetched
+        assert_eq!(position.get_balance(), dec!(1360));
+
+        let handled = handle_position(&provider, &mut position).await.unwrap();
+        assert!(handled.has_price_override());
+        assert_eq!(handled.get_last_spot(), Some(dec!(136)));
+        assert_eq!(handled.get_balance(), dec!(1360));
+        assert_eq!(handled.get_unrealized_gain(), Some(dec!(-140)));
+        assert_eq!(handled.get_day_change(), None);
+
+        // never stale, but always marked
+        let max_age = chrono::Duration::days(DEFAULT_MAX_QUOTE_AGE_DAYS);
+        assert!(!handled.is_stale(max_age));
+        assert_eq!(handled.get_marked_name(max_age), "Apple (what-if)");
+
+        // kept when saving, and ignored without a ticker
+        assert!(to_string(&[handled])
+            .unwrap()
+            .contains("\"PriceOverride\": 136"));
+        let cash = from_string(
+            r#"[{"Name": "Savings", "AssetClass": "Cash", "Amount": 100, "PriceOverride": 2}]"#,
+        )
+        .remove(0);
+        assert!(!cash.has_price_override());
+        assert_eq!(cash.get_balance(), dec!(100));
+    }
+
+    #[test]
+    fn test_tags_round_trip() {
+        let positions = from_string(
+            r#"[
+                {"Name": "World ETF", "AssetClass": "Stocks", "Amount": 5, "Tags": ["retirement", " kids ", ""]},
+                {"Name": "Savings", "AssetClass": "Cash", "Amount": 100}
+            ]"#,
+        );
+        assert_eq!(positions[0].get_tags(), ["retirement", "kids"]);
+        assert!(positions[1].get_tags().is_empty());
+
+        let json = to_string(&positions).unwrap();
+        let positions = from_string(&json);
+        assert_eq!(positions[0].get_tags(), ["retirement", "kids"]);
+        assert!(positions[1].get_tags().is_empty());
+        assert_eq!(json.matches("Tags").count(), 1);
+    }
+
+    #[test]
+    fn test_account_round_trip() {
+        let positions = from_string(
+            r#"[
+                {"Name": "World ETF", "AssetClass": "Stocks", "Amount": 5, "Account": "Roth IRA"},
+                {"Name": "Savings", "AssetClass": "Cash", "Amount": 100}
+            ]"#,
+        );
+        assert_eq!(positions[0].get_account(), Some("Roth IRA"));
+        assert_eq!(positions[1].get_account(), None);
+
+        let json = to_string(&positions).unwrap();
+        let positions = from_string(&json);
+        assert_eq!(positions[0].get_account(), Some("Roth IRA"));
+        assert_eq!(positions[1].get_account(), None);
+        assert_eq!(json.matches("Account").count(), 1);
+    }
+
+    #[test]
+    fn test_unrealized_gain() {
+        let positions = from_string(
+            r#"[
+                {"Ticker": "AAPL", "AssetClass": "Stocks", "Amount": 10, "CostBasis": 150},
+                {"Ticker": "MSFT", "AssetClass": "Stocks", "Amount": 10},
+                {"Name": "Savings", "AssetClass": "Cash", "Amount": 100, "CostBasis": 100}
+            ]"#,
+        );
+        let aapl = positions[0].clone().with_last_spot(170.0);
+        assert_eq!(aapl.get_cost_basis(), Some(dec!(150)));
+        assert_eq!(aapl.get_unrealized_gain(), Some(dec!(200)));
+        assert_eq!(positions[1].get_unrealized_gain(), None);
+        assert_eq!(positions[2].get_unrealized_gain(), None);
+    }
+
+    #[test]
+    fn test_annualized_return() {
+        let positions = from_string(
+            r#"[
+                {"Ticker": "AAPL", "AssetClass": "Stocks", "Amount": 10, "CostBasis": 100,
+                 "PurchaseDate": "2022-01-01"},
+                {"Ticker": "AAPL", "AssetClass": "Stocks", "Amount": 5, "CostBasis": 100,
+                 "PurchaseDate": "2023-07-01"},
+                {"Ticker": "MSFT", "AssetClass": "Stocks", "Amount": 10, "CostBasis": 300},
+                {"Ticker": "NVDA", "AssetClass": "Stocks", "Amount": 10, "PurchaseDate": "2022-01-01"},
+                {"Name": "Savings", "AssetClass": "Cash", "Amount": 100, "CostBasis": 100,
+                 "PurchaseDate": "2022-01-01"}
+            ]"#,
+        );
+        let as_of = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let assert_close = |actual: Option<f64>, expected: f64| {
+            let actual = actual.unwrap();
+            assert!(
+                (actual - expected).abs() < 1e-9,
+                "{actual} is not {expected}"
+            );
+        };
+
+        // doubled in 730 days: 2^(365/730) - 1
+        let aapl = positions[0].clone().with_last_spot(200.0);
+        assert_close(aapl.annualized_return(as_of), 2f64.sqrt() - 1.0);
+        // exactly a year is the simple return
+        let year = Utc.with_ymd_and_hms(2023, 1, 1, 0, 0, 0).unwrap();
+        assert_close(aapl.annualized_return(year), 1.0);
+        // a loss stays above -100%
+        let loss = positions[0].clone().with_last_spot(81.0);
+        assert_close(loss.annualized_return(as_of), 0.9 - 1.0);
+
+        // bought today (or later): the simple return instead of a huge exponent
+        let today = Utc.with_ymd_and_hms(2022, 1, 1, 18, 0, 0).unwrap();
+        assert_close(aapl.annualized_return(today), 1.0);
+
+        // merged lots are held since the earliest purchase
+        let merged = consolidate(positions[..2].to_vec())
+            .remove(0)
+            .with_last_spot(200.0);
+        assert_close(merged.annualized_return(as_of), 2f64.sqrt() - 1.0);
+
+        // without a purchase date or cost basis, and for cash
+        assert_eq!(
+            positions[2]
+                .clone()
+                .with_last_spot(330.0)
+                .annualized_return(as_of),
+            None
+        );
+        assert_eq!(
+            positions[3]
+                .clone()
+                .with_last_spot(500.0)
+                .annualized_return(as_of),
+            None
+        );
+        assert_eq!(positions[4].annualized_return(as_of), None);
+    }
+
+    #[test]
+    fn test_has_tags() {
+        let position = from_string(
+            r#"[{"Name": "World ETF", "AssetClass": "Stocks", "Amount": 5, "Tags": ["retirement", "kids"]}]"#,
+        )
+        .remove(0);
+
+        assert!(position.has_tags(&[]));
+        assert!(position.has_tags(&["retirement".to_string()]));
+        assert!(position.has_tags(&["retirement".to_string(), "kids".to_string()]));
+        assert!(position.has_tags(&["kids".to_string(), "".to_string()]));
+        assert!(position.has_tags(&["Retirement".to_string(), " KIDS ".to_string()]));
+        assert!(!position.has_tags(&["retirement".to_string(), "speculative".to_string()]));
+    }
+
+    #[test]
+    fn test_classification() {
+        let positions = from_string(
+            r#"[
+                {"Name": "Apple", "Ticker": "AAPL", "AssetClass": "Stocks", "Amount": 10},
+                {"Name": "Bitcoin", "Ticker": "BTC-EUR", "AssetClass": "Crypto", "Amount": 0.5},
+                {"Name": "Savings", "AssetClass": "Cash", "Amount": 100},
+                {"Name": "Checking", "AssetClass": "CASH", "Amount": 50},
+                {"Name": "Money Market", "Ticker": "SGOV", "AssetClass": "cash", "Amount": 5},
+                {"Name": "House", "AssetClass": "Real Estate", "Amount": 300000}
+            ]"#,
+        );
+        let classes: Vec<(bool, bool)> = positions
+            .iter()
+            .map(|p| (p.is_market_instrument(), p.is_cash()))
+            .collect();
+        assert_eq!(
+            classes,
+            [
+                (true, false),
+                (true, false),
+                (false, true),
+                (false, true),
+                (true, true),
+                (false, false)
+            ]
+        );
+
+        let crypto: Vec<bool> = positions.iter().map(|p| p.is_crypto()).collect();
+        assert_eq!(crypto, [false, true, false, false, false, false]);
+        assert_eq!(
+            positions[0].historic_price_window(),
+            chrono::Duration::days(3)
+        );
+        assert_eq!(
+            positions[1].historic_price_window(),
+            chrono::Duration::days(1)
+        );
+
+        // only market instruments are priced
+        let house = positions[5].clone().with_last_spot(1.5);
+        assert_eq!(house.get_balance(), dec!(300000));
+        assert_eq!(house.get_last_spot(), None);
+        let fund = positions[4].clone().with_last_spot(100.5);
+        assert_eq!(fund.get_balance(), dec!(502.5));
+    }
+
+    #[test]
+    fn test_builder() {
+        let position = PortfolioPosition::new("Apple", Some("AAPL"), "Stocks", dec!(10)).unwrap();
+        assert_eq!(position.get_ticker(), Some("AAPL"));
+        assert_eq!(position.get_amount(), dec!(10));
+        assert!(position.is_market_instrument());
+
+        // the same as the position of a data file
+        let from_data = from_string(
+            r#"[{"Name": "Savings", "AssetClass": "Cash", "Amount": 1000, "InterestRate": 3.65,
+                 "PaymentFrequencyDays": 30, "NextInterestPayment": "2024-01-31",
+                 "LastInterestPayment": "2024-01-01"}]"#,
+        );
+        assert_eq!(
+            serde_json::to_value(savings_account()).unwrap(),
+            serde_json::to_value(&from_data[0]).unwrap()
+        );
+        assert!(savings_account().is_cash_with_interest());
+
+        let loan = PortfolioPosition::builder("Car Loan", None, "Liability", dec!(10000))
+            .with_interest_rate(dec!(6))
+            .with_payment_frequency_days(30)
+            .with_next_interest_payment(at("2024-02-01"))
+            .with_payment_amount(dec!(300))
+            .build()
+            .unwrap();
+        assert!(loan.is_amortized_loan());
+    }
+
+    #[test]
+    fn test_builder_validation() {
+        let error = |builder: PositionBuilder| builder.build().unwrap_err();
+        let savings = || {
+            PortfolioPosition::builder("Savings", None, "Cash", dec!(1000))
+                .with_interest_rate(dec!(3.65))
+                .with_payment_frequency_days(30)
+                .with_next_interest_payment(at("2024-01-31"))
+        };
+
+        assert_eq!(
+            PortfolioPosition::new("Apple", Some("AAPL"), " ", dec!(10)).unwrap_err(),
+            "The position Apple is not valid: the AssetClass is empty"
+        );
+        assert!(PortfolioPosition::new("Apple", Some(""), "Stocks", dec!(10)).is_err());
+        assert!(PortfolioPosition::new("", None, "Cash", dec!(10)).is_err());
+        assert_eq!(
+            error(savings().with_payment_frequency_days(0)),
+            "The position Savings is not valid: the PaymentFrequencyDays has to be at least 1"
+        );
+        // the schedule and the rate only work together
+        assert!(error(
+            PortfolioPosition::builder("Savings", None, "Cash", dec!(1000))
+                .with_payment_frequency_days(30)
+        )
+        .ends_with("the interest payments have no InterestRate"));
+        assert!(error(
+            PortfolioPosition::builder("Savings", None, "Cash", dec!(1000))
+                .with_interest_rate(dec!(3.65))
+                .with_payment_frequency_days(30)
+        )
+        .ends_with("the InterestRate needs a PaymentFrequencyDays and a NextInterestPayment"));
+        assert!(
+            error(savings().with_last_interest_payment(at("2024-01-31")))
+                .ends_with("the NextInterestPayment is not after the LastInterestPayment")
+        );
+        // interest is paid on cash and charged on liabilities only
+        let mut bond = savings();
+        bond.position.asset_class = "Bonds".to_string();
+        assert!(error(bond).ends_with("interest is only paid on Cash or a Liability"));
+        let mut money_market = savings();
+        money_market.position.ticker = vec!["VMFXX".to_string()];
+        assert!(error(money_market).ends_with("not paid on a position priced by its Ticker"));
+        assert!(error(savings().with_payment_amount(dec!(100)))
+            .ends_with("only a Liability has a PaymentAmount"));
+    }
+
+    #[test]
+    fn test_interest_needs_cash_asset_class() {
+        let mut position = savings_account();
+        position.asset_class = "Bonds".to_string();
+        assert!(!position.is_cash_with_interest());
+
+        position.asset_class = "cash".to_string();
+        assert!(position.is_cash_with_interest());
+
+        position.ticker = vec!["SGOV".to_string()];
+        assert!(!position.is_cash_with_interest());
+    }
+
+    #[test]
+    fn test_calculate_interest() {
+        let position = savings_account();
+        assert_eq!(position.daily_interest_amount(), dec!(0.1));
+
+        let from = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let to = Utc.with_ymd_and_hms(2024, 1, 31, 0, 0, 0).unwrap();
+        assert_eq!(position.calculate_interest(from, to), dec!(3));
+        assert_eq!(position.calculate_interest(to, from), dec!(0));
+    }
+
+    #[test]
+    fn test_preview_interest_due_does_not_mutate() {
+        let position = savings_account();
+        let current_date = Utc.with_ymd_and_hms(2024, 2, 1, 0, 0, 0).unwrap();
+
+        let preview = position
+            .preview_interest_due(current_date, DEFAULT_CURRENCY_DECIMALS)
+            .unwrap();
+        assert_eq!(preview.interest, dec!(3));
+        assert_eq!(preview.new_amount, dec!(1003));
+        assert_eq!(preview.payments, 1);
+        assert_eq!(
+            preview.last_interest_payment,
+            Utc.with_ymd_and_hms(2024, 1, 31, 0, 0, 0).unwrap()
+        );
+        assert_eq!(
+            preview.next_interest_payment,
+            Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap()
+        );
+
+        assert_eq!(position.get_amount(), dec!(1000));
+        assert_eq!(
+            position.get_next_interest_payment(),
+            Some(Utc.with_ymd_and_hms(2024, 1, 31, 0, 0, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_preview_interest_not_due() {
+        let position = savings_account();
+        let current_date = Utc.with_ymd_and_hms(2024, 1, 15, 0, 0, 0).unwrap();
+        assert_eq!(
+            position.preview_interest_due(current_date, DEFAULT_CURRENCY_DECIMALS),
+            None
+        );
+
+        let stock = position_quoted_at(Some("AAPL"), None);
+        assert_eq!(
+            stock.preview_interest_due(current_date, DEFAULT_CURRENCY_DECIMALS),
+            None
+        );
+    }
+
+    #[test]
+    fn test_apply_interest_if_due() {
+        let mut position = savings_account();
+        let current_date = Utc.with_ymd_and_hms(2024, 3, 5, 0, 0, 0).unwrap();
+        let preview = position
+            .preview_interest_due(current_date, DEFAULT_CURRENCY_DECIMALS)
+            .unwrap();
+
+        // two payments are due, the second one compounds on the first
+        // and is rounded to cents (3.009 -> 3.01)
+        let interest = position
+            .apply_interest_if_due(current_date, DEFAULT_CURRENCY_DECIMALS)
+            .unwrap();
+        assert_eq!(preview.payments, 2);
+        assert_eq!(interest, preview.interest);
+        assert_eq!(interest, dec!(6.01));
+        assert_eq!(position.get_amount(), dec!(1006.01));
+        assert_eq!(position.get_amount(), preview.new_amount);
+        assert_eq!(
+            position.get_next_interest_payment(),
+            Some(Utc.with_ymd_and_hms(2024, 3, 31, 0, 0, 0).unwrap())
+        );
+
+        // nothing left to pay until the next payment date
+        assert_eq!(
+            position.apply_interest_if_due(current_date, DEFAULT_CURRENCY_DECIMALS),
+            None
+        );
+    }
+
+    #[test]
+    fn test_daily_interest_has_no_penny_drift() {
+        // 1000 at 5% is 0.136986... a day, every payment is rounded
+        let mut daily = from_string(
+            r#"[{
+                "Name": "Savings",
+                "AssetClass": "Cash",
+                "Amount": 1000,
+                "InterestRate": 5,
+                "PaymentFrequencyDays": 1,
+                "NextInterestPayment": "2024-01-02"
+            }]"#,
+        )
+        .remove(0);
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        assert_ne!(
+            daily.daily_interest_amount(),
+            round_to(daily.daily_interest_amount(), 2)
+        );
+
+        // the payments rounded one by one, each on the balance after the one before
+        let mut balance = dec!(1000);
+        let mut rounded = Decimal::ZERO;
+        for _ in 1..=30 {
+            let payment = round_to(balance * dec!(5) / dec!(100) / dec!(365), 2);
+            balance += payment;
+            rounded += payment;
+        }
+
+        let mut credited = Decimal::ZERO;
+        for day in 1..=30 {
+            let date = start + chrono::Duration::days(day);
+            credited += daily.apply_interest_if_due(date, 2).unwrap();
+        }
+
+        assert_eq!(rounded, dec!(4.2));
+        assert_eq!(credited, rounded);
+        assert_eq!(daily.get_amount(), dec!(1000) + rounded);
+    }
+
+    #[test]
+    fn test_year_of_daily_interest_is_exact() {
+        let position = savings_account();
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+
+        // 0.1 a day, which has no exact f64 representation
+        let mut sum = Decimal::ZERO;
+        for _ in 0..365 {
+            sum += position.daily_interest_amount();
+        }
+        assert_eq!(sum, dec!(36.5));
+        assert_eq!(
+            position.calculate_interest(start, start + chrono::Duration::days(365)),
+            dec!(36.5)
+        );
+    }
+
+    #[test]
+    fn test_to_decimal() {
+        assert_eq!(to_decimal(0.1), dec!(0.1));
+        assert_eq!(to_decimal(0.1) + to_decimal(0.2), dec!(0.3));
+        assert_eq!(to_decimal(170.25), dec!(170.25));
+        assert_eq!(to_f64(dec!(0.3)), 0.3);
+    }
+
+    #[test]
+    fn test_currency_decimals() {
+        assert_eq!(currency_decimals("EUR"), 2);
+        assert_eq!(currency_decimals("jpy"), 0);
+        assert_eq!(currency_decimals("KWD"), 3);
+
+        let mut position = savings_account();
+        position.amount = dec!(1000.005);
+        position.round_to_cents(0);
+        assert_eq!(position.get_amount(), dec!(1000));
     }
 }
@@ -0,0 +1,360 @@
+use crate::position::{handle_positions, PortfolioPosition, UNPRICED_MARKER};
+use crate::provider::PriceProvider;
+use yahoo_finance_api as yahoo;
+
+// Why a quote couldn't be fetched, so the user knows whether a retry helps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ErrorKind {
+    Timeout,
+    Network,
+    NotFound,
+    RateLimit,
+    Other,
+}
+
+impl ErrorKind {
+    pub fn of(error: &yahoo::YahooError) -> Self {
+        match error {
+            yahoo::YahooError::TooManyRequests(_) => ErrorKind::RateLimit,
+            yahoo::YahooError::ConnectionFailed(e) if e.is_timeout() => ErrorKind::Timeout,
+            yahoo::YahooError::NoResult | yahoo::YahooError::NoQuotes => ErrorKind::NotFound,
+            // the chart API answers an unknown symbol with the code "Not Found"
+            yahoo::YahooError::ApiError(message)
+                if message
+                    .code
+                    .as_deref()
+                    .is_some_and(|code| code.eq_ignore_ascii_case("not found")) =>
+            {
+                ErrorKind::NotFound
+            }
+            yahoo::YahooError::FetchFailed(message)
+                if message.to_lowercase().contains("not found") =>
+            {
+                ErrorKind::NotFound
+            }
+            yahoo::YahooError::ConnectionFailed(_)
+            | yahoo::YahooError::NoResponse
+            | yahoo::YahooError::FetchFailed(_) => ErrorKind::Network,
+            _ => ErrorKind::Other,
+        }
+    }
+
+    fn advice(&self) -> &'static str {
+        match self {
+            ErrorKind::Timeout => {
+                "Yahoo didn't answer in time, try again or raise request_timeout_seconds"
+            }
+            ErrorKind::Network => "check the connection (or the proxy) and try again",
+            ErrorKind::NotFound => "check the ticker, trying again won't help",
+            ErrorKind::RateLimit => "Yahoo limits the requests, try again in a few minutes",
+            ErrorKind::Other => "try again, -v logs the requests",
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            ErrorKind::Timeout => "timed out",
+            ErrorKind::Network => "network error",
+            ErrorKind::NotFound => "not found",
+            ErrorKind::RateLimit => "rate limited",
+            ErrorKind::Other => "error",
+        })
+    }
+}
+
+// A position whose quote couldn't be fetched
+#[derive(Debug, Clone, PartialEq)]
+pub struct PositionError {
+    pub name: String,
+    pub ticker: Option<String>,
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+// The positions in the order of the input, the failed ones unpriced (valued like
+// with --no-fetch and marked with UNPRICED_MARKER), and why they failed
+#[derive(Debug, Clone, Default)]
+pub struct PricingReport {
+    pub positions: Vec<PortfolioPosition>,
+    pub errors: Vec<PositionError>,
+}
+
+impl PricingReport {
+    // "1 of 3 tickers failed: GONE (not found)", without failures there is none
+    pub fn summary(&self) -> Option<String> {
+        (!self.errors.is_empty())
+            .then(|| ticker_summary(&self.errors, count_tickers(&self.positions)))
+    }
+}
+
+// The tickers (or ISINs) that are priced by a quote, positions of the same one
+// count once
+pub fn count_tickers(positions: &[PortfolioPosition]) -> usize {
+    let mut tickers: Vec<&str> = positions
+        .iter()
+        .filter(|position| position.is_market_instrument())
+        .filter_map(|position| position.get_ticker().or(position.get_isin()))
+        .collect();
+    tickers.sort();
+    tickers.dedup();
+    tickers.len()
+}
+
+// How many of the tickers failed and why, on one line
+pub fn ticker_summary(errors: &[PositionError], tickers: usize) -> String {
+    let mut failed: Vec<String> = Vec::new();
+    for error in errors {
+        let label = format!(
+            "{} ({})",
+            error.ticker.as_deref().unwrap_or(&error.name),
+            error.kind
+        );
+        if !failed.contains(&label) {
+            failed.push(label);
+        }
+    }
+    format!(
+        "{} of {} tickers failed: {}",
+        failed.len(),
+        tickers.max(failed.len()),
+        failed.join(", ")
+    )
+}
+
+// Prices the positions like handle_positions, but a failed position doesn't get
+// lost: the others are priced as usual and it is kept without a price
+pub async fn price_positions(
+    provider: &impl PriceProvider,
+    positions: Vec<PortfolioPosition>,
+    on_progress: impl Fn(usize, usize) + Sync,
+) -> PricingReport {
+    let results = handle_positions(provider, positions.clone(), on_progress).await;
+    let mut report = PricingReport::default();
+    for (position, result) in positions.into_iter().zip(results) {
+        match result {
+            Ok(priced) => report.positions.push(priced),
+            Err(e) => {
+                report.errors.push(PositionError {
+                    name: position.get_name().to_string(),
+                    ticker: position.get_ticker().map(|ticker| ticker.to_string()),
+                    kind: e.kind,
+                    message: e.to_string(),
+                });
+                report.positions.push(position.with_unpriced());
+            }
+        }
+    }
+    report
+}
+
+// The section after the output of a command: how many of the tickers failed, one
+// line per failed position and what to do about each kind of failure
+pub fn render_errors(errors: &[PositionError], tickers: usize) -> String {
+    if errors.is_empty() {
+        return String::new();
+    }
+    let mut output = format!(
+        "\n{}\n{} position(s) could not be priced and are shown as {UNPRICED_MARKER}:\n",
+        ticker_summary(errors, tickers),
+        errors.len()
+    );
+    for error in errors {
+        let label = match &error.ticker {
+            Some(ticker) if ticker != &error.name => format!("{} ({ticker})", error.name),
+            _ => error.name.clone(),
+        };
+        output.push_str(&format!("  {label}: {}: {}\n", error.kind, error.message));
+    }
+    let mut kinds: Vec<ErrorKind> = errors.iter().map(|error| error.kind).collect();
+    kinds.sort();
+    kinds.dedup();
+    for kind in kinds {
+        let kind_name = kind.to_string();
+        let mut chars = kind_name.chars();
+        let capitalized: String = chars
+            .next()
+            .map(|first| first.to_uppercase().chain(chars).collect())
+            .unwrap_or_default();
+        output.push_str(&format!("{capitalized}: {}\n", kind.advice()));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::from_string;
+    use crate::provider::MockProvider;
+    use rust_decimal::dec;
+
+    fn positions() -> Vec<PortfolioPosition> {
+        from_string(
+            r#"[
+                {"Name": "Apple", "Ticker": "AAPL", "AssetClass": "Stocks", "Amount": 10},
+                {"Name": "Delisted", "Ticker": "GONE", "AssetClass": "Stocks", "Amount": 5},
+                {"Name": "Microsoft", "Ticker": "MSFT", "AssetClass": "Stocks", "Amount": 2},
+                {"Name": "Busy", "Ticker": "BUSY", "AssetClass": "Stocks", "Amount": 1},
+                {"Name": "Offline", "Ticker": "DOWN", "AssetClass": "Stocks", "Amount": 1},
+                {"Name": "Savings", "AssetClass": "Cash", "Amount": 1000}
+            ]"#,
+        )
+    }
+
+    fn provider() -> MockProvider {
+        MockProvider::new()
+            .with_price("AAPL", 200.0)
+            .with_price("MSFT", 400.0)
+            .with_error("BUSY", || {
+                yahoo::YahooError::TooManyRequests("request url: chart/BUSY".to_string())
+            })
+            .with_error("DOWN", || {
+                yahoo::YahooError::FetchFailed("HTTP error: 503 Service Unavailable".to_string())
+            })
+    }
+
+    #[tokio::test]
+    async fn test_price_positions_with_failures() {
+        let report = price_positions(&provider(), positions(), |_, _| {}).await;
+
+        // nothing is lost and the order is kept
+        let names: Vec<&str> = report
+            .positions
+            .iter()
+            .map(|position| position.get_name())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "Apple",
+                "Delisted",
+                "Microsoft",
+                "Busy",
+                "Offline",
+                "Savings"
+            ]
+        );
+        let balances: Vec<_> = report
+            .positions
+            .iter()
+            .map(|position| position.get_balance())
+            .collect();
+        assert_eq!(
+            balances,
+            [dec!(2000), dec!(0), dec!(800), dec!(0), dec!(0), dec!(1000)]
+        );
+        let unpriced: Vec<bool> = report
+            .positions
+            .iter()
+            .map(|position| position.is_unpriced())
+            .collect();
+        assert_eq!(unpriced, [false, true, false, true, true, false]);
+        let max_age = chrono::Duration::days(3);
+        assert_eq!(
+            report.positions[1].get_marked_name(max_age),
+            "Delisted (unpriced)"
+        );
+
+        let kinds: Vec<(&str, ErrorKind)> = report
+            .errors
+            .iter()
+            .map(|error| (error.name.as_str(), error.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                ("Delisted", ErrorKind::NotFound),
+                ("Busy", ErrorKind::RateLimit),
+                ("Offline", ErrorKind::Network)
+            ]
+        );
+        assert_eq!(report.errors[0].ticker.as_deref(), Some("GONE"));
+    }
+
+    #[tokio::test]
+    async fn test_price_positions_without_failures() {
+        let positions: Vec<PortfolioPosition> = positions()
+            .into_iter()
+            .filter(|position| position.get_ticker().is_none_or(|ticker| ticker == "AAPL"))
+            .collect();
+        let report = price_positions(&provider(), positions, |_, _| {}).await;
+        assert_eq!(report.positions.len(), 2);
+        assert!(report.errors.is_empty());
+        assert_eq!(report.summary(), None);
+        assert_eq!(render_errors(&report.errors, 1), "");
+    }
+
+    #[tokio::test]
+    async fn test_summary_of_a_mistyped_ticker() {
+        let positions = from_string(
+            r#"[
+                {"Name": "Apple", "Ticker": "AAPL", "AssetClass": "Stocks", "Amount": 10},
+                {"Name": "Apple (IRA)", "Ticker": "AAPL", "AssetClass": "Stocks", "Amount": 5},
+                {"Name": "Typo", "Ticker": "APPL", "AssetClass": "Stocks", "Amount": 3}
+            ]"#,
+        );
+        let report = price_positions(&provider(), positions, |_, _| {}).await;
+
+        // the value of the priced ones is still there
+        let total: rust_decimal::Decimal = report
+            .positions
+            .iter()
+            .map(|position| position.get_balance())
+            .sum();
+        assert_eq!(total, dec!(3000));
+        assert_eq!(count_tickers(&report.positions), 2);
+        assert_eq!(
+            report.summary().as_deref(),
+            Some("1 of 2 tickers failed: APPL (not found)")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_render_errors() {
+        let report = price_positions(&provider(), positions(), |_, _| {}).await;
+        assert_eq!(
+            render_errors(&report.errors, count_tickers(&report.positions)),
+            "
+3 of 5 tickers failed: GONE (not found), BUSY (rate limited), DOWN (network error)
+3 position(s) could not be priced and are shown as (unpriced):
+  Delisted (GONE): not found: yahoo! finance returned an empty data set
+  Busy (BUSY): rate limited: Too many requests (rate limited by Yahoo) during: request url: chart/BUSY
+  Offline (DOWN): network error: fetching the data from yahoo! finance failed: HTTP error: 503 Service Unavailable
+Network error: check the connection (or the proxy) and try again
+Not found: check the ticker, trying again won't help
+Rate limited: Yahoo limits the requests, try again in a few minutes
+"
+        );
+    }
+
+    // the error of a chart response, as Yahoo sends it for the code
+    fn api_error(code: &str) -> yahoo::YahooError {
+        let chart: yahoo::YChart = serde_json::from_value(serde_json::json!({
+            "result": null,
+            "error": {"code": code, "description": "No data found, symbol may be delisted"}
+        }))
+        .unwrap();
+        yahoo::YahooError::ApiError(chart.error.unwrap())
+    }
+
+    #[test]
+    fn test_error_kinds() {
+        assert_eq!(ErrorKind::of(&api_error("Not Found")), ErrorKind::NotFound);
+        assert_eq!(ErrorKind::of(&api_error("Bad Request")), ErrorKind::Other);
+        assert_eq!(
+            ErrorKind::of(&yahoo::YahooError::FetchFailed(
+                "Ticker X not found".to_string()
+            )),
+            ErrorKind::NotFound
+        );
+        assert_eq!(
+            ErrorKind::of(&yahoo::YahooError::NoResponse),
+            ErrorKind::Network
+        );
+        assert_eq!(
+            ErrorKind::of(&yahoo::YahooError::InvalidCrumb),
+            ErrorKind::Other
+        );
+    }
+}
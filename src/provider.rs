@@ -0,0 +1,1341 @@
+use crate::data_file::write_atomic;
+use crate::pricing::ErrorKind;
+use crate::rate_limit;
+use chrono::prelude::*;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use time::OffsetDateTime;
+use yahoo_finance_api as yahoo;
+
+// Price of a ticker and the time it was quoted at
+#[derive(Debug, Clone, PartialEq)]
+pub struct PriceQuote {
+    pub price: f64,
+    pub time: Option<DateTime<Utc>>,
+    // close of the trading day before, for the day change
+    pub previous_close: Option<f64>,
+    // open of the trading day the quote is from, for the move over that day
+    pub open: Option<f64>,
+    // the currency the ticker is quoted in, if Yahoo reports it
+    pub currency: Option<String>,
+}
+
+// A result of a symbol search, e.g. AAPL on NMS for "apple"
+#[derive(Debug, Clone, PartialEq)]
+pub struct SymbolMatch {
+    pub symbol: String,
+    pub name: String,
+    pub exchange: String,
+    // EQUITY, ETF, CRYPTOCURRENCY, MUTUALFUND, ...
+    pub quote_type: String,
+}
+
+// Which close price to use for historic valuations
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum PriceAdjustment {
+    // Adjusted for splits and dividends (Yahoo's adjclose), so it can be
+    // multiplied with today's amount. Used for return calculations (default).
+    #[default]
+    Adjusted,
+    // The price that was actually traded on the day, e.g. to match a
+    // brokerage statement from before a split
+    Raw,
+}
+
+impl std::fmt::Display for PriceAdjustment {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PriceAdjustment::Adjusted => write!(f, "adjusted"),
+            PriceAdjustment::Raw => write!(f, "raw"),
+        }
+    }
+}
+
+// A historic close price, the kind of close it is and the trading day it is from.
+// The day is later than the requested date if the market was closed on it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HistoricPrice {
+    pub price: f64,
+    pub adjustment: PriceAdjustment,
+    pub date: NaiveDate,
+}
+
+// Why a provider has no price (or name, or splits) for a ticker. The kind tells
+// the user whether trying again helps, the message is shown as it is.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuoteError {
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+impl QuoteError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        QuoteError {
+            kind,
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for QuoteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for QuoteError {}
+
+impl From<yahoo::YahooError> for QuoteError {
+    fn from(error: yahoo::YahooError) -> Self {
+        QuoteError::new(ErrorKind::of(&error), error.to_string())
+    }
+}
+
+// A split of a ticker on the day of the date. Yahoo reports e.g. the 10-for-1
+// split of NVDA in 2024 as numerator 10, denominator 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Split {
+    pub date: DateTime<Utc>,
+    pub numerator: f64,
+    pub denominator: f64,
+}
+
+impl From<yahoo::Split> for Split {
+    fn from(split: yahoo::Split) -> Self {
+        Split {
+            date: DateTime::from_timestamp(split.date, 0).unwrap_or_default(),
+            numerator: split.numerator,
+            denominator: split.denominator,
+        }
+    }
+}
+
+fn to_splits(splits: Vec<yahoo::Split>) -> Vec<Split> {
+    splits.into_iter().map(Split::from).collect()
+}
+
+// Shares held after the split for every share held before
+pub fn split_ratio(split: &Split) -> f64 {
+    split.numerator / split.denominator
+}
+
+// Yahoo's close prices are adjusted for all later splits. Undo the
+// splits that happened after `timestamp` to get the price traded back then.
+pub fn unadjust_for_splits(close: f64, timestamp: i64, splits: &[Split]) -> f64 {
+    splits
+        .iter()
+        .filter(|split| split.date.timestamp() > timestamp && split.denominator != 0.0)
+        .fold(close, |price, split| price * split_ratio(split))
+}
+
+// Yahoo's timestamps are unix seconds, so they are UTC whatever the timezone of
+// the exchange is. None for a timestamp out of chrono's range.
+pub fn quote_time(quote: &yahoo::Quote) -> Option<DateTime<Utc>> {
+    DateTime::from_timestamp(quote.timestamp, 0)
+}
+
+fn trading_day(quote: &yahoo::Quote) -> Option<NaiveDate> {
+    quote_time(quote).map(|time| time.date_naive())
+}
+
+// A day without trades can come back with a close of NaN or 0
+pub fn has_valid_close(quote: &yahoo::Quote) -> bool {
+    quote.close.is_finite() && quote.close != 0.0
+}
+
+// The most recent quote with a valid close, the price of a closed market
+pub fn latest_valid_quote(quotes: &[yahoo::Quote]) -> Option<&yahoo::Quote> {
+    quotes.iter().rev().find(|quote| has_valid_close(quote))
+}
+
+// The first quote with a valid close, the close closest to the start of a history
+pub fn earliest_valid_quote(quotes: &[yahoo::Quote]) -> Option<&yahoo::Quote> {
+    quotes.iter().find(|quote| has_valid_close(quote))
+}
+
+// Close of the trading day before the latest quote, from the daily quotes of the
+// last month. Yahoo can return the current day twice, so the day has to differ,
+// not just the timestamp. If the latest quote is from before today the market hasn't
+// opened yet and nothing changed today, so the latest close is the previous one.
+pub fn previous_close(
+    quotes: &[yahoo::Quote],
+    latest: &yahoo::Quote,
+    today: NaiveDate,
+) -> Option<f64> {
+    let latest_day = trading_day(latest)?;
+    if latest_day < today {
+        return Some(latest.close);
+    }
+    quotes
+        .iter()
+        .rev()
+        .filter(|previous| has_valid_close(previous))
+        .find(|previous| trading_day(previous).is_some_and(|day| day < latest_day))
+        .map(|previous| previous.close)
+}
+
+// Days after the requested date searched for a historic close. Exchanges are closed
+// on weekends and holidays, crypto trades every day and needs a single day.
+pub const HISTORIC_WINDOW_DAYS: i64 = 3;
+pub const CRYPTO_HISTORIC_WINDOW_DAYS: i64 = 1;
+
+// Fiat currencies Yahoo quotes crypto pairs in, e.g. BTC-EUR
+const CRYPTO_QUOTE_CURRENCIES: [&str; 6] = ["USD", "EUR", "GBP", "JPY", "CHF", "USDT"];
+
+// Heuristic for tickers of crypto pairs: a dash followed by a fiat currency
+// (BTC-USD, ETH-EUR). Share classes like BRK-B have no currency suffix.
+pub fn is_crypto_ticker(ticker: &str) -> bool {
+    ticker.rsplit_once('-').is_some_and(|(base, quote)| {
+        !base.is_empty() && CRYPTO_QUOTE_CURRENCIES.contains(&quote.to_uppercase().as_str())
+    })
+}
+
+// Source of the prices used to value the positions. Yahoo Finance in production,
+// tests use a MockProvider with canned prices so they can run offline.
+pub trait PriceProvider {
+    // Latest available price, the last close if the market is closed
+    fn get_latest_price(
+        &self,
+        ticker: &str,
+    ) -> impl Future<Output = Result<PriceQuote, QuoteError>> + Send;
+
+    // Close price at (or shortly after) the given date, the first one in the window after
+    // it. A longer window finds a close after a long market closure, e.g. a holiday week.
+    fn get_historic_price(
+        &self,
+        ticker: &str,
+        date: DateTime<Utc>,
+        adjustment: PriceAdjustment,
+        window: chrono::Duration,
+    ) -> impl Future<Output = Result<HistoricPrice, QuoteError>> + Send;
+
+    // Splits of the ticker after the given date, oldest first
+    fn get_splits(
+        &self,
+        ticker: &str,
+        since: DateTime<Utc>,
+    ) -> impl Future<Output = Result<Vec<Split>, QuoteError>> + Send;
+
+    // Short name of the ticker, e.g. "Apple Inc." for AAPL
+    fn get_name(&self, ticker: &str) -> impl Future<Output = Result<String, QuoteError>> + Send;
+
+    // Symbols found for a search, e.g. for an ISIN, the best match first
+    fn find_symbols(
+        &self,
+        query: &str,
+    ) -> impl Future<Output = Result<Vec<String>, QuoteError>> + Send;
+
+    // The matches of a search for a company name, a partial ticker or an ISIN,
+    // the best match first
+    fn search_symbols(
+        &self,
+        query: &str,
+    ) -> impl Future<Output = Result<Vec<SymbolMatch>, QuoteError>> + Send;
+
+    // Whether a position without a Name gets one from get_name when it is priced,
+    // without it is shown by its ticker
+    fn looks_up_names(&self) -> bool {
+        true
+    }
+}
+
+impl PriceProvider for yahoo::YahooConnector {
+    async fn get_latest_price(&self, ticker: &str) -> Result<PriceQuote, QuoteError> {
+        log::debug!("Fetching the latest quote of {ticker}");
+        let response = rate_limit::shared()
+            .send(|| self.get_latest_quotes(ticker, "1d"))
+            .await
+            .inspect_err(|e| log::info!("Quote request for {ticker} failed: {e}"))?;
+        let quotes = response.quotes()?;
+        let quote = match response.last_quote() {
+            Ok(quote) if has_valid_close(&quote) => quote,
+            // if the market is closed, try to get the last available price
+            result => {
+                let Some(quote) = latest_valid_quote(&quotes).cloned() else {
+                    log::warn!(
+                        "No valid close of {ticker} in the response, its price is unavailable"
+                    );
+                    return Err(result.err().unwrap_or(yahoo::YahooError::NoQuotes).into());
+                };
+                log::info!(
+                    "No current quote of {ticker}, the market is probably closed: using the close of {}",
+                    quote_time(&quote)
+                        .map(|time| time.format("%Y-%m-%d %H:%M UTC").to_string())
+                        .unwrap_or_default()
+                );
+                quote
+            }
+        };
+        let previous_close = previous_close(&quotes, &quote, Utc::now().date_naive());
+        log::debug!(
+            "{ticker}: {} at timestamp {}, previous close {previous_close:?}",
+            quote.close,
+            quote.timestamp
+        );
+
+        Ok(PriceQuote {
+            price: quote.close,
+            time: quote_time(&quote),
+            previous_close,
+            open: Some(quote.open).filter(|open| open.is_finite() && *open > 0.0),
+            currency: response
+                .metadata()
+                .ok()
+                .and_then(|metadata| metadata.currency),
+        })
+    }
+
+    async fn get_historic_price(
+        &self,
+        ticker: &str,
+        date: DateTime<Utc>,
+        adjustment: PriceAdjustment,
+        window: chrono::Duration,
+    ) -> Result<HistoricPrice, QuoteError> {
+        let start = OffsetDateTime::from_unix_timestamp(date.timestamp()).unwrap();
+        let end = start + time::Duration::seconds(window.num_seconds());
+
+        log::debug!(
+            "Fetching the {adjustment:?} close of {ticker} from {} within {} days",
+            date.format("%Y-%m-%d"),
+            window.num_days()
+        );
+        let response = rate_limit::shared()
+            .send(|| self.get_quote_history(ticker, start, end))
+            .await
+            .inspect_err(|e| log::info!("History request for {ticker} failed: {e}"))?;
+        let quotes = response.quotes()?;
+        let quote = earliest_valid_quote(&quotes).ok_or(yahoo::YahooError::NoQuotes)?;
+        let close_date = trading_day(quote).unwrap_or(date.date_naive());
+        log::debug!("{ticker}: historic close {} of {close_date}", quote.close);
+
+        let price = match adjustment {
+            PriceAdjustment::Adjusted => quote.adjclose,
+            PriceAdjustment::Raw => {
+                // the splits since the date are only part of a response covering them
+                let until_now = rate_limit::shared()
+                    .send(|| {
+                        self.get_quote_history_interval(
+                            ticker,
+                            start,
+                            OffsetDateTime::now_utc(),
+                            "1mo",
+                        )
+                    })
+                    .await?;
+                let splits = to_splits(until_now.splits()?);
+                if !splits.is_empty() {
+                    log::debug!(
+                        "{ticker}: undoing {} split(s) since the close",
+                        splits.len()
+                    );
+                }
+                unadjust_for_splits(quote.close, quote.timestamp, &splits)
+            }
+        };
+
+        Ok(HistoricPrice {
+            price,
+            adjustment,
+            date: close_date,
+        })
+    }
+
+    async fn get_splits(
+        &self,
+        ticker: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<Split>, QuoteError> {
+        // monthly quotes are enough, the splits are events of the whole range
+        let start = OffsetDateTime::from_unix_timestamp(since.timestamp()).unwrap();
+        let response = rate_limit::shared()
+            .send(|| {
+                self.get_quote_history_interval(ticker, start, OffsetDateTime::now_utc(), "1mo")
+            })
+            .await?;
+        let mut splits = to_splits(response.splits()?);
+        splits.retain(|split| split.date > since);
+        Ok(splits)
+    }
+
+    async fn get_name(&self, ticker: &str) -> Result<String, QuoteError> {
+        let resp = rate_limit::shared()
+            .send(|| self.search_ticker(ticker))
+            .await?;
+
+        if let Some(item) = resp.quotes.first() {
+            Ok(item.short_name.clone())
+        } else {
+            Err(yahoo::YahooError::NoResult.into())
+        }
+    }
+
+    async fn find_symbols(&self, query: &str) -> Result<Vec<String>, QuoteError> {
+        let matches = self.search_symbols(query).await?;
+        Ok(matches.into_iter().map(|found| found.symbol).collect())
+    }
+
+    async fn search_symbols(&self, query: &str) -> Result<Vec<SymbolMatch>, QuoteError> {
+        log::debug!("Searching the symbols of {query}");
+        let resp = rate_limit::shared()
+            .send(|| self.search_ticker(query))
+            .await?;
+        Ok(resp
+            .quotes
+            .into_iter()
+            .map(|item| SymbolMatch {
+                // not every result has a short name
+                name: if item.short_name.is_empty() {
+                    item.long_name
+                } else {
+                    item.short_name
+                },
+                symbol: item.symbol,
+                exchange: item.exchange,
+                quote_type: item.quote_type,
+            })
+            .collect())
+    }
+}
+
+// A quote that didn't change since the fetch before is from a closed market
+const CLOSED_MARKET_MAX_AGE_MINUTES: i64 = 15;
+
+struct CachedQuote {
+    quote: PriceQuote,
+    fetched_at: DateTime<Utc>,
+    // the quote time was the same as on the fetch before
+    unchanged: bool,
+}
+
+// Keeps the latest quotes of another provider, e.g. across the refreshes of
+// balances --watch. A quote is fetched again once it is older than max_age, or
+// after 15 minutes if it didn't change on the last fetch because the market is
+// closed. Names and the symbols found for a search are kept for good. Historic
+// prices and splits are not cached.
+pub struct CachedProvider<P> {
+    inner: P,
+    max_age: chrono::Duration,
+    // the current time, replaced in tests
+    clock: Box<dyn Fn() -> DateTime<Utc> + Send + Sync>,
+    quotes: Mutex<HashMap<String, CachedQuote>>,
+    names: Mutex<HashMap<String, String>>,
+    symbols: Mutex<HashMap<String, Vec<String>>>,
+    // false for fetch_names = false in the config file
+    name_lookup: bool,
+}
+
+impl<P> CachedProvider<P> {
+    pub fn new(inner: P, max_age: chrono::Duration) -> Self {
+        CachedProvider {
+            inner,
+            max_age,
+            clock: Box::new(Utc::now),
+            quotes: Mutex::new(HashMap::new()),
+            names: Mutex::new(HashMap::new()),
+            symbols: Mutex::new(HashMap::new()),
+            name_lookup: true,
+        }
+    }
+
+    pub fn with_name_lookup(mut self, name_lookup: bool) -> Self {
+        self.name_lookup = name_lookup;
+        self
+    }
+
+    #[cfg(test)]
+    pub fn with_clock(mut self, clock: impl Fn() -> DateTime<Utc> + Send + Sync + 'static) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    #[cfg(test)]
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+
+    fn cached_quote(&self, ticker: &str) -> Option<PriceQuote> {
+        let now = (self.clock)();
+        let quotes = self.quotes.lock().unwrap();
+        let cached = quotes.get(ticker)?;
+        let max_age = if cached.unchanged {
+            chrono::Duration::minutes(CLOSED_MARKET_MAX_AGE_MINUTES).max(self.max_age)
+        } else {
+            self.max_age
+        };
+        (now - cached.fetched_at < max_age).then(|| cached.quote.clone())
+    }
+}
+
+impl<P: PriceProvider + Sync> PriceProvider for CachedProvider<P> {
+    async fn get_latest_price(&self, ticker: &str) -> Result<PriceQuote, QuoteError> {
+        if let Some(quote) = self.cached_quote(ticker) {
+            log::debug!("Cache hit for the quote of {ticker}");
+            return Ok(quote);
+        }
+        log::debug!("Cache miss for the quote of {ticker}");
+        let quote = self.inner.get_latest_price(ticker).await?;
+        let mut quotes = self.quotes.lock().unwrap();
+        let unchanged = quote.time.is_some()
+            && quotes
+                .get(ticker)
+                .is_some_and(|cached| cached.quote.time == quote.time);
+        if unchanged {
+            log::debug!(
+                "The quote of {ticker} didn't change, checking again in {CLOSED_MARKET_MAX_AGE_MINUTES} minutes"
+            );
+        }
+        quotes.insert(
+            ticker.to_string(),
+            CachedQuote {
+                quote: quote.clone(),
+                fetched_at: (self.clock)(),
+                unchanged,
+            },
+        );
+        Ok(quote)
+    }
+
+    async fn get_historic_price(
+        &self,
+        ticker: &str,
+        date: DateTime<Utc>,
+        adjustment: PriceAdjustment,
+        window: chrono::Duration,
+    ) -> Result<HistoricPrice, QuoteError> {
+        self.inner
+            .get_historic_price(ticker, date, adjustment, window)
+            .await
+    }
+
+    async fn get_splits(
+        &self,
+        ticker: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<Split>, QuoteError> {
+        self.inner.get_splits(ticker, since).await
+    }
+
+    async fn get_name(&self, ticker: &str) -> Result<String, QuoteError> {
+        if let Some(name) = self.names.lock().unwrap().get(ticker) {
+            return Ok(name.clone());
+        }
+        let name = self.inner.get_name(ticker).await?;
+        self.names
+            .lock()
+            .unwrap()
+            .insert(ticker.to_string(), name.clone());
+        Ok(name)
+    }
+
+    async fn find_symbols(&self, query: &str) -> Result<Vec<String>, QuoteError> {
+        if let Some(symbols) = self.symbols.lock().unwrap().get(query) {
+            log::debug!("Cache hit for the symbols of {query}");
+            return Ok(symbols.clone());
+        }
+        let symbols = self.inner.find_symbols(query).await?;
+        // nothing found may be found later, e.g. once Yahoo lists a new fund
+        if !symbols.is_empty() {
+            self.symbols
+                .lock()
+                .unwrap()
+                .insert(query.to_string(), symbols.clone());
+        }
+        Ok(symbols)
+    }
+
+    async fn search_symbols(&self, query: &str) -> Result<Vec<SymbolMatch>, QuoteError> {
+        self.inner.search_symbols(query).await
+    }
+
+    fn looks_up_names(&self) -> bool {
+        self.name_lookup && self.inner.looks_up_names()
+    }
+}
+
+// Keeps the names of the tickers in a file across runs (names.json next to the
+// config file), so a position without a Name is looked up once and not on every
+// run. It is only a cache: a file that can't be read is ignored and the names are
+// looked up again. Everything else is passed to the inner provider.
+pub struct StoredNames<P> {
+    inner: P,
+    path: PathBuf,
+    names: Mutex<BTreeMap<String, String>>,
+    // a name was added since the file was read
+    changed: AtomicBool,
+    // false for fetch_names = false in the config file
+    name_lookup: bool,
+}
+
+impl<P> StoredNames<P> {
+    pub fn open(inner: P, path: PathBuf) -> Self {
+        let names = match std::fs::read_to_string(&path) {
+            Ok(data) => serde_json::from_str(&data).unwrap_or_else(|e| {
+                log::warn!("Ignoring the names in {}: {e}", path.display());
+                BTreeMap::new()
+            }),
+            Err(_) => BTreeMap::new(),
+        };
+        StoredNames {
+            inner,
+            path,
+            names: Mutex::new(names),
+            changed: AtomicBool::new(false),
+            name_lookup: true,
+        }
+    }
+
+    pub fn with_name_lookup(mut self, name_lookup: bool) -> Self {
+        self.name_lookup = name_lookup;
+        self
+    }
+
+    // Writes the names to the file if one was added
+    pub fn save(&self) -> Result<(), String> {
+        if !self.changed.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        if let Some(directory) = self.path.parent() {
+            std::fs::create_dir_all(directory)
+                .map_err(|e| format!("Failed to create {}: {e}", directory.display()))?;
+        }
+        let data = serde_json::to_string_pretty(&*self.names.lock().unwrap())
+            .map_err(|e| format!("Failed to serialize the names: {e}"))?;
+        write_atomic(&self.path.to_string_lossy(), &data)?;
+        self.changed.store(false, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+impl<P: PriceProvider + Sync> PriceProvider for StoredNames<P> {
+    async fn get_latest_price(&self, ticker: &str) -> Result<PriceQuote, QuoteError> {
+        self.inner.get_latest_price(ticker).await
+    }
+
+    async fn get_historic_price(
+        &self,
+        ticker: &str,
+        date: DateTime<Utc>,
+        adjustment: PriceAdjustment,
+        window: chrono::Duration,
+    ) -> Result<HistoricPrice, QuoteError> {
+        self.inner
+            .get_historic_price(ticker, date, adjustment, window)
+            .await
+    }
+
+    async fn get_splits(
+        &self,
+        ticker: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<Split>, QuoteError> {
+        self.inner.get_splits(ticker, since).await
+    }
+
+    async fn get_name(&self, ticker: &str) -> Result<String, QuoteError> {
+        if let Some(name) = self.names.lock().unwrap().get(ticker) {
+            log::debug!("The name of {ticker} is stored: {name}");
+            return Ok(name.clone());
+        }
+        let name = self.inner.get_name(ticker).await?;
+        self.names
+            .lock()
+            .unwrap()
+            .insert(ticker.to_string(), name.clone());
+        self.changed.store(true, Ordering::SeqCst);
+        Ok(name)
+    }
+
+    async fn find_symbols(&self, query: &str) -> Result<Vec<String>, QuoteError> {
+        self.inner.find_symbols(query).await
+    }
+
+    async fn search_symbols(&self, query: &str) -> Result<Vec<SymbolMatch>, QuoteError> {
+        self.inner.search_symbols(query).await
+    }
+
+    fn looks_up_names(&self) -> bool {
+        self.name_lookup && self.inner.looks_up_names()
+    }
+}
+
+// Canned prices for offline tests. Tickers without a price fail with NoQuotes,
+// tickers without a name fail with NoResult, searches without symbols or matches
+// find none.
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct MockProvider {
+    prices: std::collections::HashMap<String, f64>,
+    previous_closes: std::collections::HashMap<String, f64>,
+    opens: std::collections::HashMap<String, f64>,
+    // quotes are from now unless set otherwise
+    quote_times: std::collections::HashMap<String, DateTime<Utc>>,
+    historic_prices: std::collections::HashMap<String, f64>,
+    names: std::collections::HashMap<String, String>,
+    currencies: std::collections::HashMap<String, String>,
+    splits: std::collections::HashMap<String, Vec<Split>>,
+    // the window of every historic request, to check which one a ticker got
+    historic_windows: std::sync::Mutex<Vec<(String, chrono::Duration)>>,
+    // the tickers of every request for a latest price, to count them
+    latest_requests: std::sync::Mutex<Vec<String>>,
+    symbols: std::collections::HashMap<String, Vec<String>>,
+    matches: std::collections::HashMap<String, Vec<SymbolMatch>>,
+    searches: std::sync::Mutex<Vec<String>>,
+    // the error a request for the latest price of a ticker fails with
+    errors: std::collections::HashMap<String, fn() -> yahoo::YahooError>,
+}
+
+#[cfg(test)]
+impl MockProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_price(mut self, ticker: &str, price: f64) -> Self {
+        self.prices.insert(ticker.to_string(), price);
+        self
+    }
+
+    pub fn with_previous_close(mut self, ticker: &str, price: f64) -> Self {
+        self.previous_closes.insert(ticker.to_string(), price);
+        self
+    }
+
+    pub fn with_open(mut self, ticker: &str, price: f64) -> Self {
+        self.opens.insert(ticker.to_string(), price);
+        self
+    }
+
+    pub fn with_quote_time(mut self, ticker: &str, time: DateTime<Utc>) -> Self {
+        self.quote_times.insert(ticker.to_string(), time);
+        self
+    }
+
+    pub fn with_historic_price(mut self, ticker: &str, price: f64) -> Self {
+        self.historic_prices.insert(ticker.to_string(), price);
+        self
+    }
+
+    pub fn with_name(mut self, ticker: &str, name: &str) -> Self {
+        self.names.insert(ticker.to_string(), name.to_string());
+        self
+    }
+
+    pub fn with_currency(mut self, ticker: &str, currency: &str) -> Self {
+        self.currencies
+            .insert(ticker.to_string(), currency.to_string());
+        self
+    }
+
+    pub fn with_splits(mut self, ticker: &str, splits: Vec<Split>) -> Self {
+        self.splits.insert(ticker.to_string(), splits);
+        self
+    }
+
+    pub fn with_symbols(mut self, query: &str, symbols: &[&str]) -> Self {
+        self.symbols.insert(
+            query.to_string(),
+            symbols.iter().map(|symbol| symbol.to_string()).collect(),
+        );
+        self
+    }
+
+    pub fn with_error(mut self, ticker: &str, error: fn() -> yahoo::YahooError) -> Self {
+        self.errors.insert(ticker.to_string(), error);
+        self
+    }
+
+    pub fn with_matches(mut self, query: &str, matches: Vec<SymbolMatch>) -> Self {
+        self.matches.insert(query.to_string(), matches);
+        self
+    }
+
+    pub fn searches(&self, query: &str) -> usize {
+        self.searches
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|searched| *searched == query)
+            .count()
+    }
+
+    pub fn latest_requests(&self, ticker: &str) -> usize {
+        self.latest_requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|requested| *requested == ticker)
+            .count()
+    }
+
+    // the window of the latest historic request of the ticker
+    pub fn historic_window(&self, ticker: &str) -> Option<chrono::Duration> {
+        self.historic_windows
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .find(|(requested, _)| requested == ticker)
+            .map(|(_, window)| *window)
+    }
+}
+
+// A split on the given day, at the time Yahoo reports it
+#[cfg(test)]
+pub fn mock_split(date: &str, numerator: f64, denominator: f64) -> Split {
+    Split {
+        date: NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .unwrap()
+            .and_hms_opt(13, 30, 0)
+            .unwrap()
+            .and_utc(),
+        numerator,
+        denominator,
+    }
+}
+
+// The positions of the JSON data, priced with the mock provider
+#[cfg(test)]
+pub async fn priced(
+    provider: &MockProvider,
+    data: &str,
+) -> Vec<crate::position::PortfolioPosition> {
+    let mut positions = Vec::new();
+    for mut position in crate::position::from_string(data) {
+        positions.push(
+            crate::position::handle_position(provider, &mut position)
+                .await
+                .unwrap(),
+        );
+    }
+    positions
+}
+
+// priced, for the tests that don't run async
+#[cfg(test)]
+pub fn priced_blocking(
+    provider: &MockProvider,
+    data: &str,
+) -> Vec<crate::position::PortfolioPosition> {
+    futures::executor::block_on(priced(provider, data))
+}
+
+// The positions of the JSON data, every Ticker quoted at the price given for it
+#[cfg(test)]
+pub fn priced_at(
+    data: &str,
+    price: impl Fn(&str) -> f64,
+) -> Vec<crate::position::PortfolioPosition> {
+    let tickers: Vec<String> = crate::position::from_string(data)
+        .iter()
+        .filter_map(|position| position.get_ticker().map(str::to_string))
+        .collect();
+    let provider = tickers
+        .iter()
+        .fold(MockProvider::new(), |provider, ticker| {
+            provider.with_price(ticker, price(ticker))
+        });
+    priced_blocking(&provider, data)
+}
+
+#[cfg(test)]
+impl PriceProvider for MockProvider {
+    async fn get_latest_price(&self, ticker: &str) -> Result<PriceQuote, QuoteError> {
+        self.latest_requests
+            .lock()
+            .unwrap()
+            .push(ticker.to_string());
+        if let Some(error) = self.errors.get(ticker) {
+            return Err(error().into());
+        }
+        let price = *self.prices.get(ticker).ok_or(yahoo::YahooError::NoQuotes)?;
+        Ok(PriceQuote {
+            price,
+            time: Some(
+                self.quote_times
+                    .get(ticker)
+                    .copied()
+                    .unwrap_or_else(Utc::now),
+            ),
+            previous_close: self.previous_closes.get(ticker).copied(),
+            open: self.opens.get(ticker).copied(),
+            currency: self.currencies.get(ticker).cloned(),
+        })
+    }
+
+    async fn get_historic_price(
+        &self,
+        ticker: &str,
+        date: DateTime<Utc>,
+        adjustment: PriceAdjustment,
+        window: chrono::Duration,
+    ) -> Result<HistoricPrice, QuoteError> {
+        self.historic_windows
+            .lock()
+            .unwrap()
+            .push((ticker.to_string(), window));
+        let price = *self
+            .historic_prices
+            .get(ticker)
+            .ok_or(yahoo::YahooError::NoQuotes)?;
+        // the close is from the requested day
+        Ok(HistoricPrice {
+            price,
+            adjustment,
+            date: date.date_naive(),
+        })
+    }
+
+    async fn get_splits(
+        &self,
+        ticker: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<Split>, QuoteError> {
+        Ok(self
+            .splits
+            .get(ticker)
+            .into_iter()
+            .flatten()
+            .filter(|split| split.date > since)
+            .cloned()
+            .collect())
+    }
+
+    async fn get_name(&self, ticker: &str) -> Result<String, QuoteError> {
+        self.names
+            .get(ticker)
+            .cloned()
+            .ok_or(yahoo::YahooError::NoResult.into())
+    }
+
+    async fn find_symbols(&self, query: &str) -> Result<Vec<String>, QuoteError> {
+        self.searches.lock().unwrap().push(query.to_string());
+        Ok(self.symbols.get(query).cloned().unwrap_or_default())
+    }
+
+    async fn search_symbols(&self, query: &str) -> Result<Vec<SymbolMatch>, QuoteError> {
+        self.searches.lock().unwrap().push(query.to_string());
+        Ok(self.matches.get(query).cloned().unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_yahoo::MockYahoo;
+    use crate::position::{connector_with, NetworkSettings};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_get_name() {
+        let yahoo = MockYahoo::aapl().await;
+        let connector = yahoo.connector();
+        assert_eq!(connector.get_name("AAPL").await.unwrap(), "Apple Inc.");
+        assert_eq!(
+            connector.get_name("NOPE").await.unwrap_err().kind,
+            ErrorKind::NotFound
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_latest_price() {
+        let yahoo = MockYahoo::aapl().await;
+        let connector = yahoo.connector();
+        let quote = connector.get_latest_price("AAPL").await.unwrap();
+        // the bar of the last day has no close yet, the close before is used
+        assert_eq!(quote.price, 235.0);
+        assert_eq!(
+            quote.time,
+            Some(Utc.with_ymd_and_hms(2024, 10, 18, 13, 30, 0).unwrap())
+        );
+        // the quote is from before today, nothing changed since its close
+        assert_eq!(quote.previous_close, Some(235.0));
+        assert_eq!(quote.open, Some(236.18));
+        assert_eq!(quote.currency.as_deref(), Some("USD"));
+        assert_eq!(yahoo.requests("/v8/finance/chart/AAPL").len(), 1);
+
+        let error = connector.get_latest_price("GONE").await.unwrap_err();
+        assert_eq!(error.kind, ErrorKind::NotFound);
+    }
+
+    fn window() -> chrono::Duration {
+        chrono::Duration::days(HISTORIC_WINDOW_DAYS)
+    }
+
+    #[tokio::test]
+    async fn test_get_historic_price() {
+        let yahoo = MockYahoo::aapl().await;
+        let connector = yahoo.connector();
+        let date = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+
+        let adjusted = connector
+            .get_historic_price("AAPL", date, PriceAdjustment::Adjusted, window())
+            .await
+            .unwrap();
+        assert_eq!(adjusted.adjustment, PriceAdjustment::Adjusted);
+        assert_eq!(adjusted.price, 72.7962);
+        // new year's day is a holiday, the close is the one of the next trading day
+        assert_eq!(adjusted.date, NaiveDate::from_ymd_opt(2020, 1, 2).unwrap());
+        let requests = yahoo.requests("/v8/finance/chart/AAPL");
+        assert!(requests[0].contains("period1=1577836800&period2=1578096000"));
+
+        // the split since is undone
+        let raw = connector
+            .get_historic_price("AAPL", date, PriceAdjustment::Raw, window())
+            .await
+            .unwrap();
+        assert_eq!(raw.adjustment, PriceAdjustment::Raw);
+        assert_eq!(raw.price, 75.0875 * 4.0);
+        assert_eq!(raw.date, adjusted.date);
+    }
+
+    #[tokio::test]
+    async fn test_get_splits() {
+        let yahoo = MockYahoo::aapl().await;
+        let connector = yahoo.connector();
+        let since = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        let splits = connector.get_splits("AAPL", since).await.unwrap();
+        assert_eq!(splits.len(), 1);
+        assert_eq!(split_ratio(&splits[0]), 4.0);
+        assert_eq!(splits[0].date, mock_split("2020-08-31", 4.0, 1.0).date);
+
+        // splits before the date are left out
+        let after = Utc.with_ymd_and_hms(2021, 1, 1, 0, 0, 0).unwrap();
+        assert!(connector
+            .get_splits("AAPL", after)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_search_symbols() {
+        let yahoo = MockYahoo::aapl().await;
+        let connector = yahoo.connector();
+        let matches = connector.search_symbols("AAPL").await.unwrap();
+        assert_eq!(
+            matches[1],
+            SymbolMatch {
+                symbol: "APC.DE".to_string(),
+                name: "APPLE INC".to_string(),
+                exchange: "GER".to_string(),
+                quote_type: "EQUITY".to_string(),
+            }
+        );
+        assert_eq!(
+            connector.find_symbols("AAPL").await.unwrap(),
+            ["AAPL", "APC.DE"]
+        );
+        assert!(connector.find_symbols("NOPE").await.unwrap().is_empty());
+    }
+
+    // Checks the connector against the real API, run it with --ignored to see
+    // whether Yahoo changed its responses
+    #[tokio::test]
+    #[ignore = "requires network access to Yahoo Finance"]
+    async fn test_live_yahoo() {
+        let connector = connector_with(&NetworkSettings::default()).unwrap();
+        assert_eq!(connector.get_name("AAPL").await.unwrap(), "Apple Inc.");
+        assert_eq!(connector.get_name("BTC-EUR").await.unwrap(), "Bitcoin EUR");
+
+        let quote = connector.get_latest_price("AAPL").await.unwrap();
+        assert!(quote.price > 0.0);
+        assert!(quote.time.is_some());
+
+        let date = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        let raw = connector
+            .get_historic_price("AAPL", date, PriceAdjustment::Raw, window())
+            .await
+            .unwrap();
+        assert_eq!(raw.date, NaiveDate::from_ymd_opt(2020, 1, 2).unwrap());
+        assert!(raw.price > 250.0);
+    }
+
+    #[test]
+    fn test_unadjust_for_splits() {
+        // NVDA split 4-for-1 in 2021 and 10-for-1 in 2024
+        let splits = [
+            mock_split("2021-07-20", 4.0, 1.0),
+            mock_split("2024-06-10", 10.0, 1.0),
+        ];
+        let timestamp = |date: &str| mock_split(date, 1.0, 1.0).date.timestamp();
+
+        // an adjusted close of 13.0 at the start of 2021 was traded at 520
+        assert_eq!(
+            unadjust_for_splits(13.0, timestamp("2021-01-04"), &splits),
+            520.0
+        );
+        // between the splits only the second one has to be undone
+        assert_eq!(
+            unadjust_for_splits(13.0, timestamp("2023-01-03"), &splits),
+            130.0
+        );
+        // after the last split adjusted and raw closes are the same
+        assert_eq!(
+            unadjust_for_splits(13.0, timestamp("2024-07-01"), &splits),
+            13.0
+        );
+
+        // a 1-for-8 reverse split makes the earlier raw price smaller
+        let reverse = [mock_split("2023-05-01", 1.0, 8.0)];
+        assert_eq!(
+            unadjust_for_splits(80.0, timestamp("2023-01-03"), &reverse),
+            10.0
+        );
+    }
+
+    fn daily_quote(time: DateTime<Utc>, close: f64) -> yahoo::Quote {
+        yahoo::Quote {
+            timestamp: time.timestamp(),
+            open: close,
+            high: close,
+            low: close,
+            volume: 0,
+            close,
+            adjclose: close,
+        }
+    }
+
+    #[test]
+    fn test_quote_time() {
+        // 15:30 in Frankfurt in winter, the timestamp doesn't depend on the exchange
+        let quote = yahoo::Quote {
+            timestamp: 1_704_464_400,
+            ..daily_quote(Utc::now(), 1.0)
+        };
+        assert_eq!(
+            quote_time(&quote),
+            Some(Utc.with_ymd_and_hms(2024, 1, 5, 14, 20, 0).unwrap())
+        );
+        assert_eq!(trading_day(&quote), NaiveDate::from_ymd_opt(2024, 1, 5));
+
+        // just before midnight UTC is still the same day, not the next one
+        let late = daily_quote(Utc.with_ymd_and_hms(2024, 1, 5, 23, 59, 59).unwrap(), 1.0);
+        assert_eq!(trading_day(&late), NaiveDate::from_ymd_opt(2024, 1, 5));
+
+        let out_of_range = yahoo::Quote {
+            timestamp: i64::MAX,
+            ..late
+        };
+        assert_eq!(quote_time(&out_of_range), None);
+    }
+
+    #[test]
+    fn test_previous_close() {
+        let thursday = Utc.with_ymd_and_hms(2024, 1, 4, 14, 30, 0).unwrap();
+        let friday = Utc.with_ymd_and_hms(2024, 1, 5, 14, 30, 0).unwrap();
+        let quotes = [daily_quote(thursday, 100.0), daily_quote(friday, 103.0)];
+
+        // during friday's session the change is against thursday
+        let today = friday.date_naive();
+        assert_eq!(previous_close(&quotes, &quotes[1], today), Some(100.0));
+
+        // a live quote of the same day is not the previous close
+        let mut live = quotes.to_vec();
+        live.push(daily_quote(friday + chrono::Duration::hours(2), 104.0));
+        assert_eq!(previous_close(&live, &live[2], today), Some(100.0));
+
+        // before monday's open nothing has changed yet
+        let monday = Utc.with_ymd_and_hms(2024, 1, 8, 0, 0, 0).unwrap();
+        assert_eq!(
+            previous_close(&quotes, &quotes[1], monday.date_naive()),
+            Some(103.0)
+        );
+
+        // without an earlier day the previous close is unknown
+        assert_eq!(previous_close(&quotes[1..], &quotes[1], today), None);
+    }
+
+    #[test]
+    fn test_earliest_valid_quote() {
+        // a long weekend: the requested friday had no trade, tuesday is the closest close
+        let friday = Utc.with_ymd_and_hms(2024, 3, 29, 14, 30, 0).unwrap();
+        let quotes = [
+            daily_quote(friday, f64::NAN),
+            daily_quote(friday + chrono::Duration::days(4), 171.0),
+            daily_quote(friday + chrono::Duration::days(5), 169.0),
+        ];
+        assert_eq!(earliest_valid_quote(&quotes), Some(&quotes[1]));
+        assert_eq!(
+            trading_day(earliest_valid_quote(&quotes).unwrap()),
+            NaiveDate::from_ymd_opt(2024, 4, 2)
+        );
+        assert_eq!(earliest_valid_quote(&quotes[..1]), None);
+    }
+
+    #[test]
+    fn test_latest_valid_quote() {
+        let thursday = Utc.with_ymd_and_hms(2024, 1, 4, 14, 30, 0).unwrap();
+        let friday = Utc.with_ymd_and_hms(2024, 1, 5, 14, 30, 0).unwrap();
+        let monday = Utc.with_ymd_and_hms(2024, 1, 8, 14, 30, 0).unwrap();
+        // monday had no trade yet
+        let quotes = [
+            daily_quote(thursday, 100.0),
+            daily_quote(friday, 103.0),
+            daily_quote(monday, f64::NAN),
+        ];
+        assert_eq!(latest_valid_quote(&quotes), Some(&quotes[1]));
+
+        let mut empty_days = quotes.to_vec();
+        empty_days[1].close = 0.0;
+        assert_eq!(latest_valid_quote(&empty_days), Some(&quotes[0]));
+        // the invalid day is skipped for the previous close as well
+        assert_eq!(
+            previous_close(&empty_days, &quotes[0], monday.date_naive()),
+            Some(100.0)
+        );
+        let mut live = empty_days.clone();
+        live[2].close = 101.0;
+        assert_eq!(
+            previous_close(&live, &live[2], monday.date_naive()),
+            Some(100.0)
+        );
+
+        let none = [
+            daily_quote(monday, f64::NAN),
+            daily_quote(friday, f64::INFINITY),
+        ];
+        assert_eq!(latest_valid_quote(&none), None);
+        assert_eq!(latest_valid_quote(&[]), None);
+    }
+
+    #[test]
+    fn test_is_crypto_ticker() {
+        assert!(is_crypto_ticker("BTC-EUR"));
+        assert!(is_crypto_ticker("eth-usd"));
+        assert!(is_crypto_ticker("SOL-USDT"));
+        assert!(!is_crypto_ticker("AAPL"));
+        assert!(!is_crypto_ticker("BRK-B"));
+        assert!(!is_crypto_ticker("EUNL.DE"));
+        assert!(!is_crypto_ticker("-USD"));
+    }
+
+    #[tokio::test]
+    async fn test_cached_provider() {
+        let now = Arc::new(Mutex::new(
+            Utc.with_ymd_and_hms(2024, 3, 4, 15, 0, 0).unwrap(),
+        ));
+        let clock = {
+            let now = now.clone();
+            move || *now.lock().unwrap()
+        };
+        let closed = Utc.with_ymd_and_hms(2024, 3, 1, 21, 0, 0).unwrap();
+        let provider = CachedProvider::new(
+            MockProvider::new()
+                .with_price("AAPL", 180.0)
+                .with_price("SAP.DE", 170.0)
+                .with_quote_time("SAP.DE", closed)
+                .with_name("AAPL", "Apple Inc."),
+            chrono::Duration::seconds(30),
+        )
+        .with_clock(clock);
+        let advance = |seconds| *now.lock().unwrap() += chrono::Duration::seconds(seconds);
+
+        for ticker in ["AAPL", "SAP.DE", "AAPL"] {
+            provider.get_latest_price(ticker).await.unwrap();
+        }
+        assert_eq!(provider.inner.latest_requests("AAPL"), 1);
+
+        // both are fetched again after max_age, SAP.DE didn't change since
+        advance(60);
+        provider.get_latest_price("AAPL").await.unwrap();
+        provider.get_latest_price("SAP.DE").await.unwrap();
+        assert_eq!(provider.inner.latest_requests("AAPL"), 2);
+        assert_eq!(provider.inner.latest_requests("SAP.DE"), 2);
+
+        // so its closed market is only checked every 15 minutes
+        advance(60);
+        provider.get_latest_price("AAPL").await.unwrap();
+        provider.get_latest_price("SAP.DE").await.unwrap();
+        assert_eq!(provider.inner.latest_requests("AAPL"), 3);
+        assert_eq!(provider.inner.latest_requests("SAP.DE"), 2);
+        advance(15 * 60);
+        provider.get_latest_price("SAP.DE").await.unwrap();
+        assert_eq!(provider.inner.latest_requests("SAP.DE"), 3);
+
+        assert_eq!(provider.get_name("AAPL").await.unwrap(), "Apple Inc.");
+        assert_eq!(provider.get_name("AAPL").await.unwrap(), "Apple Inc.");
+        // failures are not cached
+        assert!(provider.get_latest_price("MSFT").await.is_err());
+        assert!(provider.get_latest_price("MSFT").await.is_err());
+        assert_eq!(provider.inner.latest_requests("MSFT"), 2);
+    }
+
+    #[tokio::test]
+    async fn test_cached_symbols() {
+        let provider = CachedProvider::new(
+            MockProvider::new().with_symbols("IE00B4L5Y983", &["EUNL.DE", "IWDA.AS"]),
+            chrono::Duration::seconds(30),
+        );
+        for _ in 0..2 {
+            assert_eq!(
+                provider.find_symbols("IE00B4L5Y983").await.unwrap(),
+                ["EUNL.DE", "IWDA.AS"]
+            );
+        }
+        assert_eq!(provider.inner().searches("IE00B4L5Y983"), 1);
+
+        // nothing found is searched again
+        for _ in 0..2 {
+            assert!(provider
+                .find_symbols("XX0000000000")
+                .await
+                .unwrap()
+                .is_empty());
+        }
+        assert_eq!(provider.inner().searches("XX0000000000"), 2);
+    }
+
+    #[tokio::test]
+    async fn test_stored_names() {
+        let path = std::env::temp_dir().join(format!("names_{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let provider = StoredNames::open(
+            MockProvider::new().with_name("AAPL", "Apple Inc."),
+            path.clone(),
+        );
+        assert_eq!(provider.get_name("AAPL").await.unwrap(), "Apple Inc.");
+        assert!(provider.get_name("MSFT").await.is_err());
+        provider.save().unwrap();
+
+        // the next run knows the name without asking the inner provider
+        let provider = StoredNames::open(MockProvider::new(), path.clone());
+        assert_eq!(provider.get_name("AAPL").await.unwrap(), "Apple Inc.");
+        std::fs::remove_file(&path).unwrap();
+        // nothing was added, so nothing is written
+        provider.save().unwrap();
+        assert!(!path.exists());
+
+        std::fs::write(&path, "not json").unwrap();
+        let provider = StoredNames::open(MockProvider::new(), path.clone());
+        assert!(provider.get_name("AAPL").await.is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_mock_provider() {
+        let provider = MockProvider::new()
+            .with_price("AAPL", 170.0)
+            .with_name("AAPL", "Apple Inc.");
+
+        assert_eq!(
+            provider.get_latest_price("AAPL").await.unwrap().price,
+            170.0
+        );
+        assert_eq!(provider.get_name("AAPL").await.unwrap(), "Apple Inc.");
+        assert!(provider.get_latest_price("MSFT").await.is_err());
+        assert!(provider
+            .get_historic_price(
+                "AAPL",
+                Utc::now(),
+                PriceAdjustment::default(),
+                chrono::Duration::days(HISTORIC_WINDOW_DAYS)
+            )
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,118 @@
+use crate::format::NumberFormat;
+use crate::position::to_decimal;
+use crate::provider::PriceProvider;
+use rust_decimal::{Decimal, RoundingStrategy};
+
+// What a cash amount buys of a ticker at its latest price
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SharePurchase {
+    pub price: Decimal,
+    pub shares: Decimal,
+    pub cost: Decimal,
+    // the part of the cash that isn't enough for another (fraction of a) share
+    pub leftover: Decimal,
+}
+
+// Shares of the ticker the cash buys, floored to whole shares unless fractional
+// shares are allowed, then to `decimals` places (the shares precision) so the
+// cost never exceeds the cash
+pub async fn shares_for_amount(
+    provider: &impl PriceProvider,
+    ticker: &str,
+    cash: Decimal,
+    allow_fractional: bool,
+    decimals: u32,
+) -> Result<SharePurchase, String> {
+    if cash < Decimal::ZERO {
+        return Err(format!("The cash amount can't be negative: {cash}"));
+    }
+    let quote = provider
+        .get_latest_price(ticker)
+        .await
+        .map_err(|e| format!("There is no price for {ticker}: {e}"))?;
+    let price = to_decimal(quote.price);
+    if price <= Decimal::ZERO {
+        return Err(format!(
+            "The price of {ticker} is {price}, the number of shares can't be computed"
+        ));
+    }
+
+    let decimals = if allow_fractional { decimals } else { 0 };
+    let shares = (cash / price).round_dp_with_strategy(decimals, RoundingStrategy::ToZero);
+    let cost = shares * price;
+    Ok(SharePurchase {
+        price,
+        shares,
+        cost,
+        leftover: cash - cost,
+    })
+}
+
+pub fn print_share_purchase(ticker: &str, purchase: &SharePurchase, format: &NumberFormat) {
+    let shares = purchase.shares.normalize();
+    println!(
+        "{ticker} at {}: {} share(s) for {}, {} left over",
+        format.money(purchase.price),
+        format.number_with(shares, shares.scale()),
+        format.money(purchase.cost),
+        format.money(purchase.leftover)
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::MockProvider;
+    use rust_decimal::dec;
+
+    #[tokio::test]
+    async fn test_shares_for_amount() {
+        let provider = MockProvider::new().with_price("AAPL", 170.0);
+
+        let whole = shares_for_amount(&provider, "AAPL", dec!(1000), false, 6)
+            .await
+            .unwrap();
+        assert_eq!(
+            whole,
+            SharePurchase {
+                price: dec!(170),
+                shares: dec!(5),
+                cost: dec!(850),
+                leftover: dec!(150),
+            }
+        );
+
+        let fractional = shares_for_amount(&provider, "AAPL", dec!(1000), true, 6)
+            .await
+            .unwrap();
+        assert_eq!(fractional.shares, dec!(5.882352));
+        assert!(fractional.cost <= dec!(1000));
+        assert_eq!(fractional.cost + fractional.leftover, dec!(1000));
+
+        let none = shares_for_amount(&provider, "AAPL", dec!(100), false, 6)
+            .await
+            .unwrap();
+        assert_eq!(none.shares, dec!(0));
+        assert_eq!(none.leftover, dec!(100));
+    }
+
+    #[tokio::test]
+    async fn test_shares_for_amount_errors() {
+        let provider = MockProvider::new()
+            .with_price("AAPL", 170.0)
+            .with_price("DELISTED", 0.0);
+        assert_eq!(
+            shares_for_amount(&provider, "DELISTED", dec!(1000), false, 6)
+                .await
+                .unwrap_err(),
+            "The price of DELISTED is 0, the number of shares can't be computed"
+        );
+        assert!(shares_for_amount(&provider, "MSFT", dec!(1000), false, 6)
+            .await
+            .unwrap_err()
+            .starts_with("There is no price for MSFT: "));
+        assert!(shares_for_amount(&provider, "AAPL", dec!(-1), false, 6)
+            .await
+            .is_err());
+    }
+}
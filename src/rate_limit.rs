@@ -0,0 +1,220 @@
+use std::future::Future;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use yahoo_finance_api as yahoo;
+
+// Requests per second sent to Yahoo, unless the config file sets them
+pub const DEFAULT_REQUESTS_PER_SECOND: f64 = 5.0;
+
+// How long all requests wait after Yahoo answered one with 429 Too Many Requests
+pub const RATE_LIMIT_PAUSE_SECONDS: u64 = 30;
+
+struct Bucket {
+    // the earliest time the next request may be sent
+    next: Instant,
+    // no request is sent before then, Yahoo limited the requests
+    paused_until: Option<Instant>,
+}
+
+// When a request may be sent
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Slot {
+    // after waiting this long, the slot is taken
+    After(Duration),
+    // the requests are paused for this long, the request has to ask again
+    Paused(Duration),
+}
+
+// A token bucket holding a single token, so the requests are spaced 1/rate apart.
+// Each request takes the next free slot under the lock and then waits for it on
+// its own, so the requests under the limit don't wait at all and the ones over it
+// don't wait for each other to finish. A 429 pauses the whole bucket.
+pub struct RateLimiter {
+    interval: Duration,
+    pause: Duration,
+    bucket: Mutex<Bucket>,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_second: f64) -> Self {
+        RateLimiter {
+            interval: Duration::from_secs_f64(1.0 / requests_per_second),
+            pause: Duration::from_secs(RATE_LIMIT_PAUSE_SECONDS),
+            bucket: Mutex::new(Bucket {
+                next: Instant::now(),
+                paused_until: None,
+            }),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn with_pause(mut self, pause: Duration) -> Self {
+        self.pause = pause;
+        self
+    }
+
+    fn take_slot(&self, now: Instant) -> Slot {
+        let mut bucket = self.bucket.lock().unwrap();
+        if let Some(until) = bucket.paused_until.filter(|until| *until > now) {
+            return Slot::Paused(until - now);
+        }
+        let slot = bucket.next.max(now);
+        bucket.next = slot + self.interval;
+        Slot::After(slot - now)
+    }
+
+    fn is_paused(&self, now: Instant) -> bool {
+        self.bucket
+            .lock()
+            .unwrap()
+            .paused_until
+            .is_some_and(|until| until > now)
+    }
+
+    // Holds back every request for the pause, the slots taken before are given up
+    fn pause_at(&self, now: Instant) {
+        let mut bucket = self.bucket.lock().unwrap();
+        let until = bucket
+            .paused_until
+            .map_or(now + self.pause, |until| until.max(now + self.pause));
+        bucket.paused_until = Some(until);
+        bucket.next = bucket.next.max(until);
+    }
+
+    // Waits until a request may be sent
+    pub async fn acquire(&self) {
+        loop {
+            match self.take_slot(Instant::now()) {
+                Slot::After(wait) => {
+                    if !wait.is_zero() {
+                        tokio::time::sleep(wait).await;
+                    }
+                    // a 429 while waiting pauses the slot as well
+                    if !self.is_paused(Instant::now()) {
+                        return;
+                    }
+                }
+                Slot::Paused(wait) => tokio::time::sleep(wait).await,
+            }
+        }
+    }
+
+    // Sends a request once the limit allows it. If Yahoo answers with 429 all
+    // requests are paused, and the request is sent once more after the pause.
+    pub async fn send<T, F>(&self, request: impl Fn() -> F) -> Result<T, yahoo::YahooError>
+    where
+        F: Future<Output = Result<T, yahoo::YahooError>>,
+    {
+        self.acquire().await;
+        match request().await {
+            Err(yahoo::YahooError::TooManyRequests(_)) => {
+                log::warn!(
+                    "Yahoo limits the requests, pausing them for {} seconds",
+                    self.pause.as_secs()
+                );
+                self.pause_at(Instant::now());
+                self.acquire().await;
+                request().await
+            }
+            result => result,
+        }
+    }
+}
+
+static SHARED: OnceLock<RateLimiter> = OnceLock::new();
+
+// Set once from the config file, before the first request
+pub fn configure(requests_per_second: f64) {
+    if SHARED.set(RateLimiter::new(requests_per_second)).is_err() {
+        log::debug!("The rate limit was already configured");
+    }
+}
+
+// The limiter all requests to Yahoo go through, whichever connector sends them
+pub fn shared() -> &'static RateLimiter {
+    SHARED.get_or_init(|| RateLimiter::new(DEFAULT_REQUESTS_PER_SECOND))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn seconds(slot: Slot) -> f64 {
+        match slot {
+            Slot::After(wait) | Slot::Paused(wait) => wait.as_secs_f64(),
+        }
+    }
+
+    #[test]
+    fn test_queued_requests() {
+        let limiter = RateLimiter::new(5.0);
+        let start = Instant::now();
+
+        // 20 requests at once at 5 per second are sent over 4 seconds
+        let slots: Vec<Slot> = (0..20).map(|_| limiter.take_slot(start)).collect();
+        assert_eq!(slots[0], Slot::After(Duration::ZERO));
+        assert!((seconds(slots[1]) - 0.2).abs() < 1e-6);
+        assert!((seconds(slots[19]) - 3.8).abs() < 1e-6);
+        assert!(slots
+            .windows(2)
+            .all(|pair| seconds(pair[1]) > seconds(pair[0])));
+
+        // once the queue is through, a request under the limit doesn't wait
+        let later = start + Duration::from_secs(10);
+        assert_eq!(limiter.take_slot(later), Slot::After(Duration::ZERO));
+        assert!((seconds(limiter.take_slot(later)) - 0.2).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_pause() {
+        let limiter = RateLimiter::new(5.0).with_pause(Duration::from_secs(30));
+        let start = Instant::now();
+        limiter.take_slot(start);
+        limiter.pause_at(start);
+
+        // every request waits for the end of the pause and asks again
+        let during = start + Duration::from_secs(10);
+        assert!(limiter.is_paused(during));
+        assert_eq!(
+            limiter.take_slot(during),
+            Slot::Paused(Duration::from_secs(20))
+        );
+        // another 429 during the pause makes it last longer
+        limiter.pause_at(during);
+        assert!(limiter.is_paused(start + Duration::from_secs(35)));
+
+        // afterwards the requests are spaced again, starting at the end of the pause
+        let after = start + Duration::from_secs(40);
+        assert!(!limiter.is_paused(after));
+        assert_eq!(limiter.take_slot(after), Slot::After(Duration::ZERO));
+        assert!((seconds(limiter.take_slot(after)) - 0.2).abs() < 1e-6);
+    }
+
+    #[tokio::test]
+    async fn test_send_pauses_after_too_many_requests() {
+        let limiter = RateLimiter::new(1000.0).with_pause(Duration::from_millis(200));
+        let attempts = AtomicUsize::new(0);
+        let start = Instant::now();
+        let result = limiter
+            .send(|| async {
+                if attempts.fetch_add(1, Ordering::SeqCst) == 0 {
+                    Err(yahoo::YahooError::TooManyRequests("chart/AAPL".to_string()))
+                } else {
+                    Ok(42)
+                }
+            })
+            .await;
+        // sent again after the pause
+        assert_eq!(result.ok(), Some(42));
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+        assert!(start.elapsed() >= Duration::from_millis(200));
+
+        // the other requests wait for the pause as well
+        limiter.pause_at(Instant::now());
+        let paused = Instant::now();
+        let result: Result<i32, _> = limiter.send(|| async { Ok(1) }).await;
+        assert_eq!(result.ok(), Some(1));
+        assert!(paused.elapsed() >= Duration::from_millis(200));
+    }
+}
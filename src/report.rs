@@ -0,0 +1,633 @@
+use crate::allocation::{
+    allocation_report, concentration_index, total_liabilities, ClassAllocation,
+};
+use crate::format::NumberFormat;
+use crate::position::{to_decimal, PortfolioPosition, OVERRIDE_MARKER, UNPRICED_MARKER};
+use chrono::prelude::*;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+// Machine-readable summary of the portfolio, e.g. for a dashboard
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct PortfolioReport {
+    // the net worth, liabilities are subtracted
+    pub total_value: Decimal,
+    // sorted by weight, the weights match allocation_percentages and only cover the assets
+    pub by_class: Vec<ClassAllocation>,
+    // the (negative) sum of all liabilities
+    #[serde(default, skip_serializing_if = "Decimal::is_zero")]
+    pub liabilities: Decimal,
+    // balance-weighted interest rate in % of all cash positions,
+    // None if the portfolio holds no cash
+    pub blended_cash_yield: Option<Decimal>,
+    // fund fees paid per year, from the ExpenseRatio of the positions
+    #[serde(default, skip_serializing_if = "Decimal::is_zero")]
+    pub annual_fund_fees: Decimal,
+    // Herfindahl index of the positions, from 1 for a single holding towards 0
+    #[serde(default)]
+    pub concentration_index: f64,
+    pub generated_at: DateTime<Utc>,
+}
+
+impl PortfolioReport {
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|e| format!("Failed to serialize report: {e}"))
+    }
+}
+
+// Cash positions without an interest rate count as earning 0%
+fn blended_cash_yield(positions: &[PortfolioPosition]) -> Option<Decimal> {
+    let cash: Vec<&PortfolioPosition> = positions
+        .iter()
+        .filter(|position| position.is_cash() && !position.is_liability())
+        .collect();
+    let cash_balance: Decimal = cash.iter().map(|position| position.get_balance()).sum();
+    if cash_balance.is_zero() {
+        return None;
+    }
+
+    let weighted_rates: Decimal = cash
+        .iter()
+        .map(|position| position.get_balance() * position.get_interest_rate().unwrap_or_default())
+        .sum();
+    Some(weighted_rates / cash_balance)
+}
+
+// The targets are the allocation_targets of the config, the classes get their
+// target weight, drift and rebalance amount from them
+pub fn build_report(
+    positions: &[PortfolioPosition],
+    targets: &HashMap<String, f64>,
+) -> PortfolioReport {
+    PortfolioReport {
+        total_value: positions
+            .iter()
+            .map(|position| position.get_balance())
+            .sum(),
+        by_class: allocation_report(positions, targets),
+        liabilities: total_liabilities(positions),
+        blended_cash_yield: blended_cash_yield(positions),
+        annual_fund_fees: positions
+            .iter()
+            .map(|position| position.annual_expense_cost())
+            .sum(),
+        concentration_index: concentration_index(positions),
+        generated_at: Utc::now(),
+    }
+}
+
+// Number of positions listed as top gainers and top losers
+const TOP_MOVERS: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DocumentFormat {
+    Markdown,
+    Html,
+}
+
+impl FromStr for DocumentFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_lowercase().as_str() {
+            "markdown" | "md" => Ok(DocumentFormat::Markdown),
+            "html" => Ok(DocumentFormat::Html),
+            _ => Err(format!(
+                "{s} is not a document format, expected markdown or html"
+            )),
+        }
+    }
+}
+
+// A titled table of the document, rendered as Markdown or HTML
+struct Section {
+    title: &'static str,
+    headers: Vec<&'static str>,
+    // numeric columns are right-aligned
+    numeric: Vec<bool>,
+    rows: Vec<Vec<String>>,
+    // shown instead of the table if there are no rows
+    empty_note: &'static str,
+}
+
+fn balances_section(positions: &[PortfolioPosition], format: &NumberFormat) -> Section {
+    Section {
+        title: "Balances",
+        headers: vec!["Name", "Asset Class", "Amount", "Balance"],
+        numeric: vec![false, false, true, true],
+        rows: positions
+            .iter()
+            .map(|position| {
+                let name = if position.has_price_override() {
+                    format!("{} {OVERRIDE_MARKER}", position.get_name())
+                } else if position.is_unpriced() {
+                    format!("{} {UNPRICED_MARKER}", position.get_name())
+                } else {
+                    position.get_name().to_string()
+                };
+                vec![
+                    name,
+                    position.get_asset_class().to_string(),
+                    position.display_amount(format),
+                    format.number(position.get_balance()),
+                ]
+            })
+            .collect(),
+        empty_note: "No positions.",
+    }
+}
+
+fn allocation_section(report: &PortfolioReport, format: &NumberFormat) -> Section {
+    Section {
+        title: "Allocation",
+        headers: vec!["Asset Class", "Balance", "%"],
+        numeric: vec![false, true, true],
+        rows: report
+            .by_class
+            .iter()
+            .map(|class| {
+                vec![
+                    class.asset_class.clone(),
+                    format.number(class.balance),
+                    format.number_with(to_decimal(class.current_weight), 2),
+                ]
+            })
+            .collect(),
+        empty_note: "No positions.",
+    }
+}
+
+// Interest earned since the last payment of every interest-bearing cash position
+fn interest_section(
+    positions: &[PortfolioPosition],
+    as_of: DateTime<Utc>,
+    format: &NumberFormat,
+) -> Section {
+    Section {
+        title: "Interest this period",
+        headers: vec!["Name", "Rate %", "Since", "Earned"],
+        numeric: vec![false, true, false, true],
+        rows: positions
+            .iter()
+            .filter_map(|position| {
+                let since = position.interest_period_start()?;
+                let earned = position.accrued_interest(as_of);
+                Some(vec![
+                    position.get_name().to_string(),
+                    format.number_with(position.get_interest_rate().unwrap_or_default(), 2),
+                    since.format("%Y-%m-%d").to_string(),
+                    format.number(earned),
+                ])
+            })
+            .collect(),
+        empty_note: "No interest-bearing cash positions.",
+    }
+}
+
+// What the funds cost per year, with the total in the last row
+fn fees_section(
+    positions: &[PortfolioPosition],
+    report: &PortfolioReport,
+    format: &NumberFormat,
+) -> Section {
+    let mut rows: Vec<Vec<String>> = positions
+        .iter()
+        .filter_map(|position| {
+            let ratio = position.get_expense_ratio()?;
+            Some(vec![
+                position.get_name().to_string(),
+                format.number_with(ratio, 2),
+                format.number(position.get_balance()),
+                format.number(position.annual_expense_cost()),
+            ])
+        })
+        .collect();
+    if !rows.is_empty() {
+        rows.push(vec![
+            "Total".to_string(),
+            String::new(),
+            String::new(),
+            format.number(report.annual_fund_fees),
+        ]);
+    }
+
+    Section {
+        title: "Fund fees per year",
+        headers: vec!["Name", "Expense Ratio %", "Balance", "Cost"],
+        numeric: vec![false, true, true, true],
+        rows,
+        empty_note: "No positions with an expense ratio.",
+    }
+}
+
+// The positions with the largest unrealized gains and losses
+fn movers_section(positions: &[PortfolioPosition], format: &NumberFormat) -> Section {
+    let mut gains: Vec<(&PortfolioPosition, Decimal)> = positions
+        .iter()
+        .filter_map(|position| Some((position, position.get_unrealized_gain()?)))
+        .collect();
+    gains.sort_by_key(|gain| std::cmp::Reverse(gain.1));
+
+    let gainers = gains
+        .iter()
+        .filter(|(_, gain)| gain.is_sign_positive() && !gain.is_zero())
+        .take(TOP_MOVERS);
+    let losers = gains
+        .iter()
+        .rev()
+        .filter(|(_, gain)| gain.is_sign_negative() && !gain.is_zero())
+        .take(TOP_MOVERS);
+
+    Section {
+        title: "Top gainers and losers",
+        headers: vec!["Name", "Ticker", "Unrealized Gain"],
+        numeric: vec![false, false, true],
+        rows: gainers
+            .chain(losers)
+            .map(|(position, gain)| {
+                vec![
+                    position.get_name().to_string(),
+                    position.get_ticker().unwrap_or_default().to_string(),
+                    format.signed_number(*gain),
+                ]
+            })
+            .collect(),
+        empty_note: "No positions with a cost basis.",
+    }
+}
+
+fn escape_markdown(cell: &str) -> String {
+    cell.replace('|', "\\|")
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// Columns are padded to the widest cell so the table also lines up as plain text
+fn markdown_table(section: &Section) -> String {
+    let rows: Vec<Vec<String>> = section
+        .rows
+        .iter()
+        .map(|row| row.iter().map(|cell| escape_markdown(cell)).collect())
+        .collect();
+    let widths: Vec<usize> = section
+        .headers
+        .iter()
+        .enumerate()
+        .map(|(i, header)| {
+            rows.iter()
+                .map(|row| row[i].chars().count())
+                .chain([header.chars().count(), 3])
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+
+    let line = |cells: Vec<String>| format!("| {} |\n", cells.join(" | "));
+    let pad = |i: usize, cell: &str| {
+        if section.numeric[i] {
+            format!("{cell:>width$}", width = widths[i])
+        } else {
+            format!("{cell:<width$}", width = widths[i])
+        }
+    };
+
+    let mut table = line(
+        section
+            .headers
+            .iter()
+            .enumerate()
+            .map(|(i, header)| pad(i, header))
+            .collect(),
+    );
+    table += &line(
+        widths
+            .iter()
+            .enumerate()
+            .map(|(i, width)| {
+                if section.numeric[i] {
+                    format!("{}:", "-".repeat(width - 1))
+                } else {
+                    "-".repeat(*width)
+                }
+            })
+            .collect(),
+    );
+    for row in &rows {
+        table += &line(
+            row.iter()
+                .enumerate()
+                .map(|(i, cell)| pad(i, cell))
+                .collect(),
+        );
+    }
+    table
+}
+
+fn html_table(section: &Section) -> String {
+    let cell = |tag: &str, i: usize, text: &str| {
+        let align = if section.numeric[i] {
+            " style=\"text-align: right\""
+        } else {
+            ""
+        };
+        format!("<{tag}{align}>{}</{tag}>", escape_html(text))
+    };
+
+    let mut table = String::from("<table>\n<tr>");
+    for (i, header) in section.headers.iter().enumerate() {
+        table += &cell("th", i, header);
+    }
+    table += "</tr>\n";
+    for row in &section.rows {
+        table += "<tr>";
+        for (i, text) in row.iter().enumerate() {
+            table += &cell("td", i, text);
+        }
+        table += "</tr>\n";
+    }
+    table + "</table>\n"
+}
+
+// A self-contained document with the balances, the allocation, the interest
+// earned in the current period and the top gainers and losers
+pub fn render_document(
+    positions: &[PortfolioPosition],
+    as_of: DateTime<Utc>,
+    format: DocumentFormat,
+    number_format: &NumberFormat,
+) -> String {
+    let report = build_report(positions, &HashMap::new());
+    let title = format!("Portfolio report {}", as_of.format("%Y-%m-%d"));
+    let accrued: Decimal = positions
+        .iter()
+        .map(|position| position.balance_including_accrued(as_of) - position.get_balance())
+        .sum();
+    let mut total = format!("Total value: {}", number_format.money(report.total_value));
+    if !accrued.is_zero() {
+        total += &format!(
+            " ({} including accrued interest)",
+            number_format.money(report.total_value + accrued)
+        );
+    }
+    // simulated values must not be mistaken for the real ones
+    let override_note = positions
+        .iter()
+        .any(|position| position.has_price_override())
+        .then(|| {
+            format!("Positions marked {OVERRIDE_MARKER} are valued with a PriceOverride instead of their quote.")
+        });
+    let sections = [
+        balances_section(positions, number_format),
+        allocation_section(&report, number_format),
+        interest_section(positions, as_of, number_format),
+        fees_section(positions, &report, number_format),
+        movers_section(positions, number_format),
+    ];
+
+    match format {
+        DocumentFormat::Markdown => {
+            let mut document = format!("# {title}\n\n{}\n", escape_markdown(&total));
+            if let Some(note) = &override_note {
+                document += &format!("\n{}\n", escape_markdown(note));
+            }
+            for section in &sections {
+                document += &format!("\n## {}\n\n", section.title);
+                if section.rows.is_empty() {
+                    document += &format!("{}\n", section.empty_note);
+                } else {
+                    document += &markdown_table(section);
+                }
+            }
+            document
+        }
+        DocumentFormat::Html => {
+            let mut document = format!(
+                "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n</head>\n<body>\n<h1>{title}</h1>\n<p>{}</p>\n",
+                escape_html(&total)
+            );
+            if let Some(note) = &override_note {
+                document += &format!("<p>{}</p>\n", escape_html(note));
+            }
+            for section in &sections {
+                document += &format!("<h2>{}</h2>\n", section.title);
+                if section.rows.is_empty() {
+                    document += &format!("<p>{}</p>\n", section.empty_note);
+                } else {
+                    document += &html_table(section);
+                }
+            }
+            document + "</body>\n</html>\n"
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::allocation::allocation_percentages;
+    use crate::format::NumberStyle;
+    use crate::position::from_string;
+    use crate::provider::{priced, priced_at, MockProvider};
+    use rust_decimal::dec;
+
+    fn cash_portfolio() -> Vec<PortfolioPosition> {
+        priced_at(
+            r#"[
+                {"Name": "World ETF", "AssetClass": "Stocks", "Ticker": "EUNL.DE", "Amount": 10},
+                {"Name": "Savings", "AssetClass": "Cash", "Amount": 3000, "InterestRate": 4.0},
+                {"Name": "Checking", "AssetClass": "Cash", "Amount": 1000}
+            ]"#,
+            |_| 100.0,
+        )
+    }
+
+    #[test]
+    fn test_build_report() {
+        let positions = cash_portfolio();
+        let report = build_report(&positions, &HashMap::new());
+
+        assert_eq!(report.total_value, dec!(5000));
+        assert_eq!(report.blended_cash_yield, Some(dec!(3)));
+
+        let names: Vec<&str> = report
+            .by_class
+            .iter()
+            .map(|c| c.asset_class.as_str())
+            .collect();
+        assert_eq!(names, ["Cash", "Stocks"]);
+
+        let percentages = allocation_percentages(&positions);
+        for class in &report.by_class {
+            assert_eq!(class.current_weight, percentages[&class.asset_class]);
+            assert_eq!(class.target_weight, None);
+        }
+    }
+
+    #[test]
+    fn test_report_without_cash() {
+        let positions: Vec<PortfolioPosition> = cash_portfolio().into_iter().take(1).collect();
+        assert_eq!(
+            build_report(&positions, &HashMap::new()).blended_cash_yield,
+            None
+        );
+        assert_eq!(build_report(&[], &HashMap::new()).total_value, dec!(0));
+    }
+
+    #[test]
+    fn test_report_to_json() {
+        let report = build_report(&cash_portfolio(), &HashMap::new());
+        let json = report.to_json().unwrap();
+
+        let parsed: PortfolioReport = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, report);
+
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["total_value"], 5000.0);
+        assert_eq!(value["by_class"][0]["asset_class"], "Cash");
+        assert_eq!(value["by_class"][0]["current_weight"], 80.0);
+        assert!(value["by_class"][0].get("drift").is_none());
+        assert!(value["generated_at"].is_string());
+    }
+
+    #[test]
+    fn test_report_with_targets() {
+        let targets = HashMap::from([("Cash".to_string(), 60.0), ("Bonds".to_string(), 10.0)]);
+        let report = build_report(&cash_portfolio(), &targets);
+        let value: serde_json::Value = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+
+        assert_eq!(value["by_class"][0]["asset_class"], "Cash");
+        assert_eq!(value["by_class"][0]["target_weight"], 60.0);
+        assert_eq!(value["by_class"][0]["drift"], 20.0);
+        let bonds = value["by_class"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|class| class["asset_class"] == "Bonds")
+            .unwrap();
+        assert_eq!(bonds["current_weight"], 0.0);
+        assert_eq!(bonds["drift"], -10.0);
+    }
+
+    fn machine() -> NumberFormat {
+        NumberFormat::machine("EUR", 2)
+    }
+
+    async fn priced_fixture() -> Vec<PortfolioPosition> {
+        let provider = MockProvider::new()
+            .with_price("AAPL", 170.0)
+            .with_price("MSFT", 380.0)
+            .with_price("BND", 72.0)
+            .with_price("EUNL.DE", 90.0);
+        let positions_str = std::fs::read_to_string("tests/fixtures/report.json").unwrap();
+        priced(&provider, &positions_str).await
+    }
+
+    #[tokio::test]
+    async fn test_markdown_report_golden() {
+        let positions = priced_fixture().await;
+        let as_of = Utc.with_ymd_and_hms(2024, 1, 11, 0, 0, 0).unwrap();
+        let document = render_document(&positions, as_of, DocumentFormat::Markdown, &machine());
+        let golden = std::fs::read_to_string("tests/fixtures/report.md").unwrap();
+        assert_eq!(document, golden);
+    }
+
+    #[tokio::test]
+    async fn test_markdown_report_localized() {
+        let positions = priced_fixture().await;
+        let as_of = Utc.with_ymd_and_hms(2024, 1, 11, 0, 0, 0).unwrap();
+        let format = NumberFormat::for_currency("EUR").with_style(NumberStyle::De);
+        let document = render_document(&positions, as_of, DocumentFormat::Markdown, &format);
+        assert!(document.contains("Total value: 5.260,00 €"));
+        assert!(
+            document.contains("| Apple                   | Stock       |    10,00 | 1.700,00 |")
+        );
+        assert!(document.contains("| Stock       | 2.820,00 | 53,61 |"));
+        assert!(document.contains("|         +200,00 |"));
+    }
+
+    #[tokio::test]
+    async fn test_html_report_golden() {
+        let positions = priced_fixture().await;
+        let as_of = Utc.with_ymd_and_hms(2024, 1, 11, 0, 0, 0).unwrap();
+        let document = render_document(&positions, as_of, DocumentFormat::Html, &machine());
+        let golden = std::fs::read_to_string("tests/fixtures/report.html").unwrap();
+        assert_eq!(document, golden);
+    }
+
+    #[test]
+    fn test_annual_fund_fees() {
+        // 10,000 in a 0.07% fund and 25,000 in a 0.4% fund
+        let positions = priced_at(
+            r#"[
+                {"Name": "S&P 500", "Ticker": "CSPX.L", "AssetClass": "Stock", "Amount": 20, "ExpenseRatio": 0.07},
+                {"Name": "Emerging Markets", "Ticker": "EMIM.L", "AssetClass": "Stock", "Amount": 50, "ExpenseRatio": 0.4},
+                {"Name": "Savings", "AssetClass": "Cash", "Amount": 1000}
+            ]"#,
+            |_| 500.0,
+        );
+
+        let report = build_report(&positions, &HashMap::new());
+        assert_eq!(report.annual_fund_fees, dec!(107));
+        assert_eq!(report.total_value, dec!(36000));
+        let value: serde_json::Value = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+        assert_eq!(value["annual_fund_fees"], 107.0);
+        assert!(serde_json::from_str::<serde_json::Value>(
+            &build_report(&positions[2..], &HashMap::new())
+                .to_json()
+                .unwrap()
+        )
+        .unwrap()
+        .get("annual_fund_fees")
+        .is_none());
+
+        let document =
+            render_document(&positions, Utc::now(), DocumentFormat::Markdown, &machine());
+        assert!(document.contains("| S&P 500          |            0.07 | 10000.00 |   7.00 |"));
+        assert!(document.contains("| Emerging Markets |            0.40 | 25000.00 | 100.00 |"));
+        assert!(document.contains("| Total            |                 |          | 107.00 |"));
+    }
+
+    #[test]
+    fn test_report_marks_price_override() {
+        let positions = from_string(
+            r#"[
+                {"Name": "Apple", "Ticker": "AAPL", "AssetClass": "Stock", "Amount": 10, "PriceOverride": 136},
+                {"Name": "Savings", "AssetClass": "Cash", "Amount": 100}
+            ]"#,
+        );
+        let document =
+            render_document(&positions, Utc::now(), DocumentFormat::Markdown, &machine());
+        assert!(document.contains("Total value: 1460.00 EUR\n\nPositions marked (what-if) are valued with a PriceOverride instead of their quote.\n"));
+        assert!(document.contains("| Apple (what-if) | Stock       |  10.00 | 1360.00 |"));
+        assert!(document.contains("| Savings         | Cash        | 100.00 |  100.00 |"));
+
+        let html = render_document(&positions, Utc::now(), DocumentFormat::Html, &machine());
+        assert!(html.contains("<p>Positions marked (what-if) are valued with a PriceOverride instead of their quote.</p>"));
+
+        // no note without overrides
+        let document = render_document(
+            &positions[1..],
+            Utc::now(),
+            DocumentFormat::Markdown,
+            &machine(),
+        );
+        assert!(!document.contains("PriceOverride"));
+    }
+
+    #[test]
+    fn test_markdown_escapes_pipes() {
+        let positions =
+            from_string(r#"[{"Name": "Cash | EUR", "AssetClass": "Cash", "Amount": 5}]"#);
+        let document =
+            render_document(&positions, Utc::now(), DocumentFormat::Markdown, &machine());
+        assert!(document.contains("| Cash \\| EUR | Cash        |   5.00 |    5.00 |"));
+        assert!(document.contains("No positions with a cost basis."));
+    }
+}
@@ -0,0 +1,168 @@
+use crate::provider::{PriceProvider, SymbolMatch};
+
+// The quote types a search can be limited to, as Yahoo names them in lower case
+pub const QUOTE_TYPES: [&str; 4] = ["equity", "etf", "cryptocurrency", "mutualfund"];
+
+pub const DEFAULT_SEARCH_LIMIT: usize = 10;
+
+const SEARCH_HEADER: [&str; 5] = ["Symbol", "Name", "Exchange", "Type", "Currency"];
+
+// A match of the search with the currency it is quoted in, None without a quote
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchResult {
+    pub found: SymbolMatch,
+    pub currency: Option<String>,
+}
+
+// The best `limit` matches of a company name, a partial ticker or an ISIN, only those
+// of the quote type if one is given. The search doesn't report the currency, so the
+// latest quote of every match is fetched for it.
+pub async fn search(
+    provider: &impl PriceProvider,
+    query: &str,
+    quote_type: Option<&str>,
+    limit: usize,
+) -> Result<Vec<SearchResult>, String> {
+    let matches: Vec<SymbolMatch> = provider
+        .search_symbols(query)
+        .await
+        .map_err(|e| format!("The search for {query} failed: {e}"))?
+        .into_iter()
+        .filter(|found| quote_type.is_none_or(|kind| found.quote_type.eq_ignore_ascii_case(kind)))
+        .take(limit)
+        .collect();
+    let quotes = futures::future::join_all(
+        matches
+            .iter()
+            .map(|found| provider.get_latest_price(&found.symbol)),
+    )
+    .await;
+    Ok(matches
+        .into_iter()
+        .zip(quotes)
+        .map(|(found, quote)| SearchResult {
+            found,
+            currency: quote.ok().and_then(|quote| quote.currency),
+        })
+        .collect())
+}
+
+pub fn render_search_results(query: &str, results: &[SearchResult]) -> String {
+    if results.is_empty() {
+        return format!("No symbols found for {query}\n");
+    }
+    let rows: Vec<[String; 5]> = results
+        .iter()
+        .map(|result| {
+            [
+                result.found.symbol.clone(),
+                result.found.name.clone(),
+                result.found.exchange.clone(),
+                result.found.quote_type.clone(),
+                result.currency.clone().unwrap_or_else(|| "-".to_string()),
+            ]
+        })
+        .collect();
+
+    let mut widths = SEARCH_HEADER.map(|title| title.chars().count());
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let line = |cells: &[String]| {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect();
+        format!("{}\n", padded.join(" | ").trim_end())
+    };
+
+    let mut output = line(&SEARCH_HEADER.map(str::to_string));
+    output.push_str(&"=".repeat(widths.iter().sum::<usize>() + 3 * (widths.len() - 1)));
+    output.push('\n');
+    for row in &rows {
+        output.push_str(&line(row));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::MockProvider;
+
+    fn found(symbol: &str, name: &str, exchange: &str, quote_type: &str) -> SymbolMatch {
+        SymbolMatch {
+            symbol: symbol.to_string(),
+            name: name.to_string(),
+            exchange: exchange.to_string(),
+            quote_type: quote_type.to_string(),
+        }
+    }
+
+    fn provider() -> MockProvider {
+        MockProvider::new()
+            .with_matches(
+                "apple",
+                vec![
+                    found("AAPL", "Apple Inc.", "NMS", "EQUITY"),
+                    found("APC.DE", "Apple Inc.", "GER", "EQUITY"),
+                    found("APLY", "YieldMax AAPL Option Income", "PCX", "ETF"),
+                ],
+            )
+            .with_price("AAPL", 200.0)
+            .with_currency("AAPL", "USD")
+            .with_price("APLY", 15.0)
+            .with_currency("APLY", "USD")
+    }
+
+    #[tokio::test]
+    async fn test_search() {
+        let provider = provider();
+        let results = search(&provider, "apple", None, DEFAULT_SEARCH_LIMIT)
+            .await
+            .unwrap();
+        assert_eq!(
+            render_search_results("apple", &results),
+            "\
+Symbol | Name                        | Exchange | Type   | Currency
+===================================================================
+AAPL   | Apple Inc.                  | NMS      | EQUITY | USD
+APC.DE | Apple Inc.                  | GER      | EQUITY | -
+APLY   | YieldMax AAPL Option Income | PCX      | ETF    | USD
+"
+        );
+
+        let limited = search(&provider, "apple", None, 1).await.unwrap();
+        assert_eq!(limited.len(), 1);
+        assert_eq!(limited[0].found.symbol, "AAPL");
+
+        assert_eq!(
+            render_search_results("nothing", &[]),
+            "No symbols found for nothing\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_search_by_quote_type() {
+        let provider = provider();
+        let etfs = search(&provider, "apple", Some("etf"), DEFAULT_SEARCH_LIMIT)
+            .await
+            .unwrap();
+        assert_eq!(
+            etfs.iter()
+                .map(|result| result.found.symbol.as_str())
+                .collect::<Vec<_>>(),
+            ["APLY"]
+        );
+        // the limit counts the matches of the type
+        let first_etf = search(&provider, "apple", Some("ETF"), 1).await.unwrap();
+        assert_eq!(first_etf[0].found.symbol, "APLY");
+        assert!(search(&provider, "apple", Some("cryptocurrency"), 10)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,137 @@
+use crate::position::get_quote_history_range;
+use crate::provider::has_valid_close;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use yahoo_finance_api as yahoo;
+
+// One quote of a price series as Yahoo reports it, e.g. for a charting tool
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct PricePoint {
+    pub date: DateTime<Utc>,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: u64,
+}
+
+// The quotes of a ticker for a range (e.g. 1y) in the given interval (e.g. 1d).
+// Days without trading, like weekends and holidays, have no quote and are left
+// out instead of filled in, so the dates are not evenly spaced.
+pub async fn price_series(
+    connector: &yahoo::YahooConnector,
+    ticker: &str,
+    interval: &str,
+    range: &str,
+) -> Result<Vec<PricePoint>, String> {
+    let quotes = get_quote_history_range(connector, ticker, interval, range).await?;
+    Ok(to_price_points(&quotes))
+}
+
+// Quotes without a close (a gap Yahoo reports as 0) or with an invalid timestamp are left out
+pub fn to_price_points(quotes: &[yahoo::Quote]) -> Vec<PricePoint> {
+    quotes
+        .iter()
+        .filter(|quote| has_valid_close(quote))
+        .filter_map(|quote| {
+            Some(PricePoint {
+                date: DateTime::from_timestamp(quote.timestamp, 0)?,
+                open: quote.open,
+                high: quote.high,
+                low: quote.low,
+                close: quote.close,
+                volume: quote.volume,
+            })
+        })
+        .collect()
+}
+
+// Plain numbers, as the series is meant for other tools
+pub fn series_to_csv(series: &[PricePoint]) -> Result<String, String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    let error = |e: csv::Error| format!("Failed to write CSV: {e}");
+
+    writer
+        .write_record(["Date", "Open", "High", "Low", "Close", "Volume"])
+        .map_err(error)?;
+    for point in series {
+        writer
+            .write_record([
+                point.date.to_rfc3339_opts(SecondsFormat::Secs, true),
+                point.open.to_string(),
+                point.high.to_string(),
+                point.low.to_string(),
+                point.close.to_string(),
+                point.volume.to_string(),
+            ])
+            .map_err(error)?;
+    }
+
+    let bytes = writer
+        .into_inner()
+        .map_err(|e| format!("Failed to write CSV: {e}"))?;
+    String::from_utf8(bytes).map_err(|e| format!("Failed to write CSV: {e}"))
+}
+
+pub fn series_to_json(series: &[PricePoint]) -> Result<String, String> {
+    serde_json::to_string_pretty(series).map_err(|e| format!("Failed to serialize data: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Datelike, TimeZone};
+
+    fn quote(day: u32, close: f64) -> yahoo::Quote {
+        yahoo::Quote {
+            timestamp: Utc
+                .with_ymd_and_hms(2024, 1, day, 14, 30, 0)
+                .unwrap()
+                .timestamp(),
+            open: 100.0,
+            high: 102.5,
+            low: 99.0,
+            volume: 1200,
+            close,
+            adjclose: close,
+        }
+    }
+
+    #[test]
+    fn test_to_price_points() {
+        // the weekend of the 6th and 7th has no quotes, the 9th is a gap
+        let quotes = [
+            quote(5, 101.0),
+            quote(8, 101.5),
+            quote(9, 0.0),
+            quote(10, 102.0),
+        ];
+        let series = to_price_points(&quotes);
+        let days: Vec<u32> = series.iter().map(|point| point.date.day()).collect();
+        assert_eq!(days, [5, 8, 10]);
+        assert_eq!(
+            series[0],
+            PricePoint {
+                date: Utc.with_ymd_and_hms(2024, 1, 5, 14, 30, 0).unwrap(),
+                open: 100.0,
+                high: 102.5,
+                low: 99.0,
+                close: 101.0,
+                volume: 1200,
+            }
+        );
+    }
+
+    #[test]
+    fn test_series_output() {
+        let series = to_price_points(&[quote(5, 101.0)]);
+        assert_eq!(
+            series_to_csv(&series).unwrap(),
+            "Date,Open,High,Low,Close,Volume\n2024-01-05T14:30:00Z,100,102.5,99,101,1200\n"
+        );
+        let json = series_to_json(&series).unwrap();
+        assert!(json.contains("\"date\": \"2024-01-05T14:30:00Z\""));
+        let parsed: Vec<PricePoint> = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, series);
+    }
+}
@@ -0,0 +1,327 @@
+use crate::export::{balances_document, to_json};
+use crate::metrics::render_metrics;
+use crate::provider::PriceProvider;
+use http_body_util::Full;
+use hyper::body::{Bytes, Incoming};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use std::convert::Infallible;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:7878";
+// the requests have to send "Authorization: Bearer <token>" if it is set
+pub const TOKEN_VARIABLE: &str = "PORTFOLIO_RS_TOKEN";
+
+// What the server answers from. The data file is read once at the start, the
+// quotes come from the provider, which is a cache so they are only fetched again
+// once they are older than its TTL and not on every request.
+pub struct ServerState<P> {
+    pub provider: P,
+    pub positions_str: String,
+    pub currency: String,
+    pub token: Option<String>,
+}
+
+const JSON: &str = "application/json";
+// the text format read by Prometheus
+const METRICS: &str = "text/plain; version=0.0.4";
+
+// The status, the content type and the body of a response
+#[derive(Debug, PartialEq)]
+pub struct Reply {
+    pub status: StatusCode,
+    pub content_type: &'static str,
+    pub body: String,
+}
+
+impl Reply {
+    fn json(status: StatusCode, body: String) -> Self {
+        Reply {
+            status,
+            content_type: JSON,
+            body,
+        }
+    }
+
+    fn error(status: StatusCode, message: &str) -> Self {
+        Reply::json(status, serde_json::json!({ "error": message }).to_string())
+    }
+}
+
+enum Route {
+    Portfolio,
+    Position(String),
+    Metrics,
+}
+
+// %20 and the like in a position name, an invalid escape is kept as it is
+fn decode_path_segment(segment: &str) -> String {
+    let bytes = segment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let escaped = (bytes[index] == b'%')
+            .then(|| segment.get(index + 1..index + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+// Answers a request. Without a network in the tests, the routing, the
+// authorization and the documents are all in here.
+pub async fn respond<P: PriceProvider>(
+    state: &ServerState<P>,
+    method: &str,
+    path: &str,
+    authorization: Option<&str>,
+) -> Reply {
+    // the query doesn't select anything
+    let path = path.split('?').next().unwrap_or_default();
+    if method != "GET" {
+        return Reply::error(StatusCode::METHOD_NOT_ALLOWED, "Only GET is supported");
+    }
+    // the health check is open, so a monitor doesn't need the token
+    if path == "/healthz" {
+        return Reply::json(
+            StatusCode::OK,
+            serde_json::json!({ "status": "ok" }).to_string(),
+        );
+    }
+    if let Some(token) = &state.token {
+        if authorization != Some(format!("Bearer {token}").as_str()) {
+            return Reply::error(StatusCode::UNAUTHORIZED, "Missing or invalid bearer token");
+        }
+    }
+
+    let route = match path.strip_prefix("/positions/") {
+        Some(name) if !name.is_empty() => Route::Position(decode_path_segment(name)),
+        _ if path == "/portfolio" => Route::Portfolio,
+        _ if path == "/metrics" => Route::Metrics,
+        _ => return Reply::error(StatusCode::NOT_FOUND, "Not found"),
+    };
+    let (portfolio, _network_status) =
+        crate::live::price_portfolio(Some(&state.provider), &state.positions_str, false).await;
+    match route {
+        Route::Portfolio => {
+            match to_json(&portfolio.positions, &portfolio.watchlist, &state.currency) {
+                Ok(json) => Reply::json(StatusCode::OK, json),
+                Err(e) => Reply::error(StatusCode::INTERNAL_SERVER_ERROR, &e),
+            }
+        }
+        Route::Metrics => Reply {
+            status: StatusCode::OK,
+            content_type: METRICS,
+            body: render_metrics(&portfolio.positions, chrono::Utc::now()),
+        },
+        Route::Position(name) => {
+            let document = balances_document(
+                &portfolio.positions,
+                &portfolio.watchlist,
+                &state.currency,
+                chrono::Utc::now(),
+            );
+            let position = document.positions.into_iter().find(|position| {
+                position.name.eq_ignore_ascii_case(&name)
+                    || position
+                        .ticker
+                        .as_ref()
+                        .is_some_and(|ticker| ticker.eq_ignore_ascii_case(&name))
+            });
+            match position.map(|position| serde_json::to_string_pretty(&position)) {
+                Some(Ok(json)) => Reply::json(StatusCode::OK, json),
+                Some(Err(e)) => Reply::error(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    &format!("Failed to serialize data: {e}"),
+                ),
+                None => Reply::error(StatusCode::NOT_FOUND, &format!("No position named {name}")),
+            }
+        }
+    }
+}
+
+async fn handle<P: PriceProvider>(
+    state: &ServerState<P>,
+    request: Request<Incoming>,
+) -> Response<Full<Bytes>> {
+    let authorization = request
+        .headers()
+        .get(hyper::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok());
+    let reply = respond(
+        state,
+        request.method().as_str(),
+        request.uri().path(),
+        authorization,
+    )
+    .await;
+    let mut response = Response::new(Full::new(Bytes::from(reply.body)));
+    *response.status_mut() = reply.status;
+    response.headers_mut().insert(
+        hyper::header::CONTENT_TYPE,
+        hyper::header::HeaderValue::from_static(reply.content_type),
+    );
+    response
+}
+
+// Answers the connections of the listener until Ctrl-C
+pub async fn serve<P: PriceProvider + Send + Sync + 'static>(
+    listener: TcpListener,
+    state: Arc<ServerState<P>>,
+) -> Result<(), String> {
+    loop {
+        let (stream, _) = tokio::select! {
+            accepted = listener.accept() => {
+                accepted.map_err(|e| format!("Failed to accept a connection: {e}"))?
+            }
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+        };
+        let state = state.clone();
+        tokio::spawn(async move {
+            let service = service_fn(move |request| {
+                let state = state.clone();
+                async move { Ok::<_, Infallible>(handle(&state, request).await) }
+            });
+            if let Err(e) = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                log::debug!("Connection closed with an error: {e}");
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::{CachedProvider, MockProvider};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    const POSITIONS: &str = r#"[
+        {"Name": "Apple", "Ticker": "AAPL", "AssetClass": "Stocks", "Amount": 10},
+        {"Name": "Emergency Fund", "AssetClass": "Cash", "Amount": 500}
+    ]"#;
+
+    fn state(token: Option<&str>) -> ServerState<CachedProvider<MockProvider>> {
+        ServerState {
+            provider: CachedProvider::new(
+                MockProvider::new().with_price("AAPL", 180.0),
+                chrono::Duration::seconds(60),
+            ),
+            positions_str: POSITIONS.to_string(),
+            currency: "EUR".to_string(),
+            token: token.map(str::to_string),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_portfolio() {
+        let state = state(None);
+        let Reply { status, body, .. } = respond(&state, "GET", "/portfolio", None).await;
+        assert_eq!(status, StatusCode::OK);
+        let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(json["positions"].as_array().unwrap().len(), 2);
+        assert_eq!(json["positions"][0]["balance"], 1800.0);
+        assert_eq!(json["summary"]["total"], 2300.0);
+
+        // the second request is answered from the cache
+        respond(&state, "GET", "/portfolio?pretty", None).await;
+        assert_eq!(state.provider.inner().latest_requests("AAPL"), 1);
+    }
+
+    #[tokio::test]
+    async fn test_position() {
+        let state = state(None);
+        let Reply { status, body, .. } = respond(&state, "GET", "/positions/aapl", None).await;
+        assert_eq!(status, StatusCode::OK);
+        let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(json["name"], "Apple");
+        assert_eq!(json["balance"], 1800.0);
+
+        let Reply { status, body, .. } =
+            respond(&state, "GET", "/positions/Emergency%20Fund", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("\"balance\": 500"));
+
+        let Reply { status, body, .. } = respond(&state, "GET", "/positions/MSFT", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body, r#"{"error":"No position named MSFT"}"#);
+        let Reply { status, .. } = respond(&state, "GET", "/positions/", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_metrics() {
+        let state = state(None);
+        let reply = respond(&state, "GET", "/metrics", None).await;
+        assert_eq!(reply.status, StatusCode::OK);
+        assert_eq!(reply.content_type, "text/plain; version=0.0.4");
+        assert!(reply.body.contains("\nportfolio_total_balance 2300\n"));
+        assert!(reply
+            .body
+            .contains("portfolio_position_balance{name=\"Apple\",ticker=\"AAPL\",asset_class=\"Stocks\",account=\"\"} 1800\n"));
+    }
+
+    #[tokio::test]
+    async fn test_routing_and_token() {
+        let state = state(Some("secret"));
+        let health = respond(&state, "GET", "/healthz", None).await;
+        assert_eq!(health.body, r#"{"status":"ok"}"#);
+        let Reply { status, .. } = respond(&state, "GET", "/metrics", None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let Reply { status, .. } = respond(&state, "GET", "/portfolio", None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let Reply { status, .. } = respond(&state, "GET", "/portfolio", Some("Bearer wrong")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let Reply { status, .. } =
+            respond(&state, "GET", "/portfolio", Some("Bearer secret")).await;
+        assert_eq!(status, StatusCode::OK);
+        let Reply { status, .. } = respond(&state, "GET", "/balances", Some("Bearer secret")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let Reply { status, .. } =
+            respond(&state, "POST", "/portfolio", Some("Bearer secret")).await;
+        assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    #[tokio::test]
+    async fn test_serve() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, Arc::new(state(None))));
+
+        let mut stream = tokio::net::TcpStream::connect(address).await.unwrap();
+        stream
+            .write_all(
+                b"GET /positions/AAPL HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+            )
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("content-type: application/json\r\n"));
+        assert!(response.contains("\"ticker\": \"AAPL\""));
+    }
+
+    #[test]
+    fn test_decode_path_segment() {
+        assert_eq!(decode_path_segment("Emergency%20Fund"), "Emergency Fund");
+        assert_eq!(decode_path_segment("M%C3%BCnchen"), "München");
+        assert_eq!(decode_path_segment("100%"), "100%");
+        assert_eq!(decode_path_segment("%zz"), "%zz");
+    }
+}
@@ -0,0 +1,232 @@
+use crate::format::AmountPrecision;
+use crate::position::{to_decimal, PortfolioPosition};
+use crate::provider::{PriceProvider, Split};
+use chrono::prelude::*;
+use rust_decimal::Decimal;
+
+// Splits of a ticker since the amount of its position was last verified
+#[derive(Debug, Clone)]
+pub struct SplitCheck {
+    pub name: String,
+    pub ticker: String,
+    // the amount in the data file, from before the splits
+    pub amount: Decimal,
+    pub splits: Vec<Split>,
+}
+
+impl SplitCheck {
+    // New shares per old share over all splits, e.g. 40 after a 4:1 and a 10:1 split
+    pub fn factor(&self) -> Decimal {
+        self.splits
+            .iter()
+            .map(|split| to_decimal(split.numerator) / to_decimal(split.denominator))
+            .product()
+    }
+
+    // "4:1 on 2021-07-20 and 10:1 on 2024-06-10"
+    fn describe_splits(&self) -> String {
+        let splits: Vec<String> = self
+            .splits
+            .iter()
+            .map(|split| {
+                format!(
+                    "{}:{} on {}",
+                    split.numerator,
+                    split.denominator,
+                    split.date.format("%Y-%m-%d")
+                )
+            })
+            .collect();
+        match splits.split_last() {
+            Some((last, [])) => last.clone(),
+            Some((last, rest)) => format!("{} and {last}", rest.join(", ")),
+            None => String::new(),
+        }
+    }
+
+    pub fn warning(&self) -> String {
+        format!(
+            "{} split {} — your Amount may be stale",
+            self.ticker,
+            self.describe_splits()
+        )
+    }
+
+    pub fn applied(&self) -> String {
+        format!(
+            "{} split {}: Amount of {} changed from {} to {}",
+            self.ticker,
+            self.describe_splits(),
+            self.name,
+            self.amount.normalize(),
+            (self.amount * self.factor()).normalize()
+        )
+    }
+}
+
+// The splits since the LastVerified or PurchaseDate of a position, None if there
+// were none or the position has neither date
+pub async fn find_splits(
+    provider: &impl PriceProvider,
+    position: &PortfolioPosition,
+) -> Result<Option<SplitCheck>, String> {
+    let (Some(ticker), Some(since)) = (position.get_ticker(), position.split_reference_date())
+    else {
+        return Ok(None);
+    };
+
+    let splits: Vec<Split> = provider
+        .get_splits(ticker, since)
+        .await
+        .map_err(|e| format!("Error getting the splits of {ticker}: {e}"))?
+        .into_iter()
+        .filter(|split| {
+            split.numerator > 0.0 && split.denominator > 0.0 && split.numerator != split.denominator
+        })
+        .collect();
+    if splits.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(SplitCheck {
+        name: position.get_name().to_string(),
+        ticker: ticker.to_string(),
+        amount: position.get_amount(),
+        splits,
+    }))
+}
+
+// One result per position, in the order of the positions
+pub async fn check_splits(
+    provider: &impl PriceProvider,
+    positions: &[PortfolioPosition],
+) -> Vec<Result<Option<SplitCheck>, String>> {
+    futures::future::join_all(
+        positions
+            .iter()
+            .map(|position| find_splits(provider, position)),
+    )
+    .await
+}
+
+// Multiplies the amounts by their splits and marks them as verified on `today`.
+// A reverse split can leave a fraction, the amounts are rounded to their precision.
+// Returns the applied splits and the errors of the tickers that couldn't be checked.
+pub async fn apply_splits(
+    provider: &impl PriceProvider,
+    positions: &mut [PortfolioPosition],
+    today: DateTime<Utc>,
+    precision: &AmountPrecision,
+) -> (Vec<SplitCheck>, Vec<String>) {
+    let results = check_splits(provider, positions).await;
+    let mut applied = Vec::new();
+    let mut errors = Vec::new();
+    for (position, result) in positions.iter_mut().zip(results) {
+        match result {
+            Ok(Some(check)) => {
+                position.apply_split_factor(check.factor(), today);
+                position.round_amount(precision);
+                applied.push(check);
+            }
+            Ok(None) => {}
+            Err(e) => errors.push(e),
+        }
+    }
+    (applied, errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::from_string;
+    use crate::provider::{mock_split, MockProvider};
+    use rust_decimal::dec;
+
+    fn positions() -> Vec<PortfolioPosition> {
+        from_string(
+            r#"[
+                {"Name": "Nvidia", "Ticker": "NVDA", "AssetClass": "Stocks", "Amount": 10,
+                 "CostBasis": 500, "PurchaseDate": "2021-01-04"},
+                {"Name": "Reverse", "Ticker": "REV", "AssetClass": "Stocks", "Amount": 80,
+                 "LastVerified": "2023-01-03"},
+                {"Name": "Apple", "Ticker": "AAPL", "AssetClass": "Stocks", "Amount": 5,
+                 "PurchaseDate": "2024-01-02"},
+                {"Name": "Unchecked", "Ticker": "NVDA", "AssetClass": "Stocks", "Amount": 3}
+            ]"#,
+        )
+    }
+
+    fn provider() -> MockProvider {
+        MockProvider::new()
+            .with_splits(
+                "NVDA",
+                vec![
+                    mock_split("2021-07-20", 4.0, 1.0),
+                    mock_split("2024-06-10", 10.0, 1.0),
+                ],
+            )
+            .with_splits(
+                "REV",
+                vec![
+                    mock_split("2022-05-02", 1.0, 2.0),
+                    mock_split("2023-05-01", 1.0, 8.0),
+                ],
+            )
+    }
+
+    #[tokio::test]
+    async fn test_check_splits() {
+        let results = check_splits(&provider(), &positions()).await;
+        assert_eq!(results.len(), 4);
+
+        // both forward splits of NVDA since the purchase compound
+        let nvda = results[0].clone().unwrap().unwrap();
+        assert_eq!(nvda.factor(), dec!(40));
+        assert_eq!(
+            nvda.warning(),
+            "NVDA split 4:1 on 2021-07-20 and 10:1 on 2024-06-10 — your Amount may be stale"
+        );
+
+        // only the reverse split after LastVerified counts
+        let reverse = results[1].clone().unwrap().unwrap();
+        assert_eq!(reverse.factor(), dec!(0.125));
+        assert_eq!(
+            reverse.warning(),
+            "REV split 1:8 on 2023-05-01 — your Amount may be stale"
+        );
+
+        // no splits, and no date to check from
+        assert!(results[2].as_ref().unwrap().is_none());
+        assert!(results[3].as_ref().unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_apply_splits() {
+        let mut positions = positions();
+        let today = Utc.with_ymd_and_hms(2024, 7, 1, 0, 0, 0).unwrap();
+        let (applied, errors) = apply_splits(
+            &provider(),
+            &mut positions,
+            today,
+            &AmountPrecision::default(),
+        )
+        .await;
+        assert!(errors.is_empty());
+        assert_eq!(applied.len(), 2);
+        assert_eq!(
+            applied[0].applied(),
+            "NVDA split 4:1 on 2021-07-20 and 10:1 on 2024-06-10: Amount of Nvidia changed from 10 to 400"
+        );
+
+        assert_eq!(positions[0].get_amount(), dec!(400));
+        assert_eq!(positions[0].get_cost_basis(), Some(dec!(12.5)));
+        assert_eq!(positions[1].get_amount(), dec!(10));
+        assert_eq!(positions[2].get_amount(), dec!(5));
+        assert_eq!(positions[3].get_amount(), dec!(3));
+
+        // verified today, so checking again finds nothing
+        assert_eq!(positions[0].split_reference_date(), Some(today));
+        let results = check_splits(&provider(), &positions).await;
+        assert!(results.iter().all(|result| matches!(result, Ok(None))));
+    }
+}
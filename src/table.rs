@@ -0,0 +1,794 @@
+use crate::format::NumberFormat;
+use crate::grouping::{group_position_refs, group_positions, GroupBy};
+use crate::position::{to_decimal, PortfolioPosition, NOTES_MARKER};
+use chrono::prelude::*;
+use colored::Colorize;
+use rust_decimal::Decimal;
+use std::io::IsTerminal;
+
+const HEADER: [&str; 7] = [
+    "Name",
+    "Asset Class",
+    "Amount",
+    "Balance",
+    "Day Change",
+    "Day Change %",
+    "Gain",
+];
+
+// the text columns are left-aligned, the numbers right-aligned
+const TEXT_COLUMNS: usize = 2;
+
+// Colors are only written to a terminal, and never if NO_COLOR is set
+// to a non-empty value (https://no-color.org)
+pub fn use_color() -> bool {
+    color_enabled(
+        std::io::stdout().is_terminal(),
+        std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty()),
+    )
+}
+
+fn color_enabled(is_terminal: bool, no_color: bool) -> bool {
+    is_terminal && !no_color
+}
+
+// shown in the day change columns of positions that aren't traded
+const NOT_TRADED: &str = "-";
+
+// Changes carry an explicit sign, unknown changes are left empty
+fn format_change(change: Option<Decimal>, format: &NumberFormat) -> String {
+    change
+        .map(|change| format.signed_money(change))
+        .unwrap_or_default()
+}
+
+fn format_change_percent(percent: Option<Decimal>, format: &NumberFormat) -> String {
+    percent
+        .map(|percent| format.signed_percent(percent))
+        .unwrap_or_default()
+}
+
+// The day change and its percentage of the previous value, or dashes without
+// any market instrument
+fn day_change_cells(
+    traded: bool,
+    day_change: Option<Decimal>,
+    percent: Option<Decimal>,
+    format: &NumberFormat,
+) -> [String; 2] {
+    if traded {
+        [
+            format_change(day_change, format),
+            format_change_percent(percent, format),
+        ]
+    } else {
+        [NOT_TRADED.to_string(), NOT_TRADED.to_string()]
+    }
+}
+
+#[derive(PartialEq)]
+enum RowKind {
+    Position,
+    Subtotal,
+    Total,
+    // below the total, e.g. the net worth
+    Footer,
+}
+
+struct Row {
+    cells: [String; 7],
+    day_change: Option<Decimal>,
+    gain: Option<Decimal>,
+    kind: RowKind,
+}
+
+// None if no position of the rows knows the value
+fn sum_known(values: impl Iterator<Item = Option<Decimal>>) -> Option<Decimal> {
+    values
+        .flatten()
+        .fold(None, |sum, value| Some(sum.unwrap_or_default() + value))
+}
+
+fn summary_row(
+    label: &str,
+    asset_class: &str,
+    positions: &[&PortfolioPosition],
+    format: &NumberFormat,
+    kind: RowKind,
+) -> Row {
+    let balance: Decimal = positions.iter().map(|p| p.get_balance()).sum();
+    let day_change = sum_known(positions.iter().map(|p| p.get_day_change()));
+    let gain = sum_known(positions.iter().map(|p| p.get_unrealized_gain()));
+    // the change relative to the value at the previous close of the positions it covers
+    let previous_value: Decimal = positions
+        .iter()
+        .filter_map(|p| Some(p.get_balance() - p.get_day_change()?))
+        .sum();
+    let percent = day_change
+        .filter(|_| !previous_value.is_zero())
+        .map(|change| change / previous_value * Decimal::ONE_HUNDRED);
+    let traded = positions.iter().any(|p| p.is_market_instrument());
+    let [day_change_cell, percent_cell] = day_change_cells(traded, day_change, percent, format);
+    Row {
+        cells: [
+            label.to_string(),
+            asset_class.to_string(),
+            String::new(),
+            format.money(balance),
+            day_change_cell,
+            percent_cell,
+            format_change(gain, format),
+        ],
+        day_change,
+        gain,
+        kind,
+    }
+}
+
+fn position_row(
+    position: &PortfolioPosition,
+    format: &NumberFormat,
+    max_quote_age: chrono::Duration,
+) -> Row {
+    let money = position.money_format(format);
+    let day_change = position.get_day_change();
+    let gain = position.get_unrealized_gain();
+    let [day_change_cell, percent_cell] = day_change_cells(
+        position.is_market_instrument(),
+        day_change,
+        position.get_day_change_percent(),
+        &money,
+    );
+    let mut name = position.get_marked_name(max_quote_age);
+    if position.get_notes().is_some() {
+        name = format!("{name} {NOTES_MARKER}");
+    }
+    Row {
+        cells: [
+            name,
+            position.get_asset_class().to_string(),
+            position.display_amount(format),
+            position.format_balance(format),
+            day_change_cell,
+            percent_cell,
+            format_change(gain, &money),
+        ],
+        day_change,
+        gain,
+        kind: RowKind::Position,
+    }
+}
+
+fn paint_change(cell: String, change: Option<Decimal>) -> String {
+    match change {
+        Some(change) if change < Decimal::ZERO => cell.red().to_string(),
+        Some(change) if change > Decimal::ZERO => cell.green().to_string(),
+        _ => cell,
+    }
+}
+
+// Balances table with a subtotal per asset class and the total at the end.
+// The columns are as wide as their longest cell, colors are applied after padding
+// so escape codes don't break the alignment. Positions with a quote older than
+// max_quote_age are marked.
+pub fn render_balances(
+    positions: &[PortfolioPosition],
+    format: &NumberFormat,
+    color: bool,
+    max_quote_age: chrono::Duration,
+) -> String {
+    let mut rows = Vec::new();
+    for group in group_positions(positions, GroupBy::AssetClass) {
+        for position in &group.positions {
+            rows.push(position_row(position, format, max_quote_age));
+        }
+        rows.push(summary_row(
+            "Subtotal",
+            &group.name,
+            &group.positions,
+            format,
+            RowKind::Subtotal,
+        ));
+    }
+    let all: Vec<&PortfolioPosition> = positions.iter().collect();
+    let (liabilities, assets): (Vec<&PortfolioPosition>, Vec<&PortfolioPosition>) =
+        all.iter().partition(|position| position.is_liability());
+    if liabilities.is_empty() {
+        rows.push(summary_row("Total", "", &all, format, RowKind::Total));
+    } else {
+        // the footer starts at the first of the three lines
+        rows.push(summary_row("Assets", "", &assets, format, RowKind::Total));
+        rows.push(summary_row(
+            "Liabilities",
+            "",
+            &liabilities,
+            format,
+            RowKind::Footer,
+        ));
+        rows.push(summary_row("Net Worth", "", &all, format, RowKind::Footer));
+    }
+
+    let mut widths = HEADER.map(|title| title.chars().count());
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(&row.cells) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let pad = |column: usize, cell: &str| {
+        let width = widths[column];
+        if column < TEXT_COLUMNS {
+            format!("{cell:<width$}")
+        } else {
+            format!("{cell:>width$}")
+        }
+    };
+    let rule = "=".repeat(widths.iter().sum::<usize>() + 3 * (widths.len() - 1));
+
+    let mut output = String::new();
+    let header: Vec<String> = HEADER
+        .iter()
+        .enumerate()
+        .map(|(column, title)| pad(column, title))
+        .collect();
+    output.push_str(&header.join(" | "));
+    output.push('\n');
+    output.push_str(&rule);
+    output.push('\n');
+
+    for row in rows {
+        if row.kind == RowKind::Total {
+            output.push_str(&rule);
+            output.push('\n');
+        }
+        let cells: Vec<String> = row
+            .cells
+            .iter()
+            .enumerate()
+            .map(|(column, cell)| {
+                let cell = pad(column, cell);
+                if !color {
+                    return cell;
+                }
+                let cell = match column {
+                    4 | 5 => paint_change(cell, row.day_change),
+                    6 => paint_change(cell, row.gain),
+                    _ => cell,
+                };
+                if row.kind == RowKind::Position {
+                    cell
+                } else {
+                    cell.bold().to_string()
+                }
+            })
+            .collect();
+        output.push_str(cells.join(" | ").trim_end());
+        output.push('\n');
+    }
+    if positions
+        .iter()
+        .any(|position| position.get_notes().is_some())
+    {
+        output.push_str(&format!(
+            "{NOTES_MARKER} has notes, see `portfolio_rs show NAME`\n"
+        ));
+    }
+
+    output
+}
+
+// The lines describing a position, for the detail pane of the TUI and the show command
+pub fn position_details(position: &PortfolioPosition, format: &NumberFormat) -> Vec<String> {
+    let date = |date: DateTime<Utc>| date.format("%Y-%m-%d").to_string();
+    let mut lines = vec![
+        match position.get_ticker() {
+            Some(ticker) => format!("{} ({ticker})", position.get_name()),
+            None => position.get_name().to_string(),
+        },
+        format!("Asset class: {}", position.get_asset_class()),
+        format!("Amount: {}", position.display_amount(format)),
+    ];
+    let format = &position.money_format(format);
+    if position.get_listings().len() > 1 {
+        lines.push(format!("Listings: {}", position.get_listings().join(", ")));
+    }
+    if let Some(isin) = position.get_isin() {
+        lines.push(format!("ISIN: {isin}"));
+    }
+    if let Some(institution) = position.get_institution() {
+        lines.push(format!("Institution: {institution}"));
+    }
+    if let Some(account) = position.get_account() {
+        lines.push(format!("Account: {account}"));
+    }
+    if !position.get_tags().is_empty() {
+        lines.push(format!("Tags: {}", position.get_tags().join(", ")));
+    }
+    if let Some(price) = position.get_last_spot() {
+        lines.push(format!("Price: {}", format.money(price)));
+    }
+    lines.push(format!("Balance: {}", format.money(position.get_balance())));
+    if let Some(change) = position.get_day_change() {
+        let percent = position
+            .get_day_change_percent()
+            .map(|percent| format!(" ({})", format.signed_percent(percent)))
+            .unwrap_or_default();
+        lines.push(format!(
+            "Day change: {}{percent}",
+            format.signed_money(change)
+        ));
+    }
+    if let Some(cost_basis) = position.get_cost_basis() {
+        lines.push(format!("Cost basis: {} per unit", format.money(cost_basis)));
+    }
+    if let Some(gain) = position.get_unrealized_gain() {
+        lines.push(format!("Unrealized gain: {}", format.signed_money(gain)));
+    }
+    if let Some(annualized) = position.annualized_return(Utc::now()) {
+        lines.push(format!(
+            "Annualized return: {}",
+            format.signed_percent(to_decimal(annualized * 100.0))
+        ));
+    }
+    if let Some(rate) = position.get_interest_rate() {
+        lines.push(String::new());
+        lines.push(format!(
+            "Interest: {}% every {} days",
+            format.number(rate),
+            position.get_payment_frequency_days().unwrap_or_default()
+        ));
+        if let Some(next) = position.get_next_interest_payment() {
+            lines.push(format!("Next payment: {}", date(next)));
+        }
+        if let Some(last) = position.get_last_interest_payment() {
+            lines.push(format!("Last payment: {}", date(last)));
+        }
+        lines.push(format!(
+            "Next 12 months: {}",
+            format.money(position.projected_interest())
+        ));
+    }
+    if let Some(notes) = position.get_notes() {
+        lines.push(String::new());
+        lines.push("Notes:".to_string());
+        lines.extend(notes.lines().map(|line| line.to_string()));
+    }
+    lines
+}
+
+// Breaks a line at the spaces so no part is longer than width, a word that
+// doesn't fit on its own is left whole. Empty lines are kept.
+fn wrap_text(line: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    for word in line.split_whitespace() {
+        if !current.is_empty() && current.chars().count() + 1 + word.chars().count() > width {
+            lines.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+    lines.push(current);
+    lines
+}
+
+// The details of a position for the terminal, wrapped to its width
+pub fn render_position_details(
+    position: &PortfolioPosition,
+    format: &NumberFormat,
+    width: usize,
+) -> String {
+    let mut output = String::new();
+    for line in position_details(position, format) {
+        for part in wrap_text(&line, width) {
+            output.push_str(&part);
+            output.push('\n');
+        }
+    }
+    output
+}
+
+const WATCHLIST_HEADER: [&str; 5] = ["Name", "Ticker", "Price", "Day Change", "Day Change %"];
+
+// The watched tickers with their price and its change since the previous close,
+// laid out like the balances table
+pub fn render_watchlist(
+    watchlist: &[PortfolioPosition],
+    format: &NumberFormat,
+    color: bool,
+    max_quote_age: chrono::Duration,
+) -> String {
+    let rows: Vec<[String; 5]> = watchlist
+        .iter()
+        .map(|position| {
+            let money = position.money_format(format);
+            [
+                position.get_marked_name(max_quote_age),
+                position.get_ticker().unwrap_or_default().to_string(),
+                position
+                    .get_last_spot()
+                    .map(|price| money.money(price))
+                    .unwrap_or_default(),
+                format_change(position.get_price_change(), &money),
+                format_change_percent(position.get_day_change_percent(), format),
+            ]
+        })
+        .collect();
+
+    let mut widths = WATCHLIST_HEADER.map(|title| title.chars().count());
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let pad = |column: usize, cell: &str| {
+        let width = widths[column];
+        if column < TEXT_COLUMNS {
+            format!("{cell:<width$}")
+        } else {
+            format!("{cell:>width$}")
+        }
+    };
+
+    let mut output = String::new();
+    let header: Vec<String> = WATCHLIST_HEADER
+        .iter()
+        .enumerate()
+        .map(|(column, title)| pad(column, title))
+        .collect();
+    output.push_str(&header.join(" | "));
+    output.push('\n');
+    output.push_str(&"=".repeat(widths.iter().sum::<usize>() + 3 * (widths.len() - 1)));
+    output.push('\n');
+
+    for (position, row) in watchlist.iter().zip(&rows) {
+        let cells: Vec<String> = row
+            .iter()
+            .enumerate()
+            .map(|(column, cell)| {
+                let cell = pad(column, cell);
+                if color && column >= 3 {
+                    paint_change(cell, position.get_price_change())
+                } else {
+                    cell
+                }
+            })
+            .collect();
+        output.push_str(cells.join(" | ").trim_end());
+        output.push('\n');
+    }
+
+    output
+}
+
+const ACCOUNTS_HEADER: [&str; 3] = ["Account", "Asset Class", "Balance"];
+
+// The balance of each account followed by those of its asset classes, the
+// accounts by name with the unassigned positions last, the classes by balance
+pub fn render_account_balances(
+    positions: &[PortfolioPosition],
+    format: &NumberFormat,
+    color: bool,
+) -> String {
+    // the account rows are the subtotals
+    let mut rows: Vec<([String; 3], bool)> = Vec::new();
+    for account in group_positions(positions, GroupBy::Account) {
+        rows.push((
+            [account.name, String::new(), format.money(account.subtotal)],
+            true,
+        ));
+        let mut classes = group_position_refs(account.positions, GroupBy::AssetClass);
+        classes.sort_by(|a, b| {
+            b.subtotal
+                .cmp(&a.subtotal)
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        for class in classes {
+            rows.push((
+                [String::new(), class.name, format.money(class.subtotal)],
+                false,
+            ));
+        }
+    }
+
+    let mut widths = ACCOUNTS_HEADER.map(|title| title.chars().count());
+    for (row, _) in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let pad = |column: usize, cell: &str| {
+        let width = widths[column];
+        if column < TEXT_COLUMNS {
+            format!("{cell:<width$}")
+        } else {
+            format!("{cell:>width$}")
+        }
+    };
+
+    let mut output = String::new();
+    let header: Vec<String> = ACCOUNTS_HEADER
+        .iter()
+        .enumerate()
+        .map(|(column, title)| pad(column, title))
+        .collect();
+    output.push_str(&header.join(" | "));
+    output.push('\n');
+    output.push_str(&"=".repeat(widths.iter().sum::<usize>() + 3 * (widths.len() - 1)));
+    output.push('\n');
+
+    for (row, subtotal) in &rows {
+        let cells: Vec<String> = row
+            .iter()
+            .enumerate()
+            .map(|(column, cell)| {
+                let cell = pad(column, cell);
+                if color && *subtotal {
+                    cell.bold().to_string()
+                } else {
+                    cell
+                }
+            })
+            .collect();
+        output.push_str(cells.join(" | ").trim_end());
+        output.push('\n');
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::{from_string, DEFAULT_MAX_QUOTE_AGE_DAYS};
+    use crate::provider::{priced_at, priced_blocking, MockProvider};
+
+    fn priced_portfolio() -> Vec<PortfolioPosition> {
+        let positions_str = std::fs::read_to_string("tests/fixtures/priced.json").unwrap();
+        let provider = MockProvider::new()
+            .with_price("AAPL", 170.0)
+            .with_previous_close("AAPL", 175.0)
+            .with_price("MSFT", 380.0)
+            .with_previous_close("MSFT", 370.0)
+            .with_price("BND", 72.0)
+            .with_previous_close("BND", 72.0)
+            .with_price("EUNL.DE", 90.0)
+            .with_previous_close("EUNL.DE", 88.5)
+            // the quote of the world ETF is a month old
+            .with_quote_time("EUNL.DE", Utc::now() - chrono::Duration::days(30));
+        priced_blocking(&provider, &positions_str)
+    }
+
+    #[test]
+    fn test_render_balances_plain() {
+        let table = render_balances(
+            &priced_portfolio(),
+            &NumberFormat::for_currency("USD"),
+            false,
+            chrono::Duration::days(DEFAULT_MAX_QUOTE_AGE_DAYS),
+        );
+        let expected = "\
+Name                      | Asset Class | Amount |   Balance | Day Change | Day Change % |     Gain
+===================================================================================================
+Vanguard Total Bond       | Bond        |  20.00 | $1,440.00 |      $0.00 |        0.00% |  -$60.00
+Subtotal                  | Bond        |        | $1,440.00 |      $0.00 |        0.00% |  -$60.00
+Savings                   | Cash        | 300.00 |   $300.00 |          - |            - |
+Subtotal                  | Cash        |        |   $300.00 |          - |            - |
+Apple                     | Stock       |  10.00 | $1,700.00 |    -$50.00 |       -2.86% | +$200.00
+Microsoft                 | Stock       |   2.00 |   $760.00 |    +$20.00 |       +2.70% |  -$40.00
+iShares Core MSCI World * | Stock       |   4.00 |   $360.00 |     +$6.00 |       +1.69% |
+Subtotal                  | Stock       |        | $2,820.00 |    -$24.00 |       -0.84% | +$160.00
+===================================================================================================
+Total                     |             |        | $4,560.00 |    -$24.00 |       -0.56% | +$100.00
+";
+        assert_eq!(table, expected);
+    }
+
+    #[test]
+    fn test_color_enabled() {
+        assert!(color_enabled(true, false));
+        assert!(!color_enabled(false, false));
+        assert!(!color_enabled(true, true));
+    }
+
+    #[test]
+    fn test_render_balances_net_worth() {
+        let positions = from_string(
+            r#"[
+                {"Name": "Savings", "AssetClass": "Cash", "Amount": 5000},
+                {"Name": "Car Loan", "AssetClass": "Liability", "Amount": 3000}
+            ]"#,
+        );
+        let table = render_balances(
+            &positions,
+            &NumberFormat::for_currency("USD"),
+            false,
+            chrono::Duration::days(DEFAULT_MAX_QUOTE_AGE_DAYS),
+        );
+        let expected = "\
+Name        | Asset Class |   Amount |    Balance | Day Change | Day Change % | Gain
+====================================================================================
+Savings     | Cash        | 5,000.00 |  $5,000.00 |          - |            - |
+Subtotal    | Cash        |          |  $5,000.00 |          - |            - |
+Car Loan    | Liability   | 3,000.00 | -$3,000.00 |          - |            - |
+Subtotal    | Liability   |          | -$3,000.00 |          - |            - |
+====================================================================================
+Assets      |             |          |  $5,000.00 |          - |            - |
+Liabilities |             |          | -$3,000.00 |          - |            - |
+Net Worth   |             |          |  $2,000.00 |          - |            - |
+";
+        assert_eq!(table, expected);
+    }
+
+    #[test]
+    fn test_render_watchlist() {
+        let provider = MockProvider::new()
+            .with_price("NVDA", 120.0)
+            .with_previous_close("NVDA", 125.0)
+            .with_price("TSLA", 250.0)
+            .with_previous_close("TSLA", 240.0);
+        let watchlist = priced_blocking(
+            &provider,
+            r#"[
+                {"Name": "Nvidia", "Ticker": "NVDA", "AssetClass": "Stock", "Amount": 0},
+                {"Name": "Tesla", "Ticker": "TSLA", "AssetClass": "Stock", "Watch": true}
+            ]"#,
+        );
+
+        let table = render_watchlist(
+            &watchlist,
+            &NumberFormat::for_currency("USD"),
+            false,
+            chrono::Duration::days(DEFAULT_MAX_QUOTE_AGE_DAYS),
+        );
+        let expected = "\
+Name   | Ticker |   Price | Day Change | Day Change %
+=====================================================
+Nvidia | NVDA   | $120.00 |     -$5.00 |       -4.00%
+Tesla  | TSLA   | $250.00 |    +$10.00 |       +4.17%
+";
+        assert_eq!(table, expected);
+    }
+
+    #[test]
+    fn test_position_details() {
+        let position = from_string(
+            r#"[{"Name": "Savings", "AssetClass": "Cash", "Amount": 1000, "InterestRate": 3.65,
+                 "PaymentFrequencyDays": 30, "NextInterestPayment": "2024-01-31"}]"#,
+        )
+        .remove(0);
+        let lines = position_details(&position, &NumberFormat::for_currency("EUR"));
+        assert_eq!(lines[0], "Savings");
+        assert!(lines.contains(&"Interest: 3.65% every 30 days".to_string()));
+        assert!(lines.contains(&"Next payment: 2024-01-31".to_string()));
+        assert!(lines.contains(&"Next 12 months: 36.50 €".to_string()));
+    }
+
+    #[test]
+    fn test_position_details_annualized_return() {
+        let purchased = Utc::now() - chrono::Duration::days(730);
+        let position = priced_at(
+            &format!(
+                r#"[{{"Name": "Apple", "Ticker": "AAPL", "AssetClass": "Stocks", "Amount": 10,
+                     "CostBasis": 100, "PurchaseDate": "{}"}}]"#,
+                purchased.format("%Y-%m-%d")
+            ),
+            |_| 121.0,
+        )
+        .remove(0);
+        let lines = position_details(&position, &NumberFormat::for_currency("USD"));
+        // 1.21 over two years is 10% a year
+        assert!(lines.contains(&"Annualized return: +10.00%".to_string()));
+    }
+
+    #[test]
+    fn test_position_details_notes() {
+        let position = priced_at(
+            r#"[{"Name": "RSUs", "Ticker": "ACME", "AssetClass": "Stocks", "Amount": 10,
+                 "Institution": "Example Broker", "Account": "Equity plan", "Tags": ["work"],
+                 "Notes": "vested in 2024, sellable after the lock-up ends in May 2026\nask HR about taxes"}]"#,
+            |_| 12.5,
+        )
+        .remove(0);
+        let details = render_position_details(&position, &NumberFormat::for_currency("USD"), 30);
+        assert_eq!(
+            details,
+            "RSUs (ACME)\n\
+             Asset class: Stocks\n\
+             Amount: 10.00\n\
+             Institution: Example Broker\n\
+             Account: Equity plan\n\
+             Tags: work\n\
+             Price: $12.50\n\
+             Balance: $125.00\n\
+             \n\
+             Notes:\n\
+             vested in 2024, sellable after\n\
+             the lock-up ends in May 2026\n\
+             ask HR about taxes\n"
+        );
+    }
+
+    #[test]
+    fn test_wrap_text() {
+        assert_eq!(wrap_text("a bb ccc dddd", 6), ["a bb", "ccc", "dddd"]);
+        assert_eq!(
+            wrap_text("see https://example.com/a/long/path", 10),
+            ["see", "https://example.com/a/long/path"]
+        );
+        assert_eq!(wrap_text("", 10), [""]);
+    }
+
+    #[test]
+    fn test_render_balances_position_currency() {
+        let positions = from_string(
+            r#"[
+                {"Name": "ISA", "AssetClass": "Cash", "Amount": 1200, "Currency": "GBP"},
+                {"Name": "Savings", "AssetClass": "Cash", "Amount": 1200}
+            ]"#,
+        );
+        let table = render_balances(
+            &positions,
+            &NumberFormat::for_currency("USD"),
+            false,
+            chrono::Duration::days(DEFAULT_MAX_QUOTE_AGE_DAYS),
+        );
+        let lines: Vec<&str> = table.lines().collect();
+        assert!(lines[2].starts_with("ISA ") && lines[2].contains("£1,200.00"));
+        assert!(lines[3].starts_with("Savings ") && lines[3].contains("$1,200.00"));
+        // the subtotal is in the currency of the portfolio
+        assert!(lines[4].contains("$2,400.00"));
+    }
+
+    #[test]
+    fn test_render_balances_notes_marker() {
+        let positions = from_string(
+            r#"[
+                {"Name": "RSUs", "AssetClass": "Stocks", "Amount": 10, "Notes": "vested"},
+                {"Name": "Savings", "AssetClass": "Cash", "Amount": 100}
+            ]"#,
+        );
+        let table = render_balances(
+            &positions,
+            &NumberFormat::for_currency("USD"),
+            false,
+            chrono::Duration::days(DEFAULT_MAX_QUOTE_AGE_DAYS),
+        );
+        assert!(table.contains(&format!("RSUs {NOTES_MARKER} ")));
+        assert!(table.contains("Savings "));
+        assert!(!table.contains(&format!("Savings {NOTES_MARKER}")));
+        assert!(table.ends_with(&format!(
+            "{NOTES_MARKER} has notes, see `portfolio_rs show NAME`\n"
+        )));
+    }
+
+    #[test]
+    fn test_render_account_balances() {
+        // the same ticker in a taxable account and an IRA
+        let positions = from_string(
+            r#"[
+                {"Name": "S&P 500", "Ticker": "VOO", "AssetClass": "Stocks", "Amount": 10, "ManualPrice": 500, "Account": "Taxable"},
+                {"Name": "S&P 500 (IRA)", "Ticker": "VOO", "AssetClass": "Stocks", "Amount": 4, "ManualPrice": 500, "Account": "IRA"},
+                {"Name": "Bonds (IRA)", "AssetClass": "Bonds", "Amount": 3000, "Account": "IRA"},
+                {"Name": "Settlement", "AssetClass": "Cash", "Amount": 200, "Account": "Taxable"},
+                {"Name": "Wallet", "AssetClass": "Cash", "Amount": 50}
+            ]"#,
+        );
+        let table = render_account_balances(&positions, &NumberFormat::for_currency("USD"), false);
+        let expected = "\
+Account      | Asset Class |   Balance
+======================================
+IRA          |             | $5,000.00
+             | Bonds       | $3,000.00
+             | Stocks      | $2,000.00
+Taxable      |             | $5,200.00
+             | Stocks      | $5,000.00
+             | Cash        |   $200.00
+(unassigned) |             |    $50.00
+             | Cash        |    $50.00
+";
+        assert_eq!(table, expected);
+    }
+}
@@ -1,4 +1,10 @@
+use crate::filter::{PositionSort, SortKey};
+use crate::format::NumberFormat;
+use crate::history::{load_history, resample, HistorySelector};
 use crate::portfolio::Portfolio;
+use crate::position::{to_decimal, PortfolioPosition};
+use crate::table::position_details;
+use chrono::prelude::*;
 use crossterm::{
     event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind},
     execute,
@@ -10,87 +16,30 @@ use ratatui::{
     style::{Color, Modifier, Style},
     text::{Line, Span},
     widgets::{
-        BarChart, Block, Borders, Cell, Clear, List, ListItem, Paragraph, Row, Table, Tabs, Wrap,
+        BarChart, Block, Borders, Cell, Clear, List, ListItem, Paragraph, Row, Sparkline, Table,
+        Tabs, Wrap,
     },
     Frame, Terminal,
 };
+use rust_decimal::Decimal;
 use std::collections::HashMap;
 use std::io;
-use std::time::{Duration, Instant};
+use std::str::FromStr;
+use std::time::Duration;
 use tokio::sync::mpsc;
 use tui_big_text::{BigText, PixelSize};
 
-fn format_currency(value: f64, currency: &str) -> String {
-    let formatted_number = if value >= 1000.0 {
-        format_with_commas(value)
+fn format_amount(amount: Decimal, format: &NumberFormat) -> String {
+    let decimals = if amount.fract().is_zero() {
+        0
+    } else if amount >= Decimal::ONE {
+        2
+    } else if amount >= Decimal::new(1, 2) {
+        4
     } else {
-        match currency {
-            "JPY" => format!("{value:.0}"),
-            _ => format!("{value:.2}"),
-        }
+        8
     };
-
-    match currency {
-        "USD" | "CAD" | "AUD" | "HKD" | "SGD" => format!("${formatted_number}"),
-        "EUR" => format!("{formatted_number} €"),
-        "GBP" => format!("£{formatted_number}"),
-        "JPY" => {
-            let integer_value = value as i64;
-            let formatted = format!("{integer_value}");
-            let formatted_with_commas = formatted
-                .chars()
-                .rev()
-                .collect::<String>()
-                .chars()
-                .collect::<Vec<_>>()
-                .chunks(3)
-                .map(|chunk| chunk.iter().collect::<String>())
-                .collect::<Vec<_>>()
-                .join(",")
-                .chars()
-                .rev()
-                .collect::<String>();
-            format!("¥{formatted_with_commas}")
-        }
-        "CHF" => format!("{formatted_number} CHF"),
-        "SEK" | "NOK" | "DKK" => format!("{formatted_number} {currency}"),
-        _ => format!("{formatted_number} {currency}"),
-    }
-}
-
-fn format_with_commas(value: f64) -> String {
-    let formatted = format!("{value:.2}");
-    let parts: Vec<&str> = formatted.split('.').collect();
-    let integer_part = parts[0];
-    let decimal_part = parts.get(1).unwrap_or(&"00");
-
-    let formatted_integer = integer_part
-        .chars()
-        .rev()
-        .collect::<String>()
-        .chars()
-        .collect::<Vec<_>>()
-        .chunks(3)
-        .map(|chunk| chunk.iter().collect::<String>())
-        .collect::<Vec<_>>()
-        .join(",")
-        .chars()
-        .rev()
-        .collect::<String>();
-
-    format!("{formatted_integer}.{decimal_part}")
-}
-
-fn format_amount(amount: f64) -> String {
-    if amount.fract() == 0.0 {
-        format!("{amount:.0}")
-    } else if amount >= 1.0 {
-        format!("{amount:.2}")
-    } else if amount >= 0.01 {
-        format!("{amount:.4}")
-    } else {
-        format!("{amount:.8}")
-    }
+    format.number_with(amount, decimals)
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -105,6 +54,18 @@ pub enum AppMode {
     Edit,
 }
 
+impl FromStr for Tab {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_lowercase().as_str() {
+            "overview" => Ok(Tab::Overview),
+            "balances" => Ok(Tab::Balances),
+            _ => Err(format!("{s} is not a tab, expected overview or balances")),
+        }
+    }
+}
+
 impl Tab {
     fn title(self) -> &'static str {
         match self {
@@ -116,14 +77,6 @@ impl Tab {
     fn all() -> &'static [Tab] {
         &[Tab::Overview, Tab::Balances]
     }
-
-    pub fn from_str(s: &str) -> Option<Self> {
-        match s.to_lowercase().as_str() {
-            "overview" => Some(Tab::Overview),
-            "balances" => Some(Tab::Balances),
-            _ => None,
-        }
-    }
 }
 
 pub struct App {
@@ -133,15 +86,23 @@ pub struct App {
     pub loading: bool,
     pub error_message: Option<String>,
     pub currency: String,
-    pub previous_values: HashMap<String, f64>,
+    pub number_format: NumberFormat,
+    pub previous_values: HashMap<String, Decimal>,
     pub trends: HashMap<String, Trend>,
-    pub last_update: Instant,
+    // time of the last refresh of the prices
+    pub last_update: DateTime<Local>,
     pub flash_state: bool,
     pub positions_str: String,
     pub mode: AppMode,
+    // index in the positions of the data file, whatever the sort
     pub selected_position: usize,
+    // None keeps the order of the data file
+    pub sort: Option<PositionSort>,
+    // total value of the stored balances runs, see the chart command
+    pub history: Vec<(NaiveDate, f64)>,
     pub edit_input: String,
-    pub data_file_path: String,
+    // several files if a household of portfolios is loaded
+    pub data_files: Vec<String>,
     pub portfolio_receiver: Option<mpsc::UnboundedReceiver<(Portfolio, NetworkStatus)>>,
     pub network_status: NetworkStatus,
 }
@@ -161,7 +122,12 @@ pub enum NetworkStatus {
 }
 
 impl App {
-    pub fn new(currency: String, positions_str: String, data_file_path: String) -> App {
+    pub fn new(
+        currency: String,
+        number_format: NumberFormat,
+        positions_str: String,
+        data_files: Vec<String>,
+    ) -> App {
         App {
             current_tab: Tab::Overview,
             portfolio: None,
@@ -169,15 +135,18 @@ impl App {
             loading: false,
             error_message: None,
             currency,
+            number_format,
             previous_values: HashMap::new(),
             trends: HashMap::new(),
-            last_update: Instant::now(),
+            last_update: Local::now(),
             flash_state: false,
             positions_str,
             mode: AppMode::Normal,
             selected_position: 0,
+            sort: None,
+            history: Vec::new(),
             edit_input: String::new(),
-            data_file_path,
+            data_files,
             portfolio_receiver: None,
             network_status: NetworkStatus::Connected,
         }
@@ -203,11 +172,78 @@ impl App {
         false
     }
 
+    // A refreshed portfolio keeps the selected position, found by its name in case
+    // positions were added or removed. Without it the selection stays in range.
     pub fn set_portfolio(&mut self, portfolio: Portfolio) {
+        let selected_name = self
+            .selected()
+            .map(|position| position.get_name().to_string());
+        self.selected_position = selected_name
+            .and_then(|name| {
+                portfolio
+                    .positions
+                    .iter()
+                    .position(|position| position.get_name() == name)
+            })
+            .unwrap_or(self.selected_position)
+            .min(portfolio.positions.len().saturating_sub(1));
         self.portfolio = Some(portfolio);
         self.loading = false;
     }
 
+    pub fn selected(&self) -> Option<&PortfolioPosition> {
+        self.portfolio
+            .as_ref()
+            .and_then(|portfolio| portfolio.positions.get(self.selected_position))
+    }
+
+    // Indices of the positions in the order of the table. The positions are not
+    // sorted themselves, saving an edit keeps the order of the data file.
+    pub fn display_order(&self) -> Vec<usize> {
+        let Some(portfolio) = &self.portfolio else {
+            return Vec::new();
+        };
+        let mut order: Vec<usize> = (0..portfolio.positions.len()).collect();
+        if let Some(sort) = &self.sort {
+            order.sort_by(|a, b| sort.cmp(&portfolio.positions[*a], &portfolio.positions[*b]));
+        }
+        order
+    }
+
+    // Data file order, then balance, name, gain and asset class in their usual direction
+    pub fn cycle_sort(&mut self) {
+        self.sort = match self.sort.map(|sort| sort.key) {
+            None => Some((SortKey::Balance, true)),
+            Some(SortKey::Balance) => Some((SortKey::Name, false)),
+            Some(SortKey::Name) => Some((SortKey::Gain, true)),
+            Some(SortKey::Gain) => Some((SortKey::AssetClass, false)),
+            Some(SortKey::AssetClass) => None,
+        }
+        .map(|(key, descending)| PositionSort { key, descending });
+    }
+
+    pub fn reverse_sort(&mut self) {
+        if let Some(sort) = &mut self.sort {
+            sort.descending = !sort.descending;
+        }
+    }
+
+    fn sort_label(&self) -> String {
+        match &self.sort {
+            None => "file order".to_string(),
+            Some(sort) => {
+                let key = match sort.key {
+                    SortKey::Balance => "balance",
+                    SortKey::Name => "name",
+                    SortKey::Gain => "gain",
+                    SortKey::AssetClass => "asset class",
+                };
+                let arrow = if sort.descending { "↓" } else { "↑" };
+                format!("{key} {arrow}")
+            }
+        }
+    }
+
     pub fn next_tab(&mut self) {
         let tabs = Tab::all();
         let current_index = tabs
@@ -233,7 +269,7 @@ impl App {
 
             if let Some(&previous_value) = self.previous_values.get(&name) {
                 // Use a small threshold to avoid noise from tiny changes
-                let threshold = 0.01; // 1 cent threshold
+                let threshold = Decimal::new(1, 2); // 1 cent threshold
                 let trend = if current_value > previous_value + threshold {
                     Trend::Up
                 } else if current_value < previous_value - threshold {
@@ -253,7 +289,7 @@ impl App {
     }
 
     pub fn mark_refreshed(&mut self) {
-        self.last_update = Instant::now();
+        self.last_update = Local::now();
         self.flash_state = !self.flash_state; // Toggle flash state for animation
     }
 
@@ -277,17 +313,22 @@ impl App {
         }
     }
 
+    // The next row of the table, which depends on the sort
     pub fn select_next(&mut self) {
-        if let Some(portfolio) = &self.portfolio {
-            if self.selected_position < portfolio.positions.len().saturating_sub(1) {
-                self.selected_position += 1;
+        let order = self.display_order();
+        if let Some(row) = order.iter().position(|&i| i == self.selected_position) {
+            if let Some(&next) = order.get(row + 1) {
+                self.selected_position = next;
             }
         }
     }
 
     pub fn select_previous(&mut self) {
-        if self.selected_position > 0 {
-            self.selected_position -= 1;
+        let order = self.display_order();
+        if let Some(row) = order.iter().position(|&i| i == self.selected_position) {
+            if row > 0 {
+                self.selected_position = order[row - 1];
+            }
         }
     }
 
@@ -297,12 +338,8 @@ impl App {
                 self.mode = AppMode::Edit;
                 // Start with the raw number as string to preserve user input format
                 let amount = portfolio.positions[self.selected_position].get_amount();
-                // Convert to string preserving reasonable precision, but allow user to modify
-                self.edit_input = if amount.fract() == 0.0 {
-                    format!("{}", amount as i64)
-                } else {
-                    format!("{amount}")
-                };
+                // without trailing zeros, e.g. 10 instead of 10.00
+                self.edit_input = amount.normalize().to_string();
             }
         }
     }
@@ -315,9 +352,9 @@ impl App {
     pub fn save_edit(&mut self) -> Result<(), String> {
         if let Some(portfolio) = &mut self.portfolio {
             if self.selected_position < portfolio.positions.len() {
-                match self.edit_input.parse::<f64>() {
+                match self.edit_input.parse::<Decimal>() {
                     Ok(new_amount) => {
-                        if new_amount >= 0.0 {
+                        if !new_amount.is_sign_negative() {
                             // Update the position amount
                             portfolio.positions[self.selected_position].set_amount(new_amount);
 
@@ -342,53 +379,7 @@ impl App {
 
     fn save_to_file(&self) -> Result<(), String> {
         if let Some(portfolio) = &self.portfolio {
-            let positions_data: Vec<serde_json::Value> = portfolio
-                .positions
-                .iter()
-                .map(|pos| {
-                    let mut obj = serde_json::Map::new();
-
-                    // Only include Name if it exists and is different from ticker
-                    if let Some(name) = pos.get_name_option() {
-                        // Only add Name field if it's explicitly set (not derived from ticker)
-                        if pos.get_ticker().is_none() || Some(name) != pos.get_ticker() {
-                            obj.insert(
-                                "Name".to_string(),
-                                serde_json::Value::String(name.to_string()),
-                            );
-                        }
-                    }
-
-                    // Always include Ticker if it exists
-                    if let Some(ticker) = pos.get_ticker() {
-                        obj.insert(
-                            "Ticker".to_string(),
-                            serde_json::Value::String(ticker.to_string()),
-                        );
-                    }
-
-                    obj.insert(
-                        "AssetClass".to_string(),
-                        serde_json::Value::String(pos.get_asset_class().to_string()),
-                    );
-                    obj.insert(
-                        "Amount".to_string(),
-                        serde_json::Value::Number(
-                            serde_json::Number::from_f64(pos.get_amount()).unwrap_or_else(|| {
-                                serde_json::Number::from_f64(0.0).unwrap()
-                            }),
-                        ),
-                    );
-
-                    serde_json::Value::Object(obj)
-                })
-                .collect();
-
-            let json_string = serde_json::to_string_pretty(&positions_data)
-                .map_err(|e| format!("Failed to serialize data: {e}"))?;
-
-            std::fs::write(&self.data_file_path, json_string)
-                .map_err(|e| format!("Failed to write to file: {e}"))?;
+            crate::live::save_data_files(&self.data_files, portfolio)?;
         }
         Ok(())
     }
@@ -397,9 +388,11 @@ impl App {
 pub async fn run_tui(
     portfolio: Portfolio,
     currency: String,
+    number_format: NumberFormat,
     positions_str: String,
-    data_file_path: String,
+    data_files: Vec<String>,
     tab: Option<Tab>,
+    fetch: crate::live::FetchSettings,
 ) -> Result<(), Box<dyn std::error::Error>> {
     enable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -407,8 +400,13 @@ pub async fn run_tui(
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let mut app = App::new(currency, positions_str.clone(), data_file_path);
+    let mut app = App::new(currency, number_format, positions_str.clone(), data_files);
     app.set_portfolio(portfolio);
+    // the history is only read once, the TUI doesn't store balances
+    app.history = sled::open("database")
+        .map_err(|e| format!("Database error: {e}"))
+        .and_then(|db| load_history(&db, &HistorySelector::Total))
+        .unwrap_or_default();
     if let Some(tab) = tab {
         app.current_tab = tab;
     }
@@ -424,7 +422,7 @@ pub async fn run_tui(
         loop {
             interval.tick().await;
             let (portfolio, network_status) =
-                crate::create_live_portfolio(positions_str_bg.clone()).await;
+                crate::live::create_live_portfolio(positions_str_bg.clone(), &fetch).await;
             if portfolio_sender.send((portfolio, network_status)).is_err() {
                 break; // Channel closed, exit task
             }
@@ -472,20 +470,24 @@ async fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::R
                                 KeyCode::Char('l') | KeyCode::Right | KeyCode::Tab => {
                                     app.next_tab();
                                 }
-                                KeyCode::Char('j') | KeyCode::Down => {
-                                    if app.current_tab == Tab::Balances {
-                                        app.select_next();
-                                    }
+                                KeyCode::Char('j') | KeyCode::Down
+                                    if app.current_tab == Tab::Balances =>
+                                {
+                                    app.select_next();
                                 }
-                                KeyCode::Char('k') | KeyCode::Up => {
-                                    if app.current_tab == Tab::Balances {
-                                        app.select_previous();
-                                    }
+                                KeyCode::Char('k') | KeyCode::Up
+                                    if app.current_tab == Tab::Balances =>
+                                {
+                                    app.select_previous();
                                 }
-                                KeyCode::Char('e') => {
-                                    if app.current_tab == Tab::Balances {
-                                        app.enter_edit_mode();
-                                    }
+                                KeyCode::Char('e') if app.current_tab == Tab::Balances => {
+                                    app.enter_edit_mode();
+                                }
+                                KeyCode::Char('s') if app.current_tab == Tab::Balances => {
+                                    app.cycle_sort();
+                                }
+                                KeyCode::Char('r') if app.current_tab == Tab::Balances => {
+                                    app.reverse_sort();
                                 }
                                 KeyCode::BackTab => {
                                     app.previous_tab();
@@ -503,9 +505,11 @@ async fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::R
                                 KeyCode::Enter => {
                                     match app.save_edit() {
                                         Ok(()) => {
-                                            // Update positions_str with new data from file
-                                            if let Ok(new_positions_str) =
-                                                std::fs::read_to_string(&app.data_file_path)
+                                            // Update positions_str with the saved data
+                                            if let Some(Ok(new_positions_str)) = app
+                                                .portfolio
+                                                .as_ref()
+                                                .map(Portfolio::to_data_string)
                                             {
                                                 app.positions_str = new_positions_str;
                                             }
@@ -520,12 +524,11 @@ async fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::R
                                 KeyCode::Backspace => {
                                     app.edit_input.pop();
                                 }
-                                KeyCode::Char(c) => {
+                                KeyCode::Char(c)
                                     if c.is_ascii_digit()
-                                        || (c == '.' && !app.edit_input.contains('.'))
-                                    {
-                                        app.edit_input.push(c);
-                                    }
+                                        || (c == '.' && !app.edit_input.contains('.')) =>
+                                {
+                                    app.edit_input.push(c);
                                 }
                                 _ => {}
                             }
@@ -596,6 +599,7 @@ fn render_overview(f: &mut Frame, area: Rect, app: &App) {
             .direction(Direction::Vertical)
             .constraints([
                 Constraint::Length(7), // Total value display
+                Constraint::Length(6), // History
                 Constraint::Min(0),    // Allocation section
                 Constraint::Length(3), // Help
             ])
@@ -603,34 +607,8 @@ fn render_overview(f: &mut Frame, area: Rect, app: &App) {
 
         // Total Portfolio Value (big text display)
         let total_value = portfolio.get_total_value();
-        // Create formatted currency for big text with full accuracy
-        let big_text_value = match app.currency.as_str() {
-            "USD" | "CAD" | "AUD" | "HKD" | "SGD" => {
-                format!("${}", format_with_commas(total_value))
-            }
-            "EUR" => format!("{} EUR", format_with_commas(total_value)),
-            "GBP" => format!("£{}", format_with_commas(total_value)),
-            "JPY" => {
-                let integer_value = total_value as i64;
-                let formatted = format!("{integer_value}");
-                let formatted_with_commas = formatted
-                    .chars()
-                    .rev()
-                    .collect::<String>()
-                    .chars()
-                    .collect::<Vec<_>>()
-                    .chunks(3)
-                    .map(|chunk| chunk.iter().collect::<String>())
-                    .collect::<Vec<_>>()
-                    .join(",")
-                    .chars()
-                    .rev()
-                    .collect::<String>();
-                format!("{formatted_with_commas} JPY")
-            }
-            "CHF" => format!("{} CHF", format_with_commas(total_value)),
-            _ => format!("{} {}", format_with_commas(total_value), app.currency),
-        };
+        // the big font has no glyphs for currency symbols, so the code is used instead
+        let big_text_value = format!("{} {}", app.number_format.number(total_value), app.currency);
 
         let big_text = BigText::builder()
             .pixel_size(PixelSize::Quadrant)
@@ -651,8 +629,11 @@ fn render_overview(f: &mut Frame, area: Rect, app: &App) {
         let big_text_widget = Block::default()
             .borders(Borders::ALL)
             .title(format!(
-                "Total Portfolio Value ({}) {} {}",
-                app.currency, refresh_indicator, network_indicator
+                "Total Portfolio Value ({}) {} {} Updated {}",
+                app.currency,
+                refresh_indicator,
+                network_indicator,
+                app.last_update.format("%H:%M:%S")
             ))
             .title_alignment(Alignment::Center);
 
@@ -682,11 +663,13 @@ fn render_overview(f: &mut Frame, area: Rect, app: &App) {
 
         f.render_widget(big_text, centered_area);
 
+        render_history(f, main_chunks[1], app);
+
         // Allocation section: bar chart on left, detailed list on right
         let allocation_chunks = Layout::default()
             .direction(Direction::Horizontal)
             .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
-            .split(main_chunks[1]);
+            .split(main_chunks[2]);
 
         let allocation = portfolio.get_allocation();
         let mut allocation_vec: Vec<(&String, &f64)> = allocation.iter().collect();
@@ -729,7 +712,7 @@ fn render_overview(f: &mut Frame, area: Rect, app: &App) {
                         Style::default().fg(trend_color),
                     ),
                     Span::styled(
-                        format!("{percentage:>8.2}%"),
+                        format!("{:>9}", app.number_format.percent(to_decimal(**percentage))),
                         Style::default().fg(trend_color),
                     ),
                 ]))
@@ -747,12 +730,12 @@ fn render_overview(f: &mut Frame, area: Rect, app: &App) {
         f.render_widget(list, allocation_chunks[1]);
 
         // Help text
-        let help_text = Paragraph::new("Navigation: h/l (tabs) | j/k (select in Balances) | e (edit in Balances) | 1-2 (direct) | q (quit)")
+        let help_text = Paragraph::new("Navigation: h/l (tabs) | j/k (select in Balances) | s/r (sort in Balances) | e (edit in Balances) | 1-2 (direct) | q (quit)")
             .block(Block::default().borders(Borders::ALL).title("Help"))
             .style(Style::default().fg(Color::Gray))
             .alignment(Alignment::Center);
 
-        f.render_widget(help_text, main_chunks[2]);
+        f.render_widget(help_text, main_chunks[3]);
     } else {
         render_loading(f, area);
     }
@@ -771,7 +754,8 @@ fn render_balances(f: &mut Frame, area: Rect, app: &App) {
             });
         let header = Row::new(header_cells).height(1).bottom_margin(1);
 
-        let rows = portfolio.positions.iter().enumerate().map(|(i, position)| {
+        let rows = app.display_order().into_iter().map(|i| {
+            let position = &portfolio.positions[i];
             let name = position.get_name();
             let balance_color = app.get_trend_color(name, Color::White);
 
@@ -783,7 +767,7 @@ fn render_balances(f: &mut Frame, area: Rect, app: &App) {
             };
 
             // Add indicator for positions with tickers (live data) vs static positions
-            let name_with_indicator = if position.get_ticker().is_some() {
+            let name_with_indicator = if position.is_market_instrument() {
                 format!("● {}", position.get_name()) // Live data indicator
             } else {
                 format!("○ {}", position.get_name()) // Static data indicator
@@ -792,9 +776,9 @@ fn render_balances(f: &mut Frame, area: Rect, app: &App) {
             let cells = vec![
                 Cell::from(name_with_indicator).style(Style::default().fg(balance_color)),
                 Cell::from(position.get_asset_class()).style(Style::default().fg(balance_color)),
-                Cell::from(format_amount(position.get_amount()))
+                Cell::from(format_amount(position.get_amount(), &app.number_format))
                     .style(Style::default().fg(balance_color)),
-                Cell::from(format_currency(position.get_balance(), &app.currency))
+                Cell::from(position.format_balance(&app.number_format))
                     .style(Style::default().fg(balance_color)),
             ];
             Row::new(cells).height(1).style(row_style)
@@ -809,7 +793,7 @@ fn render_balances(f: &mut Frame, area: Rect, app: &App) {
             ),
             Cell::from(""),
             Cell::from(""),
-            Cell::from(format_currency(total_value, &app.currency)).style(
+            Cell::from(app.number_format.money(total_value)).style(
                 Style::default()
                     .fg(Color::Green)
                     .add_modifier(Modifier::BOLD),
@@ -818,11 +802,21 @@ fn render_balances(f: &mut Frame, area: Rect, app: &App) {
         .height(1);
 
         let help_text = match app.mode {
-            AppMode::Normal => "Navigation: j/k (select) | e (edit) | h/l (tabs) | q (quit)",
+            AppMode::Normal => {
+                "Navigation: j/k (select) | s (sort) | r (reverse) | e (edit) | h/l (tabs) | q (quit)"
+            }
             AppMode::Edit => "Edit Mode: Enter (save) | Esc (cancel)",
         };
 
-        let table_title = format!("Portfolio Balances - {help_text}");
+        let table_title = format!(
+            "Portfolio Balances ({}, updated {}) - {help_text}",
+            app.sort_label(),
+            app.last_update.format("%H:%M:%S")
+        );
+        let chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(65), Constraint::Percentage(35)])
+            .split(area);
 
         let table = Table::new(
             rows.chain(std::iter::once(total_row)),
@@ -837,7 +831,8 @@ fn render_balances(f: &mut Frame, area: Rect, app: &App) {
         .block(Block::default().borders(Borders::ALL).title(table_title))
         .style(Style::default().fg(Color::White));
 
-        f.render_widget(table, area);
+        f.render_widget(table, chunks[0]);
+        render_position_details(f, chunks[1], app);
 
         // Render edit dialog if in edit mode
         if app.mode == AppMode::Edit {
@@ -848,6 +843,63 @@ fn render_balances(f: &mut Frame, area: Rect, app: &App) {
     }
 }
 
+// Heights of the sparkline, from 1 for the lowest value so that it stays visible
+fn sparkline_data(history: &[(NaiveDate, f64)], width: usize) -> Vec<u64> {
+    let values = resample(history, width);
+    let max = values.iter().copied().fold(f64::MIN, f64::max);
+    let min = values.iter().copied().fold(f64::MAX, f64::min);
+    values
+        .iter()
+        .map(|value| {
+            if max > min {
+                ((value - min) / (max - min) * 99.0).round() as u64 + 1
+            } else {
+                50
+            }
+        })
+        .collect()
+}
+
+fn render_history(f: &mut Frame, area: Rect, app: &App) {
+    let (Some((first, _)), Some((last, latest))) = (app.history.first(), app.history.last()) else {
+        let empty = Paragraph::new("No history yet, it is stored by every run of balances")
+            .block(Block::default().borders(Borders::ALL).title("History"))
+            .style(Style::default().fg(Color::Gray))
+            .alignment(Alignment::Center);
+        f.render_widget(empty, area);
+        return;
+    };
+
+    let title = format!(
+        "History {} to {} (last stored {})",
+        first.format("%Y-%m-%d"),
+        last.format("%Y-%m-%d"),
+        app.number_format.money(to_decimal(*latest))
+    );
+    let data = sparkline_data(&app.history, area.width.saturating_sub(2) as usize);
+    let sparkline = Sparkline::default()
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .data(&data)
+        .max(100)
+        .style(Style::default().fg(Color::Green));
+    f.render_widget(sparkline, area);
+}
+
+fn render_position_details(f: &mut Frame, area: Rect, app: &App) {
+    let lines: Vec<Line> = app
+        .selected()
+        .map(|position| position_details(position, &app.number_format))
+        .unwrap_or_default()
+        .into_iter()
+        .map(Line::from)
+        .collect();
+    let details = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::ALL).title("Details"))
+        .style(Style::default().fg(Color::White))
+        .wrap(Wrap { trim: true });
+    f.render_widget(details, area);
+}
+
 fn render_loading(f: &mut Frame, area: Rect) {
     let loading_text = Paragraph::new("Loading portfolio data...")
         .block(Block::default().borders(Borders::ALL).title("Loading"))
@@ -903,10 +955,13 @@ fn render_edit_dialog(f: &mut Frame, app: &App) {
             f.render_widget(info_paragraph, popup_layout[0]);
 
             // Current value display with smart decimal formatting
-            let current_value = format!("Current Amount: {}", format_amount(position.get_amount()));
+            let current_value = format!(
+                "Current Amount: {}",
+                format_amount(position.get_amount(), &app.number_format)
+            );
             let current_balance = format!(
                 "Current Balance: {}",
-                format_currency(position.get_balance(), &app.currency)
+                position.format_balance(&app.number_format)
             );
             let current_text = format!("{current_value}\n{current_balance}");
 
@@ -938,25 +993,23 @@ fn render_edit_dialog(f: &mut Frame, app: &App) {
                     "Enter amount...".to_string(),
                     Style::default().fg(Color::Gray),
                 )
-            } else if let Ok(new_amount) = app.edit_input.parse::<f64>() {
-                if new_amount < 0.0 {
+            } else if let Ok(new_amount) = app.edit_input.parse::<Decimal>() {
+                if new_amount.is_sign_negative() {
                     (
                         "Amount cannot be negative".to_string(),
                         Style::default().fg(Color::Red),
                     )
                 } else {
-                    let new_balance = if position.get_ticker().is_some() {
-                        // For positions with tickers, calculate balance using last spot price
-                        let last_spot = position.get_balance() / position.get_amount();
-                        new_amount * last_spot
-                    } else {
+                    let new_balance = match position.get_last_spot() {
+                        // For quoted or manually priced positions, calculate balance using the price
+                        Some(price) => new_amount * price,
                         // For cash positions, amount equals balance
-                        new_amount
+                        None => new_amount,
                     };
 
                     let preview = format!(
                         "New Balance: {}",
-                        format_currency(new_balance, &app.currency)
+                        position.money_format(&app.number_format).money(new_balance)
                     );
                     (preview, Style::default().fg(Color::Green))
                 }
@@ -964,7 +1017,7 @@ fn render_edit_dialog(f: &mut Frame, app: &App) {
                 // Check if it's a valid intermediate state (like "1." or "0.")
                 let trimmed = app.edit_input.trim();
                 if trimmed.ends_with('.') && trimmed.len() > 1 {
-                    if trimmed[..trimmed.len()-1].parse::<f64>().is_ok() {
+                    if trimmed[..trimmed.len() - 1].parse::<Decimal>().is_ok() {
                         // Valid intermediate state like "1." or "123."
                         (
                             "Enter decimal places...".to_string(),
@@ -1067,3 +1120,131 @@ fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
         ])
         .split(popup_layout[1])[1]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::priced_at;
+
+    fn portfolio(spots: &[(&str, f64)]) -> Portfolio {
+        let mut portfolio = Portfolio::new();
+        let data: Vec<String> = spots
+            .iter()
+            .map(|(name, _)| {
+                format!(r#"{{"Name": "{name}", "Ticker": "{name}", "AssetClass": "Stocks", "Amount": 10}}"#)
+            })
+            .collect();
+        let price = |ticker: &str| spots.iter().find(|(name, _)| *name == ticker).unwrap().1;
+        for position in priced_at(&format!("[{}]", data.join(",")), price) {
+            portfolio.add_position(position);
+        }
+        portfolio
+    }
+
+    fn app() -> App {
+        let mut app = App::new(
+            "EUR".to_string(),
+            NumberFormat::for_currency("EUR"),
+            String::new(),
+            vec!["portfolio.json".to_string()],
+        );
+        app.set_portfolio(portfolio(&[
+            ("Beta", 20.0),
+            ("Alpha", 10.0),
+            ("Gamma", 30.0),
+        ]));
+        app
+    }
+
+    fn selected_name(app: &App) -> &str {
+        app.selected().unwrap().get_name()
+    }
+
+    #[test]
+    fn test_sorting() {
+        let mut app = app();
+        assert_eq!(app.display_order(), [0, 1, 2]);
+        assert_eq!(app.sort_label(), "file order");
+
+        app.cycle_sort();
+        assert_eq!(app.display_order(), [2, 0, 1]);
+        assert_eq!(app.sort_label(), "balance ↓");
+        app.reverse_sort();
+        assert_eq!(app.display_order(), [1, 0, 2]);
+
+        app.cycle_sort();
+        assert_eq!(app.sort_label(), "name ↑");
+        assert_eq!(app.display_order(), [1, 0, 2]);
+        for _ in 0..3 {
+            app.cycle_sort();
+        }
+        assert_eq!(app.sort, None);
+        // the positions themselves keep the order of the data file
+        let names: Vec<&str> = app
+            .portfolio
+            .as_ref()
+            .unwrap()
+            .positions
+            .iter()
+            .map(|position| position.get_name())
+            .collect();
+        assert_eq!(names, ["Beta", "Alpha", "Gamma"]);
+    }
+
+    #[test]
+    fn test_selection_follows_the_sort() {
+        let mut app = app();
+        app.cycle_sort();
+        // Gamma, Beta, Alpha by balance
+        app.selected_position = 2;
+        app.select_next();
+        assert_eq!(selected_name(&app), "Beta");
+        app.select_next();
+        assert_eq!(selected_name(&app), "Alpha");
+        app.select_next();
+        assert_eq!(selected_name(&app), "Alpha");
+        app.select_previous();
+        app.select_previous();
+        app.select_previous();
+        assert_eq!(selected_name(&app), "Gamma");
+    }
+
+    #[test]
+    fn test_refresh_keeps_the_selection() {
+        let mut app = app();
+        app.selected_position = 1;
+        app.update_trends(&portfolio(&[
+            ("Beta", 21.0),
+            ("Alpha", 9.0),
+            ("Gamma", 30.0),
+        ]));
+
+        // Beta was removed, Alpha moved to the front
+        let before = app.last_update;
+        let refreshed = portfolio(&[("Alpha", 12.0), ("Gamma", 30.0)]);
+        app.update_trends(&refreshed);
+        app.set_portfolio(refreshed);
+        app.mark_refreshed();
+        assert_eq!(app.selected_position, 0);
+        assert_eq!(selected_name(&app), "Alpha");
+        assert_eq!(app.trends["Alpha"], Trend::Up);
+        assert!(app.last_update >= before);
+
+        // without the selected position the selection stays in range
+        app.selected_position = 1;
+        app.set_portfolio(portfolio(&[("Delta", 1.0)]));
+        assert_eq!(selected_name(&app), "Delta");
+        app.set_portfolio(Portfolio::new());
+        assert!(app.selected().is_none());
+        assert!(app.display_order().is_empty());
+    }
+
+    #[test]
+    fn test_sparkline_data() {
+        let day = |d| NaiveDate::from_ymd_opt(2024, 1, d).unwrap();
+        let history = [(day(1), 100.0), (day(3), 200.0), (day(4), 150.0)];
+        assert_eq!(sparkline_data(&history, 80), [1, 51, 100, 51]);
+        assert_eq!(sparkline_data(&history[..1], 80), [50]);
+        assert!(sparkline_data(&[], 80).is_empty());
+    }
+}
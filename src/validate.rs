@@ -0,0 +1,703 @@
+use crate::position::{
+    apply_defaults, is_duplicate_of, missing_data_report, weekday_duration, PortfolioPosition,
+    DATA_FILE_KEYS,
+};
+use crate::provider::{PriceProvider, PriceQuote};
+use chrono::prelude::*;
+use colored::Colorize;
+use rust_decimal::{dec, Decimal};
+
+// An annual InterestRate beyond this (in %) is more likely a typo than a rate
+const MAX_PLAUSIBLE_INTEREST_RATE: Decimal = dec!(50);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Ok,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    // for a message, e.g. "Warning: ..."
+    fn label(self) -> &'static str {
+        match self {
+            Severity::Ok => "OK",
+            Severity::Warning => "Warning",
+            Severity::Error => "Error",
+        }
+    }
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Severity::Ok => write!(f, "OK"),
+            Severity::Warning => write!(f, "WARNING"),
+            Severity::Error => write!(f, "ERROR"),
+        }
+    }
+}
+
+// What validate found about a position, or about the data file as a whole
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub subject: String,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Finding {
+    fn new(subject: &str, severity: Severity, message: String) -> Self {
+        Finding {
+            subject: subject.to_string(),
+            severity,
+            message,
+        }
+    }
+}
+
+// Checks the data of one file like --strict parses it, with the Defaults merged into
+// the cash positions, then every position on its own: a position that can't be parsed or has unknown keys is an error, the others
+// are checked with check_position
+pub async fn validate_data(
+    provider: &impl PriceProvider,
+    data: &str,
+    max_quote_age: chrono::Duration,
+    now: DateTime<Utc>,
+) -> Vec<Finding> {
+    const FILE: &str = "Data file";
+    let mut value: serde_json::Value = match serde_json::from_str(data) {
+        Ok(value) => value,
+        Err(e) => {
+            return vec![Finding::new(
+                FILE,
+                Severity::Error,
+                format!("the data can't be parsed: {e}"),
+            )]
+        }
+    };
+
+    apply_defaults(&mut value);
+    let mut findings = Vec::new();
+    let positions = match value {
+        serde_json::Value::Array(positions) => positions,
+        serde_json::Value::Object(mut object) => {
+            for key in object.keys() {
+                if !DATA_FILE_KEYS.contains(&key.as_str()) {
+                    findings.push(Finding::new(
+                        FILE,
+                        Severity::Error,
+                        format!("unknown key {key}"),
+                    ));
+                }
+            }
+            match object.remove("Positions") {
+                Some(serde_json::Value::Array(positions)) => positions,
+                _ => {
+                    findings.push(Finding::new(
+                        FILE,
+                        Severity::Error,
+                        "there is no list of Positions".to_string(),
+                    ));
+                    Vec::new()
+                }
+            }
+        }
+        _ => {
+            findings.push(Finding::new(
+                FILE,
+                Severity::Error,
+                "the data is neither a list of positions nor an object with Positions".to_string(),
+            ));
+            Vec::new()
+        }
+    };
+
+    for (index, value) in positions.into_iter().enumerate() {
+        // the position is named like it would be in the reports, if it can be
+        let subject = ["Name", "Ticker", "ISIN"]
+            .iter()
+            .find_map(|key| value.get(key).and_then(|value| value.as_str()))
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("Position {}", index + 1));
+        match serde_json::from_value::<PortfolioPosition>(value) {
+            Ok(position) => {
+                findings.extend(check_position(provider, &position, max_quote_age, now).await)
+            }
+            Err(e) => findings.push(Finding::new(
+                &subject,
+                Severity::Error,
+                format!("the position can't be parsed: {e}"),
+            )),
+        }
+    }
+    findings
+}
+
+// Unknown keys and tickers without a quote are errors, missing or inconsistent data
+// is a warning (the other commands show the position anyway). A position without
+// either is OK, with the name and the currency of its quote.
+pub async fn check_position(
+    provider: &impl PriceProvider,
+    position: &PortfolioPosition,
+    max_quote_age: chrono::Duration,
+    now: DateTime<Utc>,
+) -> Vec<Finding> {
+    let subject = position.get_name();
+    let finding = |severity: Severity, message: String| Finding::new(subject, severity, message);
+    let mut findings: Vec<Finding> = position
+        .unknown_keys()
+        .map(|key| finding(Severity::Error, format!("unknown key {key}")))
+        .collect();
+
+    for (_, missing) in missing_data_report(std::slice::from_ref(position)) {
+        findings.push(finding(
+            Severity::Warning,
+            format!("{} is missing", missing.join(", ")),
+        ));
+    }
+    for warning in position.interest_date_warnings(now) {
+        findings.push(finding(Severity::Warning, warning));
+    }
+    if let Some(rate) = position
+        .get_interest_rate()
+        .filter(|rate| rate.abs() > MAX_PLAUSIBLE_INTEREST_RATE)
+    {
+        findings.push(finding(
+            Severity::Warning,
+            format!("the InterestRate {rate} is not a plausible annual rate in %"),
+        ));
+    }
+
+    let summary = if position.is_market_instrument() {
+        match check_quote(provider, position, max_quote_age, now).await {
+            Ok((summary, warnings)) => {
+                findings.extend(warnings.into_iter().map(|w| finding(Severity::Warning, w)));
+                Some(summary)
+            }
+            Err(e) => {
+                findings.push(finding(Severity::Error, e));
+                None
+            }
+        }
+    } else if position.has_manual_price() {
+        Some("manual price".to_string())
+    } else {
+        Some("valued at its amount".to_string())
+    };
+    if let (true, Some(summary)) = (findings.is_empty(), summary) {
+        findings.push(finding(Severity::Ok, summary));
+    }
+    findings
+}
+
+// The ticker, name and currency of the first listing (or the symbol of the ISIN) that
+// has a quote, and the warnings about it
+async fn check_quote(
+    provider: &impl PriceProvider,
+    position: &PortfolioPosition,
+    max_quote_age: chrono::Duration,
+    now: DateTime<Utc>,
+) -> Result<(String, Vec<String>), String> {
+    let mut warnings = Vec::new();
+    let tickers = match (position.get_listings(), position.get_isin()) {
+        ([], Some(isin)) => {
+            let symbols = provider
+                .find_symbols(isin)
+                .await
+                .map_err(|e| format!("the symbol of the ISIN {isin} can't be looked up: {e}"))?;
+            match symbols.first() {
+                Some(symbol) => vec![symbol.clone()],
+                None => return Err(format!("no symbol found for the ISIN {isin}")),
+            }
+        }
+        (listings, _) => listings.to_vec(),
+    };
+
+    let mut quoted: Option<(String, PriceQuote)> = None;
+    let mut errors = Vec::new();
+    for ticker in tickers {
+        match provider.get_latest_price(&ticker).await {
+            Ok(quote) => {
+                quoted = Some((ticker, quote));
+                break;
+            }
+            Err(e) => errors.push(format!("{ticker}: {e}")),
+        }
+    }
+    let Some((ticker, quote)) = quoted else {
+        return Err(format!("unknown ticker ({})", errors.join("; ")));
+    };
+    if !errors.is_empty() {
+        warnings.push(format!(
+            "priced with {ticker}, there is no quote for {}",
+            errors.join("; ")
+        ));
+    }
+
+    match quote.time {
+        Some(time) if weekday_duration(time, now) > max_quote_age => warnings.push(format!(
+            "the latest quote of {ticker} is from {}, older than {} day(s)",
+            time.format("%Y-%m-%d"),
+            max_quote_age.num_days()
+        )),
+        Some(_) => {}
+        None => warnings.push(format!("the latest quote of {ticker} has no time")),
+    }
+
+    let name = match provider.get_name(&ticker).await {
+        Ok(name) => {
+            if let Some(given) = position.get_given_name() {
+                if !names_match(given, &name) {
+                    warnings.push(format!(
+                        "the Name {given} doesn't match {name}, the name of {ticker}"
+                    ));
+                }
+            }
+            name
+        }
+        Err(e) => {
+            warnings.push(format!("no name found for {ticker}: {e}"));
+            "no name".to_string()
+        }
+    };
+    let currency = quote.currency.as_deref().unwrap_or("unknown currency");
+    Ok((format!("{ticker}: {name}, {currency}"), warnings))
+}
+
+// Names are rarely written like Yahoo has them ("Apple" for "Apple Inc."), so they
+// match if one contains the other or they start with the same word, ignoring case
+// and punctuation
+fn names_match(given: &str, resolved: &str) -> bool {
+    let words = |name: &str| -> Vec<String> {
+        name.split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(str::to_lowercase)
+            .collect()
+    };
+    let (given, resolved) = (words(given), words(resolved));
+    let (given_text, resolved_text) = (given.join(" "), resolved.join(" "));
+    given
+        .first()
+        .is_some_and(|word| resolved.first() == Some(word))
+        || given_text.contains(&resolved_text)
+        || resolved_text.contains(&given_text)
+}
+
+// One line per finding, in the order of the positions, and how many there are of each
+pub fn print_findings(findings: &[Finding]) {
+    for finding in findings {
+        let severity = format!("{:<7}", finding.severity.to_string());
+        let severity = match finding.severity {
+            Severity::Ok => severity.green(),
+            Severity::Warning => severity.yellow(),
+            Severity::Error => severity.red(),
+        };
+        println!("{severity} {}: {}", finding.subject, finding.message);
+    }
+    let count = |severity: Severity| {
+        findings
+            .iter()
+            .filter(|finding| finding.severity == severity)
+            .count()
+    };
+    println!(
+        "{} OK, {} warning(s), {} error(s)",
+        count(Severity::Ok),
+        count(Severity::Warning),
+        count(Severity::Error)
+    );
+}
+
+pub fn has_errors(findings: &[Finding]) -> bool {
+    findings
+        .iter()
+        .any(|finding| finding.severity == Severity::Error)
+}
+
+// A problem of a parsed position that the other commands would silently work
+// around, found by validate_positions
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationIssue {
+    // of the position in the data file, from 0
+    pub index: usize,
+    pub name: String,
+    pub severity: Severity,
+    pub message: String,
+    // what to change in the data file
+    pub suggestion: String,
+}
+
+impl std::fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (position {}): {}, {}",
+            self.name,
+            self.index + 1,
+            self.message,
+            self.suggestion
+        )
+    }
+}
+
+// The checks that need no quotes, run on every data file after it is loaded.
+// Errors are data the calculations can't use as it is written (an interest that is
+// never paid, a debt that is counted as an asset), warnings are data that is used
+// but likely not what was meant.
+pub fn validate_positions(positions: &[PortfolioPosition]) -> Vec<ValidationIssue> {
+    let mut issues = Vec::new();
+    for (index, position) in positions.iter().enumerate() {
+        let mut issue = |severity: Severity, message: String, suggestion: &str| {
+            issues.push(ValidationIssue {
+                index,
+                name: position.get_name().to_string(),
+                severity,
+                message,
+                suggestion: suggestion.to_string(),
+            })
+        };
+
+        if position.get_amount() < Decimal::ZERO
+            && !position.is_liability_class()
+            && !position.is_cash()
+        {
+            issue(
+                Severity::Error,
+                format!(
+                    "the Amount {} of a {} position is negative",
+                    position.get_amount(),
+                    position.get_asset_class()
+                ),
+                "give a debt the AssetClass Liability and the Amount owed, or make the Amount positive",
+            );
+        }
+        if position.get_payment_frequency_days() == Some(0) {
+            issue(
+                Severity::Error,
+                "the PaymentFrequencyDays is 0, interest is never paid".to_string(),
+                "set it to the days between two payments, e.g. 30 for monthly payments",
+            );
+        }
+        if let (Some(next), Some(last)) = (
+            position.get_given_next_interest_payment(),
+            position.get_last_interest_payment(),
+        ) {
+            if next <= last {
+                issue(
+                    Severity::Warning,
+                    format!(
+                        "the NextInterestPayment {} is not after the LastInterestPayment {}",
+                        next.format("%Y-%m-%d"),
+                        last.format("%Y-%m-%d")
+                    ),
+                    "set the NextInterestPayment to the date of the coming payment",
+                );
+            }
+        }
+        if position.has_interest_terms() && !position.is_cash() && !position.is_liability_class() {
+            issue(
+                Severity::Error,
+                format!(
+                    "interest is only paid on Cash or a Liability, not on {}",
+                    position.get_asset_class()
+                ),
+                "remove the interest keys, or use a Cash position for the interest it pays",
+            );
+        }
+
+        let duplicate = positions[..index]
+            .iter()
+            .position(|other| is_duplicate_of(other, position));
+        if let Some(first) = duplicate {
+            let same = match (position.get_listings().first(), position.get_isin()) {
+                (Some(_), _) => "Ticker",
+                (None, Some(_)) => "ISIN",
+                (None, None) => "Name, without a Ticker,",
+            };
+            issue(
+                Severity::Warning,
+                format!(
+                    "the same {same} as position {}, it is counted twice",
+                    first + 1
+                ),
+                "merge them with balances --merge-duplicates, or set the Account of each if they are held apart",
+            );
+        }
+    }
+    issues
+}
+
+// The issues of a data file on stderr, so they don't end up in the output of a command
+pub fn print_validation_issues(source: &str, issues: &[ValidationIssue]) {
+    for issue in issues {
+        let line = format!("{}: {source}: {issue}", issue.severity.label());
+        match issue.severity {
+            Severity::Error => eprintln!("{}", line.red()),
+            _ => eprintln!("{}", line.yellow()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::MockProvider;
+
+    fn messages(findings: &[Finding], subject: &str) -> Vec<(Severity, String)> {
+        findings
+            .iter()
+            .filter(|finding| finding.subject == subject)
+            .map(|finding| (finding.severity, finding.message.clone()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_validate_data() {
+        let now = Utc::now();
+        let provider = MockProvider::new()
+            .with_price("AAPL", 170.0)
+            .with_name("AAPL", "Apple Inc.")
+            .with_currency("AAPL", "USD")
+            .with_price("MSFT", 400.0)
+            .with_name("MSFT", "Microsoft Corporation")
+            .with_price("VWCE.DE", 110.0)
+            .with_name("VWCE.DE", "Vanguard FTSE All-World UCITS ETF")
+            .with_quote_time("VWCE.DE", now - chrono::Duration::days(30));
+        let data = r#"{
+            "Name": "Alex",
+            "Owner": "Alex",
+            "Positions": [
+                {"Name": "Apple", "Ticker": "AAPL", "AssetClass": "Stocks", "Amount": 10},
+                {"Name": "Tesla", "Ticker": "MSFT", "AssetClass": "Stocks", "Amount": 1},
+                {"Name": "Vanguard FTSE All-World", "Ticker": "VWCE.DE", "AssetClass": "ETF", "Amount": 5},
+                {"Name": "Typo", "Ticker": "APPL", "AssetClass": "Stocks", "Amount": 1},
+                {"Name": "Broken", "AssetClass": "Stocks", "Amount": "ten"},
+                {"Name": "House", "AssetClass": "Real Estate", "Amount": 300000, "Broker": "none"},
+                {"Name": "Savings", "AssetClass": "Cash", "Amount": 1000, "InterestRate": 250,
+                 "PaymentFrequencyDays": 0, "NextInterestPayment": "2024-01-01",
+                 "LastInterestPayment": "2024-01-31"}
+            ]
+        }"#;
+        let findings = validate_data(&provider, data, chrono::Duration::days(3), now).await;
+
+        assert_eq!(
+            messages(&findings, "Data file"),
+            [(Severity::Error, "unknown key Owner".to_string())]
+        );
+        assert_eq!(
+            messages(&findings, "Apple"),
+            [(Severity::Ok, "AAPL: Apple Inc., USD".to_string())]
+        );
+        assert_eq!(
+            messages(&findings, "Tesla"),
+            [(
+                Severity::Warning,
+                "the Name Tesla doesn't match Microsoft Corporation, the name of MSFT".to_string()
+            )]
+        );
+        let stale = messages(&findings, "Vanguard FTSE All-World");
+        assert_eq!(stale.len(), 1);
+        assert_eq!(stale[0].0, Severity::Warning);
+        assert!(stale[0]
+            .1
+            .starts_with("the latest quote of VWCE.DE is from "));
+        let typo = messages(&findings, "Typo");
+        assert_eq!(typo.len(), 1);
+        assert_eq!(typo[0].0, Severity::Error);
+        assert!(typo[0].1.starts_with("unknown ticker (APPL: "));
+        let broken = messages(&findings, "Broken");
+        assert_eq!(broken.len(), 1);
+        assert_eq!(broken[0].0, Severity::Error);
+        assert!(broken[0].1.starts_with("the position can't be parsed: "));
+        assert_eq!(
+            messages(&findings, "House"),
+            [(Severity::Error, "unknown key Broker".to_string())]
+        );
+        let savings: Vec<Severity> = messages(&findings, "Savings")
+            .into_iter()
+            .map(|(severity, _)| severity)
+            .collect();
+        // no frequency, the dates in the wrong order and a rate of 250%
+        assert_eq!(savings, [Severity::Warning; 3]);
+        assert!(has_errors(&findings));
+    }
+
+    #[tokio::test]
+    async fn test_validate_interest_dates() {
+        let provider = MockProvider::new();
+        let savings = r#"[{"Name": "Savings", "AssetClass": "Cash", "Amount": 1000,
+            "InterestRate": 2.5, "PaymentFrequencyDays": 30,
+            "NextInterestPayment": "2024-02-15", "LastInterestPayment": "2024-01-15"}]"#;
+        let at = |date: &str| {
+            NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap()
+                .and_utc()
+        };
+        let max_age = chrono::Duration::days(3);
+
+        let findings = validate_data(&provider, savings, max_age, at("2024-02-01")).await;
+        assert_eq!(
+            messages(&findings, "Savings"),
+            [(Severity::Ok, "valued at its amount".to_string())]
+        );
+        let findings = validate_data(&provider, savings, max_age, at("2024-01-01")).await;
+        assert_eq!(
+            messages(&findings, "Savings"),
+            [(
+                Severity::Warning,
+                "the LastInterestPayment 2024-01-15 is in the future, no interest is credited until the next payment".to_string()
+            )]
+        );
+        assert!(!has_errors(&findings));
+    }
+
+    #[tokio::test]
+    async fn test_validate_isin_and_listings() {
+        let provider = MockProvider::new()
+            .with_symbols("IE00B4L5Y983", &["EUNL.DE"])
+            .with_price("EUNL.DE", 90.0)
+            .with_name("EUNL.DE", "iShares Core MSCI World UCITS ETF")
+            .with_price("RDSB.L", 25.0)
+            .with_name("RDSB.L", "Shell plc");
+        let data = r#"[
+            {"Name": "iShares Core MSCI World", "ISIN": "IE00B4L5Y983", "AssetClass": "ETF", "Amount": 1},
+            {"Name": "Unknown ETF", "ISIN": "XX0000000000", "AssetClass": "ETF", "Amount": 1},
+            {"Name": "Shell", "Ticker": ["SHEL", "RDSB.L"], "AssetClass": "Stocks", "Amount": 1}
+        ]"#;
+        let findings = validate_data(&provider, data, chrono::Duration::days(3), Utc::now()).await;
+
+        assert_eq!(
+            messages(&findings, "iShares Core MSCI World"),
+            [(
+                Severity::Ok,
+                "EUNL.DE: iShares Core MSCI World UCITS ETF, unknown currency".to_string()
+            )]
+        );
+        assert_eq!(
+            messages(&findings, "Unknown ETF"),
+            [(
+                Severity::Error,
+                "no symbol found for the ISIN XX0000000000".to_string()
+            )]
+        );
+        let shell = messages(&findings, "Shell");
+        assert_eq!(shell.len(), 1);
+        assert_eq!(shell[0].0, Severity::Warning);
+        assert!(shell[0]
+            .1
+            .starts_with("priced with RDSB.L, there is no quote for SHEL: "));
+    }
+
+    #[tokio::test]
+    async fn test_validate_invalid_json() {
+        let findings = validate_data(
+            &MockProvider::new(),
+            "[{\"Name\": ",
+            chrono::Duration::days(3),
+            Utc::now(),
+        )
+        .await;
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].severity, Severity::Error);
+        assert!(findings[0]
+            .message
+            .starts_with("the data can't be parsed: "));
+    }
+
+    #[test]
+    fn test_names_match() {
+        assert!(names_match("Apple", "Apple Inc."));
+        assert!(names_match("apple inc", "Apple Inc."));
+        assert!(names_match(
+            "Vanguard FTSE All-World",
+            "Vanguard FTSE All-World UCITS ETF"
+        ));
+        assert!(names_match("Bitcoin", "Bitcoin EUR"));
+        assert!(!names_match("Tesla", "Microsoft Corporation"));
+        assert!(!names_match("My Apple shares", "Apple Inc."));
+    }
+
+    #[test]
+    fn test_validate_positions() {
+        let data = std::fs::read_to_string("tests/fixtures/invalid_positions.json").unwrap();
+        let issues = validate_positions(&crate::position::from_string(&data));
+        let found: Vec<(usize, &str, Severity, &str)> = issues
+            .iter()
+            .map(|issue| {
+                (
+                    issue.index,
+                    issue.name.as_str(),
+                    issue.severity,
+                    issue.message.as_str(),
+                )
+            })
+            .collect();
+
+        // Apple, the Mortgage, the Overdraft and VOO in two accounts are fine
+        let expected = [
+            (
+                1,
+                "Short Tesla",
+                Severity::Error,
+                "the Amount -5 of a Stocks position is negative",
+            ),
+            (
+                2,
+                "Savings",
+                Severity::Error,
+                "the PaymentFrequencyDays is 0, interest is never paid",
+            ),
+            (
+                3,
+                "Deposit",
+                Severity::Warning,
+                "the NextInterestPayment 2024-01-01 is not after the LastInterestPayment 2024-01-31",
+            ),
+            (
+                4,
+                "Dividend Stock",
+                Severity::Error,
+                "interest is only paid on Cash or a Liability, not on Stocks",
+            ),
+            (
+                5,
+                "Apple",
+                Severity::Warning,
+                "the same Ticker as position 1, it is counted twice",
+            ),
+            (
+                9,
+                "checking",
+                Severity::Warning,
+                "the same Name, without a Ticker, as position 9, it is counted twice",
+            ),
+        ];
+        assert_eq!(found, expected);
+        assert!(issues.iter().all(|issue| !issue.suggestion.is_empty()));
+        assert_eq!(
+            issues[1].to_string(),
+            "Savings (position 3): the PaymentFrequencyDays is 0, interest is never paid, \
+             set it to the days between two payments, e.g. 30 for monthly payments"
+        );
+    }
+
+    #[test]
+    fn test_validate_positions_of_several_portfolios() {
+        // the same position in two files of a household is not a duplicate
+        let positions = crate::household::combine(&[
+            (
+                "alex.json".to_string(),
+                r#"[{"Name": "Savings", "AssetClass": "Cash", "Amount": 100}]"#.to_string(),
+            ),
+            (
+                "sam.json".to_string(),
+                r#"[{"Name": "Savings", "AssetClass": "Cash", "Amount": 200}]"#.to_string(),
+            ),
+        ])
+        .unwrap();
+        assert!(validate_positions(&crate::position::from_string(&positions)).is_empty());
+    }
+}
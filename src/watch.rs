@@ -0,0 +1,277 @@
+use crate::filter::{PositionFilter, PositionSort};
+use crate::format::NumberFormat;
+use crate::portfolio::Portfolio;
+use crate::position::{handle_positions, PortfolioPosition};
+use crate::provider::PriceProvider;
+use crate::table::{render_balances, render_watchlist};
+use chrono::prelude::*;
+use colored::Colorize;
+use rust_decimal::Decimal;
+
+// What balances --watch shows besides the quotes
+pub struct WatchOptions {
+    pub interval_seconds: u64,
+    pub filter: PositionFilter,
+    pub sort: Option<PositionSort>,
+    pub max_quote_age: chrono::Duration,
+    pub color: bool,
+}
+
+// The priced positions of one refresh, in the order of the data file
+pub struct WatchFrame {
+    pub positions: Vec<PortfolioPosition>,
+    pub total_investment: Option<Decimal>,
+    pub time: DateTime<Utc>,
+    // the positions that kept the values of the refresh before
+    pub warnings: Vec<String>,
+}
+
+impl WatchFrame {
+    // The positions shown with the filter and the sort, like balances does it
+    pub fn portfolio(&self, options: &WatchOptions) -> Portfolio {
+        let mut portfolio = Portfolio::new();
+        for position in &self.positions {
+            portfolio.add_position(position.clone());
+        }
+        portfolio.total_investment = self.total_investment;
+        if let Some(total) = self.total_investment {
+            // the error is the one of the first refresh, shown by balances
+            let _ = portfolio.resolve_amounts(total);
+        }
+        portfolio.apply_filter(&options.filter);
+        if let Some(sort) = &options.sort {
+            portfolio.sort(sort);
+        }
+        portfolio
+    }
+}
+
+// Prices the positions again. A position whose quote can't be fetched keeps its
+// value of the previous frame and gets a warning, so a network failure doesn't
+// blank the table. Without a previous value it is shown unpriced.
+pub async fn refresh(
+    provider: &impl PriceProvider,
+    positions: &[PortfolioPosition],
+    total_investment: Option<Decimal>,
+    previous: Option<&WatchFrame>,
+    now: DateTime<Utc>,
+) -> WatchFrame {
+    let results = handle_positions(provider, positions.to_vec(), |_, _| {}).await;
+    let mut priced = Vec::new();
+    let mut warnings = Vec::new();
+    for (index, (position, result)) in positions.iter().zip(results).enumerate() {
+        match result {
+            Ok(position) => priced.push(position),
+            Err(e) => {
+                let label = position.get_ticker().unwrap_or(position.get_name());
+                // the data file is read once, so the positions of both frames line up
+                let kept = previous.and_then(|previous| {
+                    previous
+                        .positions
+                        .get(index)
+                        .map(|kept| (kept, previous.time))
+                });
+                match kept {
+                    Some((kept, time)) => {
+                        warnings.push(format!(
+                            "Could not refresh {label} ({e}), showing the value of {}",
+                            time.with_timezone(&Local).format("%H:%M:%S")
+                        ));
+                        priced.push(kept.clone());
+                    }
+                    None => {
+                        warnings.push(format!("Could not fetch {label} ({e})"));
+                        priced.push(position.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    WatchFrame {
+        positions: priced,
+        total_investment,
+        time: now,
+        warnings,
+    }
+}
+
+// One screen of the watch mode: the balances table, the change of the total since
+// the previous refresh and a banner with the positions that couldn't be refreshed
+pub fn render_frame(
+    frame: &WatchFrame,
+    previous: Option<&WatchFrame>,
+    options: &WatchOptions,
+    format: &NumberFormat,
+) -> String {
+    let portfolio = frame.portfolio(options);
+    let mut screen = format!(
+        "Balances at {}, refreshed every {} s (Ctrl-C to quit)\n\n",
+        frame.time.with_timezone(&Local).format("%H:%M:%S"),
+        options.interval_seconds
+    );
+    screen.push_str(&render_balances(
+        &portfolio.positions,
+        format,
+        options.color,
+        options.max_quote_age,
+    ));
+    if !portfolio.watchlist.is_empty() {
+        screen.push_str("\nWatchlist\n");
+        screen.push_str(&render_watchlist(
+            &portfolio.watchlist,
+            format,
+            options.color,
+            options.max_quote_age,
+        ));
+    }
+
+    let total = portfolio.get_total_value();
+    screen.push_str(&format!("\nTotal: {}", format.money(total)));
+    if let Some(previous) = previous {
+        let change = total - previous.portfolio(options).get_total_value();
+        screen.push_str(&format!(
+            " ({} since {})",
+            format.signed_money(change),
+            previous.time.with_timezone(&Local).format("%H:%M:%S")
+        ));
+    }
+    screen.push('\n');
+
+    for warning in &frame.warnings {
+        let warning = format!("Warning: {warning}");
+        if options.color {
+            screen.push_str(&format!("{}\n", warning.yellow()));
+        } else {
+            screen.push_str(&format!("{warning}\n"));
+        }
+    }
+    screen
+}
+
+// Refreshes and redraws the balances every interval until Ctrl-C
+pub async fn run_watch(
+    provider: &impl PriceProvider,
+    positions: Vec<PortfolioPosition>,
+    total_investment: Option<Decimal>,
+    options: WatchOptions,
+    format: &NumberFormat,
+) {
+    let mut interval =
+        tokio::time::interval(std::time::Duration::from_secs(options.interval_seconds));
+    let mut previous: Option<WatchFrame> = None;
+    loop {
+        let frame = tokio::select! {
+            frame = async {
+                interval.tick().await;
+                refresh(provider, &positions, total_investment, previous.as_ref(), Utc::now()).await
+            } => frame,
+            _ = tokio::signal::ctrl_c() => break,
+        };
+        // clear the screen and move the cursor to the top
+        print!(
+            "\x1B[2J\x1B[H{}",
+            render_frame(&frame, previous.as_ref(), &options, format)
+        );
+        previous = Some(frame);
+    }
+    println!();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::from_string;
+    use crate::provider::MockProvider;
+    use rust_decimal::dec;
+
+    fn positions() -> Vec<PortfolioPosition> {
+        from_string(
+            r#"[
+                {"Name": "Apple", "Ticker": "AAPL", "AssetClass": "Stocks", "Amount": 10},
+                {"Name": "Microsoft", "Ticker": "MSFT", "AssetClass": "Stocks", "Amount": 2},
+                {"Name": "Savings", "AssetClass": "Cash", "Amount": 500}
+            ]"#,
+        )
+    }
+
+    fn options() -> WatchOptions {
+        WatchOptions {
+            interval_seconds: 60,
+            filter: PositionFilter::default(),
+            sort: None,
+            max_quote_age: chrono::Duration::days(7),
+            color: false,
+        }
+    }
+
+    fn time(minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, 4, 15, minute, 0).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_refresh() {
+        let format = NumberFormat::for_currency("EUR");
+        let first = MockProvider::new()
+            .with_price("AAPL", 180.0)
+            .with_price("MSFT", 400.0);
+        let frame = refresh(&first, &positions(), None, None, time(0)).await;
+        assert!(frame.warnings.is_empty());
+        assert_eq!(frame.portfolio(&options()).get_total_value(), dec!(3100));
+        let screen = render_frame(&frame, None, &options(), &format);
+        assert!(screen.contains("refreshed every 60 s"));
+        assert!(screen.contains("\nTotal: 3,100.00 €\n"));
+
+        // AAPL moved, the total change is shown
+        let second = MockProvider::new()
+            .with_price("AAPL", 181.5)
+            .with_price("MSFT", 400.0);
+        let next = refresh(&second, &positions(), None, Some(&frame), time(1)).await;
+        let screen = render_frame(&next, Some(&frame), &options(), &format);
+        assert!(screen.contains("Total: 3,115.00 € (+15.00 € since "));
+    }
+
+    #[tokio::test]
+    async fn test_refresh_keeps_the_previous_values() {
+        let format = NumberFormat::for_currency("EUR");
+        let provider = MockProvider::new()
+            .with_price("AAPL", 180.0)
+            .with_price("MSFT", 400.0);
+        let frame = refresh(&provider, &positions(), None, None, time(0)).await;
+
+        // offline: every quote fails, the values of the frame before stay on screen
+        let offline = MockProvider::new();
+        let next = refresh(&offline, &positions(), None, Some(&frame), time(1)).await;
+        assert_eq!(next.warnings.len(), 2);
+        assert!(next.warnings[0].starts_with("Could not refresh AAPL"));
+        assert_eq!(next.portfolio(&options()).get_total_value(), dec!(3100));
+        let screen = render_frame(&next, Some(&frame), &options(), &format);
+        assert!(screen.contains("Total: 3,100.00 € (0.00 € since "));
+        assert!(screen.contains("\nWarning: Could not refresh MSFT"));
+
+        // without a previous value the position is unpriced
+        let first = refresh(&offline, &positions(), None, None, time(0)).await;
+        assert_eq!(
+            first.warnings[0],
+            "Could not fetch AAPL (yahoo! finance returned an empty data set)"
+        );
+        assert_eq!(first.portfolio(&options()).get_total_value(), dec!(500));
+    }
+
+    #[tokio::test]
+    async fn test_refresh_with_filter() {
+        let provider = MockProvider::new()
+            .with_price("AAPL", 180.0)
+            .with_price("MSFT", 400.0);
+        let frame = refresh(&provider, &positions(), None, None, time(0)).await;
+        let options = WatchOptions {
+            filter: PositionFilter {
+                asset_class: Some("cash".to_string()),
+                ..PositionFilter::default()
+            },
+            ..options()
+        };
+        assert_eq!(frame.portfolio(&options).positions.len(), 1);
+        assert_eq!(frame.portfolio(&options).get_total_value(), dec!(500));
+    }
+}